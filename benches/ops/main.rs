@@ -12,11 +12,12 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 mod read;
+mod request;
 mod utils;
 mod write;
 
 use criterion::criterion_group;
 use criterion::criterion_main;
 
-criterion_group!(benches, read::bench, write::bench);
+criterion_group!(benches, read::bench, request::bench, write::bench);
 criterion_main!(benches);
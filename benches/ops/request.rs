@@ -0,0 +1,90 @@
+// Copyright 2022 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+use std::convert::Infallible;
+use std::net::TcpListener;
+
+use criterion::Criterion;
+use futures::io;
+use hyper::service::make_service_fn;
+use hyper::service::service_fn;
+use opendal::credential::Credential;
+use opendal::services::s3;
+use opendal::Operator;
+
+use super::utils::*;
+
+/// Size of objects returned by the local server.
+const SIZE: usize = 4 * 1024;
+
+/// Bench the cost of building, signing and parsing requests.
+///
+/// Requests are served by a local server which responds immediately, so
+/// that the result is not dominated by the network.
+pub fn bench(c: &mut Criterion) {
+    let op = TOKIO.block_on(async {
+        let endpoint = serve();
+
+        let mut builder = s3::Backend::build();
+        builder
+            .root("/path/to/root")
+            .bucket("bench")
+            .endpoint(&endpoint)
+            .region("us-east-1")
+            .server_side_encryption_with_customer_key("AES256", &[0; 32])
+            .credential(Credential::hmac("access_key_id", "secret_access_key"));
+        Operator::new(builder.finish().await.expect("build s3 backend"))
+    });
+
+    let mut group = c.benchmark_group("request");
+
+    group.bench_function("stat", |b| {
+        b.to_async(&*TOKIO).iter(|| async {
+            op.object("dir/file").metadata().await.unwrap();
+        })
+    });
+    group.bench_function("read_small", |b| {
+        b.to_async(&*TOKIO).iter(|| async {
+            let r = op.object("dir/file").limited_reader(SIZE as u64);
+            io::copy(r, &mut io::sink()).await.unwrap();
+        })
+    });
+
+    group.finish()
+}
+
+/// Serve s3 requests locally, returns the endpoint.
+fn serve() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").expect("bind local server");
+    let addr = listener.local_addr().expect("get local addr");
+
+    let server = hyper::Server::from_tcp(listener)
+        .expect("build local server")
+        .serve(make_service_fn(|_| async {
+            Ok::<_, Infallible>(service_fn(|req: hyper::Request<hyper::Body>| async move {
+                let body = match *req.method() {
+                    hyper::Method::GET => hyper::Body::from(vec![0; SIZE]),
+                    _ => hyper::Body::empty(),
+                };
+                Ok::<_, Infallible>(
+                    hyper::Response::builder()
+                        .header(hyper::header::CONTENT_LENGTH, SIZE)
+                        .body(body)
+                        .expect("build response"),
+                )
+            }))
+        }));
+    tokio::spawn(server);
+
+    format!("http://{}", addr)
+}
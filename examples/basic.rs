@@ -26,10 +26,10 @@ async fn main() -> Result<()> {
 
     // Write data info file;
     let w = o.writer();
-    let n = w
+    let meta = w
         .write_bytes("Hello, World!".to_string().into_bytes())
         .await?;
-    assert_eq!(n, 13);
+    assert_eq!(meta.content_length(), 13);
 
     // Read data from file;
     let mut r = o.reader();
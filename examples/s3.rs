@@ -118,7 +118,7 @@ OPENDAL_S3_BUCKET=opendal OPENDAL_S3_ACCESS_KEY_ID=minioadmin OPENDAL_S3_SECRET_
     );
 
     info!("try to delete file: {}", &path);
-    let _ = op.object(&path).delete().await?;
+    op.object(&path).delete().await?;
     info!("delete file successful");
 
     Ok(())
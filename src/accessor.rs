@@ -12,21 +12,46 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::collections::HashMap;
 use std::fmt::Debug;
 use std::sync::Arc;
 
+use anyhow::anyhow;
 use async_trait::async_trait;
+use bytes::Bytes;
+use bytes::BytesMut;
+use futures::StreamExt;
+use futures::TryStreamExt;
 
+use crate::error::Error;
+use crate::error::Kind;
 use crate::error::Result;
 use crate::io::BytesStream;
+use crate::ops::BatchOperation;
+use crate::ops::BatchResult;
+use crate::ops::ObjectPart;
+use crate::ops::OpAbortMultipart;
+use crate::ops::OpAppend;
+use crate::ops::OpBatch;
+use crate::ops::OpCompleteMultipart;
+use crate::ops::OpCopy;
+use crate::ops::OpCreate;
+use crate::ops::OpCreateMultipart;
 use crate::ops::OpDelete;
 use crate::ops::OpList;
+use crate::ops::OpPresign;
 use crate::ops::OpRead;
+use crate::ops::OpRename;
+use crate::ops::OpScan;
 use crate::ops::OpStat;
 use crate::ops::OpWrite;
+use crate::ops::OpWriteMultipart;
+use crate::ops::PresignedRequest;
 use crate::BoxedAsyncReader;
 use crate::BoxedObjectStream;
 use crate::Metadata;
+use crate::ObjectMode;
+use crate::Scheme;
 
 /// Underlying trait of all backends for implementors.
 ///
@@ -36,15 +61,107 @@ use crate::Metadata;
 /// use [`Operator`][crate::Operator] instead.
 #[async_trait]
 pub trait Accessor: Send + Sync + Debug {
+    /// Read data from the underlying storage into a bytes stream.
+    ///
+    /// ## Behavior
+    ///
+    /// - `Read` with `size == Some(0)` returns an empty stream immediately
+    ///   without accessing the storage, even if the object doesn't exist.
+    /// - `Read` with `offset` equals to object's length returns an empty stream.
+    /// - `Read` with `size` but without `offset` returns the last `size` bytes,
+    ///   or the whole object if it's shorter.
     async fn read(&self, args: &OpRead) -> Result<BytesStream> {
         let _ = args;
         unimplemented!()
     }
+    /// Read data from the underlying storage into a single `Bytes`.
+    ///
+    /// Backends with [`AccessorMetadata::mmap_read`] return a `Bytes` backed
+    /// by a memory map without copying. Default to collecting the stream
+    /// returned by `read`.
+    async fn read_mmap(&self, args: &OpRead) -> Result<Bytes> {
+        let mut s = self.read(args).await?;
+        let mut buf = BytesMut::new();
+        while let Some(bs) = s.try_next().await? {
+            buf.extend_from_slice(&bs);
+        }
+        Ok(buf.freeze())
+    }
+    /// Read data like `read`, and return the metadata of the whole object
+    /// alongside the stream.
+    ///
+    /// The `content_length` of the metadata is the length of the whole
+    /// object even if only a range is read. Services whose read responses
+    /// carry metadata (like s3) override this to avoid a separate request.
+    /// Default to `stat` followed by `read`.
+    async fn read_with_metadata(&self, args: &OpRead) -> Result<(BytesStream, Metadata)> {
+        let mut op = OpStat::new(&args.path);
+        op.version = args.version.clone();
+        op.raw_headers = args.raw_headers;
+        let meta = self.stat(&op).await?;
+
+        Ok((self.read(args).await?, meta))
+    }
     /// Write data from input reader to the underlying storage.
-    async fn write(&self, r: BoxedAsyncReader, args: &OpWrite) -> Result<usize> {
+    ///
+    /// Returns the metadata of the written object, the `content_length` is
+    /// the number of bytes that have been written. `etag` and `version_id`
+    /// will be set if the service returns them.
+    async fn write(&self, r: BoxedAsyncReader, args: &OpWrite) -> Result<Metadata> {
         let (_, _) = (r, args);
         unimplemented!()
     }
+    /// Append data from input reader to the end of the object.
+    ///
+    /// Returns the metadata of the object, the `content_length` is the
+    /// length of the object after appending.
+    ///
+    /// ## Behavior
+    ///
+    /// - `Append` at `position` other than the object's length returns
+    ///   `Kind::ObjectPreconditionFailed`, and nothing is appended.
+    /// - `Append` at `0` creates the object if it doesn't exist, otherwise
+    ///   appending a nonexistent object returns `Kind::ObjectNotExist`.
+    ///
+    /// Default to `Kind::Unsupported`, read [`AccessorMetadata::append`]
+    /// to check the capability.
+    async fn append(&self, r: BoxedAsyncReader, args: &OpAppend) -> Result<Metadata> {
+        let _ = r;
+        Err(unsupported("append", &args.path))
+    }
+    /// Create an empty object at the specified path.
+    ///
+    /// ## Behavior
+    ///
+    /// - `Create` with `ObjectMode::FILE` creates a zero-byte object, the
+    ///   existing object will be overwritten. The path must not end with `/`.
+    /// - `Create` with `ObjectMode::DIR` creates a dir, `/` will be appended
+    ///   to the path if missing. On s3 alike services, it's a zero-byte
+    ///   marker object, so that the dir exists even without any children.
+    ///
+    /// Default to a zero-byte `write`.
+    async fn create(&self, args: &OpCreate) -> Result<Metadata> {
+        let path = match args.mode {
+            ObjectMode::FILE if !args.path.ends_with('/') => args.path.clone(),
+            ObjectMode::DIR if args.path.ends_with('/') => args.path.clone(),
+            ObjectMode::DIR => format!("{}/", args.path),
+            mode => {
+                return Err(Error::Object {
+                    kind: Kind::Unexpected,
+                    op: "create",
+                    path: args.path.clone(),
+                    context: HashMap::from([("mode".to_string(), mode.to_string())]),
+                    source: anyhow!("path doesn't match the mode of create"),
+                })
+            }
+        };
+
+        let mut op = OpWrite::new(&path, 0);
+        op.priority = args.priority;
+        let mut meta = self.write(Box::new(futures::io::empty()), &op).await?;
+        meta.set_mode(args.mode);
+        Ok(meta)
+    }
     /// Invoke the `stat` operation on the specified path.
     ///
     /// ## Behavior
@@ -52,7 +169,8 @@ pub trait Accessor: Send + Sync + Debug {
     /// - `Stat` empty path means stat backend's root path.
     /// - `Stat` a path endswith "/" means stating a dir.
     ///   - On fs, an error could return if not a dir.
-    ///   - On s3 alike backends, a dir object will return no matter it exist or not.
+    ///   - On s3 alike backends, a dir object will return if there is any
+    ///     object under it, otherwise `ObjectNotExist` will be returned.
     async fn stat(&self, args: &OpStat) -> Result<Metadata> {
         let _ = args;
         unimplemented!()
@@ -67,11 +185,212 @@ pub trait Accessor: Send + Sync + Debug {
         let _ = args;
         unimplemented!()
     }
+    /// Run a batch of sub-operations like deletes and stats, and return
+    /// the result of every sub-operation in the same order along with its
+    /// path.
+    ///
+    /// ## Behavior
+    ///
+    /// - A failed sub-operation doesn't fail the batch or others, its error
+    ///   is returned as its result.
+    /// - Services like s3 delete up to 1000 objects per request.
+    ///
+    /// Default to run every sub-operation by its own operation, at most
+    /// `concurrency` of them at the same time.
+    async fn batch(&self, args: &OpBatch) -> Result<Vec<(String, BatchResult)>> {
+        Ok(batch_each(self, args).await)
+    }
+    /// Copy the object at `src` to `dst` inside the storage, without
+    /// transferring its content through the client.
+    ///
+    /// Returns the metadata of the destination object, `etag` and
+    /// `last_modified` will be set if the service returns them.
+    ///
+    /// ## Behavior
+    ///
+    /// - `Copy` overwrites `dst` if it exists.
+    /// - `Copy` a nonexistent `src` returns `Kind::ObjectNotExist`.
+    /// - `Copy` onto itself is allowed, services like s3 will refresh the
+    ///   object's last modified time.
+    ///
+    /// Default to `Kind::Unsupported`.
+    async fn copy(&self, args: &OpCopy) -> Result<Metadata> {
+        Err(unsupported("copy", &args.src).with_context("dst", &args.dst))
+    }
+
+    /// Rename the object at `src` to `dst`.
+    ///
+    /// Returns the metadata of the renamed object.
+    ///
+    /// ## Behavior
+    ///
+    /// - `Rename` overwrites `dst` if it exists, the same as s3's copy.
+    /// - `Rename` a nonexistent `src` returns `Kind::ObjectNotExist`.
+    /// - `Rename` onto itself keeps the object.
+    ///
+    /// Default to [`copy`][Accessor::copy] followed by a `delete` of `src`,
+    /// so it's not atomic: if the delete fails, both objects exist and the
+    /// returned error will say so. Services with native rename like fs
+    /// should override it to finish in one call.
+    async fn rename(&self, args: &OpRename) -> Result<Metadata> {
+        let mut op = OpCopy::new(&args.src, &args.dst);
+        op.priority = args.priority;
+        let meta = self.copy(&op).await?;
+
+        if is_same_path(&args.src, &args.dst) {
+            return Ok(meta);
+        }
+
+        let mut op = OpDelete::new(&args.src);
+        op.priority = args.priority;
+        self.delete(&op).await.map_err(|err| Error::Object {
+            kind: err.kind(),
+            op: "rename",
+            path: args.src.clone(),
+            context: HashMap::from([("dst".to_string(), args.dst.clone())]),
+            source: anyhow!(
+                "object has been copied to {}, but failed to delete the source: {}",
+                args.dst,
+                err
+            ),
+        })?;
+
+        Ok(meta)
+    }
+
+    /// Generate a request signed in advance, so that clients like browsers
+    /// can read or write the object without credentials before it expires.
+    ///
+    /// No request will be sent to the storage.
+    ///
+    /// Default to `Kind::Unsupported`.
+    fn presign(&self, args: &OpPresign) -> Result<PresignedRequest> {
+        Err(unsupported("presign", &args.path))
+    }
+
+    /// Create a multipart upload at the specified path, returns the id
+    /// of the upload.
+    ///
+    /// Parts can be written by `write_multipart` concurrently, even from
+    /// different machines, and the object will be visible only after
+    /// `complete_multipart`. Uploads should be aborted by `abort_multipart`
+    /// if not completed, otherwise their parts will be kept and charged.
+    ///
+    /// Default to `Kind::Unsupported`.
+    async fn create_multipart(&self, args: &OpCreateMultipart) -> Result<String> {
+        Err(unsupported("create_multipart", &args.path))
+    }
+    /// Write a part of the multipart upload from input reader.
+    ///
+    /// Returns the written part, which is required by `complete_multipart`.
+    ///
+    /// Default to `Kind::Unsupported`.
+    async fn write_multipart(
+        &self,
+        r: BoxedAsyncReader,
+        args: &OpWriteMultipart,
+    ) -> Result<ObjectPart> {
+        let _ = r;
+        Err(unsupported("write_multipart", &args.path))
+    }
+    /// Complete the multipart upload with all its parts.
+    ///
+    /// Returns the metadata of the object, `content_length` is not set
+    /// since sizes of the parts are unknown.
+    ///
+    /// Default to `Kind::Unsupported`.
+    async fn complete_multipart(&self, args: &OpCompleteMultipart) -> Result<Metadata> {
+        Err(unsupported("complete_multipart", &args.path))
+    }
+    /// Abort the multipart upload and delete its written parts.
+    ///
+    /// Default to `Kind::Unsupported`.
+    async fn abort_multipart(&self, args: &OpAbortMultipart) -> Result<()> {
+        Err(unsupported("abort_multipart", &args.path))
+    }
 
     async fn list(&self, args: &OpList) -> Result<BoxedObjectStream> {
         let _ = args;
         unimplemented!()
     }
+    /// Scan all objects under the dir regardless of depth.
+    ///
+    /// Unlike `list`, which is dir-oriented, `scan` only yields `FILE`
+    /// entries: dirs and dir markers are skipped. Paths of entries are
+    /// still relative to the root of the backend.
+    ///
+    /// Default to a recursive `list` with `DIR` entries filtered out.
+    /// Services like s3 should override it to list without delimiter.
+    async fn scan(&self, args: &OpScan) -> Result<BoxedObjectStream> {
+        scan_by_list(self, args).await
+    }
+
+    /// Return the metadata of this accessor, including capabilities.
+    ///
+    /// Default to a metadata without any capabilities.
+    fn metadata(&self) -> AccessorMetadata {
+        AccessorMetadata::default()
+    }
+}
+
+/// Run every sub-operation of the batch by its own operation of `acc`,
+/// at most `concurrency` of them at the same time.
+///
+/// Layers checking every operation, like [`ImmutableLayer`][crate::layers::ImmutableLayer],
+/// batch by this, so that sub-operations are checked as well.
+pub(crate) async fn batch_each<A: Accessor + ?Sized>(
+    acc: &A,
+    args: &OpBatch,
+) -> Vec<(String, BatchResult)> {
+    futures::stream::iter(args.ops.iter().cloned())
+        .map(|op| async move {
+            let res = match &op {
+                BatchOperation::Delete(v) => BatchResult::Delete(acc.delete(v).await),
+                BatchOperation::Stat(v) => BatchResult::Stat(acc.stat(v).await.map(Box::new)),
+            };
+            (op.path().to_string(), res)
+        })
+        .buffered(args.concurrency.max(1))
+        .collect()
+        .await
+}
+
+/// Scan by a recursive `list` of `acc` with `DIR` entries filtered out.
+pub(crate) async fn scan_by_list<A: Accessor + ?Sized>(
+    acc: &A,
+    args: &OpScan,
+) -> Result<BoxedObjectStream> {
+    let mut op = OpList::new(&args.path);
+    op.recursive = true;
+    op.priority = args.priority;
+    let s = acc.list(&op).await?;
+
+    Ok(Box::new(s.try_filter(|o| {
+        futures::future::ready(o.metadata_ref().mode() == ObjectMode::FILE)
+    })))
+}
+
+fn unsupported(op: &'static str, path: &str) -> Error {
+    Error::Object {
+        kind: Kind::Unsupported,
+        op,
+        path: path.to_string(),
+        context: HashMap::new(),
+        source: anyhow!("{} is not supported", op),
+    }
+}
+
+/// Check whether two paths point to the same object, ignoring the
+/// leading `/` and internal `//`.
+fn is_same_path(a: &str, b: &str) -> bool {
+    let segments = |p: &str| {
+        p.split('/')
+            .filter(|v| !v.is_empty())
+            .map(|v| v.to_string())
+            .collect::<Vec<_>>()
+    };
+
+    a.ends_with('/') == b.ends_with('/') && segments(a) == segments(b)
 }
 
 /// All functions in `Accessor` only requires `&self`, so it's safe to implement
@@ -81,16 +400,215 @@ impl<T: Accessor> Accessor for Arc<T> {
     async fn read(&self, args: &OpRead) -> Result<BytesStream> {
         self.as_ref().read(args).await
     }
-    async fn write(&self, r: BoxedAsyncReader, args: &OpWrite) -> Result<usize> {
+    async fn read_mmap(&self, args: &OpRead) -> Result<Bytes> {
+        self.as_ref().read_mmap(args).await
+    }
+    async fn read_with_metadata(&self, args: &OpRead) -> Result<(BytesStream, Metadata)> {
+        self.as_ref().read_with_metadata(args).await
+    }
+    async fn write(&self, r: BoxedAsyncReader, args: &OpWrite) -> Result<Metadata> {
         self.as_ref().write(r, args).await
     }
+    async fn append(&self, r: BoxedAsyncReader, args: &OpAppend) -> Result<Metadata> {
+        self.as_ref().append(r, args).await
+    }
+    async fn create(&self, args: &OpCreate) -> Result<Metadata> {
+        self.as_ref().create(args).await
+    }
     async fn stat(&self, args: &OpStat) -> Result<Metadata> {
         self.as_ref().stat(args).await
     }
     async fn delete(&self, args: &OpDelete) -> Result<()> {
         self.as_ref().delete(args).await
     }
+    async fn batch(&self, args: &OpBatch) -> Result<Vec<(String, BatchResult)>> {
+        self.as_ref().batch(args).await
+    }
+    async fn copy(&self, args: &OpCopy) -> Result<Metadata> {
+        self.as_ref().copy(args).await
+    }
+    async fn rename(&self, args: &OpRename) -> Result<Metadata> {
+        self.as_ref().rename(args).await
+    }
+    fn presign(&self, args: &OpPresign) -> Result<PresignedRequest> {
+        self.as_ref().presign(args)
+    }
+    async fn create_multipart(&self, args: &OpCreateMultipart) -> Result<String> {
+        self.as_ref().create_multipart(args).await
+    }
+    async fn write_multipart(
+        &self,
+        r: BoxedAsyncReader,
+        args: &OpWriteMultipart,
+    ) -> Result<ObjectPart> {
+        self.as_ref().write_multipart(r, args).await
+    }
+    async fn complete_multipart(&self, args: &OpCompleteMultipart) -> Result<Metadata> {
+        self.as_ref().complete_multipart(args).await
+    }
+    async fn abort_multipart(&self, args: &OpAbortMultipart) -> Result<()> {
+        self.as_ref().abort_multipart(args).await
+    }
     async fn list(&self, args: &OpList) -> Result<BoxedObjectStream> {
         self.as_ref().list(args).await
     }
+    async fn scan(&self, args: &OpScan) -> Result<BoxedObjectStream> {
+        self.as_ref().scan(args).await
+    }
+    fn metadata(&self) -> AccessorMetadata {
+        self.as_ref().metadata()
+    }
+}
+
+/// Metadata for accessor, users can use this metadata to get information of
+/// the underlying backend.
+///
+/// Libraries built on OpenDAL can check the capabilities to degrade
+/// gracefully, for example, fall back to streamed copy without native
+/// [`copy`][AccessorMetadata::copy], instead of probing with requests.
+#[derive(Debug, Clone, Default)]
+pub struct AccessorMetadata {
+    scheme: Option<Scheme>,
+    root: String,
+
+    list: bool,
+    copy: bool,
+    append: bool,
+    presign: bool,
+    multipart: bool,
+    ordered_list: bool,
+    conditional_write: bool,
+    mmap_read: bool,
+    recursive_list: bool,
+}
+
+impl AccessorMetadata {
+    /// Scheme of the underlying backend, `None` for accessors that don't
+    /// report it.
+    pub fn scheme(&self) -> Option<Scheme> {
+        self.scheme.clone()
+    }
+
+    pub fn set_scheme(&mut self, scheme: Scheme) -> &mut Self {
+        self.scheme = Some(scheme);
+        self
+    }
+
+    /// Root of the backend, like `/abc/`, that all paths are relative to.
+    pub fn root(&self) -> &str {
+        &self.root
+    }
+
+    pub fn set_root(&mut self, root: &str) -> &mut Self {
+        self.root = root.to_string();
+        self
+    }
+
+    /// Whether `list` is supported.
+    pub fn list(&self) -> bool {
+        self.list
+    }
+
+    pub fn set_list(&mut self, list: bool) -> &mut Self {
+        self.list = list;
+        self
+    }
+
+    /// Whether `copy` is done inside the storage without transferring the
+    /// content through the client.
+    ///
+    /// `rename` falls back to `copy` and `delete` without native rename.
+    pub fn copy(&self) -> bool {
+        self.copy
+    }
+
+    pub fn set_copy(&mut self, copy: bool) -> &mut Self {
+        self.copy = copy;
+        self
+    }
+
+    /// Whether `append` is supported.
+    ///
+    /// Backends without support will return `Kind::Unsupported`.
+    pub fn append(&self) -> bool {
+        self.append
+    }
+
+    pub fn set_append(&mut self, append: bool) -> &mut Self {
+        self.append = append;
+        self
+    }
+
+    /// Whether `presign` is supported.
+    pub fn presign(&self) -> bool {
+        self.presign
+    }
+
+    pub fn set_presign(&mut self, presign: bool) -> &mut Self {
+        self.presign = presign;
+        self
+    }
+
+    /// Whether multipart uploads like `create_multipart` are supported.
+    pub fn multipart(&self) -> bool {
+        self.multipart
+    }
+
+    pub fn set_multipart(&mut self, multipart: bool) -> &mut Self {
+        self.multipart = multipart;
+        self
+    }
+
+    /// Whether `list` yields entries in lexicographical order of their paths
+    /// without extra cost.
+    ///
+    /// Backends without natural ordering will sort entries in memory while
+    /// `OpList::ordered` is set.
+    pub fn ordered_list(&self) -> bool {
+        self.ordered_list
+    }
+
+    pub fn set_ordered_list(&mut self, ordered: bool) -> &mut Self {
+        self.ordered_list = ordered;
+        self
+    }
+
+    /// Whether `write` and `delete` support ETag preconditions like
+    /// `OpWrite::if_match` and `OpWrite::if_none_match`.
+    ///
+    /// Backends without support will return `Kind::Unsupported` for
+    /// conditional writes instead of ignoring the preconditions.
+    pub fn conditional_write(&self) -> bool {
+        self.conditional_write
+    }
+
+    pub fn set_conditional_write(&mut self, conditional: bool) -> &mut Self {
+        self.conditional_write = conditional;
+        self
+    }
+
+    /// Whether `read_mmap` returns memory mapped data without copying.
+    ///
+    /// Backends without support will fall back to a buffered read.
+    pub fn mmap_read(&self) -> bool {
+        self.mmap_read
+    }
+
+    pub fn set_mmap_read(&mut self, mmap: bool) -> &mut Self {
+        self.mmap_read = mmap;
+        self
+    }
+
+    /// Whether `list` supports `OpList::recursive`.
+    ///
+    /// Backends without support will return `Kind::Unsupported` for
+    /// recursive lists.
+    pub fn recursive_list(&self) -> bool {
+        self.recursive_list
+    }
+
+    pub fn set_recursive_list(&mut self, recursive: bool) -> &mut Self {
+        self.recursive_list = recursive;
+        self
+    }
 }
@@ -0,0 +1,138 @@
+// Copyright 2022 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::collections::BinaryHeap;
+
+use serde::Serialize;
+
+/// How many largest keys will be tracked by [`PrefixAnalysis`].
+pub(crate) const ANALYZE_LARGEST_KEYS: usize = 10;
+
+/// Options for [`Operator::analyze_prefix`](crate::Operator::analyze_prefix).
+#[derive(Default, Debug, Clone)]
+pub struct AnalyzeOptions {
+    pub(crate) max_depth: Option<usize>,
+    pub(crate) scan_limit: Option<u64>,
+}
+
+impl AnalyzeOptions {
+    /// Only analyze keys at most `depth` levels under the prefix.
+    ///
+    /// Keys directly under the prefix are at depth 1.
+    pub fn max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = Some(depth);
+        self
+    }
+
+    /// Fail with `Kind::ScanLimitExceeded` once more than `limit` entries
+    /// have been listed, including skipped and dir entries.
+    pub fn scan_limit(mut self, limit: u64) -> Self {
+        self.scan_limit = Some(limit);
+        self
+    }
+}
+
+/// Count and total bytes of a group of keys.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct KeyStats {
+    /// Count of keys.
+    pub count: u64,
+    /// Total bytes of keys.
+    pub bytes: u64,
+}
+
+/// A key and its size.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct KeySize {
+    /// Size of the key in bytes.
+    pub size: u64,
+    /// Path of the key.
+    pub path: String,
+}
+
+/// Aggregated statistics of keys under a prefix, returned by
+/// [`Operator::analyze_prefix`](crate::Operator::analyze_prefix).
+#[derive(Default, Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PrefixAnalysis {
+    /// Statistics of all analyzed keys.
+    pub total: KeyStats,
+    /// Statistics per lowercased extension without the leading dot.
+    ///
+    /// Keys without extension are grouped under the empty string.
+    pub extensions: BTreeMap<String, KeyStats>,
+    /// Count of keys per depth under the prefix.
+    pub depths: BTreeMap<usize, u64>,
+    /// The largest keys, ordered by size descending.
+    pub largest: Vec<KeySize>,
+}
+
+/// Streaming aggregation of [`PrefixAnalysis`].
+///
+/// Memory usage is bounded by the count of extensions and depths, the
+/// largest keys are tracked in a bounded min-heap.
+#[derive(Default)]
+pub(crate) struct Analyzer {
+    analysis: PrefixAnalysis,
+    /// Min-heap of `(size, Reverse(path))`, the smaller path wins on ties.
+    largest: BinaryHeap<Reverse<(u64, Reverse<String>)>>,
+}
+
+impl Analyzer {
+    pub(crate) fn add(&mut self, path: &str, depth: usize, size: u64) {
+        let a = &mut self.analysis;
+        a.total.count += 1;
+        a.total.bytes += size;
+
+        let ext = a.extensions.entry(extension(path)).or_default();
+        ext.count += 1;
+        ext.bytes += size;
+
+        *a.depths.entry(depth).or_default() += 1;
+
+        if self.largest.len() < ANALYZE_LARGEST_KEYS {
+            self.largest
+                .push(Reverse((size, Reverse(path.to_string()))));
+        } else if let Some(mut min) = self.largest.peek_mut() {
+            let (min_size, Reverse(min_path)) = &min.0;
+            if (size, Reverse(path)) > (*min_size, Reverse(min_path.as_str())) {
+                *min = Reverse((size, Reverse(path.to_string())));
+            }
+        }
+    }
+
+    pub(crate) fn finish(self) -> PrefixAnalysis {
+        let mut analysis = self.analysis;
+        analysis.largest = self
+            .largest
+            .into_sorted_vec()
+            .into_iter()
+            .map(|Reverse((size, Reverse(path)))| KeySize { size, path })
+            .collect();
+        analysis
+    }
+}
+
+/// Extract the lowercased extension of the path's file name.
+///
+/// Only the part after the last dot is used, so `a.tar.gz` is `gz`.
+/// Hidden files like `.env` and names ending with dot have no extension.
+pub(crate) fn extension(path: &str) -> String {
+    let name = path.rsplit('/').next().unwrap_or_default();
+    match name.rfind('.') {
+        Some(idx) if idx > 0 => name[idx + 1..].to_lowercase(),
+        _ => String::new(),
+    }
+}
@@ -0,0 +1,297 @@
+// Copyright 2022 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::io;
+use std::sync::Arc;
+
+use anyhow::anyhow;
+use futures::AsyncReadExt;
+use futures::StreamExt;
+use tokio::runtime::Handle;
+use tokio::runtime::Runtime;
+
+use crate::error::Error;
+use crate::error::Kind;
+use crate::error::Result;
+use crate::Metadata;
+use crate::Object;
+use crate::ObjectStream;
+use crate::Operator;
+use crate::Reader;
+
+/// Blocking APIs of [`Operator`] for non-async callers, like threads of
+/// rayon.
+///
+/// Futures are driven by a tokio runtime, either a dedicated one created
+/// by [`BlockingOperator::new`], or an existing one provided by
+/// [`BlockingOperator::with_handle`].
+///
+/// # Note
+///
+/// Blocking APIs must not be called inside a tokio runtime, including the
+/// one driving them, which could deadlock the runtime. They return an
+/// error of [`Kind::Unexpected`] instead, use the async APIs there.
+///
+/// # Example
+///
+/// ```
+/// use std::io::Read;
+///
+/// use anyhow::Result;
+/// use opendal::services::memory;
+/// use opendal::BlockingOperator;
+/// use opendal::Operator;
+///
+/// fn main() -> Result<()> {
+///     let rt = tokio::runtime::Runtime::new()?;
+///     let acc = rt.block_on(memory::Backend::build().finish())?;
+///     let op = BlockingOperator::with_handle(Operator::new(acc), rt.handle().clone());
+///
+///     op.write("test", "Hello, World!".as_bytes().to_vec())?;
+///
+///     let mut content = String::new();
+///     op.reader("test").read_to_string(&mut content)?;
+///     assert_eq!(content, "Hello, World!");
+///
+///     for o in op.list("")? {
+///         let meta = o?.metadata_ref().clone();
+///         assert_eq!(meta.path(), "test");
+///     }
+///
+///     Ok(())
+/// }
+/// ```
+#[derive(Clone)]
+pub struct BlockingOperator {
+    op: Operator,
+    handle: Handle,
+    // Keep the dedicated runtime alive as long as the operator.
+    _runtime: Option<Arc<DedicatedRuntime>>,
+}
+
+impl BlockingOperator {
+    /// Create a blocking operator with a dedicated multi-thread runtime.
+    pub fn new(op: Operator) -> Result<Self> {
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .thread_name("opendal-blocking")
+            .enable_all()
+            .build()
+            .map_err(|e| Error::Unexpected {
+                context: HashMap::new(),
+                source: anyhow!("build blocking runtime: {}", e),
+            })?;
+
+        Ok(Self {
+            op,
+            handle: runtime.handle().clone(),
+            _runtime: Some(Arc::new(DedicatedRuntime(Some(runtime)))),
+        })
+    }
+
+    /// Create a blocking operator on an existing runtime.
+    ///
+    /// The runtime must be a multi-thread one, or be driven by other
+    /// threads, so that IO could make progress while blocking.
+    pub fn with_handle(op: Operator, handle: Handle) -> Self {
+        Self {
+            op,
+            handle,
+            _runtime: None,
+        }
+    }
+
+    /// Get the async operator inside.
+    pub fn operator(&self) -> &Operator {
+        &self.op
+    }
+
+    /// Read the whole object into memory.
+    pub fn read(&self, path: &str) -> Result<Vec<u8>> {
+        let o = self.op.object(path);
+        let fut = async move {
+            let mut s = o.stream(None, None).await?;
+            let mut buf = Vec::new();
+            while let Some(bs) = s.next().await {
+                buf.extend_from_slice(&bs?);
+            }
+            Ok::<_, Error>(buf)
+        };
+
+        self.block_on("read", path, fut)?
+    }
+
+    /// Create a reader which implements [`std::io::Read`].
+    pub fn reader(&self, path: &str) -> BlockingReader {
+        BlockingReader {
+            handle: self.handle.clone(),
+            inner: self.op.object(path).reader(),
+        }
+    }
+
+    /// Write bytes into the object.
+    pub fn write(&self, path: &str, bs: Vec<u8>) -> Result<Metadata> {
+        let w = self.op.object(path).writer();
+        self.block_on("write", path, w.write_bytes(bs))?
+    }
+
+    /// Get the metadata of the object.
+    pub fn metadata(&self, path: &str) -> Result<Metadata> {
+        let o = self.op.object(path);
+        self.block_on("stat", path, async move { o.metadata().await })?
+    }
+
+    /// Delete the object.
+    pub fn delete(&self, path: &str) -> Result<()> {
+        let o = self.op.object(path);
+        self.block_on("delete", path, async move { o.delete().await })?
+    }
+
+    /// List objects under the dir, pages are fetched on demand while
+    /// iterating.
+    pub fn list(&self, path: &str) -> Result<BlockingObjectIterator> {
+        check_blocking("list", path)?;
+
+        Ok(BlockingObjectIterator {
+            handle: self.handle.clone(),
+            path: path.to_string(),
+            inner: self.op.objects(path),
+        })
+    }
+
+    fn block_on<F: Future>(&self, op: &'static str, path: &str, fut: F) -> Result<F::Output> {
+        check_blocking(op, path)?;
+        Ok(self.handle.block_on(fut))
+    }
+}
+
+/// Refuse to block inside a tokio runtime.
+fn check_blocking(op: &'static str, path: &str) -> Result<()> {
+    if Handle::try_current().is_ok() {
+        return Err(Error::Object {
+            kind: Kind::Unexpected,
+            op,
+            path: path.to_string(),
+            context: HashMap::new(),
+            source: anyhow!("blocking api called inside a tokio runtime, use async api instead"),
+        });
+    }
+    Ok(())
+}
+
+/// DedicatedRuntime shuts the runtime down in background while dropping,
+/// so that dropping the operator inside another runtime doesn't panic.
+struct DedicatedRuntime(Option<Runtime>);
+
+impl Drop for DedicatedRuntime {
+    fn drop(&mut self) {
+        if let Some(runtime) = self.0.take() {
+            runtime.shutdown_background();
+        }
+    }
+}
+
+/// BlockingReader reads the object by [`std::io::Read`], created by
+/// [`BlockingOperator::reader`].
+pub struct BlockingReader {
+    handle: Handle,
+    inner: Reader,
+}
+
+impl io::Read for BlockingReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if Handle::try_current().is_ok() {
+            return Err(io::Error::other(
+                "blocking api called inside a tokio runtime, use async api instead",
+            ));
+        }
+        self.handle.block_on(self.inner.read(buf))
+    }
+}
+
+/// BlockingObjectIterator yields objects of a dir, created by
+/// [`BlockingOperator::list`].
+pub struct BlockingObjectIterator {
+    handle: Handle,
+    path: String,
+    inner: ObjectStream,
+}
+
+impl Iterator for BlockingObjectIterator {
+    type Item = Result<Object>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Err(e) = check_blocking("list", &self.path) {
+            return Some(Err(e));
+        }
+        self.handle.block_on(self.inner.next())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use super::*;
+    use crate::services::memory;
+
+    #[test]
+    fn test_blocking_operator() -> Result<()> {
+        let op = BlockingOperator::new(Operator::new(
+            tokio::runtime::Runtime::new()
+                .unwrap()
+                .block_on(memory::Backend::build().finish())?,
+        ))?;
+
+        op.write("dir/a", "Hello, World!".as_bytes().to_vec())?;
+        op.write("dir/b", vec![])?;
+        assert_eq!(op.read("dir/a")?, "Hello, World!".as_bytes());
+        assert_eq!(op.metadata("dir/a")?.content_length(), 13);
+
+        let mut r = op.reader("dir/a");
+        let mut buf = [0; 5];
+        r.read_exact(&mut buf).map_err(anyhow::Error::from)?;
+        assert_eq!(&buf, b"Hello");
+
+        let mut paths = op
+            .list("dir/")?
+            .map(|o| o.map(|o| o.metadata_ref().path().to_string()))
+            .collect::<Result<Vec<_>>>()?;
+        paths.sort();
+        assert_eq!(paths, vec!["dir/a", "dir/b"]);
+
+        op.delete("dir/a")?;
+        let err = op.metadata("dir/a").unwrap_err();
+        assert_eq!(err.kind(), Kind::ObjectNotExist);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_blocking_inside_runtime() -> Result<()> {
+        let op = BlockingOperator::new(Operator::new(memory::Backend::build().finish().await?))?;
+
+        let err = op.read("a").unwrap_err();
+        assert_eq!(err.kind(), Kind::Unexpected);
+        assert!(err.to_string().contains("inside a tokio runtime"));
+        assert!(op.list("").is_err());
+        assert!(op.reader("a").read(&mut [0; 1]).is_err());
+
+        // Dropping the dedicated runtime inside a runtime must not panic.
+        drop(op);
+
+        Ok(())
+    }
+}
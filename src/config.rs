@@ -0,0 +1,96 @@
+// Copyright 2022 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+
+use anyhow::anyhow;
+
+use crate::error::Error;
+use crate::error::Kind;
+use crate::error::Result;
+use crate::Scheme;
+
+/// Options of a backend in key-value form, consumed by `from_map` of builders.
+///
+/// Errors carry the option name in context `key`, so that callers like
+/// [`Operator::from_env`][crate::Operator::from_env] could map it back to
+/// their own names. Values are never carried since they could be secrets.
+pub(crate) struct ConfigMap {
+    scheme: Scheme,
+    map: HashMap<String, String>,
+}
+
+impl ConfigMap {
+    pub fn new(scheme: Scheme, map: HashMap<String, String>) -> Self {
+        Self { scheme, map }
+    }
+
+    /// Take the value of an option, empty values are treated as unset.
+    pub fn take(&mut self, key: &str) -> Option<String> {
+        self.map.remove(key).filter(|v| !v.is_empty())
+    }
+
+    /// Take the value of a required option.
+    pub fn take_required(&mut self, key: &str) -> Result<String> {
+        self.take(key)
+            .ok_or_else(|| self.error(key, anyhow!("{} is required", key)))
+    }
+
+    /// Take an option of `true` or `false`.
+    pub fn take_bool(&mut self, key: &str) -> Result<Option<bool>> {
+        match self.take(key).as_deref() {
+            None => Ok(None),
+            Some("true") => Ok(Some(true)),
+            Some("false") => Ok(Some(false)),
+            Some(_) => Err(self.error(key, anyhow!("{} must be `true` or `false`", key))),
+        }
+    }
+
+    /// Take a pair of options which must be set together, like credentials.
+    pub fn take_pair(&mut self, first: &str, second: &str) -> Result<Option<(String, String)>> {
+        match (self.take(first), self.take(second)) {
+            (Some(a), Some(b)) => Ok(Some((a, b))),
+            (None, None) => Ok(None),
+            (None, Some(_)) => Err(self.error(
+                first,
+                anyhow!("{} and {} must be set together", first, second),
+            )),
+            (Some(_), None) => Err(self.error(
+                second,
+                anyhow!("{} and {} must be set together", first, second),
+            )),
+        }
+    }
+
+    /// Check that all options have been taken by the builder.
+    pub fn finish(self) -> Result<()> {
+        let mut keys: Vec<_> = self.map.keys().collect();
+        keys.sort();
+        match keys.first() {
+            None => Ok(()),
+            Some(key) => Err(self.error(key, anyhow!("{} is not supported", key))),
+        }
+    }
+
+    fn error(&self, key: &str, source: anyhow::Error) -> Error {
+        Error::Backend {
+            kind: Kind::BackendConfigurationInvalid,
+            context: HashMap::from([
+                ("scheme".to_string(), format!("{:?}", self.scheme)),
+                ("key".to_string(), key.to_string()),
+            ]),
+            source,
+        }
+    }
+}
@@ -38,9 +38,11 @@
 //! }
 //! ```
 
+use std::collections::BTreeMap;
 use std::collections::HashMap;
 use std::io;
 
+use anyhow::anyhow;
 use thiserror::Error;
 
 // TODO: implement From<Result> for `common_exception::Result`.s
@@ -65,6 +67,64 @@ pub enum Kind {
     ObjectNotExist,
     #[error("object permission denied")]
     ObjectPermissionDenied,
+    /// The uploaded content doesn't match its checksum, retry is safe.
+    #[error("object checksum mismatch")]
+    ObjectChecksumMismatch,
+    /// The object's ETag matches `if_none_match` of the read.
+    #[error("object not modified")]
+    ObjectNotModified,
+    /// The object's ETag doesn't match `if_match` of the read.
+    #[error("object precondition failed")]
+    ObjectPreconditionFailed,
+    /// The lock object is held by another owner and has not expired.
+    #[error("object locked")]
+    ObjectLocked,
+    /// Reading the object's content was interrupted by the backend, for
+    /// example, connection reset or throttled in the middle of the transfer.
+    ///
+    /// Retry is safe, the `offset` in context is the position of the object
+    /// reached so far.
+    #[error("object read interrupted")]
+    ObjectReadInterrupted,
+    /// The object's content doesn't follow its format, for example, a
+    /// framed object that has been truncated or modified.
+    #[error("object corrupted")]
+    ObjectCorrupted,
+    /// The object has been changed or deleted since it was pinned, for
+    /// example, by a manifest of [`ManifestLayer`][crate::layers::manifest::ManifestLayer],
+    /// or by the first response of a resumed read.
+    #[error("object changed")]
+    ObjectChanged,
+    /// The object is larger than the size limit of the read, for example,
+    /// `max_size` of [`ReadManyOptions`][crate::ReadManyOptions].
+    #[error("object too large")]
+    ObjectTooLarge,
+    /// The object is archived, like `GLACIER` and `DEEP_ARCHIVE` on s3, and
+    /// needs to be restored before reading.
+    #[error("object not available")]
+    ObjectNotAvailable,
+
+    /// The object path escapes the scope of the operator.
+    #[error("object out of scope")]
+    ObjectOutOfScope,
+    /// The object path is invalid, like containing `..` segments or NUL
+    /// bytes, no request has been sent.
+    #[error("object path invalid")]
+    ObjectPathInvalid,
+
+    /// Listing has been stopped by the backend's `list_scan_limit`.
+    #[error("scan limit exceeded")]
+    ScanLimitExceeded,
+    /// The operation is not supported by the backend under current options.
+    #[error("unsupported")]
+    Unsupported,
+    /// The operation didn't finish in time.
+    #[error("timeout")]
+    Timeout,
+    /// The service failed temporarily, like `503 Slow Down` or
+    /// `500 Internal Error` on s3, retry with backoff is safe.
+    #[error("service unavailable")]
+    ServiceUnavailable,
 
     #[error("unexpected")]
     Unexpected,
@@ -75,25 +135,34 @@ pub enum Kind {
 /// ## Style
 ///
 /// The error will be formatted as `description: (keyA: valueA, keyB: valueB, ...)`.
+///
+/// ## Context
+///
+/// Applications can attach their own context via [`Error::with_context`]
+/// without losing the [`Kind`]. Context will be displayed in key order.
 #[derive(Error, Debug)]
 pub enum Error {
-    #[error("{kind}: (context: {context:?}, source: {source})")]
+    #[error("{kind}: (context: {}, source: {source})", format_context(.context))]
     Backend {
         kind: Kind,
         context: HashMap<String, String>,
         source: anyhow::Error,
     },
 
-    #[error("{kind}: (op: {op}, path: {path}, source: {source})")]
+    #[error("{kind}: (op: {op}, path: {path}{}, source: {source})", format_extra_context(.context))]
     Object {
         kind: Kind,
         op: &'static str,
         path: String,
+        context: HashMap<String, String>,
         source: anyhow::Error,
     },
 
-    #[error("unexpected: (source: {0})")]
-    Unexpected(#[from] anyhow::Error),
+    #[error("unexpected: ({}source: {source})", format_leading_context(.context))]
+    Unexpected {
+        context: HashMap<String, String>,
+        source: anyhow::Error,
+    },
 }
 
 impl Error {
@@ -101,24 +170,132 @@ impl Error {
         match self {
             Error::Backend { kind, .. } => *kind,
             Error::Object { kind, .. } => *kind,
-            Error::Unexpected(_) => Kind::Unexpected,
+            Error::Unexpected { .. } => Kind::Unexpected,
+        }
+    }
+
+    /// Whether the failed operation could succeed by retrying as is, like
+    /// errors of `Kind::ServiceUnavailable` and `Kind::Timeout`.
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self.kind(),
+            Kind::ServiceUnavailable
+                | Kind::Timeout
+                | Kind::ObjectReadInterrupted
+                | Kind::ObjectChecksumMismatch
+        )
+    }
+
+    /// Attach a key-value pair to the context of this error.
+    ///
+    /// Value of an existing key will be overwritten.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use anyhow::anyhow;
+    /// use opendal::error::Error;
+    /// use opendal::error::Kind;
+    ///
+    /// let err = Error::from(anyhow!("oops")).with_context("table", "t1");
+    /// assert_eq!(err.kind(), Kind::Unexpected);
+    /// assert_eq!(err.context()["table"], "t1");
+    /// ```
+    #[must_use]
+    pub fn with_context(mut self, key: &str, value: impl ToString) -> Error {
+        let context = match &mut self {
+            Error::Backend { context, .. } => context,
+            Error::Object { context, .. } => context,
+            Error::Unexpected { context, .. } => context,
+        };
+        context.insert(key.to_string(), value.to_string());
+        self
+    }
+
+    /// Context of this error.
+    pub fn context(&self) -> &HashMap<String, String> {
+        match self {
+            Error::Backend { context, .. } => context,
+            Error::Object { context, .. } => context,
+            Error::Unexpected { context, .. } => context,
         }
     }
 }
 
+impl From<anyhow::Error> for Error {
+    fn from(err: anyhow::Error) -> Self {
+        Error::Unexpected {
+            context: HashMap::new(),
+            source: err,
+        }
+    }
+}
+
+/// Duplicate the error for other readers of the same result, like the
+/// same fetch of [`read_many`][crate::Operator::read_many], the source is
+/// kept as its message.
+pub(crate) fn share_error(err: &Error) -> Error {
+    match err {
+        Error::Backend { kind, context, .. } => Error::Backend {
+            kind: *kind,
+            context: context.clone(),
+            source: anyhow!("{}", err),
+        },
+        Error::Object {
+            kind,
+            op,
+            path,
+            context,
+            ..
+        } => Error::Object {
+            kind: *kind,
+            op,
+            path: path.clone(),
+            context: context.clone(),
+            source: anyhow!("{}", err),
+        },
+        Error::Unexpected { context, .. } => Error::Unexpected {
+            context: context.clone(),
+            source: anyhow!("{}", err),
+        },
+    }
+}
+
+/// Format context like `{"keyA": "valueA", "keyB": "valueB"}` in key order.
+fn format_context(context: &HashMap<String, String>) -> String {
+    format!("{:?}", context.iter().collect::<BTreeMap<_, _>>())
+}
+
+fn format_extra_context(context: &HashMap<String, String>) -> String {
+    if context.is_empty() {
+        return String::new();
+    }
+    format!(", context: {}", format_context(context))
+}
+
+fn format_leading_context(context: &HashMap<String, String>) -> String {
+    if context.is_empty() {
+        return String::new();
+    }
+    format!("context: {}, ", format_context(context))
+}
+
 // Make it easier to convert to `std::io::Error`
 impl From<Error> for io::Error {
     fn from(err: Error) -> Self {
         match err {
-            Error::Backend { .. } => io::Error::new(io::ErrorKind::Other, err),
+            Error::Backend { .. } => io::Error::other(err),
             Error::Object { kind, .. } => match kind {
                 Kind::ObjectNotExist => io::Error::new(io::ErrorKind::NotFound, err),
                 Kind::ObjectPermissionDenied => {
                     io::Error::new(io::ErrorKind::PermissionDenied, err)
                 }
-                _ => io::Error::new(io::ErrorKind::Other, err),
+                Kind::ObjectChecksumMismatch | Kind::ObjectCorrupted => {
+                    io::Error::new(io::ErrorKind::InvalidData, err)
+                }
+                _ => io::Error::other(err),
             },
-            Error::Unexpected(_) => io::Error::new(io::ErrorKind::Other, err),
+            Error::Unexpected { .. } => io::Error::other(err),
         }
     }
 }
@@ -0,0 +1,952 @@
+// Copyright 2022 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A versioned chunked envelope for layers that transform the content of
+//! objects chunk by chunk, like client-side encryption and compression.
+//!
+//! The plaintext is split into chunks of `chunk_size` bytes, every chunk is
+//! transformed by a [`ChunkCodec`] independently, so that a range of the
+//! plaintext can be served by reading and decoding only the chunks covering
+//! it, see [`Header::chunk_range`] and [`Header::stored_range`].
+//!
+//! # Format
+//!
+//! All integers are unsigned and big-endian. An object is:
+//!
+//! ```text
+//! object  = header *chunk end trailer
+//!
+//! header  = magic      4 bytes, "OFRM"
+//!           version    u8, 1
+//!           flags      u8, bit 0: CHECKSUM, bit 1: DIGEST, others must be 0
+//!           nonce_len  u8, 0 to 32
+//!           reserved   u8, 0
+//!           chunk_size u32, at least 1
+//!
+//! chunk   = stored_len u32, at least 1
+//!           plain_len  u32, 1 to chunk_size
+//!           nonce      nonce_len bytes
+//!           checksum   u32, CRC-32C of payload, only if CHECKSUM is set
+//!           payload    stored_len bytes
+//!
+//! end     = stored_len u32, 0
+//!           plain_len  u32, 0
+//!
+//! trailer = plain_total u64, sum of plain_len of all chunks
+//!           chunk_count u64, count of chunks
+//!           digest      16 bytes, MD5 of the plaintext, only if DIGEST is set
+//!           magic       4 bytes, "OFRE"
+//! ```
+//!
+//! The `i`th chunk (starting from 0) holds the plaintext from
+//! `i * chunk_size`, all chunks except the last one must be full
+//! (`plain_len == chunk_size`). Zero-length plaintext has no chunks.
+//! `payload` is the output of the codec for the chunk's plaintext, the codec
+//! decides the `nonce` and the meaning of the payload.
+//!
+//! Readers must reject:
+//!
+//! - Unknown `magic`, `version`, `flags` bits or non-zero `reserved` with
+//!   `Kind::Unsupported` for `version` and `flags`, `Kind::ObjectCorrupted`
+//!   for others.
+//! - Chunks violating the rules above, checksum or digest mismatches and
+//!   trailers that don't match the chunks with `Kind::ObjectCorrupted`.
+//! - Objects ending before the trailer's magic or having data after it with
+//!   `Kind::ObjectCorrupted`.
+
+use std::collections::HashMap;
+use std::collections::VecDeque;
+use std::fmt::Debug;
+use std::io;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::Context;
+use std::task::Poll;
+
+use anyhow::anyhow;
+use bytes::Buf;
+use bytes::BufMut;
+use bytes::Bytes;
+use bytes::BytesMut;
+use futures::ready;
+use futures::AsyncRead;
+use futures::Stream;
+use futures::StreamExt;
+use pin_project::pin_project;
+
+use crate::error::Error;
+use crate::error::Kind;
+use crate::error::Result;
+use crate::io::BytesStream;
+
+/// Magic at the start of framed objects.
+pub const MAGIC: &[u8; 4] = b"OFRM";
+/// Magic at the end of framed objects.
+pub const END_MAGIC: &[u8; 4] = b"OFRE";
+/// The latest version of the format.
+pub const VERSION: u8 = 1;
+/// Length of the header.
+pub const HEADER_LEN: usize = 12;
+/// Max length of the nonce of every chunk.
+pub const MAX_NONCE_LEN: u8 = 32;
+
+const FLAG_CHECKSUM: u8 = 1;
+const FLAG_DIGEST: u8 = 1 << 1;
+/// Length of `stored_len` and `plain_len` of chunks and the end marker.
+const CHUNK_LENS_LEN: usize = 8;
+
+/// ChunkCodec transforms the plaintext of every chunk into the payload
+/// stored in the object, and back.
+pub trait ChunkCodec: Send + Sync + Debug {
+    /// Length of the nonce stored with every chunk, `0` means no nonce.
+    fn nonce_len(&self) -> u8 {
+        0
+    }
+
+    /// Encode the plaintext of the `index`th chunk into its payload.
+    ///
+    /// `nonce` has `nonce_len` bytes to be filled by the codec. The payload
+    /// must not be empty.
+    fn encode(&self, index: u64, plain: &[u8], nonce: &mut [u8]) -> Result<Vec<u8>>;
+
+    /// Decode the payload of the `index`th chunk back into `plain_len`
+    /// bytes of plaintext.
+    fn decode(&self, index: u64, payload: &[u8], nonce: &[u8], plain_len: usize)
+        -> Result<Vec<u8>>;
+}
+
+/// Identity stores the plaintext as the payload, useful for adding
+/// checksums to objects.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Identity;
+
+impl ChunkCodec for Identity {
+    fn encode(&self, _: u64, plain: &[u8], _: &mut [u8]) -> Result<Vec<u8>> {
+        Ok(plain.to_vec())
+    }
+
+    fn decode(&self, _: u64, payload: &[u8], _: &[u8], _: usize) -> Result<Vec<u8>> {
+        Ok(payload.to_vec())
+    }
+}
+
+/// Header of framed objects.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Header {
+    chunk_size: u32,
+    nonce_len: u8,
+    checksum: bool,
+    digest: bool,
+}
+
+impl Header {
+    /// Create a new header of the latest version without checksum, digest
+    /// and nonce.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `chunk_size` is 0.
+    pub fn new(chunk_size: u32) -> Self {
+        assert!(chunk_size > 0, "chunk size must be at least 1");
+
+        Self {
+            chunk_size,
+            nonce_len: 0,
+            checksum: false,
+            digest: false,
+        }
+    }
+
+    /// Store the CRC-32C of every chunk's payload.
+    pub fn checksum(mut self, enabled: bool) -> Self {
+        self.checksum = enabled;
+        self
+    }
+
+    /// Store the MD5 of the whole plaintext in the trailer.
+    ///
+    /// The digest is stored in clear, don't enable it if the plaintext
+    /// must stay secret.
+    pub fn digest(mut self, enabled: bool) -> Self {
+        self.digest = enabled;
+        self
+    }
+
+    /// Set the length of the nonce of every chunk, must equal to
+    /// [`ChunkCodec::nonce_len`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `len` is larger than [`MAX_NONCE_LEN`].
+    pub fn nonce_len(mut self, len: u8) -> Self {
+        assert!(len <= MAX_NONCE_LEN, "nonce length must be at most 32");
+        self.nonce_len = len;
+        self
+    }
+
+    pub fn chunk_size(&self) -> u32 {
+        self.chunk_size
+    }
+
+    pub fn has_checksum(&self) -> bool {
+        self.checksum
+    }
+
+    pub fn has_digest(&self) -> bool {
+        self.digest
+    }
+
+    pub fn get_nonce_len(&self) -> u8 {
+        self.nonce_len
+    }
+
+    /// Serialize the header.
+    pub fn to_bytes(&self) -> [u8; HEADER_LEN] {
+        let mut flags = 0;
+        if self.checksum {
+            flags |= FLAG_CHECKSUM;
+        }
+        if self.digest {
+            flags |= FLAG_DIGEST;
+        }
+
+        let mut bs = [0; HEADER_LEN];
+        bs[..4].copy_from_slice(MAGIC);
+        bs[4] = VERSION;
+        bs[5] = flags;
+        bs[6] = self.nonce_len;
+        bs[8..].copy_from_slice(&self.chunk_size.to_be_bytes());
+        bs
+    }
+
+    /// Parse the header from the first [`HEADER_LEN`] bytes of `bs`.
+    ///
+    /// `path` is only used in errors.
+    pub fn parse(path: &str, bs: &[u8]) -> Result<Self> {
+        if bs.len() < HEADER_LEN {
+            return Err(corrupted(path, anyhow!("header is truncated")));
+        }
+        if &bs[..4] != MAGIC {
+            return Err(corrupted(path, anyhow!("invalid magic: {:?}", &bs[..4])));
+        }
+        if bs[4] != VERSION {
+            return Err(unsupported(path, anyhow!("unknown version: {}", bs[4])));
+        }
+        let flags = bs[5];
+        if flags & !(FLAG_CHECKSUM | FLAG_DIGEST) != 0 {
+            return Err(unsupported(path, anyhow!("unknown flags: {:#010b}", flags)));
+        }
+        let nonce_len = bs[6];
+        if nonce_len > MAX_NONCE_LEN {
+            return Err(corrupted(
+                path,
+                anyhow!("invalid nonce length: {}", nonce_len),
+            ));
+        }
+        if bs[7] != 0 {
+            return Err(corrupted(path, anyhow!("reserved byte is not zero")));
+        }
+        let chunk_size = u32::from_be_bytes(bs[8..12].try_into().expect("must be 4 bytes"));
+        if chunk_size == 0 {
+            return Err(corrupted(path, anyhow!("chunk size is zero")));
+        }
+
+        Ok(Self {
+            chunk_size,
+            nonce_len,
+            checksum: flags & FLAG_CHECKSUM != 0,
+            digest: flags & FLAG_DIGEST != 0,
+        })
+    }
+
+    /// Length of every chunk's fields before the payload.
+    pub fn chunk_header_len(&self) -> usize {
+        CHUNK_LENS_LEN + self.nonce_len as usize + if self.checksum { 4 } else { 0 }
+    }
+
+    /// Length of the end marker and the trailer.
+    pub fn trailer_len(&self) -> usize {
+        CHUNK_LENS_LEN + 16 + if self.digest { 16 } else { 0 } + END_MAGIC.len()
+    }
+
+    /// Length of the framed object of `plain_total` bytes plaintext, for
+    /// codecs whose payload is always `overhead` bytes longer than the
+    /// plaintext.
+    pub fn encoded_len(&self, plain_total: u64, overhead: u64) -> u64 {
+        let (full, rem) = (
+            plain_total / self.chunk_size as u64,
+            plain_total % self.chunk_size as u64,
+        );
+        let mut len =
+            HEADER_LEN as u64 + full * self.stored_chunk_len(self.chunk_size as u64, overhead);
+        if rem > 0 {
+            len += self.stored_chunk_len(rem, overhead);
+        }
+        len + self.trailer_len() as u64
+    }
+
+    /// Map the plaintext range starting from `offset` with `size` bytes
+    /// (to the end if `None`) to the chunks covering it.
+    ///
+    /// The range will be truncated by `plain_total`.
+    pub fn chunk_range(&self, plain_total: u64, offset: u64, size: Option<u64>) -> ChunkRange {
+        let chunk_size = self.chunk_size as u64;
+        let start = offset.min(plain_total);
+        let end = match size {
+            Some(size) => start.saturating_add(size).min(plain_total),
+            None => plain_total,
+        };
+        if start == end {
+            return ChunkRange {
+                first: start / chunk_size,
+                count: 0,
+                skip: 0,
+                len: 0,
+            };
+        }
+
+        let first = start / chunk_size;
+        let last = (end - 1) / chunk_size;
+        ChunkRange {
+            first,
+            count: last - first + 1,
+            skip: start - first * chunk_size,
+            len: end - start,
+        }
+    }
+
+    /// Map the chunks to the range of the framed object as `(offset, size)`,
+    /// for codecs whose payload is always `overhead` bytes longer than the
+    /// plaintext.
+    ///
+    /// Codecs with variable payload length (like compression) need to walk
+    /// through the chunks from the start instead.
+    pub fn stored_range(&self, plain_total: u64, range: &ChunkRange, overhead: u64) -> (u64, u64) {
+        let chunk_size = self.chunk_size as u64;
+        let full = self.stored_chunk_len(chunk_size, overhead);
+        let offset = HEADER_LEN as u64 + range.first * full;
+        if range.count == 0 {
+            return (offset, 0);
+        }
+
+        let last = range.first + range.count - 1;
+        let last_plain = (plain_total - last * chunk_size).min(chunk_size);
+        (
+            offset,
+            (range.count - 1) * full + self.stored_chunk_len(last_plain, overhead),
+        )
+    }
+
+    fn stored_chunk_len(&self, plain_len: u64, overhead: u64) -> u64 {
+        self.chunk_header_len() as u64 + plain_len + overhead
+    }
+}
+
+/// Chunks covering a plaintext range, returned by [`Header::chunk_range`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChunkRange {
+    /// Index of the first chunk.
+    pub first: u64,
+    /// Count of chunks, `0` means the range is empty.
+    pub count: u64,
+    /// Bytes to skip in the plaintext of the first chunk.
+    pub skip: u64,
+    /// Bytes of the range.
+    pub len: u64,
+}
+
+/// Trailer of framed objects.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Trailer {
+    /// Length of the plaintext.
+    pub plain_total: u64,
+    /// Count of chunks.
+    pub chunk_count: u64,
+    /// MD5 of the plaintext, only if the header has digest enabled.
+    pub digest: Option<[u8; 16]>,
+}
+
+impl Trailer {
+    /// Parse the trailer from the last [`Header::trailer_len`] bytes of
+    /// a framed object, starting with the end marker.
+    ///
+    /// `path` is only used in errors.
+    pub fn parse(path: &str, header: &Header, bs: &[u8]) -> Result<Self> {
+        if bs.len() != header.trailer_len() {
+            return Err(corrupted(path, anyhow!("trailer is truncated")));
+        }
+        if bs[..CHUNK_LENS_LEN] != [0; CHUNK_LENS_LEN] {
+            return Err(corrupted(path, anyhow!("invalid end of chunks")));
+        }
+        if &bs[bs.len() - END_MAGIC.len()..] != END_MAGIC {
+            return Err(corrupted(path, anyhow!("invalid end magic")));
+        }
+
+        let mut bs = &bs[CHUNK_LENS_LEN..];
+        let plain_total = bs.get_u64();
+        let chunk_count = bs.get_u64();
+        let digest = if header.digest {
+            Some(bs[..16].try_into().expect("must be 16 bytes"))
+        } else {
+            None
+        };
+        Ok(Self {
+            plain_total,
+            chunk_count,
+            digest,
+        })
+    }
+}
+
+/// Encoder splits the plaintext into chunks and frames them.
+pub struct Encoder {
+    codec: Arc<dyn ChunkCodec>,
+    header: Header,
+    started: bool,
+    finished: bool,
+    pending: BytesMut,
+    index: u64,
+    plain_total: u64,
+    digest: Option<md5::Context>,
+}
+
+impl Encoder {
+    /// Create a new encoder.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the nonce length of `header` and `codec` don't match.
+    pub fn new(codec: Arc<dyn ChunkCodec>, header: Header) -> Self {
+        assert_eq!(
+            codec.nonce_len(),
+            header.nonce_len,
+            "nonce length of header and codec must match"
+        );
+
+        Self {
+            digest: header.digest.then(md5::Context::new),
+            codec,
+            header,
+            started: false,
+            finished: false,
+            pending: BytesMut::new(),
+            index: 0,
+            plain_total: 0,
+        }
+    }
+
+    /// Push plaintext into the encoder, framed bytes of full chunks will
+    /// be appended to `out`.
+    pub fn push(&mut self, plain: &[u8], out: &mut BytesMut) -> Result<()> {
+        debug_assert!(!self.finished, "push after finish");
+        self.start(out);
+
+        self.pending.extend_from_slice(plain);
+        let chunk_size = self.header.chunk_size as usize;
+        while self.pending.len() >= chunk_size {
+            let chunk = self.pending.split_to(chunk_size);
+            self.write_chunk(&chunk, out)?;
+        }
+        Ok(())
+    }
+
+    /// Flush the last chunk and append the trailer to `out`.
+    pub fn finish(&mut self, out: &mut BytesMut) -> Result<()> {
+        if self.finished {
+            return Ok(());
+        }
+        self.start(out);
+
+        if !self.pending.is_empty() {
+            let chunk = self.pending.split();
+            self.write_chunk(&chunk, out)?;
+        }
+
+        out.put_bytes(0, CHUNK_LENS_LEN);
+        out.put_u64(self.plain_total);
+        out.put_u64(self.index);
+        if let Some(digest) = self.digest.take() {
+            out.put_slice(&digest.compute().0);
+        }
+        out.put_slice(END_MAGIC);
+
+        self.finished = true;
+        Ok(())
+    }
+
+    fn start(&mut self, out: &mut BytesMut) {
+        if !self.started {
+            out.put_slice(&self.header.to_bytes());
+            self.started = true;
+        }
+    }
+
+    fn write_chunk(&mut self, plain: &[u8], out: &mut BytesMut) -> Result<()> {
+        let mut nonce = vec![0; self.header.nonce_len as usize];
+        let payload = self.codec.encode(self.index, plain, &mut nonce)?;
+        if payload.is_empty() || payload.len() > u32::MAX as usize {
+            return Err(Error::Unexpected {
+                context: HashMap::from([("chunk".to_string(), self.index.to_string())]),
+                source: anyhow!("codec returned payload of invalid length {}", payload.len()),
+            });
+        }
+
+        out.put_u32(payload.len() as u32);
+        out.put_u32(plain.len() as u32);
+        out.put_slice(&nonce);
+        if self.header.checksum {
+            out.put_u32(crc32c(&payload));
+        }
+        out.put_slice(&payload);
+
+        if let Some(digest) = &mut self.digest {
+            digest.consume(plain);
+        }
+        self.index += 1;
+        self.plain_total += plain.len() as u64;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DecodeState {
+    Header,
+    Chunk,
+    Trailer,
+    Done,
+}
+
+/// Decoder parses framed bytes back into plaintext, validating the format
+/// along the way.
+pub struct Decoder {
+    path: String,
+    codec: Arc<dyn ChunkCodec>,
+    header: Option<Header>,
+    state: DecodeState,
+    buf: BytesMut,
+    /// Decoding started from the middle of the object, the trailer may be
+    /// absent and the digest can't be verified.
+    ranged: bool,
+    index: u64,
+    plain_total: u64,
+    last_seen: bool,
+    digest: Option<md5::Context>,
+}
+
+impl Decoder {
+    /// Create a new decoder for the whole framed object.
+    ///
+    /// `path` is only used in errors.
+    pub fn new(path: &str, codec: Arc<dyn ChunkCodec>) -> Self {
+        Self {
+            path: path.to_string(),
+            codec,
+            header: None,
+            state: DecodeState::Header,
+            buf: BytesMut::new(),
+            ranged: false,
+            index: 0,
+            plain_total: 0,
+            last_seen: false,
+            digest: None,
+        }
+    }
+
+    /// Create a new decoder for framed bytes starting from the `first`
+    /// chunk, like the range returned by [`Header::stored_range`].
+    ///
+    /// The input could end at any chunk boundary.
+    pub fn resume(path: &str, codec: Arc<dyn ChunkCodec>, header: Header, first: u64) -> Self {
+        Self {
+            path: path.to_string(),
+            codec,
+            header: Some(header),
+            state: DecodeState::Chunk,
+            buf: BytesMut::new(),
+            ranged: true,
+            index: first,
+            plain_total: first * header.chunk_size as u64,
+            last_seen: false,
+            digest: None,
+        }
+    }
+
+    /// The header of the object, available after it has been decoded.
+    pub fn header(&self) -> Option<Header> {
+        self.header
+    }
+
+    /// Feed framed bytes into the decoder, returns the plaintext of all
+    /// chunks completed by them.
+    pub fn feed(&mut self, bs: &[u8]) -> Result<Vec<Bytes>> {
+        self.buf.extend_from_slice(bs);
+
+        let mut out = Vec::new();
+        loop {
+            match self.state {
+                DecodeState::Header => {
+                    if self.buf.len() < HEADER_LEN {
+                        return Ok(out);
+                    }
+                    let header = Header::parse(&self.path, &self.buf)?;
+                    if header.nonce_len != self.codec.nonce_len() {
+                        return Err(corrupted(
+                            &self.path,
+                            anyhow!("nonce length {} doesn't match codec", header.nonce_len),
+                        ));
+                    }
+                    self.buf.advance(HEADER_LEN);
+                    self.digest = header.digest.then(md5::Context::new);
+                    self.header = Some(header);
+                    self.state = DecodeState::Chunk;
+                }
+                DecodeState::Chunk => match self.decode_chunk()? {
+                    Some(bs) => out.push(bs),
+                    None => {
+                        if self.state == DecodeState::Chunk {
+                            return Ok(out);
+                        }
+                    }
+                },
+                DecodeState::Trailer => {
+                    let header = self.header.expect("header must be decoded");
+                    let len = header.trailer_len() - CHUNK_LENS_LEN;
+                    if self.buf.len() < len {
+                        return Ok(out);
+                    }
+                    let mut bs = vec![0; CHUNK_LENS_LEN];
+                    bs.extend_from_slice(&self.buf[..len]);
+                    self.buf.advance(len);
+                    self.check_trailer(&Trailer::parse(&self.path, &header, &bs)?)?;
+                    self.state = DecodeState::Done;
+                }
+                DecodeState::Done => {
+                    if !self.buf.is_empty() {
+                        return Err(corrupted(&self.path, anyhow!("data after trailer")));
+                    }
+                    return Ok(out);
+                }
+            }
+        }
+    }
+
+    /// Check that the input ended at a valid position.
+    pub fn finish(&self) -> Result<()> {
+        match self.state {
+            DecodeState::Done => Ok(()),
+            DecodeState::Chunk if self.ranged && self.buf.is_empty() => Ok(()),
+            state => Err(corrupted(
+                &self.path,
+                anyhow!(
+                    "object is truncated while decoding {:?} of chunk {}",
+                    state,
+                    self.index
+                ),
+            )),
+        }
+    }
+
+    /// Decode the next chunk, returns `None` if more input is required or
+    /// the end of chunks has been reached.
+    fn decode_chunk(&mut self) -> Result<Option<Bytes>> {
+        let header = self.header.expect("header must be decoded");
+        if self.buf.len() < CHUNK_LENS_LEN {
+            return Ok(None);
+        }
+
+        let stored_len = u32::from_be_bytes(self.buf[..4].try_into().expect("must be 4 bytes"));
+        let plain_len = u32::from_be_bytes(self.buf[4..8].try_into().expect("must be 4 bytes"));
+        if stored_len == 0 && plain_len == 0 {
+            self.buf.advance(CHUNK_LENS_LEN);
+            self.state = DecodeState::Trailer;
+            return Ok(None);
+        }
+
+        if stored_len == 0 || plain_len == 0 || plain_len > header.chunk_size {
+            return Err(corrupted(
+                &self.path,
+                anyhow!(
+                    "chunk {} has invalid length: stored {}, plain {}",
+                    self.index,
+                    stored_len,
+                    plain_len
+                ),
+            ));
+        }
+        if self.last_seen {
+            return Err(corrupted(
+                &self.path,
+                anyhow!("chunk {} follows a partial chunk", self.index),
+            ));
+        }
+
+        let header_len = header.chunk_header_len();
+        if self.buf.len() < header_len + stored_len as usize {
+            return Ok(None);
+        }
+
+        let chunk = self.buf.split_to(header_len + stored_len as usize);
+        let nonce = &chunk[CHUNK_LENS_LEN..CHUNK_LENS_LEN + header.nonce_len as usize];
+        let payload = &chunk[header_len..];
+        if header.checksum {
+            let pos = CHUNK_LENS_LEN + header.nonce_len as usize;
+            let expected =
+                u32::from_be_bytes(chunk[pos..pos + 4].try_into().expect("must be 4 bytes"));
+            let actual = crc32c(payload);
+            if expected != actual {
+                return Err(corrupted(
+                    &self.path,
+                    anyhow!(
+                        "chunk {} checksum mismatch: expected {:#010x}, actual {:#010x}",
+                        self.index,
+                        expected,
+                        actual
+                    ),
+                ));
+            }
+        }
+
+        let plain = self
+            .codec
+            .decode(self.index, payload, nonce, plain_len as usize)?;
+        if plain.len() != plain_len as usize {
+            return Err(corrupted(
+                &self.path,
+                anyhow!(
+                    "chunk {} decoded into {} bytes, expected {}",
+                    self.index,
+                    plain.len(),
+                    plain_len
+                ),
+            ));
+        }
+
+        if let Some(digest) = &mut self.digest {
+            digest.consume(&plain);
+        }
+        self.last_seen = plain_len < header.chunk_size;
+        self.index += 1;
+        self.plain_total += plain_len as u64;
+        Ok(Some(Bytes::from(plain)))
+    }
+
+    fn check_trailer(&mut self, trailer: &Trailer) -> Result<()> {
+        if trailer.plain_total != self.plain_total || trailer.chunk_count != self.index {
+            return Err(corrupted(
+                &self.path,
+                anyhow!(
+                    "trailer doesn't match chunks: expected {} bytes in {} chunks, actual {} bytes in {} chunks",
+                    trailer.plain_total,
+                    trailer.chunk_count,
+                    self.plain_total,
+                    self.index
+                ),
+            ));
+        }
+
+        if let (Some(expected), Some(digest)) = (trailer.digest, self.digest.take()) {
+            let actual = digest.compute().0;
+            if expected != actual {
+                return Err(corrupted(&self.path, anyhow!("plaintext digest mismatch")));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// EncodeReader frames the plaintext read from the inner reader.
+#[pin_project]
+pub struct EncodeReader<R> {
+    #[pin]
+    inner: R,
+    encoder: Encoder,
+    buf: Vec<u8>,
+    out: BytesMut,
+    eof: bool,
+}
+
+impl<R: AsyncRead> EncodeReader<R> {
+    pub fn new(inner: R, encoder: Encoder) -> Self {
+        Self {
+            inner,
+            encoder,
+            buf: vec![0; 64 * 1024],
+            out: BytesMut::new(),
+            eof: false,
+        }
+    }
+}
+
+impl<R: AsyncRead> AsyncRead for EncodeReader<R> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        dst: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let mut this = self.project();
+
+        loop {
+            if !this.out.is_empty() {
+                let n = dst.len().min(this.out.len());
+                dst[..n].copy_from_slice(&this.out[..n]);
+                this.out.advance(n);
+                return Poll::Ready(Ok(n));
+            }
+            if *this.eof {
+                return Poll::Ready(Ok(0));
+            }
+
+            let n = ready!(this.inner.as_mut().poll_read(cx, this.buf))?;
+            if n == 0 {
+                this.encoder.finish(this.out)?;
+                *this.eof = true;
+            } else {
+                this.encoder.push(&this.buf[..n], this.out)?;
+            }
+        }
+    }
+}
+
+/// DecodeStream decodes the framed bytes from the inner stream into
+/// plaintext.
+pub struct DecodeStream {
+    inner: BytesStream,
+    decoder: Decoder,
+    ready: VecDeque<Bytes>,
+    skip: u64,
+    remaining: Option<u64>,
+    done: bool,
+}
+
+impl DecodeStream {
+    pub fn new(inner: BytesStream, decoder: Decoder) -> Self {
+        Self {
+            inner,
+            decoder,
+            ready: VecDeque::new(),
+            skip: 0,
+            remaining: None,
+            done: false,
+        }
+    }
+
+    /// Only yield `len` bytes after skipping `skip` bytes of the plaintext,
+    /// like the [`ChunkRange`] to read.
+    pub fn window(mut self, skip: u64, len: u64) -> Self {
+        self.skip = skip;
+        self.remaining = Some(len);
+        self
+    }
+}
+
+impl Stream for DecodeStream {
+    type Item = Result<Bytes>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            if self.remaining == Some(0) {
+                self.done = true;
+                return Poll::Ready(None);
+            }
+
+            if let Some(mut bs) = self.ready.pop_front() {
+                if self.skip >= bs.len() as u64 {
+                    self.skip -= bs.len() as u64;
+                    continue;
+                }
+                bs.advance(self.skip as usize);
+                self.skip = 0;
+                if let Some(remaining) = &mut self.remaining {
+                    bs.truncate((*remaining).min(bs.len() as u64) as usize);
+                    *remaining -= bs.len() as u64;
+                }
+                return Poll::Ready(Some(Ok(bs)));
+            }
+
+            if self.done {
+                return Poll::Ready(None);
+            }
+
+            let this = &mut *self;
+            match ready!(this.inner.poll_next_unpin(cx)) {
+                Some(Ok(bs)) => match this.decoder.feed(&bs) {
+                    Ok(v) => this.ready.extend(v),
+                    Err(e) => {
+                        this.done = true;
+                        return Poll::Ready(Some(Err(e)));
+                    }
+                },
+                Some(Err(e)) => {
+                    this.done = true;
+                    return Poll::Ready(Some(Err(e)));
+                }
+                None => {
+                    this.done = true;
+                    if let Err(e) = this.decoder.finish() {
+                        return Poll::Ready(Some(Err(e)));
+                    }
+                }
+            }
+        }
+    }
+}
+
+const CRC32C_TABLE: [u32; 256] = {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut j = 0;
+        while j < 8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0x82f6_3b78
+            } else {
+                crc >> 1
+            };
+            j += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+/// CRC-32C (Castagnoli) of `bs`.
+pub(crate) fn crc32c(bs: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for b in bs {
+        crc = CRC32C_TABLE[((crc ^ *b as u32) & 0xff) as usize] ^ (crc >> 8);
+    }
+    !crc
+}
+
+fn corrupted(path: &str, source: anyhow::Error) -> Error {
+    Error::Object {
+        kind: Kind::ObjectCorrupted,
+        op: "read",
+        path: path.to_string(),
+        context: HashMap::new(),
+        source,
+    }
+}
+
+fn unsupported(path: &str, source: anyhow::Error) -> Error {
+    Error::Object {
+        kind: Kind::Unsupported,
+        op: "read",
+        path: path.to_string(),
+        context: HashMap::new(),
+        source,
+    }
+}
@@ -0,0 +1,95 @@
+// Copyright 2022 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::time::Instant;
+
+use log::warn;
+
+use super::notify_request;
+use super::record::Exchange;
+use super::Recorder;
+use super::ReplayClient;
+
+/// HttpClient is the http client shared by http based services.
+///
+/// Requests will be recorded if a [`Recorder`] has been set, and will be
+/// served by the [`ReplayClient`] without touching the network if set.
+#[derive(Debug, Clone)]
+pub(crate) struct HttpClient {
+    client: hyper::Client<hyper_tls::HttpsConnector<hyper::client::HttpConnector>, hyper::Body>,
+    recorder: Option<Recorder>,
+    replay: Option<ReplayClient>,
+}
+
+impl HttpClient {
+    pub fn new() -> Self {
+        Self {
+            client: hyper::Client::builder().build(hyper_tls::HttpsConnector::new()),
+            recorder: None,
+            replay: None,
+        }
+    }
+
+    pub fn set_recorder(&mut self, recorder: Recorder) -> &mut Self {
+        self.recorder = Some(recorder);
+        self
+    }
+
+    pub fn set_replay(&mut self, replay: ReplayClient) -> &mut Self {
+        self.replay = Some(replay);
+        self
+    }
+
+    pub async fn request(
+        &self,
+        req: hyper::Request<hyper::Body>,
+    ) -> anyhow::Result<hyper::Response<hyper::Body>> {
+        notify_request(&req);
+
+        if let Some(replay) = &self.replay {
+            return replay.serve(req.method(), req.uri());
+        }
+
+        let recorder = match &self.recorder {
+            None => return Ok(self.client.request(req).await?),
+            Some(recorder) => recorder,
+        };
+
+        // Bodies have to be buffered so that we can record them.
+        let (parts, body) = req.into_parts();
+        let req_body = hyper::body::to_bytes(body).await?;
+        let mut exchange = Exchange::new(&parts.method, &parts.uri, &parts.headers);
+
+        let start = Instant::now();
+        let resp = self
+            .client
+            .request(hyper::Request::from_parts(
+                parts,
+                hyper::Body::from(req_body.clone()),
+            ))
+            .await?;
+        let (parts, body) = resp.into_parts();
+        let resp_body = hyper::body::to_bytes(body).await?;
+        exchange.set_response(parts.status, &parts.headers, start.elapsed());
+
+        if let Err(e) = recorder.record(exchange, &req_body, &resp_body).await {
+            warn!("record http exchange: {:?}", e);
+        }
+
+        Ok(hyper::Response::from_parts(
+            parts,
+            hyper::Body::from(resp_body),
+        ))
+    }
+}
@@ -0,0 +1,66 @@
+// Copyright 2022 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::future::Future;
+
+use http::header::HeaderName;
+use http::header::USER_AGENT;
+use http::HeaderValue;
+
+/// Header of the id of the operation that sends the request, see
+/// [`RequestIdLayer`][crate::layers::RequestIdLayer].
+pub const X_OPENDAL_REQUEST_ID: &str = "x-opendal-request-id";
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+/// Run `fut` with `id` as the request id of all requests sent by it.
+pub(crate) async fn with_request_id<F: Future>(id: String, fut: F) -> F::Output {
+    REQUEST_ID.scope(id, fut).await
+}
+
+/// Run `f` with `id` as the request id, used while polling streams.
+pub(crate) fn sync_with_request_id<R>(id: String, f: impl FnOnce() -> R) -> R {
+    REQUEST_ID.sync_scope(id, f)
+}
+
+/// Build the `User-Agent` like `opendal/0.3.0`, with `suffix` appended
+/// after a space if given.
+///
+/// Returns `None` if `suffix` is not a valid header value.
+pub(crate) fn user_agent(suffix: Option<&str>) -> Option<HeaderValue> {
+    let ua = match suffix {
+        Some(suffix) => format!("opendal/{} {}", env!("CARGO_PKG_VERSION"), suffix),
+        None => format!("opendal/{}", env!("CARGO_PKG_VERSION")),
+    };
+    HeaderValue::from_str(&ua).ok()
+}
+
+/// Insert `User-Agent` and the request id, if any, into the request.
+///
+/// Must be called before signing, so that the headers are covered by the
+/// signature.
+pub(crate) fn insert_client_info(req: &mut hyper::Request<hyper::Body>, user_agent: &HeaderValue) {
+    let headers = req.headers_mut();
+    headers.insert(USER_AGENT, user_agent.clone());
+
+    let id = REQUEST_ID
+        .try_with(|id| HeaderValue::from_str(id))
+        .ok()
+        .and_then(|v| v.ok());
+    if let Some(id) = id {
+        headers.insert(HeaderName::from_static(X_OPENDAL_REQUEST_ID), id);
+    }
+}
@@ -0,0 +1,138 @@
+// Copyright 2022 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use time::format_description::well_known::Iso8601;
+use time::format_description::well_known::Rfc2822;
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+use time::UtcOffset;
+
+/// Parse timestamps returned by services, the result is always in UTC.
+///
+/// Services use different formats for the same timestamp, for example, s3
+/// returns `Sat, 30 Apr 2016 23:51:29 GMT` in `Last-Modified` header but
+/// `2016-04-30T23:51:29.000Z` in list XML. Supported formats are:
+///
+/// - RFC 2822 (and the RFC 7231 http-date): `Sat, 30 Apr 2016 23:51:29 GMT`
+/// - RFC 3339: `2016-04-30T23:51:29.000Z`, `2016-05-01T07:51:29+08:00`
+/// - ISO 8601: `20160430T235129Z`
+pub(crate) fn parse_datetime(s: &str) -> Result<OffsetDateTime, time::error::Parse> {
+    let s = s.trim();
+
+    let t = OffsetDateTime::parse(s, &Rfc2822)
+        .or_else(|_| OffsetDateTime::parse(s, &Rfc3339))
+        .or_else(|_| OffsetDateTime::parse(s, &Iso8601::DEFAULT))?;
+
+    Ok(t.to_offset(UtcOffset::UTC))
+}
+
+/// Format the timestamp as http-date like `Sat, 30 Apr 2016 23:51:29 GMT`,
+/// which is the RFC 2822 format in UTC.
+///
+/// Sub-second precision is truncated instead of rounded, so that
+/// `If-Modified-Since` never reports objects modified within the same second
+/// before `t` as modified.
+pub(crate) fn format_http_date(t: OffsetDateTime) -> Result<String, time::error::Format> {
+    let t = t
+        .to_offset(UtcOffset::UTC)
+        .replace_nanosecond(0)
+        .expect("zero nanosecond must be valid");
+
+    Ok(t.format(&Rfc2822)?.replace("+0000", "GMT"))
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::Rng;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_datetime() {
+        let expected = OffsetDateTime::from_unix_timestamp(1462060289).unwrap();
+
+        for s in [
+            "Sat, 30 Apr 2016 23:51:29 GMT",
+            "Sat, 30 Apr 2016 23:51:29 +0000",
+            "Sun, 01 May 2016 07:51:29 +0800",
+            " Sat, 30 Apr 2016 23:51:29 GMT ",
+            "2016-04-30T23:51:29Z",
+            "2016-04-30T23:51:29.000Z",
+            "2016-05-01T07:51:29+08:00",
+            "20160430T235129Z",
+        ] {
+            let t = parse_datetime(s).unwrap_or_else(|e| panic!("parse {:?}: {:?}", s, e));
+            assert_eq!(t, expected, "{:?}", s);
+            assert_eq!(t.offset(), UtcOffset::UTC, "{:?}", s);
+        }
+
+        assert!(parse_datetime("").is_err());
+        assert!(parse_datetime("yesterday").is_err());
+    }
+
+    #[test]
+    fn test_format_http_date() {
+        let t = OffsetDateTime::from_unix_timestamp(1462060289).unwrap();
+        assert_eq!(
+            format_http_date(t).unwrap(),
+            "Sat, 30 Apr 2016 23:51:29 GMT"
+        );
+
+        // Sub-second precision is truncated, even close to the next second.
+        for nanos in [1, 500_000_000, 999_999_999] {
+            let v = t + time::Duration::nanoseconds(nanos);
+            assert_eq!(
+                format_http_date(v).unwrap(),
+                "Sat, 30 Apr 2016 23:51:29 GMT"
+            );
+        }
+
+        let local = (t + time::Duration::milliseconds(700))
+            .to_offset(UtcOffset::from_hms(8, 0, 0).unwrap());
+        assert_eq!(
+            format_http_date(local).unwrap(),
+            "Sat, 30 Apr 2016 23:51:29 GMT"
+        );
+        assert_eq!(
+            parse_datetime(&format_http_date(local).unwrap()).unwrap(),
+            t
+        );
+    }
+
+    #[test]
+    fn test_parse_datetime_formats_equivalent() {
+        let mut rng = rand::thread_rng();
+
+        for _ in 0..1000 {
+            // Timestamps in [1970, 2100) with whole seconds, as http-date
+            // doesn't carry sub-second precision.
+            let t = OffsetDateTime::from_unix_timestamp(rng.gen_range(0..4102444800)).unwrap();
+            let offset = UtcOffset::from_whole_seconds(rng.gen_range(-12..=14) * 3600).unwrap();
+            let local = t.to_offset(offset);
+
+            let variants = [
+                t.format(&Rfc2822).unwrap(),
+                t.format(&Rfc2822).unwrap().replace("+0000", "GMT"),
+                local.format(&Rfc2822).unwrap(),
+                t.format(&Rfc3339).unwrap(),
+                t.format(&Rfc3339).unwrap().replace('Z', ".000Z"),
+                local.format(&Rfc3339).unwrap(),
+                t.format(&Iso8601::DEFAULT).unwrap(),
+            ];
+            for v in variants {
+                assert_eq!(parse_datetime(&v).unwrap(), t, "{:?}", v);
+            }
+        }
+    }
+}
@@ -0,0 +1,305 @@
+// Copyright 2022 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Parse headers of http responses, which could carry any bytes from
+//! misbehaving services or proxies.
+//!
+//! Invalid headers are returned as errors naming the header and the
+//! operation, [`lenient`] could be used to skip optional ones.
+
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::str::FromStr;
+
+use anyhow::anyhow;
+use http::header::AsHeaderName;
+use http::header::CONTENT_ENCODING;
+use http::header::CONTENT_LENGTH;
+use http::header::CONTENT_RANGE;
+use http::HeaderMap;
+use http::HeaderValue;
+use log::warn;
+use time::OffsetDateTime;
+
+use crate::error::Error;
+use crate::error::Kind;
+use crate::error::Result;
+use crate::http_util::parse_datetime;
+
+fn invalid(op: &'static str, path: &str, name: impl Display, source: anyhow::Error) -> Error {
+    Error::Object {
+        kind: Kind::Unexpected,
+        op,
+        path: path.to_string(),
+        context: HashMap::from([("header".to_string(), name.to_string())]),
+        source,
+    }
+}
+
+/// Parse the header as a visible ASCII string, returns `None` if it's absent.
+pub(crate) fn parse_header_str<'a, K>(
+    op: &'static str,
+    path: &str,
+    headers: &'a HeaderMap,
+    name: K,
+) -> Result<Option<&'a str>>
+where
+    K: AsHeaderName + Display + Copy,
+{
+    match headers.get(name) {
+        None => Ok(None),
+        Some(v) => parse_value_str(op, path, name, v).map(Some),
+    }
+}
+
+/// Parse a value of the header as a visible ASCII string, for headers
+/// which could have multiple values.
+pub(crate) fn parse_value_str<'a>(
+    op: &'static str,
+    path: &str,
+    name: impl Display,
+    value: &'a HeaderValue,
+) -> Result<&'a str> {
+    value.to_str().map_err(|e| {
+        invalid(
+            op,
+            path,
+            &name,
+            anyhow!("header {} is not ascii: {:?}", name, e),
+        )
+    })
+}
+
+/// Parse the header as a decimal integer, returns `None` if it's absent.
+pub(crate) fn parse_header_u64<K>(
+    op: &'static str,
+    path: &str,
+    headers: &HeaderMap,
+    name: K,
+) -> Result<Option<u64>>
+where
+    K: AsHeaderName + Display + Copy,
+{
+    match parse_header_str(op, path, headers, name)? {
+        None => Ok(None),
+        Some(v) => u64::from_str(v.trim()).map(Some).map_err(|e| {
+            invalid(
+                op,
+                path,
+                name,
+                anyhow!("header {} is not a valid integer {:?}: {:?}", name, v, e),
+            )
+        }),
+    }
+}
+
+/// Parse the header as a datetime like http-date, returns `None` if it's
+/// absent.
+pub(crate) fn parse_header_datetime<K>(
+    op: &'static str,
+    path: &str,
+    headers: &HeaderMap,
+    name: K,
+) -> Result<Option<OffsetDateTime>>
+where
+    K: AsHeaderName + Display + Copy,
+{
+    match parse_header_str(op, path, headers, name)? {
+        None => Ok(None),
+        Some(v) => parse_datetime(v).map(Some).map_err(|e| {
+            invalid(
+                op,
+                path,
+                name,
+                anyhow!("header {} is not a valid datetime {:?}: {:?}", name, v, e),
+            )
+        }),
+    }
+}
+
+/// Parse the header as a string, returns `None` if it's absent or invalid.
+///
+/// Use it only where the header is a hint, like conditions of following
+/// requests that are safe to skip.
+pub(crate) fn header_str<K: AsHeaderName>(headers: &HeaderMap, name: K) -> Option<&str> {
+    headers.get(name).and_then(|v| v.to_str().ok())
+}
+
+pub(crate) fn parse_content_length(headers: &HeaderMap) -> Option<u64> {
+    header_str(headers, &CONTENT_LENGTH).and_then(|v| u64::from_str(v.trim()).ok())
+}
+
+/// Parse the start of `Content-Range` like `bytes 100-199/1000`.
+pub(crate) fn parse_content_range_start(headers: &HeaderMap) -> Option<u64> {
+    header_str(headers, &CONTENT_RANGE)
+        .and_then(|v| v.strip_prefix("bytes "))
+        .and_then(|v| v.split_once('-'))
+        .and_then(|(start, _)| u64::from_str(start).ok())
+}
+
+/// Parse the total length from `Content-Range` like `bytes */1234`.
+pub(crate) fn parse_content_range_total(headers: &HeaderMap) -> Option<u64> {
+    header_str(headers, &CONTENT_RANGE)
+        .and_then(|v| v.rsplit_once('/'))
+        .and_then(|(_, total)| u64::from_str(total).ok())
+}
+
+/// Whether the body is encoded by gzip, like `Content-Encoding: gzip`.
+pub(crate) fn is_gzip_encoded(headers: &HeaderMap) -> bool {
+    header_str(headers, &CONTENT_ENCODING)
+        .map(|v| v.trim())
+        .map(|v| v.eq_ignore_ascii_case("gzip") || v.eq_ignore_ascii_case("x-gzip"))
+        .unwrap_or_default()
+}
+
+/// Skip the optional header if it's invalid, with a warning.
+pub(crate) fn lenient<T>(res: Result<Option<T>>) -> Option<T> {
+    res.unwrap_or_else(|e| {
+        warn!("ignore invalid header: {}", e);
+        None
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::Rng;
+    use rand::SeedableRng;
+
+    use super::*;
+
+    /// Random header values of bytes allowed on the wire, including
+    /// obs-text which is not valid ASCII.
+    fn random_values(n: usize) -> Vec<HeaderValue> {
+        let mut rng = StdRng::seed_from_u64(0x5eed);
+        (0..n)
+            .map(|_| {
+                let len = rng.gen_range(0..32);
+                let bs: Vec<u8> = (0..len)
+                    .map(|_| match rng.gen_range(0..4) {
+                        0 => rng.gen_range(b'0'..=b'9'),
+                        1 => rng.gen_range(0x80..=0xff),
+                        2 => b'\t',
+                        _ => rng.gen_range(0x20..0x7f),
+                    })
+                    .collect();
+                HeaderValue::from_bytes(&bs).expect("value must be valid")
+            })
+            .collect()
+    }
+
+    fn headers_of(name: &'static str, v: HeaderValue) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(name, v);
+        headers
+    }
+
+    fn assert_invalid(err: Error, name: &str) {
+        assert_eq!(err.kind(), Kind::Unexpected);
+        assert_eq!(err.context()["header"], name);
+        assert!(err.to_string().contains("op: stat"), "{}", err);
+    }
+
+    #[test]
+    fn test_parse_header_str() {
+        for v in random_values(10000) {
+            let ascii = v
+                .as_bytes()
+                .iter()
+                .all(|b| *b == b'\t' || (0x20..0x7f).contains(b));
+            let headers = headers_of("etag", v.clone());
+            match parse_header_str("stat", "path", &headers, "etag") {
+                Ok(Some(s)) => assert_eq!(s.as_bytes(), v.as_bytes()),
+                Ok(None) => panic!("header must exist"),
+                Err(err) => {
+                    assert!(!ascii, "{:?}", v);
+                    assert_invalid(err, "etag");
+                }
+            }
+            assert_eq!(header_str(&headers, "etag").is_some(), ascii, "{:?}", v);
+        }
+
+        let headers = HeaderMap::new();
+        assert_eq!(
+            parse_header_str("stat", "path", &headers, "etag").unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_parse_header_u64() {
+        for v in random_values(10000) {
+            let headers = headers_of("content-length", v.clone());
+            match parse_header_u64("stat", "path", &headers, "content-length") {
+                Ok(Some(_)) => {
+                    let s = v.to_str().unwrap().trim().trim_start_matches('+');
+                    assert!(s.bytes().all(|b| b.is_ascii_digit()), "{:?}", v);
+                }
+                Ok(None) => panic!("header must exist"),
+                Err(err) => assert_invalid(err, "content-length"),
+            }
+            // Never panics.
+            let _ = parse_content_length(&headers);
+        }
+
+        let headers = headers_of("content-length", HeaderValue::from_static(" 1024 "));
+        assert_eq!(
+            parse_header_u64("stat", "path", &headers, "content-length").unwrap(),
+            Some(1024)
+        );
+        let headers = headers_of("content-length", HeaderValue::from_static("-1"));
+        assert_invalid(
+            parse_header_u64("stat", "path", &headers, "content-length").unwrap_err(),
+            "content-length",
+        );
+    }
+
+    #[test]
+    fn test_parse_header_datetime() {
+        for v in random_values(10000) {
+            let headers = headers_of("last-modified", v);
+            match parse_header_datetime("stat", "path", &headers, "last-modified") {
+                Ok(v) => assert!(v.is_some()),
+                Err(err) => assert_invalid(err, "last-modified"),
+            }
+        }
+
+        let headers = headers_of(
+            "last-modified",
+            HeaderValue::from_static("Sat, 30 Apr 2016 23:51:29 GMT"),
+        );
+        let t = parse_header_datetime("stat", "path", &headers, "last-modified")
+            .unwrap()
+            .unwrap();
+        assert_eq!(t.unix_timestamp(), 1462060289);
+    }
+
+    #[test]
+    fn test_parse_content_range() {
+        for v in random_values(10000) {
+            let headers = headers_of("content-range", v);
+            // Never panics.
+            let _ = parse_content_range_start(&headers);
+            let _ = parse_content_range_total(&headers);
+            let _ = is_gzip_encoded(&headers);
+        }
+
+        let headers = headers_of(
+            "content-range",
+            HeaderValue::from_static("bytes 100-199/1000"),
+        );
+        assert_eq!(parse_content_range_start(&headers), Some(100));
+        assert_eq!(parse_content_range_total(&headers), Some(1000));
+    }
+}
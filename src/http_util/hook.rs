@@ -0,0 +1,42 @@
+// Copyright 2022 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::future::Future;
+use std::sync::Arc;
+
+/// RequestHook observes every request sent by [`HttpClient`][super::HttpClient].
+///
+/// Hooks are installed per task so that requests made by composite
+/// operations (multipart parts, listing pages, ...) can be attributed to the
+/// operation that issued them without changing every service.
+pub(crate) type RequestHook = Arc<dyn Fn(&hyper::Request<hyper::Body>) + Send + Sync>;
+
+tokio::task_local! {
+    static REQUEST_HOOK: RequestHook;
+}
+
+/// Run `fut` with `hook` installed.
+pub(crate) async fn with_request_hook<F: Future>(hook: RequestHook, fut: F) -> F::Output {
+    REQUEST_HOOK.scope(hook, fut).await
+}
+
+/// Run `f` with `hook` installed, used while polling streams.
+pub(crate) fn sync_with_request_hook<R>(hook: RequestHook, f: impl FnOnce() -> R) -> R {
+    REQUEST_HOOK.sync_scope(hook, f)
+}
+
+/// Notify the installed hook, if any, that `req` is going to be sent.
+pub(crate) fn notify_request(req: &hyper::Request<hyper::Body>) {
+    let _ = REQUEST_HOOK.try_with(|hook| hook(req));
+}
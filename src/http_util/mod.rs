@@ -0,0 +1,55 @@
+// Copyright 2022 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Utilities for the http based services.
+//!
+//! - [`Recorder`] records the wire-level traffic of a backend for debugging.
+//! - [`ReplayClient`] serves a recorded session back to a backend for
+//!   deterministic tests.
+
+mod client;
+pub(crate) use client::HttpClient;
+
+mod client_info;
+pub(crate) use client_info::insert_client_info;
+pub(crate) use client_info::sync_with_request_id;
+pub(crate) use client_info::user_agent;
+pub(crate) use client_info::with_request_id;
+pub use client_info::X_OPENDAL_REQUEST_ID;
+
+mod datetime;
+pub(crate) use datetime::format_http_date;
+pub(crate) use datetime::parse_datetime;
+
+mod header;
+pub(crate) use header::header_str;
+pub(crate) use header::is_gzip_encoded;
+pub(crate) use header::lenient;
+pub(crate) use header::parse_content_length;
+pub(crate) use header::parse_content_range_start;
+pub(crate) use header::parse_content_range_total;
+pub(crate) use header::parse_header_datetime;
+pub(crate) use header::parse_header_str;
+pub(crate) use header::parse_header_u64;
+pub(crate) use header::parse_value_str;
+
+mod hook;
+pub(crate) use hook::notify_request;
+pub(crate) use hook::sync_with_request_hook;
+pub(crate) use hook::with_request_hook;
+pub(crate) use hook::RequestHook;
+
+mod record;
+pub use record::Recorder;
+pub use record::ReplayClient;
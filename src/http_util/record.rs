@@ -0,0 +1,466 @@
+// Copyright 2022 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::fmt::Formatter;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use anyhow::anyhow;
+use async_trait::async_trait;
+use http::HeaderMap;
+use http::Method;
+use http::StatusCode;
+use http::Uri;
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::error::Error;
+use crate::error::Kind;
+use crate::error::Result;
+use crate::shutdown::InFlight;
+use crate::Close;
+use crate::Operator;
+
+/// Request headers that will not be recorded at all.
+const DROPPED_HEADERS: &[&str] = &["authorization", "cookie", "proxy-authorization"];
+
+/// Headers whose value will be replaced by [`REDACTED`].
+const REDACTED_HEADERS: &[&str] = &[
+    "set-cookie",
+    "x-amz-security-token",
+    "x-amz-server-side-encryption-aws-kms-key-id",
+    "x-amz-server-side-encryption-customer-key",
+    "x-amz-server-side-encryption-customer-key-md5",
+    "x-ms-encryption-key",
+    "x-ms-encryption-key-sha256",
+];
+
+/// Query keys whose value will be replaced by [`REDACTED`].
+const REDACTED_QUERY_KEYS: &[&str] = &[
+    "awsaccesskeyid",
+    "signature",
+    "x-amz-credential",
+    "x-amz-security-token",
+    "x-amz-signature",
+];
+
+const REDACTED: &str = "REDACTED";
+
+/// Default max size of recorded bodies.
+const DEFAULT_BODY_LIMIT: usize = 64 * 1024;
+
+/// Exchange is a recorded request/response pair.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct Exchange {
+    method: String,
+    path: String,
+    query: Vec<(String, String)>,
+    request_headers: Vec<(String, String)>,
+    request_body_size: usize,
+
+    status: u16,
+    response_headers: Vec<(String, String)>,
+    response_body_size: usize,
+    /// The response body has been truncated by the recorder's body limit.
+    response_body_truncated: bool,
+    elapsed_ms: u128,
+}
+
+impl Exchange {
+    /// Create a new exchange with sanitized request.
+    pub fn new(method: &Method, uri: &Uri, headers: &HeaderMap) -> Self {
+        Self {
+            method: method.to_string(),
+            path: uri.path().to_string(),
+            query: sanitize_query(uri.query().unwrap_or_default()),
+            request_headers: sanitize_headers(headers),
+            request_body_size: 0,
+
+            status: 0,
+            response_headers: vec![],
+            response_body_size: 0,
+            response_body_truncated: false,
+            elapsed_ms: 0,
+        }
+    }
+
+    pub fn set_response(&mut self, status: StatusCode, headers: &HeaderMap, elapsed: Duration) {
+        self.status = status.as_u16();
+        self.response_headers = sanitize_headers(headers);
+        self.elapsed_ms = elapsed.as_millis();
+    }
+}
+
+fn sanitize_headers(headers: &HeaderMap) -> Vec<(String, String)> {
+    headers
+        .iter()
+        .filter(|(k, _)| !DROPPED_HEADERS.contains(&k.as_str()))
+        .map(|(k, v)| {
+            let v = if REDACTED_HEADERS.contains(&k.as_str()) {
+                REDACTED.to_string()
+            } else {
+                String::from_utf8_lossy(v.as_bytes()).to_string()
+            };
+            (k.to_string(), v)
+        })
+        .collect()
+}
+
+fn sanitize_query(query: &str) -> Vec<(String, String)> {
+    query
+        .split('&')
+        .filter(|v| !v.is_empty())
+        .map(|kv| {
+            let (k, v) = kv.split_once('=').unwrap_or((kv, ""));
+            let v = if REDACTED_QUERY_KEYS.contains(&k.to_lowercase().as_str()) {
+                REDACTED
+            } else {
+                v
+            };
+            (k.to_string(), v.to_string())
+        })
+        .collect()
+}
+
+fn io_error(err: std::io::Error, op: &'static str, path: &Path) -> Error {
+    Error::Object {
+        kind: Kind::Unexpected,
+        op,
+        path: path.to_string_lossy().to_string(),
+        context: HashMap::new(),
+        source: anyhow::Error::from(err),
+    }
+}
+
+enum Sink {
+    Dir(PathBuf),
+    Operator(Operator),
+}
+
+impl Debug for Sink {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Sink::Dir(dir) => f.debug_tuple("Dir").field(dir).finish(),
+            Sink::Operator(_) => f.debug_tuple("Operator").finish(),
+        }
+    }
+}
+
+/// Recorder records every request/response pair sent by a backend.
+///
+/// For every exchange, the recorder writes numbered files:
+///
+/// - `000001.json`: method, path, query, request headers, response status and headers.
+/// - `000001.request.bin`: request body, up to the body limit.
+/// - `000001.response.bin`: response body, up to the body limit.
+///
+/// Signatures, tokens and SSE keys will be removed or redacted before writing.
+///
+/// # Note
+///
+/// Bodies will be buffered in memory while recording, only enable it for debugging.
+///
+/// Recorders are closed by [`Close`], which stops recording and waits for
+/// exchanges being written, so that the recorded session is complete.
+#[derive(Debug, Clone)]
+pub struct Recorder {
+    sink: Arc<Sink>,
+    body_limit: usize,
+    seq: Arc<AtomicUsize>,
+    closed: Arc<AtomicBool>,
+    in_flight: Arc<InFlight>,
+}
+
+impl Recorder {
+    /// Record exchanges into a local dir.
+    pub fn new(dir: &str) -> Self {
+        Self::with_sink(Sink::Dir(PathBuf::from(dir)))
+    }
+
+    /// Record exchanges through an operator.
+    pub fn with_operator(op: Operator) -> Self {
+        Self::with_sink(Sink::Operator(op))
+    }
+
+    fn with_sink(sink: Sink) -> Self {
+        Self {
+            sink: Arc::new(sink),
+            body_limit: DEFAULT_BODY_LIMIT,
+            seq: Arc::new(AtomicUsize::new(0)),
+            closed: Arc::new(AtomicBool::new(false)),
+            in_flight: Arc::new(InFlight::default()),
+        }
+    }
+
+    /// Set the max size of bodies to record, default to 64 KiB.
+    #[must_use]
+    pub fn body_limit(mut self, limit: usize) -> Self {
+        self.body_limit = limit;
+        self
+    }
+
+    pub(crate) async fn record(
+        &self,
+        mut exchange: Exchange,
+        req_body: &[u8],
+        resp_body: &[u8],
+    ) -> Result<()> {
+        let _token = self.in_flight.enter();
+        if self.closed.load(Ordering::Acquire) {
+            return Ok(());
+        }
+        let seq = self.seq.fetch_add(1, Ordering::SeqCst) + 1;
+
+        exchange.request_body_size = req_body.len();
+        exchange.response_body_size = resp_body.len();
+        exchange.response_body_truncated = resp_body.len() > self.body_limit;
+
+        let meta = serde_json::to_vec_pretty(&exchange)
+            .map_err(|e| Error::from(anyhow::Error::from(e)))?;
+        self.write(&format!("{:06}.json", seq), meta).await?;
+        for (name, body) in [("request", req_body), ("response", resp_body)] {
+            if body.is_empty() {
+                continue;
+            }
+            let body = body[..body.len().min(self.body_limit)].to_vec();
+            self.write(&format!("{:06}.{}.bin", seq, name), body)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn write(&self, name: &str, bs: Vec<u8>) -> Result<()> {
+        match self.sink.as_ref() {
+            Sink::Dir(dir) => {
+                let path = dir.join(name);
+                tokio::fs::create_dir_all(dir)
+                    .await
+                    .map_err(|e| io_error(e, "record", &path))?;
+                tokio::fs::write(&path, bs)
+                    .await
+                    .map_err(|e| io_error(e, "record", &path))?;
+            }
+            Sink::Operator(op) => {
+                op.object(name).writer().write_bytes(bs).await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Close for Recorder {
+    /// Stop recording, and wait for exchanges being written.
+    async fn close(&mut self) -> Result<()> {
+        self.closed.store(true, Ordering::Release);
+        self.in_flight.wait().await;
+        Ok(())
+    }
+}
+
+type Recorded = (Exchange, Vec<u8>, bool);
+
+/// ReplayClient serves a session recorded by [`Recorder`] back to the backend.
+///
+/// Requests are matched with recorded exchanges by method, path and query.
+/// Every recorded exchange will be served only once, in recorded order.
+///
+/// Redacted query values are always ignored while matching, and more
+/// fuzziness can be configured by [`ReplayClient::ignore_query_key`] and
+/// [`ReplayClient::ignore_query`].
+#[derive(Debug, Clone)]
+pub struct ReplayClient {
+    /// Recorded exchanges with their response body and whether served.
+    exchanges: Arc<Mutex<Vec<Recorded>>>,
+    ignored_query_keys: Vec<String>,
+    ignore_query: bool,
+}
+
+impl ReplayClient {
+    /// Load a recorded session from a local dir.
+    pub async fn from_dir(dir: &str) -> Result<Self> {
+        let dir = PathBuf::from(dir);
+
+        let mut names = Vec::new();
+        let mut rd = tokio::fs::read_dir(&dir)
+            .await
+            .map_err(|e| io_error(e, "replay", &dir))?;
+        while let Some(entry) = rd
+            .next_entry()
+            .await
+            .map_err(|e| io_error(e, "replay", &dir))?
+        {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if let Some(seq) = name.strip_suffix(".json") {
+                names.push(seq.to_string());
+            }
+        }
+        names.sort();
+
+        let mut exchanges = Vec::with_capacity(names.len());
+        for seq in names {
+            let path = dir.join(format!("{}.json", seq));
+            let bs = tokio::fs::read(&path)
+                .await
+                .map_err(|e| io_error(e, "replay", &path))?;
+            let exchange: Exchange = serde_json::from_slice(&bs).map_err(|e| Error::Object {
+                kind: Kind::Unexpected,
+                op: "replay",
+                path: path.to_string_lossy().to_string(),
+                context: HashMap::new(),
+                source: anyhow!("parse recorded exchange: {:?}", e),
+            })?;
+            let path = dir.join(format!("{}.response.bin", seq));
+            let body = match tokio::fs::read(&path).await {
+                Ok(body) => body,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => vec![],
+                Err(e) => return Err(io_error(e, "replay", &path)),
+            };
+            exchanges.push((exchange, body, false));
+        }
+
+        Ok(Self {
+            exchanges: Arc::new(Mutex::new(exchanges)),
+            ignored_query_keys: vec![],
+            ignore_query: false,
+        })
+    }
+
+    /// Ignore the query key while matching.
+    #[must_use]
+    pub fn ignore_query_key(mut self, key: &str) -> Self {
+        self.ignored_query_keys.push(key.to_string());
+        self
+    }
+
+    /// Ignore the whole query while matching, only method and path are matched.
+    #[must_use]
+    pub fn ignore_query(mut self) -> Self {
+        self.ignore_query = true;
+        self
+    }
+
+    fn normalize_query(&self, query: &[(String, String)]) -> Vec<(String, String)> {
+        if self.ignore_query {
+            return vec![];
+        }
+
+        let mut query: Vec<(String, String)> = query
+            .iter()
+            .filter(|(k, _)| !self.ignored_query_keys.contains(k))
+            .filter(|(k, _)| !REDACTED_QUERY_KEYS.contains(&k.to_lowercase().as_str()))
+            .cloned()
+            .collect();
+        query.sort();
+        query
+    }
+
+    pub(crate) fn serve(
+        &self,
+        method: &Method,
+        uri: &Uri,
+    ) -> anyhow::Result<hyper::Response<hyper::Body>> {
+        let query = self.normalize_query(&sanitize_query(uri.query().unwrap_or_default()));
+
+        let mut exchanges = self.exchanges.lock().expect("lock poisoned");
+        let (exchange, body, served) = exchanges
+            .iter_mut()
+            .find(|(v, _, served)| {
+                !served
+                    && v.method == method.as_str()
+                    && v.path == uri.path()
+                    && self.normalize_query(&v.query) == query
+            })
+            .ok_or_else(|| anyhow!("no recorded exchange matches {} {}", method, uri))?;
+        *served = true;
+
+        let mut resp = hyper::Response::builder().status(exchange.status);
+        for (k, v) in &exchange.response_headers {
+            // Content length will be decided by the replayed body.
+            if k == "content-length" && exchange.response_body_truncated {
+                continue;
+            }
+            resp = resp.header(k.as_str(), v.as_str());
+        }
+        Ok(resp.body(hyper::Body::from(body.clone()))?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use http::HeaderValue;
+
+    use super::*;
+
+    #[test]
+    fn test_sanitize() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "authorization",
+            HeaderValue::from_static("AWS4-HMAC-SHA256 Credential=access_key_id/..."),
+        );
+        headers.insert(
+            "x-amz-security-token",
+            HeaderValue::from_static("session_token"),
+        );
+        headers.insert(
+            "x-amz-server-side-encryption-customer-key",
+            HeaderValue::from_static("c2VjcmV0"),
+        );
+        headers.insert("content-type", HeaderValue::from_static("text/plain"));
+
+        let uri: Uri = "/bucket/path?X-Amz-Signature=abc&X-Amz-Credential=ak&prefix=dir/"
+            .parse()
+            .unwrap();
+        let exchange = Exchange::new(&Method::GET, &uri, &headers);
+
+        assert_eq!(exchange.path, "/bucket/path");
+        assert_eq!(
+            exchange.query,
+            vec![
+                ("X-Amz-Signature".to_string(), REDACTED.to_string()),
+                ("X-Amz-Credential".to_string(), REDACTED.to_string()),
+                ("prefix".to_string(), "dir/".to_string()),
+            ]
+        );
+
+        let mut request_headers = exchange.request_headers.clone();
+        request_headers.sort();
+        assert_eq!(
+            request_headers,
+            vec![
+                ("content-type".to_string(), "text/plain".to_string()),
+                ("x-amz-security-token".to_string(), REDACTED.to_string()),
+                (
+                    "x-amz-server-side-encryption-customer-key".to_string(),
+                    REDACTED.to_string()
+                ),
+            ]
+        );
+
+        let recorded = serde_json::to_string(&exchange).unwrap();
+        for secret in ["access_key_id", "session_token", "c2VjcmV0", "abc", "ak\""] {
+            assert!(!recorded.contains(secret), "{} leaked", secret);
+        }
+    }
+}
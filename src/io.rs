@@ -12,6 +12,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::collections::HashMap;
 use std::future::Future;
 use std::io;
 use std::io::SeekFrom;
@@ -20,19 +21,32 @@ use std::sync::Arc;
 use std::task::Context;
 use std::task::Poll;
 
+use anyhow::anyhow;
+use async_trait::async_trait;
 use bytes::Bytes;
+use bytes::BytesMut;
+use futures::channel::mpsc;
 use futures::future::BoxFuture;
 use futures::ready;
 use futures::AsyncRead;
 use futures::AsyncSeek;
+use futures::AsyncWrite;
 use futures::Stream;
 use futures::TryStreamExt;
+use h2::Reason;
+use time::OffsetDateTime;
 
+use crate::error::Error;
+use crate::error::Kind;
 use crate::error::Result;
+use crate::ops::BytesRange;
+use crate::ops::OpPriority;
 use crate::ops::OpRead;
 use crate::ops::OpStat;
 use crate::ops::OpWrite;
+use crate::ops::ResponseOverrides;
 use crate::Accessor;
+use crate::Close;
 use crate::Metadata;
 
 /// BoxedAsyncReader is a boxed AsyncRead.
@@ -40,6 +54,95 @@ pub type BoxedAsyncReader = Box<dyn AsyncRead + Unpin + Send>;
 /// BytesStream represents a stream of bytes.
 pub type BytesStream = Box<dyn Stream<Item = Result<Bytes>> + Unpin + Send>;
 
+/// HttpBodyStream turns the body of a read response into [`BytesStream`].
+///
+/// The offset reached so far is tracked, and attached to the `offset` context
+/// of body errors, so that interrupted reads can be resumed from there.
+pub(crate) struct HttpBodyStream {
+    body: hyper::Body,
+    path: String,
+    offset: u64,
+}
+
+impl HttpBodyStream {
+    /// Create a new stream of the body which starts at `offset` of the object.
+    pub(crate) fn new(body: hyper::Body, path: &str, offset: u64) -> Self {
+        Self {
+            body,
+            path: path.to_string(),
+            offset,
+        }
+    }
+}
+
+impl Stream for HttpBodyStream {
+    type Item = Result<Bytes>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        match ready!(Pin::new(&mut self.body).poll_next(cx)) {
+            Some(Ok(bs)) => {
+                self.offset += bs.len() as u64;
+                Poll::Ready(Some(Ok(bs)))
+            }
+            Some(Err(e)) => {
+                let kind = if is_interrupted(&e) {
+                    Kind::ObjectReadInterrupted
+                } else {
+                    Kind::Unexpected
+                };
+                Poll::Ready(Some(Err(Error::Object {
+                    kind,
+                    op: "read",
+                    path: self.path.clone(),
+                    context: HashMap::from([("offset".to_string(), self.offset.to_string())]),
+                    source: anyhow!(e),
+                })))
+            }
+            None => Poll::Ready(None),
+        }
+    }
+}
+
+/// Check whether the body error is caused by the connection or stream
+/// being interrupted, which is temporary and safe to retry.
+fn is_interrupted(err: &hyper::Error) -> bool {
+    // Connection closed before the whole body has been received.
+    if err.is_incomplete_message() || err.is_timeout() {
+        return true;
+    }
+
+    let mut source: Option<&(dyn std::error::Error + 'static)> = Some(err);
+    while let Some(e) = source {
+        if let Some(e) = e.downcast_ref::<io::Error>() {
+            if matches!(
+                e.kind(),
+                io::ErrorKind::ConnectionReset
+                    | io::ErrorKind::ConnectionAborted
+                    | io::ErrorKind::BrokenPipe
+                    | io::ErrorKind::UnexpectedEof
+                    | io::ErrorKind::TimedOut
+            ) {
+                return true;
+            }
+        }
+        if let Some(e) = e.downcast_ref::<h2::Error>() {
+            // Streams reset or refused by the server, `ENHANCE_YOUR_CALM`
+            // is used for throttling.
+            if matches!(
+                e.reason(),
+                Some(Reason::NO_ERROR)
+                    | Some(Reason::REFUSED_STREAM)
+                    | Some(Reason::CANCEL)
+                    | Some(Reason::ENHANCE_YOUR_CALM)
+            ) {
+                return true;
+            }
+        }
+        source = e.source();
+    }
+    false
+}
+
 /// Reader is used for reading data from underlying backend.
 ///
 /// # Lazy Stat
@@ -51,6 +154,13 @@ pub struct Reader {
     path: String,
     offset: Option<u64>,
     size: Option<u64>,
+    version: Option<String>,
+    if_match: Option<String>,
+    if_none_match: Option<String>,
+    if_modified_since: Option<OffsetDateTime>,
+    if_unmodified_since: Option<OffsetDateTime>,
+    response_overrides: ResponseOverrides,
+    priority: OpPriority,
 
     pos: u64,
     state: ReadState,
@@ -70,12 +180,81 @@ impl Reader {
             path: path.to_string(),
             offset,
             size,
+            version: None,
+            if_match: None,
+            if_none_match: None,
+            if_modified_since: None,
+            if_unmodified_since: None,
+            response_overrides: ResponseOverrides::default(),
+            priority: OpPriority::Normal,
 
             pos: 0,
             state: ReadState::Idle,
         }
     }
 
+    /// Read the given version of the object, like the `versionId` of s3.
+    ///
+    /// Fail with `Kind::ObjectNotExist` if the version doesn't exist.
+    #[must_use]
+    pub fn version(mut self, version: &str) -> Self {
+        self.version = Some(version.to_string());
+        self
+    }
+
+    /// Only read if the object's ETag matches `etag`.
+    ///
+    /// The condition applies to every request sent by this reader, so that
+    /// reads after seek will fail with `Kind::ObjectPreconditionFailed`
+    /// if the object has been changed underneath.
+    #[must_use]
+    pub fn if_match(mut self, etag: &str) -> Self {
+        self.if_match = Some(etag.to_string());
+        self
+    }
+
+    /// Only read if the object's ETag doesn't match `etag`, or fail with
+    /// `Kind::ObjectNotModified`.
+    #[must_use]
+    pub fn if_none_match(mut self, etag: &str) -> Self {
+        self.if_none_match = Some(etag.to_string());
+        self
+    }
+
+    /// Only read if the object has been modified after `t`, or fail with
+    /// `Kind::ObjectNotModified`.
+    ///
+    /// Sub-second precision of `t` will be truncated.
+    #[must_use]
+    pub fn if_modified_since(mut self, t: OffsetDateTime) -> Self {
+        self.if_modified_since = Some(t);
+        self
+    }
+
+    /// Only read if the object has not been modified after `t`, or fail with
+    /// `Kind::ObjectPreconditionFailed`.
+    ///
+    /// Sub-second precision of `t` will be truncated.
+    #[must_use]
+    pub fn if_unmodified_since(mut self, t: OffsetDateTime) -> Self {
+        self.if_unmodified_since = Some(t);
+        self
+    }
+
+    /// Set the priority of all requests sent by this reader.
+    #[must_use]
+    pub fn priority(mut self, priority: OpPriority) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// Override the headers of responses, like `Content-Disposition`.
+    #[must_use]
+    pub fn response_overrides(mut self, overrides: ResponseOverrides) -> Self {
+        self.response_overrides = overrides;
+        self
+    }
+
     fn current_offset(&self) -> u64 {
         self.offset.unwrap_or_default() + self.pos
     }
@@ -91,13 +270,25 @@ impl AsyncRead for Reader {
         cx: &mut Context<'_>,
         buf: &mut [u8],
     ) -> Poll<std::io::Result<usize>> {
+        // Nothing left to read, don't bother the accessor.
+        if matches!(self.state, ReadState::Idle) && self.current_size() == Some(0) {
+            return Poll::Ready(Ok(0));
+        }
+
         match &mut self.state {
             ReadState::Idle => {
                 let acc = self.acc.clone();
                 let op = OpRead {
                     path: self.path.to_string(),
-                    offset: Some(self.current_offset()),
-                    size: self.current_size(),
+                    range: BytesRange::new(Some(self.current_offset()), self.current_size()),
+                    version: self.version.clone(),
+                    if_match: self.if_match.clone(),
+                    if_none_match: self.if_none_match.clone(),
+                    if_modified_since: self.if_modified_since,
+                    if_unmodified_since: self.if_unmodified_since,
+                    response_overrides: self.response_overrides.clone(),
+                    priority: self.priority,
+                    raw_headers: false,
                 };
 
                 let future = async move { acc.read(&op).await };
@@ -149,7 +340,9 @@ impl AsyncSeek for Reader {
                 // Stat the object to get it's content-length.
                 if self.size.is_none() {
                     let acc = self.acc.clone();
-                    let op = OpStat::new(&self.path);
+                    let mut op = OpStat::new(&self.path);
+                    op.version = self.version.clone();
+                    op.priority = self.priority;
 
                     let future = async move { acc.stat(&op).await };
 
@@ -170,39 +363,523 @@ impl AsyncSeek for Reader {
     }
 }
 
-/// Writer is used to write data into underlying backend.
+/// Default size of the read-ahead buffer of [`SeekableReader`].
+const SEEKABLE_READER_BUFFER_SIZE: usize = 64 * 1024;
+
+/// SeekableReader reads the object by ranged reads on demand, for parsers
+/// that seek around like parquet or zip readers.
 ///
-/// # TODO
+/// Seeking only records the new position, while reads fetch at least
+/// `buffer_size` bytes from the position into a read-ahead buffer, so that
+/// small reads don't send one request each.
 ///
-/// maybe we can implement `AsyncWrite` for `Writer`
-pub struct Writer {
+/// Like `tokio::fs::File`, seeking past the end of object is allowed and
+/// reads there return 0 bytes, while seeking before the start fails with
+/// `InvalidInput`.
+///
+/// # Lazy Stat
+///
+/// The object's content length is fetched by the first read or
+/// `SeekFrom::End`, unless given by [`SeekableReader::content_length`].
+/// Ranged reads require the ETag returned by stat, or given by
+/// [`SeekableReader::if_match`], so that contents of a replaced object will
+/// not be mixed.
+pub struct SeekableReader {
     acc: Arc<dyn Accessor>,
     path: String,
+    buffer_size: usize,
+    priority: OpPriority,
+
+    content_length: Option<u64>,
+    etag: Option<String>,
+    pos: u64,
+    /// The offset of `buf` in the object.
+    buf_start: u64,
+    buf: Bytes,
+    state: SeekableReadState,
 }
 
-impl Writer {
+enum SeekableReadState {
+    Idle,
+    Stating(BoxFuture<'static, Result<Metadata>>),
+    Reading(BoxFuture<'static, Result<Bytes>>),
+}
+
+impl SeekableReader {
     pub fn new(acc: Arc<dyn Accessor>, path: &str) -> Self {
         Self {
             acc,
             path: path.to_string(),
+            buffer_size: SEEKABLE_READER_BUFFER_SIZE,
+            priority: OpPriority::Normal,
+
+            content_length: None,
+            etag: None,
+            pos: 0,
+            buf_start: 0,
+            buf: Bytes::new(),
+            state: SeekableReadState::Idle,
+        }
+    }
+
+    /// Set the min bytes fetched by every ranged read.
+    ///
+    /// Default to 64 KiB.
+    #[must_use]
+    pub fn buffer_size(mut self, size: usize) -> Self {
+        self.buffer_size = size.max(1);
+        self
+    }
+
+    /// Set the content length of the object to skip stat, for example, the
+    /// length from listing.
+    #[must_use]
+    pub fn content_length(mut self, length: u64) -> Self {
+        self.content_length = Some(length);
+        self
+    }
+
+    /// Only read if the object's ETag matches `etag`, or fail with
+    /// `Kind::ObjectPreconditionFailed`.
+    ///
+    /// Default to the ETag returned by stat.
+    #[must_use]
+    pub fn if_match(mut self, etag: &str) -> Self {
+        self.etag = Some(etag.to_string());
+        self
+    }
+
+    /// Set the priority of all requests sent by this reader.
+    #[must_use]
+    pub fn priority(mut self, priority: OpPriority) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    fn stat(&mut self) -> SeekableReadState {
+        let acc = self.acc.clone();
+        let mut op = OpStat::new(&self.path);
+        op.priority = self.priority;
+
+        SeekableReadState::Stating(Box::pin(async move { acc.stat(&op).await }))
+    }
+
+    /// Poll the pending stat, returns the content length.
+    fn poll_stat(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<u64>> {
+        if let Some(length) = self.content_length {
+            return Poll::Ready(Ok(length));
+        }
+        if !matches!(self.state, SeekableReadState::Stating(_)) {
+            self.state = self.stat();
+        }
+        let res = match &mut self.state {
+            SeekableReadState::Stating(future) => ready!(future.as_mut().poll(cx)),
+            _ => unreachable!("state must be stating"),
+        };
+        self.state = SeekableReadState::Idle;
+        let meta = res.map_err(io::Error::from)?;
+        self.content_length = Some(meta.content_length());
+        if self.etag.is_none() {
+            self.etag = meta.etag();
+        }
+        Poll::Ready(Ok(meta.content_length()))
+    }
+}
+
+impl AsyncRead for SeekableReader {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        if buf.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+
+        loop {
+            // Serve from the read-ahead buffer.
+            let buf_end = self.buf_start + self.buf.len() as u64;
+            if self.buf_start <= self.pos && self.pos < buf_end {
+                let start = (self.pos - self.buf_start) as usize;
+                let n = buf.len().min(self.buf.len() - start);
+                buf[..n].copy_from_slice(&self.buf[start..start + n]);
+                self.pos += n as u64;
+                return Poll::Ready(Ok(n));
+            }
+
+            match &mut self.state {
+                SeekableReadState::Reading(future) => {
+                    let res = ready!(future.as_mut().poll(cx));
+                    self.state = SeekableReadState::Idle;
+                    let bs = res.map_err(io::Error::from)?;
+                    // The object has been truncated underneath.
+                    if bs.is_empty() {
+                        return Poll::Ready(Ok(0));
+                    }
+                    self.buf_start = self.pos;
+                    self.buf = bs;
+                }
+                SeekableReadState::Idle | SeekableReadState::Stating(_) => {
+                    let length = ready!(self.poll_stat(cx))?;
+                    if self.pos >= length {
+                        return Poll::Ready(Ok(0));
+                    }
+
+                    let size = (buf.len().max(self.buffer_size) as u64).min(length - self.pos);
+                    let acc = self.acc.clone();
+                    let op = OpRead {
+                        path: self.path.clone(),
+                        range: BytesRange::from(self.pos..self.pos + size),
+                        if_match: self.etag.clone(),
+                        priority: self.priority,
+                        ..Default::default()
+                    };
+                    self.state =
+                        SeekableReadState::Reading(Box::pin(
+                            async move { acc.read_mmap(&op).await },
+                        ));
+                }
+            }
         }
     }
+}
 
-    pub async fn write_bytes(self, bs: Vec<u8>) -> Result<usize> {
-        let op = &OpWrite {
-            path: self.path.clone(),
-            size: bs.len() as u64,
+impl AsyncSeek for SeekableReader {
+    fn poll_seek(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        pos: SeekFrom,
+    ) -> Poll<io::Result<u64>> {
+        let (base, off) = match pos {
+            SeekFrom::Start(off) => (off, 0),
+            SeekFrom::Current(off) => (self.pos, off),
+            SeekFrom::End(off) => (ready!(self.poll_stat(cx))?, off),
         };
+        let pos = base.checked_add_signed(off).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )
+        })?;
+
+        // The pending read is for the previous position.
+        if matches!(self.state, SeekableReadState::Reading(_)) {
+            self.state = SeekableReadState::Idle;
+        }
+        self.pos = pos;
+        Poll::Ready(Ok(pos))
+    }
+}
+
+/// Default size of data buffered by [`Writer`] before sending to the backend.
+pub(crate) const DEFAULT_WRITE_BUFFER_SIZE: usize = 256 * 1024;
+
+/// Writer is used to write data into underlying backend.
+///
+/// Besides the one-shot `write_*` APIs, Writer implements [`AsyncWrite`]
+/// to write incrementally, which is sent to the backend like
+/// [`Writer::write_stream`], e.g. by multipart upload on s3 once the data
+/// grows large.
+///
+/// # Close
+///
+/// The object will be committed only after [`Writer::close`], which returns
+/// the metadata of the written object. Dropping the writer without closing
+/// aborts the write, so that partial data will never be committed.
+///
+/// Writer implements [`Close`] as well, so that it could be committed by
+/// a [`ShutdownGuard`](crate::ShutdownGuard) while shutting down.
+pub struct Writer {
+    acc: Arc<dyn Accessor>,
+    args: OpWrite,
+
+    buffer_size: usize,
+    buf: BytesMut,
+    state: WriteState,
+}
+
+enum WriteState {
+    Idle,
+    Writing {
+        // Dropped to send EOF while closing.
+        tx: Option<mpsc::Sender<io::Result<Bytes>>>,
+        fut: BoxFuture<'static, Result<Metadata>>,
+    },
+    Closed(Box<Metadata>),
+    Failed,
+}
+
+impl Writer {
+    pub fn new(acc: Arc<dyn Accessor>, path: &str) -> Self {
+        Self {
+            acc,
+            args: OpWrite::new(path, 0),
+            buffer_size: DEFAULT_WRITE_BUFFER_SIZE,
+            buf: BytesMut::new(),
+            state: WriteState::Idle,
+        }
+    }
+
+    /// Set the size of data buffered before sending to the backend while
+    /// writing by [`AsyncWrite`].
+    ///
+    /// Default to 256 KiB.
+    #[must_use]
+    pub fn buffer_size(mut self, size: usize) -> Self {
+        self.buffer_size = size.max(1);
+        self
+    }
+
+    /// Set the `Content-Type` of this write.
+    #[must_use]
+    pub fn content_type(mut self, content_type: &str) -> Self {
+        self.args.content_type = Some(content_type.to_string());
+        self
+    }
+
+    /// Set the storage class of this write.
+    #[must_use]
+    pub fn storage_class(mut self, storage_class: &str) -> Self {
+        self.args.storage_class = Some(storage_class.to_string());
+        self
+    }
+
+    /// Set the `Cache-Control` of this write.
+    #[must_use]
+    pub fn cache_control(mut self, cache_control: &str) -> Self {
+        self.args.cache_control = Some(cache_control.to_string());
+        self
+    }
+
+    /// Set the `Content-Disposition` of this write.
+    #[must_use]
+    pub fn content_disposition(mut self, content_disposition: &str) -> Self {
+        self.args.content_disposition = Some(content_disposition.to_string());
+        self
+    }
+
+    /// Set the `Content-Encoding` of this write.
+    #[must_use]
+    pub fn content_encoding(mut self, content_encoding: &str) -> Self {
+        self.args.content_encoding = Some(content_encoding.to_string());
+        self
+    }
+
+    /// Set the `Expires` of this write.
+    #[must_use]
+    pub fn expires(mut self, expires: OffsetDateTime) -> Self {
+        self.args.expires = Some(expires);
+        self
+    }
+
+    /// Attach a tag to the object of this write.
+    #[must_use]
+    pub fn tag(mut self, key: &str, value: &str) -> Self {
+        self.args.tags.insert(key.to_string(), value.to_string());
+        self
+    }
+
+    /// Attach a user-defined metadata to the object of this write.
+    ///
+    /// Key will be lowercased.
+    #[must_use]
+    pub fn user_metadata(mut self, key: &str, value: &str) -> Self {
+        self.args
+            .user_metadata
+            .insert(key.to_lowercase(), value.to_string());
+        self
+    }
+
+    /// Only write if the existing object's ETag matches `etag`, or fail
+    /// with `Kind::ObjectPreconditionFailed`.
+    #[must_use]
+    pub fn if_match(mut self, etag: &str) -> Self {
+        self.args.if_match = Some(etag.to_string());
+        self
+    }
+
+    /// Only write if the existing object's ETag doesn't match `etag`, or
+    /// fail with `Kind::ObjectPreconditionFailed`.
+    ///
+    /// Use `*` to write only if the object doesn't exist.
+    #[must_use]
+    pub fn if_none_match(mut self, etag: &str) -> Self {
+        self.args.if_none_match = Some(etag.to_string());
+        self
+    }
+
+    /// Set the priority of this write.
+    #[must_use]
+    pub fn priority(mut self, priority: OpPriority) -> Self {
+        self.args.priority = priority;
+        self
+    }
+
+    pub async fn write_bytes(mut self, bs: Vec<u8>) -> Result<Metadata> {
+        self.args.size = Some(bs.len() as u64);
         let r = Box::new(futures::io::Cursor::new(bs));
 
-        self.acc.write(r, op).await
+        self.acc.write(r, &self.args).await
     }
-    pub async fn write_reader(self, r: BoxedAsyncReader, size: u64) -> Result<usize> {
-        let op = &OpWrite {
-            path: self.path.clone(),
-            size,
+    pub async fn write_reader(mut self, r: BoxedAsyncReader, size: u64) -> Result<Metadata> {
+        self.args.size = Some(size);
+
+        self.acc.write(r, &self.args).await
+    }
+
+    /// Write all data from a reader whose size is unknown, like a
+    /// compression stream.
+    ///
+    /// Returns the metadata of the written object, whose `content_length` is
+    /// the number of bytes that have been written.
+    ///
+    /// # Note
+    ///
+    /// Backends handle unknown size differently:
+    ///
+    /// - s3 uploads the data by multipart upload part by part.
+    /// - azblob buffers the whole data in memory before uploading.
+    pub async fn write_stream(mut self, r: BoxedAsyncReader) -> Result<Metadata> {
+        self.args.size = None;
+
+        self.acc.write(r, &self.args).await
+    }
+
+    /// Commit data written by [`AsyncWrite`] and return the metadata of the
+    /// written object.
+    ///
+    /// An empty object will be created if nothing has been written.
+    pub async fn close(&mut self) -> Result<Metadata> {
+        futures::future::poll_fn(|cx| self.poll_commit(cx)).await
+    }
+
+    fn start(&mut self) {
+        if !matches!(self.state, WriteState::Idle) {
+            return;
+        }
+
+        // The backend reads data from the channel until it's closed.
+        let (tx, rx) = mpsc::channel(0);
+        let acc = self.acc.clone();
+        let mut args = self.args.clone();
+        args.size = None;
+        let fut = async move { acc.write(Box::new(rx.into_async_read()), &args).await };
+
+        self.state = WriteState::Writing {
+            tx: Some(tx),
+            fut: Box::pin(fut),
+        };
+    }
+
+    /// Send the buffered data to the backend, and drive the write meanwhile.
+    fn poll_send(&mut self, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        self.start();
+
+        while !self.buf.is_empty() {
+            let (tx, fut) = match &mut self.state {
+                WriteState::Writing { tx: Some(tx), fut } => (tx, fut),
+                _ => return Poll::Ready(Err(self.closed_error())),
+            };
+
+            // The write must not finish before all data has been sent.
+            if let Poll::Ready(res) = fut.as_mut().poll(cx) {
+                self.state = WriteState::Failed;
+                return Poll::Ready(Err(res.err().unwrap_or_else(|| {
+                    self.closed_error()
+                        .with_context("reason", "write finished before close")
+                })));
+            }
+            match tx.poll_ready(cx) {
+                Poll::Ready(Ok(())) => {
+                    let bs = self.buf.split().freeze();
+                    tx.start_send(Ok(bs)).expect("sender must be ready to send");
+                }
+                // The backend dropped the reader, its error will be
+                // returned by the future.
+                Poll::Ready(Err(_)) => {
+                    let res = ready!(fut.as_mut().poll(cx));
+                    self.state = WriteState::Failed;
+                    return Poll::Ready(Err(res.err().unwrap_or_else(|| {
+                        self.closed_error()
+                            .with_context("reason", "write finished before close")
+                    })));
+                }
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_commit(&mut self, cx: &mut Context<'_>) -> Poll<Result<Metadata>> {
+        if let WriteState::Closed(meta) = &self.state {
+            return Poll::Ready(Ok(meta.as_ref().clone()));
+        }
+        ready!(self.poll_send(cx))?;
+
+        let fut = match &mut self.state {
+            WriteState::Writing { tx, fut } => {
+                // Send EOF to the backend.
+                tx.take();
+                fut
+            }
+            _ => return Poll::Ready(Err(self.closed_error())),
+        };
+        let res = ready!(fut.as_mut().poll(cx));
+        self.state = match &res {
+            Ok(meta) => WriteState::Closed(Box::new(meta.clone())),
+            Err(_) => WriteState::Failed,
         };
+        Poll::Ready(res)
+    }
+
+    fn closed_error(&self) -> Error {
+        Error::Object {
+            kind: Kind::Unexpected,
+            op: "write",
+            path: self.args.path.clone(),
+            context: HashMap::new(),
+            source: anyhow!("writer has been closed or failed"),
+        }
+    }
+}
+
+impl AsyncWrite for Writer {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        if self.buf.len() >= self.buffer_size {
+            ready!(self.poll_send(cx))?;
+        }
+        if !matches!(self.state, WriteState::Idle | WriteState::Writing { .. }) {
+            return Poll::Ready(Err(self.closed_error().into()));
+        }
+
+        let n = buf.len().min(self.buffer_size - self.buf.len());
+        self.buf.extend_from_slice(&buf[..n]);
+        Poll::Ready(Ok(n))
+    }
+
+    /// Send the buffered data to the backend, it will not be committed
+    /// until closed.
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        if self.buf.is_empty() {
+            return Poll::Ready(Ok(()));
+        }
+        self.poll_send(cx).map_err(io::Error::from)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.poll_commit(cx).map_ok(|_| ()).map_err(io::Error::from)
+    }
+}
 
-        self.acc.write(r, op).await
+#[async_trait]
+impl Close for Writer {
+    /// Commit the written data, see [`Writer::close`].
+    async fn close(&mut self) -> Result<()> {
+        Writer::close(self).await.map(|_| ())
     }
 }
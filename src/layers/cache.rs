@@ -0,0 +1,609 @@
+// Copyright 2022 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::fmt::Formatter;
+use std::pin::Pin;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::task::Context;
+use std::task::Poll;
+use std::time::SystemTime;
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use bytes::BytesMut;
+use futures::io::Cursor;
+use futures::ready;
+use futures::Stream;
+use futures::StreamExt;
+use log::debug;
+use log::warn;
+
+use super::rebind;
+use crate::error::Kind;
+use crate::error::Result;
+use crate::io::BytesStream;
+use crate::ops::BatchOperation;
+use crate::ops::BatchResult;
+use crate::ops::ObjectPart;
+use crate::ops::OpAbortMultipart;
+use crate::ops::OpAppend;
+use crate::ops::OpBatch;
+use crate::ops::OpCompleteMultipart;
+use crate::ops::OpCopy;
+use crate::ops::OpCreate;
+use crate::ops::OpCreateMultipart;
+use crate::ops::OpDelete;
+use crate::ops::OpList;
+use crate::ops::OpPresign;
+use crate::ops::OpRead;
+use crate::ops::OpRename;
+use crate::ops::OpScan;
+use crate::ops::OpStat;
+use crate::ops::OpWrite;
+use crate::ops::OpWriteMultipart;
+use crate::ops::PresignedRequest;
+use crate::shutdown::InFlight;
+use crate::Accessor;
+use crate::AccessorMetadata;
+use crate::BoxedAsyncReader;
+use crate::BoxedObjectStream;
+use crate::Close;
+use crate::Layer;
+use crate::Metadata;
+use crate::Operator;
+
+/// Default max size of objects to be cached.
+const DEFAULT_CACHE_MAX_SIZE: u64 = 64 * 1024 * 1024;
+
+/// How [`CacheLayer`] serves reads of a range.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheRange {
+    /// Read ranges from the cache if the object has been cached, otherwise
+    /// from the backend without caching.
+    Bypass,
+    /// Cache the whole object on miss if it's not larger than the max size,
+    /// and read the range from the cache.
+    WholeObject,
+}
+
+/// CacheLayer caches objects read from the backend in another operator,
+/// like a local fs or memory one.
+///
+/// - Reads try the cache first. On miss, they read from the backend while
+///   teeing the bytes into memory, and fill the cache in background once
+///   the whole object has been read.
+/// - Objects larger than [`CacheLayer::max_size`] are never cached.
+/// - Reads of ranges are served by the [`CacheRange`] policy.
+/// - Reads of versions, with conditions or response overrides always go to
+///   the backend.
+/// - Writes, deletes, copies and renames invalidate the cached objects
+///   they touch, after finishing on the backend.
+/// - `stat` always goes to the backend, so does the metadata returned by
+///   `read_with_metadata`.
+///
+/// # Staleness
+///
+/// The cache only sees changes made through this layer. With
+/// [`CacheLayer::validate`], reads stat the backend first, and cached
+/// objects are dropped if their ETag, or last modified time and length
+/// changed. Validators are kept in memory, objects cached by other
+/// processes or before restarts are refetched once while validating.
+///
+/// # Shutdown
+///
+/// CacheLayer is closed by [`Close`], which stops filling the cache and
+/// waits for fills in background, so that objects read before closing
+/// are cached. Clones of the layer share the same state.
+///
+/// # Example
+///
+/// ```
+/// use anyhow::Result;
+/// use opendal::layers::CacheLayer;
+/// use opendal::layers::CacheRange;
+/// use opendal::services::memory;
+/// use opendal::Operator;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<()> {
+///     let cache = Operator::new(memory::Backend::build().finish().await?);
+///     let op = Operator::new(memory::Backend::build().finish().await?).layer(
+///         CacheLayer::new(cache)
+///             .range(CacheRange::WholeObject)
+///             .max_size(16 * 1024 * 1024)
+///             .validate(true),
+///     );
+///
+///     Ok(())
+/// }
+/// ```
+#[derive(Clone)]
+pub struct CacheLayer {
+    cache: Operator,
+    range: CacheRange,
+    max_size: u64,
+    validate: bool,
+    closed: Arc<AtomicBool>,
+    in_flight: Arc<InFlight>,
+}
+
+impl Debug for CacheLayer {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CacheLayer")
+            .field("cache", &self.cache.inner())
+            .field("range", &self.range)
+            .field("max_size", &self.max_size)
+            .field("validate", &self.validate)
+            .finish()
+    }
+}
+
+impl CacheLayer {
+    /// Create a new layer which caches objects in `cache`.
+    pub fn new(cache: Operator) -> Self {
+        Self {
+            cache,
+            range: CacheRange::Bypass,
+            max_size: DEFAULT_CACHE_MAX_SIZE,
+            validate: false,
+            closed: Arc::new(AtomicBool::new(false)),
+            in_flight: Arc::new(InFlight::default()),
+        }
+    }
+
+    /// Set how reads of ranges are served.
+    ///
+    /// Default to [`CacheRange::Bypass`].
+    #[must_use]
+    pub fn range(mut self, range: CacheRange) -> Self {
+        self.range = range;
+        self
+    }
+
+    /// Set the max size of objects to be cached, which are buffered in
+    /// memory while filling the cache.
+    ///
+    /// Default to 64 MiB.
+    #[must_use]
+    pub fn max_size(mut self, size: u64) -> Self {
+        self.max_size = size;
+        self
+    }
+
+    /// Stat the backend before reading from the cache to detect stale
+    /// objects, see [staleness](#staleness) for details.
+    #[must_use]
+    pub fn validate(mut self, enabled: bool) -> Self {
+        self.validate = enabled;
+        self
+    }
+}
+
+impl Layer for CacheLayer {
+    fn layer(&self, inner: Arc<dyn Accessor>) -> Arc<dyn Accessor> {
+        Arc::new(CacheAccessor {
+            inner,
+            state: Arc::new(CacheState {
+                cache: self.cache.inner(),
+                range: self.range,
+                max_size: self.max_size,
+                validate: self.validate,
+                validators: Mutex::new(HashMap::new()),
+                generation: AtomicU64::new(0),
+                closed: self.closed.clone(),
+                in_flight: self.in_flight.clone(),
+            }),
+        })
+    }
+}
+
+#[async_trait]
+impl Close for CacheLayer {
+    /// Stop filling the cache, and wait for fills in background.
+    async fn close(&mut self) -> Result<()> {
+        self.closed.store(true, Ordering::Release);
+        self.in_flight.wait().await;
+        Ok(())
+    }
+}
+
+/// Validator of a cached object, taken from the backend's metadata.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Validator {
+    etag: Option<String>,
+    last_modified: Option<SystemTime>,
+    content_length: u64,
+}
+
+impl Validator {
+    fn new(meta: &Metadata) -> Self {
+        Self {
+            etag: meta.etag(),
+            last_modified: meta.last_modified(),
+            content_length: meta.content_length(),
+        }
+    }
+
+    fn matches(&self, meta: &Metadata) -> bool {
+        match (&self.etag, meta.etag()) {
+            (Some(a), Some(b)) => *a == b,
+            _ => {
+                self.last_modified.is_some()
+                    && self.last_modified == meta.last_modified()
+                    && self.content_length == meta.content_length()
+            }
+        }
+    }
+}
+
+struct CacheState {
+    cache: Arc<dyn Accessor>,
+    range: CacheRange,
+    max_size: u64,
+    validate: bool,
+    validators: Mutex<HashMap<String, Validator>>,
+    /// Bumped by every invalidation, so that fills started before it
+    /// don't bring stale objects back.
+    generation: AtomicU64,
+    closed: Arc<AtomicBool>,
+    in_flight: Arc<InFlight>,
+}
+
+impl Debug for CacheState {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CacheState")
+            .field("cache", &self.cache)
+            .field("range", &self.range)
+            .field("max_size", &self.max_size)
+            .field("validate", &self.validate)
+            .finish()
+    }
+}
+
+impl CacheState {
+    /// Whether the cached object is still fresh according to the backend's
+    /// metadata.
+    fn is_fresh(&self, path: &str, meta: &Metadata) -> bool {
+        let validators = self.validators.lock().expect("lock must not be poisoned");
+        validators.get(path).is_some_and(|v| v.matches(meta))
+    }
+
+    /// Read from the cache, returns `None` on miss.
+    async fn read_cache(&self, args: &OpRead) -> Option<BytesStream> {
+        let op = OpRead {
+            path: args.path.clone(),
+            range: args.range,
+            ..Default::default()
+        };
+        match self.cache.read(&op).await {
+            Ok(s) => {
+                debug!("cache {} hit", args.path);
+                Some(s)
+            }
+            Err(err) if err.kind() == Kind::ObjectNotExist => None,
+            Err(err) => {
+                warn!("cache {} read failed: {}", args.path, err);
+                None
+            }
+        }
+    }
+
+    /// Write the whole object into the cache, unless it has been
+    /// invalidated since `generation`.
+    async fn fill(&self, path: &str, bs: Bytes, meta: &Metadata, generation: u64) {
+        if self.generation.load(Ordering::Acquire) != generation {
+            return;
+        }
+
+        let op = OpWrite::new(path, bs.len() as u64);
+        if let Err(err) = self.cache.write(Box::new(Cursor::new(bs)), &op).await {
+            warn!("cache {} fill failed: {}", path, err);
+            return;
+        }
+
+        let fresh = {
+            let mut validators = self.validators.lock().expect("lock must not be poisoned");
+            let fresh = self.generation.load(Ordering::Acquire) == generation;
+            if fresh {
+                validators.insert(path.to_string(), Validator::new(meta));
+            }
+            fresh
+        };
+        // Invalidated while filling, the object could be stale.
+        if !fresh {
+            self.remove(path).await;
+        }
+    }
+
+    /// Fill the cache in background, unless closed.
+    fn spawn_fill(self: &Arc<Self>, path: String, bs: Bytes, meta: Metadata, generation: u64) {
+        if self.closed.load(Ordering::Acquire) {
+            return;
+        }
+
+        let handle = match tokio::runtime::Handle::try_current() {
+            Ok(handle) => handle,
+            Err(_) => return,
+        };
+
+        let state = self.clone();
+        let token = self.in_flight.enter();
+        handle.spawn(async move {
+            state.fill(&path, bs, &meta, generation).await;
+            drop(token);
+        });
+    }
+
+    async fn invalidate(&self, path: &str) {
+        {
+            let mut validators = self.validators.lock().expect("lock must not be poisoned");
+            self.generation.fetch_add(1, Ordering::AcqRel);
+            validators.remove(path);
+        }
+        self.remove(path).await;
+    }
+
+    async fn remove(&self, path: &str) {
+        match self.cache.delete(&OpDelete::new(path)).await {
+            Ok(()) => {}
+            Err(err) if err.kind() == Kind::ObjectNotExist => {}
+            Err(err) => warn!("cache {} invalidate failed: {}", path, err),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct CacheAccessor {
+    inner: Arc<dyn Accessor>,
+    state: Arc<CacheState>,
+}
+
+impl CacheAccessor {
+    /// Reads of versions, with conditions or overrides can't be served by
+    /// the cache.
+    fn cacheable(args: &OpRead) -> bool {
+        args.version.is_none()
+            && args.if_match.is_none()
+            && args.if_none_match.is_none()
+            && args.if_modified_since.is_none()
+            && args.if_unmodified_since.is_none()
+            && args.response_overrides == Default::default()
+            && !args.raw_headers
+    }
+
+    /// Stat the backend and drop the cached object if it's stale.
+    ///
+    /// Returns the metadata if the cached object is fresh.
+    async fn check_fresh(&self, path: &str) -> Result<Option<Metadata>> {
+        let meta = self.inner.stat(&OpStat::new(path)).await?;
+        if self.state.is_fresh(path, &meta) {
+            return Ok(Some(meta));
+        }
+        self.state.invalidate(path).await;
+        Ok(None)
+    }
+
+    /// Read the whole object from the backend, and fill the cache with it
+    /// once consumed.
+    async fn read_through(&self, args: &OpRead) -> Result<BytesStream> {
+        let generation = self.state.generation.load(Ordering::Acquire);
+        let (s, meta) = self.inner.read_with_metadata(args).await?;
+        if meta.content_length() > self.state.max_size {
+            return Ok(s);
+        }
+
+        Ok(Box::new(TeeStream {
+            inner: s,
+            buf: Some(BytesMut::with_capacity(meta.content_length() as usize)),
+            path: args.path.clone(),
+            meta,
+            generation,
+            state: self.state.clone(),
+        }))
+    }
+
+    /// Read the whole object from the backend into the cache, and then the
+    /// range from the cache.
+    async fn read_whole(&self, args: &OpRead, meta: Metadata) -> Result<BytesStream> {
+        if meta.content_length() > self.state.max_size {
+            return self.inner.read(args).await;
+        }
+
+        let generation = self.state.generation.load(Ordering::Acquire);
+        let op = OpRead {
+            path: args.path.clone(),
+            ..Default::default()
+        };
+        let mut s = self.inner.read(&op).await?;
+        let mut buf = BytesMut::with_capacity(meta.content_length() as usize);
+        while let Some(bs) = s.next().await {
+            buf.extend_from_slice(&bs?);
+        }
+        self.state
+            .fill(&args.path, buf.freeze(), &meta, generation)
+            .await;
+
+        match self.state.read_cache(args).await {
+            Some(s) => Ok(s),
+            None => self.inner.read(args).await,
+        }
+    }
+}
+
+/// TeeStream buffers the bytes of the whole object, and fills the cache
+/// once the object has been read completely.
+struct TeeStream {
+    inner: BytesStream,
+    /// `None` once the bytes can't be cached, like errors.
+    buf: Option<BytesMut>,
+    path: String,
+    meta: Metadata,
+    generation: u64,
+    state: Arc<CacheState>,
+}
+
+impl Stream for TeeStream {
+    type Item = Result<Bytes>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let v = ready!(self.inner.poll_next_unpin(cx));
+        match &v {
+            Some(Ok(bs)) => {
+                if let Some(buf) = &mut self.buf {
+                    buf.extend_from_slice(bs);
+                }
+            }
+            Some(Err(_)) => self.buf = None,
+            None => {
+                if let Some(buf) = self.buf.take() {
+                    // Only cache objects that have been read completely.
+                    if buf.len() as u64 == self.meta.content_length() {
+                        self.state.spawn_fill(
+                            self.path.clone(),
+                            buf.freeze(),
+                            self.meta.clone(),
+                            self.generation,
+                        );
+                    }
+                }
+            }
+        }
+        Poll::Ready(v)
+    }
+}
+
+#[async_trait]
+impl Accessor for CacheAccessor {
+    async fn read(&self, args: &OpRead) -> Result<BytesStream> {
+        if !Self::cacheable(args) {
+            return self.inner.read(args).await;
+        }
+
+        let mut meta = None;
+        if self.state.validate {
+            meta = self.check_fresh(&args.path).await?;
+        }
+        if !self.state.validate || meta.is_some() {
+            if let Some(s) = self.state.read_cache(args).await {
+                return Ok(s);
+            }
+        }
+
+        if args.range.is_full() {
+            return self.read_through(args).await;
+        }
+        match self.state.range {
+            CacheRange::Bypass => self.inner.read(args).await,
+            CacheRange::WholeObject => {
+                let meta = match meta {
+                    Some(meta) => meta,
+                    None => self.inner.stat(&OpStat::new(&args.path)).await?,
+                };
+                self.read_whole(args, meta).await
+            }
+        }
+    }
+    async fn read_mmap(&self, args: &OpRead) -> Result<Bytes> {
+        self.inner.read_mmap(args).await
+    }
+    async fn write(&self, r: BoxedAsyncReader, args: &OpWrite) -> Result<Metadata> {
+        let result = self.inner.write(r, args).await;
+        self.state.invalidate(&args.path).await;
+        result
+    }
+    async fn append(&self, r: BoxedAsyncReader, args: &OpAppend) -> Result<Metadata> {
+        let result = self.inner.append(r, args).await;
+        self.state.invalidate(&args.path).await;
+        result
+    }
+    async fn create(&self, args: &OpCreate) -> Result<Metadata> {
+        let result = self.inner.create(args).await;
+        self.state.invalidate(&args.path).await;
+        result
+    }
+    async fn stat(&self, args: &OpStat) -> Result<Metadata> {
+        let meta = self.inner.stat(args).await?;
+        if self.state.validate && args.version.is_none() && !self.state.is_fresh(&args.path, &meta)
+        {
+            self.state.invalidate(&args.path).await;
+        }
+        Ok(meta)
+    }
+    async fn delete(&self, args: &OpDelete) -> Result<()> {
+        let result = self.inner.delete(args).await;
+        self.state.invalidate(&args.path).await;
+        result
+    }
+    async fn batch(&self, args: &OpBatch) -> Result<Vec<(String, BatchResult)>> {
+        let result = self.inner.batch(args).await;
+        for op in &args.ops {
+            if let BatchOperation::Delete(v) = op {
+                self.state.invalidate(&v.path).await;
+            }
+        }
+        result
+    }
+    async fn copy(&self, args: &OpCopy) -> Result<Metadata> {
+        let result = self.inner.copy(args).await;
+        self.state.invalidate(&args.dst).await;
+        result
+    }
+    async fn rename(&self, args: &OpRename) -> Result<Metadata> {
+        let result = self.inner.rename(args).await;
+        self.state.invalidate(&args.src).await;
+        self.state.invalidate(&args.dst).await;
+        result
+    }
+    fn presign(&self, args: &OpPresign) -> Result<PresignedRequest> {
+        self.inner.presign(args)
+    }
+    async fn create_multipart(&self, args: &OpCreateMultipart) -> Result<String> {
+        self.inner.create_multipart(args).await
+    }
+    async fn write_multipart(
+        &self,
+        r: BoxedAsyncReader,
+        args: &OpWriteMultipart,
+    ) -> Result<ObjectPart> {
+        self.inner.write_multipart(r, args).await
+    }
+    async fn complete_multipart(&self, args: &OpCompleteMultipart) -> Result<Metadata> {
+        let result = self.inner.complete_multipart(args).await;
+        self.state.invalidate(&args.path).await;
+        result
+    }
+    async fn abort_multipart(&self, args: &OpAbortMultipart) -> Result<()> {
+        self.inner.abort_multipart(args).await
+    }
+    async fn list(&self, args: &OpList) -> Result<BoxedObjectStream> {
+        let obs = self.inner.list(args).await?;
+
+        Ok(rebind(obs, Arc::new(self.clone())))
+    }
+    async fn scan(&self, args: &OpScan) -> Result<BoxedObjectStream> {
+        let obs = self.inner.scan(args).await?;
+
+        Ok(rebind(obs, Arc::new(self.clone())))
+    }
+
+    fn metadata(&self) -> AccessorMetadata {
+        self.inner.metadata()
+    }
+}
@@ -0,0 +1,478 @@
+// Copyright 2022 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::task::Context;
+use std::task::Poll;
+
+use anyhow::anyhow;
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures::ready;
+use futures::Stream;
+use futures::StreamExt;
+use rand::rngs::StdRng;
+use rand::Rng;
+use rand::SeedableRng;
+
+use super::rebind;
+use crate::error::Error;
+use crate::error::Kind;
+use crate::error::Result;
+use crate::io::BytesStream;
+use crate::ops::BatchResult;
+use crate::ops::ObjectPart;
+use crate::ops::OpAbortMultipart;
+use crate::ops::OpAppend;
+use crate::ops::OpBatch;
+use crate::ops::OpCompleteMultipart;
+use crate::ops::OpCopy;
+use crate::ops::OpCreate;
+use crate::ops::OpCreateMultipart;
+use crate::ops::OpDelete;
+use crate::ops::OpList;
+use crate::ops::OpPresign;
+use crate::ops::OpRead;
+use crate::ops::OpRename;
+use crate::ops::OpScan;
+use crate::ops::OpStat;
+use crate::ops::OpWrite;
+use crate::ops::OpWriteMultipart;
+use crate::ops::PresignedRequest;
+use crate::Accessor;
+use crate::AccessorMetadata;
+use crate::BoxedAsyncReader;
+use crate::BoxedObjectStream;
+use crate::Layer;
+use crate::Metadata;
+
+/// ChaosLayer injects failures into operations, for testing how
+/// applications handle them.
+///
+/// Faults are decided by a random number generator seeded by the given
+/// seed, so that a sequence of operations fails the same way in every run.
+/// Concurrent operations draw in the order they are called, which could
+/// differ between runs.
+///
+/// Injected errors carry context `chaos` as `injected`. Faults are:
+///
+/// - [`ChaosLayer::error_rate`]: operations fail with
+///   `Kind::ServiceUnavailable` before being sent.
+/// - [`ChaosLayer::read_cut`]: read streams fail with
+///   `Kind::ObjectReadInterrupted` after some bytes.
+/// - [`ChaosLayer::write_timeout_rate`]: writes fail with `Kind::Timeout`
+///   before being sent.
+/// - [`ChaosLayer::stale_stat_rate`]: stat returns the metadata of the
+///   object when it was first stat-ed through the layer.
+///
+/// Requires the `chaos` feature.
+///
+/// # Example
+///
+/// ```
+/// use anyhow::Result;
+/// use opendal::layers::ChaosLayer;
+/// use opendal::services::memory;
+/// use opendal::Operator;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<()> {
+///     let op = Operator::new(memory::Backend::build().finish().await?).layer(
+///         ChaosLayer::new(42)
+///             .error_rate("read", 0.1)
+///             .read_cut(0.05, 1024),
+///     );
+///
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct ChaosLayer {
+    seed: u64,
+    error_rates: HashMap<&'static str, f64>,
+    read_cut: (f64, u64),
+    write_timeout_rate: f64,
+    stale_stat_rate: f64,
+}
+
+impl ChaosLayer {
+    /// Create a new layer which injects nothing until faults are set.
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            error_rates: HashMap::new(),
+            read_cut: (0.0, 0),
+            write_timeout_rate: 0.0,
+            stale_stat_rate: 0.0,
+        }
+    }
+
+    /// Fail `rate` of the operation with a retryable error, `op` is the name
+    /// of the operation in errors, like `read`, `stat` or `list`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `rate` is not in `[0.0, 1.0]`.
+    #[must_use]
+    pub fn error_rate(mut self, op: &'static str, rate: f64) -> Self {
+        check_rate(rate);
+        self.error_rates.insert(op, rate);
+        self
+    }
+
+    /// Cut `rate` of read streams after `after` bytes.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `rate` is not in `[0.0, 1.0]`.
+    #[must_use]
+    pub fn read_cut(mut self, rate: f64, after: u64) -> Self {
+        check_rate(rate);
+        self.read_cut = (rate, after);
+        self
+    }
+
+    /// Fail `rate` of writes with timeout.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `rate` is not in `[0.0, 1.0]`.
+    #[must_use]
+    pub fn write_timeout_rate(mut self, rate: f64) -> Self {
+        check_rate(rate);
+        self.write_timeout_rate = rate;
+        self
+    }
+
+    /// Return stale metadata for `rate` of stats.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `rate` is not in `[0.0, 1.0]`.
+    #[must_use]
+    pub fn stale_stat_rate(mut self, rate: f64) -> Self {
+        check_rate(rate);
+        self.stale_stat_rate = rate;
+        self
+    }
+}
+
+fn check_rate(rate: f64) {
+    assert!((0.0..=1.0).contains(&rate), "rate must be in [0.0, 1.0]");
+}
+
+impl Layer for ChaosLayer {
+    fn layer(&self, inner: Arc<dyn Accessor>) -> Arc<dyn Accessor> {
+        Arc::new(ChaosAccessor {
+            inner,
+            state: Arc::new(ChaosState {
+                layer: self.clone(),
+                rng: Mutex::new(StdRng::seed_from_u64(self.seed)),
+                stats: Mutex::new(HashMap::new()),
+            }),
+        })
+    }
+}
+
+#[derive(Debug)]
+struct ChaosState {
+    layer: ChaosLayer,
+    rng: Mutex<StdRng>,
+    /// Metadata of objects when they were first stat-ed.
+    stats: Mutex<HashMap<String, Metadata>>,
+}
+
+impl ChaosState {
+    fn roll(&self, rate: f64) -> bool {
+        if rate <= 0.0 {
+            return false;
+        }
+        let mut rng = self.rng.lock().expect("lock must not be poisoned");
+        rng.gen_bool(rate)
+    }
+
+    /// Fail the operation with a retryable error by its error rate.
+    fn check(&self, op: &'static str, path: &str) -> Result<()> {
+        let rate = self.layer.error_rates.get(op).copied().unwrap_or_default();
+        if self.roll(rate) {
+            return Err(injected(Kind::ServiceUnavailable, op, path));
+        }
+        Ok(())
+    }
+
+    fn check_write(&self, op: &'static str, path: &str) -> Result<()> {
+        self.check(op, path)?;
+        if self.roll(self.layer.write_timeout_rate) {
+            return Err(injected(Kind::Timeout, op, path));
+        }
+        Ok(())
+    }
+
+    /// Cut the stream after some bytes by the read cut rate.
+    fn cut(&self, path: &str, s: BytesStream) -> BytesStream {
+        let (rate, after) = self.layer.read_cut;
+        if !self.roll(rate) {
+            return s;
+        }
+        Box::new(CutStream {
+            inner: s,
+            path: path.to_string(),
+            rest: after,
+            done: false,
+        })
+    }
+}
+
+fn injected(kind: Kind, op: &'static str, path: &str) -> Error {
+    Error::Object {
+        kind,
+        op,
+        path: path.to_string(),
+        context: HashMap::from([("chaos".to_string(), "injected".to_string())]),
+        source: anyhow!("{} injected by chaos layer", kind),
+    }
+}
+
+/// CutStream yields `rest` bytes of the inner stream, and then fails.
+struct CutStream {
+    inner: BytesStream,
+    path: String,
+    rest: u64,
+    done: bool,
+}
+
+impl Stream for CutStream {
+    type Item = Result<Bytes>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if self.done {
+            return Poll::Ready(None);
+        }
+        if self.rest == 0 {
+            self.done = true;
+            return Poll::Ready(Some(Err(injected(
+                Kind::ObjectReadInterrupted,
+                "read",
+                &self.path,
+            ))));
+        }
+
+        match ready!(self.inner.poll_next_unpin(cx)) {
+            Some(Ok(mut bs)) => {
+                if bs.len() as u64 > self.rest {
+                    bs.truncate(self.rest as usize);
+                }
+                self.rest -= bs.len() as u64;
+                Poll::Ready(Some(Ok(bs)))
+            }
+            v => {
+                self.done = true;
+                Poll::Ready(v)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct ChaosAccessor {
+    inner: Arc<dyn Accessor>,
+    state: Arc<ChaosState>,
+}
+
+#[async_trait]
+impl Accessor for ChaosAccessor {
+    async fn read(&self, args: &OpRead) -> Result<BytesStream> {
+        self.state.check("read", &args.path)?;
+        let s = self.inner.read(args).await?;
+        Ok(self.state.cut(&args.path, s))
+    }
+    async fn read_mmap(&self, args: &OpRead) -> Result<Bytes> {
+        self.state.check("read", &args.path)?;
+        self.inner.read_mmap(args).await
+    }
+    async fn read_with_metadata(&self, args: &OpRead) -> Result<(BytesStream, Metadata)> {
+        self.state.check("read", &args.path)?;
+        let (s, meta) = self.inner.read_with_metadata(args).await?;
+        Ok((self.state.cut(&args.path, s), meta))
+    }
+    async fn write(&self, r: BoxedAsyncReader, args: &OpWrite) -> Result<Metadata> {
+        self.state.check_write("write", &args.path)?;
+        self.inner.write(r, args).await
+    }
+    async fn append(&self, r: BoxedAsyncReader, args: &OpAppend) -> Result<Metadata> {
+        self.state.check_write("append", &args.path)?;
+        self.inner.append(r, args).await
+    }
+    async fn create(&self, args: &OpCreate) -> Result<Metadata> {
+        self.state.check("create", &args.path)?;
+        self.inner.create(args).await
+    }
+    async fn stat(&self, args: &OpStat) -> Result<Metadata> {
+        self.state.check("stat", &args.path)?;
+        let meta = self.inner.stat(args).await?;
+
+        let mut stats = self.state.stats.lock().expect("lock must not be poisoned");
+        let first = stats
+            .entry(args.path.clone())
+            .or_insert_with(|| meta.clone())
+            .clone();
+        drop(stats);
+        if self.state.roll(self.state.layer.stale_stat_rate) {
+            return Ok(first);
+        }
+        Ok(meta)
+    }
+    async fn delete(&self, args: &OpDelete) -> Result<()> {
+        self.state.check("delete", &args.path)?;
+        self.inner.delete(args).await
+    }
+    async fn batch(&self, args: &OpBatch) -> Result<Vec<(String, BatchResult)>> {
+        let path = args.ops.first().map(|v| v.path()).unwrap_or_default();
+        self.state.check("batch", path)?;
+        self.inner.batch(args).await
+    }
+    async fn copy(&self, args: &OpCopy) -> Result<Metadata> {
+        self.state.check("copy", &args.src)?;
+        self.inner.copy(args).await
+    }
+    async fn rename(&self, args: &OpRename) -> Result<Metadata> {
+        self.state.check("rename", &args.src)?;
+        self.inner.rename(args).await
+    }
+    fn presign(&self, args: &OpPresign) -> Result<PresignedRequest> {
+        self.inner.presign(args)
+    }
+    async fn create_multipart(&self, args: &OpCreateMultipart) -> Result<String> {
+        self.state.check("create_multipart", &args.path)?;
+        self.inner.create_multipart(args).await
+    }
+    async fn write_multipart(
+        &self,
+        r: BoxedAsyncReader,
+        args: &OpWriteMultipart,
+    ) -> Result<ObjectPart> {
+        self.state.check_write("write_multipart", &args.path)?;
+        self.inner.write_multipart(r, args).await
+    }
+    async fn complete_multipart(&self, args: &OpCompleteMultipart) -> Result<Metadata> {
+        self.state.check("complete_multipart", &args.path)?;
+        self.inner.complete_multipart(args).await
+    }
+    async fn abort_multipart(&self, args: &OpAbortMultipart) -> Result<()> {
+        self.state.check("abort_multipart", &args.path)?;
+        self.inner.abort_multipart(args).await
+    }
+    async fn list(&self, args: &OpList) -> Result<BoxedObjectStream> {
+        self.state.check("list", &args.path)?;
+        let obs = self.inner.list(args).await?;
+
+        Ok(rebind(obs, Arc::new(self.clone())))
+    }
+    async fn scan(&self, args: &OpScan) -> Result<BoxedObjectStream> {
+        self.state.check("scan", &args.path)?;
+        let obs = self.inner.scan(args).await?;
+
+        Ok(rebind(obs, Arc::new(self.clone())))
+    }
+
+    fn metadata(&self) -> AccessorMetadata {
+        self.inner.metadata()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::AsyncReadExt;
+
+    use super::*;
+    use crate::services::memory;
+    use crate::Operator;
+
+    #[tokio::test]
+    async fn test_chaos_layer_reproducible() -> Result<()> {
+        let inner = memory::Backend::build().finish().await?;
+        Operator::new(inner.clone())
+            .object("file")
+            .writer()
+            .write_bytes(vec![0; 16])
+            .await?;
+
+        let outcomes = |seed: u64| {
+            let op =
+                Operator::new(inner.clone()).layer(ChaosLayer::new(seed).error_rate("stat", 0.5));
+            async move {
+                let mut v = Vec::new();
+                for _ in 0..64 {
+                    v.push(op.object("file").metadata().await.is_ok());
+                }
+                v
+            }
+        };
+
+        let first = outcomes(7).await;
+        assert_eq!(first, outcomes(7).await);
+        assert!(first.iter().any(|v| *v) && first.iter().any(|v| !*v));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_chaos_layer_faults() -> Result<()> {
+        let inner = memory::Backend::build().finish().await?;
+        let op = Operator::new(inner.clone()).layer(
+            ChaosLayer::new(0)
+                .read_cut(1.0, 5)
+                .write_timeout_rate(1.0)
+                .stale_stat_rate(1.0),
+        );
+        let raw = Operator::new(inner);
+
+        // Writes time out before being sent.
+        let err = op
+            .object("file")
+            .writer()
+            .write_bytes(vec![0; 16])
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), Kind::Timeout);
+        assert_eq!(err.context()["chaos"], "injected");
+        assert!(!raw.object("file").is_exist().await?);
+
+        // Streams are cut in the middle.
+        raw.object("file").writer().write_bytes(vec![0; 16]).await?;
+        let mut buf = Vec::new();
+        let err = op
+            .object("file")
+            .reader()
+            .read_to_end(&mut buf)
+            .await
+            .unwrap_err();
+        assert_eq!(buf.len(), 5);
+        let err = err
+            .into_inner()
+            .and_then(|e| e.downcast::<Error>().ok())
+            .expect("must be an error of opendal");
+        assert_eq!(err.kind(), Kind::ObjectReadInterrupted);
+
+        // Stats return the first seen metadata.
+        assert_eq!(op.object("file").metadata().await?.content_length(), 16);
+        raw.object("file").writer().write_bytes(vec![0; 8]).await?;
+        assert_eq!(op.object("file").metadata().await?.content_length(), 16);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,252 @@
+// Copyright 2022 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use tokio::sync::OwnedSemaphorePermit;
+use tokio::sync::Semaphore;
+
+use super::rebind;
+use super::PermitStream;
+use crate::error::Result;
+use crate::io::BytesStream;
+use crate::ops::BatchResult;
+use crate::ops::ObjectPart;
+use crate::ops::OpAbortMultipart;
+use crate::ops::OpAppend;
+use crate::ops::OpBatch;
+use crate::ops::OpCompleteMultipart;
+use crate::ops::OpCopy;
+use crate::ops::OpCreate;
+use crate::ops::OpCreateMultipart;
+use crate::ops::OpDelete;
+use crate::ops::OpList;
+use crate::ops::OpPresign;
+use crate::ops::OpRead;
+use crate::ops::OpRename;
+use crate::ops::OpScan;
+use crate::ops::OpStat;
+use crate::ops::OpWrite;
+use crate::ops::OpWriteMultipart;
+use crate::ops::PresignedRequest;
+use crate::Accessor;
+use crate::AccessorMetadata;
+use crate::BoxedAsyncReader;
+use crate::BoxedObjectStream;
+use crate::Layer;
+use crate::Metadata;
+
+/// ConcurrencyLimitLayer limits the count of in-flight operations of an
+/// operator, extra operations wait for permits in order.
+///
+/// Operations hold their permits until finished. For `read`, permits are
+/// held until the returned stream is exhausted or dropped, since the stream
+/// keeps the connection. For `list` and `scan`, permits are released once
+/// the first entry is listed, so that operations on listed objects don't
+/// wait for the listing.
+///
+/// Reads (`read`, `stat`, `list` and `scan`) and writes (all the others)
+/// are limited separately, so that a reader could be piped into a writer.
+/// Use [`ConcurrencyLimitLayer::with_limits`] to set different limits.
+/// `presign` sends no request and is never limited.
+///
+/// Every operator wrapped by the layer has its own limits.
+///
+/// # Example
+///
+/// ```
+/// use anyhow::Result;
+/// use opendal::layers::ConcurrencyLimitLayer;
+/// use opendal::services::memory;
+/// use opendal::Operator;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<()> {
+///     let op = Operator::new(memory::Backend::build().finish().await?)
+///         .layer(ConcurrencyLimitLayer::with_limits(256, 32));
+///
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct ConcurrencyLimitLayer {
+    read_limit: usize,
+    write_limit: usize,
+}
+
+impl ConcurrencyLimitLayer {
+    /// Create a new layer which allows at most `limit` reads and `limit`
+    /// writes at the same time.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `limit` is 0.
+    pub fn new(limit: usize) -> Self {
+        Self::with_limits(limit, limit)
+    }
+
+    /// Create a new layer which allows at most `read_limit` reads and
+    /// `write_limit` writes at the same time.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any limit is 0.
+    pub fn with_limits(read_limit: usize, write_limit: usize) -> Self {
+        assert!(
+            read_limit > 0 && write_limit > 0,
+            "limits must be at least 1"
+        );
+
+        Self {
+            read_limit,
+            write_limit,
+        }
+    }
+}
+
+impl Layer for ConcurrencyLimitLayer {
+    fn layer(&self, inner: Arc<dyn Accessor>) -> Arc<dyn Accessor> {
+        Arc::new(ConcurrencyLimitAccessor {
+            inner,
+            read: Arc::new(Semaphore::new(self.read_limit)),
+            write: Arc::new(Semaphore::new(self.write_limit)),
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+struct ConcurrencyLimitAccessor {
+    inner: Arc<dyn Accessor>,
+    read: Arc<Semaphore>,
+    write: Arc<Semaphore>,
+}
+
+impl ConcurrencyLimitAccessor {
+    async fn acquire_read(&self) -> OwnedSemaphorePermit {
+        acquire(&self.read).await
+    }
+
+    async fn acquire_write(&self) -> OwnedSemaphorePermit {
+        acquire(&self.write).await
+    }
+
+    /// Hold the permit until the first entry is listed, and keep listed
+    /// objects behind this layer.
+    fn list_stream(
+        &self,
+        obs: BoxedObjectStream,
+        permit: OwnedSemaphorePermit,
+    ) -> BoxedObjectStream {
+        let obs = PermitStream::until_first(obs, permit);
+        rebind(Box::new(obs), Arc::new(self.clone()))
+    }
+}
+
+async fn acquire(semaphore: &Arc<Semaphore>) -> OwnedSemaphorePermit {
+    semaphore
+        .clone()
+        .acquire_owned()
+        .await
+        .expect("semaphore must not be closed")
+}
+
+#[async_trait]
+impl Accessor for ConcurrencyLimitAccessor {
+    async fn read(&self, args: &OpRead) -> Result<BytesStream> {
+        let permit = self.acquire_read().await;
+        let s = self.inner.read(args).await?;
+        Ok(Box::new(PermitStream::new(s, permit)))
+    }
+    async fn read_mmap(&self, args: &OpRead) -> Result<Bytes> {
+        let _permit = self.acquire_read().await;
+        self.inner.read_mmap(args).await
+    }
+    async fn read_with_metadata(&self, args: &OpRead) -> Result<(BytesStream, Metadata)> {
+        let permit = self.acquire_read().await;
+        let (s, meta) = self.inner.read_with_metadata(args).await?;
+        Ok((Box::new(PermitStream::new(s, permit)), meta))
+    }
+    async fn write(&self, r: BoxedAsyncReader, args: &OpWrite) -> Result<Metadata> {
+        let _permit = self.acquire_write().await;
+        self.inner.write(r, args).await
+    }
+    async fn append(&self, r: BoxedAsyncReader, args: &OpAppend) -> Result<Metadata> {
+        let _permit = self.acquire_write().await;
+        self.inner.append(r, args).await
+    }
+    async fn create(&self, args: &OpCreate) -> Result<Metadata> {
+        let _permit = self.acquire_write().await;
+        self.inner.create(args).await
+    }
+    async fn stat(&self, args: &OpStat) -> Result<Metadata> {
+        let _permit = self.acquire_read().await;
+        self.inner.stat(args).await
+    }
+    async fn delete(&self, args: &OpDelete) -> Result<()> {
+        let _permit = self.acquire_write().await;
+        self.inner.delete(args).await
+    }
+    async fn batch(&self, args: &OpBatch) -> Result<Vec<(String, BatchResult)>> {
+        let _permit = self.acquire_write().await;
+        self.inner.batch(args).await
+    }
+    async fn copy(&self, args: &OpCopy) -> Result<Metadata> {
+        let _permit = self.acquire_write().await;
+        self.inner.copy(args).await
+    }
+    async fn rename(&self, args: &OpRename) -> Result<Metadata> {
+        let _permit = self.acquire_write().await;
+        self.inner.rename(args).await
+    }
+    // No request is sent by presign, no permit is required.
+    fn presign(&self, args: &OpPresign) -> Result<PresignedRequest> {
+        self.inner.presign(args)
+    }
+    async fn create_multipart(&self, args: &OpCreateMultipart) -> Result<String> {
+        let _permit = self.acquire_write().await;
+        self.inner.create_multipart(args).await
+    }
+    async fn write_multipart(
+        &self,
+        r: BoxedAsyncReader,
+        args: &OpWriteMultipart,
+    ) -> Result<ObjectPart> {
+        let _permit = self.acquire_write().await;
+        self.inner.write_multipart(r, args).await
+    }
+    async fn complete_multipart(&self, args: &OpCompleteMultipart) -> Result<Metadata> {
+        let _permit = self.acquire_write().await;
+        self.inner.complete_multipart(args).await
+    }
+    async fn abort_multipart(&self, args: &OpAbortMultipart) -> Result<()> {
+        let _permit = self.acquire_write().await;
+        self.inner.abort_multipart(args).await
+    }
+    async fn list(&self, args: &OpList) -> Result<BoxedObjectStream> {
+        let permit = self.acquire_read().await;
+        let obs = self.inner.list(args).await?;
+        Ok(self.list_stream(obs, permit))
+    }
+    async fn scan(&self, args: &OpScan) -> Result<BoxedObjectStream> {
+        let permit = self.acquire_read().await;
+        let obs = self.inner.scan(args).await?;
+        Ok(self.list_stream(obs, permit))
+    }
+
+    fn metadata(&self) -> AccessorMetadata {
+        self.inner.metadata()
+    }
+}
@@ -0,0 +1,500 @@
+// Copyright 2022 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::task::Context;
+use std::task::Poll;
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures::Stream;
+use futures::StreamExt;
+use http::Method;
+use metrics::gauge;
+use serde::Serialize;
+
+use super::rebind;
+use crate::error::Result;
+use crate::http_util::sync_with_request_hook;
+use crate::http_util::with_request_hook;
+use crate::http_util::RequestHook;
+use crate::io::BytesStream;
+use crate::ops::BatchResult;
+use crate::ops::ObjectPart;
+use crate::ops::OpAbortMultipart;
+use crate::ops::OpAppend;
+use crate::ops::OpBatch;
+use crate::ops::OpCompleteMultipart;
+use crate::ops::OpCopy;
+use crate::ops::OpCreate;
+use crate::ops::OpCreateMultipart;
+use crate::ops::OpDelete;
+use crate::ops::OpList;
+use crate::ops::OpPresign;
+use crate::ops::OpRead;
+use crate::ops::OpRename;
+use crate::ops::OpScan;
+use crate::ops::OpStat;
+use crate::ops::OpWrite;
+use crate::ops::OpWriteMultipart;
+use crate::ops::PresignedRequest;
+use crate::readers::CallbackReader;
+use crate::Accessor;
+use crate::AccessorMetadata;
+use crate::BoxedAsyncReader;
+use crate::BoxedObjectStream;
+use crate::Layer;
+use crate::Metadata;
+
+const GIB: u128 = 1024 * 1024 * 1024;
+
+/// Prices used by [`CostLayer`] to estimate cost, all in nano dollars
+/// (`1_000_000_000` is one dollar).
+///
+/// Requests are classified like S3 does:
+///
+/// - Class A: `PUT`, `COPY`, `POST` and `LIST` requests.
+/// - Class B: `GET`, `HEAD`, `SELECT` and all other requests.
+/// - Free: `DELETE` requests.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PriceTable {
+    /// Price of 1000 class A requests.
+    pub class_a_per_1k: u64,
+    /// Price of 1000 class B requests.
+    pub class_b_per_1k: u64,
+    /// Price of 1 GiB read from the storage.
+    pub egress_per_gib: u64,
+    /// Price of 1 GiB written to the storage.
+    pub ingress_per_gib: u64,
+}
+
+impl PriceTable {
+    /// Prices of AWS S3 Standard storage in us-east-1.
+    pub const AWS_S3_STANDARD: PriceTable = PriceTable {
+        class_a_per_1k: 5_000_000,
+        class_b_per_1k: 400_000,
+        egress_per_gib: 90_000_000,
+        ingress_per_gib: 0,
+    };
+
+    /// Estimate the cost of `usage` in nano dollars.
+    ///
+    /// Every item is rounded down separately.
+    pub fn cost(&self, usage: &Usage) -> u64 {
+        let v = usage.class_a as u128 * self.class_a_per_1k as u128 / 1000
+            + usage.class_b as u128 * self.class_b_per_1k as u128 / 1000
+            + usage.bytes_read as u128 * self.egress_per_gib as u128 / GIB
+            + usage.bytes_written as u128 * self.ingress_per_gib as u128 / GIB;
+        v.min(u64::MAX as u128) as u64
+    }
+}
+
+impl Default for PriceTable {
+    fn default() -> Self {
+        Self::AWS_S3_STANDARD
+    }
+}
+
+/// Requests and bytes counted by [`CostLayer`].
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Usage {
+    /// Count of class A requests.
+    pub class_a: u64,
+    /// Count of class B requests.
+    pub class_b: u64,
+    /// Count of free requests.
+    pub free: u64,
+    /// Bytes returned by `read`.
+    pub bytes_read: u64,
+    /// Bytes consumed by `write`.
+    pub bytes_written: u64,
+}
+
+impl Usage {
+    fn merge(&mut self, other: &Usage) {
+        self.class_a += other.class_a;
+        self.class_b += other.class_b;
+        self.free += other.free;
+        self.bytes_read += other.bytes_read;
+        self.bytes_written += other.bytes_written;
+    }
+}
+
+/// A snapshot of the usage and estimated cost, returned by
+/// [`CostLayer::cost_report`].
+#[derive(Default, Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CostReport {
+    /// Usage of all operations.
+    pub total: Usage,
+    /// Usage per operation like `read` and `list`.
+    pub operations: BTreeMap<String, Usage>,
+    /// Estimated cost of `total` in nano dollars.
+    pub cost: u64,
+}
+
+/// CostLayer counts requests sent to the storage and estimates their cost
+/// with a [`PriceTable`].
+///
+/// Every http request is counted, including the ones hidden behind a single
+/// operation: multipart uploads count their initiation, every part and the
+/// completion, listings count every page. Requests are classified by method
+/// and query, so non-S3 services are counted with the same rules.
+/// Services that don't talk http (like `fs` and `memory`) only count bytes.
+///
+/// Clones of the layer share the same counters.
+///
+/// # Note
+///
+/// Region detection happens while building the backend, before any layer
+/// is applied. Build the backend inside [`CostLayer::instrument`] to count it.
+///
+/// Metrics (if enabled):
+///
+/// - `opendal_cost_requests`: number of requests, labeled by `class`.
+/// - `opendal_cost_bytes`: number of bytes, labeled by `direction`.
+/// - `opendal_cost_estimate_nanodollars`: estimated cost.
+///
+/// # Example
+///
+/// ```
+/// use anyhow::Result;
+/// use opendal::layers::CostLayer;
+/// use opendal::layers::PriceTable;
+/// use opendal::services::memory;
+/// use opendal::Operator;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<()> {
+///     let cost = CostLayer::new(PriceTable::default());
+///     let op = Operator::new(memory::Backend::build().finish().await?).layer(cost.clone());
+///
+///     op.object("test")
+///         .writer()
+///         .write_bytes("Hello, World!".as_bytes().to_vec())
+///         .await?;
+///     assert_eq!(cost.cost_report().total.bytes_written, 13);
+///
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct CostLayer {
+    state: Arc<CostState>,
+}
+
+impl CostLayer {
+    /// Create a new cost layer which estimates cost with `prices`.
+    pub fn new(prices: PriceTable) -> Self {
+        Self {
+            state: Arc::new(CostState {
+                prices,
+                metrics: AtomicBool::new(false),
+                usage: Mutex::new(BTreeMap::new()),
+            }),
+        }
+    }
+
+    /// Publish the usage and estimated cost as metrics gauges.
+    pub fn enable_metrics(self, enabled: bool) -> Self {
+        self.state.metrics.store(enabled, Ordering::Relaxed);
+        self
+    }
+
+    /// Take a snapshot of the usage and estimated cost.
+    pub fn cost_report(&self) -> CostReport {
+        self.state.report()
+    }
+
+    /// Run `fut` and count the requests it sends under operation `op`.
+    pub async fn instrument<F: Future>(&self, op: &'static str, fut: F) -> F::Output {
+        with_request_hook(self.state.hook(op), fut).await
+    }
+}
+
+impl Layer for CostLayer {
+    fn layer(&self, inner: Arc<dyn Accessor>) -> Arc<dyn Accessor> {
+        Arc::new(CostAccessor {
+            inner,
+            state: self.state.clone(),
+        })
+    }
+}
+
+#[derive(Debug)]
+struct CostState {
+    prices: PriceTable,
+    metrics: AtomicBool,
+    usage: Mutex<BTreeMap<&'static str, Usage>>,
+}
+
+impl CostState {
+    fn record(&self, op: &'static str, usage: Usage) {
+        let mut ops = self.usage.lock().expect("lock must succeed");
+        ops.entry(op).or_default().merge(&usage);
+
+        if self.metrics.load(Ordering::Relaxed) {
+            let mut total = Usage::default();
+            ops.values().for_each(|v| total.merge(v));
+            gauge!("opendal_cost_requests", total.class_a as f64, "class" => "a");
+            gauge!("opendal_cost_requests", total.class_b as f64, "class" => "b");
+            gauge!("opendal_cost_requests", total.free as f64, "class" => "free");
+            gauge!("opendal_cost_bytes", total.bytes_read as f64, "direction" => "read");
+            gauge!("opendal_cost_bytes", total.bytes_written as f64, "direction" => "write");
+            gauge!(
+                "opendal_cost_estimate_nanodollars",
+                self.prices.cost(&total) as f64
+            );
+        }
+    }
+
+    fn report(&self) -> CostReport {
+        let ops = self.usage.lock().expect("lock must succeed");
+        let mut report = CostReport::default();
+        for (op, usage) in ops.iter() {
+            report.total.merge(usage);
+            report.operations.insert(op.to_string(), *usage);
+        }
+        report.cost = self.prices.cost(&report.total);
+        report
+    }
+
+    fn hook(self: &Arc<Self>, op: &'static str) -> RequestHook {
+        let state = self.clone();
+        Arc::new(move |req| state.record(op, classify(req)))
+    }
+}
+
+/// Classify a request into the price classes of S3.
+fn classify(req: &hyper::Request<hyper::Body>) -> Usage {
+    let has_query = |key: &str, value: Option<&str>| {
+        req.uri()
+            .query()
+            .unwrap_or_default()
+            .split('&')
+            .any(|kv| match kv.split_once('=') {
+                Some((k, v)) => k == key && value.map(|value| value == v).unwrap_or(true),
+                None => kv == key && value.is_none(),
+            })
+    };
+
+    let mut usage = Usage::default();
+    match *req.method() {
+        Method::DELETE => usage.free = 1,
+        // Deleting objects in batch is free as well.
+        Method::POST if has_query("delete", None) => usage.free = 1,
+        Method::POST if has_query("select", None) => usage.class_b = 1,
+        Method::GET if has_query("list-type", None) || has_query("comp", Some("list")) => {
+            usage.class_a = 1
+        }
+        Method::GET | Method::HEAD => usage.class_b = 1,
+        Method::PUT | Method::POST => usage.class_a = 1,
+        _ => usage.class_b = 1,
+    }
+    usage
+}
+
+#[derive(Debug, Clone)]
+struct CostAccessor {
+    inner: Arc<dyn Accessor>,
+    state: Arc<CostState>,
+}
+
+impl CostAccessor {
+    /// Record the bytes read from the stream, and requests sent while
+    /// reading like the chunks of parallel reads.
+    fn count_read(&self, s: BytesStream) -> BytesStream {
+        let state = self.state.clone();
+        let hook = self.state.hook("read");
+        Box::new(CostStream { inner: s, hook }.inspect(move |v| {
+            if let Ok(bs) = v {
+                state.record(
+                    "read",
+                    Usage {
+                        bytes_read: bs.len() as u64,
+                        ..Default::default()
+                    },
+                )
+            }
+        }))
+    }
+}
+
+#[async_trait]
+impl Accessor for CostAccessor {
+    async fn read(&self, args: &OpRead) -> Result<BytesStream> {
+        let s = with_request_hook(self.state.hook("read"), self.inner.read(args)).await?;
+        Ok(self.count_read(s))
+    }
+    async fn read_mmap(&self, args: &OpRead) -> Result<Bytes> {
+        let bs = with_request_hook(self.state.hook("read"), self.inner.read_mmap(args)).await?;
+        self.state.record(
+            "read",
+            Usage {
+                bytes_read: bs.len() as u64,
+                ..Default::default()
+            },
+        );
+        Ok(bs)
+    }
+    async fn read_with_metadata(&self, args: &OpRead) -> Result<(BytesStream, Metadata)> {
+        let (s, meta) =
+            with_request_hook(self.state.hook("read"), self.inner.read_with_metadata(args)).await?;
+        Ok((self.count_read(s), meta))
+    }
+    async fn write(&self, r: BoxedAsyncReader, args: &OpWrite) -> Result<Metadata> {
+        let state = self.state.clone();
+        let r = CallbackReader::new(r, move |n| {
+            state.record(
+                "write",
+                Usage {
+                    bytes_written: n as u64,
+                    ..Default::default()
+                },
+            )
+        });
+
+        with_request_hook(
+            self.state.hook("write"),
+            self.inner.write(Box::new(r), args),
+        )
+        .await
+    }
+    async fn append(&self, r: BoxedAsyncReader, args: &OpAppend) -> Result<Metadata> {
+        let state = self.state.clone();
+        let r = CallbackReader::new(r, move |n| {
+            state.record(
+                "append",
+                Usage {
+                    bytes_written: n as u64,
+                    ..Default::default()
+                },
+            )
+        });
+
+        with_request_hook(
+            self.state.hook("append"),
+            self.inner.append(Box::new(r), args),
+        )
+        .await
+    }
+    async fn create(&self, args: &OpCreate) -> Result<Metadata> {
+        with_request_hook(self.state.hook("create"), self.inner.create(args)).await
+    }
+    async fn stat(&self, args: &OpStat) -> Result<Metadata> {
+        with_request_hook(self.state.hook("stat"), self.inner.stat(args)).await
+    }
+    async fn delete(&self, args: &OpDelete) -> Result<()> {
+        with_request_hook(self.state.hook("delete"), self.inner.delete(args)).await
+    }
+    async fn batch(&self, args: &OpBatch) -> Result<Vec<(String, BatchResult)>> {
+        with_request_hook(self.state.hook("batch"), self.inner.batch(args)).await
+    }
+    async fn copy(&self, args: &OpCopy) -> Result<Metadata> {
+        with_request_hook(self.state.hook("copy"), self.inner.copy(args)).await
+    }
+    async fn rename(&self, args: &OpRename) -> Result<Metadata> {
+        with_request_hook(self.state.hook("rename"), self.inner.rename(args)).await
+    }
+    // No request is sent by presign, it costs nothing.
+    fn presign(&self, args: &OpPresign) -> Result<PresignedRequest> {
+        self.inner.presign(args)
+    }
+    async fn create_multipart(&self, args: &OpCreateMultipart) -> Result<String> {
+        with_request_hook(
+            self.state.hook("create_multipart"),
+            self.inner.create_multipart(args),
+        )
+        .await
+    }
+    async fn write_multipart(
+        &self,
+        r: BoxedAsyncReader,
+        args: &OpWriteMultipart,
+    ) -> Result<ObjectPart> {
+        let state = self.state.clone();
+        let r = CallbackReader::new(r, move |n| {
+            state.record(
+                "write_multipart",
+                Usage {
+                    bytes_written: n as u64,
+                    ..Default::default()
+                },
+            )
+        });
+
+        with_request_hook(
+            self.state.hook("write_multipart"),
+            self.inner.write_multipart(Box::new(r), args),
+        )
+        .await
+    }
+    async fn complete_multipart(&self, args: &OpCompleteMultipart) -> Result<Metadata> {
+        with_request_hook(
+            self.state.hook("complete_multipart"),
+            self.inner.complete_multipart(args),
+        )
+        .await
+    }
+    async fn abort_multipart(&self, args: &OpAbortMultipart) -> Result<()> {
+        with_request_hook(
+            self.state.hook("abort_multipart"),
+            self.inner.abort_multipart(args),
+        )
+        .await
+    }
+    async fn list(&self, args: &OpList) -> Result<BoxedObjectStream> {
+        let hook = self.state.hook("list");
+        let obs = with_request_hook(hook.clone(), self.inner.list(args)).await?;
+
+        Ok(rebind(
+            Box::new(CostStream { inner: obs, hook }),
+            Arc::new(self.clone()),
+        ))
+    }
+    async fn scan(&self, args: &OpScan) -> Result<BoxedObjectStream> {
+        let hook = self.state.hook("scan");
+        let obs = with_request_hook(hook.clone(), self.inner.scan(args)).await?;
+
+        Ok(rebind(
+            Box::new(CostStream { inner: obs, hook }),
+            Arc::new(self.clone()),
+        ))
+    }
+
+    fn metadata(&self) -> AccessorMetadata {
+        self.inner.metadata()
+    }
+}
+
+/// CostStream installs the hook while polling, so that requests sent by
+/// streams like following pages of list are counted.
+struct CostStream<S> {
+    inner: S,
+    hook: RequestHook,
+}
+
+impl<S: Stream + Unpin> Stream for CostStream<S> {
+    type Item = S::Item;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let hook = self.hook.clone();
+        sync_with_request_hook(hook, || self.inner.poll_next_unpin(cx))
+    }
+}
@@ -0,0 +1,102 @@
+// Copyright 2022 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+/// Glob matches object paths against a pattern.
+///
+/// - `?` matches any single char except `/`.
+/// - `*` matches any sequence of chars except `/`.
+/// - `**` matches any sequence of chars including `/`.
+///
+/// Other chars are matched literally.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Glob {
+    pattern: Vec<char>,
+}
+
+impl Glob {
+    pub fn new(pattern: &str) -> Self {
+        Self {
+            pattern: pattern.chars().collect(),
+        }
+    }
+
+    pub fn matches(&self, path: &str) -> bool {
+        let path: Vec<char> = path.chars().collect();
+        Self::match_from(&self.pattern, &path)
+    }
+
+    fn match_from(pattern: &[char], path: &[char]) -> bool {
+        match pattern.first() {
+            None => path.is_empty(),
+            Some('*') if pattern.get(1) == Some(&'*') => {
+                let rest = &pattern[2..];
+                (0..=path.len()).any(|i| Self::match_from(rest, &path[i..]))
+            }
+            Some('*') => {
+                let rest = &pattern[1..];
+                for i in 0..=path.len() {
+                    if Self::match_from(rest, &path[i..]) {
+                        return true;
+                    }
+                    if path.get(i) == Some(&'/') {
+                        break;
+                    }
+                }
+                false
+            }
+            Some('?') => match path.first() {
+                Some(c) if *c != '/' => Self::match_from(&pattern[1..], &path[1..]),
+                _ => false,
+            },
+            Some(c) => match path.first() {
+                Some(p) if p == c => Self::match_from(&pattern[1..], &path[1..]),
+                _ => false,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matches() {
+        let cases = vec![
+            ("tmp/**", "tmp/a", true),
+            ("tmp/**", "tmp/a/b/c", true),
+            ("tmp/**", "tmpx/a", false),
+            ("tmp/*", "tmp/a", true),
+            ("tmp/*", "tmp/a/b", false),
+            ("**/*.parquet", "a/b/c.parquet", true),
+            ("**/*.parquet", "a/b/c.csv", false),
+            ("file-?", "file-1", true),
+            ("file-?", "file-10", false),
+            ("a?c", "a/c", false),
+            ("**", "", true),
+            ("exact", "exact", true),
+            ("exact", "exact/", false),
+        ];
+
+        for (pattern, path, expected) in cases {
+            assert_eq!(
+                Glob::new(pattern).matches(path),
+                expected,
+                "{} {}",
+                pattern,
+                path
+            );
+        }
+    }
+}
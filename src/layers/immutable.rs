@@ -0,0 +1,158 @@
+// Copyright 2022 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use anyhow::anyhow;
+use async_trait::async_trait;
+use bytes::Bytes;
+use http::Method;
+
+use super::rebind;
+use crate::accessor::batch_each;
+use crate::error::Error;
+use crate::error::Kind;
+use crate::error::Result;
+use crate::io::BytesStream;
+use crate::ops::BatchResult;
+use crate::ops::ObjectPart;
+use crate::ops::OpAbortMultipart;
+use crate::ops::OpAppend;
+use crate::ops::OpBatch;
+use crate::ops::OpCompleteMultipart;
+use crate::ops::OpCopy;
+use crate::ops::OpCreate;
+use crate::ops::OpCreateMultipart;
+use crate::ops::OpDelete;
+use crate::ops::OpList;
+use crate::ops::OpPresign;
+use crate::ops::OpRead;
+use crate::ops::OpRename;
+use crate::ops::OpScan;
+use crate::ops::OpStat;
+use crate::ops::OpWrite;
+use crate::ops::OpWriteMultipart;
+use crate::ops::PresignedRequest;
+use crate::Accessor;
+use crate::AccessorMetadata;
+use crate::BoxedAsyncReader;
+use crate::BoxedObjectStream;
+use crate::Layer;
+use crate::Metadata;
+
+/// ImmutableLayer makes the underlying storage read-only.
+///
+/// All mutations (`write`, `delete`, `copy`) will be rejected with
+/// `Kind::ObjectPermissionDenied` before reaching the inner accessor.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ImmutableLayer;
+
+impl Layer for ImmutableLayer {
+    fn layer(&self, inner: Arc<dyn Accessor>) -> Arc<dyn Accessor> {
+        Arc::new(ImmutableAccessor { inner })
+    }
+}
+
+#[derive(Debug, Clone)]
+struct ImmutableAccessor {
+    inner: Arc<dyn Accessor>,
+}
+
+impl ImmutableAccessor {
+    fn denied(op: &'static str, path: &str) -> Error {
+        Error::Object {
+            kind: Kind::ObjectPermissionDenied,
+            op,
+            path: path.to_string(),
+            context: HashMap::new(),
+            source: anyhow!("operator is read-only"),
+        }
+    }
+}
+
+#[async_trait]
+impl Accessor for ImmutableAccessor {
+    async fn read(&self, args: &OpRead) -> Result<BytesStream> {
+        self.inner.read(args).await
+    }
+    async fn read_mmap(&self, args: &OpRead) -> Result<Bytes> {
+        self.inner.read_mmap(args).await
+    }
+    async fn read_with_metadata(&self, args: &OpRead) -> Result<(BytesStream, Metadata)> {
+        self.inner.read_with_metadata(args).await
+    }
+    async fn write(&self, _: BoxedAsyncReader, args: &OpWrite) -> Result<Metadata> {
+        Err(Self::denied("write", &args.path))
+    }
+    async fn append(&self, _: BoxedAsyncReader, args: &OpAppend) -> Result<Metadata> {
+        Err(Self::denied("append", &args.path))
+    }
+    async fn create(&self, args: &OpCreate) -> Result<Metadata> {
+        Err(Self::denied("create", &args.path))
+    }
+    async fn stat(&self, args: &OpStat) -> Result<Metadata> {
+        self.inner.stat(args).await
+    }
+    async fn delete(&self, args: &OpDelete) -> Result<()> {
+        Err(Self::denied("delete", &args.path))
+    }
+    async fn batch(&self, args: &OpBatch) -> Result<Vec<(String, BatchResult)>> {
+        // Deletes are denied one by one.
+        Ok(batch_each(self, args).await)
+    }
+    async fn copy(&self, args: &OpCopy) -> Result<Metadata> {
+        Err(Self::denied("copy", &args.dst))
+    }
+    async fn rename(&self, args: &OpRename) -> Result<Metadata> {
+        Err(Self::denied("rename", &args.src))
+    }
+    fn presign(&self, args: &OpPresign) -> Result<PresignedRequest> {
+        if args.method == Method::PUT {
+            return Err(Self::denied("presign", &args.path));
+        }
+        self.inner.presign(args)
+    }
+    async fn create_multipart(&self, args: &OpCreateMultipart) -> Result<String> {
+        Err(Self::denied("create_multipart", &args.path))
+    }
+    async fn write_multipart(
+        &self,
+        _: BoxedAsyncReader,
+        args: &OpWriteMultipart,
+    ) -> Result<ObjectPart> {
+        Err(Self::denied("write_multipart", &args.path))
+    }
+    async fn complete_multipart(&self, args: &OpCompleteMultipart) -> Result<Metadata> {
+        Err(Self::denied("complete_multipart", &args.path))
+    }
+    async fn abort_multipart(&self, args: &OpAbortMultipart) -> Result<()> {
+        Err(Self::denied("abort_multipart", &args.path))
+    }
+    async fn list(&self, args: &OpList) -> Result<BoxedObjectStream> {
+        let obs = self.inner.list(args).await?;
+        Ok(rebind(obs, Arc::new(self.clone())))
+    }
+    async fn scan(&self, args: &OpScan) -> Result<BoxedObjectStream> {
+        let obs = self.inner.scan(args).await?;
+        Ok(rebind(obs, Arc::new(self.clone())))
+    }
+
+    fn metadata(&self) -> AccessorMetadata {
+        // Presigned reads are still allowed.
+        let mut am = self.inner.metadata();
+        am.set_copy(false).set_append(false).set_multipart(false);
+        am
+    }
+}
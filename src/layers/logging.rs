@@ -0,0 +1,469 @@
+// Copyright 2022 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::hash_map::DefaultHasher;
+use std::fmt::Write;
+use std::future::Future;
+use std::hash::Hash;
+use std::hash::Hasher;
+use std::sync::Arc;
+use std::time::Duration;
+use std::time::Instant;
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use log::log;
+use log::log_enabled;
+use log::Level;
+
+use super::rebind;
+use crate::error::Error;
+use crate::error::Kind;
+use crate::error::Result;
+use crate::io::BytesStream;
+use crate::ops::BatchResult;
+use crate::ops::ObjectPart;
+use crate::ops::OpAbortMultipart;
+use crate::ops::OpAppend;
+use crate::ops::OpBatch;
+use crate::ops::OpCompleteMultipart;
+use crate::ops::OpCopy;
+use crate::ops::OpCreate;
+use crate::ops::OpCreateMultipart;
+use crate::ops::OpDelete;
+use crate::ops::OpList;
+use crate::ops::OpPresign;
+use crate::ops::OpRead;
+use crate::ops::OpRename;
+use crate::ops::OpScan;
+use crate::ops::OpStat;
+use crate::ops::OpWrite;
+use crate::ops::OpWriteMultipart;
+use crate::ops::PresignedRequest;
+use crate::Accessor;
+use crate::AccessorMetadata;
+use crate::BoxedAsyncReader;
+use crate::BoxedObjectStream;
+use crate::Layer;
+use crate::Metadata;
+
+/// LoggingLayer logs a line for every operation with its outcome and
+/// latency, under the target `opendal::layers::logging`.
+///
+/// Lines are like:
+///
+/// ```text
+/// op=read path=dir/file range=bytes=0-1023 duration=12.3ms result=ok
+/// op=stat path=dir/file duration=8.1ms result=not-found
+/// op=write path=dir/file size=1024 duration=1.2s result=error kind=Timeout error=...
+/// ```
+///
+/// By default, successes and not found errors are logged at `DEBUG`, other
+/// errors at `WARN`.
+///
+/// # Note
+///
+/// - Bodies are never read nor buffered. For `read`, `list` and `scan`,
+///   the duration only covers the call that returns the stream.
+/// - With [`LoggingLayer::hash_path`], paths are replaced by their hashes,
+///   and error messages, which contain paths, are not logged. The hash is
+///   not cryptographic, it's meant to correlate lines instead of hiding
+///   paths from those who can guess them.
+///
+/// # Example
+///
+/// ```
+/// use anyhow::Result;
+/// use log::Level;
+/// use opendal::layers::LoggingLayer;
+/// use opendal::services::memory;
+/// use opendal::Operator;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<()> {
+///     let op = Operator::new(memory::Backend::build().finish().await?)
+///         .layer(LoggingLayer::new().ok_level(Level::Trace).hash_path(true));
+///
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct LoggingLayer {
+    ok_level: Level,
+    not_found_level: Level,
+    error_level: Level,
+    hash_path: bool,
+}
+
+impl Default for LoggingLayer {
+    fn default() -> Self {
+        Self {
+            ok_level: Level::Debug,
+            not_found_level: Level::Debug,
+            error_level: Level::Warn,
+            hash_path: false,
+        }
+    }
+}
+
+impl LoggingLayer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the level of successful operations.
+    #[must_use]
+    pub fn ok_level(mut self, level: Level) -> Self {
+        self.ok_level = level;
+        self
+    }
+
+    /// Set the level of operations failed with [`Kind::ObjectNotExist`],
+    /// which are expected by callers like `is_exist`.
+    #[must_use]
+    pub fn not_found_level(mut self, level: Level) -> Self {
+        self.not_found_level = level;
+        self
+    }
+
+    /// Set the level of operations failed with other errors.
+    #[must_use]
+    pub fn error_level(mut self, level: Level) -> Self {
+        self.error_level = level;
+        self
+    }
+
+    /// Log hashes of paths instead of paths.
+    #[must_use]
+    pub fn hash_path(mut self, enabled: bool) -> Self {
+        self.hash_path = enabled;
+        self
+    }
+}
+
+impl Layer for LoggingLayer {
+    fn layer(&self, inner: Arc<dyn Accessor>) -> Arc<dyn Accessor> {
+        Arc::new(LoggingAccessor {
+            inner,
+            layer: *self,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+struct LoggingAccessor {
+    inner: Arc<dyn Accessor>,
+    layer: LoggingLayer,
+}
+
+impl LoggingAccessor {
+    async fn log<T>(
+        &self,
+        op: &'static str,
+        path: &str,
+        extra: impl FnOnce(&LoggingLayer) -> String,
+        fut: impl Future<Output = Result<T>>,
+    ) -> Result<T> {
+        let start = Instant::now();
+        let result = fut.await;
+        self.log_result(op, path, extra, start.elapsed(), &result);
+        result
+    }
+
+    fn log_result<T>(
+        &self,
+        op: &'static str,
+        path: &str,
+        extra: impl FnOnce(&LoggingLayer) -> String,
+        elapsed: Duration,
+        result: &Result<T>,
+    ) {
+        let level = self.layer.level(result.as_ref().err());
+        if log_enabled!(level) {
+            let line = self.layer.format_line(
+                op,
+                path,
+                &extra(&self.layer),
+                elapsed,
+                result.as_ref().err(),
+            );
+            log!(level, "{}", line);
+        }
+    }
+}
+
+impl LoggingLayer {
+    fn level(&self, err: Option<&Error>) -> Level {
+        match err {
+            None => self.ok_level,
+            Some(err) if err.kind() == Kind::ObjectNotExist => self.not_found_level,
+            Some(_) => self.error_level,
+        }
+    }
+
+    fn path(&self, path: &str) -> String {
+        if !self.hash_path {
+            return path.to_string();
+        }
+        let mut h = DefaultHasher::new();
+        path.hash(&mut h);
+        format!("{:016x}", h.finish())
+    }
+
+    fn format_line(
+        &self,
+        op: &str,
+        path: &str,
+        extra: &str,
+        elapsed: Duration,
+        err: Option<&Error>,
+    ) -> String {
+        let mut line = format!("op={} path={}", op, self.path(path));
+        if !extra.is_empty() {
+            let _ = write!(line, " {}", extra);
+        }
+        let _ = write!(line, " duration={:?}", elapsed);
+        match err {
+            None => line.push_str(" result=ok"),
+            Some(err) if err.kind() == Kind::ObjectNotExist => line.push_str(" result=not-found"),
+            // Error messages contain paths.
+            Some(err) if self.hash_path => {
+                let _ = write!(line, " result=error kind={:?}", err.kind());
+            }
+            Some(err) => {
+                let _ = write!(line, " result=error kind={:?} error={}", err.kind(), err);
+            }
+        }
+        line
+    }
+}
+
+fn no_extra(_: &LoggingLayer) -> String {
+    String::new()
+}
+
+#[async_trait]
+impl Accessor for LoggingAccessor {
+    async fn read(&self, args: &OpRead) -> Result<BytesStream> {
+        let extra = |_: &LoggingLayer| format!("range={}", args.range);
+        self.log("read", &args.path, extra, self.inner.read(args))
+            .await
+    }
+    async fn read_mmap(&self, args: &OpRead) -> Result<Bytes> {
+        let extra = |_: &LoggingLayer| format!("range={}", args.range);
+        self.log("read", &args.path, extra, self.inner.read_mmap(args))
+            .await
+    }
+    async fn read_with_metadata(&self, args: &OpRead) -> Result<(BytesStream, Metadata)> {
+        let extra = |_: &LoggingLayer| format!("range={}", args.range);
+        self.log(
+            "read",
+            &args.path,
+            extra,
+            self.inner.read_with_metadata(args),
+        )
+        .await
+    }
+    async fn write(&self, r: BoxedAsyncReader, args: &OpWrite) -> Result<Metadata> {
+        let extra = |_: &LoggingLayer| match args.size {
+            Some(size) => format!("size={}", size),
+            None => "size=unknown".to_string(),
+        };
+        self.log("write", &args.path, extra, self.inner.write(r, args))
+            .await
+    }
+    async fn append(&self, r: BoxedAsyncReader, args: &OpAppend) -> Result<Metadata> {
+        let extra = |_: &LoggingLayer| format!("position={} size={}", args.position, args.size);
+        self.log("append", &args.path, extra, self.inner.append(r, args))
+            .await
+    }
+    async fn create(&self, args: &OpCreate) -> Result<Metadata> {
+        self.log("create", &args.path, no_extra, self.inner.create(args))
+            .await
+    }
+    async fn stat(&self, args: &OpStat) -> Result<Metadata> {
+        self.log("stat", &args.path, no_extra, self.inner.stat(args))
+            .await
+    }
+    async fn delete(&self, args: &OpDelete) -> Result<()> {
+        self.log("delete", &args.path, no_extra, self.inner.delete(args))
+            .await
+    }
+    async fn batch(&self, args: &OpBatch) -> Result<Vec<(String, BatchResult)>> {
+        let path = args.ops.first().map(|v| v.path()).unwrap_or_default();
+        let extra = |_: &LoggingLayer| format!("ops={}", args.ops.len());
+        self.log("batch", path, extra, self.inner.batch(args)).await
+    }
+    async fn copy(&self, args: &OpCopy) -> Result<Metadata> {
+        let extra = |l: &LoggingLayer| format!("to={}", l.path(&args.dst));
+        self.log("copy", &args.src, extra, self.inner.copy(args))
+            .await
+    }
+    async fn rename(&self, args: &OpRename) -> Result<Metadata> {
+        let extra = |l: &LoggingLayer| format!("to={}", l.path(&args.dst));
+        self.log("rename", &args.src, extra, self.inner.rename(args))
+            .await
+    }
+    fn presign(&self, args: &OpPresign) -> Result<PresignedRequest> {
+        let start = Instant::now();
+        let result = self.inner.presign(args);
+        let extra = |_: &LoggingLayer| format!("method={}", args.method);
+        self.log_result("presign", &args.path, extra, start.elapsed(), &result);
+        result
+    }
+    async fn create_multipart(&self, args: &OpCreateMultipart) -> Result<String> {
+        self.log(
+            "create_multipart",
+            &args.path,
+            no_extra,
+            self.inner.create_multipart(args),
+        )
+        .await
+    }
+    async fn write_multipart(
+        &self,
+        r: BoxedAsyncReader,
+        args: &OpWriteMultipart,
+    ) -> Result<ObjectPart> {
+        let extra = |_: &LoggingLayer| format!("part={} size={}", args.part_number, args.size);
+        self.log(
+            "write_multipart",
+            &args.path,
+            extra,
+            self.inner.write_multipart(r, args),
+        )
+        .await
+    }
+    async fn complete_multipart(&self, args: &OpCompleteMultipart) -> Result<Metadata> {
+        self.log(
+            "complete_multipart",
+            &args.path,
+            no_extra,
+            self.inner.complete_multipart(args),
+        )
+        .await
+    }
+    async fn abort_multipart(&self, args: &OpAbortMultipart) -> Result<()> {
+        self.log(
+            "abort_multipart",
+            &args.path,
+            no_extra,
+            self.inner.abort_multipart(args),
+        )
+        .await
+    }
+    async fn list(&self, args: &OpList) -> Result<BoxedObjectStream> {
+        let obs = self
+            .log("list", &args.path, no_extra, self.inner.list(args))
+            .await?;
+
+        Ok(rebind(obs, Arc::new(self.clone())))
+    }
+    async fn scan(&self, args: &OpScan) -> Result<BoxedObjectStream> {
+        let obs = self
+            .log("scan", &args.path, no_extra, self.inner.scan(args))
+            .await?;
+
+        Ok(rebind(obs, Arc::new(self.clone())))
+    }
+
+    fn metadata(&self) -> AccessorMetadata {
+        self.inner.metadata()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use anyhow::anyhow;
+
+    use super::*;
+
+    fn error(kind: Kind) -> Error {
+        Error::Object {
+            kind,
+            op: "stat",
+            path: "dir/secret".to_string(),
+            context: HashMap::new(),
+            source: anyhow!("boom"),
+        }
+    }
+
+    #[test]
+    fn test_format_line() {
+        let layer = LoggingLayer::new();
+        let elapsed = Duration::from_millis(12);
+
+        assert_eq!(
+            layer.format_line("read", "dir/a", "range=bytes=0-9", elapsed, None),
+            "op=read path=dir/a range=bytes=0-9 duration=12ms result=ok"
+        );
+        assert_eq!(
+            layer.format_line(
+                "stat",
+                "dir/a",
+                "",
+                elapsed,
+                Some(&error(Kind::ObjectNotExist))
+            ),
+            "op=stat path=dir/a duration=12ms result=not-found"
+        );
+        let line = layer.format_line(
+            "stat",
+            "dir/a",
+            "",
+            elapsed,
+            Some(&error(Kind::ObjectPermissionDenied)),
+        );
+        assert!(
+            line.starts_with(
+                "op=stat path=dir/a duration=12ms result=error kind=ObjectPermissionDenied error="
+            ),
+            "{}",
+            line
+        );
+        assert!(line.contains("boom"), "{}", line);
+    }
+
+    #[test]
+    fn test_format_line_hash_path() {
+        let layer = LoggingLayer::new().hash_path(true);
+        let line = layer.format_line(
+            "stat",
+            "dir/secret",
+            "",
+            Duration::from_millis(1),
+            Some(&error(Kind::Timeout)),
+        );
+        assert!(!line.contains("secret"), "{}", line);
+        assert!(line.ends_with("result=error kind=Timeout"), "{}", line);
+
+        // Hashes are stable so that lines could be correlated.
+        assert_eq!(layer.path("dir/secret"), layer.path("dir/secret"));
+        assert_ne!(layer.path("dir/secret"), layer.path("dir/other"));
+        assert_eq!(layer.path("dir/secret").len(), 16);
+    }
+
+    #[test]
+    fn test_level() {
+        let layer = LoggingLayer::new().not_found_level(Level::Trace);
+        assert_eq!(layer.level(None), Level::Debug);
+        assert_eq!(
+            layer.level(Some(&error(Kind::ObjectNotExist))),
+            Level::Trace
+        );
+        assert_eq!(layer.level(Some(&error(Kind::Timeout))), Level::Warn);
+    }
+}
@@ -0,0 +1,529 @@
+// Copyright 2022 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Pin a dataset to a manifest of objects.
+//!
+//! A manifest is a newline delimited JSON file, the first line is a header
+//! like `{"format":"opendal-manifest","version":1}`, followed by one line
+//! per object like `{"path":"data/a","etag":"abc","size":1024}`.
+//! `etag` is `null` if the backend doesn't return ETags.
+//!
+//! Manifests are created by [`Operator::snapshot_prefix`], and opened by
+//! [`ManifestLayer::load`].
+
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::ops::Bound;
+use std::sync::Arc;
+
+use anyhow::anyhow;
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures::stream;
+use futures::TryStreamExt;
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::accessor::batch_each;
+use crate::accessor::scan_by_list;
+use crate::error::Error;
+use crate::error::Kind;
+use crate::error::Result;
+use crate::io::BytesStream;
+use crate::object::filter_by_pattern;
+use crate::object::LimitedObjectStream;
+use crate::ops::BatchResult;
+use crate::ops::ObjectPart;
+use crate::ops::OpAbortMultipart;
+use crate::ops::OpAppend;
+use crate::ops::OpBatch;
+use crate::ops::OpCompleteMultipart;
+use crate::ops::OpCopy;
+use crate::ops::OpCreate;
+use crate::ops::OpCreateMultipart;
+use crate::ops::OpDelete;
+use crate::ops::OpList;
+use crate::ops::OpPresign;
+use crate::ops::OpRead;
+use crate::ops::OpRename;
+use crate::ops::OpScan;
+use crate::ops::OpStat;
+use crate::ops::OpWrite;
+use crate::ops::OpWriteMultipart;
+use crate::ops::PresignedRequest;
+use crate::Accessor;
+use crate::AccessorMetadata;
+use crate::BoxedAsyncReader;
+use crate::BoxedObjectStream;
+use crate::Layer;
+use crate::Metadata;
+use crate::Object;
+use crate::ObjectMode;
+use crate::Operator;
+
+/// `format` in the header of manifests.
+const MANIFEST_FORMAT: &str = "opendal-manifest";
+/// The only version of manifests supported so far.
+const MANIFEST_VERSION: u32 = 1;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ManifestHeader {
+    format: String,
+    version: u32,
+}
+
+/// An object pinned by the manifest.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct ManifestEntry {
+    pub(crate) path: String,
+    pub(crate) etag: Option<String>,
+    pub(crate) size: u64,
+}
+
+/// Encode entries into a manifest with the header.
+pub(crate) fn encode_manifest(entries: &[ManifestEntry]) -> Vec<u8> {
+    let header = ManifestHeader {
+        format: MANIFEST_FORMAT.to_string(),
+        version: MANIFEST_VERSION,
+    };
+
+    let mut bs = serde_json::to_vec(&header).expect("manifest header must be valid json");
+    bs.push(b'\n');
+    for entry in entries {
+        serde_json::to_writer(&mut bs, entry).expect("manifest entry must be valid json");
+        bs.push(b'\n');
+    }
+    bs
+}
+
+fn normalize_path(path: &str) -> &str {
+    path.trim_start_matches('/')
+}
+
+/// Parse the manifest line by line, so that the manifest is never held in
+/// memory as a whole.
+struct ManifestParser {
+    path: String,
+    line: usize,
+    has_header: bool,
+    entries: BTreeMap<String, ManifestEntry>,
+}
+
+impl ManifestParser {
+    fn new(path: &str) -> Self {
+        Self {
+            path: path.to_string(),
+            line: 0,
+            has_header: false,
+            entries: BTreeMap::new(),
+        }
+    }
+
+    fn error(&self, kind: Kind, source: anyhow::Error) -> Error {
+        Error::Object {
+            kind,
+            op: "read",
+            path: self.path.clone(),
+            context: HashMap::from([("line".to_string(), self.line.to_string())]),
+            source,
+        }
+    }
+
+    fn feed(&mut self, line: &[u8]) -> Result<()> {
+        self.line += 1;
+        if line.iter().all(|b| b.is_ascii_whitespace()) {
+            return Ok(());
+        }
+
+        if !self.has_header {
+            let header: ManifestHeader = serde_json::from_slice(line).map_err(|e| {
+                self.error(
+                    Kind::ObjectCorrupted,
+                    anyhow!("invalid manifest header: {:?}", e),
+                )
+            })?;
+            if header.format != MANIFEST_FORMAT {
+                return Err(self.error(
+                    Kind::ObjectCorrupted,
+                    anyhow!("unexpected manifest format {}", header.format),
+                ));
+            }
+            if header.version != MANIFEST_VERSION {
+                return Err(self.error(
+                    Kind::Unsupported,
+                    anyhow!("unsupported manifest version {}", header.version),
+                ));
+            }
+            self.has_header = true;
+            return Ok(());
+        }
+
+        let mut entry: ManifestEntry = serde_json::from_slice(line).map_err(|e| {
+            self.error(
+                Kind::ObjectCorrupted,
+                anyhow!("invalid manifest entry: {:?}", e),
+            )
+        })?;
+        entry.path = normalize_path(&entry.path).to_string();
+        if self.entries.contains_key(&entry.path) {
+            return Err(self.error(
+                Kind::ObjectCorrupted,
+                anyhow!("duplicate manifest entry {}", entry.path),
+            ));
+        }
+        self.entries.insert(entry.path.clone(), entry);
+        Ok(())
+    }
+
+    fn finish(self) -> Result<BTreeMap<String, ManifestEntry>> {
+        if !self.has_header {
+            return Err(self.error(Kind::ObjectCorrupted, anyhow!("manifest is empty")));
+        }
+        Ok(self.entries)
+    }
+}
+
+/// ManifestLayer serves a read-only view of the objects pinned by a
+/// manifest.
+///
+/// - `list` returns exactly the entries of the manifest under the path
+///   recursively, in lexicographical order, without listing the backend.
+/// - `read` and `stat` only succeed if the object's ETag still matches the
+///   manifest, or fail with `Kind::ObjectChanged`. Objects deleted since
+///   the snapshot fail with `Kind::ObjectChanged` too. Objects not in the
+///   manifest fail with `Kind::ObjectNotExist`.
+/// - `write` and `delete` are rejected with `Kind::ObjectPermissionDenied`.
+///
+/// Backends without ETag support can't pin the content of objects, only
+/// the sizes returned by `stat` will be checked.
+///
+/// # Example
+///
+/// ```
+/// use anyhow::Result;
+/// use opendal::error::Kind;
+/// use opendal::layers::manifest::ManifestLayer;
+/// use opendal::services::memory;
+/// use opendal::Operator;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<()> {
+///     let op = Operator::new(memory::Backend::build().finish().await?);
+///     op.object("data/a").writer().write_bytes(vec![1; 4]).await?;
+///     op.snapshot_prefix("data/", &op.object("manifests/v1")).await?;
+///
+///     let view = op.clone().layer(ManifestLayer::load(&op, "manifests/v1").await?);
+///     assert_eq!(view.object("data/a").metadata().await?.content_length(), 4);
+///
+///     op.object("data/a").writer().write_bytes(vec![2; 4]).await?;
+///     let err = view.object("data/a").metadata().await.unwrap_err();
+///     assert_eq!(err.kind(), Kind::ObjectChanged);
+///
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct ManifestLayer {
+    entries: Arc<BTreeMap<String, ManifestEntry>>,
+}
+
+impl ManifestLayer {
+    /// Load the manifest at `manifest_path` of `inner`.
+    ///
+    /// The manifest is parsed while streaming, returns
+    /// `Kind::ObjectCorrupted` if it's malformed, or `Kind::Unsupported` if
+    /// its version is unknown.
+    pub async fn load(inner: &Operator, manifest_path: &str) -> Result<Self> {
+        let mut parser = ManifestParser::new(manifest_path);
+        let mut s = inner.object(manifest_path).stream(None, None).await?;
+
+        let mut buf = Vec::new();
+        while let Some(bs) = s.try_next().await? {
+            buf.extend_from_slice(&bs);
+
+            let mut start = 0;
+            while let Some(pos) = buf[start..].iter().position(|b| *b == b'\n') {
+                parser.feed(&buf[start..start + pos])?;
+                start += pos + 1;
+            }
+            buf.drain(..start);
+        }
+        // The last line could be not terminated.
+        if !buf.is_empty() {
+            parser.feed(&buf)?;
+        }
+
+        Ok(Self {
+            entries: Arc::new(parser.finish()?),
+        })
+    }
+
+    /// Count of objects in the manifest.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether the manifest has no objects.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+impl Layer for ManifestLayer {
+    fn layer(&self, inner: Arc<dyn Accessor>) -> Arc<dyn Accessor> {
+        Arc::new(ManifestAccessor {
+            inner,
+            entries: self.entries.clone(),
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+struct ManifestAccessor {
+    inner: Arc<dyn Accessor>,
+    entries: Arc<BTreeMap<String, ManifestEntry>>,
+}
+
+impl ManifestAccessor {
+    fn entry(&self, op: &'static str, path: &str) -> Result<&ManifestEntry> {
+        self.entries
+            .get(normalize_path(path))
+            .ok_or_else(|| Error::Object {
+                kind: Kind::ObjectNotExist,
+                op,
+                path: path.to_string(),
+                context: HashMap::new(),
+                source: anyhow!("object is not in the manifest"),
+            })
+    }
+
+    /// Attach the recorded ETag to the read.
+    fn pin_read(&self, op: &'static str, args: &OpRead) -> Result<OpRead> {
+        let entry = self.entry(op, &args.path)?;
+        let mut args = args.clone();
+        if entry.etag.is_some() {
+            args.if_match = entry.etag.clone();
+        }
+        Ok(args)
+    }
+
+    /// Check the metadata against the manifest, for backends that ignore
+    /// `if_match`.
+    fn verify(&self, op: &'static str, meta: &Metadata) -> Result<()> {
+        let entry = self.entry(op, meta.path())?;
+
+        let etag_changed = matches!(
+            (&entry.etag, meta.etag()),
+            (Some(expected), Some(actual)) if expected != &actual
+        );
+        // Decoded reads don't know the length of the object.
+        let size_changed = matches!(meta.try_content_length(), Some(size) if size != entry.size);
+        if etag_changed || size_changed {
+            return Err(Error::Object {
+                kind: Kind::ObjectChanged,
+                op,
+                path: meta.path().to_string(),
+                context: HashMap::new(),
+                source: anyhow!("object {:?} doesn't match the manifest {:?}", meta, entry),
+            });
+        }
+        Ok(())
+    }
+
+    fn denied(op: &'static str, path: &str) -> Error {
+        Error::Object {
+            kind: Kind::ObjectPermissionDenied,
+            op,
+            path: path.to_string(),
+            context: HashMap::new(),
+            source: anyhow!("manifest view is read-only"),
+        }
+    }
+}
+
+/// Turn errors of pinned operations caused by changes since the snapshot
+/// into `Kind::ObjectChanged`.
+fn changed(err: Error) -> Error {
+    match err {
+        Error::Object {
+            kind: Kind::ObjectPreconditionFailed | Kind::ObjectNotExist,
+            op,
+            path,
+            context,
+            source,
+        } => Error::Object {
+            kind: Kind::ObjectChanged,
+            op,
+            path,
+            context,
+            source: source.context("object has changed since the manifest was taken"),
+        },
+        err => err,
+    }
+}
+
+#[async_trait]
+impl Accessor for ManifestAccessor {
+    async fn read(&self, args: &OpRead) -> Result<BytesStream> {
+        let args = self.pin_read("read", args)?;
+        let s = self.inner.read(&args).await.map_err(changed)?;
+        Ok(Box::new(s.map_err(changed)))
+    }
+    async fn read_mmap(&self, args: &OpRead) -> Result<Bytes> {
+        let args = self.pin_read("read", args)?;
+        self.inner.read_mmap(&args).await.map_err(changed)
+    }
+    async fn read_with_metadata(&self, args: &OpRead) -> Result<(BytesStream, Metadata)> {
+        let args = self.pin_read("read", args)?;
+        let (s, mut meta) = self
+            .inner
+            .read_with_metadata(&args)
+            .await
+            .map_err(changed)?;
+        // Services could return the absolute path in metadata.
+        meta.set_path(&args.path);
+        self.verify("read", &meta)?;
+        Ok((Box::new(s.map_err(changed)), meta))
+    }
+    async fn write(&self, _: BoxedAsyncReader, args: &OpWrite) -> Result<Metadata> {
+        Err(Self::denied("write", &args.path))
+    }
+    async fn append(&self, _: BoxedAsyncReader, args: &OpAppend) -> Result<Metadata> {
+        Err(Self::denied("append", &args.path))
+    }
+    async fn create(&self, args: &OpCreate) -> Result<Metadata> {
+        Err(Self::denied("create", &args.path))
+    }
+    async fn stat(&self, args: &OpStat) -> Result<Metadata> {
+        if args.path.is_empty() || args.path.ends_with('/') {
+            let mut meta = Metadata::default();
+            meta.set_path(&args.path)
+                .set_mode(ObjectMode::DIR)
+                .set_content_length(0)
+                .set_complete();
+            return Ok(meta);
+        }
+
+        let entry = self.entry("stat", &args.path)?;
+        let mut args = args.clone();
+        if entry.etag.is_some() {
+            args.if_match = entry.etag.clone();
+        }
+        let mut meta = self.inner.stat(&args).await.map_err(changed)?;
+        meta.set_path(&args.path);
+        self.verify("stat", &meta)?;
+        Ok(meta)
+    }
+    async fn delete(&self, args: &OpDelete) -> Result<()> {
+        Err(Self::denied("delete", &args.path))
+    }
+    async fn batch(&self, args: &OpBatch) -> Result<Vec<(String, BatchResult)>> {
+        // Deletes are denied one by one, stats are served by the manifest.
+        Ok(batch_each(self, args).await)
+    }
+    async fn copy(&self, args: &OpCopy) -> Result<Metadata> {
+        Err(Self::denied("copy", &args.dst))
+    }
+    async fn rename(&self, args: &OpRename) -> Result<Metadata> {
+        Err(Self::denied("rename", &args.src))
+    }
+    fn presign(&self, args: &OpPresign) -> Result<PresignedRequest> {
+        // Presigned requests always access the latest version.
+        Err(Error::Object {
+            kind: Kind::Unsupported,
+            op: "presign",
+            path: normalize_path(&args.path).to_string(),
+            context: HashMap::new(),
+            source: anyhow!("manifest view doesn't support presign"),
+        })
+    }
+    async fn create_multipart(&self, args: &OpCreateMultipart) -> Result<String> {
+        Err(Self::denied("create_multipart", &args.path))
+    }
+    async fn write_multipart(
+        &self,
+        _: BoxedAsyncReader,
+        args: &OpWriteMultipart,
+    ) -> Result<ObjectPart> {
+        Err(Self::denied("write_multipart", &args.path))
+    }
+    async fn complete_multipart(&self, args: &OpCompleteMultipart) -> Result<Metadata> {
+        Err(Self::denied("complete_multipart", &args.path))
+    }
+    async fn abort_multipart(&self, args: &OpAbortMultipart) -> Result<()> {
+        Err(Self::denied("abort_multipart", &args.path))
+    }
+    async fn list(&self, args: &OpList) -> Result<BoxedObjectStream> {
+        let prefix = normalize_path(&args.path).to_string();
+        // Only the pinned versions are visible through the manifest.
+        if args.versions {
+            return Err(Error::Object {
+                kind: Kind::Unsupported,
+                op: "list",
+                path: prefix,
+                context: HashMap::new(),
+                source: anyhow!("manifest view doesn't support listing versions"),
+            });
+        }
+        let start = match &args.start_after {
+            Some(start_after) if start_after.as_str() >= prefix.as_str() => {
+                Bound::Excluded(start_after.clone())
+            }
+            _ => Bound::Included(prefix.clone()),
+        };
+
+        let acc: Arc<dyn Accessor> = Arc::new(self.clone());
+        let obs: Vec<Result<Object>> = self
+            .entries
+            .range((start, Bound::Unbounded))
+            .take_while(|(path, _)| path.starts_with(&prefix))
+            .map(|(path, entry)| {
+                let mut o = Object::new(acc.clone(), path);
+                let meta = o.metadata_mut();
+                meta.set_path(path)
+                    .set_mode(ObjectMode::FILE)
+                    .set_content_length(entry.size)
+                    .set_complete();
+                if let Some(etag) = &entry.etag {
+                    meta.set_etag(etag);
+                }
+                Ok(o)
+            })
+            .collect();
+
+        Ok(Box::new(LimitedObjectStream::new(
+            filter_by_pattern(Box::new(stream::iter(obs)), args.pattern.as_deref()),
+            &args.path,
+            args.max_results,
+            None,
+        )))
+    }
+    async fn scan(&self, args: &OpScan) -> Result<BoxedObjectStream> {
+        // Entries of the manifest are files only, scan is the recursive list.
+        scan_by_list(self, args).await
+    }
+
+    fn metadata(&self) -> AccessorMetadata {
+        let mut am = self.inner.metadata();
+        am.set_list(true)
+            .set_copy(false)
+            .set_append(false)
+            .set_presign(false)
+            .set_multipart(false)
+            .set_ordered_list(true)
+            .set_conditional_write(false)
+            .set_recursive_list(true);
+        am
+    }
+}
@@ -0,0 +1,245 @@
+// Copyright 2022 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Instant;
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures::StreamExt;
+use metrics::counter;
+use metrics::histogram;
+use metrics::increment_counter;
+
+use super::rebind;
+use crate::error::Error;
+use crate::error::Result;
+use crate::io::BytesStream;
+use crate::ops::BatchResult;
+use crate::ops::ObjectPart;
+use crate::ops::OpAbortMultipart;
+use crate::ops::OpAppend;
+use crate::ops::OpBatch;
+use crate::ops::OpCompleteMultipart;
+use crate::ops::OpCopy;
+use crate::ops::OpCreate;
+use crate::ops::OpCreateMultipart;
+use crate::ops::OpDelete;
+use crate::ops::OpList;
+use crate::ops::OpPresign;
+use crate::ops::OpRead;
+use crate::ops::OpRename;
+use crate::ops::OpScan;
+use crate::ops::OpStat;
+use crate::ops::OpWrite;
+use crate::ops::OpWriteMultipart;
+use crate::ops::PresignedRequest;
+use crate::readers::CallbackReader;
+use crate::Accessor;
+use crate::AccessorMetadata;
+use crate::BoxedAsyncReader;
+use crate::BoxedObjectStream;
+use crate::Layer;
+use crate::Metadata;
+
+/// MetricsLayer records metrics of every operation via the `metrics` crate,
+/// with labels `scheme` and `operation`:
+///
+/// - `opendal_requests_total`: count of operations.
+/// - `opendal_errors_total`: count of failed operations, with label `kind`.
+/// - `opendal_request_duration_seconds`: histogram of operation durations.
+/// - `opendal_bytes_read_total` and `opendal_bytes_written_total`: bytes
+///   actually transferred, without label `operation`.
+///
+/// # Note
+///
+/// For `read`, `list` and `scan`, the duration only covers the call that
+/// returns the stream. Bytes are counted while consuming the stream, and
+/// errors of the stream are counted in `opendal_errors_total` too.
+///
+/// # Example
+///
+/// ```
+/// use anyhow::Result;
+/// use opendal::layers::MetricsLayer;
+/// use opendal::services::memory;
+/// use opendal::Operator;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<()> {
+///     let op = Operator::new(memory::Backend::build().finish().await?).layer(MetricsLayer);
+///
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MetricsLayer;
+
+impl Layer for MetricsLayer {
+    fn layer(&self, inner: Arc<dyn Accessor>) -> Arc<dyn Accessor> {
+        let scheme = inner
+            .metadata()
+            .scheme()
+            .map(|v| v.as_str())
+            .unwrap_or("unknown");
+        Arc::new(MetricsAccessor { inner, scheme })
+    }
+}
+
+#[derive(Debug, Clone)]
+struct MetricsAccessor {
+    inner: Arc<dyn Accessor>,
+    scheme: &'static str,
+}
+
+impl MetricsAccessor {
+    async fn observe<T>(
+        &self,
+        op: &'static str,
+        fut: impl Future<Output = Result<T>>,
+    ) -> Result<T> {
+        let start = Instant::now();
+        let result = fut.await;
+        self.record(op, start, &result);
+        result
+    }
+
+    fn record<T>(&self, op: &'static str, start: Instant, result: &Result<T>) {
+        increment_counter!("opendal_requests_total", "scheme" => self.scheme, "operation" => op);
+        histogram!(
+            "opendal_request_duration_seconds",
+            start.elapsed().as_secs_f64(),
+            "scheme" => self.scheme,
+            "operation" => op
+        );
+        if let Err(err) = result {
+            record_error(self.scheme, op, err);
+        }
+    }
+
+    /// Count bytes of the stream while it's consumed.
+    fn count_read(&self, s: BytesStream) -> BytesStream {
+        let scheme = self.scheme;
+        Box::new(s.inspect(move |v| match v {
+            Ok(bs) => {
+                counter!("opendal_bytes_read_total", bs.len() as u64, "scheme" => scheme);
+            }
+            Err(err) => record_error(scheme, "read", err),
+        }))
+    }
+
+    /// Count bytes taken from the reader by the backend.
+    fn count_write(&self, r: BoxedAsyncReader) -> BoxedAsyncReader {
+        let scheme = self.scheme;
+        Box::new(CallbackReader::new(r, move |n| {
+            counter!("opendal_bytes_written_total", n as u64, "scheme" => scheme);
+        }))
+    }
+}
+
+fn record_error(scheme: &'static str, op: &'static str, err: &Error) {
+    increment_counter!(
+        "opendal_errors_total",
+        "scheme" => scheme,
+        "operation" => op,
+        "kind" => format!("{:?}", err.kind())
+    );
+}
+
+#[async_trait]
+impl Accessor for MetricsAccessor {
+    async fn read(&self, args: &OpRead) -> Result<BytesStream> {
+        let s = self.observe("read", self.inner.read(args)).await?;
+        Ok(self.count_read(s))
+    }
+    async fn read_mmap(&self, args: &OpRead) -> Result<Bytes> {
+        let bs = self.observe("read", self.inner.read_mmap(args)).await?;
+        counter!("opendal_bytes_read_total", bs.len() as u64, "scheme" => self.scheme);
+        Ok(bs)
+    }
+    async fn read_with_metadata(&self, args: &OpRead) -> Result<(BytesStream, Metadata)> {
+        let (s, meta) = self
+            .observe("read", self.inner.read_with_metadata(args))
+            .await?;
+        Ok((self.count_read(s), meta))
+    }
+    async fn write(&self, r: BoxedAsyncReader, args: &OpWrite) -> Result<Metadata> {
+        let r = self.count_write(r);
+        self.observe("write", self.inner.write(r, args)).await
+    }
+    async fn append(&self, r: BoxedAsyncReader, args: &OpAppend) -> Result<Metadata> {
+        let r = self.count_write(r);
+        self.observe("append", self.inner.append(r, args)).await
+    }
+    async fn create(&self, args: &OpCreate) -> Result<Metadata> {
+        self.observe("create", self.inner.create(args)).await
+    }
+    async fn stat(&self, args: &OpStat) -> Result<Metadata> {
+        self.observe("stat", self.inner.stat(args)).await
+    }
+    async fn delete(&self, args: &OpDelete) -> Result<()> {
+        self.observe("delete", self.inner.delete(args)).await
+    }
+    async fn batch(&self, args: &OpBatch) -> Result<Vec<(String, BatchResult)>> {
+        self.observe("batch", self.inner.batch(args)).await
+    }
+    async fn copy(&self, args: &OpCopy) -> Result<Metadata> {
+        self.observe("copy", self.inner.copy(args)).await
+    }
+    async fn rename(&self, args: &OpRename) -> Result<Metadata> {
+        self.observe("rename", self.inner.rename(args)).await
+    }
+    fn presign(&self, args: &OpPresign) -> Result<PresignedRequest> {
+        let start = Instant::now();
+        let result = self.inner.presign(args);
+        self.record("presign", start, &result);
+        result
+    }
+    async fn create_multipart(&self, args: &OpCreateMultipart) -> Result<String> {
+        self.observe("create_multipart", self.inner.create_multipart(args))
+            .await
+    }
+    async fn write_multipart(
+        &self,
+        r: BoxedAsyncReader,
+        args: &OpWriteMultipart,
+    ) -> Result<ObjectPart> {
+        let r = self.count_write(r);
+        self.observe("write_multipart", self.inner.write_multipart(r, args))
+            .await
+    }
+    async fn complete_multipart(&self, args: &OpCompleteMultipart) -> Result<Metadata> {
+        self.observe("complete_multipart", self.inner.complete_multipart(args))
+            .await
+    }
+    async fn abort_multipart(&self, args: &OpAbortMultipart) -> Result<()> {
+        self.observe("abort_multipart", self.inner.abort_multipart(args))
+            .await
+    }
+    async fn list(&self, args: &OpList) -> Result<BoxedObjectStream> {
+        let obs = self.observe("list", self.inner.list(args)).await?;
+
+        Ok(rebind(obs, Arc::new(self.clone())))
+    }
+    async fn scan(&self, args: &OpScan) -> Result<BoxedObjectStream> {
+        let obs = self.observe("scan", self.inner.scan(args)).await?;
+
+        Ok(rebind(obs, Arc::new(self.clone())))
+    }
+
+    fn metadata(&self) -> AccessorMetadata {
+        self.inner.metadata()
+    }
+}
@@ -0,0 +1,814 @@
+// Copyright 2022 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Mirror operations to a shadow backend for comparison.
+
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::fmt::Formatter;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::task::Context;
+use std::task::Poll;
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures::channel::oneshot;
+use futures::io::Cursor;
+use futures::ready;
+use futures::AsyncReadExt;
+use futures::Stream;
+use futures::StreamExt;
+use log::debug;
+use metrics::increment_counter;
+use tokio::sync::Semaphore;
+
+use super::rebind;
+use crate::accessor::batch_each;
+use crate::error::Error;
+use crate::error::Kind;
+use crate::error::Result;
+use crate::io::BytesStream;
+use crate::ops::BatchResult;
+use crate::ops::ObjectPart;
+use crate::ops::OpAbortMultipart;
+use crate::ops::OpAppend;
+use crate::ops::OpBatch;
+use crate::ops::OpCompleteMultipart;
+use crate::ops::OpCopy;
+use crate::ops::OpCreate;
+use crate::ops::OpCreateMultipart;
+use crate::ops::OpDelete;
+use crate::ops::OpList;
+use crate::ops::OpPresign;
+use crate::ops::OpRead;
+use crate::ops::OpRename;
+use crate::ops::OpScan;
+use crate::ops::OpStat;
+use crate::ops::OpWrite;
+use crate::ops::OpWriteMultipart;
+use crate::ops::PresignedRequest;
+use crate::shutdown::InFlight;
+use crate::Accessor;
+use crate::AccessorMetadata;
+use crate::BoxedAsyncReader;
+use crate::BoxedObjectStream;
+use crate::Close;
+use crate::Layer;
+use crate::Metadata;
+use crate::Operator;
+
+/// MirrorPolicy selects the operations to mirror, and how many of them.
+///
+/// By default, `read` and `stat` are mirrored, `write` and `delete` are not.
+#[derive(Debug, Clone, Copy)]
+pub struct MirrorPolicy {
+    rate: f64,
+    read: bool,
+    stat: bool,
+    write: bool,
+    delete: bool,
+    content_hash: bool,
+    concurrency: usize,
+    queue_size: usize,
+}
+
+impl MirrorPolicy {
+    /// Create a policy which mirrors `rate` of the selected operations,
+    /// like `0.01` for 1%.
+    ///
+    /// Operations are sampled evenly by their order instead of randomly, so
+    /// `rate` of `0.25` mirrors exactly one in every four operations.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `rate` is not in `[0.0, 1.0]`.
+    pub fn new(rate: f64) -> Self {
+        assert!((0.0..=1.0).contains(&rate), "rate must be in [0.0, 1.0]");
+
+        Self {
+            rate,
+            read: true,
+            stat: true,
+            write: false,
+            delete: false,
+            content_hash: false,
+            concurrency: 8,
+            queue_size: 64,
+        }
+    }
+
+    /// Mirror `read`, default to `true`.
+    pub fn read(mut self, enabled: bool) -> Self {
+        self.read = enabled;
+        self
+    }
+
+    /// Mirror `stat`, default to `true`.
+    pub fn stat(mut self, enabled: bool) -> Self {
+        self.stat = enabled;
+        self
+    }
+
+    /// Mirror `write`, `create`, `copy` and `rename`, default to `false`.
+    ///
+    /// The content of sampled writes is buffered in memory, so that it can
+    /// be written to the shadow again. Other operations are replayed on the
+    /// shadow as they are.
+    pub fn write(mut self, enabled: bool) -> Self {
+        self.write = enabled;
+        self
+    }
+
+    /// Mirror `delete`, default to `false`.
+    pub fn delete(mut self, enabled: bool) -> Self {
+        self.delete = enabled;
+        self
+    }
+
+    /// Compare the MD5 of the content of sampled reads, default to `false`.
+    pub fn content_hash(mut self, enabled: bool) -> Self {
+        self.content_hash = enabled;
+        self
+    }
+
+    /// Replay at most `concurrency` operations against the shadow at the
+    /// same time, default to `8`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `concurrency` is 0.
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        assert!(concurrency > 0, "concurrency must be at least 1");
+
+        self.concurrency = concurrency;
+        self
+    }
+
+    /// Keep at most `queue_size` sampled operations waiting for replay,
+    /// default to `64`. Operations sampled while the queue is full are
+    /// dropped.
+    pub fn queue_size(mut self, queue_size: usize) -> Self {
+        self.queue_size = queue_size;
+        self
+    }
+}
+
+/// Outcome of an operation on one side of the mirror.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MirrorOutcome {
+    /// Kind of the error, `None` if the operation succeeded.
+    pub error: Option<Kind>,
+    /// Size of the object for `stat` and `write`, or bytes read for `read`.
+    pub size: Option<u64>,
+    /// ETag of the object if returned.
+    pub etag: Option<String>,
+    /// Hex encoded MD5 of the content read, only set while
+    /// [`MirrorPolicy::content_hash`] is enabled.
+    pub content_md5: Option<String>,
+}
+
+impl MirrorOutcome {
+    fn from_result<T>(r: &Result<T>) -> Self {
+        Self {
+            error: r.as_ref().err().map(|e| e.kind()),
+            ..Default::default()
+        }
+    }
+
+    fn from_metadata(r: &Result<Metadata>) -> Self {
+        let mut outcome = Self::from_result(r);
+        if let Ok(meta) = r {
+            outcome.size = meta.try_content_length();
+            outcome.etag = meta.etag();
+        }
+        outcome
+    }
+}
+
+/// Comparison of an operation mirrored to the shadow, passed to the
+/// comparator of [`MirrorLayer`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MirrorComparison {
+    /// Operation like `read` and `stat`.
+    pub op: &'static str,
+    /// Path of the operation.
+    pub path: String,
+    /// Outcome of the primary.
+    pub primary: MirrorOutcome,
+    /// Outcome of the shadow.
+    pub shadow: MirrorOutcome,
+}
+
+impl MirrorComparison {
+    /// Whether the shadow diverged from the primary.
+    ///
+    /// Outcomes diverge if their errors differ, or their sizes or content
+    /// MD5s differ while both are known. ETags are not compared, since
+    /// vendors calculate them differently.
+    pub fn diverged(&self) -> bool {
+        self.primary.error != self.shadow.error
+            || differ(&self.primary.size, &self.shadow.size)
+            || differ(&self.primary.content_md5, &self.shadow.content_md5)
+    }
+}
+
+/// Whether both values are known and differ.
+fn differ<T: PartialEq>(a: &Option<T>, b: &Option<T>) -> bool {
+    a.is_some() && b.is_some() && a != b
+}
+
+type Comparator = Arc<dyn Fn(&MirrorComparison) + Send + Sync>;
+
+/// MirrorLayer replays sampled operations against a shadow backend, and
+/// compares their outcomes with the primary, which is the operator this
+/// layer is applied on.
+///
+/// Results of the primary are returned to callers unchanged. Replays run
+/// in spawned tasks after the primary operation, shadow failures are only
+/// recorded and never returned. Replays are limited by
+/// [`MirrorPolicy::concurrency`], and sampled operations are dropped
+/// instead of waiting if [`MirrorPolicy::queue_size`] is exceeded, or
+/// there is no tokio runtime.
+///
+/// Reads are compared after the caller consumes the returned stream, reads
+/// that are not consumed to the end are not compared. `list` is never
+/// mirrored.
+///
+/// # Metrics
+///
+/// - `opendal_mirror_sampled`: operations sampled for mirroring.
+/// - `opendal_mirror_dropped`: sampled operations dropped by overload.
+/// - `opendal_mirror_compared`: operations compared with the shadow.
+/// - `opendal_mirror_diverged`: compared operations that diverged.
+///
+/// All are labeled by `operation`.
+///
+/// # Shutdown
+///
+/// MirrorLayer is closed by [`Close`], which stops mirroring and waits for
+/// pending replays of all operators it is applied on, so that their
+/// comparisons are not lost. Clones of the layer share the same state.
+///
+/// # Example
+///
+/// ```
+/// use anyhow::Result;
+/// use log::warn;
+/// use opendal::layers::mirror::MirrorLayer;
+/// use opendal::layers::mirror::MirrorPolicy;
+/// use opendal::services::memory;
+/// use opendal::Operator;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<()> {
+///     let shadow = Operator::new(memory::Backend::build().finish().await?);
+///     let mirror = MirrorLayer::new(shadow, MirrorPolicy::new(0.1).content_hash(true))
+///         .comparator(|c| {
+///             if c.diverged() {
+///                 warn!("mirror diverged: {:?}", c);
+///             }
+///         });
+///     let op = Operator::new(memory::Backend::build().finish().await?).layer(mirror);
+///
+///     op.object("test").writer().write_bytes(vec![0; 4]).await?;
+///     let _ = op.object("test").metadata().await?;
+///
+///     Ok(())
+/// }
+/// ```
+#[derive(Clone)]
+pub struct MirrorLayer {
+    shadow: Operator,
+    policy: MirrorPolicy,
+    comparator: Option<Comparator>,
+    closed: Arc<AtomicBool>,
+    in_flight: Arc<InFlight>,
+}
+
+impl Debug for MirrorLayer {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MirrorLayer")
+            .field("shadow", &self.shadow.inner())
+            .field("policy", &self.policy)
+            .finish()
+    }
+}
+
+impl MirrorLayer {
+    /// Create a new layer which mirrors operations selected by `policy` to
+    /// `shadow`.
+    pub fn new(shadow: Operator, policy: MirrorPolicy) -> Self {
+        Self {
+            shadow,
+            policy,
+            comparator: None,
+            closed: Arc::new(AtomicBool::new(false)),
+            in_flight: Arc::new(InFlight::default()),
+        }
+    }
+
+    /// Call `f` with every comparison, for recording divergences.
+    ///
+    /// `f` is called in the replaying task, it should not block.
+    pub fn comparator(mut self, f: impl Fn(&MirrorComparison) + Send + Sync + 'static) -> Self {
+        self.comparator = Some(Arc::new(f));
+        self
+    }
+}
+
+impl Layer for MirrorLayer {
+    fn layer(&self, inner: Arc<dyn Accessor>) -> Arc<dyn Accessor> {
+        Arc::new(MirrorAccessor {
+            inner,
+            state: Arc::new(MirrorState {
+                shadow: self.shadow.inner(),
+                policy: self.policy,
+                comparator: self.comparator.clone(),
+                sampled: AtomicU64::new(0),
+                pending: AtomicUsize::new(0),
+                permits: Semaphore::new(self.policy.concurrency),
+                closed: self.closed.clone(),
+                in_flight: self.in_flight.clone(),
+            }),
+        })
+    }
+}
+
+#[async_trait]
+impl Close for MirrorLayer {
+    /// Stop mirroring, and wait for pending replays.
+    async fn close(&mut self) -> Result<()> {
+        self.closed.store(true, Ordering::Release);
+        self.in_flight.wait().await;
+        Ok(())
+    }
+}
+
+struct MirrorState {
+    shadow: Arc<dyn Accessor>,
+    policy: MirrorPolicy,
+    comparator: Option<Comparator>,
+    /// Count of operations that have been considered for sampling.
+    sampled: AtomicU64,
+    /// Count of replays queued or running.
+    pending: AtomicUsize,
+    permits: Semaphore,
+    closed: Arc<AtomicBool>,
+    in_flight: Arc<InFlight>,
+}
+
+impl Debug for MirrorState {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MirrorState")
+            .field("shadow", &self.shadow)
+            .field("policy", &self.policy)
+            .field("pending", &self.pending)
+            .finish()
+    }
+}
+
+impl MirrorState {
+    /// Decide whether to mirror this operation, which spreads sampled
+    /// operations evenly by `rate`. Nothing is mirrored once closed.
+    fn sample(&self, op: &'static str, enabled: bool) -> bool {
+        if !enabled || self.closed.load(Ordering::Acquire) {
+            return false;
+        }
+
+        let n = self.sampled.fetch_add(1, Ordering::Relaxed) as f64;
+        let rate = self.policy.rate;
+        let sampled = ((n + 1.0) * rate).floor() > (n * rate).floor();
+        if sampled {
+            increment_counter!("opendal_mirror_sampled", "operation" => op);
+        }
+        sampled
+    }
+
+    /// Spawn the replay, which resolves to the comparison, or `None` if it
+    /// can't be compared.
+    fn spawn<F>(self: &Arc<Self>, op: &'static str, path: &str, replay: F)
+    where
+        F: Future<Output = Option<MirrorComparison>> + Send + 'static,
+    {
+        let handle = match tokio::runtime::Handle::try_current() {
+            Ok(handle) => handle,
+            Err(_) => return self.drop_replay(op, path),
+        };
+
+        let limit = self.policy.concurrency + self.policy.queue_size;
+        if self
+            .pending
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |v| {
+                (v < limit).then_some(v + 1)
+            })
+            .is_err()
+        {
+            return self.drop_replay(op, path);
+        }
+
+        let state = self.clone();
+        let token = self.in_flight.enter();
+        handle.spawn(async move {
+            if let Some(c) = replay.await {
+                state.compare(&c);
+            }
+            state.pending.fetch_sub(1, Ordering::AcqRel);
+            drop(token);
+        });
+    }
+
+    fn drop_replay(&self, op: &'static str, path: &str) {
+        debug!("mirror {} {} dropped by overload", op, path);
+        increment_counter!("opendal_mirror_dropped", "operation" => op);
+    }
+
+    fn compare(&self, c: &MirrorComparison) {
+        increment_counter!("opendal_mirror_compared", "operation" => c.op);
+        if c.diverged() {
+            debug!("mirror {} {} diverged: {:?}", c.op, c.path, c);
+            increment_counter!("opendal_mirror_diverged", "operation" => c.op);
+        }
+        if let Some(f) = &self.comparator {
+            f(c)
+        }
+    }
+
+    /// Read from the shadow, and consume the stream to get the outcome.
+    async fn shadow_read(&self, args: &OpRead) -> MirrorOutcome {
+        let mut s = match self.shadow.read(args).await {
+            Ok(s) => s,
+            Err(err) => return MirrorOutcome::from_result::<()>(&Err(err)),
+        };
+
+        let mut digest = ContentDigest::new(self.policy.content_hash);
+        while let Some(v) = s.next().await {
+            match v {
+                Ok(bs) => digest.consume(&bs),
+                Err(err) => return MirrorOutcome::from_result::<()>(&Err(err)),
+            }
+        }
+        digest.outcome()
+    }
+}
+
+/// ContentDigest counts the size and optionally the MD5 of the content.
+struct ContentDigest {
+    size: u64,
+    md5: Option<md5::Context>,
+}
+
+impl ContentDigest {
+    fn new(content_hash: bool) -> Self {
+        Self {
+            size: 0,
+            md5: content_hash.then(md5::Context::new),
+        }
+    }
+
+    fn consume(&mut self, bs: &[u8]) {
+        self.size += bs.len() as u64;
+        if let Some(md5) = &mut self.md5 {
+            md5.consume(bs);
+        }
+    }
+
+    fn outcome(self) -> MirrorOutcome {
+        MirrorOutcome {
+            size: Some(self.size),
+            content_md5: self.md5.map(|v| format!("{:x}", v.compute())),
+            ..Default::default()
+        }
+    }
+}
+
+/// MirrorStream returns the primary stream as is, and sends its outcome
+/// once it's consumed to the end or failed.
+struct MirrorStream {
+    inner: BytesStream,
+    digest: Option<ContentDigest>,
+    tx: Option<oneshot::Sender<MirrorOutcome>>,
+}
+
+impl Stream for MirrorStream {
+    type Item = Result<Bytes>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let v = ready!(self.inner.poll_next_unpin(cx));
+        let outcome = match &v {
+            Some(Ok(bs)) => {
+                if let Some(digest) = &mut self.digest {
+                    digest.consume(bs);
+                }
+                None
+            }
+            Some(Err(err)) => Some(MirrorOutcome {
+                error: Some(err.kind()),
+                ..Default::default()
+            }),
+            None => self.digest.take().map(|v| v.outcome()),
+        };
+        if let Some(outcome) = outcome {
+            if let Some(tx) = self.tx.take() {
+                let _ = tx.send(outcome);
+            }
+        }
+        Poll::Ready(v)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct MirrorAccessor {
+    inner: Arc<dyn Accessor>,
+    state: Arc<MirrorState>,
+}
+
+impl MirrorAccessor {
+    /// Mirror the read whose primary returned `r`, and wrap the primary
+    /// stream to get its outcome.
+    fn mirror_read(&self, args: &OpRead, r: Result<BytesStream>) -> Result<BytesStream> {
+        let (s, rx) = match r {
+            Ok(s) => {
+                let (tx, rx) = oneshot::channel();
+                let s: BytesStream = Box::new(MirrorStream {
+                    inner: s,
+                    digest: Some(ContentDigest::new(self.state.policy.content_hash)),
+                    tx: Some(tx),
+                });
+                (Ok(s), Some(rx))
+            }
+            Err(err) => (Err(err), None),
+        };
+        let primary = MirrorOutcome::from_result(&s);
+
+        let state = self.state.clone();
+        let replay = args.clone();
+        self.state.spawn("read", &args.path, async move {
+            let shadow = {
+                let _permit = state.permits.acquire().await.ok()?;
+                state.shadow_read(&replay).await
+            };
+            let primary = match rx {
+                // Reads not consumed to the end drop the sender.
+                Some(rx) => rx.await.ok()?,
+                None => primary,
+            };
+            Some(MirrorComparison {
+                op: "read",
+                path: replay.path,
+                primary,
+                shadow,
+            })
+        });
+        s
+    }
+}
+
+#[async_trait]
+impl Accessor for MirrorAccessor {
+    async fn read(&self, args: &OpRead) -> Result<BytesStream> {
+        let r = self.inner.read(args).await;
+        if !self.state.sample("read", self.state.policy.read) {
+            return r;
+        }
+        self.mirror_read(args, r)
+    }
+    async fn read_mmap(&self, args: &OpRead) -> Result<Bytes> {
+        let r = self.inner.read_mmap(args).await;
+        if !self.state.sample("read", self.state.policy.read) {
+            return r;
+        }
+
+        let mut primary = MirrorOutcome::from_result(&r);
+        if let Ok(bs) = &r {
+            let mut digest = ContentDigest::new(self.state.policy.content_hash);
+            digest.consume(bs);
+            primary = digest.outcome();
+        }
+        let state = self.state.clone();
+        let replay = args.clone();
+        self.state.spawn("read", &args.path, async move {
+            let _permit = state.permits.acquire().await.ok()?;
+            Some(MirrorComparison {
+                op: "read",
+                shadow: state.shadow_read(&replay).await,
+                path: replay.path,
+                primary,
+            })
+        });
+        r
+    }
+    async fn read_with_metadata(&self, args: &OpRead) -> Result<(BytesStream, Metadata)> {
+        let r = self.inner.read_with_metadata(args).await;
+        if !self.state.sample("read", self.state.policy.read) {
+            return r;
+        }
+
+        let (r, meta) = match r {
+            Ok((s, meta)) => (Ok(s), Some(meta)),
+            Err(err) => (Err(err), None),
+        };
+        let s = self.mirror_read(args, r)?;
+        Ok((s, meta.expect("metadata must exist for succeeded reads")))
+    }
+    async fn write(&self, mut r: BoxedAsyncReader, args: &OpWrite) -> Result<Metadata> {
+        if !self.state.sample("write", self.state.policy.write) {
+            return self.inner.write(r, args).await;
+        }
+
+        let mut buf = Vec::new();
+        r.read_to_end(&mut buf).await.map_err(|e| Error::Object {
+            kind: Kind::Unexpected,
+            op: "write",
+            path: args.path.to_string(),
+            context: HashMap::new(),
+            source: anyhow::Error::from(e),
+        })?;
+        let bs = Bytes::from(buf);
+
+        let result = self
+            .inner
+            .write(Box::new(Cursor::new(bs.clone())), args)
+            .await;
+        let primary = MirrorOutcome::from_metadata(&result);
+
+        let state = self.state.clone();
+        let replay = args.clone();
+        self.state.spawn("write", &args.path, async move {
+            let _permit = state.permits.acquire().await.ok()?;
+            let shadow = state.shadow.write(Box::new(Cursor::new(bs)), &replay).await;
+            Some(MirrorComparison {
+                op: "write",
+                path: replay.path,
+                primary,
+                shadow: MirrorOutcome::from_metadata(&shadow),
+            })
+        });
+        result
+    }
+    // Appends are fenced by the length of the object, which could differ
+    // between the primary and the shadow, they are not mirrored.
+    async fn append(&self, r: BoxedAsyncReader, args: &OpAppend) -> Result<Metadata> {
+        self.inner.append(r, args).await
+    }
+    async fn create(&self, args: &OpCreate) -> Result<Metadata> {
+        let result = self.inner.create(args).await;
+        if !self.state.sample("create", self.state.policy.write) {
+            return result;
+        }
+
+        let primary = MirrorOutcome::from_metadata(&result);
+        let state = self.state.clone();
+        let replay = args.clone();
+        self.state.spawn("create", &args.path, async move {
+            let _permit = state.permits.acquire().await.ok()?;
+            let shadow = state.shadow.create(&replay).await;
+            Some(MirrorComparison {
+                op: "create",
+                path: replay.path,
+                primary,
+                shadow: MirrorOutcome::from_metadata(&shadow),
+            })
+        });
+        result
+    }
+    async fn stat(&self, args: &OpStat) -> Result<Metadata> {
+        let result = self.inner.stat(args).await;
+        if !self.state.sample("stat", self.state.policy.stat) {
+            return result;
+        }
+
+        let primary = MirrorOutcome::from_metadata(&result);
+        let state = self.state.clone();
+        let replay = args.clone();
+        self.state.spawn("stat", &args.path, async move {
+            let _permit = state.permits.acquire().await.ok()?;
+            let shadow = state.shadow.stat(&replay).await;
+            Some(MirrorComparison {
+                op: "stat",
+                path: replay.path,
+                primary,
+                shadow: MirrorOutcome::from_metadata(&shadow),
+            })
+        });
+        result
+    }
+    async fn delete(&self, args: &OpDelete) -> Result<()> {
+        let result = self.inner.delete(args).await;
+        if !self.state.sample("delete", self.state.policy.delete) {
+            return result;
+        }
+
+        let primary = MirrorOutcome::from_result(&result);
+        let state = self.state.clone();
+        let replay = args.clone();
+        self.state.spawn("delete", &args.path, async move {
+            let _permit = state.permits.acquire().await.ok()?;
+            let shadow = state.shadow.delete(&replay).await;
+            Some(MirrorComparison {
+                op: "delete",
+                path: replay.path,
+                primary,
+                shadow: MirrorOutcome::from_result(&shadow),
+            })
+        });
+        result
+    }
+    async fn batch(&self, args: &OpBatch) -> Result<Vec<(String, BatchResult)>> {
+        // Sub-operations are mirrored one by one.
+        Ok(batch_each(self, args).await)
+    }
+    async fn copy(&self, args: &OpCopy) -> Result<Metadata> {
+        let result = self.inner.copy(args).await;
+        if !self.state.sample("copy", self.state.policy.write) {
+            return result;
+        }
+
+        let primary = MirrorOutcome::from_metadata(&result);
+        let state = self.state.clone();
+        let replay = args.clone();
+        self.state.spawn("copy", &args.dst, async move {
+            let _permit = state.permits.acquire().await.ok()?;
+            let shadow = state.shadow.copy(&replay).await;
+            Some(MirrorComparison {
+                op: "copy",
+                path: replay.dst,
+                primary,
+                shadow: MirrorOutcome::from_metadata(&shadow),
+            })
+        });
+        result
+    }
+    async fn rename(&self, args: &OpRename) -> Result<Metadata> {
+        let result = self.inner.rename(args).await;
+        if !self.state.sample("rename", self.state.policy.write) {
+            return result;
+        }
+
+        let primary = MirrorOutcome::from_metadata(&result);
+        let state = self.state.clone();
+        let replay = args.clone();
+        self.state.spawn("rename", &args.dst, async move {
+            let _permit = state.permits.acquire().await.ok()?;
+            let shadow = state.shadow.rename(&replay).await;
+            Some(MirrorComparison {
+                op: "rename",
+                path: replay.dst,
+                primary,
+                shadow: MirrorOutcome::from_metadata(&shadow),
+            })
+        });
+        result
+    }
+    fn presign(&self, args: &OpPresign) -> Result<PresignedRequest> {
+        self.inner.presign(args)
+    }
+    // Upload ids of the primary are unknown to the shadow, multipart
+    // uploads are not mirrored.
+    async fn create_multipart(&self, args: &OpCreateMultipart) -> Result<String> {
+        self.inner.create_multipart(args).await
+    }
+    async fn write_multipart(
+        &self,
+        r: BoxedAsyncReader,
+        args: &OpWriteMultipart,
+    ) -> Result<ObjectPart> {
+        self.inner.write_multipart(r, args).await
+    }
+    async fn complete_multipart(&self, args: &OpCompleteMultipart) -> Result<Metadata> {
+        self.inner.complete_multipart(args).await
+    }
+    async fn abort_multipart(&self, args: &OpAbortMultipart) -> Result<()> {
+        self.inner.abort_multipart(args).await
+    }
+    async fn list(&self, args: &OpList) -> Result<BoxedObjectStream> {
+        let obs = self.inner.list(args).await?;
+
+        Ok(rebind(obs, Arc::new(self.clone())))
+    }
+    async fn scan(&self, args: &OpScan) -> Result<BoxedObjectStream> {
+        let obs = self.inner.scan(args).await?;
+
+        Ok(rebind(obs, Arc::new(self.clone())))
+    }
+
+    fn metadata(&self) -> AccessorMetadata {
+        self.inner.metadata()
+    }
+}
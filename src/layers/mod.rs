@@ -0,0 +1,158 @@
+// Copyright 2022 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Providing layers that can be applied on [`Operator`][crate::Operator].
+//!
+//! Every layer implements [`Layer`][crate::Layer], and can be used like:
+//!
+//! ```
+//! use anyhow::Result;
+//! use opendal::layers::ImmutableLayer;
+//! use opendal::services::memory;
+//! use opendal::Operator;
+//!
+//! #[tokio::main]
+//! async fn main() -> Result<()> {
+//!     let op = Operator::new(memory::Backend::build().finish().await?).layer(ImmutableLayer);
+//!
+//!     Ok(())
+//! }
+//! ```
+
+mod cache;
+pub use cache::CacheLayer;
+pub use cache::CacheRange;
+
+#[cfg(feature = "chaos")]
+mod chaos;
+#[cfg(feature = "chaos")]
+pub use chaos::ChaosLayer;
+
+mod concurrency_limit;
+pub use concurrency_limit::ConcurrencyLimitLayer;
+
+mod cost;
+pub use cost::CostLayer;
+pub use cost::CostReport;
+pub use cost::PriceTable;
+pub use cost::Usage;
+
+pub(crate) mod glob;
+
+mod immutable;
+pub use immutable::ImmutableLayer;
+
+mod logging;
+pub use logging::LoggingLayer;
+
+pub mod manifest;
+
+mod metrics;
+pub use self::metrics::MetricsLayer;
+
+pub mod mirror;
+
+pub mod policy;
+
+pub mod qos;
+
+mod request_id;
+pub use request_id::RequestIdLayer;
+
+mod retry;
+pub use retry::Backoff;
+pub use retry::ExponentialBackoff;
+pub use retry::RetryLayer;
+
+mod scope_guard;
+pub use scope_guard::ScopeGuardLayer;
+
+mod subdir;
+pub use subdir::SubdirLayer;
+
+mod throttle;
+pub use throttle::ThrottleLayer;
+
+mod timeout;
+pub use timeout::TimeoutLayer;
+
+#[cfg(feature = "tracing")]
+mod tracing;
+#[cfg(feature = "tracing")]
+pub use self::tracing::TracingLayer;
+
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::Context;
+use std::task::Poll;
+
+use futures::ready;
+use futures::Stream;
+use futures::StreamExt;
+
+use crate::Accessor;
+use crate::BoxedObjectStream;
+
+/// Bind objects listed by `obs` to `acc`, so that operations on them go
+/// through the layer instead of the accessor it wraps.
+pub(crate) fn rebind(obs: BoxedObjectStream, acc: Arc<dyn Accessor>) -> BoxedObjectStream {
+    Box::new(obs.map(move |o| {
+        let mut o = o?;
+        o.set_accessor(acc.clone());
+        Ok(o)
+    }))
+}
+
+/// PermitStream holds the permit of a limiting layer while the inner stream
+/// is in use.
+pub(crate) struct PermitStream<S, P> {
+    inner: S,
+    permit: Option<P>,
+    until_first: bool,
+}
+
+impl<S, P> PermitStream<S, P> {
+    /// Hold `permit` until `inner` is exhausted or dropped.
+    pub(crate) fn new(inner: S, permit: P) -> Self {
+        Self {
+            inner,
+            permit: Some(permit),
+            until_first: false,
+        }
+    }
+
+    /// Hold `permit` until the first item of `inner` is ready.
+    ///
+    /// Used by listings, so that operations on listed objects don't wait
+    /// for the listing which yields them.
+    pub(crate) fn until_first(inner: S, permit: P) -> Self {
+        Self {
+            inner,
+            permit: Some(permit),
+            until_first: true,
+        }
+    }
+}
+
+impl<S: Stream + Unpin, P: Unpin> Stream for PermitStream<S, P> {
+    type Item = S::Item;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let v = ready!(self.inner.poll_next_unpin(cx));
+        if v.is_none() || self.until_first {
+            self.permit = None;
+        }
+        Poll::Ready(v)
+    }
+}
@@ -0,0 +1,408 @@
+// Copyright 2022 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Policies that decide write options by object path and size.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use arc_swap::ArcSwap;
+use async_trait::async_trait;
+use bytes::Bytes;
+use metrics::increment_counter;
+
+use super::glob::Glob;
+use super::rebind;
+use crate::error::Result;
+use crate::io::BytesStream;
+use crate::ops::BatchResult;
+use crate::ops::ObjectPart;
+use crate::ops::OpAbortMultipart;
+use crate::ops::OpAppend;
+use crate::ops::OpBatch;
+use crate::ops::OpCompleteMultipart;
+use crate::ops::OpCopy;
+use crate::ops::OpCreate;
+use crate::ops::OpCreateMultipart;
+use crate::ops::OpDelete;
+use crate::ops::OpList;
+use crate::ops::OpPresign;
+use crate::ops::OpRead;
+use crate::ops::OpRename;
+use crate::ops::OpScan;
+use crate::ops::OpStat;
+use crate::ops::OpWrite;
+use crate::ops::OpWriteMultipart;
+use crate::ops::PresignedRequest;
+use crate::Accessor;
+use crate::AccessorMetadata;
+use crate::BoxedAsyncReader;
+use crate::BoxedObjectStream;
+use crate::Layer;
+use crate::Metadata;
+
+/// StorageClassRule decides storage class, tags and cache-control for writes
+/// whose path matches the glob and whose size fulfills the size predicate.
+#[derive(Debug, Clone)]
+pub struct StorageClassRule {
+    glob: Glob,
+    min_size: Option<u64>,
+    max_size: Option<u64>,
+
+    storage_class: Option<String>,
+    cache_control: Option<String>,
+    tags: HashMap<String, String>,
+}
+
+impl StorageClassRule {
+    /// Create a rule that matches paths by glob pattern.
+    ///
+    /// - `?` matches any single char except `/`.
+    /// - `*` matches any sequence of chars except `/`.
+    /// - `**` matches any sequence of chars including `/`.
+    pub fn new(pattern: &str) -> Self {
+        Self {
+            glob: Glob::new(pattern),
+            min_size: None,
+            max_size: None,
+
+            storage_class: None,
+            cache_control: None,
+            tags: HashMap::new(),
+        }
+    }
+
+    /// Only match writes whose size is larger than or equal to `size`.
+    #[must_use]
+    pub fn min_size(mut self, size: u64) -> Self {
+        self.min_size = Some(size);
+        self
+    }
+
+    /// Only match writes whose size is smaller than `size`.
+    #[must_use]
+    pub fn max_size(mut self, size: u64) -> Self {
+        self.max_size = Some(size);
+        self
+    }
+
+    /// Set storage class for matched writes.
+    #[must_use]
+    pub fn storage_class(mut self, storage_class: &str) -> Self {
+        self.storage_class = Some(storage_class.to_string());
+        self
+    }
+
+    /// Set `Cache-Control` for matched writes.
+    #[must_use]
+    pub fn cache_control(mut self, cache_control: &str) -> Self {
+        self.cache_control = Some(cache_control.to_string());
+        self
+    }
+
+    /// Attach a tag to matched writes.
+    #[must_use]
+    pub fn tag(mut self, key: &str, value: &str) -> Self {
+        self.tags.insert(key.to_string(), value.to_string());
+        self
+    }
+
+    fn requires_size(&self) -> bool {
+        self.min_size.is_some() || self.max_size.is_some()
+    }
+
+    /// Check whether this rule matches the write.
+    ///
+    /// Returns `None` if this rule requires size but the size is unknown.
+    fn matches(&self, path: &str, size: Option<u64>) -> Option<bool> {
+        if !self.glob.matches(path) {
+            return Some(false);
+        }
+        if !self.requires_size() {
+            return Some(true);
+        }
+
+        let size = size?;
+        Some(
+            self.min_size.map(|v| size >= v).unwrap_or(true)
+                && self.max_size.map(|v| size < v).unwrap_or(true),
+        )
+    }
+
+    /// Merge values of this rule into the write, explicit values in write win.
+    fn apply(&self, args: &mut OpWrite) {
+        if args.storage_class.is_none() {
+            args.storage_class = self.storage_class.clone();
+        }
+        if args.cache_control.is_none() {
+            args.cache_control = self.cache_control.clone();
+        }
+        for (k, v) in &self.tags {
+            args.tags.entry(k.clone()).or_insert_with(|| v.clone());
+        }
+    }
+}
+
+/// StorageClassPolicyLayer applies ordered [`StorageClassRule`]s on every write.
+///
+/// # Evaluation
+///
+/// - Rules are evaluated in order, the first matched rule wins and the rest will be ignored.
+/// - Values of the matched rule are merged into `OpWrite`, values set explicitly on the
+///   write (including tags with the same key) take precedence.
+/// - Rules with size predicates are skipped while the write's size is unknown, and
+///   `opendal_storage_class_policy_rules_skipped` will be increased.
+///
+/// # Reload
+///
+/// Rules can be replaced at runtime by [`StorageClassPolicyLayer::reload`], which takes
+/// effect on all operators this layer has been applied to. In-flight writes keep using
+/// the rules they have loaded.
+///
+/// # Example
+///
+/// ```
+/// use anyhow::Result;
+/// use opendal::layers::policy::StorageClassPolicyLayer;
+/// use opendal::layers::policy::StorageClassRule;
+/// use opendal::services::memory;
+/// use opendal::Operator;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<()> {
+///     let policy = StorageClassPolicyLayer::new(vec![
+///         StorageClassRule::new("tmp/**")
+///             .storage_class("STANDARD")
+///             .tag("expiry", "7d"),
+///         StorageClassRule::new("archive/**")
+///             .min_size(1024 * 1024 * 1024)
+///             .storage_class("GLACIER_IR"),
+///         StorageClassRule::new("**").storage_class("STANDARD_IA"),
+///     ]);
+///
+///     let op = Operator::new(memory::Backend::build().finish().await?).layer(policy.clone());
+///
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct StorageClassPolicyLayer {
+    rules: Arc<ArcSwap<Vec<StorageClassRule>>>,
+}
+
+impl StorageClassPolicyLayer {
+    /// Create a new policy layer with ordered rules.
+    pub fn new(rules: Vec<StorageClassRule>) -> Self {
+        Self {
+            rules: Arc::new(ArcSwap::from_pointee(rules)),
+        }
+    }
+
+    /// Replace all rules.
+    pub fn reload(&self, rules: Vec<StorageClassRule>) {
+        self.rules.store(Arc::new(rules));
+    }
+}
+
+impl Layer for StorageClassPolicyLayer {
+    fn layer(&self, inner: Arc<dyn Accessor>) -> Arc<dyn Accessor> {
+        Arc::new(StorageClassPolicyAccessor {
+            inner,
+            rules: self.rules.clone(),
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+struct StorageClassPolicyAccessor {
+    inner: Arc<dyn Accessor>,
+    rules: Arc<ArcSwap<Vec<StorageClassRule>>>,
+}
+
+impl StorageClassPolicyAccessor {
+    fn evaluate(&self, args: &OpWrite, size: Option<u64>) -> OpWrite {
+        let mut args = args.clone();
+
+        let rules = self.rules.load();
+        for rule in rules.iter() {
+            match rule.matches(&args.path, size) {
+                Some(true) => {
+                    rule.apply(&mut args);
+                    break;
+                }
+                Some(false) => continue,
+                None => {
+                    increment_counter!("opendal_storage_class_policy_rules_skipped");
+                    continue;
+                }
+            }
+        }
+
+        args
+    }
+}
+
+#[async_trait]
+impl Accessor for StorageClassPolicyAccessor {
+    async fn read(&self, args: &OpRead) -> Result<BytesStream> {
+        self.inner.read(args).await
+    }
+    async fn read_mmap(&self, args: &OpRead) -> Result<Bytes> {
+        self.inner.read_mmap(args).await
+    }
+    async fn read_with_metadata(&self, args: &OpRead) -> Result<(BytesStream, Metadata)> {
+        self.inner.read_with_metadata(args).await
+    }
+    async fn write(&self, r: BoxedAsyncReader, args: &OpWrite) -> Result<Metadata> {
+        let args = self.evaluate(args, args.size);
+        self.inner.write(r, &args).await
+    }
+    async fn append(&self, r: BoxedAsyncReader, args: &OpAppend) -> Result<Metadata> {
+        self.inner.append(r, args).await
+    }
+    async fn create(&self, args: &OpCreate) -> Result<Metadata> {
+        self.inner.create(args).await
+    }
+    async fn stat(&self, args: &OpStat) -> Result<Metadata> {
+        self.inner.stat(args).await
+    }
+    async fn delete(&self, args: &OpDelete) -> Result<()> {
+        self.inner.delete(args).await
+    }
+    async fn batch(&self, args: &OpBatch) -> Result<Vec<(String, BatchResult)>> {
+        self.inner.batch(args).await
+    }
+    async fn copy(&self, args: &OpCopy) -> Result<Metadata> {
+        self.inner.copy(args).await
+    }
+    async fn rename(&self, args: &OpRename) -> Result<Metadata> {
+        self.inner.rename(args).await
+    }
+    fn presign(&self, args: &OpPresign) -> Result<PresignedRequest> {
+        self.inner.presign(args)
+    }
+    async fn create_multipart(&self, args: &OpCreateMultipart) -> Result<String> {
+        self.inner.create_multipart(args).await
+    }
+    async fn write_multipart(
+        &self,
+        r: BoxedAsyncReader,
+        args: &OpWriteMultipart,
+    ) -> Result<ObjectPart> {
+        self.inner.write_multipart(r, args).await
+    }
+    async fn complete_multipart(&self, args: &OpCompleteMultipart) -> Result<Metadata> {
+        self.inner.complete_multipart(args).await
+    }
+    async fn abort_multipart(&self, args: &OpAbortMultipart) -> Result<()> {
+        self.inner.abort_multipart(args).await
+    }
+    async fn list(&self, args: &OpList) -> Result<BoxedObjectStream> {
+        let obs = self.inner.list(args).await?;
+        Ok(rebind(obs, Arc::new(self.clone())))
+    }
+    async fn scan(&self, args: &OpScan) -> Result<BoxedObjectStream> {
+        let obs = self.inner.scan(args).await?;
+        Ok(rebind(obs, Arc::new(self.clone())))
+    }
+
+    fn metadata(&self) -> AccessorMetadata {
+        self.inner.metadata()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn evaluate(rules: Vec<StorageClassRule>, args: &OpWrite, size: Option<u64>) -> OpWrite {
+        let acc = StorageClassPolicyAccessor {
+            inner: Arc::new(crate::services::memory::Backend::default()),
+            rules: Arc::new(ArcSwap::from_pointee(rules)),
+        };
+        acc.evaluate(args, size)
+    }
+
+    #[test]
+    fn test_glob_rule() {
+        let rules = vec![StorageClassRule::new("tmp/**")
+            .storage_class("STANDARD")
+            .cache_control("no-cache")
+            .tag("expiry", "7d")];
+
+        let args = evaluate(rules.clone(), &OpWrite::new("tmp/a/b", 1), Some(1));
+        assert_eq!(args.storage_class.as_deref(), Some("STANDARD"));
+        assert_eq!(args.cache_control.as_deref(), Some("no-cache"));
+        assert_eq!(args.tags.get("expiry").map(|v| v.as_str()), Some("7d"));
+
+        let args = evaluate(rules, &OpWrite::new("data/a", 1), Some(1));
+        assert_eq!(args.storage_class, None);
+        assert!(args.tags.is_empty());
+    }
+
+    #[test]
+    fn test_size_rule() {
+        let rules = vec![
+            StorageClassRule::new("archive/**")
+                .min_size(1024)
+                .storage_class("GLACIER_IR"),
+            StorageClassRule::new("**").storage_class("STANDARD_IA"),
+        ];
+
+        for (size, expected) in [
+            (Some(1024), "GLACIER_IR"),
+            (Some(1023), "STANDARD_IA"),
+            // Rules require size are skipped while size is unknown.
+            (None, "STANDARD_IA"),
+        ] {
+            let args = evaluate(rules.clone(), &OpWrite::new("archive/a", 0), size);
+            assert_eq!(args.storage_class.as_deref(), Some(expected), "{:?}", size);
+        }
+
+        let rules = vec![StorageClassRule::new("**")
+            .max_size(1024)
+            .storage_class("STANDARD")];
+        let args = evaluate(rules.clone(), &OpWrite::new("a", 0), Some(1023));
+        assert_eq!(args.storage_class.as_deref(), Some("STANDARD"));
+        let args = evaluate(rules, &OpWrite::new("a", 0), Some(1024));
+        assert_eq!(args.storage_class, None);
+    }
+
+    #[test]
+    fn test_first_match_and_explicit_wins() {
+        let rules = vec![
+            StorageClassRule::new("tmp/**")
+                .storage_class("STANDARD")
+                .tag("expiry", "7d")
+                .tag("team", "data"),
+            StorageClassRule::new("**")
+                .storage_class("STANDARD_IA")
+                .cache_control("max-age=3600"),
+        ];
+
+        // Only the first matched rule is applied.
+        let args = evaluate(rules.clone(), &OpWrite::new("tmp/a", 1), Some(1));
+        assert_eq!(args.storage_class.as_deref(), Some("STANDARD"));
+        assert_eq!(args.cache_control, None);
+
+        // Explicit values win.
+        let mut op = OpWrite::new("tmp/a", 1);
+        op.storage_class = Some("ONEZONE_IA".to_string());
+        op.tags.insert("expiry".to_string(), "1d".to_string());
+        let args = evaluate(rules, &op, Some(1));
+        assert_eq!(args.storage_class.as_deref(), Some("ONEZONE_IA"));
+        assert_eq!(args.tags.get("expiry").map(|v| v.as_str()), Some("1d"));
+        assert_eq!(args.tags.get("team").map(|v| v.as_str()), Some("data"));
+    }
+}
@@ -0,0 +1,420 @@
+// Copyright 2022 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Admit operations by their priorities.
+
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Instant;
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures::channel::oneshot;
+use metrics::gauge;
+use metrics::histogram;
+
+use super::rebind;
+use super::PermitStream;
+use crate::error::Result;
+use crate::io::BytesStream;
+use crate::ops::BatchResult;
+use crate::ops::ObjectPart;
+use crate::ops::OpAbortMultipart;
+use crate::ops::OpAppend;
+use crate::ops::OpBatch;
+use crate::ops::OpCompleteMultipart;
+use crate::ops::OpCopy;
+use crate::ops::OpCreate;
+use crate::ops::OpCreateMultipart;
+use crate::ops::OpDelete;
+use crate::ops::OpList;
+use crate::ops::OpPresign;
+use crate::ops::OpPriority;
+use crate::ops::OpRead;
+use crate::ops::OpRename;
+use crate::ops::OpScan;
+use crate::ops::OpStat;
+use crate::ops::OpWrite;
+use crate::ops::OpWriteMultipart;
+use crate::ops::PresignedRequest;
+use crate::Accessor;
+use crate::AccessorMetadata;
+use crate::BoxedAsyncReader;
+use crate::BoxedObjectStream;
+use crate::Layer;
+use crate::Metadata;
+
+/// Priorities from the highest to the lowest, the order of queues.
+const PRIORITIES: [OpPriority; 3] = [OpPriority::High, OpPriority::Normal, OpPriority::Low];
+
+/// QosLayer limits the count of concurrent operations, and admits waiting
+/// operations with higher [`OpPriority`] first.
+///
+/// Reads (`read`, `stat`, `list` and `scan`) and writes (all the others)
+/// are admitted separately, each up to the limit, so that a reader could be
+/// piped into a writer. `presign` sends no request and is never limited.
+///
+/// Operations hold their permits until finished. For `read`, permits are
+/// held until the returned stream is exhausted or dropped. For `list` and
+/// `scan`, permits are released once the first entry is listed, so that
+/// operations on listed objects don't wait for the listing.
+///
+/// # Weights
+///
+/// By default, lower priorities are only admitted while no higher one is
+/// waiting, so they could be starved by constant load. Use
+/// [`QosLayer::weights`] to share admissions between waiting priorities by
+/// weights instead, for example, `weights(8, 4, 1)` admits 1 `Low` operation
+/// in every 13 admissions while all priorities are waiting.
+///
+/// # Metrics
+///
+/// - `opendal_qos_queue_depth`: gauge of operations waiting for admission.
+/// - `opendal_qos_wait_seconds`: histogram of time waited for admission.
+///
+/// Both are labeled by `priority` as `high`, `normal` or `low`.
+///
+/// # Example
+///
+/// ```
+/// use anyhow::Result;
+/// use futures::io;
+/// use opendal::layers::qos::QosLayer;
+/// use opendal::ops::OpPriority;
+/// use opendal::services::memory;
+/// use opendal::Operator;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<()> {
+///     let op = Operator::new(memory::Backend::build().finish().await?)
+///         .layer(QosLayer::new(64).weights(8, 4, 1));
+///
+///     op.object("test")
+///         .writer()
+///         .priority(OpPriority::Low)
+///         .write_bytes("Hello, World!".as_bytes().to_vec())
+///         .await?;
+///     let mut r = op.object("test").reader().priority(OpPriority::High);
+///     io::copy(&mut r, &mut io::sink()).await?;
+///
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct QosLayer {
+    limit: usize,
+    weights: Option<[u32; 3]>,
+}
+
+impl QosLayer {
+    /// Create a new layer which admits at most `limit` reads and `limit`
+    /// writes at the same time.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `limit` is 0.
+    pub fn new(limit: usize) -> Self {
+        assert!(limit > 0, "limit must be at least 1");
+
+        Self {
+            limit,
+            weights: None,
+        }
+    }
+
+    /// Share admissions between waiting priorities by weights, see
+    /// [weights](#weights) for details.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any weight is 0.
+    pub fn weights(mut self, high: u32, normal: u32, low: u32) -> Self {
+        assert!(
+            high > 0 && normal > 0 && low > 0,
+            "weights must be at least 1"
+        );
+
+        self.weights = Some([high, normal, low]);
+        self
+    }
+}
+
+impl Layer for QosLayer {
+    fn layer(&self, inner: Arc<dyn Accessor>) -> Arc<dyn Accessor> {
+        let state = || {
+            Arc::new(QosState {
+                weights: self.weights,
+                queues: Mutex::new(Queues {
+                    available: self.limit,
+                    waiters: Default::default(),
+                    current: [0; 3],
+                }),
+            })
+        };
+
+        Arc::new(QosAccessor {
+            inner,
+            read: state(),
+            write: state(),
+        })
+    }
+}
+
+#[derive(Debug)]
+struct QosState {
+    weights: Option<[u32; 3]>,
+    queues: Mutex<Queues>,
+}
+
+#[derive(Debug)]
+struct Queues {
+    /// Permits that are not held by anyone.
+    available: usize,
+    /// Waiting operations of every priority, in the order of `PRIORITIES`.
+    waiters: [VecDeque<oneshot::Sender<()>>; 3],
+    /// Current weights of the smooth weighted round robin.
+    current: [i64; 3],
+}
+
+impl Queues {
+    /// Pick the queue to admit from, returns `None` if nobody is waiting.
+    fn next(&mut self, weights: Option<[u32; 3]>) -> Option<usize> {
+        let weights = match weights {
+            Some(weights) => weights,
+            None => return (0..PRIORITIES.len()).find(|&i| !self.waiters[i].is_empty()),
+        };
+
+        // Smooth weighted round robin between waiting queues, ties go to
+        // the higher priority.
+        let mut total = 0;
+        let mut picked: Option<usize> = None;
+        for (i, weight) in weights.iter().enumerate() {
+            if self.waiters[i].is_empty() {
+                continue;
+            }
+            self.current[i] += *weight as i64;
+            total += *weight as i64;
+            if !matches!(picked, Some(p) if self.current[i] <= self.current[p]) {
+                picked = Some(i);
+            }
+        }
+
+        let picked = picked?;
+        self.current[picked] -= total;
+        Some(picked)
+    }
+}
+
+impl QosState {
+    async fn acquire(self: &Arc<Self>, priority: OpPriority) -> Permit {
+        let i = PRIORITIES
+            .iter()
+            .position(|v| *v == priority)
+            .expect("priority must be valid");
+        let label = label(priority);
+
+        let rx = {
+            let mut queues = self.queues.lock().expect("lock must not be poisoned");
+            if queues.available > 0 && queues.waiters.iter().all(|v| v.is_empty()) {
+                queues.available -= 1;
+                histogram!("opendal_qos_wait_seconds", 0.0, "priority" => label);
+                return Permit {
+                    state: self.clone(),
+                };
+            }
+
+            let (tx, rx) = oneshot::channel();
+            queues.waiters[i].push_back(tx);
+            gauge!("opendal_qos_queue_depth", queues.waiters[i].len() as f64, "priority" => label);
+            rx
+        };
+
+        let start = Instant::now();
+        let mut waiting = Waiting {
+            rx,
+            state: self.clone(),
+            admitted: false,
+        };
+        // Senders are only dropped after sending, the permit is ours once
+        // received.
+        let _ = (&mut waiting.rx).await;
+        waiting.admitted = true;
+        histogram!("opendal_qos_wait_seconds", start.elapsed().as_secs_f64(), "priority" => label);
+
+        Permit {
+            state: self.clone(),
+        }
+    }
+
+    /// Hand the released permit over to the next waiting operation.
+    fn release(&self) {
+        let mut queues = self.queues.lock().expect("lock must not be poisoned");
+        while let Some(i) = queues.next(self.weights) {
+            let tx = queues.waiters[i]
+                .pop_front()
+                .expect("picked queue must not be empty");
+            let depth = queues.waiters[i].len();
+            gauge!("opendal_qos_queue_depth", depth as f64, "priority" => label(PRIORITIES[i]));
+            if depth == 0 {
+                queues.current[i] = 0;
+            }
+
+            // The waiting operation may have been cancelled, try the next one.
+            if tx.send(()).is_ok() {
+                return;
+            }
+        }
+        queues.available += 1;
+    }
+}
+
+fn label(priority: OpPriority) -> &'static str {
+    match priority {
+        OpPriority::High => "high",
+        OpPriority::Normal => "normal",
+        OpPriority::Low => "low",
+    }
+}
+
+/// Waiting gives the permit back if the operation is cancelled after the
+/// permit has been handed over.
+struct Waiting {
+    rx: oneshot::Receiver<()>,
+    state: Arc<QosState>,
+    admitted: bool,
+}
+
+impl Drop for Waiting {
+    fn drop(&mut self) {
+        if self.admitted {
+            return;
+        }
+
+        self.rx.close();
+        if let Ok(Some(())) = self.rx.try_recv() {
+            self.state.release();
+        }
+    }
+}
+
+/// Permit of an admitted operation, released on drop.
+struct Permit {
+    state: Arc<QosState>,
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        self.state.release();
+    }
+}
+
+#[derive(Debug, Clone)]
+struct QosAccessor {
+    inner: Arc<dyn Accessor>,
+    read: Arc<QosState>,
+    write: Arc<QosState>,
+}
+
+#[async_trait]
+impl Accessor for QosAccessor {
+    async fn read(&self, args: &OpRead) -> Result<BytesStream> {
+        let permit = self.read.acquire(args.priority).await;
+        let s = self.inner.read(args).await?;
+        Ok(Box::new(PermitStream::new(s, permit)))
+    }
+    async fn read_mmap(&self, args: &OpRead) -> Result<Bytes> {
+        let _permit = self.read.acquire(args.priority).await;
+        self.inner.read_mmap(args).await
+    }
+    async fn read_with_metadata(&self, args: &OpRead) -> Result<(BytesStream, Metadata)> {
+        let permit = self.read.acquire(args.priority).await;
+        let (s, meta) = self.inner.read_with_metadata(args).await?;
+        Ok((Box::new(PermitStream::new(s, permit)), meta))
+    }
+    async fn write(&self, r: BoxedAsyncReader, args: &OpWrite) -> Result<Metadata> {
+        let _permit = self.write.acquire(args.priority).await;
+        self.inner.write(r, args).await
+    }
+    async fn append(&self, r: BoxedAsyncReader, args: &OpAppend) -> Result<Metadata> {
+        let _permit = self.write.acquire(args.priority).await;
+        self.inner.append(r, args).await
+    }
+    async fn create(&self, args: &OpCreate) -> Result<Metadata> {
+        let _permit = self.write.acquire(args.priority).await;
+        self.inner.create(args).await
+    }
+    async fn stat(&self, args: &OpStat) -> Result<Metadata> {
+        let _permit = self.read.acquire(args.priority).await;
+        self.inner.stat(args).await
+    }
+    async fn delete(&self, args: &OpDelete) -> Result<()> {
+        let _permit = self.write.acquire(args.priority).await;
+        self.inner.delete(args).await
+    }
+    async fn batch(&self, args: &OpBatch) -> Result<Vec<(String, BatchResult)>> {
+        let _permit = self.write.acquire(args.priority).await;
+        self.inner.batch(args).await
+    }
+    async fn copy(&self, args: &OpCopy) -> Result<Metadata> {
+        let _permit = self.write.acquire(args.priority).await;
+        self.inner.copy(args).await
+    }
+    async fn rename(&self, args: &OpRename) -> Result<Metadata> {
+        let _permit = self.write.acquire(args.priority).await;
+        self.inner.rename(args).await
+    }
+    // No request is sent by presign, no permit is required.
+    fn presign(&self, args: &OpPresign) -> Result<PresignedRequest> {
+        self.inner.presign(args)
+    }
+    async fn create_multipart(&self, args: &OpCreateMultipart) -> Result<String> {
+        let _permit = self.write.acquire(args.priority).await;
+        self.inner.create_multipart(args).await
+    }
+    async fn write_multipart(
+        &self,
+        r: BoxedAsyncReader,
+        args: &OpWriteMultipart,
+    ) -> Result<ObjectPart> {
+        let _permit = self.write.acquire(args.priority).await;
+        self.inner.write_multipart(r, args).await
+    }
+    async fn complete_multipart(&self, args: &OpCompleteMultipart) -> Result<Metadata> {
+        let _permit = self.write.acquire(args.priority).await;
+        self.inner.complete_multipart(args).await
+    }
+    async fn abort_multipart(&self, args: &OpAbortMultipart) -> Result<()> {
+        let _permit = self.write.acquire(args.priority).await;
+        self.inner.abort_multipart(args).await
+    }
+    async fn list(&self, args: &OpList) -> Result<BoxedObjectStream> {
+        let permit = self.read.acquire(args.priority).await;
+        let obs = self.inner.list(args).await?;
+
+        let obs = PermitStream::until_first(obs, permit);
+        Ok(rebind(Box::new(obs), Arc::new(self.clone())))
+    }
+    async fn scan(&self, args: &OpScan) -> Result<BoxedObjectStream> {
+        let permit = self.read.acquire(args.priority).await;
+        let obs = self.inner.scan(args).await?;
+
+        let obs = PermitStream::until_first(obs, permit);
+        Ok(rebind(Box::new(obs), Arc::new(self.clone())))
+    }
+
+    fn metadata(&self) -> AccessorMetadata {
+        self.inner.metadata()
+    }
+}
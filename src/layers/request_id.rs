@@ -0,0 +1,225 @@
+// Copyright 2022 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::Context;
+use std::task::Poll;
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures::Stream;
+use futures::StreamExt;
+use uuid::Uuid;
+
+use super::rebind;
+use crate::error::Error;
+use crate::error::Result;
+use crate::http_util::sync_with_request_id;
+use crate::http_util::with_request_id;
+use crate::io::BytesStream;
+use crate::ops::BatchResult;
+use crate::ops::ObjectPart;
+use crate::ops::OpAbortMultipart;
+use crate::ops::OpAppend;
+use crate::ops::OpBatch;
+use crate::ops::OpCompleteMultipart;
+use crate::ops::OpCopy;
+use crate::ops::OpCreate;
+use crate::ops::OpCreateMultipart;
+use crate::ops::OpDelete;
+use crate::ops::OpList;
+use crate::ops::OpPresign;
+use crate::ops::OpRead;
+use crate::ops::OpRename;
+use crate::ops::OpScan;
+use crate::ops::OpStat;
+use crate::ops::OpWrite;
+use crate::ops::OpWriteMultipart;
+use crate::ops::PresignedRequest;
+use crate::Accessor;
+use crate::AccessorMetadata;
+use crate::BoxedAsyncReader;
+use crate::BoxedObjectStream;
+use crate::Layer;
+use crate::Metadata;
+
+/// RequestIdLayer assigns a random UUID to every operation, which is sent
+/// as the `x-opendal-request-id` header of all requests of the operation,
+/// like pages of list or parts of multipart upload.
+///
+/// The id is also attached to the `request_id` context of errors returned
+/// by the operation, so that client logs, server logs and error reports can
+/// be correlated. Request hooks observe the id in the request headers.
+///
+/// Services without http requests, like memory and fs, ignore the id
+/// except for errors.
+///
+/// # Example
+///
+/// ```
+/// use anyhow::Result;
+/// use opendal::layers::RequestIdLayer;
+/// use opendal::services::memory;
+/// use opendal::Operator;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<()> {
+///     let op = Operator::new(memory::Backend::build().finish().await?).layer(RequestIdLayer);
+///
+///     let err = op.object("not_exist").metadata().await.unwrap_err();
+///     assert!(err.context().contains_key("request_id"));
+///
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RequestIdLayer;
+
+impl Layer for RequestIdLayer {
+    fn layer(&self, inner: Arc<dyn Accessor>) -> Arc<dyn Accessor> {
+        Arc::new(RequestIdAccessor { inner })
+    }
+}
+
+#[derive(Debug, Clone)]
+struct RequestIdAccessor {
+    inner: Arc<dyn Accessor>,
+}
+
+/// Run `fut` under a new request id, returns the id with the output.
+async fn scoped<T>(fut: impl Future<Output = Result<T>>) -> (String, Result<T>) {
+    let id = Uuid::new_v4().to_string();
+    let res = with_request_id(id.clone(), fut).await;
+    let res = res.map_err(|e| attach(e, &id));
+    (id, res)
+}
+
+/// Attach the request id to the context of the error.
+fn attach(mut err: Error, id: &str) -> Error {
+    match &mut err {
+        Error::Backend { context, .. }
+        | Error::Object { context, .. }
+        | Error::Unexpected { context, .. } => {
+            context.insert("request_id".to_string(), id.to_string());
+        }
+    }
+    err
+}
+
+#[async_trait]
+impl Accessor for RequestIdAccessor {
+    async fn read(&self, args: &OpRead) -> Result<BytesStream> {
+        let (id, s) = scoped(self.inner.read(args)).await;
+        Ok(Box::new(RequestIdStream::new(s?, id)))
+    }
+    async fn read_mmap(&self, args: &OpRead) -> Result<Bytes> {
+        scoped(self.inner.read_mmap(args)).await.1
+    }
+    async fn read_with_metadata(&self, args: &OpRead) -> Result<(BytesStream, Metadata)> {
+        let (id, res) = scoped(self.inner.read_with_metadata(args)).await;
+        let (s, meta) = res?;
+        Ok((Box::new(RequestIdStream::new(s, id)), meta))
+    }
+    async fn write(&self, r: BoxedAsyncReader, args: &OpWrite) -> Result<Metadata> {
+        scoped(self.inner.write(r, args)).await.1
+    }
+    async fn append(&self, r: BoxedAsyncReader, args: &OpAppend) -> Result<Metadata> {
+        scoped(self.inner.append(r, args)).await.1
+    }
+    async fn create(&self, args: &OpCreate) -> Result<Metadata> {
+        scoped(self.inner.create(args)).await.1
+    }
+    async fn stat(&self, args: &OpStat) -> Result<Metadata> {
+        scoped(self.inner.stat(args)).await.1
+    }
+    async fn delete(&self, args: &OpDelete) -> Result<()> {
+        scoped(self.inner.delete(args)).await.1
+    }
+    async fn batch(&self, args: &OpBatch) -> Result<Vec<(String, BatchResult)>> {
+        scoped(self.inner.batch(args)).await.1
+    }
+    async fn copy(&self, args: &OpCopy) -> Result<Metadata> {
+        scoped(self.inner.copy(args)).await.1
+    }
+    async fn rename(&self, args: &OpRename) -> Result<Metadata> {
+        scoped(self.inner.rename(args)).await.1
+    }
+    fn presign(&self, args: &OpPresign) -> Result<PresignedRequest> {
+        self.inner.presign(args)
+    }
+    async fn create_multipart(&self, args: &OpCreateMultipart) -> Result<String> {
+        scoped(self.inner.create_multipart(args)).await.1
+    }
+    async fn write_multipart(
+        &self,
+        r: BoxedAsyncReader,
+        args: &OpWriteMultipart,
+    ) -> Result<ObjectPart> {
+        scoped(self.inner.write_multipart(r, args)).await.1
+    }
+    async fn complete_multipart(&self, args: &OpCompleteMultipart) -> Result<Metadata> {
+        scoped(self.inner.complete_multipart(args)).await.1
+    }
+    async fn abort_multipart(&self, args: &OpAbortMultipart) -> Result<()> {
+        scoped(self.inner.abort_multipart(args)).await.1
+    }
+    async fn list(&self, args: &OpList) -> Result<BoxedObjectStream> {
+        let (id, obs) = scoped(self.inner.list(args)).await;
+
+        Ok(rebind(
+            Box::new(RequestIdStream::new(obs?, id)),
+            Arc::new(self.clone()),
+        ))
+    }
+    async fn scan(&self, args: &OpScan) -> Result<BoxedObjectStream> {
+        let (id, obs) = scoped(self.inner.scan(args)).await;
+
+        Ok(rebind(
+            Box::new(RequestIdStream::new(obs?, id)),
+            Arc::new(self.clone()),
+        ))
+    }
+
+    fn metadata(&self) -> AccessorMetadata {
+        self.inner.metadata()
+    }
+}
+
+/// RequestIdStream keeps the request id while polling, so that requests
+/// sent by streams like following pages of list carry the same id.
+struct RequestIdStream<S> {
+    inner: S,
+    id: String,
+}
+
+impl<S> RequestIdStream<S> {
+    fn new(inner: S, id: String) -> Self {
+        Self { inner, id }
+    }
+}
+
+impl<S, T> Stream for RequestIdStream<S>
+where
+    S: Stream<Item = Result<T>> + Unpin,
+{
+    type Item = Result<T>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let id = self.id.clone();
+        let next = sync_with_request_id(id, || self.inner.poll_next_unpin(cx));
+        next.map(|v| v.map(|v| v.map_err(|e| attach(e, &self.id))))
+    }
+}
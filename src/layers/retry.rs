@@ -0,0 +1,443 @@
+// Copyright 2022 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::future::Future;
+use std::hash::BuildHasher;
+use std::hash::Hasher;
+use std::sync::Arc;
+use std::time::Duration;
+use std::time::Instant;
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures::io::Cursor;
+use futures::AsyncReadExt;
+use log::warn;
+
+use super::rebind;
+use crate::error::Error;
+use crate::error::Kind;
+use crate::error::Result;
+use crate::io::BytesStream;
+use crate::ops::BatchResult;
+use crate::ops::ObjectPart;
+use crate::ops::OpAbortMultipart;
+use crate::ops::OpAppend;
+use crate::ops::OpBatch;
+use crate::ops::OpCompleteMultipart;
+use crate::ops::OpCopy;
+use crate::ops::OpCreate;
+use crate::ops::OpCreateMultipart;
+use crate::ops::OpDelete;
+use crate::ops::OpList;
+use crate::ops::OpPresign;
+use crate::ops::OpRead;
+use crate::ops::OpRename;
+use crate::ops::OpScan;
+use crate::ops::OpStat;
+use crate::ops::OpWrite;
+use crate::ops::OpWriteMultipart;
+use crate::ops::PresignedRequest;
+use crate::Accessor;
+use crate::AccessorMetadata;
+use crate::BoxedAsyncReader;
+use crate::BoxedObjectStream;
+use crate::Layer;
+use crate::Metadata;
+
+/// Default max size of writes buffered in memory so that they can be retried.
+const DEFAULT_WRITE_BUFFER_SIZE: u64 = 8 * 1024 * 1024;
+
+/// Backoff policy of [`RetryLayer`], which yields the delay before every
+/// retry, and retrying stops once it ends.
+///
+/// Any cloneable iterator of durations is a policy, like the ones built by
+/// crates such as `backon`. Every operation starts from a fresh clone.
+pub trait Backoff: Iterator<Item = Duration> + Clone + Debug + Send + Sync + 'static {}
+
+impl<T> Backoff for T where T: Iterator<Item = Duration> + Clone + Debug + Send + Sync + 'static {}
+
+/// ExponentialBackoff doubles the delay after every retry, with jitter.
+///
+/// Default to at most 3 retries, starting from 100ms and up to 10s.
+#[derive(Debug, Clone)]
+pub struct ExponentialBackoff {
+    min_delay: Duration,
+    max_delay: Duration,
+    factor: f64,
+    max_times: usize,
+    jitter: bool,
+
+    attempts: usize,
+}
+
+impl Default for ExponentialBackoff {
+    fn default() -> Self {
+        Self {
+            min_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(10),
+            factor: 2.0,
+            max_times: 3,
+            jitter: true,
+            attempts: 0,
+        }
+    }
+}
+
+impl ExponentialBackoff {
+    /// Set the delay before the first retry.
+    #[must_use]
+    pub fn min_delay(mut self, delay: Duration) -> Self {
+        self.min_delay = delay;
+        self
+    }
+
+    /// Set the max delay between retries.
+    #[must_use]
+    pub fn max_delay(mut self, delay: Duration) -> Self {
+        self.max_delay = delay;
+        self
+    }
+
+    /// Set the factor that the delay grows by after every retry.
+    #[must_use]
+    pub fn factor(mut self, factor: f64) -> Self {
+        self.factor = factor.max(1.0);
+        self
+    }
+
+    /// Set the max times to retry, `0` disables retrying.
+    #[must_use]
+    pub fn max_times(mut self, times: usize) -> Self {
+        self.max_times = times;
+        self
+    }
+
+    /// Randomize delays to `[delay / 2, delay)`, so that clients failed at
+    /// the same time will not retry at the same time. Enabled by default.
+    #[must_use]
+    pub fn jitter(mut self, enabled: bool) -> Self {
+        self.jitter = enabled;
+        self
+    }
+}
+
+impl Iterator for ExponentialBackoff {
+    type Item = Duration;
+
+    fn next(&mut self) -> Option<Duration> {
+        if self.attempts >= self.max_times {
+            return None;
+        }
+
+        let delay = self
+            .min_delay
+            .mul_f64(self.factor.powi(self.attempts as i32))
+            .min(self.max_delay);
+        self.attempts += 1;
+
+        if !self.jitter {
+            return Some(delay);
+        }
+        Some(delay / 2 + (delay / 2).mul_f64(random_fraction()))
+    }
+}
+
+/// A random number in `[0, 1)`, which is good enough for jitter.
+fn random_fraction() -> f64 {
+    let v = RandomState::new().build_hasher().finish();
+    (v >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// RetryLayer retries operations failed with retryable errors, read
+/// [`Error::is_retryable`] for them.
+///
+/// # Operations
+///
+/// - `read`, `list` and `scan` are retried until the stream is returned,
+///   errors while consuming the stream are not retried. Interrupted reads
+///   are resumed by services like s3 instead.
+/// - `write` and `write_multipart` are retried only if the size is known
+///   and not larger than the write buffer size, since the data has to be
+///   buffered in memory to be sent again. Services like s3 abort the
+///   multipart upload of a failed write, so that it can be retried as a whole.
+/// - `rename`, `append`, `create_multipart` and `complete_multipart` are not
+///   retried, since they are not idempotent.
+/// - Other operations are idempotent and retried.
+///
+/// Errors that have been retried carry the count in context `retries`.
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use anyhow::Result;
+/// use opendal::layers::ExponentialBackoff;
+/// use opendal::layers::RetryLayer;
+/// use opendal::services::memory;
+/// use opendal::Operator;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<()> {
+///     let backoff = ExponentialBackoff::default()
+///         .max_times(5)
+///         .max_delay(Duration::from_secs(5));
+///     let op = Operator::new(memory::Backend::build().finish().await?).layer(
+///         RetryLayer::with_backoff(backoff).max_elapsed(Duration::from_secs(30)),
+///     );
+///
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct RetryLayer<B: Backoff = ExponentialBackoff> {
+    backoff: B,
+    max_elapsed: Option<Duration>,
+    write_buffer_size: u64,
+}
+
+impl RetryLayer {
+    /// Create a retry layer with the default [`ExponentialBackoff`].
+    pub fn new() -> Self {
+        Self::with_backoff(ExponentialBackoff::default())
+    }
+}
+
+impl Default for RetryLayer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<B: Backoff> RetryLayer<B> {
+    /// Create a retry layer with the backoff policy.
+    pub fn with_backoff(backoff: B) -> Self {
+        Self {
+            backoff,
+            max_elapsed: None,
+            write_buffer_size: DEFAULT_WRITE_BUFFER_SIZE,
+        }
+    }
+
+    /// Stop retrying once the next retry would start after `elapsed` since
+    /// the first attempt.
+    #[must_use]
+    pub fn max_elapsed(mut self, elapsed: Duration) -> Self {
+        self.max_elapsed = Some(elapsed);
+        self
+    }
+
+    /// Set the max size of writes buffered in memory to be retried, larger
+    /// writes are not retried.
+    ///
+    /// Default to 8 MiB.
+    #[must_use]
+    pub fn write_buffer_size(mut self, size: u64) -> Self {
+        self.write_buffer_size = size;
+        self
+    }
+}
+
+impl<B: Backoff> Layer for RetryLayer<B> {
+    fn layer(&self, inner: Arc<dyn Accessor>) -> Arc<dyn Accessor> {
+        Arc::new(RetryAccessor {
+            inner,
+            backoff: self.backoff.clone(),
+            max_elapsed: self.max_elapsed,
+            write_buffer_size: self.write_buffer_size,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+struct RetryAccessor<B: Backoff> {
+    inner: Arc<dyn Accessor>,
+    backoff: B,
+    max_elapsed: Option<Duration>,
+    write_buffer_size: u64,
+}
+
+impl<B: Backoff> RetryAccessor<B> {
+    async fn retry<T, F, Fut>(&self, op: &'static str, path: &str, f: F) -> Result<T>
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let start = Instant::now();
+        let mut backoff = self.backoff.clone();
+        let mut retries = 0;
+        loop {
+            let err = match f().await {
+                Ok(v) => return Ok(v),
+                Err(err) => err,
+            };
+
+            let delay = match backoff.next() {
+                Some(delay) if err.is_retryable() && self.within_elapsed(start, delay) => delay,
+                _ if retries > 0 => return Err(err.with_context("retries", retries)),
+                _ => return Err(err),
+            };
+            warn!(
+                "object {} {} failed, retry after {:?}: {:?}",
+                path, op, delay, err
+            );
+            tokio::time::sleep(delay).await;
+            retries += 1;
+        }
+    }
+
+    fn within_elapsed(&self, start: Instant, delay: Duration) -> bool {
+        self.max_elapsed
+            .is_none_or(|max| start.elapsed() + delay <= max)
+    }
+
+    /// Buffer the data of `size` bytes so that it can be sent again.
+    ///
+    /// Returns `None` if the data should not be buffered.
+    async fn buffer(
+        &self,
+        op: &'static str,
+        path: &str,
+        r: &mut BoxedAsyncReader,
+        size: Option<u64>,
+    ) -> Result<Option<Bytes>> {
+        let size = match size {
+            Some(size) if size <= self.write_buffer_size => size,
+            _ => return Ok(None),
+        };
+
+        let mut bs = Vec::with_capacity(size as usize);
+        r.take(size)
+            .read_to_end(&mut bs)
+            .await
+            .map_err(|e| Error::Object {
+                kind: Kind::Unexpected,
+                op,
+                path: path.to_string(),
+                context: HashMap::new(),
+                source: anyhow::Error::from(e),
+            })?;
+        Ok(Some(Bytes::from(bs)))
+    }
+}
+
+#[async_trait]
+impl<B: Backoff> Accessor for RetryAccessor<B> {
+    async fn read(&self, args: &OpRead) -> Result<BytesStream> {
+        self.retry("read", &args.path, || self.inner.read(args))
+            .await
+    }
+    async fn read_mmap(&self, args: &OpRead) -> Result<Bytes> {
+        self.retry("read", &args.path, || self.inner.read_mmap(args))
+            .await
+    }
+    async fn read_with_metadata(&self, args: &OpRead) -> Result<(BytesStream, Metadata)> {
+        self.retry("read", &args.path, || self.inner.read_with_metadata(args))
+            .await
+    }
+    async fn write(&self, mut r: BoxedAsyncReader, args: &OpWrite) -> Result<Metadata> {
+        match self.buffer("write", &args.path, &mut r, args.size).await? {
+            Some(bs) => {
+                self.retry("write", &args.path, || {
+                    self.inner.write(Box::new(Cursor::new(bs.clone())), args)
+                })
+                .await
+            }
+            None => self.inner.write(r, args).await,
+        }
+    }
+    async fn append(&self, r: BoxedAsyncReader, args: &OpAppend) -> Result<Metadata> {
+        self.inner.append(r, args).await
+    }
+    async fn create(&self, args: &OpCreate) -> Result<Metadata> {
+        self.retry("create", &args.path, || self.inner.create(args))
+            .await
+    }
+    async fn stat(&self, args: &OpStat) -> Result<Metadata> {
+        self.retry("stat", &args.path, || self.inner.stat(args))
+            .await
+    }
+    async fn delete(&self, args: &OpDelete) -> Result<()> {
+        self.retry("delete", &args.path, || self.inner.delete(args))
+            .await
+    }
+    async fn batch(&self, args: &OpBatch) -> Result<Vec<(String, BatchResult)>> {
+        let path = args.ops.first().map(|v| v.path()).unwrap_or_default();
+        self.retry("batch", path, || self.inner.batch(args)).await
+    }
+    async fn copy(&self, args: &OpCopy) -> Result<Metadata> {
+        self.retry("copy", &args.src, || self.inner.copy(args))
+            .await
+    }
+    async fn rename(&self, args: &OpRename) -> Result<Metadata> {
+        self.inner.rename(args).await
+    }
+    fn presign(&self, args: &OpPresign) -> Result<PresignedRequest> {
+        self.inner.presign(args)
+    }
+    async fn create_multipart(&self, args: &OpCreateMultipart) -> Result<String> {
+        self.inner.create_multipart(args).await
+    }
+    async fn write_multipart(
+        &self,
+        mut r: BoxedAsyncReader,
+        args: &OpWriteMultipart,
+    ) -> Result<ObjectPart> {
+        match self
+            .buffer("write_multipart", &args.path, &mut r, Some(args.size))
+            .await?
+        {
+            // Writing the same part number again overwrites the part.
+            Some(bs) => {
+                self.retry("write_multipart", &args.path, || {
+                    self.inner
+                        .write_multipart(Box::new(Cursor::new(bs.clone())), args)
+                })
+                .await
+            }
+            None => self.inner.write_multipart(r, args).await,
+        }
+    }
+    async fn complete_multipart(&self, args: &OpCompleteMultipart) -> Result<Metadata> {
+        self.inner.complete_multipart(args).await
+    }
+    async fn abort_multipart(&self, args: &OpAbortMultipart) -> Result<()> {
+        self.retry("abort_multipart", &args.path, || {
+            self.inner.abort_multipart(args)
+        })
+        .await
+    }
+    async fn list(&self, args: &OpList) -> Result<BoxedObjectStream> {
+        let obs = self
+            .retry("list", &args.path, || self.inner.list(args))
+            .await?;
+
+        Ok(rebind(obs, Arc::new(self.clone())))
+    }
+    async fn scan(&self, args: &OpScan) -> Result<BoxedObjectStream> {
+        let obs = self
+            .retry("scan", &args.path, || self.inner.scan(args))
+            .await?;
+
+        Ok(rebind(obs, Arc::new(self.clone())))
+    }
+
+    fn metadata(&self) -> AccessorMetadata {
+        self.inner.metadata()
+    }
+}
@@ -0,0 +1,236 @@
+// Copyright 2022 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use anyhow::anyhow;
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures::StreamExt;
+use log::error;
+use metrics::increment_counter;
+
+use super::rebind;
+use crate::accessor::batch_each;
+use crate::error::Error;
+use crate::error::Kind;
+use crate::error::Result;
+use crate::io::BytesStream;
+use crate::ops::BatchResult;
+use crate::ops::ObjectPart;
+use crate::ops::OpAbortMultipart;
+use crate::ops::OpAppend;
+use crate::ops::OpBatch;
+use crate::ops::OpCompleteMultipart;
+use crate::ops::OpCopy;
+use crate::ops::OpCreate;
+use crate::ops::OpCreateMultipart;
+use crate::ops::OpDelete;
+use crate::ops::OpList;
+use crate::ops::OpPresign;
+use crate::ops::OpRead;
+use crate::ops::OpRename;
+use crate::ops::OpScan;
+use crate::ops::OpStat;
+use crate::ops::OpWrite;
+use crate::ops::OpWriteMultipart;
+use crate::ops::PresignedRequest;
+use crate::Accessor;
+use crate::AccessorMetadata;
+use crate::BoxedAsyncReader;
+use crate::BoxedObjectStream;
+use crate::Layer;
+use crate::Metadata;
+
+/// ScopeGuardLayer makes sure all paths stay under the expected prefix.
+///
+/// Every path sent to the inner accessor and every path returned by `list`
+/// will be checked, an `Kind::ObjectOutOfScope` error will be returned if the
+/// path isn't under the prefix or contains `.` / `..` segments.
+///
+/// This layer is designed to be placed right above the backend (or below
+/// [`SubdirLayer`][super::SubdirLayer]) so that it can see the final paths.
+/// Checks are plain string comparisons, cheap enough for production.
+///
+/// Metrics:
+///
+/// - `opendal_scope_guard_checks`: number of checked paths.
+/// - `opendal_scope_guard_violations`: number of paths out of scope.
+#[derive(Debug, Clone)]
+pub struct ScopeGuardLayer {
+    // prefix will be "" or "abc/"
+    prefix: String,
+}
+
+impl ScopeGuardLayer {
+    /// Create a new scope guard layer, `prefix` could be like `/abc/`, `abc` or `abc/`.
+    pub fn new(prefix: &str) -> Self {
+        let mut prefix = prefix
+            .split('/')
+            .filter(|v| !v.is_empty())
+            .collect::<Vec<&str>>()
+            .join("/");
+        if !prefix.is_empty() {
+            prefix.push('/');
+        }
+
+        Self { prefix }
+    }
+}
+
+impl Layer for ScopeGuardLayer {
+    fn layer(&self, inner: Arc<dyn Accessor>) -> Arc<dyn Accessor> {
+        Arc::new(ScopeGuardAccessor {
+            inner,
+            prefix: self.prefix.clone(),
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+struct ScopeGuardAccessor {
+    inner: Arc<dyn Accessor>,
+    prefix: String,
+}
+
+impl ScopeGuardAccessor {
+    fn check(&self, op: &'static str, path: &str) -> Result<()> {
+        increment_counter!("opendal_scope_guard_checks");
+
+        let normalized = path.trim_start_matches('/');
+        let reason = if normalized.split('/').any(|v| v == "." || v == "..") {
+            "contains relative segments"
+        } else if !normalized.starts_with(&self.prefix) {
+            "not under prefix"
+        } else {
+            return Ok(());
+        };
+
+        increment_counter!("opendal_scope_guard_violations");
+        error!(
+            "scope guard denied {} on path {:?}: {} {:?}",
+            op, path, reason, &self.prefix
+        );
+        Err(Error::Object {
+            kind: Kind::ObjectOutOfScope,
+            op,
+            path: path.to_string(),
+            context: HashMap::new(),
+            source: anyhow!("path {} {:?}", reason, &self.prefix),
+        })
+    }
+}
+
+#[async_trait]
+impl Accessor for ScopeGuardAccessor {
+    async fn read(&self, args: &OpRead) -> Result<BytesStream> {
+        self.check("read", &args.path)?;
+        self.inner.read(args).await
+    }
+    async fn read_mmap(&self, args: &OpRead) -> Result<Bytes> {
+        self.check("read", &args.path)?;
+        self.inner.read_mmap(args).await
+    }
+    async fn read_with_metadata(&self, args: &OpRead) -> Result<(BytesStream, Metadata)> {
+        self.check("read", &args.path)?;
+        self.inner.read_with_metadata(args).await
+    }
+    async fn write(&self, r: BoxedAsyncReader, args: &OpWrite) -> Result<Metadata> {
+        self.check("write", &args.path)?;
+        self.inner.write(r, args).await
+    }
+    async fn append(&self, r: BoxedAsyncReader, args: &OpAppend) -> Result<Metadata> {
+        self.check("append", &args.path)?;
+        self.inner.append(r, args).await
+    }
+    async fn create(&self, args: &OpCreate) -> Result<Metadata> {
+        self.check("create", &args.path)?;
+        self.inner.create(args).await
+    }
+    async fn stat(&self, args: &OpStat) -> Result<Metadata> {
+        self.check("stat", &args.path)?;
+        self.inner.stat(args).await
+    }
+    async fn delete(&self, args: &OpDelete) -> Result<()> {
+        self.check("delete", &args.path)?;
+        self.inner.delete(args).await
+    }
+    async fn batch(&self, args: &OpBatch) -> Result<Vec<(String, BatchResult)>> {
+        // Sub-operations are checked one by one.
+        Ok(batch_each(self, args).await)
+    }
+    async fn copy(&self, args: &OpCopy) -> Result<Metadata> {
+        self.check("copy", &args.src)?;
+        self.check("copy", &args.dst)?;
+        self.inner.copy(args).await
+    }
+    async fn rename(&self, args: &OpRename) -> Result<Metadata> {
+        self.check("rename", &args.src)?;
+        self.check("rename", &args.dst)?;
+        self.inner.rename(args).await
+    }
+    fn presign(&self, args: &OpPresign) -> Result<PresignedRequest> {
+        self.check("presign", &args.path)?;
+        self.inner.presign(args)
+    }
+    async fn create_multipart(&self, args: &OpCreateMultipart) -> Result<String> {
+        self.check("create_multipart", &args.path)?;
+        self.inner.create_multipart(args).await
+    }
+    async fn write_multipart(
+        &self,
+        r: BoxedAsyncReader,
+        args: &OpWriteMultipart,
+    ) -> Result<ObjectPart> {
+        self.check("write_multipart", &args.path)?;
+        self.inner.write_multipart(r, args).await
+    }
+    async fn complete_multipart(&self, args: &OpCompleteMultipart) -> Result<Metadata> {
+        self.check("complete_multipart", &args.path)?;
+        self.inner.complete_multipart(args).await
+    }
+    async fn abort_multipart(&self, args: &OpAbortMultipart) -> Result<()> {
+        self.check("abort_multipart", &args.path)?;
+        self.inner.abort_multipart(args).await
+    }
+    async fn list(&self, args: &OpList) -> Result<BoxedObjectStream> {
+        self.check("list", &args.path)?;
+
+        let this = self.clone();
+        let obs = self.inner.list(args).await?.map(move |o| {
+            let mut o = o?;
+            this.check("list", o.metadata_mut().path())?;
+            Ok(o)
+        });
+
+        Ok(rebind(Box::new(obs), Arc::new(self.clone())))
+    }
+    async fn scan(&self, args: &OpScan) -> Result<BoxedObjectStream> {
+        self.check("scan", &args.path)?;
+
+        let this = self.clone();
+        let obs = self.inner.scan(args).await?.map(move |o| {
+            let mut o = o?;
+            this.check("scan", o.metadata_mut().path())?;
+            Ok(o)
+        });
+
+        Ok(rebind(Box::new(obs), Arc::new(self.clone())))
+    }
+
+    fn metadata(&self) -> AccessorMetadata {
+        self.inner.metadata()
+    }
+}
@@ -0,0 +1,263 @@
+// Copyright 2022 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures::StreamExt;
+
+use super::rebind;
+use crate::error::Result;
+use crate::io::BytesStream;
+use crate::ops::BatchOperation;
+use crate::ops::BatchResult;
+use crate::ops::ObjectPart;
+use crate::ops::OpAbortMultipart;
+use crate::ops::OpAppend;
+use crate::ops::OpBatch;
+use crate::ops::OpCompleteMultipart;
+use crate::ops::OpCopy;
+use crate::ops::OpCreate;
+use crate::ops::OpCreateMultipart;
+use crate::ops::OpDelete;
+use crate::ops::OpList;
+use crate::ops::OpPresign;
+use crate::ops::OpRead;
+use crate::ops::OpRename;
+use crate::ops::OpScan;
+use crate::ops::OpStat;
+use crate::ops::OpWrite;
+use crate::ops::OpWriteMultipart;
+use crate::ops::PresignedRequest;
+use crate::Accessor;
+use crate::AccessorMetadata;
+use crate::BoxedAsyncReader;
+use crate::BoxedObjectStream;
+use crate::Layer;
+use crate::Metadata;
+
+/// SubdirLayer makes a dir of the underlying storage become the root.
+///
+/// All paths will be prefixed by the dir before sending to the inner accessor,
+/// and paths returned by `stat` and `list` will be relative to the dir.
+#[derive(Debug, Clone)]
+pub struct SubdirLayer {
+    // prefix will be "" or "abc/"
+    prefix: String,
+}
+
+impl SubdirLayer {
+    /// Create a new subdir layer, `root` could be like `/abc/`, `abc` or `abc/`.
+    pub fn new(root: &str) -> Self {
+        let mut prefix = root
+            .split('/')
+            .filter(|v| !v.is_empty())
+            .collect::<Vec<&str>>()
+            .join("/");
+        if !prefix.is_empty() {
+            prefix.push('/');
+        }
+
+        Self { prefix }
+    }
+}
+
+impl Layer for SubdirLayer {
+    fn layer(&self, inner: Arc<dyn Accessor>) -> Arc<dyn Accessor> {
+        Arc::new(SubdirAccessor {
+            inner,
+            prefix: self.prefix.clone(),
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+struct SubdirAccessor {
+    inner: Arc<dyn Accessor>,
+    prefix: String,
+}
+
+impl SubdirAccessor {
+    fn abs_path(&self, path: &str) -> String {
+        format!("{}{}", self.prefix, path.trim_start_matches('/'))
+    }
+
+    fn rel_path(&self, path: &str) -> String {
+        let path = path.trim_start_matches('/');
+        path.strip_prefix(&self.prefix).unwrap_or(path).to_string()
+    }
+}
+
+#[async_trait]
+impl Accessor for SubdirAccessor {
+    async fn read(&self, args: &OpRead) -> Result<BytesStream> {
+        let mut args = args.clone();
+        args.path = self.abs_path(&args.path);
+        self.inner.read(&args).await
+    }
+    async fn read_mmap(&self, args: &OpRead) -> Result<Bytes> {
+        let mut args = args.clone();
+        args.path = self.abs_path(&args.path);
+        self.inner.read_mmap(&args).await
+    }
+    async fn read_with_metadata(&self, args: &OpRead) -> Result<(BytesStream, Metadata)> {
+        let mut abs_args = args.clone();
+        abs_args.path = self.abs_path(&args.path);
+        let (s, mut meta) = self.inner.read_with_metadata(&abs_args).await?;
+        meta.set_path(&args.path);
+        Ok((s, meta))
+    }
+    async fn write(&self, r: BoxedAsyncReader, args: &OpWrite) -> Result<Metadata> {
+        let mut abs_args = args.clone();
+        abs_args.path = self.abs_path(&args.path);
+        let mut meta = self.inner.write(r, &abs_args).await?;
+        meta.set_path(&args.path);
+        Ok(meta)
+    }
+    async fn append(&self, r: BoxedAsyncReader, args: &OpAppend) -> Result<Metadata> {
+        let mut abs_args = args.clone();
+        abs_args.path = self.abs_path(&args.path);
+        let mut meta = self.inner.append(r, &abs_args).await?;
+        meta.set_path(&args.path);
+        Ok(meta)
+    }
+    async fn create(&self, args: &OpCreate) -> Result<Metadata> {
+        let mut abs_args = args.clone();
+        abs_args.path = self.abs_path(&args.path);
+        let mut meta = self.inner.create(&abs_args).await?;
+        // `/` could be appended to the path of dirs.
+        let path = self.rel_path(meta.path());
+        meta.set_path(&path);
+        Ok(meta)
+    }
+    async fn stat(&self, args: &OpStat) -> Result<Metadata> {
+        let mut abs_args = args.clone();
+        abs_args.path = self.abs_path(&args.path);
+        let mut meta = self.inner.stat(&abs_args).await?;
+        meta.set_path(&args.path);
+        Ok(meta)
+    }
+    async fn delete(&self, args: &OpDelete) -> Result<()> {
+        let mut args = args.clone();
+        args.path = self.abs_path(&args.path);
+        self.inner.delete(&args).await
+    }
+    async fn batch(&self, args: &OpBatch) -> Result<Vec<(String, BatchResult)>> {
+        let mut abs_args = args.clone();
+        for op in abs_args.ops.iter_mut() {
+            match op {
+                BatchOperation::Delete(v) => v.path = self.abs_path(&v.path),
+                BatchOperation::Stat(v) => v.path = self.abs_path(&v.path),
+            }
+        }
+        let results = self.inner.batch(&abs_args).await?;
+
+        Ok(args
+            .ops
+            .iter()
+            .zip(results)
+            .map(|(op, (_, mut res))| {
+                if let BatchResult::Stat(Ok(meta)) = &mut res {
+                    meta.set_path(op.path());
+                }
+                (op.path().to_string(), res)
+            })
+            .collect())
+    }
+    async fn copy(&self, args: &OpCopy) -> Result<Metadata> {
+        let mut abs_args = args.clone();
+        abs_args.src = self.abs_path(&args.src);
+        abs_args.dst = self.abs_path(&args.dst);
+        let mut meta = self.inner.copy(&abs_args).await?;
+        meta.set_path(&args.dst);
+        Ok(meta)
+    }
+    async fn rename(&self, args: &OpRename) -> Result<Metadata> {
+        let mut abs_args = args.clone();
+        abs_args.src = self.abs_path(&args.src);
+        abs_args.dst = self.abs_path(&args.dst);
+        let mut meta = self.inner.rename(&abs_args).await?;
+        meta.set_path(&args.dst);
+        Ok(meta)
+    }
+    fn presign(&self, args: &OpPresign) -> Result<PresignedRequest> {
+        let mut abs_args = args.clone();
+        abs_args.path = self.abs_path(&args.path);
+        self.inner.presign(&abs_args)
+    }
+    async fn create_multipart(&self, args: &OpCreateMultipart) -> Result<String> {
+        let mut abs_args = args.clone();
+        abs_args.path = self.abs_path(&args.path);
+        self.inner.create_multipart(&abs_args).await
+    }
+    async fn write_multipart(
+        &self,
+        r: BoxedAsyncReader,
+        args: &OpWriteMultipart,
+    ) -> Result<ObjectPart> {
+        let mut abs_args = args.clone();
+        abs_args.path = self.abs_path(&args.path);
+        self.inner.write_multipart(r, &abs_args).await
+    }
+    async fn complete_multipart(&self, args: &OpCompleteMultipart) -> Result<Metadata> {
+        let mut abs_args = args.clone();
+        abs_args.path = self.abs_path(&args.path);
+        let mut meta = self.inner.complete_multipart(&abs_args).await?;
+        meta.set_path(&args.path);
+        Ok(meta)
+    }
+    async fn abort_multipart(&self, args: &OpAbortMultipart) -> Result<()> {
+        let mut abs_args = args.clone();
+        abs_args.path = self.abs_path(&args.path);
+        self.inner.abort_multipart(&abs_args).await
+    }
+    async fn list(&self, args: &OpList) -> Result<BoxedObjectStream> {
+        let mut args = args.clone();
+        args.path = self.abs_path(&args.path);
+        // Patterns are matched against the paths of the inner accessor.
+        args.pattern = args.pattern.map(|v| self.abs_path(&v));
+
+        let this = self.clone();
+        let obs = self.inner.list(&args).await?.map(move |o| {
+            let mut o = o?;
+            let path = this.rel_path(o.metadata_mut().path());
+            o.metadata_mut().set_path(&path);
+            Ok(o)
+        });
+
+        Ok(rebind(Box::new(obs), Arc::new(self.clone())))
+    }
+    async fn scan(&self, args: &OpScan) -> Result<BoxedObjectStream> {
+        let mut args = args.clone();
+        args.path = self.abs_path(&args.path);
+
+        let this = self.clone();
+        let obs = self.inner.scan(&args).await?.map(move |o| {
+            let mut o = o?;
+            let path = this.rel_path(o.metadata_mut().path());
+            o.metadata_mut().set_path(&path);
+            Ok(o)
+        });
+
+        Ok(rebind(Box::new(obs), Arc::new(self.clone())))
+    }
+
+    fn metadata(&self) -> AccessorMetadata {
+        let mut am = self.inner.metadata();
+        let root = format!("{}{}", am.root(), self.prefix);
+        am.set_root(&root);
+        am
+    }
+}
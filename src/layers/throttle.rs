@@ -0,0 +1,309 @@
+// Copyright 2022 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::task::Context;
+use std::task::Poll;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures::ready;
+use futures::AsyncRead;
+use futures::Stream;
+use futures::StreamExt;
+use tokio::time::Instant;
+use tokio::time::Sleep;
+
+use super::rebind;
+use crate::error::Result;
+use crate::io::BytesStream;
+use crate::ops::BatchResult;
+use crate::ops::ObjectPart;
+use crate::ops::OpAbortMultipart;
+use crate::ops::OpAppend;
+use crate::ops::OpBatch;
+use crate::ops::OpCompleteMultipart;
+use crate::ops::OpCopy;
+use crate::ops::OpCreate;
+use crate::ops::OpCreateMultipart;
+use crate::ops::OpDelete;
+use crate::ops::OpList;
+use crate::ops::OpPresign;
+use crate::ops::OpRead;
+use crate::ops::OpRename;
+use crate::ops::OpScan;
+use crate::ops::OpStat;
+use crate::ops::OpWrite;
+use crate::ops::OpWriteMultipart;
+use crate::ops::PresignedRequest;
+use crate::Accessor;
+use crate::AccessorMetadata;
+use crate::BoxedAsyncReader;
+use crate::BoxedObjectStream;
+use crate::Layer;
+use crate::Metadata;
+
+/// ThrottleLayer limits the bandwidth of an operator by a token bucket
+/// over bytes, shared by reads and writes.
+///
+/// The bucket is refilled by `bandwidth` bytes per second up to `burst`
+/// bytes. Bytes are taken from the bucket once transferred, and the next
+/// transfer waits until the bucket is no longer in debt, so that chunks
+/// larger than `burst` are allowed while the average rate is kept.
+///
+/// Every operator wrapped by the layer has its own bucket.
+///
+/// # Example
+///
+/// ```
+/// use anyhow::Result;
+/// use opendal::layers::ThrottleLayer;
+/// use opendal::services::memory;
+/// use opendal::Operator;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<()> {
+///     // 50 MiB/s with bursts of 4 MiB.
+///     let op = Operator::new(memory::Backend::build().finish().await?)
+///         .layer(ThrottleLayer::new(50 * 1024 * 1024, 4 * 1024 * 1024));
+///
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct ThrottleLayer {
+    bandwidth: u64,
+    burst: u64,
+}
+
+impl ThrottleLayer {
+    /// Create a new layer which transfers at most `bandwidth` bytes per
+    /// second on average, and at most `burst` bytes at once after idle.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `bandwidth` or `burst` is 0.
+    pub fn new(bandwidth: u64, burst: u64) -> Self {
+        assert!(
+            bandwidth > 0 && burst > 0,
+            "bandwidth and burst must be at least 1"
+        );
+
+        Self { bandwidth, burst }
+    }
+}
+
+impl Layer for ThrottleLayer {
+    fn layer(&self, inner: Arc<dyn Accessor>) -> Arc<dyn Accessor> {
+        Arc::new(ThrottleAccessor {
+            inner,
+            bucket: Arc::new(Bucket {
+                bandwidth: self.bandwidth as f64,
+                burst: self.burst as f64,
+                state: Mutex::new((self.burst as f64, Instant::now())),
+            }),
+        })
+    }
+}
+
+#[derive(Debug)]
+struct Bucket {
+    bandwidth: f64,
+    burst: f64,
+    /// Tokens available, which are negative in debt, and the time they
+    /// were refilled.
+    state: Mutex<(f64, Instant)>,
+}
+
+impl Bucket {
+    /// Take `n` bytes from the bucket, returns the time to wait until the
+    /// bucket is out of debt.
+    fn take(&self, n: usize) -> Option<Instant> {
+        let mut state = self.state.lock().expect("lock must not be poisoned");
+        let now = Instant::now();
+        let (tokens, last) = *state;
+        let tokens = (tokens + now.duration_since(last).as_secs_f64() * self.bandwidth)
+            .min(self.burst)
+            - n as f64;
+        *state = (tokens, now);
+
+        if tokens >= 0.0 {
+            return None;
+        }
+        Some(now + Duration::from_secs_f64(-tokens / self.bandwidth))
+    }
+}
+
+#[derive(Debug, Clone)]
+struct ThrottleAccessor {
+    inner: Arc<dyn Accessor>,
+    bucket: Arc<Bucket>,
+}
+
+impl ThrottleAccessor {
+    fn throttle_read(&self, s: BytesStream) -> BytesStream {
+        Box::new(ThrottleStream {
+            inner: s,
+            bucket: self.bucket.clone(),
+            pending: None,
+        })
+    }
+
+    fn throttle_write(&self, r: BoxedAsyncReader) -> BoxedAsyncReader {
+        Box::new(ThrottleReader {
+            inner: r,
+            bucket: self.bucket.clone(),
+            sleep: None,
+        })
+    }
+}
+
+/// ThrottleStream delays every chunk until the bucket is out of debt.
+struct ThrottleStream {
+    inner: BytesStream,
+    bucket: Arc<Bucket>,
+    pending: Option<(Bytes, Pin<Box<Sleep>>)>,
+}
+
+impl Stream for ThrottleStream {
+    type Item = Result<Bytes>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if let Some((_, sleep)) = &mut self.pending {
+            ready!(sleep.as_mut().poll(cx));
+            let (bs, _) = self.pending.take().expect("pending chunk must exist");
+            return Poll::Ready(Some(Ok(bs)));
+        }
+
+        match ready!(self.inner.poll_next_unpin(cx)) {
+            Some(Ok(bs)) => match self.bucket.take(bs.len()) {
+                None => Poll::Ready(Some(Ok(bs))),
+                Some(deadline) => {
+                    self.pending = Some((bs, Box::pin(tokio::time::sleep_until(deadline))));
+                    self.poll_next(cx)
+                }
+            },
+            v => Poll::Ready(v),
+        }
+    }
+}
+
+/// ThrottleReader waits until the bucket is out of debt before every read.
+struct ThrottleReader {
+    inner: BoxedAsyncReader,
+    bucket: Arc<Bucket>,
+    sleep: Option<Pin<Box<Sleep>>>,
+}
+
+impl AsyncRead for ThrottleReader {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        if let Some(sleep) = &mut self.sleep {
+            ready!(sleep.as_mut().poll(cx));
+            self.sleep = None;
+        }
+
+        let n = ready!(Pin::new(&mut self.inner).poll_read(cx, buf))?;
+        if let Some(deadline) = self.bucket.take(n) {
+            self.sleep = Some(Box::pin(tokio::time::sleep_until(deadline)));
+        }
+        Poll::Ready(Ok(n))
+    }
+}
+
+#[async_trait]
+impl Accessor for ThrottleAccessor {
+    async fn read(&self, args: &OpRead) -> Result<BytesStream> {
+        let s = self.inner.read(args).await?;
+        Ok(self.throttle_read(s))
+    }
+    async fn read_mmap(&self, args: &OpRead) -> Result<Bytes> {
+        let bs = self.inner.read_mmap(args).await?;
+        if let Some(deadline) = self.bucket.take(bs.len()) {
+            tokio::time::sleep_until(deadline).await;
+        }
+        Ok(bs)
+    }
+    async fn read_with_metadata(&self, args: &OpRead) -> Result<(BytesStream, Metadata)> {
+        let (s, meta) = self.inner.read_with_metadata(args).await?;
+        Ok((self.throttle_read(s), meta))
+    }
+    async fn write(&self, r: BoxedAsyncReader, args: &OpWrite) -> Result<Metadata> {
+        self.inner.write(self.throttle_write(r), args).await
+    }
+    async fn append(&self, r: BoxedAsyncReader, args: &OpAppend) -> Result<Metadata> {
+        self.inner.append(self.throttle_write(r), args).await
+    }
+    async fn create(&self, args: &OpCreate) -> Result<Metadata> {
+        self.inner.create(args).await
+    }
+    async fn stat(&self, args: &OpStat) -> Result<Metadata> {
+        self.inner.stat(args).await
+    }
+    async fn delete(&self, args: &OpDelete) -> Result<()> {
+        self.inner.delete(args).await
+    }
+    async fn batch(&self, args: &OpBatch) -> Result<Vec<(String, BatchResult)>> {
+        self.inner.batch(args).await
+    }
+    async fn copy(&self, args: &OpCopy) -> Result<Metadata> {
+        self.inner.copy(args).await
+    }
+    async fn rename(&self, args: &OpRename) -> Result<Metadata> {
+        self.inner.rename(args).await
+    }
+    fn presign(&self, args: &OpPresign) -> Result<PresignedRequest> {
+        self.inner.presign(args)
+    }
+    async fn create_multipart(&self, args: &OpCreateMultipart) -> Result<String> {
+        self.inner.create_multipart(args).await
+    }
+    async fn write_multipart(
+        &self,
+        r: BoxedAsyncReader,
+        args: &OpWriteMultipart,
+    ) -> Result<ObjectPart> {
+        self.inner
+            .write_multipart(self.throttle_write(r), args)
+            .await
+    }
+    async fn complete_multipart(&self, args: &OpCompleteMultipart) -> Result<Metadata> {
+        self.inner.complete_multipart(args).await
+    }
+    async fn abort_multipart(&self, args: &OpAbortMultipart) -> Result<()> {
+        self.inner.abort_multipart(args).await
+    }
+    async fn list(&self, args: &OpList) -> Result<BoxedObjectStream> {
+        let obs = self.inner.list(args).await?;
+
+        Ok(rebind(obs, Arc::new(self.clone())))
+    }
+    async fn scan(&self, args: &OpScan) -> Result<BoxedObjectStream> {
+        let obs = self.inner.scan(args).await?;
+
+        Ok(rebind(obs, Arc::new(self.clone())))
+    }
+
+    fn metadata(&self) -> AccessorMetadata {
+        self.inner.metadata()
+    }
+}
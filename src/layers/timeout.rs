@@ -0,0 +1,399 @@
+// Copyright 2022 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::Context;
+use std::task::Poll;
+use std::time::Duration;
+
+use anyhow::anyhow;
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures::Stream;
+use tokio::time::Instant;
+use tokio::time::Sleep;
+
+use super::rebind;
+use crate::error::Error;
+use crate::error::Kind;
+use crate::error::Result;
+use crate::io::BytesStream;
+use crate::ops::BatchResult;
+use crate::ops::ObjectPart;
+use crate::ops::OpAbortMultipart;
+use crate::ops::OpAppend;
+use crate::ops::OpBatch;
+use crate::ops::OpCompleteMultipart;
+use crate::ops::OpCopy;
+use crate::ops::OpCreate;
+use crate::ops::OpCreateMultipart;
+use crate::ops::OpDelete;
+use crate::ops::OpList;
+use crate::ops::OpPresign;
+use crate::ops::OpRead;
+use crate::ops::OpRename;
+use crate::ops::OpScan;
+use crate::ops::OpStat;
+use crate::ops::OpWrite;
+use crate::ops::OpWriteMultipart;
+use crate::ops::PresignedRequest;
+use crate::Accessor;
+use crate::AccessorMetadata;
+use crate::BoxedAsyncReader;
+use crate::BoxedObjectStream;
+use crate::Layer;
+use crate::Metadata;
+
+/// TimeoutLayer fails operations that don't finish in the given duration
+/// with `Kind::Timeout`, which is retryable by
+/// [`RetryLayer`][crate::layers::RetryLayer].
+///
+/// For `read`, `list` and `scan`, the timeout covers consuming the returned
+/// stream too, and the stream yields the error once it's exceeded. Besides:
+///
+/// - [`TimeoutLayer::first_byte_timeout`] limits the time until the first
+///   chunk of `read` arrives.
+/// - [`TimeoutLayer::io_timeout`] limits the time waiting for every next
+///   item of the streams, so that stalled transfers fail early.
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use anyhow::Result;
+/// use opendal::layers::TimeoutLayer;
+/// use opendal::services::memory;
+/// use opendal::Operator;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<()> {
+///     let op = Operator::new(memory::Backend::build().finish().await?).layer(
+///         TimeoutLayer::new(Duration::from_secs(600))
+///             .first_byte_timeout(Duration::from_secs(10))
+///             .io_timeout(Duration::from_secs(30)),
+///     );
+///
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct TimeoutLayer {
+    timeout: Duration,
+    first_byte_timeout: Option<Duration>,
+    io_timeout: Option<Duration>,
+}
+
+impl TimeoutLayer {
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            timeout,
+            first_byte_timeout: None,
+            io_timeout: None,
+        }
+    }
+
+    /// Fail `read` if the first chunk doesn't arrive in the duration since
+    /// the operation started.
+    #[must_use]
+    pub fn first_byte_timeout(mut self, timeout: Duration) -> Self {
+        self.first_byte_timeout = Some(timeout);
+        self
+    }
+
+    /// Fail streams of `read`, `list` and `scan` if the next item doesn't
+    /// arrive in the duration.
+    #[must_use]
+    pub fn io_timeout(mut self, timeout: Duration) -> Self {
+        self.io_timeout = Some(timeout);
+        self
+    }
+}
+
+impl Layer for TimeoutLayer {
+    fn layer(&self, inner: Arc<dyn Accessor>) -> Arc<dyn Accessor> {
+        Arc::new(TimeoutAccessor {
+            inner,
+            layer: *self,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+struct TimeoutAccessor {
+    inner: Arc<dyn Accessor>,
+    layer: TimeoutLayer,
+}
+
+impl TimeoutAccessor {
+    async fn timeout<T>(
+        &self,
+        op: &'static str,
+        path: &str,
+        fut: impl Future<Output = Result<T>>,
+    ) -> Result<T> {
+        tokio::time::timeout(self.layer.timeout, fut)
+            .await
+            .map_err(|_| {
+                timeout_error(
+                    op,
+                    path,
+                    anyhow!("operation timeout after {:?}", self.layer.timeout),
+                )
+            })?
+    }
+
+    /// Call `fut` which returns a stream, and limit consuming the stream.
+    async fn timeout_stream<S, T>(
+        &self,
+        op: &'static str,
+        path: &str,
+        first_byte_timeout: Option<Duration>,
+        fut: impl Future<Output = Result<S>>,
+    ) -> Result<TimeoutStream<S>>
+    where
+        S: Stream<Item = Result<T>> + Unpin,
+    {
+        let start = Instant::now();
+        let deadline = start + self.layer.timeout;
+        let first_byte_deadline = first_byte_timeout.map(|v| start + v);
+
+        let s = match tokio::time::timeout_at(
+            first_byte_deadline.map_or(deadline, |v| v.min(deadline)),
+            fut,
+        )
+        .await
+        {
+            Ok(s) => s?,
+            Err(_) => {
+                let source = match first_byte_deadline {
+                    Some(v) if v < deadline => anyhow!(
+                        "first byte timeout after {:?}",
+                        first_byte_timeout.unwrap_or_default()
+                    ),
+                    _ => anyhow!("operation timeout after {:?}", self.layer.timeout),
+                };
+                return Err(timeout_error(op, path, source));
+            }
+        };
+
+        Ok(TimeoutStream {
+            inner: s,
+            op,
+            path: path.to_string(),
+            timeout: self.layer.timeout,
+            deadline: Box::pin(tokio::time::sleep_until(deadline)),
+            first_byte_timeout,
+            io_timeout: self.layer.io_timeout,
+            next: first_byte_deadline
+                .or_else(|| self.layer.io_timeout.map(|v| Instant::now() + v))
+                .map(|v| Box::pin(tokio::time::sleep_until(v))),
+            started: false,
+            done: false,
+        })
+    }
+}
+
+fn timeout_error(op: &'static str, path: &str, source: anyhow::Error) -> Error {
+    Error::Object {
+        kind: Kind::Timeout,
+        op,
+        path: path.to_string(),
+        context: HashMap::new(),
+        source,
+    }
+}
+
+/// TimeoutStream yields a timeout error if the operation exceeds the
+/// deadline, or the next item doesn't arrive in time.
+struct TimeoutStream<S> {
+    inner: S,
+    op: &'static str,
+    path: String,
+
+    timeout: Duration,
+    deadline: Pin<Box<Sleep>>,
+    first_byte_timeout: Option<Duration>,
+    io_timeout: Option<Duration>,
+    /// Timer of the next item, the first item is limited by the first byte
+    /// timeout if set.
+    next: Option<Pin<Box<Sleep>>>,
+    started: bool,
+    done: bool,
+}
+
+impl<S, T> Stream for TimeoutStream<S>
+where
+    S: Stream<Item = Result<T>> + Unpin,
+{
+    type Item = Result<T>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        if this.done {
+            return Poll::Ready(None);
+        }
+
+        if let Poll::Ready(v) = Pin::new(&mut this.inner).poll_next(cx) {
+            match &v {
+                None => this.done = true,
+                Some(_) => {
+                    this.started = true;
+                    this.next = this
+                        .io_timeout
+                        .map(|d| Box::pin(tokio::time::sleep_until(Instant::now() + d)));
+                }
+            }
+            return Poll::Ready(v);
+        }
+
+        let source = if this.deadline.as_mut().poll(cx).is_ready() {
+            anyhow!("operation timeout after {:?}", this.timeout)
+        } else if let Some(Poll::Ready(())) = this.next.as_mut().map(|v| v.as_mut().poll(cx)) {
+            match (this.started, this.first_byte_timeout) {
+                (false, Some(d)) => anyhow!("first byte timeout after {:?}", d),
+                _ => anyhow!(
+                    "no data received in {:?}",
+                    this.io_timeout.unwrap_or_default()
+                ),
+            }
+        } else {
+            return Poll::Pending;
+        };
+
+        this.done = true;
+        Poll::Ready(Some(Err(timeout_error(this.op, &this.path, source))))
+    }
+}
+
+#[async_trait]
+impl Accessor for TimeoutAccessor {
+    async fn read(&self, args: &OpRead) -> Result<BytesStream> {
+        let s = self
+            .timeout_stream(
+                "read",
+                &args.path,
+                self.layer.first_byte_timeout,
+                self.inner.read(args),
+            )
+            .await?;
+        Ok(Box::new(s))
+    }
+    async fn read_mmap(&self, args: &OpRead) -> Result<Bytes> {
+        self.timeout("read", &args.path, self.inner.read_mmap(args))
+            .await
+    }
+    async fn read_with_metadata(&self, args: &OpRead) -> Result<(BytesStream, Metadata)> {
+        let mut meta = None;
+        let s = self
+            .timeout_stream("read", &args.path, self.layer.first_byte_timeout, async {
+                let (s, m) = self.inner.read_with_metadata(args).await?;
+                meta = Some(m);
+                Ok::<_, Error>(s)
+            })
+            .await?;
+        Ok((Box::new(s), meta.expect("metadata must be set")))
+    }
+    async fn write(&self, r: BoxedAsyncReader, args: &OpWrite) -> Result<Metadata> {
+        self.timeout("write", &args.path, self.inner.write(r, args))
+            .await
+    }
+    async fn append(&self, r: BoxedAsyncReader, args: &OpAppend) -> Result<Metadata> {
+        self.timeout("append", &args.path, self.inner.append(r, args))
+            .await
+    }
+    async fn create(&self, args: &OpCreate) -> Result<Metadata> {
+        self.timeout("create", &args.path, self.inner.create(args))
+            .await
+    }
+    async fn stat(&self, args: &OpStat) -> Result<Metadata> {
+        self.timeout("stat", &args.path, self.inner.stat(args))
+            .await
+    }
+    async fn delete(&self, args: &OpDelete) -> Result<()> {
+        self.timeout("delete", &args.path, self.inner.delete(args))
+            .await
+    }
+    async fn batch(&self, args: &OpBatch) -> Result<Vec<(String, BatchResult)>> {
+        let path = args.ops.first().map(|v| v.path()).unwrap_or_default();
+        self.timeout("batch", path, self.inner.batch(args)).await
+    }
+    async fn copy(&self, args: &OpCopy) -> Result<Metadata> {
+        self.timeout("copy", &args.src, self.inner.copy(args)).await
+    }
+    async fn rename(&self, args: &OpRename) -> Result<Metadata> {
+        self.timeout("rename", &args.src, self.inner.rename(args))
+            .await
+    }
+    fn presign(&self, args: &OpPresign) -> Result<PresignedRequest> {
+        self.inner.presign(args)
+    }
+    async fn create_multipart(&self, args: &OpCreateMultipart) -> Result<String> {
+        self.timeout(
+            "create_multipart",
+            &args.path,
+            self.inner.create_multipart(args),
+        )
+        .await
+    }
+    async fn write_multipart(
+        &self,
+        r: BoxedAsyncReader,
+        args: &OpWriteMultipart,
+    ) -> Result<ObjectPart> {
+        self.timeout(
+            "write_multipart",
+            &args.path,
+            self.inner.write_multipart(r, args),
+        )
+        .await
+    }
+    async fn complete_multipart(&self, args: &OpCompleteMultipart) -> Result<Metadata> {
+        self.timeout(
+            "complete_multipart",
+            &args.path,
+            self.inner.complete_multipart(args),
+        )
+        .await
+    }
+    async fn abort_multipart(&self, args: &OpAbortMultipart) -> Result<()> {
+        self.timeout(
+            "abort_multipart",
+            &args.path,
+            self.inner.abort_multipart(args),
+        )
+        .await
+    }
+    async fn list(&self, args: &OpList) -> Result<BoxedObjectStream> {
+        let obs = self
+            .timeout_stream("list", &args.path, None, self.inner.list(args))
+            .await?;
+
+        Ok(rebind(Box::new(obs), Arc::new(self.clone())))
+    }
+    async fn scan(&self, args: &OpScan) -> Result<BoxedObjectStream> {
+        let obs = self
+            .timeout_stream("scan", &args.path, None, self.inner.scan(args))
+            .await?;
+
+        Ok(rebind(Box::new(obs), Arc::new(self.clone())))
+    }
+
+    fn metadata(&self) -> AccessorMetadata {
+        self.inner.metadata()
+    }
+}
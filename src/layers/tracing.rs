@@ -0,0 +1,297 @@
+// Copyright 2022 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::task::Context;
+use std::task::Poll;
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures::Stream;
+use tracing::field::Empty;
+use tracing::info_span;
+use tracing::Instrument;
+use tracing::Span;
+
+use super::rebind;
+use crate::error::Result;
+use crate::io::BytesStream;
+use crate::ops::BatchResult;
+use crate::ops::ObjectPart;
+use crate::ops::OpAbortMultipart;
+use crate::ops::OpAppend;
+use crate::ops::OpBatch;
+use crate::ops::OpCompleteMultipart;
+use crate::ops::OpCopy;
+use crate::ops::OpCreate;
+use crate::ops::OpCreateMultipart;
+use crate::ops::OpDelete;
+use crate::ops::OpList;
+use crate::ops::OpPresign;
+use crate::ops::OpRead;
+use crate::ops::OpRename;
+use crate::ops::OpScan;
+use crate::ops::OpStat;
+use crate::ops::OpWrite;
+use crate::ops::OpWriteMultipart;
+use crate::ops::PresignedRequest;
+use crate::readers::CallbackReader;
+use crate::Accessor;
+use crate::AccessorMetadata;
+use crate::BoxedAsyncReader;
+use crate::BoxedObjectStream;
+use crate::Layer;
+use crate::Metadata;
+
+/// TracingLayer creates a span named `opendal` for every operation via the
+/// `tracing` crate, with fields:
+///
+/// - `operation`, `scheme` and `path`.
+/// - `bytes`: bytes actually read or written, recorded once the body has
+///   been consumed.
+/// - `error`: the error if the operation failed.
+///
+/// Spans are children of the current span, and entered while the backend
+/// is polled, including the returned streams of `read`, so that spans and
+/// events of the http client are nested in them.
+///
+/// Requires the `tracing` feature.
+///
+/// # Example
+///
+/// ```
+/// use anyhow::Result;
+/// use opendal::layers::TracingLayer;
+/// use opendal::services::memory;
+/// use opendal::Operator;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<()> {
+///     let op = Operator::new(memory::Backend::build().finish().await?).layer(TracingLayer);
+///
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TracingLayer;
+
+impl Layer for TracingLayer {
+    fn layer(&self, inner: Arc<dyn Accessor>) -> Arc<dyn Accessor> {
+        let scheme = inner
+            .metadata()
+            .scheme()
+            .map(|v| v.as_str())
+            .unwrap_or("unknown");
+        Arc::new(TracingAccessor { inner, scheme })
+    }
+}
+
+#[derive(Debug, Clone)]
+struct TracingAccessor {
+    inner: Arc<dyn Accessor>,
+    scheme: &'static str,
+}
+
+impl TracingAccessor {
+    fn span(&self, op: &'static str, path: &str) -> Span {
+        info_span!(
+            "opendal",
+            operation = op,
+            scheme = self.scheme,
+            path = path,
+            bytes = Empty,
+            error = Empty
+        )
+    }
+
+    async fn trace<T>(&self, span: Span, fut: impl Future<Output = Result<T>>) -> Result<T> {
+        let result = fut.instrument(span.clone()).await;
+        if let Err(err) = &result {
+            span.record("error", tracing::field::display(err));
+        }
+        result
+    }
+
+    /// Count bytes taken from the reader by the backend into `bytes`.
+    fn count_write(r: BoxedAsyncReader) -> (BoxedAsyncReader, Arc<AtomicU64>) {
+        let bytes = Arc::new(AtomicU64::new(0));
+        let counter = bytes.clone();
+        let r = CallbackReader::new(r, move |n| {
+            counter.fetch_add(n as u64, Ordering::Relaxed);
+        });
+        (Box::new(r), bytes)
+    }
+
+    async fn trace_write<T>(
+        &self,
+        span: Span,
+        bytes: Arc<AtomicU64>,
+        fut: impl Future<Output = Result<T>>,
+    ) -> Result<T> {
+        let result = self.trace(span.clone(), fut).await;
+        span.record("bytes", bytes.load(Ordering::Relaxed));
+        result
+    }
+}
+
+#[async_trait]
+impl Accessor for TracingAccessor {
+    async fn read(&self, args: &OpRead) -> Result<BytesStream> {
+        let span = self.span("read", &args.path);
+        let s = self.trace(span.clone(), self.inner.read(args)).await?;
+        Ok(Box::new(TracingStream::new(s, span)))
+    }
+    async fn read_mmap(&self, args: &OpRead) -> Result<Bytes> {
+        let span = self.span("read", &args.path);
+        let bs = self.trace(span.clone(), self.inner.read_mmap(args)).await?;
+        span.record("bytes", bs.len() as u64);
+        Ok(bs)
+    }
+    async fn read_with_metadata(&self, args: &OpRead) -> Result<(BytesStream, Metadata)> {
+        let span = self.span("read", &args.path);
+        let (s, meta) = self
+            .trace(span.clone(), self.inner.read_with_metadata(args))
+            .await?;
+        Ok((Box::new(TracingStream::new(s, span)), meta))
+    }
+    async fn write(&self, r: BoxedAsyncReader, args: &OpWrite) -> Result<Metadata> {
+        let (r, bytes) = Self::count_write(r);
+        let span = self.span("write", &args.path);
+        self.trace_write(span, bytes, self.inner.write(r, args))
+            .await
+    }
+    async fn append(&self, r: BoxedAsyncReader, args: &OpAppend) -> Result<Metadata> {
+        let (r, bytes) = Self::count_write(r);
+        let span = self.span("append", &args.path);
+        self.trace_write(span, bytes, self.inner.append(r, args))
+            .await
+    }
+    async fn create(&self, args: &OpCreate) -> Result<Metadata> {
+        let span = self.span("create", &args.path);
+        self.trace(span, self.inner.create(args)).await
+    }
+    async fn stat(&self, args: &OpStat) -> Result<Metadata> {
+        let span = self.span("stat", &args.path);
+        self.trace(span, self.inner.stat(args)).await
+    }
+    async fn delete(&self, args: &OpDelete) -> Result<()> {
+        let span = self.span("delete", &args.path);
+        self.trace(span, self.inner.delete(args)).await
+    }
+    async fn batch(&self, args: &OpBatch) -> Result<Vec<(String, BatchResult)>> {
+        let path = args.ops.first().map(|v| v.path()).unwrap_or_default();
+        let span = self.span("batch", path);
+        self.trace(span, self.inner.batch(args)).await
+    }
+    async fn copy(&self, args: &OpCopy) -> Result<Metadata> {
+        let span = self.span("copy", &args.src);
+        self.trace(span, self.inner.copy(args)).await
+    }
+    async fn rename(&self, args: &OpRename) -> Result<Metadata> {
+        let span = self.span("rename", &args.src);
+        self.trace(span, self.inner.rename(args)).await
+    }
+    fn presign(&self, args: &OpPresign) -> Result<PresignedRequest> {
+        let span = self.span("presign", &args.path);
+        let result = span.in_scope(|| self.inner.presign(args));
+        if let Err(err) = &result {
+            span.record("error", tracing::field::display(err));
+        }
+        result
+    }
+    async fn create_multipart(&self, args: &OpCreateMultipart) -> Result<String> {
+        let span = self.span("create_multipart", &args.path);
+        self.trace(span, self.inner.create_multipart(args)).await
+    }
+    async fn write_multipart(
+        &self,
+        r: BoxedAsyncReader,
+        args: &OpWriteMultipart,
+    ) -> Result<ObjectPart> {
+        let (r, bytes) = Self::count_write(r);
+        let span = self.span("write_multipart", &args.path);
+        self.trace_write(span, bytes, self.inner.write_multipart(r, args))
+            .await
+    }
+    async fn complete_multipart(&self, args: &OpCompleteMultipart) -> Result<Metadata> {
+        let span = self.span("complete_multipart", &args.path);
+        self.trace(span, self.inner.complete_multipart(args)).await
+    }
+    async fn abort_multipart(&self, args: &OpAbortMultipart) -> Result<()> {
+        let span = self.span("abort_multipart", &args.path);
+        self.trace(span, self.inner.abort_multipart(args)).await
+    }
+    async fn list(&self, args: &OpList) -> Result<BoxedObjectStream> {
+        let span = self.span("list", &args.path);
+        let obs = self.trace(span, self.inner.list(args)).await?;
+
+        Ok(rebind(obs, Arc::new(self.clone())))
+    }
+    async fn scan(&self, args: &OpScan) -> Result<BoxedObjectStream> {
+        let span = self.span("scan", &args.path);
+        let obs = self.trace(span, self.inner.scan(args)).await?;
+
+        Ok(rebind(obs, Arc::new(self.clone())))
+    }
+
+    fn metadata(&self) -> AccessorMetadata {
+        self.inner.metadata()
+    }
+}
+
+/// TracingStream enters the span while polling the stream, and records the
+/// bytes read once it's dropped.
+struct TracingStream {
+    inner: BytesStream,
+    span: Span,
+    bytes: u64,
+}
+
+impl TracingStream {
+    fn new(inner: BytesStream, span: Span) -> Self {
+        Self {
+            inner,
+            span,
+            bytes: 0,
+        }
+    }
+}
+
+impl Stream for TracingStream {
+    type Item = Result<Bytes>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        let _enter = this.span.enter();
+        let poll = Pin::new(&mut this.inner).poll_next(cx);
+        match &poll {
+            Poll::Ready(Some(Ok(bs))) => this.bytes += bs.len() as u64,
+            Poll::Ready(Some(Err(err))) => {
+                this.span.record("error", tracing::field::display(err));
+            }
+            _ => {}
+        }
+        poll
+    }
+}
+
+impl Drop for TracingStream {
+    fn drop(&mut self) {
+        self.span.record("bytes", self.bytes);
+    }
+}
@@ -32,7 +32,7 @@
 //!
 //!     // Write data info file;
 //!     let w = o.writer();
-//!     let n = w
+//!     let meta = w
 //!        .write_bytes("Hello, World!".to_string().into_bytes())
 //!         .await?;
 //!
@@ -74,17 +74,44 @@ extern crate core;
 
 mod accessor;
 pub use accessor::Accessor;
+pub use accessor::AccessorMetadata;
 
 mod io;
 pub use io::BoxedAsyncReader;
 pub use io::Reader;
+pub use io::SeekableReader;
 pub use io::Writer;
 
 mod layer;
 pub use layer::Layer;
 
+mod lock;
+pub use lock::Lock;
+
+mod shutdown;
+pub use shutdown::Close;
+pub use shutdown::ShutdownGuard;
+
+mod analyze;
+pub use analyze::AnalyzeOptions;
+pub use analyze::KeySize;
+pub use analyze::KeyStats;
+pub use analyze::PrefixAnalysis;
+
+mod read_many;
+pub use read_many::ReadManyOptions;
+pub use read_many::ReadManyStream;
+
 mod operator;
+pub use operator::Digest;
 pub use operator::Operator;
+pub use operator::OperatorOptions;
+pub use operator::WriteOutcome;
+
+mod blocking;
+pub use blocking::BlockingObjectIterator;
+pub use blocking::BlockingOperator;
+pub use blocking::BlockingReader;
 
 mod object;
 pub use object::BoxedObjectStream;
@@ -96,8 +123,15 @@ pub use object::ObjectStream;
 mod scheme;
 pub use scheme::Scheme;
 
+mod config;
+mod path;
+mod uri;
+
 pub mod credential;
 pub mod error;
+pub mod format;
+pub mod http_util;
+pub mod layers;
 pub mod readers;
 
 pub mod ops;
@@ -0,0 +1,351 @@
+// Copyright 2022 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::fmt::Formatter;
+use std::time::Duration;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+use anyhow::anyhow;
+use async_trait::async_trait;
+use futures::TryStreamExt;
+use log::debug;
+use log::warn;
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::error::Error;
+use crate::error::Kind;
+use crate::error::Result;
+use crate::ops::OpDelete;
+use crate::ops::OpRead;
+use crate::ops::OpStat;
+use crate::shutdown::spawn_cleanup;
+use crate::Close;
+use crate::Operator;
+
+/// Expired locks can only be stolen after this margin, to tolerate clock
+/// skew between workers.
+pub(crate) const LOCK_CLOCK_SKEW_MARGIN: Duration = Duration::from_secs(5);
+
+/// Max attempts of acquire while the lock object keeps changing underneath.
+const LOCK_ACQUIRE_ATTEMPTS: usize = 3;
+
+/// Content of the lock object.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct LockContent {
+    pub(crate) owner: String,
+    /// Milliseconds since unix epoch.
+    pub(crate) expires_at: u64,
+}
+
+impl LockContent {
+    fn expired(&self, now: SystemTime) -> bool {
+        unix_ms(now) > self.expires_at + LOCK_CLOCK_SKEW_MARGIN.as_millis() as u64
+    }
+}
+
+fn unix_ms(t: SystemTime) -> u64 {
+    t.duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64
+}
+
+/// Lock is a cooperative lease on an object, like "only one worker processes
+/// prefix X at a time".
+///
+/// The lock object contains the owner and the expiry of the lease, and all
+/// changes of it are done by conditional writes:
+///
+/// - [`Lock::acquire`] creates the lock object only if it doesn't exist,
+///   or steals it with ETag CAS once it has expired for more than a safety
+///   margin of clock skew.
+/// - [`Lock::renew`] extends the lease only if the lock object is still the
+///   one we wrote.
+/// - [`Lock::release`] deletes the lock object only if we still own it.
+///
+/// # Note
+///
+/// The lock is advisory: it doesn't prevent anyone from accessing the data,
+/// and holders must renew the lease before it expires, or other workers may
+/// steal it. It depends on the backend's conditional write support, read
+/// [`AccessorMetadata::conditional_write`][crate::AccessorMetadata::conditional_write]
+/// for details, `Kind::Unsupported` will be returned otherwise.
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use anyhow::Result;
+/// use opendal::error::Kind;
+/// use opendal::services::memory;
+/// use opendal::Lock;
+/// use opendal::Operator;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<()> {
+///     let op = Operator::new(memory::Backend::build().finish().await?);
+///
+///     let ttl = Duration::from_secs(60);
+///     let mut lock = Lock::acquire(&op, "locks/daily", ttl, "worker-1").await?;
+///
+///     let err = Lock::acquire(&op, "locks/daily", ttl, "worker-2").await.unwrap_err();
+///     assert_eq!(err.kind(), Kind::ObjectLocked);
+///
+///     lock.renew().await?;
+///     lock.release().await?;
+///
+///     Ok(())
+/// }
+/// ```
+pub struct Lock {
+    op: Operator,
+    path: String,
+    owner: String,
+    ttl: Duration,
+
+    etag: String,
+    expires_at: SystemTime,
+    /// Whether the lock object is written by us and not released yet.
+    held: bool,
+}
+
+impl Debug for Lock {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Lock")
+            .field("path", &self.path)
+            .field("owner", &self.owner)
+            .field("ttl", &self.ttl)
+            .field("etag", &self.etag)
+            .field("expires_at", &self.expires_at)
+            .field("held", &self.held)
+            .finish()
+    }
+}
+
+impl Lock {
+    /// Acquire the lock at `path` for `ttl` on behalf of `owner`.
+    ///
+    /// Fails with `Kind::ObjectLocked` if the lock is held by others and
+    /// has not expired.
+    pub async fn acquire(op: &Operator, path: &str, ttl: Duration, owner: &str) -> Result<Lock> {
+        if !op.metadata().conditional_write() {
+            return Err(Error::Object {
+                kind: Kind::Unsupported,
+                op: "lock",
+                path: path.to_string(),
+                context: HashMap::new(),
+                source: anyhow!("lock requires conditional write support of backend"),
+            });
+        }
+
+        let mut lock = Lock {
+            op: op.clone(),
+            path: path.to_string(),
+            owner: owner.to_string(),
+            ttl,
+
+            etag: String::new(),
+            expires_at: UNIX_EPOCH,
+            held: false,
+        };
+
+        let mut last_err = None;
+        for _ in 0..LOCK_ACQUIRE_ATTEMPTS {
+            // Create the lock object if it doesn't exist.
+            match lock.write(None).await {
+                Err(e) if e.kind() == Kind::ObjectPreconditionFailed => {}
+                res => return res.map(|_| lock),
+            }
+
+            let (etag, current) = match lock.read_current().await {
+                Ok(v) => v,
+                // The lock object has been changed or released underneath.
+                Err(e) if is_conflict(&e) => {
+                    last_err = Some(e);
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+            if !current.expired(SystemTime::now()) {
+                return Err(Error::Object {
+                    kind: Kind::ObjectLocked,
+                    op: "lock",
+                    path: path.to_string(),
+                    context: HashMap::from([
+                        ("owner".to_string(), current.owner),
+                        ("expires_at".to_string(), current.expires_at.to_string()),
+                    ]),
+                    source: anyhow!("lock is held by others"),
+                });
+            }
+
+            // Steal the expired lock, only one of the concurrent stealers wins.
+            debug!("lock {} of {} expired, steal it", path, current.owner);
+            match lock.write(Some(&etag)).await {
+                Err(e) if is_conflict(&e) => last_err = Some(e),
+                res => return res.map(|_| lock),
+            }
+        }
+
+        Err(last_err.expect("last error must be set after failed attempts"))
+    }
+
+    /// Extend the lease for another `ttl` from now.
+    ///
+    /// Fails with `Kind::ObjectPreconditionFailed` or `Kind::ObjectNotExist`
+    /// if the lock has been stolen or released, the lock is lost then.
+    pub async fn renew(&mut self) -> Result<()> {
+        let etag = self.etag.clone();
+        self.write(Some(&etag)).await
+    }
+
+    /// Release the lock.
+    ///
+    /// Fails with `Kind::ObjectPreconditionFailed` if the lock has been stolen,
+    /// the lock object will be kept for the new owner then.
+    ///
+    /// Locks dropped without release will be released in background if
+    /// there is a tokio runtime, or kept until they expire otherwise.
+    pub async fn release(mut self) -> Result<()> {
+        self.close().await
+    }
+
+    /// Path of the lock object.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Owner of the lock.
+    pub fn owner(&self) -> &str {
+        &self.owner
+    }
+
+    /// Expiry of the lease, according to the local clock.
+    pub fn expires_at(&self) -> SystemTime {
+        self.expires_at
+    }
+
+    /// Write the lock object with a new expiry, only if it doesn't exist or
+    /// its ETag matches `if_match`.
+    async fn write(&mut self, if_match: Option<&str>) -> Result<()> {
+        let expires_at = SystemTime::now() + self.ttl;
+        let content = LockContent {
+            owner: self.owner.clone(),
+            expires_at: unix_ms(expires_at),
+        };
+        let bs = serde_json::to_vec(&content).expect("lock content must be valid json");
+
+        let w = self.op.object(&self.path).writer();
+        let w = match if_match {
+            Some(etag) => w.if_match(etag),
+            None => w.if_none_match("*"),
+        };
+        let meta = w.write_bytes(bs).await?;
+        let etag = match meta.etag() {
+            Some(etag) => etag,
+            None => self
+                .op
+                .object(&self.path)
+                .metadata()
+                .await?
+                .etag()
+                .ok_or_else(|| Error::Object {
+                    kind: Kind::Unexpected,
+                    op: "lock",
+                    path: self.path.clone(),
+                    context: HashMap::new(),
+                    source: anyhow!("etag of lock object is missing"),
+                })?,
+        };
+
+        self.etag = etag;
+        self.expires_at = expires_at;
+        self.held = true;
+        Ok(())
+    }
+
+    fn delete_op(&self) -> OpDelete {
+        OpDelete {
+            path: self.path.clone(),
+            if_match: Some(self.etag.clone()),
+            ..Default::default()
+        }
+    }
+
+    /// Read the current lock object and its ETag.
+    async fn read_current(&self) -> Result<(String, LockContent)> {
+        let acc = self.op.inner();
+        let meta = acc.stat(&OpStat::new(&self.path)).await?;
+        let etag = meta.etag().unwrap_or_default();
+
+        // Read with the etag so that the content matches the etag.
+        let mut s = acc
+            .read(&OpRead {
+                path: self.path.clone(),
+                if_match: Some(etag.clone()),
+                ..Default::default()
+            })
+            .await?;
+        let mut bs = Vec::new();
+        while let Some(b) = s.try_next().await? {
+            bs.extend_from_slice(&b);
+        }
+        let content = serde_json::from_slice(&bs).map_err(|e| Error::Object {
+            kind: Kind::Unexpected,
+            op: "lock",
+            path: self.path.clone(),
+            context: HashMap::new(),
+            source: anyhow!("invalid lock content: {:?}", e),
+        })?;
+
+        Ok((etag, content))
+    }
+}
+
+/// Whether the error is caused by concurrent changes of the lock object.
+fn is_conflict(e: &Error) -> bool {
+    matches!(
+        e.kind(),
+        Kind::ObjectPreconditionFailed | Kind::ObjectNotExist
+    )
+}
+
+#[async_trait]
+impl Close for Lock {
+    /// Release the lock if it's held, see [`Lock::release`].
+    async fn close(&mut self) -> Result<()> {
+        if !self.held {
+            return Ok(());
+        }
+        self.held = false;
+        self.op.inner().delete(&self.delete_op()).await
+    }
+}
+
+impl Drop for Lock {
+    fn drop(&mut self) {
+        if !self.held {
+            return;
+        }
+
+        let (acc, op, path) = (self.op.inner(), self.delete_op(), self.path.clone());
+        spawn_cleanup("lock", &self.path, async move {
+            if let Err(e) = acc.delete(&op).await {
+                warn!("lock {} release in background: {:?}", path, e);
+            }
+        });
+    }
+}
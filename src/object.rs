@@ -11,6 +11,7 @@
 // WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
 // See the License for the specific language governing permissions and
 // limitations under the License.
+use std::collections::HashMap;
 use std::fmt::Debug;
 use std::fmt::Display;
 use std::fmt::Formatter;
@@ -19,20 +20,46 @@ use std::pin::Pin;
 use std::sync::Arc;
 use std::task::Context;
 use std::task::Poll;
+use std::time::Duration;
 use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
 
+use anyhow::anyhow;
 use futures::future::BoxFuture;
 use futures::ready;
+use futures::StreamExt;
+use futures::TryStreamExt;
+use http::HeaderMap;
+use http::Method;
+use time::OffsetDateTime;
 
+use crate::error::Error;
 use crate::error::Kind;
 use crate::error::Result;
 use crate::io::BytesStream;
+use crate::layers::glob::Glob;
+use crate::ops::BytesRange;
+use crate::ops::ListMetadata;
+use crate::ops::ObjectPart;
+use crate::ops::OpAbortMultipart;
+use crate::ops::OpAppend;
+use crate::ops::OpCompleteMultipart;
+use crate::ops::OpCopy;
+use crate::ops::OpCreate;
+use crate::ops::OpCreateMultipart;
 use crate::ops::OpDelete;
 use crate::ops::OpList;
+use crate::ops::OpPresign;
+use crate::ops::OpPriority;
 use crate::ops::OpRead;
+use crate::ops::OpRename;
 use crate::ops::OpStat;
+use crate::ops::OpWriteMultipart;
+use crate::ops::PresignedRequest;
 use crate::Accessor;
+use crate::BoxedAsyncReader;
 use crate::Reader;
+use crate::SeekableReader;
 use crate::Writer;
 
 /// Handler for all object related operations.
@@ -54,16 +81,175 @@ impl Object {
         }
     }
 
+    /// Read the object into a bytes stream.
+    ///
+    /// Read at most `size` bytes since `offset`, or the last `size` bytes if
+    /// `offset` is `None`, which reads footers without knowing the length.
     pub async fn stream(&self, offset: Option<u64>, size: Option<u64>) -> Result<BytesStream> {
         self.acc
             .read(&OpRead {
                 path: self.meta.path().to_string(),
-                offset,
-                size,
+                range: BytesRange::new(offset, size),
+                ..Default::default()
+            })
+            .await
+    }
+
+    /// Read the object into a bytes stream and return its metadata
+    /// alongside, in a single request on services like s3.
+    ///
+    /// The `content_length` of the metadata is the length of the whole
+    /// object even if only a range is read.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use opendal::services::memory;
+    /// use anyhow::Result;
+    /// use futures::TryStreamExt;
+    /// use opendal::Operator;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<()> {
+    ///     let op = Operator::new(memory::Backend::build().finish().await?);
+    ///     let o = op.object("test");
+    ///     o.writer()
+    ///         .write_bytes("Hello, World!".as_bytes().to_vec())
+    ///         .await?;
+    ///
+    ///     let (s, meta) = o.read_with_metadata(Some(7), None).await?;
+    ///     let bs = s.map_ok(|v| v.to_vec()).try_concat().await?;
+    ///     assert_eq!(bs, b"World!");
+    ///     assert_eq!(meta.content_length(), 13);
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub async fn read_with_metadata(
+        &self,
+        offset: Option<u64>,
+        size: Option<u64>,
+    ) -> Result<(BytesStream, Metadata)> {
+        self.acc
+            .read_with_metadata(&OpRead {
+                path: self.meta.path().to_string(),
+                range: BytesRange::new(offset, size),
+                ..Default::default()
             })
             .await
     }
 
+    /// Read like [`Object::read_with_metadata`], and keep all headers of
+    /// the response in [`Metadata::raw_headers`].
+    pub async fn read_with_raw_headers(
+        &self,
+        offset: Option<u64>,
+        size: Option<u64>,
+    ) -> Result<(BytesStream, Metadata)> {
+        self.acc
+            .read_with_metadata(&OpRead {
+                path: self.meta.path().to_string(),
+                range: BytesRange::new(offset, size),
+                raw_headers: true,
+                ..Default::default()
+            })
+            .await
+    }
+
+    /// Read the whole object into memory.
+    ///
+    /// Objects larger than 64 MiB are rejected with `Kind::ObjectTooLarge`,
+    /// use [`Object::range_read_with_max_size`] for another limit, or
+    /// [`Object::reader`] to read by stream.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use opendal::services::memory;
+    /// use anyhow::Result;
+    /// use opendal::Operator;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<()> {
+    ///     let op = Operator::new(memory::Backend::build().finish().await?);
+    ///     let o = op.object("test");
+    ///     o.writer()
+    ///         .write_bytes("Hello, World!".as_bytes().to_vec())
+    ///         .await?;
+    ///
+    ///     assert_eq!(o.read().await?, b"Hello, World!");
+    ///     assert_eq!(o.range_read(7..12).await?, b"World");
+    ///     // Ranges up to `n` are the last `n` bytes.
+    ///     assert_eq!(o.range_read(..6).await?, b"World!");
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub async fn read(&self) -> Result<Vec<u8>> {
+        self.range_read(..).await
+    }
+
+    /// Read a range of the object into memory, read [`BytesRange`] for the
+    /// semantics of ranges.
+    ///
+    /// Ranges larger than 64 MiB are rejected with `Kind::ObjectTooLarge`.
+    pub async fn range_read(&self, range: impl Into<BytesRange>) -> Result<Vec<u8>> {
+        self.range_read_with_max_size(range, DEFAULT_READ_MAX_SIZE)
+            .await
+    }
+
+    /// Read a range of the object into memory, fails with
+    /// `Kind::ObjectTooLarge` if the range is larger than `max_size`.
+    ///
+    /// The size is checked against the object's length before reading its
+    /// content, the error carries the length in context `size`.
+    pub async fn range_read_with_max_size(
+        &self,
+        range: impl Into<BytesRange>,
+        max_size: u64,
+    ) -> Result<Vec<u8>> {
+        let range = range.into();
+        let (mut s, meta) = self
+            .acc
+            .read_with_metadata(&OpRead {
+                path: self.meta.path().to_string(),
+                range,
+                ..Default::default()
+            })
+            .await?;
+
+        let too_large = |size: String| Error::Object {
+            kind: Kind::ObjectTooLarge,
+            op: "read",
+            path: self.meta.path().to_string(),
+            context: HashMap::from([("size".to_string(), size.clone())]),
+            source: anyhow!(
+                "range {} of object sized {} is larger than {} bytes",
+                range,
+                size,
+                max_size
+            ),
+        };
+        let length = meta.try_content_length();
+        if let Some(length) = length {
+            if range_length(range, length) > max_size {
+                return Err(too_large(length.to_string()));
+            }
+        }
+
+        // The length could be unknown or changed after stat.
+        let mut buf = Vec::new();
+        while let Some(bs) = s.try_next().await? {
+            if (buf.len() + bs.len()) as u64 > max_size {
+                return Err(too_large(
+                    length.map_or_else(|| "unknown".to_string(), |v| v.to_string()),
+                ));
+            }
+            buf.extend_from_slice(&bs);
+        }
+        Ok(buf)
+    }
+
     /// Create a new reader which can read the whole object.
     ///
     /// # Example
@@ -189,14 +375,63 @@ impl Object {
         Reader::new(self.acc.clone(), self.meta.path(), None, Some(size))
     }
 
+    /// Create a new seekable reader which reads by ranged reads on demand,
+    /// read [`SeekableReader`] for details.
+    ///
+    /// The cached content length and ETag will be used if the metadata is
+    /// complete.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::io::SeekFrom;
+    ///
+    /// use anyhow::Result;
+    /// use futures::AsyncReadExt;
+    /// use futures::AsyncSeekExt;
+    /// use opendal::services::memory;
+    /// use opendal::Operator;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<()> {
+    ///     let op = Operator::new(memory::Backend::build().finish().await?);
+    ///
+    ///     let bs = "Hello, World!".as_bytes().to_vec();
+    ///     op.object("test").writer().write_bytes(bs).await?;
+    ///
+    ///     // Read the last 6 bytes like a footer.
+    ///     let mut r = op.object("test").seekable_reader();
+    ///     r.seek(SeekFrom::End(-6)).await?;
+    ///     let mut footer = String::new();
+    ///     r.read_to_string(&mut footer).await?;
+    ///     assert_eq!(footer, "World!");
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn seekable_reader(&self) -> SeekableReader {
+        let mut r = SeekableReader::new(self.acc.clone(), self.meta.path());
+        if self.meta.complete() {
+            r = r.content_length(self.meta.content_length());
+            if let Some(etag) = self.meta.etag() {
+                r = r.if_match(&etag);
+            }
+        }
+        r
+    }
+
     /// Create a new writer which can write data into the object.
     ///
+    /// Data could be written in one shot, or incrementally by
+    /// [`AsyncWrite`][futures::AsyncWrite] which is committed by
+    /// [`Writer::close`].
+    ///
     /// # Example
     ///
     /// ```
     /// use opendal::services::memory;
     /// use anyhow::Result;
-    /// use futures::io;
+    /// use futures::AsyncWriteExt;
     /// use opendal::Operator;
     ///
     /// #[tokio::main]
@@ -206,6 +441,12 @@ impl Object {
     ///     let bs = "Hello, World!".as_bytes().to_vec();
     ///     op.object("test").writer().write_bytes(bs).await?;
     ///
+    ///     let mut w = op.object("test").writer();
+    ///     w.write_all(b"Hello, ").await?;
+    ///     w.write_all(b"World!").await?;
+    ///     let meta = w.close().await?;
+    ///     assert_eq!(meta.content_length(), 13);
+    ///
     ///     Ok(())
     /// }
     /// ```
@@ -213,6 +454,42 @@ impl Object {
         Writer::new(self.acc.clone(), self.meta.path())
     }
 
+    /// Create an empty object at current path.
+    ///
+    /// Paths end with `/` create a dir, which exists even without any
+    /// children, otherwise a zero-byte file will be created.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use opendal::services::memory;
+    /// use anyhow::Result;
+    /// use opendal::ObjectMode;
+    /// use opendal::Operator;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<()> {
+    ///     let op = Operator::new(memory::Backend::build().finish().await?);
+    ///
+    ///     op.object("dir/").create().await?;
+    ///     let meta = op.object("dir/").metadata().await?;
+    ///     assert_eq!(meta.mode(), ObjectMode::DIR);
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub async fn create(&self) -> Result<Metadata> {
+        let path = self.meta.path();
+        let mode = if path.ends_with('/') {
+            ObjectMode::DIR
+        } else {
+            ObjectMode::FILE
+        };
+        let op = &OpCreate::new(path, mode);
+
+        self.acc.create(op).await
+    }
+
     /// Delete current object.
     ///
     /// # Example
@@ -239,6 +516,211 @@ impl Object {
         self.acc.delete(op).await
     }
 
+    /// Copy current object to `dst` inside the storage, returns the
+    /// metadata of the copied object.
+    ///
+    /// `dst` will be overwritten if exists. Backends without server side
+    /// copy return `Kind::Unsupported`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use opendal::services::memory;
+    /// use anyhow::Result;
+    /// use opendal::Operator;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<()> {
+    ///     let op = Operator::new(memory::Backend::build().finish().await?);
+    ///
+    ///     op.object("test").writer().write_bytes(b"Hello".to_vec()).await?;
+    ///     op.object("test").copy_to("test.bak").await?;
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub async fn copy_to(&self, dst: &str) -> Result<Metadata> {
+        let op = &OpCopy::new(self.meta.path(), dst);
+
+        self.acc.copy(op).await
+    }
+
+    /// Rename current object to `dst`, returns the metadata of the renamed
+    /// object.
+    ///
+    /// `dst` will be overwritten if exists. Services without native rename
+    /// copy the object then delete current one, it's not atomic: if the
+    /// delete fails, the returned error will say so and both objects exist.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use opendal::services::memory;
+    /// use anyhow::Result;
+    /// use opendal::Operator;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<()> {
+    ///     let op = Operator::new(memory::Backend::build().finish().await?);
+    ///
+    ///     op.object("test").writer().write_bytes(b"Hello".to_vec()).await?;
+    ///     op.object("test").rename_to("test.bak").await?;
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub async fn rename_to(&self, dst: &str) -> Result<Metadata> {
+        let op = &OpRename::new(self.meta.path(), dst);
+
+        self.acc.rename(op).await
+    }
+
+    /// Presign a request of `method` on current object, which is valid in
+    /// `expire`, so that clients like browsers can read or write it without
+    /// credentials.
+    ///
+    /// `GET`, `PUT` and `HEAD` are supported, backends without presign
+    /// support return `Kind::Unsupported`.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    ///
+    /// use anyhow::Result;
+    /// use http::Method;
+    /// use opendal::services::s3;
+    /// use opendal::Operator;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<()> {
+    ///     let mut builder = s3::Backend::build();
+    ///     builder.bucket("test");
+    ///     let op = Operator::new(builder.finish().await?);
+    ///
+    ///     let req = op
+    ///         .object("test")
+    ///         .presign(Method::GET, Duration::from_secs(3600))?;
+    ///     println!("download from {}", req.uri);
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn presign(&self, method: Method, expire: Duration) -> Result<PresignedRequest> {
+        let op = &OpPresign::new(self.meta.path(), method, expire);
+
+        self.acc.presign(op)
+    }
+
+    /// Create a multipart upload of current object, returns the id of the
+    /// upload.
+    ///
+    /// Parts can be written concurrently, even from different machines,
+    /// and current object will be visible only after the upload completed.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use anyhow::Result;
+    /// use futures::io::Cursor;
+    /// use opendal::services::s3;
+    /// use opendal::Operator;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<()> {
+    ///     let mut builder = s3::Backend::build();
+    ///     builder.bucket("test");
+    ///     let op = Operator::new(builder.finish().await?);
+    ///     let o = op.object("test");
+    ///
+    ///     let upload_id = o.create_multipart().await?;
+    ///     let mut parts = Vec::new();
+    ///     for part_number in 1..=2 {
+    ///         let bs = vec![0; 5 * 1024 * 1024];
+    ///         let size = bs.len() as u64;
+    ///         let part = o
+    ///             .write_multipart(&upload_id, part_number, Box::new(Cursor::new(bs)), size)
+    ///             .await?;
+    ///         parts.push(part);
+    ///     }
+    ///     o.complete_multipart(&upload_id, parts).await?;
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub async fn create_multipart(&self) -> Result<String> {
+        let op = &OpCreateMultipart::new(self.meta.path());
+
+        self.acc.create_multipart(op).await
+    }
+
+    /// Write `size` bytes from `r` as the part `part_number` of the upload.
+    ///
+    /// `part_number` must be in `1..=10000`.
+    pub async fn write_multipart(
+        &self,
+        upload_id: &str,
+        part_number: usize,
+        r: BoxedAsyncReader,
+        size: u64,
+    ) -> Result<ObjectPart> {
+        let op = &OpWriteMultipart::new(self.meta.path(), upload_id, part_number, size);
+
+        self.acc.write_multipart(r, op).await
+    }
+
+    /// Complete the upload with all its written parts, whose part numbers
+    /// must be `1..=parts.len()`.
+    pub async fn complete_multipart(
+        &self,
+        upload_id: &str,
+        parts: Vec<ObjectPart>,
+    ) -> Result<Metadata> {
+        let op = &OpCompleteMultipart::new(self.meta.path(), upload_id, parts);
+
+        self.acc.complete_multipart(op).await
+    }
+
+    /// Abort the upload and delete its written parts.
+    pub async fn abort_multipart(&self, upload_id: &str) -> Result<()> {
+        let op = &OpAbortMultipart::new(self.meta.path(), upload_id);
+
+        self.acc.abort_multipart(op).await
+    }
+
+    /// Append `size` bytes from `r` to the end of the object, whose length
+    /// must be `position` before appending.
+    ///
+    /// Returns `Kind::ObjectPreconditionFailed` if the object's length is
+    /// not `position`, and `Kind::Unsupported` if the backend doesn't
+    /// support append, read [`AccessorMetadata::append`][crate::AccessorMetadata::append].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use anyhow::Result;
+    /// use futures::io::Cursor;
+    /// use opendal::services::fs;
+    /// use opendal::Operator;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<()> {
+    ///     let op = Operator::new(fs::Backend::build().root("/tmp").finish().await?);
+    ///     let o = op.object("test_append");
+    ///     o.writer().write_bytes(b"Hello".to_vec()).await?;
+    ///
+    ///     let meta = o.append(5, Box::new(Cursor::new(b", World!".to_vec())), 8).await?;
+    ///     assert_eq!(meta.content_length(), 13);
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub async fn append(&self, position: u64, r: BoxedAsyncReader, size: u64) -> Result<Metadata> {
+        let op = &OpAppend::new(self.meta.path(), position, size);
+
+        self.acc.append(r, op).await
+    }
+
     /// Get current object's metadata.
     ///
     /// # Example
@@ -269,6 +751,28 @@ impl Object {
         self.acc.stat(op).await
     }
 
+    /// Get current object's metadata with all headers of the response in
+    /// [`Metadata::raw_headers`].
+    ///
+    /// Backends without http responses return metadata without raw headers.
+    pub async fn metadata_with_raw_headers(&self) -> Result<Metadata> {
+        let mut op = OpStat::new(self.meta.path());
+        op.raw_headers = true;
+
+        self.acc.stat(&op).await
+    }
+
+    /// Get the metadata of the given version of current object, like the
+    /// `versionId` of s3.
+    ///
+    /// Fail with `Kind::ObjectNotExist` if the version doesn't exist.
+    pub async fn version_metadata(&self, version: &str) -> Result<Metadata> {
+        let mut op = OpStat::new(self.meta.path());
+        op.version = Some(version.to_string());
+
+        self.acc.stat(&op).await
+    }
+
     /// Use local cached metadata if possible.
     ///
     /// # Example
@@ -303,12 +807,33 @@ impl Object {
         Ok(&self.meta)
     }
 
+    /// Get the metadata cached in current object without sending any request.
+    ///
+    /// Objects yielded by list carry the fields returned along with the list,
+    /// like content length, ETag and last modified time on s3. The metadata
+    /// is partial unless [`Metadata::complete`] returns true, use
+    /// `metadata_cached` to fetch the missing fields.
+    pub fn metadata_ref(&self) -> &Metadata {
+        &self.meta
+    }
+
     pub(crate) fn metadata_mut(&mut self) -> &mut Metadata {
         &mut self.meta
     }
 
+    pub(crate) fn set_accessor(&mut self, acc: Arc<dyn Accessor>) -> &mut Self {
+        self.acc = acc;
+        self
+    }
+
     /// Check if this object exist or not.
     ///
+    /// Returns `Ok(false)` only if the stat fails with `Kind::ObjectNotExist`,
+    /// other errors like `Kind::ObjectPermissionDenied` are returned as is.
+    ///
+    /// Dirs are checked like `stat`, on s3 a dir without marker exists only
+    /// if there is any object under it.
+    ///
     /// # Example
     ///
     /// ```
@@ -348,10 +873,25 @@ pub struct Metadata {
 
     content_length: Option<u64>,
     content_md5: Option<String>,
+    content_type: Option<String>,
+    content_encoding: Option<String>,
+    storage_class: Option<String>,
+    server_side_encryption: Option<String>,
+    server_side_encryption_aws_kms_key_id: Option<String>,
+    etag: Option<String>,
+    version_id: Option<String>,
+    is_latest: Option<bool>,
+    is_delete_marker: bool,
     last_modified: Option<SystemTime>,
+    expires: Option<OffsetDateTime>,
+    user_metadata: HashMap<String, String>,
+    raw_headers: Option<HeaderMap>,
 }
 
 impl Metadata {
+    /// Whether all fields of this metadata have been fetched, like by `stat`.
+    ///
+    /// Metadata of objects yielded by list could be partial.
     pub fn complete(&self) -> bool {
         self.complete
     }
@@ -395,6 +935,12 @@ impl Metadata {
         self
     }
 
+    /// Content length of this object, `None` if it's unknown, like reads
+    /// decoded from their `Content-Encoding`.
+    pub fn try_content_length(&self) -> Option<u64> {
+        self.content_length
+    }
+
     /// Content MD5 of this object.
     pub fn content_md5(&self) -> Option<String> {
         self.content_md5.clone()
@@ -405,11 +951,165 @@ impl Metadata {
         self
     }
 
+    /// Content type of this object.
+    pub fn content_type(&self) -> Option<&str> {
+        self.content_type.as_deref()
+    }
+
+    pub(crate) fn set_content_type(&mut self, content_type: &str) -> &mut Self {
+        self.content_type = Some(content_type.to_string());
+        self
+    }
+
+    /// Content encoding of this object, like `gzip`.
+    pub fn content_encoding(&self) -> Option<String> {
+        self.content_encoding.clone()
+    }
+
+    pub(crate) fn set_content_encoding(&mut self, content_encoding: &str) -> &mut Self {
+        self.content_encoding = Some(content_encoding.to_string());
+        self
+    }
+
+    /// Storage class of this object, like `STANDARD` or `GLACIER`.
+    ///
+    /// Only available on s3, other backends leave it `None`.
+    pub fn storage_class(&self) -> Option<String> {
+        self.storage_class.clone()
+    }
+
+    pub(crate) fn set_storage_class(&mut self, storage_class: &str) -> &mut Self {
+        self.storage_class = Some(storage_class.to_string());
+        self
+    }
+
+    /// Server side encryption of this object, like `AES256` or `aws:kms`.
+    ///
+    /// Only available on s3, other backends leave it `None`.
+    pub fn server_side_encryption(&self) -> Option<String> {
+        self.server_side_encryption.clone()
+    }
+
+    pub(crate) fn set_server_side_encryption(&mut self, v: &str) -> &mut Self {
+        self.server_side_encryption = Some(v.to_string());
+        self
+    }
+
+    /// Id of the aws kms key used to encrypt this object, only available
+    /// on objects encrypted by SSE-KMS.
+    ///
+    /// The key id is not a secret, so it's returned as is.
+    pub fn server_side_encryption_aws_kms_key_id(&self) -> Option<String> {
+        self.server_side_encryption_aws_kms_key_id.clone()
+    }
+
+    pub(crate) fn set_server_side_encryption_aws_kms_key_id(&mut self, v: &str) -> &mut Self {
+        self.server_side_encryption_aws_kms_key_id = Some(v.to_string());
+        self
+    }
+
+    /// ETag of this object without the surrounding quotes, like
+    /// `d41d8cd98f00b204e9800998ecf8427e`.
+    ///
+    /// # Note
+    ///
+    /// ETag is not always the MD5 of the content, for example, objects
+    /// uploaded by multipart upload on s3.
+    pub fn etag(&self) -> Option<String> {
+        self.etag.clone()
+    }
+
+    /// Set the ETag, surrounding quotes of the ETag header will be stripped.
+    pub(crate) fn set_etag(&mut self, etag: &str) -> &mut Self {
+        self.etag = Some(unquote_etag(etag).to_string());
+        self
+    }
+
+    /// Version id of this object, only available on versioning enabled buckets.
+    pub fn version_id(&self) -> Option<String> {
+        self.version_id.clone()
+    }
+
+    pub(crate) fn set_version_id(&mut self, version_id: &str) -> &mut Self {
+        self.version_id = Some(version_id.to_string());
+        self
+    }
+
+    /// Whether this is the latest version of the object, only available
+    /// while listing versions.
+    pub fn is_latest(&self) -> Option<bool> {
+        self.is_latest
+    }
+
+    pub(crate) fn set_is_latest(&mut self, is_latest: bool) -> &mut Self {
+        self.is_latest = Some(is_latest);
+        self
+    }
+
+    /// Whether this version is a delete marker, only available while
+    /// listing versions.
+    ///
+    /// Delete markers have no content, reading them will fail.
+    pub fn is_delete_marker(&self) -> bool {
+        self.is_delete_marker
+    }
+
+    pub(crate) fn set_delete_marker(&mut self) -> &mut Self {
+        self.is_delete_marker = true;
+        self
+    }
+
     /// Last modified of this object.
     pub fn last_modified(&self) -> Option<SystemTime> {
         self.last_modified
     }
 
+    /// Expires of this object, after which caches should consider it stale.
+    pub fn expires(&self) -> Option<OffsetDateTime> {
+        self.expires
+    }
+
+    pub(crate) fn set_expires(&mut self, expires: OffsetDateTime) -> &mut Self {
+        self.expires = Some(expires);
+        self
+    }
+
+    /// User-defined metadata of this object, keys are lowercased.
+    pub fn user_metadata(&self) -> &HashMap<String, String> {
+        &self.user_metadata
+    }
+
+    pub(crate) fn set_user_metadata(
+        &mut self,
+        user_metadata: HashMap<String, String>,
+    ) -> &mut Self {
+        self.user_metadata = user_metadata;
+        self
+    }
+
+    /// All headers of the response, only available while requested by
+    /// `OpStat::raw_headers` or `OpRead::raw_headers` on http services.
+    ///
+    /// Use it for headers without first-class fields, like
+    /// `x-amz-replication-status` of s3.
+    pub fn raw_headers(&self) -> Option<&HeaderMap> {
+        self.raw_headers.as_ref()
+    }
+
+    pub(crate) fn set_raw_headers(&mut self, raw_headers: HeaderMap) -> &mut Self {
+        self.raw_headers = Some(raw_headers);
+        self
+    }
+
+    /// Last modified of this object in milliseconds since unix epoch.
+    pub fn last_modified_ms(&self) -> Option<i64> {
+        self.last_modified
+            .map(|v| match v.duration_since(UNIX_EPOCH) {
+                Ok(d) => d.as_millis() as i64,
+                Err(e) => -(e.duration().as_millis() as i64),
+            })
+    }
+
     pub(crate) fn set_last_modified(&mut self, last_modified: SystemTime) -> &mut Self {
         self.last_modified = Some(last_modified);
         self
@@ -417,22 +1117,17 @@ impl Metadata {
 }
 
 /// ObjectMode represents the corresponding object's mode.
-#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
 pub enum ObjectMode {
     /// FILE means the object has data to read.
     FILE,
     /// DIR means the object can be listed.
     DIR,
     /// Unknown means we don't know what we can do on thi object.
+    #[default]
     Unknown,
 }
 
-impl Default for ObjectMode {
-    fn default() -> Self {
-        Self::Unknown
-    }
-}
-
 impl Display for ObjectMode {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -449,7 +1144,7 @@ pub type BoxedObjectStream = Box<dyn futures::Stream<Item = Result<Object>> + Un
 /// Handler for listing object under a dir.
 pub struct ObjectStream {
     acc: Arc<dyn Accessor>,
-    path: String,
+    args: OpList,
     state: State,
 }
 
@@ -464,10 +1159,116 @@ impl ObjectStream {
     pub fn new(acc: Arc<dyn Accessor>, path: &str) -> Self {
         Self {
             acc,
-            path: path.to_string(),
+            args: OpList::new(path),
             state: State::Idle,
         }
     }
+
+    /// Stop listing after `max_results` entries have been yielded.
+    ///
+    /// The stream ends cleanly once reached, and the backend's `list_scan_limit`
+    /// will not be applied. No further pages are requested from the backend
+    /// after the last entry is yielded, so `max_results(1)` is a cheap way to
+    /// check whether a dir contains anything.
+    #[must_use]
+    pub fn max_results(mut self, max_results: u64) -> Self {
+        self.args.max_results = Some(max_results);
+        self
+    }
+
+    /// Yield entries in lexicographical order of their paths.
+    ///
+    /// Backends without natural ordering have to buffer the whole dir in
+    /// memory, and listing will fail with `Kind::Unsupported` if the dir
+    /// contains more entries than the backend's `list_sort_limit`.
+    #[must_use]
+    pub fn ordered(mut self) -> Self {
+        self.args.ordered = true;
+        self
+    }
+
+    /// Only yield entries whose paths are lexicographically after `path`.
+    ///
+    /// Backends like s3 skip the entries on the server side, others will
+    /// filter them out while listing.
+    #[must_use]
+    pub fn start_after(mut self, path: &str) -> Self {
+        self.args.start_after = Some(path.to_string());
+        self
+    }
+
+    /// List all objects under the dir recursively instead of only its
+    /// direct children.
+    ///
+    /// Backends without support will return `Kind::Unsupported`, read
+    /// [`AccessorMetadata::recursive_list`][crate::AccessorMetadata::recursive_list]
+    /// for details.
+    #[must_use]
+    pub fn recursive(mut self) -> Self {
+        self.args.recursive = true;
+        self
+    }
+
+    /// List all versions and delete markers of objects under the dir.
+    ///
+    /// Versions of the same object are yielded from the newest to the
+    /// oldest. Their contents could be read by `Reader::version`.
+    ///
+    /// Backends without versioning support will return `Kind::Unsupported`.
+    #[must_use]
+    pub fn versions(mut self) -> Self {
+        self.args.versions = true;
+        self
+    }
+
+    /// Only yield files whose paths match the glob `pattern`, dirs are
+    /// always yielded.
+    ///
+    /// - `?` matches any single char except `/`.
+    /// - `*` matches any sequence of chars except `/`.
+    /// - `**` matches any sequence of chars including `/`.
+    ///
+    /// The pattern is matched against the full path, for example
+    /// `data/*.parquet` or `data/**/*.parquet` with `recursive`.
+    #[must_use]
+    pub fn pattern(mut self, pattern: &str) -> Self {
+        self.args.pattern = Some(pattern.to_string());
+        self
+    }
+
+    /// Set the max entries of one page fetched from the backend.
+    ///
+    /// Use small pages for low latency and large pages for bulk scans.
+    /// Backends will clamp it to the max page size they support, and
+    /// backends without pagination will ignore it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `size` is 0.
+    #[must_use]
+    pub fn page_size(mut self, size: usize) -> Self {
+        assert!(size > 0, "page size must be at least 1");
+        self.args.page_size = Some(size);
+        self
+    }
+
+    /// Set the metadata required for every entry, read [`ListMetadata`]
+    /// for details.
+    ///
+    /// With `ListMetadata::Complete`, backends like s3 will stat entries
+    /// while listing, which costs one request per entry.
+    #[must_use]
+    pub fn metadata(mut self, metadata: ListMetadata) -> Self {
+        self.args.metadata = metadata;
+        self
+    }
+
+    /// Set the priority of this list.
+    #[must_use]
+    pub fn priority(mut self, priority: OpPriority) -> Self {
+        self.args.priority = priority;
+        self
+    }
 }
 
 impl futures::Stream for ObjectStream {
@@ -477,7 +1278,7 @@ impl futures::Stream for ObjectStream {
         match &mut self.state {
             State::Idle => {
                 let acc = self.acc.clone();
-                let op = OpList::new(&self.path);
+                let op = self.args.clone();
 
                 let future = async move { acc.list(&op).await };
 
@@ -489,9 +1290,227 @@ impl futures::Stream for ObjectStream {
                     self.state = State::Listing(obs);
                     self.poll_next(cx)
                 }
-                Err(e) => Poll::Ready(Some(Err(e))),
+                Err(e) => {
+                    // The listing can't go on, make sure we will not poll
+                    // the finished future again.
+                    self.state = State::Listing(Box::new(futures::stream::empty()));
+                    Poll::Ready(Some(Err(e)))
+                }
             },
             State::Listing(obs) => Pin::new(obs).poll_next(cx),
         }
     }
 }
+
+/// Default max size of objects read into memory by `Object::read`.
+pub(crate) const DEFAULT_READ_MAX_SIZE: u64 = 64 * 1024 * 1024;
+
+/// Length of the range in an object of `length` bytes.
+fn range_length(range: BytesRange, length: u64) -> u64 {
+    match (range.offset(), range.size()) {
+        (None, None) => length,
+        (None, Some(size)) => size.min(length),
+        (Some(offset), size) => {
+            let rest = length.saturating_sub(offset);
+            size.map_or(rest, |size| size.min(rest))
+        }
+    }
+}
+
+/// Default max entries that could be sorted in memory while listing with `OpList::ordered`.
+pub(crate) const DEFAULT_LIST_SORT_LIMIT: u64 = 100_000;
+
+/// Max entries stat-ed at the same time while listing with `ListMetadata::Complete`.
+pub(crate) const LIST_STAT_CONCURRENCY: usize = 16;
+
+/// Stat entries of the backend's object stream whose metadata is not
+/// complete, for `ListMetadata::Complete`.
+///
+/// Entries are still yielded in order. Dirs and delete markers are yielded
+/// as is, and files deleted before being stat-ed are skipped.
+pub(crate) fn complete_object_stream(
+    inner: BoxedObjectStream,
+    concurrency: usize,
+    priority: OpPriority,
+) -> BoxedObjectStream {
+    let s = inner
+        .map(move |o| async move {
+            let mut o = o?;
+            // Delete markers have nothing to stat.
+            if o.meta.complete() || o.meta.mode == Some(ObjectMode::DIR) || o.meta.is_delete_marker
+            {
+                return Ok(Some(o));
+            }
+
+            let mut op = OpStat::new(o.meta.path());
+            op.version = o.meta.version_id();
+            op.priority = priority;
+            match o.acc.stat(&op).await {
+                Ok(mut meta) => {
+                    // Only known while listing versions.
+                    if let Some(is_latest) = o.meta.is_latest {
+                        meta.set_is_latest(is_latest);
+                    }
+                    o.meta = meta;
+                    Ok(Some(o))
+                }
+                Err(err) if err.kind() == Kind::ObjectNotExist => Ok(None),
+                Err(err) => Err(err),
+            }
+        })
+        .buffered(concurrency.max(1))
+        .try_filter_map(|o| futures::future::ready(Ok(o)));
+
+    Box::new(s)
+}
+
+/// Collect all entries from the backend's object stream and sort them by path.
+///
+/// Returns `Kind::Unsupported` error if there are more than `limit` entries.
+pub(crate) async fn sort_object_stream(
+    mut inner: BoxedObjectStream,
+    path: &str,
+    limit: u64,
+) -> Result<BoxedObjectStream> {
+    let mut entries = Vec::new();
+    while let Some(o) = inner.next().await {
+        if entries.len() as u64 >= limit {
+            return Err(Error::Object {
+                kind: Kind::Unsupported,
+                op: "list",
+                path: path.to_string(),
+                context: HashMap::new(),
+                source: anyhow!(
+                    "ordered list supports at most {} entries in one dir, \
+                     increase the backend's list_sort_limit or list without ordered",
+                    limit
+                ),
+            });
+        }
+        entries.push(o?);
+    }
+    entries.sort_by(|a, b| a.meta.path.cmp(&b.meta.path));
+
+    Ok(Box::new(futures::stream::iter(entries.into_iter().map(Ok))))
+}
+
+/// Skip files whose paths don't match the glob `pattern`.
+pub(crate) fn filter_by_pattern(
+    inner: BoxedObjectStream,
+    pattern: Option<&str>,
+) -> BoxedObjectStream {
+    match pattern {
+        None => inner,
+        Some(pattern) => {
+            let glob = Glob::new(pattern);
+            Box::new(inner.filter(move |o| {
+                let keep = match o {
+                    Ok(o) => o.meta.mode() == ObjectMode::DIR || glob.matches(o.meta.path()),
+                    Err(_) => true,
+                };
+                futures::future::ready(keep)
+            }))
+        }
+    }
+}
+
+/// Strip the surrounding quotes of an ETag, weak ETags like `W/"abc"` are
+/// returned as is.
+pub(crate) fn unquote_etag(etag: &str) -> &str {
+    etag.strip_prefix('"')
+        .and_then(|v| v.strip_suffix('"'))
+        .unwrap_or(etag)
+}
+
+/// Skip entries whose paths are not lexicographically after `start_after`.
+pub(crate) fn skip_until_after(
+    inner: BoxedObjectStream,
+    start_after: Option<String>,
+) -> BoxedObjectStream {
+    match start_after {
+        None => inner,
+        Some(start_after) => Box::new(inner.filter(move |o| {
+            let keep = match o {
+                Ok(o) => o.meta.path.as_str() > start_after.as_str(),
+                Err(_) => true,
+            };
+            futures::future::ready(keep)
+        })),
+    }
+}
+
+/// LimitedObjectStream applies list guardrails on the backend's object stream.
+///
+/// - If `max_results` is set, the stream ends cleanly after yielding that many entries,
+///   without polling the inner stream (and fetching its next page) again.
+/// - Otherwise, if `scan_limit` is set, the stream yields at most `scan_limit` entries,
+///   and ends with a `Kind::ScanLimitExceeded` error if there are more.
+pub(crate) struct LimitedObjectStream {
+    inner: BoxedObjectStream,
+    path: String,
+    max_results: Option<u64>,
+    scan_limit: Option<u64>,
+
+    yielded: u64,
+    done: bool,
+}
+
+impl LimitedObjectStream {
+    pub fn new(
+        inner: BoxedObjectStream,
+        path: &str,
+        max_results: Option<u64>,
+        scan_limit: Option<u64>,
+    ) -> Self {
+        Self {
+            inner,
+            path: path.to_string(),
+            max_results,
+            scan_limit,
+
+            yielded: 0,
+            done: false,
+        }
+    }
+}
+
+impl futures::Stream for LimitedObjectStream {
+    type Item = Result<Object>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if self.done {
+            return Poll::Ready(None);
+        }
+
+        if let Some(max_results) = self.max_results {
+            if self.yielded >= max_results {
+                self.done = true;
+                return Poll::Ready(None);
+            }
+        } else if let Some(scan_limit) = self.scan_limit {
+            if self.yielded >= scan_limit {
+                // Peek the next entry so that a listing of exactly `scan_limit`
+                // entries is not reported as truncated.
+                let next = ready!(Pin::new(&mut self.inner).poll_next(cx));
+                self.done = true;
+                return match next {
+                    None => Poll::Ready(None),
+                    Some(Err(e)) => Poll::Ready(Some(Err(e))),
+                    Some(Ok(_)) => Poll::Ready(Some(Err(Error::Object {
+                        kind: Kind::ScanLimitExceeded,
+                        op: "list",
+                        path: self.path.clone(),
+                        context: HashMap::new(),
+                        source: anyhow!("list scan limit {} exceeded", scan_limit),
+                    }))),
+                };
+            }
+        }
+
+        let next = ready!(Pin::new(&mut self.inner).poll_next(cx));
+        if let Some(Ok(_)) = next {
+            self.yielded += 1;
+        }
+        Poll::Ready(next)
+    }
+}
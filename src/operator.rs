@@ -12,12 +12,56 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::collections::HashMap;
+use std::collections::VecDeque;
+use std::io::SeekFrom;
+use std::ops::Bound;
+use std::ops::RangeBounds;
 use std::sync::Arc;
+use std::time::Duration;
 
+use anyhow::anyhow;
+use bytes::Bytes;
+use futures::AsyncRead;
+use futures::AsyncReadExt;
+use futures::AsyncSeek;
+use futures::AsyncSeekExt;
+use futures::StreamExt;
+use log::debug;
+
+use crate::analyze::Analyzer;
+use crate::error::Error;
+use crate::error::Kind;
+use crate::error::Result;
+use crate::layers::manifest::encode_manifest;
+use crate::layers::manifest::ManifestEntry;
+use crate::layers::ImmutableLayer;
+use crate::layers::SubdirLayer;
+use crate::layers::TimeoutLayer;
+use crate::ops::BatchOperation;
+use crate::ops::BatchResult;
+use crate::ops::BytesRange;
+use crate::ops::OpBatch;
+use crate::ops::OpRead;
+use crate::ops::OpScan;
+use crate::read_many::read_many;
+use crate::services::azblob;
+use crate::services::fs;
+use crate::services::memory;
+use crate::services::s3;
+use crate::services::static_files;
 use crate::Accessor;
+use crate::AccessorMetadata;
+use crate::AnalyzeOptions;
+use crate::BoxedObjectStream;
 use crate::Layer;
 use crate::Object;
+use crate::ObjectMode;
 use crate::ObjectStream;
+use crate::PrefixAnalysis;
+use crate::ReadManyOptions;
+use crate::ReadManyStream;
+use crate::Scheme;
 
 /// User-facing APIs for object and object streams.
 #[derive(Clone)]
@@ -67,6 +111,137 @@ impl Operator {
         Self { accessor }
     }
 
+    /// Create a new operator from a uri like `s3://bucket/path/to/root?region=us-east-2`.
+    ///
+    /// The scheme selects the backend, read `from_uri` of the backend's
+    /// builder for the supported parts, like [`s3::Builder::from_uri`].
+    /// Unsupported query parameters will be rejected with
+    /// `Kind::BackendConfigurationInvalid`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use anyhow::Result;
+    /// use opendal::Operator;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<()> {
+    ///     let op = Operator::from_uri("fs:///tmp/opendal").await?;
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub async fn from_uri(uri: &str) -> Result<Operator> {
+        let scheme = match uri.split_once("://") {
+            Some((scheme, _)) => scheme.parse::<Scheme>()?,
+            None => {
+                return Err(Error::Backend {
+                    kind: Kind::BackendConfigurationInvalid,
+                    context: HashMap::new(),
+                    source: anyhow!("uri must start with a scheme like `s3://`"),
+                })
+            }
+        };
+
+        let accessor = match scheme {
+            Scheme::Azblob => azblob::Builder::from_uri(uri)?.finish().await?,
+            Scheme::Fs => fs::Builder::from_uri(uri)?.finish().await?,
+            Scheme::Memory => memory::Builder::from_uri(uri)?.finish().await?,
+            Scheme::S3 => s3::Builder::from_uri(uri)?.finish().await?,
+            Scheme::StaticFiles => {
+                return Err(Error::Backend {
+                    kind: Kind::BackendNotSupported,
+                    context: HashMap::from([("scheme".to_string(), "static_files".to_string())]),
+                    source: anyhow!("static_files can't be built from uri, use from_map instead"),
+                })
+            }
+        };
+
+        Ok(Operator::new(accessor))
+    }
+
+    /// Create a new operator from options in key-value form, like the ones
+    /// loaded from a config file.
+    ///
+    /// Read `from_map` of the backend's builder for the supported options,
+    /// like [`s3::Builder::from_map`].
+    pub async fn from_map(scheme: Scheme, map: HashMap<String, String>) -> Result<Operator> {
+        let accessor = match scheme {
+            Scheme::Azblob => azblob::Builder::from_map(map)?.finish().await?,
+            Scheme::Fs => fs::Builder::from_map(map)?.finish().await?,
+            Scheme::Memory => memory::Builder::from_map(map)?.finish().await?,
+            Scheme::S3 => s3::Builder::from_map(map)?.finish().await?,
+            Scheme::StaticFiles => static_files::Builder::from_map(map)?.finish().await?,
+        };
+
+        Ok(Operator::new(accessor))
+    }
+
+    /// Create a new operator from environment variables.
+    ///
+    /// Variables named `OPENDAL_{SCHEME}_{OPTION}` are the options of
+    /// [`Operator::from_map`] in lower case, for example:
+    ///
+    /// | Variable                       | Option of s3            |
+    /// |--------------------------------|-------------------------|
+    /// | `OPENDAL_S3_BUCKET`            | `bucket`, required      |
+    /// | `OPENDAL_S3_ROOT`              | `root`                  |
+    /// | `OPENDAL_S3_ENDPOINT`          | `endpoint`              |
+    /// | `OPENDAL_S3_REGION`            | `region`                |
+    /// | `OPENDAL_S3_ACCESS_KEY_ID`     | `access_key_id`         |
+    /// | `OPENDAL_S3_SECRET_ACCESS_KEY` | `secret_access_key`     |
+    /// | `OPENDAL_S3_ANONYMOUS`         | `anonymous`             |
+    /// | `OPENDAL_S3_REQUESTER_PAYS`    | `requester_pays`        |
+    ///
+    /// Variables of the scheme that are not supported will be rejected, and
+    /// errors of invalid options carry the variable name in context `env`.
+    ///
+    /// `static_files` is not supported since its options are objects.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use anyhow::Result;
+    /// use opendal::Operator;
+    /// use opendal::Scheme;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<()> {
+    ///     // With `OPENDAL_S3_BUCKET=test` and `OPENDAL_S3_REGION=us-east-2`.
+    ///     let op = Operator::from_env(Scheme::S3).await?;
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub async fn from_env(scheme: Scheme) -> Result<Operator> {
+        let prefix = match scheme {
+            Scheme::Azblob => "OPENDAL_AZBLOB_",
+            Scheme::Fs => "OPENDAL_FS_",
+            Scheme::Memory => "OPENDAL_MEMORY_",
+            Scheme::S3 => "OPENDAL_S3_",
+            Scheme::StaticFiles => {
+                return Err(Error::Backend {
+                    kind: Kind::BackendNotSupported,
+                    context: HashMap::from([("scheme".to_string(), "static_files".to_string())]),
+                    source: anyhow!("static_files can't be built from env, use from_map instead"),
+                })
+            }
+        };
+
+        let map: HashMap<String, String> = std::env::vars_os()
+            .filter_map(|(k, v)| Some((k.into_string().ok()?, v.into_string().ok()?)))
+            .filter_map(|(k, v)| Some((k.strip_prefix(prefix)?.to_lowercase(), v)))
+            .collect();
+        debug!("operator from env got options: {:?}", map.keys());
+
+        Operator::from_map(scheme, map).await.map_err(|err| {
+            match err.context().get("key").map(|key| key.to_uppercase()) {
+                Some(key) => err.with_context("env", format!("{}{}", prefix, key)),
+                None => err,
+            }
+        })
+    }
+
     /// Create a new layer.
     #[must_use]
     pub fn layer(self, layer: impl Layer) -> Self {
@@ -75,7 +250,71 @@ impl Operator {
         }
     }
 
-    fn inner(&self) -> Arc<dyn Accessor> {
+    /// Derive a new operator with overrides, which shares the same underlying
+    /// backend (and its connections) with current operator.
+    ///
+    /// Read [`OperatorOptions`] for overrides that can be applied without rebuilding.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::time::Duration;
+    ///
+    /// use anyhow::Result;
+    /// use opendal::services::memory;
+    /// use opendal::Operator;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<()> {
+    ///     let op = Operator::new(memory::Backend::build().finish().await?);
+    ///
+    ///     let derived = op.clone_with(|opts| {
+    ///         opts.root("/other/")
+    ///             .read_only()
+    ///             .timeout(Duration::from_secs(10))
+    ///     })?;
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn clone_with<F>(&self, f: F) -> Result<Operator>
+    where
+        F: FnOnce(&mut OperatorOptions) -> &mut OperatorOptions,
+    {
+        let mut opts = OperatorOptions::default();
+        f(&mut opts);
+
+        if !opts.rebuild.is_empty() {
+            return Err(Error::Backend {
+                kind: Kind::Unsupported,
+                context: opts.rebuild.clone(),
+                source: anyhow!(
+                    "{} can't be overridden on an existing operator, please rebuild from backend's builder",
+                    opts.rebuild.keys().cloned().collect::<Vec<_>>().join(", ")
+                ),
+            });
+        }
+
+        let mut acc = self.inner();
+        if let Some(root) = &opts.root {
+            acc = SubdirLayer::new(root).layer(acc);
+        }
+        if let Some(timeout) = opts.timeout {
+            acc = TimeoutLayer::new(timeout).layer(acc);
+        }
+        if opts.read_only {
+            acc = ImmutableLayer.layer(acc);
+        }
+
+        Ok(Operator::new(acc))
+    }
+
+    /// Get metadata of the underlying accessor.
+    pub fn metadata(&self) -> AccessorMetadata {
+        self.accessor.metadata()
+    }
+
+    pub(crate) fn inner(&self) -> Arc<dyn Accessor> {
         self.accessor.clone()
     }
 
@@ -98,10 +337,10 @@ impl Operator {
     ///
     ///     // Write data info file;
     ///     let w = o.writer();
-    ///     let n = w
+    ///     let meta = w
     ///         .write_bytes("Hello, World!".to_string().into_bytes())
     ///         .await?;
-    ///     assert_eq!(n, 13);
+    ///     assert_eq!(meta.content_length(), 13);
     ///
     ///     // Read data from file;
     ///     let mut r = o.reader();
@@ -131,6 +370,12 @@ impl Operator {
         Object::new(self.inner(), path)
     }
 
+    /// Check if the object of the path exists, read [`Object::is_exist`]
+    /// for details.
+    pub async fn exists(&self, path: &str) -> Result<bool> {
+        self.object(path).is_exist().await
+    }
+
     /// Create a new object stream handle to list objects.
     ///
     /// # Example
@@ -174,4 +419,733 @@ impl Operator {
     pub fn objects(&self, path: &str) -> ObjectStream {
         ObjectStream::new(self.inner(), path)
     }
+
+    /// Scan all objects under the dir regardless of depth.
+    ///
+    /// Only files are yielded, with their paths relative to the root of the
+    /// operator. Read [`Accessor::scan`] for details.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use anyhow::Result;
+    /// use futures::TryStreamExt;
+    /// use opendal::services::memory;
+    /// use opendal::Operator;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<()> {
+    ///     let op = Operator::new(memory::Backend::build().finish().await?);
+    ///     op.object("dir/a").writer().write_bytes(vec![1; 4]).await?;
+    ///     op.object("dir/sub/b").writer().write_bytes(vec![2; 8]).await?;
+    ///
+    ///     let mut paths: Vec<String> = op
+    ///         .scan("dir/")
+    ///         .await?
+    ///         .map_ok(|o| o.metadata_ref().path().to_string())
+    ///         .try_collect()
+    ///         .await?;
+    ///     paths.sort();
+    ///     assert_eq!(paths, vec!["dir/a", "dir/sub/b"]);
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub async fn scan(&self, path: &str) -> Result<BoxedObjectStream> {
+        self.accessor.scan(&OpScan::new(path)).await
+    }
+
+    /// Read the `range` of an object into a single `Bytes`.
+    ///
+    /// If [`AccessorMetadata::mmap_read`] is true (fs with `mmap` feature),
+    /// the returned `Bytes` is backed by a memory map of the file without
+    /// copying. Other backends fall back to a buffered read, so callers
+    /// don't need to check the capability.
+    ///
+    /// # Note
+    ///
+    /// The length of a mapped file is captured while opening. Truncating
+    /// the file while the returned `Bytes` is alive makes accessing the
+    /// removed pages raise `SIGBUS`, and in place writes will be visible in
+    /// the `Bytes`. Only map files that are replaced instead of modified.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use anyhow::Result;
+    /// use opendal::services::memory;
+    /// use opendal::Operator;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<()> {
+    ///     let op = Operator::new(memory::Backend::build().finish().await?);
+    ///     op.object("test")
+    ///         .writer()
+    ///         .write_bytes("Hello, World!".as_bytes().to_vec())
+    ///         .await?;
+    ///
+    ///     let bs = op.read_mmap("test", 7..).await?;
+    ///     assert_eq!(bs.as_ref(), b"World!");
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub async fn read_mmap(&self, path: &str, range: impl RangeBounds<u64>) -> Result<Bytes> {
+        let start = match range.start_bound() {
+            Bound::Included(v) => *v,
+            Bound::Excluded(v) => v + 1,
+            Bound::Unbounded => 0,
+        };
+        let size = match range.end_bound() {
+            Bound::Included(v) => Some((v + 1).saturating_sub(start)),
+            Bound::Excluded(v) => Some(v.saturating_sub(start)),
+            Bound::Unbounded => None,
+        };
+
+        self.accessor
+            .read_mmap(&OpRead {
+                path: path.to_string(),
+                // Ranges always start from an offset, never the suffix.
+                range: BytesRange::new(Some(start), size),
+                ..Default::default()
+            })
+            .await
+    }
+
+    /// Read many small objects into memory concurrently.
+    ///
+    /// Results are yielded as they complete instead of the input order, so
+    /// that a slow object won't block the others. Failures are yielded
+    /// with their paths, without stopping the rest. Duplicated paths are
+    /// read only once and yielded for every occurrence.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::collections::HashMap;
+    ///
+    /// use anyhow::Result;
+    /// use futures::StreamExt;
+    /// use opendal::services::memory;
+    /// use opendal::Operator;
+    /// use opendal::ReadManyOptions;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<()> {
+    ///     let op = Operator::new(memory::Backend::build().finish().await?);
+    ///     op.object("a").writer().write_bytes(vec![1; 4]).await?;
+    ///     op.object("b").writer().write_bytes(vec![2; 8]).await?;
+    ///
+    ///     let paths = vec!["a".to_string(), "b".to_string(), "c".to_string()];
+    ///     let results: HashMap<_, _> = op
+    ///         .read_many(paths, ReadManyOptions::default().concurrency(8))
+    ///         .collect()
+    ///         .await;
+    ///     assert_eq!(results["b"].as_ref().unwrap().len(), 8);
+    ///     assert!(results["c"].is_err());
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn read_many(&self, paths: Vec<String>, opts: ReadManyOptions) -> ReadManyStream {
+        read_many(self.accessor.clone(), paths, opts)
+    }
+
+    /// Run a batch of deletes and stats, and return the result of every
+    /// operation in the same order along with its path.
+    ///
+    /// Services like s3 delete up to 1000 objects per request, others run
+    /// operations concurrently. A failed operation doesn't fail the others.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use anyhow::Result;
+    /// use opendal::ops::BatchOperation;
+    /// use opendal::ops::BatchResult;
+    /// use opendal::ops::OpDelete;
+    /// use opendal::ops::OpStat;
+    /// use opendal::services::memory;
+    /// use opendal::Operator;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<()> {
+    ///     let op = Operator::new(memory::Backend::build().finish().await?);
+    ///     op.object("a").writer().write_bytes(vec![1; 4]).await?;
+    ///     op.object("b").writer().write_bytes(vec![2; 8]).await?;
+    ///
+    ///     // Operations run concurrently, don't depend on each other.
+    ///     let results = op
+    ///         .batch(vec![
+    ///             BatchOperation::Stat(OpStat::new("a")),
+    ///             BatchOperation::Delete(OpDelete::new("b")),
+    ///             BatchOperation::Stat(OpStat::new("c")),
+    ///         ])
+    ///         .await?;
+    ///     assert!(matches!(&results[0].1, BatchResult::Stat(Ok(meta)) if meta.content_length() == 4));
+    ///     assert!(results[1].1.is_ok());
+    ///     assert_eq!(results[2].0, "c");
+    ///     assert!(!results[2].1.is_ok());
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub async fn batch(&self, ops: Vec<BatchOperation>) -> Result<Vec<(String, BatchResult)>> {
+        self.accessor.batch(&OpBatch::new(ops)).await
+    }
+
+    /// Write the object only if the destination doesn't contain exactly
+    /// the same content.
+    ///
+    /// The destination will be stat-ed first, the upload will be skipped if
+    /// both the size and the digest match. If `digest` is not provided, the
+    /// content will be read once to calculate the digest, and read again
+    /// from the start if the upload is required.
+    ///
+    /// The check is conservative: we will always upload if the destination's
+    /// digest can't be decided, for example, ETag of objects uploaded by
+    /// multipart upload is not the MD5 of the content.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use anyhow::Result;
+    /// use futures::io::Cursor;
+    /// use opendal::services::memory;
+    /// use opendal::Operator;
+    /// use opendal::WriteOutcome;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<()> {
+    ///     let op = Operator::new(memory::Backend::build().finish().await?);
+    ///
+    ///     let outcome = op
+    ///         .write_if_changed("test", Cursor::new(vec![0; 4]), 4, None)
+    ///         .await?;
+    ///     assert_eq!(outcome, WriteOutcome::Written { size: 4 });
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub async fn write_if_changed<R>(
+        &self,
+        path: &str,
+        mut r: R,
+        size: u64,
+        digest: Option<Digest>,
+    ) -> Result<WriteOutcome>
+    where
+        R: AsyncRead + AsyncSeek + Unpin + Send + 'static,
+    {
+        let o = self.object(path);
+
+        let existing = match o.metadata().await {
+            Ok(meta) => Some(meta),
+            Err(e) if e.kind() == Kind::ObjectNotExist => None,
+            Err(e) => return Err(e),
+        };
+        let existing_digest = existing
+            .filter(|meta| meta.mode() == ObjectMode::FILE && meta.content_length() == size)
+            .and_then(|meta| meta.etag())
+            .and_then(|etag| Digest::from_etag(&etag));
+
+        if let Some(existing_digest) = existing_digest {
+            let digest = match digest {
+                Some(digest) => digest,
+                None => hash_and_rewind(&mut r, size)
+                    .await
+                    .map_err(|e| Error::Object {
+                        kind: Kind::Unexpected,
+                        op: "write",
+                        path: path.to_string(),
+                        context: HashMap::new(),
+                        source: anyhow::Error::from(e),
+                    })?,
+            };
+            if digest == existing_digest {
+                debug!("object {} has not been changed, skip write", path);
+                return Ok(WriteOutcome::Skipped { bytes_saved: size });
+            }
+        }
+
+        let meta = o.writer().write_reader(Box::new(r), size).await?;
+        Ok(WriteOutcome::Written {
+            size: meta.content_length(),
+        })
+    }
+
+    /// List the last `n` entries under `prefix` in lexicographical order.
+    ///
+    /// Listing the whole prefix is expensive when keys keep growing, like
+    /// logs whose keys start with timestamps. `key_bound_hint` helps us to
+    /// find a smaller window: the `k`-th call should return a candidate
+    /// lower bound that is earlier than the previous ones, for example,
+    /// `now - 2^k minutes` formatted in the key layout. Return `None` if
+    /// there are no more candidates.
+    ///
+    /// The window is probed via `start_after`:
+    ///
+    /// - Walk backwards with the hint until a window contains at least `n` keys.
+    /// - If a window contains too many keys, bisect the key space between it
+    ///   and the last window that contains too few.
+    /// - Fall back to scan from the best known bound once the hint runs out or
+    ///   the probe budget is used up.
+    ///
+    /// A poor hint never affects the result, only the number of requests.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use anyhow::Result;
+    /// use opendal::services::memory;
+    /// use opendal::Operator;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<()> {
+    ///     let op = Operator::new(memory::Backend::build().finish().await?);
+    ///     for i in 0..10 {
+    ///         op.object(&format!("logs/{:02}", i)).writer().write_bytes(vec![0]).await?;
+    ///     }
+    ///
+    ///     // Go back 2 keys more on every attempt.
+    ///     let hint = |k: u32| {
+    ///         9u32.checked_sub(2 * (k + 1))
+    ///             .map(|v| format!("logs/{:02}", v))
+    ///     };
+    ///     let obs = op.list_tail("logs/", 3, hint).await?;
+    ///     let mut paths = vec![];
+    ///     for mut o in obs {
+    ///         paths.push(o.metadata_cached().await?.path().to_string());
+    ///     }
+    ///     assert_eq!(paths, vec!["logs/07", "logs/08", "logs/09"]);
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub async fn list_tail<F>(
+        &self,
+        prefix: &str,
+        n: usize,
+        mut key_bound_hint: F,
+    ) -> Result<Vec<Object>>
+    where
+        F: FnMut(u32) -> Option<String>,
+    {
+        if n == 0 {
+            return Ok(vec![]);
+        }
+
+        // A window with more than `cap` keys is too expensive to scan.
+        let cap = (n as u64).saturating_mul(LIST_TAIL_WINDOW_FACTOR);
+        let mut budget = LIST_TAIL_PROBE_BUDGET;
+        // The latest bound known to have more than `cap` keys after it.
+        let mut lo: Option<String> = None;
+        // The earliest bound known to have less than `n` keys after it.
+        let mut hi: Option<String> = None;
+
+        let mut attempt = 0;
+        while budget > 0 {
+            let bound = match key_bound_hint(attempt) {
+                Some(bound) => bound,
+                None => break,
+            };
+            attempt += 1;
+            // The hint doesn't move backwards, stop trusting it.
+            if matches!(&hi, Some(hi) if &bound >= hi) {
+                debug!("list_tail hint {} doesn't move backwards, ignore", bound);
+                break;
+            }
+
+            budget -= 1;
+            let (tail, count) = self.list_tail_window(prefix, n, Some(&bound), cap).await?;
+            if count > cap {
+                lo = Some(bound);
+                break;
+            } else if count >= n as u64 {
+                return Ok(tail.into());
+            } else {
+                hi = Some(bound);
+            }
+        }
+
+        if let Some(mut l) = lo.take() {
+            while budget > 0 {
+                let upper = hi.clone().unwrap_or_else(|| key_upper_bound(prefix, &l));
+                let mid = match key_midpoint(&l, &upper) {
+                    Some(mid) => mid,
+                    None => break,
+                };
+
+                budget -= 1;
+                let (tail, count) = self.list_tail_window(prefix, n, Some(&mid), cap).await?;
+                if count > cap {
+                    l = mid;
+                } else if count >= n as u64 {
+                    return Ok(tail.into());
+                } else {
+                    hi = Some(mid);
+                }
+            }
+            lo = Some(l);
+        }
+
+        debug!("list_tail on {} falls back to scan after {:?}", prefix, lo);
+        let (tail, _) = self
+            .list_tail_window(prefix, n, lo.as_deref(), u64::MAX)
+            .await?;
+        Ok(tail.into())
+    }
+
+    /// List the window after `start_after`, returns the last `n` entries and
+    /// the count of scanned entries.
+    ///
+    /// The listing stops once more than `cap` entries have been scanned.
+    async fn list_tail_window(
+        &self,
+        prefix: &str,
+        n: usize,
+        start_after: Option<&str>,
+        cap: u64,
+    ) -> Result<(VecDeque<Object>, u64)> {
+        let mut obs = self.objects(prefix).ordered();
+        if let Some(start_after) = start_after {
+            obs = obs.start_after(start_after);
+        }
+        if cap < u64::MAX {
+            obs = obs.max_results(cap + 1);
+        }
+
+        let mut tail = VecDeque::with_capacity(n);
+        let mut count = 0;
+        while let Some(o) = obs.next().await {
+            let o = o?;
+            if tail.len() == n {
+                tail.pop_front();
+            }
+            tail.push_back(o);
+            count += 1;
+        }
+        Ok((tail, count))
+    }
+
+    /// Analyze all keys under the prefix in one pass, aggregating count and
+    /// bytes per extension, count per depth and the largest keys.
+    ///
+    /// Dirs are listed recursively for backends that don't list all keys
+    /// under the prefix at once. The result can be serialized via serde.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use anyhow::Result;
+    /// use opendal::services::memory;
+    /// use opendal::AnalyzeOptions;
+    /// use opendal::Operator;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<()> {
+    ///     let op = Operator::new(memory::Backend::build().finish().await?);
+    ///     op.object("data/a.parquet").writer().write_bytes(vec![0; 8]).await?;
+    ///     op.object("data/b.json").writer().write_bytes(vec![0; 2]).await?;
+    ///
+    ///     let analysis = op
+    ///         .analyze_prefix("data/", AnalyzeOptions::default().scan_limit(1000))
+    ///         .await?;
+    ///     assert_eq!(analysis.total.count, 2);
+    ///     assert_eq!(analysis.extensions["parquet"].bytes, 8);
+    ///     assert_eq!(analysis.largest[0].path, "data/a.parquet");
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub async fn analyze_prefix(&self, path: &str, opts: AnalyzeOptions) -> Result<PrefixAnalysis> {
+        let base = path.trim_start_matches('/');
+        let depth_of = |p: &str| {
+            let p = p.trim_start_matches('/');
+            let rel = p.strip_prefix(base).unwrap_or(p).trim_matches('/');
+            rel.split('/').count()
+        };
+
+        let mut analyzer = Analyzer::default();
+        let mut scanned = 0;
+        let mut dirs = vec![path.to_string()];
+        while let Some(dir) = dirs.pop() {
+            let mut obs = self.objects(&dir);
+            while let Some(o) = obs.next().await {
+                let mut o = o?;
+
+                scanned += 1;
+                if let Some(limit) = opts.scan_limit {
+                    if scanned > limit {
+                        return Err(Error::Object {
+                            kind: Kind::ScanLimitExceeded,
+                            op: "list",
+                            path: path.to_string(),
+                            context: HashMap::new(),
+                            source: anyhow!("analyze scan limit {} exceeded", limit),
+                        });
+                    }
+                }
+
+                let meta = o.metadata_cached().await?;
+                // Some backends return the dir itself in the listing.
+                if meta.path() == dir {
+                    continue;
+                }
+                let depth = depth_of(meta.path());
+                if matches!(opts.max_depth, Some(max) if depth > max) {
+                    continue;
+                }
+                match meta.mode() {
+                    ObjectMode::FILE => analyzer.add(meta.path(), depth, meta.content_length()),
+                    ObjectMode::DIR => {
+                        if !matches!(opts.max_depth, Some(max) if depth >= max) {
+                            dirs.push(meta.path().to_string());
+                        }
+                    }
+                    ObjectMode::Unknown => continue,
+                }
+            }
+        }
+
+        debug!("analyze prefix {} finished after {} entries", path, scanned);
+        Ok(analyzer.finish())
+    }
+
+    /// Snapshot all files under the prefix into a manifest with their ETags
+    /// and sizes, returns the count of files.
+    ///
+    /// The manifest object could belong to this operator or another one,
+    /// and can be opened by [`ManifestLayer`][crate::layers::manifest::ManifestLayer]
+    /// as a read-only view that errors on changed objects. Dirs are listed
+    /// recursively like [`Operator::analyze_prefix`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use anyhow::Result;
+    /// use opendal::services::memory;
+    /// use opendal::Operator;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<()> {
+    ///     let op = Operator::new(memory::Backend::build().finish().await?);
+    ///     op.object("data/a").writer().write_bytes(vec![0; 8]).await?;
+    ///     op.object("data/b").writer().write_bytes(vec![0; 2]).await?;
+    ///
+    ///     let count = op
+    ///         .snapshot_prefix("data/", &op.object("manifests/v1"))
+    ///         .await?;
+    ///     assert_eq!(count, 2);
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub async fn snapshot_prefix(&self, prefix: &str, manifest: &Object) -> Result<u64> {
+        let mut entries = vec![];
+        let mut dirs = vec![prefix.to_string()];
+        while let Some(dir) = dirs.pop() {
+            let mut obs = self.objects(&dir);
+            while let Some(o) = obs.next().await {
+                let mut o = o?;
+                let meta = o.metadata_cached().await?;
+                // Some backends return the dir itself in the listing.
+                if meta.path() == dir {
+                    continue;
+                }
+                match meta.mode() {
+                    ObjectMode::FILE => entries.push(ManifestEntry {
+                        path: meta.path().to_string(),
+                        etag: meta.etag(),
+                        size: meta.content_length(),
+                    }),
+                    ObjectMode::DIR => dirs.push(meta.path().to_string()),
+                    ObjectMode::Unknown => continue,
+                }
+            }
+        }
+        entries.sort_by(|a, b| a.path.cmp(&b.path));
+
+        debug!("snapshot prefix {} with {} entries", prefix, entries.len());
+        manifest
+            .writer()
+            .write_bytes(encode_manifest(&entries))
+            .await?;
+        Ok(entries.len() as u64)
+    }
+}
+
+/// Max probes that [`Operator::list_tail`] sends before falling back to scan.
+pub(crate) const LIST_TAIL_PROBE_BUDGET: u32 = 8;
+
+/// [`Operator::list_tail`] treats a window with more than `n * LIST_TAIL_WINDOW_FACTOR`
+/// keys as too large and narrows it down.
+const LIST_TAIL_WINDOW_FACTOR: u64 = 4;
+
+/// Returns a key that is greater than all keys under `prefix`.
+///
+/// Keys are assumed to be ascii if both `prefix` and `lo` are, so that
+/// bisection works on the ascii space instead of the whole unicode.
+fn key_upper_bound(prefix: &str, lo: &str) -> String {
+    if prefix.is_ascii() && lo.is_ascii() {
+        format!("{}{}", prefix, '\u{7f}')
+    } else {
+        format!("{}{}", prefix, char::MAX)
+    }
+}
+
+/// Returns a key between `lo` and `hi` in lexicographical order, or `None`
+/// if there is no room between them.
+///
+/// Keys are treated as numbers whose digits are chars, and the result is
+/// their average.
+pub(crate) fn key_midpoint(lo: &str, hi: &str) -> Option<String> {
+    let base: u32 = if lo.is_ascii() && hi.is_ascii() {
+        0x80
+    } else {
+        u32::from(char::MAX) + 1
+    };
+
+    let lo_digits: Vec<u32> = lo.chars().map(u32::from).collect();
+    let hi_digits: Vec<u32> = hi.chars().map(u32::from).collect();
+    // One more digit so that adjacent keys like `a` and `b` still have room.
+    let len = lo_digits.len().max(hi_digits.len()) + 1;
+    let digit = |ds: &[u32], i: usize| ds.get(i).copied().unwrap_or(0);
+
+    let mut sum = vec![0; len];
+    let mut carry = 0;
+    for i in (0..len).rev() {
+        let v = digit(&lo_digits, i) + digit(&hi_digits, i) + carry;
+        sum[i] = v % base;
+        carry = v / base;
+    }
+
+    let mut mid = String::with_capacity(len);
+    let mut rem = carry;
+    for v in sum {
+        let v = rem * base + v;
+        rem = v % 2;
+        // Surrogates are not valid chars, use the next valid one instead.
+        mid.push(char::from_u32(v / 2).unwrap_or('\u{e000}'));
+    }
+    let mid = mid.trim_end_matches('\0').to_string();
+
+    if lo < mid.as_str() && mid.as_str() < hi {
+        Some(mid)
+    } else {
+        None
+    }
+}
+
+/// Digest of the object content.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Digest {
+    /// MD5 digest of the content.
+    Md5([u8; 16]),
+}
+
+impl Digest {
+    /// Extract the MD5 digest from an ETag.
+    ///
+    /// Returns `None` if the ETag is not a plain MD5, for example, weak
+    /// etags or etags of multipart uploads like `"<hex>-2"`.
+    fn from_etag(etag: &str) -> Option<Digest> {
+        let etag = etag.trim_matches('"');
+        if etag.len() != 32 || !etag.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return None;
+        }
+
+        let mut bs = [0; 16];
+        for (i, b) in bs.iter_mut().enumerate() {
+            *b = u8::from_str_radix(&etag[i * 2..i * 2 + 2], 16).ok()?;
+        }
+        Some(Digest::Md5(bs))
+    }
+}
+
+/// Outcome of [`Operator::write_if_changed`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WriteOutcome {
+    /// The destination already has the same content, nothing was written.
+    Skipped {
+        /// Bytes that have not been uploaded.
+        bytes_saved: u64,
+    },
+    /// The content has been written.
+    Written {
+        /// Bytes that have been written.
+        size: u64,
+    },
+}
+
+/// Calculate the MD5 digest of the next `size` bytes of the reader, and
+/// seek back to where it was.
+async fn hash_and_rewind<R>(r: &mut R, size: u64) -> std::io::Result<Digest>
+where
+    R: AsyncRead + AsyncSeek + Unpin,
+{
+    let start = r.stream_position().await?;
+
+    let mut ctx = md5::Context::new();
+    let mut buf = vec![0; 64 * 1024];
+    let mut limited = (&mut *r).take(size);
+    loop {
+        let n = limited.read(&mut buf).await?;
+        if n == 0 {
+            break;
+        }
+        ctx.consume(&buf[..n]);
+    }
+    r.seek(SeekFrom::Start(start)).await?;
+
+    Ok(Digest::Md5(ctx.compute().0))
+}
+
+/// Overrides used by [`Operator::clone_with`].
+///
+/// - `root`, `read_only` and `timeout` are applied by wrapping the existing
+///   backend with layers.
+/// - `bucket` and `endpoint` require a new backend, `clone_with` will return
+///   a `Kind::Unsupported` error if they are set.
+#[derive(Debug, Clone, Default)]
+pub struct OperatorOptions {
+    root: Option<String>,
+    read_only: bool,
+    timeout: Option<Duration>,
+
+    rebuild: HashMap<String, String>,
+}
+
+impl OperatorOptions {
+    /// Use the dir as root, relative to current operator's root.
+    pub fn root(&mut self, root: &str) -> &mut Self {
+        self.root = Some(root.to_string());
+        self
+    }
+
+    /// Deny all write operations.
+    pub fn read_only(&mut self) -> &mut Self {
+        self.read_only = true;
+        self
+    }
+
+    /// Fail operations that don't finish in time.
+    pub fn timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Changing bucket requires rebuilding the backend.
+    pub fn bucket(&mut self, bucket: &str) -> &mut Self {
+        self.rebuild
+            .insert("bucket".to_string(), bucket.to_string());
+        self
+    }
+
+    /// Changing endpoint requires rebuilding the backend.
+    pub fn endpoint(&mut self, endpoint: &str) -> &mut Self {
+        self.rebuild
+            .insert("endpoint".to_string(), endpoint.to_string());
+        self
+    }
 }
@@ -14,22 +14,130 @@
 
 //! Operations used by [`Accessor`][crate::Accessor]
 
+use std::collections::HashMap;
+use std::fmt;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::ops::Range;
+use std::ops::RangeFrom;
+use std::ops::RangeFull;
+use std::ops::RangeTo;
+use std::time::Duration;
+
+use http::HeaderMap;
+use http::Method;
+use http::Uri;
+use time::OffsetDateTime;
+
+use crate::error::Result;
+use crate::Metadata;
+use crate::ObjectMode;
+
+/// Priority of an operation, layers like [`QosLayer`][crate::layers::qos::QosLayer]
+/// admit operations with higher priority first.
+///
+/// Backends ignore it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum OpPriority {
+    /// Background jobs like compaction and backup.
+    Low,
+    /// Default priority of all operations.
+    #[default]
+    Normal,
+    /// Interactive operations that are sensitive to latency.
+    High,
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct OpRead {
     pub path: String,
-    pub offset: Option<u64>,
-    pub size: Option<u64>,
+    /// Range of the object to read.
+    pub range: BytesRange,
+    /// Read the given version of the object instead of the latest one.
+    ///
+    /// Backends without versioning support will return `Kind::Unsupported`.
+    pub version: Option<String>,
+    /// Only read the object if its ETag matches, or fail with
+    /// `Kind::ObjectPreconditionFailed`.
+    ///
+    /// Backends without ETag support will ignore it.
+    pub if_match: Option<String>,
+    /// Only read the object if its ETag doesn't match, or fail with
+    /// `Kind::ObjectNotModified`.
+    ///
+    /// Backends without ETag support will ignore it.
+    pub if_none_match: Option<String>,
+    /// Only read the object if it has been modified after the time, or fail
+    /// with `Kind::ObjectNotModified`.
+    ///
+    /// Services compare in seconds, sub-second precision will be truncated.
+    /// Backends without last modified support will ignore it.
+    pub if_modified_since: Option<OffsetDateTime>,
+    /// Only read the object if it has not been modified after the time, or
+    /// fail with `Kind::ObjectPreconditionFailed`.
+    ///
+    /// Services compare in seconds, sub-second precision will be truncated.
+    /// Backends without last modified support will ignore it.
+    pub if_unmodified_since: Option<OffsetDateTime>,
+    /// Overrides of the response headers, like `response-content-type` on s3.
+    ///
+    /// Backends without support will ignore them.
+    pub response_overrides: ResponseOverrides,
+    /// Priority of this read.
+    pub priority: OpPriority,
+    /// Keep all headers of the response in [`Metadata::raw_headers`][crate::Metadata::raw_headers]
+    /// returned by `read_with_metadata`.
+    ///
+    /// Backends without http responses will ignore it.
+    pub raw_headers: bool,
+}
+
+/// Overrides of the headers returned by read, which is useful while
+/// serving the content as a download with the expected name and type.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ResponseOverrides {
+    /// Override `Content-Type`.
+    pub content_type: Option<String>,
+    /// Override `Content-Language`.
+    pub content_language: Option<String>,
+    /// Override `Expires`.
+    pub expires: Option<String>,
+    /// Override `Cache-Control`.
+    pub cache_control: Option<String>,
+    /// Override `Content-Disposition`.
+    pub content_disposition: Option<String>,
+    /// Override `Content-Encoding`.
+    pub content_encoding: Option<String>,
 }
 
 #[derive(Debug, Clone, Default)]
 pub struct OpStat {
     pub path: String,
+    /// Stat the given version of the object instead of the latest one.
+    ///
+    /// Backends without versioning support will return `Kind::Unsupported`.
+    pub version: Option<String>,
+    /// Only stat the object if its ETag matches, or fail with
+    /// `Kind::ObjectPreconditionFailed`.
+    ///
+    /// Backends without ETag support will ignore it.
+    pub if_match: Option<String>,
+    /// Priority of this stat.
+    pub priority: OpPriority,
+    /// Keep all headers of the response in [`Metadata::raw_headers`][crate::Metadata::raw_headers].
+    ///
+    /// Backends without http responses will ignore it.
+    pub raw_headers: bool,
 }
 
 impl OpStat {
     pub fn new(path: &str) -> Self {
         Self {
             path: path.to_string(),
+            version: None,
+            if_match: None,
+            priority: OpPriority::Normal,
+            raw_headers: false,
         }
     }
 }
@@ -37,55 +145,551 @@ impl OpStat {
 #[derive(Debug, Clone, Default)]
 pub struct OpWrite {
     pub path: String,
-    pub size: u64,
+    /// Size of the content, `None` means the size is unknown and the
+    /// content will be read until EOF.
+    pub size: Option<u64>,
+    /// `Content-Type` of the object.
+    ///
+    /// Services will decide the content type if not set, for example,
+    /// s3 will use `binary/octet-stream`.
+    pub content_type: Option<String>,
+    /// Storage class of the object, like `STANDARD_IA` on s3.
+    ///
+    /// Backends without storage class support will ignore it.
+    pub storage_class: Option<String>,
+    /// `Cache-Control` of the object.
+    pub cache_control: Option<String>,
+    /// `Content-Disposition` of the object.
+    pub content_disposition: Option<String>,
+    /// `Content-Encoding` of the object.
+    pub content_encoding: Option<String>,
+    /// `Expires` of the object, sub-second precision will be truncated.
+    pub expires: Option<OffsetDateTime>,
+    /// Tags attached to the object.
+    pub tags: HashMap<String, String>,
+    /// User-defined metadata of the object, like `x-amz-meta-{key}` on s3.
+    pub user_metadata: HashMap<String, String>,
+    /// Only write if the existing object's ETag matches, or fail with
+    /// `Kind::ObjectPreconditionFailed`.
+    ///
+    /// Backends without [`AccessorMetadata::conditional_write`][crate::AccessorMetadata::conditional_write]
+    /// will return `Kind::Unsupported`.
+    pub if_match: Option<String>,
+    /// Only write if the existing object's ETag doesn't match, or fail with
+    /// `Kind::ObjectPreconditionFailed`. `*` means the object must not exist.
+    ///
+    /// Backends without [`AccessorMetadata::conditional_write`][crate::AccessorMetadata::conditional_write]
+    /// will return `Kind::Unsupported`.
+    pub if_none_match: Option<String>,
+    /// Priority of this write.
+    pub priority: OpPriority,
+}
+
+impl OpWrite {
+    pub fn new(path: &str, size: u64) -> Self {
+        Self {
+            path: path.to_string(),
+            size: Some(size),
+            ..Default::default()
+        }
+    }
+
+    /// Whether the write has any precondition.
+    pub fn is_conditional(&self) -> bool {
+        self.if_match.is_some() || self.if_none_match.is_some()
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct OpCreate {
+    pub path: String,
+    /// `FILE` creates a zero-byte object, `DIR` creates a dir, which is a
+    /// zero-byte marker ends with `/` on s3 alike services.
+    pub mode: ObjectMode,
+    /// Priority of this create.
+    pub priority: OpPriority,
+}
+
+impl OpCreate {
+    pub fn new(path: &str, mode: ObjectMode) -> Self {
+        Self {
+            path: path.to_string(),
+            mode,
+            priority: OpPriority::Normal,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Default)]
 pub struct OpDelete {
     pub path: String,
+    /// Only delete if the object's ETag matches, or fail with
+    /// `Kind::ObjectPreconditionFailed`.
+    ///
+    /// Backends without [`AccessorMetadata::conditional_write`][crate::AccessorMetadata::conditional_write]
+    /// will return `Kind::Unsupported`.
+    pub if_match: Option<String>,
+    /// Priority of this delete.
+    pub priority: OpPriority,
 }
 
 impl OpDelete {
     pub fn new(path: &str) -> Self {
         Self {
             path: path.to_string(),
+            if_match: None,
+            priority: OpPriority::Normal,
+        }
+    }
+}
+
+/// Default count of sub-operations that [`OpBatch`] runs at the same time
+/// on backends without native batching.
+pub const DEFAULT_BATCH_CONCURRENCY: usize = 16;
+
+/// Sub-operation of [`OpBatch`].
+#[derive(Debug, Clone)]
+pub enum BatchOperation {
+    Delete(OpDelete),
+    Stat(OpStat),
+}
+
+impl BatchOperation {
+    /// Path of the object this operation works on.
+    pub fn path(&self) -> &str {
+        match self {
+            BatchOperation::Delete(v) => &v.path,
+            BatchOperation::Stat(v) => &v.path,
+        }
+    }
+}
+
+/// Result of a [`BatchOperation`], which fails on its own without failing
+/// other sub-operations.
+#[derive(Debug)]
+pub enum BatchResult {
+    Delete(Result<()>),
+    Stat(Result<Box<Metadata>>),
+}
+
+impl BatchResult {
+    /// Whether the sub-operation succeeded.
+    pub fn is_ok(&self) -> bool {
+        match self {
+            BatchResult::Delete(v) => v.is_ok(),
+            BatchResult::Stat(v) => v.is_ok(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct OpBatch {
+    pub ops: Vec<BatchOperation>,
+    /// Run at most `concurrency` sub-operations at the same time on
+    /// backends without native batching.
+    pub concurrency: usize,
+    /// Priority of this batch.
+    pub priority: OpPriority,
+}
+
+impl OpBatch {
+    pub fn new(ops: Vec<BatchOperation>) -> Self {
+        Self {
+            ops,
+            concurrency: DEFAULT_BATCH_CONCURRENCY,
+            priority: OpPriority::Normal,
         }
     }
 }
 
+#[derive(Debug, Clone, Default)]
+pub struct OpCopy {
+    /// Path of the source object.
+    pub src: String,
+    /// Path of the destination object, will be overwritten if exists.
+    pub dst: String,
+    /// Priority of this copy.
+    pub priority: OpPriority,
+}
+
+impl OpCopy {
+    pub fn new(src: &str, dst: &str) -> Self {
+        Self {
+            src: src.to_string(),
+            dst: dst.to_string(),
+            priority: OpPriority::Normal,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct OpRename {
+    /// Path of the object to rename.
+    pub src: String,
+    /// Path of the new object, will be overwritten if exists.
+    pub dst: String,
+    /// Priority of this rename.
+    pub priority: OpPriority,
+}
+
+impl OpRename {
+    pub fn new(src: &str, dst: &str) -> Self {
+        Self {
+            src: src.to_string(),
+            dst: dst.to_string(),
+            priority: OpPriority::Normal,
+        }
+    }
+}
+
+/// Create a multipart upload, whose parts can be written independently,
+/// even from different machines, then completed as a single object.
+#[derive(Debug, Clone, Default)]
+pub struct OpCreateMultipart {
+    pub path: String,
+    /// Priority of this create.
+    pub priority: OpPriority,
+}
+
+impl OpCreateMultipart {
+    pub fn new(path: &str) -> Self {
+        Self {
+            path: path.to_string(),
+            priority: OpPriority::Normal,
+        }
+    }
+}
+
+/// Args for `append` operation.
+///
+/// Appends are fenced by `position`, so that concurrent appenders won't
+/// interleave their content silently.
+#[derive(Debug, Clone, Default)]
+pub struct OpAppend {
+    pub path: String,
+    /// Length of the object expected before appending, which is the
+    /// position that the content will be written at.
+    ///
+    /// Append fails with `Kind::ObjectPreconditionFailed` if the object's
+    /// length is different. Appending at `0` creates the object if it
+    /// doesn't exist.
+    pub position: u64,
+    /// Size of the content, the reader must have at least `size` bytes.
+    pub size: u64,
+    /// Priority of this append.
+    pub priority: OpPriority,
+}
+
+impl OpAppend {
+    pub fn new(path: &str, position: u64, size: u64) -> Self {
+        Self {
+            path: path.to_string(),
+            position,
+            size,
+            priority: OpPriority::Normal,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct OpWriteMultipart {
+    pub path: String,
+    /// Id of the upload returned by `create_multipart`.
+    pub upload_id: String,
+    /// Number of this part, must be in `1..=10000`. Writing the same part
+    /// number again overwrites the previous part.
+    pub part_number: usize,
+    /// Size of this part, the reader must have at least `size` bytes.
+    pub size: u64,
+    /// Priority of this write.
+    pub priority: OpPriority,
+}
+
+impl OpWriteMultipart {
+    pub fn new(path: &str, upload_id: &str, part_number: usize, size: u64) -> Self {
+        Self {
+            path: path.to_string(),
+            upload_id: upload_id.to_string(),
+            part_number,
+            size,
+            priority: OpPriority::Normal,
+        }
+    }
+}
+
+/// A written part of a multipart upload.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ObjectPart {
+    pub part_number: usize,
+    pub etag: String,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct OpCompleteMultipart {
+    pub path: String,
+    /// Id of the upload returned by `create_multipart`.
+    pub upload_id: String,
+    /// All parts of the object, their part numbers must be `1..=parts.len()`
+    /// in any order.
+    pub parts: Vec<ObjectPart>,
+    /// Priority of this complete.
+    pub priority: OpPriority,
+}
+
+impl OpCompleteMultipart {
+    pub fn new(path: &str, upload_id: &str, parts: Vec<ObjectPart>) -> Self {
+        Self {
+            path: path.to_string(),
+            upload_id: upload_id.to_string(),
+            parts,
+            priority: OpPriority::Normal,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct OpAbortMultipart {
+    pub path: String,
+    /// Id of the upload returned by `create_multipart`.
+    pub upload_id: String,
+    /// Priority of this abort.
+    pub priority: OpPriority,
+}
+
+impl OpAbortMultipart {
+    pub fn new(path: &str, upload_id: &str) -> Self {
+        Self {
+            path: path.to_string(),
+            upload_id: upload_id.to_string(),
+            priority: OpPriority::Normal,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct OpPresign {
+    pub path: String,
+    /// Method of the presigned request, `GET`, `PUT` and `HEAD` are
+    /// supported.
+    pub method: Method,
+    /// The presigned request is valid in `expire` since it's generated.
+    pub expire: Duration,
+    /// Overrides of the headers in the response of `GET`.
+    pub response_overrides: ResponseOverrides,
+}
+
+impl OpPresign {
+    pub fn new(path: &str, method: Method, expire: Duration) -> Self {
+        Self {
+            path: path.to_string(),
+            method,
+            expire,
+            ..Default::default()
+        }
+    }
+}
+
+/// A request signed in advance, which can be sent by clients without
+/// credentials before it expires.
+#[derive(Debug, Clone)]
+pub struct PresignedRequest {
+    pub method: Method,
+    pub uri: Uri,
+    /// Headers that must be sent along with the request, like SSE-C
+    /// headers, since they are signed too.
+    pub headers: HeaderMap,
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct OpList {
     pub path: String,
+    /// Stop listing after yielding `max_results` entries.
+    ///
+    /// The stream ends cleanly once reached, and the backend's
+    /// `list_scan_limit` will not be applied. It's independent of
+    /// `page_size`: no more pages will be requested once reached.
+    pub max_results: Option<u64>,
+    /// Yield entries in lexicographical order of their paths.
+    ///
+    /// Backends without natural ordering will sort entries before yielding
+    /// them, read [`AccessorMetadata::ordered_list`][crate::AccessorMetadata::ordered_list]
+    /// for details.
+    pub ordered: bool,
+    /// Only yield entries whose paths are lexicographically after `start_after`.
+    ///
+    /// Entries are skipped before `max_results` is applied.
+    pub start_after: Option<String>,
+    /// List all objects under the dir instead of only its direct children.
+    ///
+    /// Objects are yielded as `FILE` entries, while dir markers (keys
+    /// endswith `/`) are yielded as `DIR` entries.
+    pub recursive: bool,
+    /// Max entries of one page fetched from the backend, the backend's
+    /// default page size will be used if not set.
+    ///
+    /// It doesn't limit the total entries, see `max_results` for that.
+    pub page_size: Option<usize>,
+    /// List all versions and delete markers of objects instead of the
+    /// latest ones, like `ListObjectVersions` of s3.
+    ///
+    /// Every version is yielded as an entry with its version id, read
+    /// [`Metadata::version_id`][crate::Metadata::version_id] for details.
+    ///
+    /// Backends without versioning support will return `Kind::Unsupported`.
+    pub versions: bool,
+    /// Only yield `FILE` entries whose paths match the glob `pattern`.
+    ///
+    /// The pattern is matched against the full path of entries like
+    /// `data/*.parquet` or `data/**/*.parquet`, read [`ObjectStream::pattern`][crate::ObjectStream::pattern]
+    /// for the syntax. `DIR` entries always pass through so that they can be
+    /// listed further.
+    ///
+    /// Entries are filtered before `max_results` is applied.
+    pub pattern: Option<String>,
+    /// Metadata required for every entry, read [`ListMetadata`] for details.
+    pub metadata: ListMetadata,
+    /// Priority of this list, covers requests for all pages.
+    pub priority: OpPriority,
 }
 
 impl OpList {
     pub fn new(path: &str) -> Self {
         Self {
             path: path.to_string(),
+            max_results: None,
+            ordered: false,
+            start_after: None,
+            recursive: false,
+            page_size: None,
+            versions: false,
+            pattern: None,
+            metadata: ListMetadata::Listed,
+            priority: OpPriority::Normal,
         }
     }
 }
 
-#[derive(Debug, Clone, Copy)]
-pub struct HeaderRange(Option<u64>, Option<u64>);
+/// Metadata required for entries yielded by `list`.
+///
+/// Directory browsing only needs the mode, while sync tools need the
+/// complete metadata of every entry. Entries without complete metadata
+/// can still fetch it lazily by [`Object::metadata_cached`][crate::Object::metadata_cached].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ListMetadata {
+    /// Only the mode is required, backends could skip other fields.
+    Mode,
+    /// Fields returned along with the list without extra requests, like
+    /// content length, ETag and last modified time on s3.
+    #[default]
+    Listed,
+    /// Complete metadata like `stat`, entries will be stat-ed while
+    /// listing unless the list returns complete metadata already.
+    ///
+    /// Dirs are yielded as is, and files deleted before being stat-ed are
+    /// skipped.
+    Complete,
+}
 
-impl HeaderRange {
+/// Args for `scan` operation.
+///
+/// Scan yields every object under the dir regardless of depth, as `FILE`
+/// entries only.
+#[derive(Debug, Clone, Default)]
+pub struct OpScan {
+    pub path: String,
+    /// Priority of this scan, covers requests for all pages.
+    pub priority: OpPriority,
+}
+
+impl OpScan {
+    pub fn new(path: &str) -> Self {
+        Self {
+            path: path.to_string(),
+            priority: OpPriority::Normal,
+        }
+    }
+}
+
+/// Range of bytes to read, which is the whole object by default.
+///
+/// # Example
+///
+/// ```
+/// use opendal::ops::BytesRange;
+///
+/// assert_eq!(BytesRange::from(5..10).to_string(), "bytes=5-9");
+/// assert_eq!(BytesRange::from(5..).to_string(), "bytes=5-");
+/// // Ranges up to `n` are the last `n` bytes.
+/// assert_eq!(BytesRange::from(..10).to_string(), "bytes=-10");
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BytesRange(Option<u64>, Option<u64>);
+
+impl BytesRange {
+    /// Create a range of at most `size` bytes since `offset`, or the last
+    /// `size` bytes of the object if `offset` is `None`.
     pub fn new(offset: Option<u64>, size: Option<u64>) -> Self {
-        HeaderRange(offset, size)
+        BytesRange(offset, size)
+    }
+
+    pub fn offset(&self) -> Option<u64> {
+        self.0
+    }
+
+    pub fn size(&self) -> Option<u64> {
+        self.1
+    }
+
+    /// Whether it's the whole object.
+    pub fn is_full(&self) -> bool {
+        self.0.unwrap_or_default() == 0 && self.1.is_none()
+    }
+
+    /// Whether it's the last `size` bytes of the object.
+    pub fn is_suffix(&self) -> bool {
+        self.0.is_none() && self.1.is_some()
+    }
+}
+
+impl From<Range<u64>> for BytesRange {
+    fn from(v: Range<u64>) -> Self {
+        BytesRange(Some(v.start), Some(v.end.saturating_sub(v.start)))
     }
 }
 
-impl ToString for HeaderRange {
+impl From<RangeFrom<u64>> for BytesRange {
+    fn from(v: RangeFrom<u64>) -> Self {
+        BytesRange(Some(v.start), None)
+    }
+}
+
+/// `..n` is the last `n` bytes of the object, the same as `bytes=-n`.
+impl From<RangeTo<u64>> for BytesRange {
+    fn from(v: RangeTo<u64>) -> Self {
+        BytesRange(None, Some(v.end))
+    }
+}
+
+impl From<RangeFull> for BytesRange {
+    fn from(_: RangeFull) -> Self {
+        BytesRange(None, None)
+    }
+}
+
+impl Display for BytesRange {
     // # NOTE
     //
-    // - `bytes=-1023` means get the suffix of the file, we must set the start to 0.
+    // - `bytes=-1024` means get the last 1024 bytes, or the whole file if it's shorter.
     // - `bytes=0-1023` means get the first 1024 bytes, we must set the end to 1023.
-    fn to_string(&self) -> String {
+    //
+    // Empty ranges can't be represented, callers should skip the request.
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         match (self.0, self.1) {
-            (Some(offset), None) => format!("bytes={}-", offset),
-            (None, Some(size)) => format!("bytes=0-{}", size - 1),
-            (Some(offset), Some(size)) => format!("bytes={}-{}", offset, offset + size - 1),
-            _ => panic!("invalid range"),
+            (offset, None) => write!(f, "bytes={}-", offset.unwrap_or_default()),
+            (None, Some(size)) => write!(f, "bytes=-{}", size),
+            (Some(offset), Some(size)) => {
+                write!(f, "bytes={}-{}", offset, offset + size.max(1) - 1)
+            }
         }
     }
 }
@@ -0,0 +1,59 @@
+// Copyright 2022 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+
+use anyhow::anyhow;
+
+use crate::error::Error;
+use crate::error::Kind;
+use crate::error::Result;
+
+/// Validate the path before backends join it with their root.
+///
+/// Paths are always relative to the root, a leading `/` like `/abc` is
+/// the same as `abc`, so the only ways to escape the root are relative
+/// segments. Returns `Kind::ObjectPathInvalid` if the path:
+///
+/// - contains `.` or `..` segments, like `a/../../b` or `./a`.
+/// - contains NUL bytes, which are rejected or truncated by file systems.
+pub(crate) fn validate_path(op: &'static str, path: &str) -> Result<()> {
+    let reason = if path.contains('\0') {
+        "contains NUL bytes"
+    } else if path.split('/').any(|v| v == "." || v == "..") {
+        "contains relative segments"
+    } else {
+        return Ok(());
+    };
+
+    Err(Error::Object {
+        kind: Kind::ObjectPathInvalid,
+        op,
+        path: path.to_string(),
+        context: HashMap::new(),
+        source: anyhow!("path {}", reason),
+    })
+}
+
+/// Build the error of paths that are not under the backend's root, like
+/// keys returned by the service that we never asked for.
+pub(crate) fn out_of_root(op: &'static str, path: &str, root: &str) -> Error {
+    Error::Object {
+        kind: Kind::ObjectOutOfScope,
+        op,
+        path: path.to_string(),
+        context: HashMap::from([("root".to_string(), root.to_string())]),
+        source: anyhow!("path doesn't start with backend root {}", root),
+    }
+}
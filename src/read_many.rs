@@ -0,0 +1,154 @@
+// Copyright 2022 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use anyhow::anyhow;
+use bytes::Bytes;
+use bytes::BytesMut;
+use futures::Stream;
+use futures::StreamExt;
+use futures::TryStreamExt;
+use log::debug;
+use metrics::increment_counter;
+
+use crate::error::share_error;
+use crate::error::Error;
+use crate::error::Kind;
+use crate::error::Result;
+use crate::Accessor;
+use crate::Object;
+
+/// Default count of objects that [`Operator::read_many`](crate::Operator::read_many)
+/// reads at the same time.
+pub(crate) const DEFAULT_READ_MANY_CONCURRENCY: usize = 16;
+
+/// Options for [`Operator::read_many`](crate::Operator::read_many).
+#[derive(Debug, Clone)]
+pub struct ReadManyOptions {
+    pub(crate) concurrency: usize,
+    pub(crate) max_size: Option<u64>,
+}
+
+impl Default for ReadManyOptions {
+    fn default() -> Self {
+        Self {
+            concurrency: DEFAULT_READ_MANY_CONCURRENCY,
+            max_size: None,
+        }
+    }
+}
+
+impl ReadManyOptions {
+    /// Read at most `concurrency` objects at the same time, default to 16.
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Fail objects larger than `size` with `Kind::ObjectTooLarge`, the
+    /// read stops once the limit is exceeded.
+    pub fn max_size(mut self, size: u64) -> Self {
+        self.max_size = Some(size);
+        self
+    }
+}
+
+/// Stream of paths and their contents returned by
+/// [`Operator::read_many`](crate::Operator::read_many).
+pub type ReadManyStream = Box<dyn Stream<Item = (String, Result<Bytes>)> + Unpin + Send>;
+
+/// Read all `paths` concurrently, yields results as they complete.
+///
+/// Duplicated paths are read only once, and the result is yielded for
+/// every occurrence of them.
+pub(crate) fn read_many(
+    acc: Arc<dyn Accessor>,
+    paths: Vec<String>,
+    opts: ReadManyOptions,
+) -> ReadManyStream {
+    let mut occurrences: HashMap<String, usize> = HashMap::new();
+    let mut unique = Vec::new();
+    for path in paths {
+        let count = occurrences.entry(path.clone()).or_default();
+        if *count == 0 {
+            unique.push(path);
+        } else {
+            increment_counter!("opendal_read_many_coalesced");
+        }
+        *count += 1;
+    }
+    debug!(
+        "read many objects: {} unique paths, concurrency {}",
+        unique.len(),
+        opts.concurrency
+    );
+
+    let max_size = opts.max_size;
+    let s = futures::stream::iter(unique)
+        .map(move |path| {
+            let acc = acc.clone();
+            async move {
+                let res = read_to_bytes(acc, &path, max_size).await;
+                (path, res)
+            }
+        })
+        .buffer_unordered(opts.concurrency)
+        .flat_map(move |(path, res)| {
+            match &res {
+                Ok(_) => increment_counter!("opendal_read_many_hits"),
+                Err(_) => increment_counter!("opendal_read_many_errors"),
+            }
+
+            let count = occurrences.get(&path).copied().unwrap_or(1);
+            let mut outputs: Vec<_> = (1..count)
+                .map(|_| {
+                    let shared = match &res {
+                        Ok(bs) => Ok(bs.clone()),
+                        Err(err) => Err(share_error(err)),
+                    };
+                    (path.clone(), shared)
+                })
+                .collect();
+            outputs.push((path, res));
+            futures::stream::iter(outputs)
+        });
+
+    Box::new(Box::pin(s))
+}
+
+/// Read the whole object into memory, fails if it's larger than `max_size`.
+async fn read_to_bytes(acc: Arc<dyn Accessor>, path: &str, max_size: Option<u64>) -> Result<Bytes> {
+    let mut s = Object::new(acc, path).stream(Some(0), None).await?;
+
+    let mut buf = BytesMut::new();
+    while let Some(bs) = s.try_next().await? {
+        buf.extend_from_slice(&bs);
+        // Ranged reads past the end fail on some backends, so the limit
+        // is checked while reading instead of sent as the read size.
+        if let Some(max) = max_size {
+            if buf.len() as u64 > max {
+                return Err(Error::Object {
+                    kind: Kind::ObjectTooLarge,
+                    op: "read",
+                    path: path.to_string(),
+                    context: HashMap::new(),
+                    source: anyhow!("object is larger than {} bytes", max),
+                });
+            }
+        }
+    }
+    Ok(buf.freeze())
+}
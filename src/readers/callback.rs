@@ -29,10 +29,7 @@ impl<F> CallbackReader<F>
 where
     F: FnMut(usize),
 {
-    /// # TODO
-    ///
-    /// Mark as dead_code for now, we will use it sooner while implement streams support.
-    #[allow(dead_code)]
+    /// Create a new reader which calls `f` with the size of every read.
     pub fn new(r: BoxedAsyncReader, f: F) -> Self {
         CallbackReader { inner: r, f }
     }
@@ -26,6 +26,20 @@ pub enum Scheme {
     Fs,
     Memory,
     S3,
+    StaticFiles,
+}
+
+impl Scheme {
+    /// Name of the scheme, used as labels of metrics and traces.
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            Scheme::Azblob => "azblob",
+            Scheme::Fs => "fs",
+            Scheme::Memory => "memory",
+            Scheme::S3 => "s3",
+            Scheme::StaticFiles => "static_files",
+        }
+    }
 }
 
 impl FromStr for Scheme {
@@ -38,6 +52,7 @@ impl FromStr for Scheme {
             "fs" => Ok(Scheme::Fs),
             "memory" => Ok(Scheme::Memory),
             "s3" => Ok(Scheme::S3),
+            "static_files" => Ok(Scheme::StaticFiles),
 
             // TODO: it's used for compatibility with dal1, should be removed in the future
             "local" | "disk" => Ok(Scheme::Fs),
@@ -14,14 +14,14 @@
 
 use std::cmp::min;
 use std::collections::HashMap;
-use std::str::FromStr;
 use std::sync::Arc;
 
 use anyhow::anyhow;
 use async_trait::async_trait;
 use bytes::BufMut;
-use futures::TryStreamExt;
+use futures::AsyncReadExt;
 use http::header::HeaderName;
+use http::HeaderValue;
 use http::Response;
 use http::StatusCode;
 use hyper::body::HttpBody;
@@ -30,30 +30,41 @@ use log::debug;
 use log::error;
 use log::info;
 use log::warn;
-use metrics::increment_counter;
+#[cfg(feature = "minitrace")]
 use minitrace::trace;
 use reqsign::services::azure::storage::Signer;
-use time::format_description::well_known::Rfc2822;
-use time::OffsetDateTime;
 
+use crate::config::ConfigMap;
 use crate::credential::Credential;
 use crate::error::Error;
 use crate::error::Kind;
 use crate::error::Result;
+use crate::http_util::insert_client_info;
+use crate::http_util::parse_header_datetime;
+use crate::http_util::parse_header_str;
+use crate::http_util::parse_header_u64;
+use crate::http_util::user_agent;
 use crate::io::BytesStream;
+use crate::io::HttpBodyStream;
 use crate::object::Metadata;
-use crate::ops::HeaderRange;
+use crate::ops::BytesRange;
 use crate::ops::OpDelete;
 use crate::ops::OpRead;
 use crate::ops::OpStat;
 use crate::ops::OpWrite;
+use crate::path::out_of_root;
+use crate::path::validate_path;
 use crate::readers::ReaderStream;
+use crate::uri::BackendUri;
 use crate::Accessor;
+use crate::AccessorMetadata;
 use crate::BoxedAsyncReader;
 use crate::ObjectMode;
+use crate::Scheme;
 
 pub const DELETE_SNAPSHOTS: &str = "x-ms-delete-snapshots";
 pub const BLOB_TYPE: &str = "x-ms-blob-type";
+pub const VERSION_ID: &str = "x-ms-version-id";
 
 #[derive(Default, Debug, Clone)]
 pub struct Builder {
@@ -88,6 +99,42 @@ impl Builder {
 
         self
     }
+
+    /// Create a builder from options in key-value form.
+    ///
+    /// Supported options are `container` which is required, `root`,
+    /// `endpoint`, and `account_name` with `account_key` which must be set
+    /// together. Other options will be rejected.
+    pub fn from_map(map: HashMap<String, String>) -> Result<Builder> {
+        let mut map = ConfigMap::new(Scheme::Azblob, map);
+
+        let mut builder = Builder::default();
+        builder.container(&map.take_required("container")?);
+        if let Some(v) = map.take("root") {
+            builder.root(&v);
+        }
+        if let Some(v) = map.take("endpoint") {
+            builder.endpoint(&v);
+        }
+        if let Some((name, key)) = map.take_pair("account_name", "account_key")? {
+            builder.credential(Credential::hmac(&name, &key));
+        }
+        map.finish()?;
+
+        Ok(builder)
+    }
+
+    /// Create a builder from a uri like `azblob://container/path/to/root?endpoint=...`.
+    ///
+    /// The host is the container and the path is the root, both percent
+    /// decoded. Query parameters are other options of [`Builder::from_map`].
+    pub fn from_uri(uri: &str) -> Result<Builder> {
+        Builder::from_map(BackendUri::parse(uri)?.into_map(
+            Scheme::Azblob,
+            Some("container"),
+            Some("root"),
+        )?)
+    }
     pub async fn finish(&mut self) -> Result<Arc<dyn Accessor>> {
         info!("backend build started: {:?}", &self);
 
@@ -171,6 +218,7 @@ impl Builder {
             container: self.container.clone(),
             client,
             account_name,
+            user_agent: user_agent(None).expect("default user agent must be valid"),
         }))
     }
 }
@@ -183,6 +231,7 @@ pub struct Backend {
     endpoint: String,
     signer: Arc<Signer>,
     account_name: String,
+    user_agent: HeaderValue,
 }
 
 impl Backend {
@@ -205,82 +254,151 @@ impl Backend {
 
         p
     }
-    pub(crate) fn get_abs_path(&self, path: &str) -> String {
+    pub(crate) fn get_abs_path(&self, op: &'static str, path: &str) -> Result<String> {
+        validate_path(op, path)?;
+
         let path = Backend::normalize_path(path);
         // root must be normalized like `/abc/`
-        format!("{}{}", self.root, path)
+        Ok(format!("{}{}", self.root, path)
             .trim_start_matches('/')
-            .to_string()
+            .to_string())
     }
-    #[allow(dead_code)]
-    pub(crate) fn get_rel_path(&self, path: &str) -> String {
+    pub(crate) fn get_rel_path(&self, op: &'static str, path: &str) -> Result<String> {
         let path = format!("/{}", path);
 
         match path.strip_prefix(&self.root) {
-            Some(v) => v.to_string(),
-            None => unreachable!(
-                "invalid path {} that not start with backend root {}",
-                &path, &self.root
-            ),
+            Some(v) => Ok(v.to_string()),
+            None => Err(out_of_root(op, &path, &self.root)),
         }
     }
 }
 #[async_trait]
 impl Accessor for Backend {
-    #[trace("read")]
+    #[cfg_attr(feature = "minitrace", trace("read"))]
     async fn read(&self, args: &OpRead) -> Result<BytesStream> {
-        increment_counter!("opendal_azure_read_requests");
-
-        let p = self.get_abs_path(&args.path);
+        let p = self.get_abs_path("read", &args.path)?;
         debug!(
             "object {} read start: offset {:?}, size {:?}",
-            &p, args.offset, args.size
+            &p,
+            args.range.offset(),
+            args.range.size()
         );
+        if args.version.is_some() {
+            return Err(Error::Object {
+                kind: Kind::Unsupported,
+                op: "read",
+                path: p.to_string(),
+                context: HashMap::new(),
+                source: anyhow!("azblob doesn't support versioning"),
+            });
+        }
+
+        if args.range.size() == Some(0) {
+            debug!("object {} read with zero size, skip request", &p);
+            return Ok(Box::new(futures::stream::empty()));
+        }
+
+        // Azure doesn't support suffix ranges, resolve them by the length.
+        let range = if args.range.is_suffix() {
+            let length = self.stat(&OpStat::new(&args.path)).await?.content_length();
+            let size = args.range.size().unwrap_or_default().min(length);
+            if size == 0 {
+                return Ok(Box::new(futures::stream::empty()));
+            }
+            BytesRange::from(length - size..length)
+        } else {
+            args.range
+        };
 
-        let resp = self.get_blob(&p, args.offset, args.size).await?;
+        let resp = self.get_blob(&p, range).await?;
         match resp.status() {
             StatusCode::OK | StatusCode::PARTIAL_CONTENT => {
                 debug!(
                     "object {} reader created: offset {:?}, size {:?}",
-                    &p, args.offset, args.size
+                    &p,
+                    range.offset(),
+                    range.size()
                 );
 
-                Ok(Box::new(resp.into_body().into_stream().map_err(move |e| {
-                    Error::Object {
-                        kind: Kind::Unexpected,
-                        op: "read",
-                        path: p.to_string(),
-                        source: anyhow::Error::from(e),
-                    }
-                })))
+                Ok(Box::new(HttpBodyStream::new(
+                    resp.into_body(),
+                    &p,
+                    range.offset().unwrap_or_default(),
+                )))
             }
             _ => Err(parse_error_response(resp, "read", &p).await),
         }
     }
-    #[trace("write")]
-    async fn write(&self, r: BoxedAsyncReader, args: &OpWrite) -> Result<usize> {
-        let p = self.get_abs_path(&args.path);
-        debug!("object {} write start: size {}", &p, args.size);
+    #[cfg_attr(feature = "minitrace", trace("write"))]
+    async fn write(&self, mut r: BoxedAsyncReader, args: &OpWrite) -> Result<Metadata> {
+        let p = self.get_abs_path("write", &args.path)?;
+        debug!("object {} write start: size {:?}", &p, args.size);
+        if args.is_conditional() {
+            return Err(Error::Object {
+                kind: Kind::Unsupported,
+                op: "write",
+                path: p.to_string(),
+                context: HashMap::new(),
+                source: anyhow!("azblob doesn't support conditional write"),
+            });
+        }
+
+        let size = match args.size {
+            Some(size) => size,
+            // Put blob requires content length, we have to buffer the whole
+            // content if the size is unknown.
+            None => {
+                let mut bs = Vec::new();
+                r.read_to_end(&mut bs).await.map_err(|e| Error::Object {
+                    kind: Kind::Unexpected,
+                    op: "write",
+                    path: p.to_string(),
+                    context: HashMap::new(),
+                    source: anyhow::Error::from(e),
+                })?;
+                let size = bs.len() as u64;
+                r = Box::new(futures::io::Cursor::new(bs));
+                size
+            }
+        };
 
-        let resp = self.put_blob(&p, r, args.size).await?;
+        let resp = self.put_blob(&p, r, size).await?;
 
         match resp.status() {
             http::StatusCode::CREATED | http::StatusCode::OK => {
-                debug!("object {} write finished: size {:?}", &p, args.size);
-                Ok(args.size as usize)
+                debug!("object {} write finished: size {:?}", &p, size);
+                let mut m = Metadata::default();
+                m.set_path(&args.path)
+                    .set_mode(ObjectMode::FILE)
+                    .set_content_length(size);
+                let header = |name| resp.headers().get(name).and_then(|v| v.to_str().ok());
+                if let Some(v) = header(http::header::ETAG.as_str()) {
+                    m.set_etag(v);
+                }
+                if let Some(v) = header(VERSION_ID) {
+                    m.set_version_id(v);
+                }
+                Ok(m)
             }
             _ => Err(parse_error_response(resp, "write", &p).await),
         }
     }
-    #[trace("stat")]
+    #[cfg_attr(feature = "minitrace", trace("stat"))]
     async fn stat(&self, args: &OpStat) -> Result<Metadata> {
-        increment_counter!("opendal_azure_stat_requests");
-
-        let p = self.get_abs_path(&args.path);
+        let p = self.get_abs_path("stat", &args.path)?;
         debug!("object {} stat start", &p);
+        if args.version.is_some() {
+            return Err(Error::Object {
+                kind: Kind::Unsupported,
+                op: "stat",
+                path: p.to_string(),
+                context: HashMap::new(),
+                source: anyhow!("azblob doesn't support versioning"),
+            });
+        }
 
         // Stat root always returns a DIR.
-        if self.get_rel_path(&p).is_empty() {
+        if self.get_rel_path("stat", &p)?.is_empty() {
             let mut m = Metadata::default();
             m.set_path(&args.path);
             m.set_content_length(0);
@@ -298,25 +416,21 @@ impl Accessor for Backend {
                 m.set_path(&args.path);
 
                 // Parse content_length
-                if let Some(v) = resp.headers().get(http::header::CONTENT_LENGTH) {
-                    let v =
-                        u64::from_str(v.to_str().expect("header must not contain non-ascii value"))
-                            .expect("content length header must contain valid length");
-
+                if let Some(v) =
+                    parse_header_u64("stat", &p, resp.headers(), &http::header::CONTENT_LENGTH)?
+                {
                     m.set_content_length(v);
                 }
 
                 // Parse content_md5
-                if let Some(v) = resp.headers().get(HeaderName::from_static("content-md5")) {
-                    let v = v.to_str().expect("header must not contain non-ascii value");
+                if let Some(v) = parse_header_str("stat", &p, resp.headers(), "content-md5")? {
                     m.set_content_md5(v);
                 }
 
                 // Parse last_modified
-                if let Some(v) = resp.headers().get(http::header::LAST_MODIFIED) {
-                    let v = v.to_str().expect("header must not contain non-ascii value");
-                    let t =
-                        OffsetDateTime::parse(v, &Rfc2822).expect("must contain valid time format");
+                if let Some(t) =
+                    parse_header_datetime("stat", &p, resp.headers(), &http::header::LAST_MODIFIED)?
+                {
                     m.set_last_modified(t.into());
                 }
 
@@ -344,12 +458,19 @@ impl Accessor for Backend {
             _ => Err(parse_error_response(resp, "stat", &p).await),
         }
     }
-    #[trace("delete")]
+    #[cfg_attr(feature = "minitrace", trace("delete"))]
     async fn delete(&self, args: &OpDelete) -> Result<()> {
-        increment_counter!("opendal_azure_delete_requests");
-
-        let p = self.get_abs_path(&args.path);
+        let p = self.get_abs_path("delete", &args.path)?;
         debug!("object {} delete start", &p);
+        if args.if_match.is_some() {
+            return Err(Error::Object {
+                kind: Kind::Unsupported,
+                op: "delete",
+                path: p.to_string(),
+                context: HashMap::new(),
+                source: anyhow!("azblob doesn't support conditional delete"),
+            });
+        }
 
         let resp = self.delete_blob(&p).await?;
         match resp.status() {
@@ -360,32 +481,35 @@ impl Accessor for Backend {
             _ => Err(parse_error_response(resp, "delete", &p).await),
         }
     }
+
+    fn metadata(&self) -> AccessorMetadata {
+        let mut am = AccessorMetadata::default();
+        am.set_scheme(Scheme::Azblob).set_root(&self.root);
+        am
+    }
 }
 
 impl Backend {
-    #[trace("get_blob")]
+    #[cfg_attr(feature = "minitrace", trace("get_blob"))]
     pub(crate) async fn get_blob(
         &self,
         path: &str,
-        offset: Option<u64>,
-        size: Option<u64>,
+        range: BytesRange,
     ) -> Result<hyper::Response<hyper::Body>> {
         let mut req = hyper::Request::get(&format!(
             "https://{}.{}/{}/{}",
             self.account_name, self.endpoint, self.container, path
         ));
 
-        if offset.is_some() || size.is_some() {
-            req = req.header(
-                http::header::RANGE,
-                HeaderRange::new(offset, size).to_string(),
-            );
+        if range.offset().is_some() || range.size().is_some() {
+            req = req.header(http::header::RANGE, range.to_string());
         }
 
         let mut req = req
             .body(hyper::Body::empty())
             .expect("must be valid request");
 
+        insert_client_info(&mut req, &self.user_agent);
         self.signer.sign(&mut req).await.expect("sign must success");
 
         self.client.request(req).await.map_err(|e| {
@@ -394,11 +518,12 @@ impl Backend {
                 kind: Kind::Unexpected,
                 op: "read",
                 path: path.to_string(),
+                context: HashMap::new(),
                 source: anyhow::Error::from(e),
             }
         })
     }
-    #[trace("put_blob")]
+    #[cfg_attr(feature = "minitrace", trace("put_blob"))]
     pub(crate) async fn put_blob(
         &self,
         path: &str,
@@ -419,6 +544,7 @@ impl Backend {
             .body(hyper::body::Body::wrap_stream(ReaderStream::new(r)))
             .expect("must be valid request");
 
+        insert_client_info(&mut req, &self.user_agent);
         self.signer.sign(&mut req).await.expect("sign must success");
 
         self.client.request(req).await.map_err(|e| {
@@ -427,12 +553,13 @@ impl Backend {
                 kind: Kind::Unexpected,
                 op: "write",
                 path: path.to_string(),
+                context: HashMap::new(),
                 source: anyhow::Error::from(e),
             }
         })
     }
 
-    #[trace("get_blob_properties")]
+    #[cfg_attr(feature = "minitrace", trace("get_blob_properties"))]
     pub(crate) async fn get_blob_properties(
         &self,
         path: &str,
@@ -445,6 +572,7 @@ impl Backend {
             .body(hyper::Body::empty())
             .expect("must be valid request");
 
+        insert_client_info(&mut req, &self.user_agent);
         self.signer.sign(&mut req).await.expect("sign must success");
 
         self.client.request(req).await.map_err(|e| {
@@ -453,12 +581,13 @@ impl Backend {
                 kind: Kind::Unexpected,
                 op: "stat",
                 path: path.to_string(),
+                context: HashMap::new(),
                 source: anyhow::Error::from(e),
             }
         })
     }
 
-    #[trace("delete_blob")]
+    #[cfg_attr(feature = "minitrace", trace("delete_blob"))]
     pub(crate) async fn delete_blob(&self, path: &str) -> Result<hyper::Response<hyper::Body>> {
         let req = hyper::Request::delete(&format!(
             "https://{}.{}/{}/{}",
@@ -469,6 +598,7 @@ impl Backend {
             .body(hyper::Body::empty())
             .expect("must be valid request");
 
+        insert_client_info(&mut req, &self.user_agent);
         self.signer.sign(&mut req).await.expect("sign must success");
 
         self.client.request(req).await.map_err(|e| {
@@ -477,6 +607,7 @@ impl Backend {
                 kind: Kind::Unexpected,
                 op: "delete",
                 path: path.to_string(),
+                context: HashMap::new(),
                 source: anyhow::Error::from(e),
             }
         })
@@ -489,6 +620,10 @@ async fn parse_error_response(resp: Response<Body>, op: &'static str, path: &str
     let kind = match part.status {
         StatusCode::NOT_FOUND => Kind::ObjectNotExist,
         StatusCode::FORBIDDEN => Kind::ObjectPermissionDenied,
+        StatusCode::INTERNAL_SERVER_ERROR
+        | StatusCode::BAD_GATEWAY
+        | StatusCode::SERVICE_UNAVAILABLE
+        | StatusCode::GATEWAY_TIMEOUT => Kind::ServiceUnavailable,
         _ => Kind::Unexpected,
     };
 
@@ -505,7 +640,7 @@ async fn parse_error_response(resp: Response<Body>, op: &'static str, path: &str
                     break;
                 }
             }
-            Err(e) => return Error::Unexpected(anyhow!("parse error response parse: {:?}", e)),
+            Err(e) => return Error::from(anyhow!("parse error response parse: {:?}", e)),
         }
     }
 
@@ -513,6 +648,7 @@ async fn parse_error_response(resp: Response<Body>, op: &'static str, path: &str
         kind,
         op,
         path: path.to_string(),
+        context: HashMap::new(),
         source: anyhow!(
             "response part: {:?}, body: {:?}",
             part,
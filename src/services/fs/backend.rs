@@ -20,6 +20,8 @@ use std::sync::Arc;
 use anyhow::anyhow;
 use async_compat::Compat;
 use async_trait::async_trait;
+#[cfg(feature = "mmap")]
+use bytes::Bytes;
 use futures::io;
 use futures::AsyncReadExt;
 use futures::AsyncSeekExt;
@@ -28,31 +30,54 @@ use futures::TryStreamExt;
 use log::debug;
 use log::error;
 use log::info;
-use metrics::increment_counter;
+use log::warn;
+#[cfg(feature = "minitrace")]
 use minitrace::trace;
 use tokio::fs;
 
 use super::error::parse_io_error;
+use super::object_stream::walk_dir;
 use super::object_stream::Readdir;
+use crate::config::ConfigMap;
 use crate::error::Error;
 use crate::error::Kind;
 use crate::error::Result;
 use crate::io::BytesStream;
+use crate::object::complete_object_stream;
+use crate::object::filter_by_pattern;
+use crate::object::skip_until_after;
+use crate::object::sort_object_stream;
 use crate::object::BoxedObjectStream;
+use crate::object::LimitedObjectStream;
 use crate::object::Metadata;
 use crate::object::ObjectMode;
+use crate::object::DEFAULT_LIST_SORT_LIMIT;
+use crate::object::LIST_STAT_CONCURRENCY;
+use crate::ops::ListMetadata;
+use crate::ops::OpAppend;
+use crate::ops::OpCopy;
+use crate::ops::OpCreate;
 use crate::ops::OpDelete;
 use crate::ops::OpList;
 use crate::ops::OpRead;
+use crate::ops::OpRename;
+use crate::ops::OpScan;
 use crate::ops::OpStat;
 use crate::ops::OpWrite;
+use crate::path::validate_path;
 use crate::readers::ReaderStream;
+use crate::shutdown::spawn_cleanup;
+use crate::uri::BackendUri;
 use crate::Accessor;
+use crate::AccessorMetadata;
 use crate::BoxedAsyncReader;
+use crate::Scheme;
 
 #[derive(Default, Debug)]
 pub struct Builder {
     root: Option<String>,
+    list_scan_limit: Option<u64>,
+    list_sort_limit: Option<u64>,
 }
 
 impl Builder {
@@ -62,6 +87,49 @@ impl Builder {
         self
     }
 
+    /// Set the max entries that a `list` without `OpList::max_results` could yield.
+    ///
+    /// Listing will end with a `Kind::ScanLimitExceeded` error once exceeded.
+    pub fn list_scan_limit(&mut self, limit: u64) -> &mut Self {
+        self.list_scan_limit = Some(limit);
+
+        self
+    }
+
+    /// Set the max entries of one dir that could be sorted while listing with `OpList::ordered`.
+    ///
+    /// Ordered listing of larger dirs will fail with `Kind::Unsupported`.
+    ///
+    /// Default to 100,000.
+    pub fn list_sort_limit(&mut self, limit: u64) -> &mut Self {
+        self.list_sort_limit = Some(limit);
+
+        self
+    }
+
+    /// Create a builder from options in key-value form.
+    ///
+    /// The only supported option is `root`, other options will be rejected.
+    pub fn from_map(map: HashMap<String, String>) -> Result<Builder> {
+        let mut map = ConfigMap::new(Scheme::Fs, map);
+
+        let mut builder = Builder::default();
+        if let Some(v) = map.take("root") {
+            builder.root(&v);
+        }
+        map.finish()?;
+
+        Ok(builder)
+    }
+
+    /// Create a builder from a uri like `fs:///path/to/root`.
+    ///
+    /// The path is the root, percent decoded. No host or query parameters
+    /// are supported.
+    pub fn from_uri(uri: &str) -> Result<Builder> {
+        Builder::from_map(BackendUri::parse(uri)?.into_map(Scheme::Fs, None, Some("root"))?)
+    }
+
     pub async fn finish(&mut self) -> Result<Arc<dyn Accessor>> {
         info!("backend build started: {:?}", &self);
 
@@ -90,7 +158,11 @@ impl Builder {
         }
 
         info!("backend build finished: {:?}", &self);
-        Ok(Arc::new(Backend { root }))
+        Ok(Arc::new(Backend {
+            root,
+            list_scan_limit: self.list_scan_limit,
+            list_sort_limit: self.list_sort_limit.unwrap_or(DEFAULT_LIST_SORT_LIMIT),
+        }))
     }
 }
 
@@ -105,6 +177,8 @@ impl Builder {
 #[derive(Debug, Clone)]
 pub struct Backend {
     root: String,
+    list_scan_limit: Option<u64>,
+    list_sort_limit: u64,
 }
 
 impl Backend {
@@ -112,7 +186,11 @@ impl Backend {
         Builder::default()
     }
 
-    pub(crate) fn get_abs_path(&self, path: &str) -> String {
+    /// Paths with `..` segments or NUL bytes are rejected with
+    /// `Kind::ObjectPathInvalid`, so that they can't escape the root.
+    pub(crate) fn get_abs_path(&self, op: &'static str, path: &str) -> Result<String> {
+        validate_path(op, path)?;
+
         // Joining an absolute path replaces the existing path, we need to
         // normalize it before.
         let path = path
@@ -121,24 +199,38 @@ impl Backend {
             .collect::<Vec<&str>>()
             .join("/");
 
-        PathBuf::from(&self.root)
+        Ok(PathBuf::from(&self.root)
             .join(path)
             .to_string_lossy()
-            .to_string()
+            .to_string())
     }
 }
 
 #[async_trait]
 impl Accessor for Backend {
-    #[trace("read")]
+    #[cfg_attr(feature = "minitrace", trace("read"))]
     async fn read(&self, args: &OpRead) -> Result<BytesStream> {
-        increment_counter!("opendal_fs_read_requests");
-
-        let path = self.get_abs_path(&args.path);
+        let path = self.get_abs_path("read", &args.path)?;
         debug!(
             "object {} read start: offset {:?}, size {:?}",
-            &path, args.offset, args.size
+            &path,
+            args.range.offset(),
+            args.range.size()
         );
+        if args.version.is_some() {
+            return Err(Error::Object {
+                kind: Kind::Unsupported,
+                op: "read",
+                path: path.to_string(),
+                context: HashMap::new(),
+                source: anyhow!("fs doesn't support versioning"),
+            });
+        }
+
+        if args.range.size() == Some(0) {
+            debug!("object {} read with zero size, skip open", &path);
+            return Ok(Box::new(futures::stream::empty()));
+        }
 
         let f = fs::OpenOptions::new()
             .read(true)
@@ -152,35 +244,97 @@ impl Accessor for Backend {
 
         let mut f = Compat::new(f);
 
-        if let Some(offset) = args.offset {
-            f.seek(SeekFrom::Start(offset)).await.map_err(|e| {
+        let pos = match (args.range.offset(), args.range.size()) {
+            (Some(offset), _) => Some(SeekFrom::Start(offset)),
+            // Read the last `size` bytes, or the whole file if it's shorter.
+            (None, Some(size)) => {
+                let len = f
+                    .get_ref()
+                    .metadata()
+                    .await
+                    .map_err(|e| parse_io_error(e, "read", &path))?
+                    .len();
+                Some(SeekFrom::Start(len.saturating_sub(size)))
+            }
+            (None, None) => None,
+        };
+        if let Some(pos) = pos {
+            f.seek(pos).await.map_err(|e| {
                 let e = parse_io_error(e, "read", &path);
                 error!("object {} seek: {:?}", &path, e);
                 e
             })?;
         };
 
-        let r: BoxedAsyncReader = match args.size {
+        let r: BoxedAsyncReader = match args.range.size() {
             Some(size) => Box::new(f.take(size)),
             None => Box::new(f),
         };
 
         // TODO: we need a better way to convert a file into stream.
-        let s = ReaderStream::new(r).map_err(|e| crate::error::Error::Unexpected(anyhow!(e)));
+        let s = ReaderStream::new(r).map_err(|e| crate::error::Error::from(anyhow!(e)));
 
         debug!(
             "object {} reader created: offset {:?}, size {:?}",
-            &path, args.offset, args.size
+            &path,
+            args.range.offset(),
+            args.range.size()
         );
         Ok(Box::new(s))
     }
 
-    #[trace("write")]
-    async fn write(&self, mut r: BoxedAsyncReader, args: &OpWrite) -> Result<usize> {
-        increment_counter!("opendal_fs_write_requests");
+    #[cfg(feature = "mmap")]
+    #[cfg_attr(feature = "minitrace", trace("read_mmap"))]
+    async fn read_mmap(&self, args: &OpRead) -> Result<Bytes> {
+        let path = self.get_abs_path("read_mmap", &args.path)?;
+        debug!(
+            "object {} read mmap start: offset {:?}, size {:?}",
+            &path,
+            args.range.offset(),
+            args.range.size()
+        );
+        if args.version.is_some() {
+            return Err(Error::Object {
+                kind: Kind::Unsupported,
+                op: "read",
+                path: path.to_string(),
+                context: HashMap::new(),
+                source: anyhow!("fs doesn't support versioning"),
+            });
+        }
+
+        if args.range.size() == Some(0) {
+            debug!("object {} read with zero size, skip open", &path);
+            return Ok(Bytes::new());
+        }
 
-        let path = self.get_abs_path(&args.path);
-        debug!("object {} write start: size {}", &path, args.size);
+        // Both open and mmap could block.
+        let (offset, size) = (args.range.offset(), args.range.size());
+        let bs = tokio::task::spawn_blocking(move || super::mmap::map_file(&path, offset, size))
+            .await
+            .map_err(|e| anyhow!(e))??;
+
+        debug!(
+            "object {} read mmap finished: size {}",
+            &args.path,
+            bs.len()
+        );
+        Ok(bs)
+    }
+
+    #[cfg_attr(feature = "minitrace", trace("write"))]
+    async fn write(&self, mut r: BoxedAsyncReader, args: &OpWrite) -> Result<Metadata> {
+        let path = self.get_abs_path("write", &args.path)?;
+        debug!("object {} write start: size {:?}", &path, args.size);
+        if args.is_conditional() {
+            return Err(Error::Object {
+                kind: Kind::Unsupported,
+                op: "write",
+                path: path.to_string(),
+                context: HashMap::new(),
+                source: anyhow!("fs doesn't support conditional write"),
+            });
+        }
 
         // Create dir before write path.
         //
@@ -204,10 +358,20 @@ impl Accessor for Backend {
             e
         })?;
 
+        // Write into a temp file and rename it to the path once all data
+        // has been written, so that failed or dropped writes never leave
+        // partial content in the object.
+        let tmp = TempFile(Some(format!(
+            "{}.{}{}",
+            &path,
+            uuid::Uuid::new_v4(),
+            TEMP_FILE_SUFFIX
+        )));
+        let tmp_path = tmp.path();
         let f = fs::OpenOptions::new()
-            .create(true)
+            .create_new(true)
             .write(true)
-            .open(&path)
+            .open(tmp_path)
             .await
             .map_err(|e| {
                 let e = parse_io_error(e, "write", &path);
@@ -233,17 +397,176 @@ impl Accessor for Backend {
             error!("object {} flush: {:?}", &path, e);
             e
         })?;
+        drop(f);
+
+        fs::rename(tmp_path, &path).await.map_err(|e| {
+            let e = parse_io_error(e, "write", &path);
+            error!("object {} rename from temp file: {:?}", &path, e);
+            e
+        })?;
+        tmp.persist();
 
         debug!("object {} write finished: size {:?}", &path, args.size);
-        Ok(s as usize)
+        let mut m = Metadata::default();
+        m.set_path(&args.path)
+            .set_mode(ObjectMode::FILE)
+            .set_content_length(s);
+        Ok(m)
     }
 
-    #[trace("stat")]
-    async fn stat(&self, args: &OpStat) -> Result<Metadata> {
-        increment_counter!("opendal_fs_stat_requests");
+    /// Append by opening the file in append mode.
+    ///
+    /// The position is checked against the length of the opened file,
+    /// appenders in other processes are not fenced between the check and
+    /// the write.
+    #[cfg_attr(feature = "minitrace", trace("append"))]
+    async fn append(&self, r: BoxedAsyncReader, args: &OpAppend) -> Result<Metadata> {
+        let path = self.get_abs_path("append", &args.path)?;
+        debug!(
+            "object {} append start: position {}, size {}",
+            &path, args.position, args.size
+        );
+
+        // Only appending at 0 creates the file.
+        if args.position == 0 {
+            let parent = PathBuf::from(&path)
+                .parent()
+                .ok_or_else(|| anyhow!("malformed path: {:?}", &path))?
+                .to_path_buf();
+
+            fs::create_dir_all(&parent).await.map_err(|e| {
+                let e = parse_io_error(e, "append", &parent.to_string_lossy());
+                error!(
+                    "object {} create_dir_all for parent {}: {:?}",
+                    &path,
+                    &parent.to_string_lossy(),
+                    e
+                );
+                e
+            })?;
+        }
+
+        let f = fs::OpenOptions::new()
+            .create(args.position == 0)
+            .append(true)
+            .open(&path)
+            .await
+            .map_err(|e| {
+                let e = parse_io_error(e, "append", &path);
+                error!("object {} open: {:?}", &path, e);
+                e
+            })?;
+
+        let len = f
+            .metadata()
+            .await
+            .map_err(|e| parse_io_error(e, "append", &path))?
+            .len();
+        if len != args.position {
+            return Err(Error::Object {
+                kind: Kind::ObjectPreconditionFailed,
+                op: "append",
+                path: args.path.to_string(),
+                context: HashMap::from([
+                    ("position".to_string(), args.position.to_string()),
+                    ("length".to_string(), len.to_string()),
+                ]),
+                source: anyhow!("append position doesn't match the object's length"),
+            });
+        }
+
+        let mut f = Compat::new(f);
+        let copied = io::copy(&mut r.take(args.size), &mut f).await;
+        let flushed = f.flush().await;
+        let err = match (copied, flushed) {
+            (Ok(n), Ok(())) if n == args.size => None,
+            (Ok(n), Ok(())) => Some(Error::Object {
+                kind: Kind::Unexpected,
+                op: "append",
+                path: args.path.to_string(),
+                context: HashMap::from([("size".to_string(), args.size.to_string())]),
+                source: anyhow!("reader got {} bytes, expected {}", n, args.size),
+            }),
+            (Err(e), _) | (_, Err(e)) => Some(parse_io_error(e, "append", &path)),
+        };
+        if let Some(e) = err {
+            // Truncate the partial content, so that the append could be
+            // retried at the same position.
+            if let Err(te) = f.get_ref().set_len(args.position).await {
+                warn!("object {} truncate after failed append: {:?}", &path, te);
+            }
+            error!("object {} append: {:?}", &path, e);
+            return Err(e);
+        }
+
+        debug!("object {} append finished: size {}", &path, args.size);
+        let mut m = Metadata::default();
+        m.set_path(&args.path)
+            .set_mode(ObjectMode::FILE)
+            .set_content_length(args.position + args.size);
+        Ok(m)
+    }
+
+    #[cfg_attr(feature = "minitrace", trace("create"))]
+    async fn create(&self, args: &OpCreate) -> Result<Metadata> {
+        let path = self.get_abs_path("create", &args.path)?;
+        debug!("object {} create start: mode {}", &path, args.mode);
+
+        let mut m = Metadata::default();
+        m.set_path(&args.path)
+            .set_mode(args.mode)
+            .set_content_length(0);
 
-        let path = self.get_abs_path(&args.path);
+        if args.mode == ObjectMode::DIR {
+            fs::create_dir_all(&path)
+                .await
+                .map_err(|e| parse_io_error(e, "create", &path))?;
+
+            debug!("object {} create finished", &path);
+            return Ok(m);
+        }
+        if args.mode != ObjectMode::FILE || args.path.ends_with('/') {
+            return Err(Error::Object {
+                kind: Kind::Unexpected,
+                op: "create",
+                path: path.to_string(),
+                context: HashMap::from([("mode".to_string(), args.mode.to_string())]),
+                source: anyhow!("path doesn't match the mode of create"),
+            });
+        }
+
+        let parent = PathBuf::from(&path)
+            .parent()
+            .ok_or_else(|| anyhow!("malformed path: {:?}", &path))?
+            .to_path_buf();
+        fs::create_dir_all(&parent)
+            .await
+            .map_err(|e| parse_io_error(e, "create", &parent.to_string_lossy()))?;
+
+        // Truncate the existing file like other services overwrite it.
+        fs::File::create(&path).await.map_err(|e| {
+            let e = parse_io_error(e, "create", &path);
+            error!("object {} create: {:?}", &path, e);
+            e
+        })?;
+
+        debug!("object {} create finished", &path);
+        Ok(m)
+    }
+
+    #[cfg_attr(feature = "minitrace", trace("stat"))]
+    async fn stat(&self, args: &OpStat) -> Result<Metadata> {
+        let path = self.get_abs_path("stat", &args.path)?;
         debug!("object {} stat start", &path);
+        if args.version.is_some() {
+            return Err(Error::Object {
+                kind: Kind::Unsupported,
+                op: "stat",
+                path: path.to_string(),
+                context: HashMap::new(),
+                source: anyhow!("fs doesn't support versioning"),
+            });
+        }
 
         let meta = fs::metadata(&path).await.map_err(|e| {
             let e = parse_io_error(e, "stat", &path);
@@ -270,12 +593,19 @@ impl Accessor for Backend {
         Ok(m)
     }
 
-    #[trace("delete")]
+    #[cfg_attr(feature = "minitrace", trace("delete"))]
     async fn delete(&self, args: &OpDelete) -> Result<()> {
-        increment_counter!("opendal_fs_delete_requests");
-
-        let path = self.get_abs_path(&args.path);
+        let path = self.get_abs_path("delete", &args.path)?;
         debug!("object {} delete start", &path);
+        if args.if_match.is_some() {
+            return Err(Error::Object {
+                kind: Kind::Unsupported,
+                op: "delete",
+                path: path.to_string(),
+                context: HashMap::new(),
+                source: anyhow!("fs doesn't support conditional delete"),
+            });
+        }
 
         // PathBuf.is_dir() is not free, call metadata directly instead.
         let meta = fs::metadata(&path).await;
@@ -305,12 +635,100 @@ impl Accessor for Backend {
         Ok(())
     }
 
-    #[trace("list")]
-    async fn list(&self, args: &OpList) -> Result<BoxedObjectStream> {
-        increment_counter!("opendal_fs_list_requests");
+    #[cfg_attr(feature = "minitrace", trace("copy"))]
+    async fn copy(&self, args: &OpCopy) -> Result<Metadata> {
+        let src = self.get_abs_path("copy", &args.src)?;
+        let dst = self.get_abs_path("copy", &args.dst)?;
+        debug!("object {} copy start: to {}", &src, &dst);
+
+        // Copying a file onto itself truncates it, only check the existence.
+        let size = if src == dst {
+            fs::metadata(&src)
+                .await
+                .map_err(|e| parse_io_error(e, "copy", &src))?
+                .len()
+        } else {
+            let parent = PathBuf::from(&dst)
+                .parent()
+                .ok_or_else(|| anyhow!("malformed path: {:?}", &dst))?
+                .to_path_buf();
+            fs::create_dir_all(&parent)
+                .await
+                .map_err(|e| parse_io_error(e, "copy", &parent.to_string_lossy()))?;
+
+            fs::copy(&src, &dst).await.map_err(|e| {
+                let e = parse_io_error(e, "copy", &src);
+                error!("object {} copy: {:?}", &src, e);
+                e
+            })?
+        };
+
+        let mut m = Metadata::default();
+        m.set_path(&args.dst)
+            .set_mode(ObjectMode::FILE)
+            .set_content_length(size);
+
+        debug!("object {} copy finished: to {}", &src, &dst);
+        Ok(m)
+    }
+
+    #[cfg_attr(feature = "minitrace", trace("rename"))]
+    async fn rename(&self, args: &OpRename) -> Result<Metadata> {
+        let src = self.get_abs_path("rename", &args.src)?;
+        let dst = self.get_abs_path("rename", &args.dst)?;
+        debug!("object {} rename start: to {}", &src, &dst);
+
+        let parent = PathBuf::from(&dst)
+            .parent()
+            .ok_or_else(|| anyhow!("malformed path: {:?}", &dst))?
+            .to_path_buf();
+        fs::create_dir_all(&parent)
+            .await
+            .map_err(|e| parse_io_error(e, "rename", &parent.to_string_lossy()))?;
+
+        // fs::rename overwrites the existing file and keeps the file if
+        // renamed onto itself.
+        fs::rename(&src, &dst).await.map_err(|e| {
+            let e = parse_io_error(e, "rename", &src);
+            error!("object {} rename: {:?}", &src, e);
+            e
+        })?;
+
+        let meta = fs::metadata(&dst)
+            .await
+            .map_err(|e| parse_io_error(e, "rename", &dst))?;
+
+        let mut m = Metadata::default();
+        m.set_path(&args.dst)
+            .set_mode(ObjectMode::FILE)
+            .set_content_length(meta.len());
 
-        let path = self.get_abs_path(&args.path);
+        debug!("object {} rename finished: to {}", &src, &dst);
+        Ok(m)
+    }
+
+    #[cfg_attr(feature = "minitrace", trace("list"))]
+    async fn list(&self, args: &OpList) -> Result<BoxedObjectStream> {
+        let path = self.get_abs_path("list", &args.path)?;
         debug!("object {} list start", &path);
+        if args.recursive {
+            return Err(Error::Object {
+                kind: Kind::Unsupported,
+                op: "list",
+                path: path.to_string(),
+                context: HashMap::new(),
+                source: anyhow!("fs doesn't support recursive list"),
+            });
+        }
+        if args.versions {
+            return Err(Error::Object {
+                kind: Kind::Unsupported,
+                op: "list",
+                path: path.to_string(),
+                context: HashMap::new(),
+                source: anyhow!("fs doesn't support versioning"),
+            });
+        }
 
         let f = fs::read_dir(&path).await.map_err(|e| {
             let e = parse_io_error(e, "read", &path);
@@ -319,7 +737,88 @@ impl Accessor for Backend {
         })?;
 
         let rd = Readdir::new(Arc::new(self.clone()), &self.root, &args.path, f);
+        let rd = skip_until_after(Box::new(rd), args.start_after.clone());
+        let rd = filter_by_pattern(rd, args.pattern.as_deref());
+
+        // readdir doesn't guarantee any order, we have to sort them by ourselves.
+        let obs: BoxedObjectStream = if args.ordered {
+            sort_object_stream(rd, &args.path, self.list_sort_limit).await?
+        } else {
+            rd
+        };
+
+        let obs: BoxedObjectStream = Box::new(LimitedObjectStream::new(
+            obs,
+            &args.path,
+            args.max_results,
+            self.list_scan_limit,
+        ));
+
+        // readdir only returns names of entries.
+        Ok(match args.metadata {
+            ListMetadata::Mode | ListMetadata::Listed => obs,
+            ListMetadata::Complete => {
+                complete_object_stream(obs, LIST_STAT_CONCURRENCY, args.priority)
+            }
+        })
+    }
+
+    #[cfg_attr(feature = "minitrace", trace("scan"))]
+    async fn scan(&self, args: &OpScan) -> Result<BoxedObjectStream> {
+        let path = self.get_abs_path("scan", &args.path)?;
+        debug!("object {} scan start", &path);
 
-        Ok(Box::new(rd))
+        let f = fs::read_dir(&path).await.map_err(|e| {
+            let e = parse_io_error(e, "scan", &path);
+            error!("object {} scan: {:?}", &path, e);
+            e
+        })?;
+
+        Ok(walk_dir(Arc::new(self.clone()), &self.root, &args.path, f))
+    }
+
+    fn metadata(&self) -> AccessorMetadata {
+        let mut am = AccessorMetadata::default();
+        am.set_scheme(Scheme::Fs)
+            .set_root(&self.root)
+            .set_list(true)
+            .set_copy(true)
+            .set_append(true)
+            .set_mmap_read(cfg!(feature = "mmap"));
+        am
+    }
+}
+
+/// Suffix of temp files of writes, which are hidden from listing.
+pub(super) const TEMP_FILE_SUFFIX: &str = ".opendal-tmp";
+
+/// TempFile removes the temp file of a write if it's dropped before being
+/// renamed to the object, like the write is failed or cancelled.
+struct TempFile(Option<String>);
+
+impl TempFile {
+    fn path(&self) -> &str {
+        self.0.as_deref().expect("temp file must not be persisted")
+    }
+
+    /// The temp file has been renamed to the object.
+    fn persist(mut self) {
+        self.0 = None;
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        if let Some(path) = self.0.take() {
+            let target = path.clone();
+            spawn_cleanup("fs_temp_file", &target, async move {
+                match fs::remove_file(&path).await {
+                    Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                        warn!("remove temp file {}: {:?}", &path, e)
+                    }
+                    _ => debug!("temp file {} removed in background", &path),
+                }
+            });
+        }
     }
 }
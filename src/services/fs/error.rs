@@ -12,6 +12,8 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::collections::HashMap;
+
 use crate::error::Error;
 use crate::error::Kind;
 
@@ -28,18 +30,21 @@ pub fn parse_io_error(err: std::io::Error, op: &'static str, path: &str) -> Erro
             kind: Kind::ObjectNotExist,
             op,
             path: path.to_string(),
+            context: HashMap::new(),
             source: anyhow::Error::from(err),
         },
         ErrorKind::PermissionDenied => Error::Object {
             kind: Kind::ObjectPermissionDenied,
             op,
             path: path.to_string(),
+            context: HashMap::new(),
             source: anyhow::Error::from(err),
         },
         _ => Error::Object {
             kind: Kind::Unexpected,
             op,
             path: path.to_string(),
+            context: HashMap::new(),
             source: anyhow::Error::from(err),
         },
     }
@@ -0,0 +1,59 @@
+// Copyright 2022 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fs::File;
+
+use bytes::Bytes;
+use memmap2::MmapOptions;
+
+use super::error::parse_io_error;
+use crate::error::Result;
+
+/// Map `size` bytes starting from `offset` of the file at `path` into a
+/// `Bytes`, the map will be unmapped after all clones of it are dropped.
+///
+/// The range is clamped by the file length captured at open, so the map
+/// never covers pages past the end of the file at that time.
+///
+/// # Safety
+///
+/// Mapping is only sound while the file isn't modified in place: writes
+/// through other handles will be visible in the returned `Bytes`, and
+/// accessing pages beyond the end of a file truncated after open raises
+/// `SIGBUS`. Only use it on files that are replaced instead of modified,
+/// like the files written by opendal itself.
+pub(crate) fn map_file(path: &str, offset: Option<u64>, size: Option<u64>) -> Result<Bytes> {
+    let f = File::open(path).map_err(|e| parse_io_error(e, "read", path))?;
+    let len = f
+        .metadata()
+        .map_err(|e| parse_io_error(e, "read", path))?
+        .len();
+
+    let offset = match (offset, size) {
+        // Read the last `size` bytes.
+        (None, Some(size)) => len.saturating_sub(size),
+        (offset, _) => offset.unwrap_or_default().min(len),
+    };
+    let size = size.unwrap_or(len - offset).min(len - offset);
+    // Empty maps are rejected by the OS.
+    if size == 0 {
+        return Ok(Bytes::new());
+    }
+
+    // Safety: the range is inside the file, see the function doc for the
+    // requirements on concurrent modification.
+    let map = unsafe { MmapOptions::new().offset(offset).len(size as usize).map(&f) }
+        .map_err(|e| parse_io_error(e, "read", path))?;
+    Ok(Bytes::from_owner(map))
+}
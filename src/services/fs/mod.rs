@@ -52,4 +52,6 @@ pub use backend::Backend;
 pub use backend::Builder;
 
 mod error;
+#[cfg(feature = "mmap")]
+mod mmap;
 mod object_stream;
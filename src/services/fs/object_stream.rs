@@ -12,6 +12,9 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::collections::HashMap;
+use std::path::Path;
+use std::path::PathBuf;
 use std::pin::Pin;
 use std::sync::Arc;
 use std::task::Context;
@@ -22,12 +25,15 @@ use log::debug;
 use log::error;
 use tokio::fs;
 
+use super::backend::TEMP_FILE_SUFFIX;
 use super::error::parse_io_error;
 use crate::error::Error;
 use crate::error::Kind;
 use crate::error::Result;
 use crate::Accessor;
+use crate::BoxedObjectStream;
 use crate::Object;
+use crate::ObjectMode;
 
 pub struct Readdir {
     acc: Arc<dyn Accessor>,
@@ -52,7 +58,13 @@ impl futures::Stream for Readdir {
     type Item = Result<Object>;
 
     fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        match ready!(Pin::new(&mut self.rd).poll_next_entry(cx)) {
+        let de = loop {
+            match ready!(Pin::new(&mut self.rd).poll_next_entry(cx)) {
+                Ok(Some(de)) if is_temp_file(&de.path()) => continue,
+                v => break v,
+            }
+        };
+        match de {
             Err(e) => {
                 error!("object {} stream poll_next: {:?}", &self.path, e);
                 Poll::Ready(Some(Err(parse_io_error(e, "list", &self.path))))
@@ -68,6 +80,7 @@ impl futures::Stream for Readdir {
                         kind: Kind::Unexpected,
                         op: "list",
                         path: de.path().to_string_lossy().to_string(),
+                        context: HashMap::new(),
                         source: anyhow::Error::from(e),
                     };
                     error!("object {:?} path strip_prefix: {:?}", &de.path(), e);
@@ -86,3 +99,115 @@ impl futures::Stream for Readdir {
         }
     }
 }
+
+/// Walk all files under the dir recursively for `scan`.
+///
+/// Dirs are walked depth-first without being yielded, and symlinks to
+/// dirs are not followed to avoid cycles.
+pub fn walk_dir(
+    acc: Arc<dyn Accessor>,
+    root: &str,
+    path: &str,
+    rd: fs::ReadDir,
+) -> BoxedObjectStream {
+    let state = WalkState {
+        acc,
+        root: PathBuf::from(root),
+        path: path.to_string(),
+        pending: Vec::new(),
+        current: Some(rd),
+    };
+
+    Box::new(Box::pin(futures::stream::try_unfold(
+        state,
+        |mut st| async move {
+            loop {
+                let rd = match st.current.as_mut() {
+                    Some(rd) => rd,
+                    None => match st.pending.pop() {
+                        Some(dir) => {
+                            let rd = fs::read_dir(&dir)
+                                .await
+                                .map_err(|e| parse_io_error(e, "scan", &dir.to_string_lossy()))?;
+                            st.current = Some(rd);
+                            continue;
+                        }
+                        None => {
+                            debug!("object {} scan done", &st.path);
+                            return Ok(None);
+                        }
+                    },
+                };
+
+                let de = match rd.next_entry().await {
+                    Ok(Some(de)) => de,
+                    Ok(None) => {
+                        st.current = None;
+                        continue;
+                    }
+                    Err(e) => {
+                        error!("object {} stream poll_next: {:?}", &st.path, e);
+                        return Err(parse_io_error(e, "scan", &st.path));
+                    }
+                };
+
+                let ft = de
+                    .file_type()
+                    .await
+                    .map_err(|e| parse_io_error(e, "scan", &de.path().to_string_lossy()))?;
+                if ft.is_dir() {
+                    st.pending.push(de.path());
+                    continue;
+                }
+                let is_file = if ft.is_symlink() {
+                    fs::metadata(de.path())
+                        .await
+                        .map(|m| m.is_file())
+                        .unwrap_or(false)
+                } else {
+                    ft.is_file()
+                };
+                if !is_file || is_temp_file(&de.path()) {
+                    continue;
+                }
+
+                let path = rel_path(&st.root, &de.path())?;
+                let mut o = Object::new(st.acc.clone(), &path);
+                o.metadata_mut().set_path(&path).set_mode(ObjectMode::FILE);
+
+                debug!("object {} got entry, path: {}", &st.path, &path);
+                return Ok(Some((o, st)));
+            }
+        },
+    )))
+}
+
+/// Temp files of writes in progress are not objects.
+fn is_temp_file(path: &Path) -> bool {
+    path.to_string_lossy().ends_with(TEMP_FILE_SUFFIX)
+}
+
+struct WalkState {
+    acc: Arc<dyn Accessor>,
+    root: PathBuf,
+    path: String,
+    /// Dirs found but not walked yet.
+    pending: Vec<PathBuf>,
+    current: Option<fs::ReadDir>,
+}
+
+fn rel_path(root: &Path, path: &Path) -> Result<String> {
+    let rel = path.strip_prefix(root).map_err(|e| {
+        let e = Error::Object {
+            kind: Kind::Unexpected,
+            op: "scan",
+            path: path.to_string_lossy().to_string(),
+            context: HashMap::new(),
+            source: anyhow::Error::from(e),
+        };
+        error!("object {:?} path strip_prefix: {:?}", path, e);
+        e
+    })?;
+
+    Ok(rel.to_string_lossy().to_string())
+}
@@ -24,36 +24,78 @@ use async_trait::async_trait;
 use bytes::Bytes;
 use futures::io;
 use futures::stream;
+#[cfg(feature = "minitrace")]
 use minitrace::trace;
 
+use crate::config::ConfigMap;
 use crate::error::Error;
 use crate::error::Kind;
 use crate::error::Result;
 use crate::io::BytesStream;
+use crate::object::filter_by_pattern;
+use crate::object::unquote_etag;
 use crate::object::BoxedObjectStream;
+use crate::object::LimitedObjectStream;
+use crate::ops::OpCopy;
 use crate::ops::OpDelete;
 use crate::ops::OpList;
 use crate::ops::OpRead;
+use crate::ops::OpRename;
 use crate::ops::OpStat;
 use crate::ops::OpWrite;
+use crate::path::validate_path;
+use crate::uri::BackendUri;
 use crate::Accessor;
+use crate::AccessorMetadata;
 use crate::BoxedAsyncReader;
 use crate::Metadata;
 use crate::Object;
 use crate::ObjectMode;
+use crate::Scheme;
 
 #[derive(Default)]
-pub struct Builder {}
+pub struct Builder {
+    list_scan_limit: Option<u64>,
+}
 
 impl Builder {
+    /// Set the max entries that a `list` without `OpList::max_results` could yield.
+    ///
+    /// Listing will end with a `Kind::ScanLimitExceeded` error once exceeded.
+    pub fn list_scan_limit(&mut self, limit: u64) -> &mut Self {
+        self.list_scan_limit = Some(limit);
+
+        self
+    }
+
+    /// Create a builder from options in key-value form.
+    ///
+    /// No options are supported yet.
+    pub fn from_map(map: HashMap<String, String>) -> Result<Builder> {
+        ConfigMap::new(Scheme::Memory, map).finish()?;
+
+        Ok(Builder::default())
+    }
+
+    /// Create a builder from the uri `memory://`.
+    ///
+    /// No host, path or query parameters are supported.
+    pub fn from_uri(uri: &str) -> Result<Builder> {
+        Builder::from_map(BackendUri::parse(uri)?.into_map(Scheme::Memory, None, None)?)
+    }
+
     pub async fn finish(&mut self) -> Result<Arc<dyn Accessor>> {
-        Ok(Arc::new(Backend::default()))
+        Ok(Arc::new(Backend {
+            list_scan_limit: self.list_scan_limit,
+            ..Default::default()
+        }))
     }
 }
 
 #[derive(Debug, Clone, Default)]
 pub struct Backend {
     inner: Arc<Mutex<HashMap<String, bytes::Bytes>>>,
+    list_scan_limit: Option<u64>,
 }
 
 impl Backend {
@@ -79,11 +121,82 @@ impl Backend {
     }
 }
 
+/// ETag of the content, which is the MD5 of the content like s3.
+fn etag(bs: &[u8]) -> String {
+    format!("{:x}", md5::compute(bs))
+}
+
+/// Check the preconditions against the current content of the object.
+///
+/// Reads fail with `ObjectNotModified` if `if_none_match` matches, while
+/// writes fail with `ObjectPreconditionFailed`, decided by `none_match_kind`.
+fn check_preconditions(
+    op: &'static str,
+    path: &str,
+    current: Option<&Bytes>,
+    if_match: &Option<String>,
+    if_none_match: &Option<String>,
+    none_match_kind: Kind,
+) -> Result<()> {
+    let current = current.map(|bs| etag(bs));
+    let matches = |expected: &str| match &current {
+        Some(etag) => expected == "*" || unquote_etag(expected) == etag,
+        None => false,
+    };
+
+    if let Some(expected) = if_match {
+        if current.is_none() {
+            return Err(Error::Object {
+                kind: Kind::ObjectNotExist,
+                op,
+                path: path.to_string(),
+                context: HashMap::new(),
+                source: anyhow!("key not exists in map"),
+            });
+        }
+        if !matches(expected) {
+            return Err(Error::Object {
+                kind: Kind::ObjectPreconditionFailed,
+                op,
+                path: path.to_string(),
+                context: HashMap::new(),
+                source: anyhow!("etag {:?} doesn't match {}", current, expected),
+            });
+        }
+    }
+    if let Some(expected) = if_none_match {
+        if matches(expected) {
+            return Err(Error::Object {
+                kind: none_match_kind,
+                op,
+                path: path.to_string(),
+                context: HashMap::new(),
+                source: anyhow!("etag {:?} matches {}", current, expected),
+            });
+        }
+    }
+    Ok(())
+}
+
 #[async_trait]
 impl Accessor for Backend {
-    #[trace("read")]
+    #[cfg_attr(feature = "minitrace", trace("read"))]
     async fn read(&self, args: &OpRead) -> Result<BytesStream> {
+        validate_path("read", &args.path)?;
         let path = Backend::normalize_path(&args.path);
+        if args.version.is_some() {
+            return Err(Error::Object {
+                kind: Kind::Unsupported,
+                op: "read",
+                path: path.to_string(),
+                context: HashMap::new(),
+                source: anyhow!("memory doesn't support versioning"),
+            });
+        }
+
+        if args.range.size() == Some(0) {
+            return Ok(Box::new(stream::empty()));
+        }
 
         let map = self.inner.lock().expect("lock poisoned");
 
@@ -91,28 +204,47 @@ impl Accessor for Backend {
             kind: Kind::ObjectNotExist,
             op: "read",
             path: path.to_string(),
+            context: HashMap::new(),
             source: anyhow!("key not exists in map"),
         })?;
+        check_preconditions(
+            "read",
+            &path,
+            Some(data),
+            &args.if_match,
+            &args.if_none_match,
+            Kind::ObjectNotModified,
+        )?;
 
         let mut data = data.clone();
-        if let Some(offset) = args.offset {
-            if offset >= data.len() as u64 {
+        let (offset, size) = match (args.range.offset(), args.range.size()) {
+            // Read the last `size` bytes, or the whole object if it's shorter.
+            (None, Some(size)) => {
+                let size = size.min(data.len() as u64);
+                (Some(data.len() as u64 - size), Some(size))
+            }
+            v => v,
+        };
+        if let Some(offset) = offset {
+            if offset > data.len() as u64 {
                 return Err(Error::Object {
                     kind: Kind::Unexpected,
                     op: "read",
                     path: path.to_string(),
-                    source: anyhow!("offset out of bound {} >= {}", offset, data.len()),
+                    context: HashMap::new(),
+                    source: anyhow!("offset out of bound {} > {}", offset, data.len()),
                 });
             }
             data = data.slice(offset as usize..data.len());
         };
 
-        if let Some(size) = args.size {
+        if let Some(size) = size {
             if size > data.len() as u64 {
                 return Err(Error::Object {
                     kind: Kind::Unexpected,
                     op: "read",
                     path: path.to_string(),
+                    context: HashMap::new(),
                     source: anyhow!("size out of bound {} > {}", size, data.len()),
                 });
             }
@@ -123,11 +255,12 @@ impl Accessor for Backend {
             Ok::<_, Error>(data)
         }))))
     }
-    #[trace("write")]
-    async fn write(&self, mut r: BoxedAsyncReader, args: &OpWrite) -> Result<usize> {
+    #[cfg_attr(feature = "minitrace", trace("write"))]
+    async fn write(&self, mut r: BoxedAsyncReader, args: &OpWrite) -> Result<Metadata> {
+        validate_path("write", &args.path)?;
         let path = Backend::normalize_path(&args.path);
 
-        let bs = vec![0; args.size as usize];
+        let bs = vec![0; args.size.unwrap_or_default() as usize];
         let mut cursor = io::Cursor::new(bs);
         let n = io::copy(&mut r, &mut cursor)
             .await
@@ -135,27 +268,71 @@ impl Accessor for Backend {
                 kind: Kind::Unexpected,
                 op: "write",
                 path: path.clone(),
+                context: HashMap::new(),
                 source: anyhow::Error::from(e),
             })?;
-        if n < args.size {
-            return Err(Error::Object {
-                kind: Kind::Unexpected,
-                op: "write",
-                path: path.clone(),
-                source: anyhow!("write short  {} M {}", n, args.size),
-            });
+        if let Some(size) = args.size {
+            if n < size {
+                return Err(Error::Object {
+                    kind: Kind::Unexpected,
+                    op: "write",
+                    path: path.clone(),
+                    context: HashMap::new(),
+                    source: anyhow!("write short  {} M {}", n, size),
+                });
+            }
         }
 
+        // Check and insert under the same lock so that conditional writes are atomic.
         let mut map = self.inner.lock().expect("lock poisoned");
-        map.insert(path.to_string(), Bytes::from(cursor.into_inner()));
-
-        Ok(n as usize)
+        check_preconditions(
+            "write",
+            &path,
+            map.get(&path),
+            &args.if_match,
+            &args.if_none_match,
+            Kind::ObjectPreconditionFailed,
+        )?;
+        let bs = Bytes::from(cursor.into_inner());
+        let etag = etag(&bs);
+        map.insert(path.to_string(), bs);
+
+        let mut m = Metadata::default();
+        m.set_path(&args.path)
+            .set_mode(ObjectMode::FILE)
+            .set_content_length(n)
+            .set_etag(&etag);
+        Ok(m)
     }
-    #[trace("stat")]
+    #[cfg_attr(feature = "minitrace", trace("stat"))]
     async fn stat(&self, args: &OpStat) -> Result<Metadata> {
+        validate_path("stat", &args.path)?;
         let path = Backend::normalize_path(&args.path);
+        if args.version.is_some() {
+            return Err(Error::Object {
+                kind: Kind::Unsupported,
+                op: "stat",
+                path: path.to_string(),
+                context: HashMap::new(),
+                source: anyhow!("memory doesn't support versioning"),
+            });
+        }
 
         if path.ends_with('/') || path.is_empty() {
+            // Like s3, a dir exists only if there are keys under it.
+            if !path.is_empty() && path != "/" {
+                let map = self.inner.lock().expect("lock poisoned");
+                if !map.keys().any(|k| k.starts_with(&path)) {
+                    return Err(Error::Object {
+                        kind: Kind::ObjectNotExist,
+                        op: "stat",
+                        path: path.to_string(),
+                        context: HashMap::new(),
+                        source: anyhow!("dir not exists in map"),
+                    });
+                }
+            }
+
             let mut meta = Metadata::default();
             meta.set_path(&path)
                 .set_mode(ObjectMode::DIR)
@@ -171,43 +348,153 @@ impl Accessor for Backend {
             kind: Kind::ObjectNotExist,
             op: "stat",
             path: path.to_string(),
+            context: HashMap::new(),
             source: anyhow!("key not exists in map"),
         })?;
+        check_preconditions(
+            "stat",
+            &path,
+            Some(data),
+            &args.if_match,
+            &None,
+            Kind::ObjectPreconditionFailed,
+        )?;
 
         let mut meta = Metadata::default();
         meta.set_path(&path)
             .set_mode(ObjectMode::FILE)
             .set_content_length(data.len() as u64)
+            .set_etag(&etag(data))
             .set_complete();
 
         Ok(meta)
     }
-    #[trace("delete")]
+    #[cfg_attr(feature = "minitrace", trace("delete"))]
     async fn delete(&self, args: &OpDelete) -> Result<()> {
+        validate_path("delete", &args.path)?;
         let path = Backend::normalize_path(&args.path);
 
         let mut map = self.inner.lock().expect("lock poisoned");
+        check_preconditions(
+            "delete",
+            &path,
+            map.get(&path),
+            &args.if_match,
+            &None,
+            Kind::ObjectPreconditionFailed,
+        )?;
         map.remove(&path);
 
         Ok(())
     }
-    #[trace("list")]
+    #[cfg_attr(feature = "minitrace", trace("copy"))]
+    async fn copy(&self, args: &OpCopy) -> Result<Metadata> {
+        validate_path("copy", &args.src)?;
+        let src = Backend::normalize_path(&args.src);
+        validate_path("copy", &args.dst)?;
+        let dst = Backend::normalize_path(&args.dst);
+
+        let mut map = self.inner.lock().expect("lock poisoned");
+        // Bytes are reference counted, the content is not copied.
+        let bs = map.get(&src).cloned().ok_or_else(|| Error::Object {
+            kind: Kind::ObjectNotExist,
+            op: "copy",
+            path: src.to_string(),
+            context: HashMap::new(),
+            source: anyhow!("key not exists in map"),
+        })?;
+        let etag = etag(&bs);
+        let size = bs.len() as u64;
+        map.insert(dst.to_string(), bs);
+
+        let mut m = Metadata::default();
+        m.set_path(&args.dst)
+            .set_mode(ObjectMode::FILE)
+            .set_content_length(size)
+            .set_etag(&etag);
+        Ok(m)
+    }
+    #[cfg_attr(feature = "minitrace", trace("rename"))]
+    async fn rename(&self, args: &OpRename) -> Result<Metadata> {
+        validate_path("rename", &args.src)?;
+        let src = Backend::normalize_path(&args.src);
+        validate_path("rename", &args.dst)?;
+        let dst = Backend::normalize_path(&args.dst);
+
+        let mut map = self.inner.lock().expect("lock poisoned");
+        let bs = map.remove(&src).ok_or_else(|| Error::Object {
+            kind: Kind::ObjectNotExist,
+            op: "rename",
+            path: src.to_string(),
+            context: HashMap::new(),
+            source: anyhow!("key not exists in map"),
+        })?;
+        let etag = etag(&bs);
+        let size = bs.len() as u64;
+        map.insert(dst.to_string(), bs);
+
+        let mut m = Metadata::default();
+        m.set_path(&args.dst)
+            .set_mode(ObjectMode::FILE)
+            .set_content_length(size)
+            .set_etag(&etag);
+        Ok(m)
+    }
+    #[cfg_attr(feature = "minitrace", trace("list"))]
     async fn list(&self, args: &OpList) -> Result<BoxedObjectStream> {
+        validate_path("list", &args.path)?;
         let path = Backend::normalize_path(&args.path);
+        if args.versions {
+            return Err(Error::Object {
+                kind: Kind::Unsupported,
+                op: "list",
+                path: path.to_string(),
+                context: HashMap::new(),
+                source: anyhow!("memory doesn't support versioning"),
+            });
+        }
 
         let map = self.inner.lock().expect("lock poisoned");
 
-        let paths = map
-            .iter()
-            .map(|(k, _)| k.clone())
-            .filter(|k| k.starts_with(&path))
+        let mut paths = map
+            .keys()
+            // The marker of the listing dir itself is not its child.
+            .filter(|k| k.starts_with(&path) && **k != path)
+            .filter(|k| match &args.start_after {
+                Some(start_after) => k.as_str() > start_after.as_str(),
+                None => true,
+            })
+            .cloned()
             .collect::<Vec<String>>();
+        // All paths are in memory already, it's cheap to sort them.
+        if args.ordered {
+            paths.sort();
+        }
 
-        Ok(Box::new(EntryStream {
+        let s = EntryStream {
             backend: self.clone(),
             paths,
             idx: 0,
-        }))
+        };
+
+        Ok(Box::new(LimitedObjectStream::new(
+            filter_by_pattern(Box::new(s), args.pattern.as_deref()),
+            &args.path,
+            args.max_results,
+            self.list_scan_limit,
+        )))
+    }
+
+    fn metadata(&self) -> AccessorMetadata {
+        let mut am = AccessorMetadata::default();
+        am.set_scheme(Scheme::Memory)
+            .set_root("/")
+            .set_list(true)
+            .set_copy(true)
+            .set_conditional_write(true);
+        // Keys are matched by prefix, so list is always recursive.
+        am.set_recursive_list(true);
+        am
     }
 }
 
@@ -220,33 +507,41 @@ struct EntryStream {
 impl futures::Stream for EntryStream {
     type Item = Result<Object>;
 
-    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        if self.idx >= self.paths.len() {
-            return Poll::Ready(None);
-        }
-
-        let idx = self.idx;
-        self.idx += 1;
-
-        let path = self.paths.get(idx).expect("path must valid");
+    fn poll_next(mut self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            if self.idx >= self.paths.len() {
+                return Poll::Ready(None);
+            }
 
-        let backend = self.backend.clone();
-        let map = backend.inner.lock().expect("lock poisoned");
+            let idx = self.idx;
+            self.idx += 1;
+
+            let path = self.paths.get(idx).expect("path must valid");
+
+            let backend = self.backend.clone();
+            let map = backend.inner.lock().expect("lock poisoned");
+
+            // If the path is not get, we can skip it safely.
+            let bs = match map.get(path) {
+                Some(bs) => bs,
+                None => continue,
+            };
+
+            let mut o = Object::new(Arc::new(self.backend.clone()), path);
+            let meta = o.metadata_mut();
+            // Keys end with `/` are dir markers created by `create`.
+            let mode = if path.ends_with('/') {
+                ObjectMode::DIR
+            } else {
+                ObjectMode::FILE
+            };
+            meta.set_path(path)
+                .set_mode(mode)
+                .set_content_length(bs.len() as u64)
+                .set_etag(&etag(bs))
+                .set_complete();
 
-        let data = map.get(path);
-        // If the path is not get, we can skip it safely.
-        if data.is_none() {
-            return self.poll_next(cx);
+            return Poll::Ready(Some(Ok(o)));
         }
-        let bs = data.expect("object must exist");
-
-        let mut o = Object::new(Arc::new(self.backend.clone()), path);
-        let meta = o.metadata_mut();
-        meta.set_path(path)
-            .set_mode(ObjectMode::FILE)
-            .set_content_length(bs.len() as u64)
-            .set_complete();
-
-        Poll::Ready(Some(Ok(o)))
     }
 }
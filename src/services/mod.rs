@@ -21,6 +21,7 @@
 
 pub mod fs;
 pub mod memory;
+pub mod static_files;
 
 pub mod azblob;
 pub mod s3;
@@ -12,20 +12,32 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::cmp::max;
 use std::cmp::min;
 use std::collections::HashMap;
 use std::fmt::Debug;
 use std::fmt::Formatter;
+use std::io::Write;
 use std::mem;
-use std::str::FromStr;
 use std::sync::Arc;
+use std::time::Duration;
+use std::time::SystemTime;
 
 use anyhow::anyhow;
+use arc_swap::ArcSwap;
 use async_trait::async_trait;
+use bytes::Buf;
 use bytes::BufMut;
+use bytes::Bytes;
+use bytes::BytesMut;
+use flate2::write::MultiGzDecoder;
+use futures::AsyncReadExt;
+use futures::StreamExt;
 use futures::TryStreamExt;
 use http::header::HeaderName;
+use http::HeaderMap;
 use http::HeaderValue;
+use http::Method;
 use http::Response;
 use http::StatusCode;
 use hyper::body::HttpBody;
@@ -35,30 +47,88 @@ use log::error;
 use log::info;
 use log::warn;
 use metrics::increment_counter;
+#[cfg(feature = "minitrace")]
 use minitrace::trace;
 use once_cell::sync::Lazy;
+use percent_encoding::utf8_percent_encode;
+use percent_encoding::AsciiSet;
+use percent_encoding::NON_ALPHANUMERIC;
+use quick_xml::de;
 use reqsign::services::aws::v4::Signer;
-use time::format_description::well_known::Rfc2822;
+use serde::Deserialize;
 use time::OffsetDateTime;
 
+use super::inventory::inventory_stream;
+use super::inventory::InventoryManifest;
+use super::multipart::ListMultipartUploadsOutput;
+use super::multipart::MultipartUpload;
 use super::object_stream::S3ObjectStream;
+use super::presign::presign_v4;
+use super::presign::MAX_PRESIGN_EXPIRE;
+use super::public_dataset::PublicDataset;
+use crate::accessor::batch_each;
+use crate::config::ConfigMap;
 use crate::credential::Credential;
+use crate::error::share_error;
 use crate::error::Error;
 use crate::error::Kind;
 use crate::error::Result;
+use crate::http_util::format_http_date;
+use crate::http_util::header_str;
+use crate::http_util::insert_client_info;
+use crate::http_util::is_gzip_encoded;
+use crate::http_util::lenient;
+use crate::http_util::parse_content_length;
+use crate::http_util::parse_content_range_start;
+use crate::http_util::parse_content_range_total;
+use crate::http_util::parse_datetime;
+use crate::http_util::parse_header_datetime;
+use crate::http_util::parse_header_str;
+use crate::http_util::parse_header_u64;
+use crate::http_util::parse_value_str;
+use crate::http_util::user_agent;
+use crate::http_util::HttpClient;
+use crate::http_util::Recorder;
+use crate::http_util::ReplayClient;
 use crate::io::BytesStream;
+use crate::io::HttpBodyStream;
+use crate::layers::ImmutableLayer;
+use crate::object::complete_object_stream;
 use crate::object::BoxedObjectStream;
+use crate::object::LimitedObjectStream;
 use crate::object::Metadata;
-use crate::ops::HeaderRange;
+use crate::object::LIST_STAT_CONCURRENCY;
+use crate::ops::BatchOperation;
+use crate::ops::BatchResult;
+use crate::ops::BytesRange;
+use crate::ops::ListMetadata;
+use crate::ops::ObjectPart;
+use crate::ops::OpAbortMultipart;
+use crate::ops::OpBatch;
+use crate::ops::OpCompleteMultipart;
+use crate::ops::OpCopy;
+use crate::ops::OpCreateMultipart;
 use crate::ops::OpDelete;
 use crate::ops::OpList;
+use crate::ops::OpPresign;
 use crate::ops::OpRead;
+use crate::ops::OpScan;
 use crate::ops::OpStat;
 use crate::ops::OpWrite;
+use crate::ops::OpWriteMultipart;
+use crate::ops::PresignedRequest;
+use crate::ops::ResponseOverrides;
+use crate::path::out_of_root;
+use crate::path::validate_path;
 use crate::readers::ReaderStream;
+use crate::shutdown::spawn_cleanup;
+use crate::uri::BackendUri;
 use crate::Accessor;
+use crate::AccessorMetadata;
 use crate::BoxedAsyncReader;
+use crate::Layer;
 use crate::ObjectMode;
+use crate::Scheme;
 
 /// Allow constructing correct region endpoint if user gives a global endpoint.
 static ENDPOINT_TEMPLATES: Lazy<HashMap<&'static str, &'static str>> = Lazy::new(|| {
@@ -71,6 +141,69 @@ static ENDPOINT_TEMPLATES: Lazy<HashMap<&'static str, &'static str>> = Lazy::new
     m
 });
 
+/// The min part size allowed by s3, except the last part.
+const MIN_MULTIPART_PART_SIZE: u64 = 5 * 1024 * 1024;
+/// The max parts count allowed by s3.
+const MAX_MULTIPART_PARTS: u64 = 10000;
+/// The max part size allowed by s3.
+const MAX_MULTIPART_PART_SIZE: u64 = 5 * 1024 * 1024 * 1024;
+/// Objects larger than it can't be copied by a single CopyObject.
+const MAX_COPY_OBJECT_SIZE: u64 = 5 * 1024 * 1024 * 1024;
+/// Use the same defaults as aws cli.
+const DEFAULT_MULTIPART_THRESHOLD: u64 = 8 * 1024 * 1024;
+const DEFAULT_MULTIPART_PART_SIZE: u64 = 8 * 1024 * 1024;
+/// Parallel reads are disabled by default.
+const DEFAULT_READ_CONCURRENCY: usize = 1;
+const DEFAULT_READ_CHUNK_SIZE: u64 = 16 * 1024 * 1024;
+const DEFAULT_READ_RESUME_RETRIES: usize = 3;
+/// The max keys of one list page allowed by s3.
+const DEFAULT_LIST_MAX_PAGE_SIZE: usize = 1000;
+/// The max keys of one DeleteObjects request allowed by s3.
+const MAX_DELETE_OBJECTS: usize = 1000;
+
+/// The max tags count allowed by s3 on one object.
+const MAX_TAGS: usize = 10;
+
+/// Canned ACLs documented by s3.
+const CANNED_ACLS: &[&str] = &[
+    "private",
+    "public-read",
+    "public-read-write",
+    "aws-exec-read",
+    "authenticated-read",
+    "bucket-owner-read",
+    "bucket-owner-full-control",
+    "log-delivery-write",
+];
+
+/// Error codes returned by s3 while the credential has expired.
+const CREDENTIAL_EXPIRED_CODES: &[&str] = &["ExpiredToken", "InvalidToken", "TokenRefreshRequired"];
+/// Max times that a read request will be retried after refreshing credential.
+const CREDENTIAL_REFRESH_RETRIES: usize = 2;
+
+/// Storage classes documented by s3.
+const STORAGE_CLASSES: &[&str] = &[
+    "STANDARD",
+    "REDUCED_REDUNDANCY",
+    "STANDARD_IA",
+    "ONEZONE_IA",
+    "INTELLIGENT_TIERING",
+    "GLACIER",
+    "GLACIER_IR",
+    "DEEP_ARCHIVE",
+    "OUTPOSTS",
+];
+
+/// Chars that need to be encoded in query values, keeps the unreserved chars.
+pub(crate) const QUERY_ENCODE_SET: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+/// Chars that need to be encoded in the path of object urls, keeps `/` too.
+const PATH_ENCODE_SET: &AsciiSet = &QUERY_ENCODE_SET.remove(b'/');
+
 mod constants {
     pub const X_AMZ_SERVER_SIDE_ENCRYPTION: &str = "x-amz-server-side-encryption";
     pub const X_AMZ_SERVER_SIDE_ENCRYPTION_CUSTOMER_ALGORITHM: &str =
@@ -81,6 +214,23 @@ mod constants {
         "x-amz-server-side-encryption-customer-key-md5";
     pub const X_AMZ_SERVER_SIDE_ENCRYPTION_AWS_KMS_KEY_ID: &str =
         "x-amz-server-side-encryption-aws-kms-key-id";
+    pub const X_AMZ_REQUEST_PAYER: &str = "x-amz-request-payer";
+    pub const X_AMZ_STORAGE_CLASS: &str = "x-amz-storage-class";
+    pub const X_AMZ_TAGGING: &str = "x-amz-tagging";
+    pub const X_AMZ_VERSION_ID: &str = "x-amz-version-id";
+    pub const X_AMZ_DELETE_MARKER: &str = "x-amz-delete-marker";
+    pub const X_AMZ_ACL: &str = "x-amz-acl";
+    pub const X_AMZ_META_PREFIX: &str = "x-amz-meta-";
+    pub const X_AMZ_COPY_SOURCE: &str = "x-amz-copy-source";
+    pub const X_AMZ_COPY_SOURCE_RANGE: &str = "x-amz-copy-source-range";
+    pub const X_AMZ_COPY_SOURCE_SERVER_SIDE_ENCRYPTION_CUSTOMER_ALGORITHM: &str =
+        "x-amz-copy-source-server-side-encryption-customer-algorithm";
+    pub const X_AMZ_COPY_SOURCE_SERVER_SIDE_ENCRYPTION_CUSTOMER_KEY: &str =
+        "x-amz-copy-source-server-side-encryption-customer-key";
+    pub const X_AMZ_COPY_SOURCE_SERVER_SIDE_ENCRYPTION_CUSTOMER_KEY_MD5: &str =
+        "x-amz-copy-source-server-side-encryption-customer-key-md5";
+    pub const X_AMZ_METADATA_DIRECTIVE: &str = "x-amz-metadata-directive";
+    pub const CONTENT_MD5: &str = "content-md5";
 }
 
 /// Builder for s3 services
@@ -120,12 +270,38 @@ pub struct Builder {
     bucket: String,
     credential: Option<Credential>,
     endpoint: Option<String>,
+    read_endpoint: Option<String>,
+    read_region: Option<String>,
     region: Option<String>,
     server_side_encryption: Option<String>,
     server_side_encryption_aws_kms_key_id: Option<String>,
     server_side_encryption_customer_algorithm: Option<String>,
     server_side_encryption_customer_key: Option<String>,
     server_side_encryption_customer_key_md5: Option<String>,
+
+    list_scan_limit: Option<u64>,
+    list_max_page_size: Option<usize>,
+    list_objects_v1: bool,
+    multipart_threshold: Option<u64>,
+    multipart_part_size: Option<u64>,
+    read_concurrency: Option<usize>,
+    read_chunk_size: Option<u64>,
+    read_resume_retries: Option<usize>,
+    read_decompress: bool,
+
+    anonymous: bool,
+    requester_pays: bool,
+    read_only: bool,
+    storage_class: Option<String>,
+    default_acl: Option<String>,
+    write_checksum: bool,
+    user_agent: Option<String>,
+
+    http_recorder: Option<Recorder>,
+    http_replay: Option<ReplayClient>,
+
+    #[cfg(test)]
+    credential_loader: Option<CredentialLoader>,
 }
 
 impl Debug for Builder {
@@ -136,7 +312,27 @@ impl Debug for Builder {
             .field("bucket", &self.bucket)
             .field("credential", &self.credential)
             .field("endpoint", &self.endpoint)
-            .field("region", &self.region);
+            .field("read_endpoint", &self.read_endpoint)
+            .field("read_region", &self.read_region)
+            .field("region", &self.region)
+            .field("list_scan_limit", &self.list_scan_limit)
+            .field("list_max_page_size", &self.list_max_page_size)
+            .field("list_objects_v1", &self.list_objects_v1)
+            .field("multipart_threshold", &self.multipart_threshold)
+            .field("multipart_part_size", &self.multipart_part_size)
+            .field("read_concurrency", &self.read_concurrency)
+            .field("read_chunk_size", &self.read_chunk_size)
+            .field("read_resume_retries", &self.read_resume_retries)
+            .field("read_decompress", &self.read_decompress)
+            .field("anonymous", &self.anonymous)
+            .field("requester_pays", &self.requester_pays)
+            .field("read_only", &self.read_only)
+            .field("storage_class", &self.storage_class)
+            .field("default_acl", &self.default_acl)
+            .field("write_checksum", &self.write_checksum)
+            .field("user_agent", &self.user_agent)
+            .field("http_recorder", &self.http_recorder)
+            .field("http_replay", &self.http_replay.is_some());
 
         if self.server_side_encryption.is_some() {
             d.field("server_side_encryption", &"<redacted>");
@@ -186,6 +382,18 @@ impl Builder {
         self
     }
 
+    /// Load credential via `loader` every time signers are built, instead
+    /// of reading it from env, so that tests can rotate credentials.
+    #[cfg(test)]
+    pub(crate) fn credential_loader(
+        &mut self,
+        loader: impl Fn() -> Credential + Send + Sync + 'static,
+    ) -> &mut Self {
+        self.credential_loader = Some(CredentialLoader(Arc::new(loader)));
+
+        self
+    }
+
     /// Set endpoint of this backend.
     ///
     /// Endpoint must be full uri, e.g.
@@ -207,6 +415,39 @@ impl Builder {
         self
     }
 
+    /// Set read endpoint of this backend.
+    ///
+    /// If set, `read`, `stat` and `list` will be sent to this endpoint while
+    /// `write` and `delete` are still sent to `endpoint`. It's useful for
+    /// topologies like writing to an internal endpoint and reading via CDN.
+    ///
+    /// Read endpoint is signed with [`Builder::read_region`], which is the
+    /// same as the region of `endpoint` by default, and will not take part
+    /// in region detection.
+    pub fn read_endpoint(&mut self, endpoint: &str) -> &mut Self {
+        self.read_endpoint = if endpoint.is_empty() {
+            None
+        } else {
+            Some(endpoint.to_string())
+        };
+
+        self
+    }
+
+    /// Set the signing region of the read endpoint, like a replica bucket
+    /// in another region.
+    ///
+    /// Default to the region of `endpoint`.
+    pub fn read_region(&mut self, region: &str) -> &mut Self {
+        self.read_region = if region.is_empty() {
+            None
+        } else {
+            Some(region.to_string())
+        };
+
+        self
+    }
+
     /// Region represent the signing region of this endpoint.
     ///
     /// - If region is set, we will take user's input first.
@@ -245,13 +486,13 @@ impl Builder {
     /// Set server_side_encryption_aws_kms_key_id for this backend
     ///
     /// - If `server_side_encryption` set to `aws:kms`, and `server_side_encryption_aws_kms_key_id`
-    /// is not set, S3 will use aws managed kms key to encrypt data.
+    ///   is not set, S3 will use aws managed kms key to encrypt data.
     /// - If `server_side_encryption` set to `aws:kms`, and `server_side_encryption_aws_kms_key_id`
-    /// is a valid kms key id, S3 will use the provided kms key to encrypt data.
+    ///   is a valid kms key id, S3 will use the provided kms key to encrypt data.
     /// - If the `server_side_encryption_aws_kms_key_id` is invalid or not found, an error will be
-    /// returned.
+    ///   returned.
     /// - If `server_side_encryption` is not `aws:kms`, setting `server_side_encryption_aws_kms_key_id`
-    /// is a noop.
+    ///   is a noop.
     ///
     /// # Note
     ///
@@ -381,13 +622,264 @@ impl Builder {
         self
     }
 
+    /// Set the max entries that a `list` without `OpList::max_results` could yield.
+    ///
+    /// Listing will end with a `Kind::ScanLimitExceeded` error once exceeded.
+    pub fn list_scan_limit(&mut self, limit: u64) -> &mut Self {
+        self.list_scan_limit = Some(limit);
+        self
+    }
+
+    /// Set the max keys of one list page, larger `OpList::page_size` will be
+    /// clamped to it.
+    ///
+    /// S3 returns at most 1000 keys per page, increase it only for s3
+    /// compatible services that allow more.
+    ///
+    /// Default to 1000.
+    pub fn list_max_page_size(&mut self, size: usize) -> &mut Self {
+        self.list_max_page_size = Some(size);
+        self
+    }
+
+    /// List by ListObjects (v1) instead of ListObjectsV2, for s3 compatible
+    /// services that don't implement `list-type=2`.
+    ///
+    /// Pages are continued by `marker`, and `OpList::start_after` is sent as
+    /// the marker of the first page. Listing versions is not affected.
+    ///
+    /// Default to false.
+    pub fn enable_list_objects_v1(&mut self, enabled: bool) -> &mut Self {
+        self.list_objects_v1 = enabled;
+        self
+    }
+
+    /// Set the size above which writes will be uploaded by multipart upload.
+    ///
+    /// Default to 8 MiB.
+    pub fn multipart_threshold(&mut self, size: u64) -> &mut Self {
+        self.multipart_threshold = Some(size);
+        self
+    }
+
+    /// Set the part size of multipart upload, must be at least 5 MiB.
+    ///
+    /// Part size will be enlarged if the object can't fit in 10000 parts.
+    ///
+    /// Default to 8 MiB.
+    pub fn multipart_part_size(&mut self, size: u64) -> &mut Self {
+        self.multipart_part_size = Some(size);
+        self
+    }
+
+    /// Set the max count of concurrent ranged requests of a read.
+    ///
+    /// Reads larger than `read_chunk_size` will be split into chunks, which
+    /// are fetched in parallel and reassembled in order. Up to
+    /// `read_concurrency * read_chunk_size` bytes will be buffered in memory
+    /// for every read. Reads fitting in a single chunk are sent as usual.
+    ///
+    /// Default to 1, which disables parallel reads.
+    pub fn read_concurrency(&mut self, concurrency: usize) -> &mut Self {
+        self.read_concurrency = Some(concurrency);
+        self
+    }
+
+    /// Set the chunk size of parallel reads, see `read_concurrency`.
+    ///
+    /// Default to 16 MiB.
+    pub fn read_chunk_size(&mut self, size: u64) -> &mut Self {
+        self.read_chunk_size = Some(size);
+        self
+    }
+
+    /// Set the max times that a read will be resumed after its body stream
+    /// has been interrupted, like connection reset in the middle of the
+    /// transfer.
+    ///
+    /// Reads are resumed by ranged requests from where they broke, with
+    /// `If-Match` of the object's ETag. Reads fail with `Kind::ObjectChanged`
+    /// if the object has been replaced since. Reads without ETag or version
+    /// will not be resumed.
+    ///
+    /// Default to 3, set to 0 to disable resuming.
+    pub fn read_resume_retries(&mut self, retries: usize) -> &mut Self {
+        self.read_resume_retries = Some(retries);
+        self
+    }
+
+    /// Decode the body of reads by its `Content-Encoding`, like objects
+    /// uploaded with `Content-Encoding: gzip`.
+    ///
+    /// Only reads of the whole object are decoded, ranged reads return the
+    /// encoded bytes as they are. Objects without a supported encoding
+    /// (only `gzip` for now) are not touched.
+    ///
+    /// The decoded length is unknown before the read finished, so the
+    /// metadata returned by decoded reads doesn't carry the content length,
+    /// see [`Metadata::try_content_length`].
+    ///
+    /// Default to false.
+    pub fn enable_read_decompress(&mut self, enabled: bool) -> &mut Self {
+        self.read_decompress = enabled;
+        self
+    }
+
+    /// Set the storage class of all writes from this backend, like
+    /// `STANDARD_IA`, `INTELLIGENT_TIERING` or `GLACIER`.
+    ///
+    /// Writes with [`OpWrite::storage_class`] set will override it.
+    pub fn storage_class(&mut self, storage_class: &str) -> &mut Self {
+        self.storage_class = if storage_class.is_empty() {
+            None
+        } else {
+            Some(storage_class.to_string())
+        };
+
+        self
+    }
+
+    /// Set the canned ACL of all writes from this backend, like
+    /// `bucket-owner-full-control`.
+    pub fn default_acl(&mut self, acl: &str) -> &mut Self {
+        self.default_acl = if acl.is_empty() {
+            None
+        } else {
+            Some(acl.to_string())
+        };
+
+        self
+    }
+
+    /// Send `Content-MD5` with every write so that s3 can verify the content.
+    ///
+    /// # Note
+    ///
+    /// The whole body of a single PUT write (smaller than `multipart_threshold`)
+    /// will be buffered in memory to calculate the digest. Parts of multipart
+    /// upload are buffered already, so no extra memory is required.
+    ///
+    /// Writes with mismatched content will fail with `Kind::ObjectChecksumMismatch`.
+    pub fn enable_write_checksum(&mut self, enabled: bool) -> &mut Self {
+        self.write_checksum = enabled;
+        self
+    }
+
+    /// Append an application identifier to the `User-Agent` of all
+    /// requests, like `opendal/0.3.0 my-app/1.2`.
+    ///
+    /// Default to `opendal/{version}`. Use [`RequestIdLayer`][crate::layers::RequestIdLayer]
+    /// to send an id per operation as the `x-opendal-request-id` header.
+    pub fn user_agent(&mut self, suffix: &str) -> &mut Self {
+        self.user_agent = if suffix.is_empty() {
+            None
+        } else {
+            Some(suffix.to_string())
+        };
+
+        self
+    }
+
+    /// Record all requests and responses of this backend via [`Recorder`].
+    ///
+    /// Signatures, tokens and SSE keys will be redacted from the records.
+    pub fn http_recorder(&mut self, recorder: Recorder) -> &mut Self {
+        self.http_recorder = Some(recorder);
+        self
+    }
+
+    /// Serve all requests from a recorded session instead of the network.
+    pub fn http_replay(&mut self, replay: ReplayClient) -> &mut Self {
+        self.http_replay = Some(replay);
+        self
+    }
+
+    /// Create a read-only builder for a public dataset.
+    ///
+    /// `dataset` could be a built-in dataset name like `sentinel-2`, or an
+    /// `http(s)://` url to a manifest. Read [`PublicDataset`] for the format.
+    ///
+    /// Endpoint, region, anonymous access and requester pays will be applied
+    /// from the manifest, and all write operations will be denied.
+    pub async fn from_public_dataset(dataset: &str) -> Result<Builder> {
+        let manifest = PublicDataset::load(dataset).await?;
+        debug!("backend use public dataset manifest: {:?}", &manifest);
+
+        let mut builder = Builder::default();
+        builder.bucket(&manifest.bucket);
+        builder.region(&manifest.region);
+        if let Some(endpoint) = &manifest.endpoint {
+            builder.endpoint(endpoint);
+        }
+        if let Some(root) = &manifest.root {
+            builder.root(root);
+        }
+        builder.anonymous = manifest.anonymous;
+        builder.requester_pays = manifest.requester_pays;
+        builder.read_only = true;
+
+        Ok(builder)
+    }
+
+    /// Create a builder from options in key-value form, like the ones
+    /// loaded from a config file.
+    ///
+    /// - `bucket`: required, read [`Builder::bucket`].
+    /// - `root`: read [`Builder::root`].
+    /// - `endpoint`, `region`: read [`Builder::endpoint`] and [`Builder::region`].
+    ///   If both are set, requests are sent to the endpoint and signed with
+    ///   the region, and region detection is skipped.
+    /// - `access_key_id`, `secret_access_key`: static credential, must be set together.
+    /// - `anonymous`: `true` to send requests without signing.
+    /// - `requester_pays`: `true` to access requester pays buckets.
+    ///
+    /// Empty values are treated as unset, and other options will be rejected
+    /// with `Kind::BackendConfigurationInvalid`.
+    pub fn from_map(map: HashMap<String, String>) -> Result<Builder> {
+        let mut map = ConfigMap::new(Scheme::S3, map);
+
+        let mut builder = Builder::default();
+        builder.bucket(&map.take_required("bucket")?);
+        if let Some(v) = map.take("root") {
+            builder.root(&v);
+        }
+        if let Some(v) = map.take("endpoint") {
+            builder.endpoint(&v);
+        }
+        if let Some(v) = map.take("region") {
+            builder.region(&v);
+        }
+        if let Some((ak, sk)) = map.take_pair("access_key_id", "secret_access_key")? {
+            builder.credential(Credential::hmac(&ak, &sk));
+        }
+        if let Some(v) = map.take_bool("anonymous")? {
+            builder.anonymous = v;
+        }
+        if let Some(v) = map.take_bool("requester_pays")? {
+            builder.requester_pays = v;
+        }
+        map.finish()?;
+
+        Ok(builder)
+    }
+
+    /// Create a builder from a uri like `s3://bucket/path/to/root?region=us-east-2`.
+    ///
+    /// The host is the bucket and the path is the root, both percent decoded.
+    /// Query parameters are other options of [`Builder::from_map`].
+    pub fn from_uri(uri: &str) -> Result<Builder> {
+        Builder::from_map(BackendUri::parse(uri)?.into_map(
+            Scheme::S3,
+            Some("bucket"),
+            Some("root"),
+        )?)
+    }
+
     // Read RFC-0057: Auto Region for detailed behavior.
     async fn detect_region(
         &self,
-        client: &hyper::Client<
-            hyper_tls::HttpsConnector<hyper::client::HttpConnector>,
-            hyper::Body,
-        >,
+        client: &HttpClient,
+        user_agent: &HeaderValue,
         bucket: &str,
         context: &HashMap<String, String>,
     ) -> Result<(String, String)> {
@@ -405,13 +897,14 @@ impl Builder {
             };
         }
 
-        let req = hyper::Request::head(format!("{endpoint}/{bucket}"))
+        let mut req = hyper::Request::head(format!("{endpoint}/{bucket}"))
             .body(hyper::Body::empty())
             .expect("must be valid request");
+        insert_client_info(&mut req, user_agent);
         let res = client.request(req).await.map_err(|e| Error::Backend {
             kind: Kind::BackendConfigurationInvalid,
             context: context.clone(),
-            source: anyhow::Error::new(e),
+            source: e,
         })?;
 
         debug!(
@@ -419,68 +912,74 @@ impl Builder {
             res.status(),
             res.headers()
         );
+        let region = parse_header_str(
+            "detect_region",
+            bucket,
+            res.headers(),
+            "x-amz-bucket-region",
+        )
+        .map_err(|e| Error::Backend {
+            kind: Kind::BackendConfigurationInvalid,
+            context: context.clone(),
+            source: anyhow::Error::new(e),
+        })?;
         match res.status() {
             // The endpoint works, return with not changed endpoint and
             // default region.
             StatusCode::OK | StatusCode::FORBIDDEN => {
-                let region = res
-                    .headers()
-                    .get("x-amz-bucket-region")
-                    .unwrap_or(&HeaderValue::from_static("us-east-1"))
-                    .to_str()
-                    .map_err(|e| Error::Backend {
-                        kind: Kind::BackendConfigurationInvalid,
-                        context: context.clone(),
-                        source: anyhow::Error::new(e),
-                    })?
-                    .to_string();
-                Ok((endpoint.to_string(), region))
+                let region = region.unwrap_or("us-east-1");
+                Ok((endpoint.to_string(), region.to_string()))
             }
             // The endpoint should move, return with constructed endpoint
             StatusCode::MOVED_PERMANENTLY => {
-                let region = res
-                    .headers()
-                    .get("x-amz-bucket-region")
-                    .ok_or(Error::Backend {
+                let region = region
+                    .ok_or_else(|| Error::Backend {
                         kind: Kind::BackendConfigurationInvalid,
                         context: context.clone(),
                         source: anyhow!("can't detect region automatically, region is empty"),
                     })?
-                    .to_str()
-                    .map_err(|e| Error::Backend {
+                    .to_string();
+                let template = ENDPOINT_TEMPLATES
+                    .get(endpoint)
+                    .ok_or_else(|| Error::Backend {
                         kind: Kind::BackendConfigurationInvalid,
                         context: context.clone(),
-                        source: anyhow::Error::new(e),
-                    })?
-                    .to_string();
-                let template = ENDPOINT_TEMPLATES.get(endpoint).ok_or(Error::Backend {
-                    kind: Kind::BackendConfigurationInvalid,
-                    context: context.clone(),
-                    source: anyhow!(
-                        "can't detect region automatically, no valid endpoint template for {}",
-                        &endpoint
-                    ),
-                })?;
+                        source: anyhow!(
+                            "can't detect region automatically, no valid endpoint template for {}",
+                            &endpoint
+                        ),
+                    })?;
 
                 let endpoint = template.replace("{region}", &region);
 
                 Ok((endpoint, region))
             }
             // Unexpected status code
-            code => {
-                return Err(Error::Backend {
-                    kind: Kind::BackendConfigurationInvalid,
-                    context: context.clone(),
-                    source: anyhow!(
-                        "can't detect region automatically, unexpected response: status code {}",
-                        code
-                    ),
-                });
-            }
+            code => Err(Error::Backend {
+                kind: Kind::BackendConfigurationInvalid,
+                context: context.clone(),
+                source: anyhow!(
+                    "can't detect region automatically, unexpected response: status code {}",
+                    code
+                ),
+            }),
         }
     }
 
     pub async fn finish(&mut self) -> Result<Arc<dyn Accessor>> {
+        let backend: Arc<dyn Accessor> = Arc::new(self.build_backend().await?);
+
+        if self.read_only {
+            return Ok(ImmutableLayer.layer(backend));
+        }
+        Ok(backend)
+    }
+
+    /// Build the s3 [`Backend`] itself, which is needed by s3 specific APIs
+    /// like [`Backend::read_inventory`].
+    ///
+    /// `read_only` is not applied here since it's a layer over the backend.
+    pub async fn build_backend(&mut self) -> Result<Backend> {
         info!("backend build started: {:?}", &self);
 
         let root = match &self.root {
@@ -514,28 +1013,49 @@ impl Builder {
         let mut context: HashMap<String, String> =
             HashMap::from([("bucket".to_string(), bucket.to_string())]);
 
-        let client = hyper::Client::builder().build(hyper_tls::HttpsConnector::new());
+        let user_agent = user_agent(self.user_agent.as_deref()).ok_or_else(|| Error::Backend {
+            kind: Kind::BackendConfigurationInvalid,
+            context: HashMap::from([(
+                "user_agent".to_string(),
+                self.user_agent.clone().unwrap_or_default(),
+            )]),
+            source: anyhow!("user agent is not a valid header value"),
+        })?;
+
+        let mut client = HttpClient::new();
+        if let Some(recorder) = self.http_recorder.take() {
+            client.set_recorder(recorder);
+        }
+        if let Some(replay) = self.http_replay.take() {
+            client.set_replay(replay);
+        }
 
-        let (endpoint, region) = self.detect_region(&client, bucket, &context).await?;
+        let (endpoint, region) = self
+            .detect_region(&client, &user_agent, bucket, &context)
+            .await?;
         context.insert("endpoint".to_string(), endpoint.clone());
         context.insert("region".to_string(), region.clone());
         debug!("backend use endpoint: {}, region: {}", &endpoint, &region);
 
-        let mut signer_builder = reqsign::services::aws::v4::Signer::builder();
-        signer_builder.service("s3");
-        signer_builder.region(&region);
-        signer_builder.allow_anonymous();
+        let read_region = self.read_region.clone().unwrap_or_else(|| region.clone());
+        let read_endpoint = match &self.read_endpoint {
+            None => endpoint.clone(),
+            Some(v) => match ENDPOINT_TEMPLATES.get(v.as_str()) {
+                Some(template) => template.replace("{region}", &read_region),
+                None => v.to_string(),
+            },
+        };
+        context.insert("read_endpoint".to_string(), read_endpoint.clone());
+        context.insert("read_region".to_string(), read_region.clone());
+        debug!(
+            "backend use read endpoint: {}, read region: {}",
+            &read_endpoint, &read_region
+        );
 
         if let Some(cred) = &self.credential {
             context.insert("credential".to_string(), "*".to_string());
             match cred {
-                Credential::HMAC {
-                    access_key_id,
-                    secret_access_key,
-                } => {
-                    signer_builder.access_key(access_key_id);
-                    signer_builder.secret_key(secret_access_key);
-                }
+                Credential::HMAC { .. } => {}
                 // We don't need to do anything if user tries to read credential from env.
                 Credential::Plain => {
                     warn!("backend got empty credential, fallback to read from env.")
@@ -550,30 +1070,158 @@ impl Builder {
             }
         }
 
-        let signer = signer_builder.build().await?;
+        let multipart_part_size = self
+            .multipart_part_size
+            .unwrap_or(DEFAULT_MULTIPART_PART_SIZE);
+        if multipart_part_size < MIN_MULTIPART_PART_SIZE {
+            return Err(Error::Backend {
+                kind: Kind::BackendConfigurationInvalid,
+                context: HashMap::from([(
+                    "multipart_part_size".to_string(),
+                    multipart_part_size.to_string(),
+                )]),
+                source: anyhow!(
+                    "multipart part size must be at least {}",
+                    MIN_MULTIPART_PART_SIZE
+                ),
+            });
+        }
+
+        let list_max_page_size = self
+            .list_max_page_size
+            .unwrap_or(DEFAULT_LIST_MAX_PAGE_SIZE);
+        if list_max_page_size == 0 {
+            return Err(Error::Backend {
+                kind: Kind::BackendConfigurationInvalid,
+                context: HashMap::from([("list_max_page_size".to_string(), "0".to_string())]),
+                source: anyhow!("list max page size must be at least 1"),
+            });
+        }
+
+        let read_concurrency = self.read_concurrency.unwrap_or(DEFAULT_READ_CONCURRENCY);
+        if read_concurrency == 0 {
+            return Err(Error::Backend {
+                kind: Kind::BackendConfigurationInvalid,
+                context: HashMap::from([("read_concurrency".to_string(), "0".to_string())]),
+                source: anyhow!("read concurrency must be at least 1"),
+            });
+        }
+        let read_chunk_size = self.read_chunk_size.unwrap_or(DEFAULT_READ_CHUNK_SIZE);
+        if read_chunk_size == 0 {
+            return Err(Error::Backend {
+                kind: Kind::BackendConfigurationInvalid,
+                context: HashMap::from([("read_chunk_size".to_string(), "0".to_string())]),
+                source: anyhow!("read chunk size must be at least 1"),
+            });
+        }
+
+        if let Some(v) = &self.storage_class {
+            if !STORAGE_CLASSES.contains(&v.as_str()) {
+                return Err(Error::Backend {
+                    kind: Kind::BackendConfigurationInvalid,
+                    context: HashMap::from([("storage_class".to_string(), v.to_string())]),
+                    source: anyhow!("storage class must be one of {:?}", STORAGE_CLASSES),
+                });
+            }
+        }
+
+        if let Some(v) = &self.default_acl {
+            if !CANNED_ACLS.contains(&v.as_str()) {
+                return Err(Error::Backend {
+                    kind: Kind::BackendConfigurationInvalid,
+                    context: HashMap::from([("default_acl".to_string(), v.to_string())]),
+                    source: anyhow!("acl must be one of {:?}", CANNED_ACLS),
+                });
+            }
+        }
+
+        // Parse SSE headers once, requests will clone the ref-counted values.
+        let sse_header = |key: &str, v: Option<String>| -> Result<Option<HeaderValue>> {
+            v.map(|v| {
+                let mut v = HeaderValue::from_str(&v).map_err(|e| Error::Backend {
+                    kind: Kind::BackendConfigurationInvalid,
+                    context: HashMap::from([(key.to_string(), "<redacted>".to_string())]),
+                    source: anyhow!("invalid header value: {:?}", e),
+                })?;
+                v.set_sensitive(true);
+                Ok(v)
+            })
+            .transpose()
+        };
+        let server_side_encryption = sse_header(
+            "server_side_encryption",
+            mem::take(&mut self.server_side_encryption),
+        )?;
+        let server_side_encryption_aws_kms_key_id = sse_header(
+            "server_side_encryption_aws_kms_key_id",
+            mem::take(&mut self.server_side_encryption_aws_kms_key_id),
+        )?;
+        let server_side_encryption_customer_algorithm = sse_header(
+            "server_side_encryption_customer_algorithm",
+            mem::take(&mut self.server_side_encryption_customer_algorithm),
+        )?;
+        let server_side_encryption_customer_key = sse_header(
+            "server_side_encryption_customer_key",
+            mem::take(&mut self.server_side_encryption_customer_key),
+        )?;
+        let server_side_encryption_customer_key_md5 = sse_header(
+            "server_side_encryption_customer_key_md5",
+            mem::take(&mut self.server_side_encryption_customer_key_md5),
+        )?;
+
+        let credential = self.credential.clone();
+        #[cfg(test)]
+        let credential = self
+            .credential_loader
+            .as_ref()
+            .map(|l| (l.0)())
+            .or(credential);
+        let signer = Backend::build_signer(&region, credential.as_ref()).await?;
+        let read_signer = Backend::build_signer(&read_region, credential.as_ref()).await?;
 
         info!("backend build finished: {:?}", &self);
-        Ok(Arc::new(Backend {
+        Ok(Backend {
             root,
             endpoint,
-            signer: Arc::new(signer),
+            signer: Arc::new(ArcSwap::from_pointee(signer)),
+            read_endpoint,
+            read_signer: Arc::new(ArcSwap::from_pointee(read_signer)),
+            read_region,
+            region,
+            credential: self.credential.clone(),
             bucket: self.bucket.clone(),
             client,
 
-            server_side_encryption: mem::take(&mut self.server_side_encryption),
-            server_side_encryption_aws_kms_key_id: mem::take(
-                &mut self.server_side_encryption_aws_kms_key_id,
-            ),
-            server_side_encryption_customer_algorithm: mem::take(
-                &mut self.server_side_encryption_customer_algorithm,
-            ),
-            server_side_encryption_customer_key: mem::take(
-                &mut self.server_side_encryption_customer_key,
-            ),
-            server_side_encryption_customer_key_md5: mem::take(
-                &mut self.server_side_encryption_customer_key_md5,
-            ),
-        }))
+            server_side_encryption,
+            server_side_encryption_aws_kms_key_id,
+            server_side_encryption_customer_algorithm,
+            server_side_encryption_customer_key,
+            server_side_encryption_customer_key_md5,
+
+            list_scan_limit: self.list_scan_limit,
+            list_max_page_size,
+            list_objects_v1: self.list_objects_v1,
+            multipart_threshold: self
+                .multipart_threshold
+                .unwrap_or(DEFAULT_MULTIPART_THRESHOLD),
+            multipart_part_size,
+            read_concurrency,
+            read_chunk_size,
+            read_resume_retries: self
+                .read_resume_retries
+                .unwrap_or(DEFAULT_READ_RESUME_RETRIES),
+            read_decompress: self.read_decompress,
+            storage_class: self.storage_class.clone(),
+            default_acl: self.default_acl.clone(),
+            write_checksum: self.write_checksum,
+            user_agent,
+
+            anonymous: self.anonymous,
+            requester_pays: self.requester_pays,
+
+            #[cfg(test)]
+            credential_loader: self.credential_loader.clone(),
+        })
     }
 }
 
@@ -582,16 +1230,57 @@ impl Builder {
 pub struct Backend {
     bucket: String,
     endpoint: String,
-    signer: Arc<Signer>,
-    client: hyper::Client<hyper_tls::HttpsConnector<hyper::client::HttpConnector>, hyper::Body>,
+    // Signers will be rebuilt while credential expired, see `refresh_credential`.
+    signer: Arc<ArcSwap<Signer>>,
+    // read_endpoint will be the same as endpoint if not set.
+    read_endpoint: String,
+    read_signer: Arc<ArcSwap<Signer>>,
+    // read_region will be the same as region if not set.
+    read_region: String,
+    region: String,
+    credential: Option<Credential>,
+    client: HttpClient,
     // root will be "/" or "/abc/"
     root: String,
 
-    server_side_encryption: Option<String>,
-    server_side_encryption_aws_kms_key_id: Option<String>,
-    server_side_encryption_customer_algorithm: Option<String>,
-    server_side_encryption_customer_key: Option<String>,
-    server_side_encryption_customer_key_md5: Option<String>,
+    // SSE headers are parsed and marked as sensitive while building.
+    server_side_encryption: Option<HeaderValue>,
+    server_side_encryption_aws_kms_key_id: Option<HeaderValue>,
+    server_side_encryption_customer_algorithm: Option<HeaderValue>,
+    server_side_encryption_customer_key: Option<HeaderValue>,
+    server_side_encryption_customer_key_md5: Option<HeaderValue>,
+
+    list_scan_limit: Option<u64>,
+    list_max_page_size: usize,
+    list_objects_v1: bool,
+    multipart_threshold: u64,
+    multipart_part_size: u64,
+    read_concurrency: usize,
+    read_chunk_size: u64,
+    read_resume_retries: usize,
+    read_decompress: bool,
+    storage_class: Option<String>,
+    default_acl: Option<String>,
+    write_checksum: bool,
+    user_agent: HeaderValue,
+
+    anonymous: bool,
+    requester_pays: bool,
+
+    #[cfg(test)]
+    credential_loader: Option<CredentialLoader>,
+}
+
+/// CredentialLoader returns the credential to build signers with.
+#[cfg(test)]
+#[derive(Clone)]
+pub(crate) struct CredentialLoader(Arc<dyn Fn() -> Credential + Send + Sync>);
+
+#[cfg(test)]
+impl Debug for CredentialLoader {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str("CredentialLoader")
+    }
 }
 
 impl Backend {
@@ -599,46 +1288,465 @@ impl Backend {
         Builder::default()
     }
 
-    // normalize_path removes all internal `//` inside path.
-    pub(crate) fn normalize_path(path: &str) -> String {
-        let has_trailing = path.ends_with('/');
-
-        let mut p = path
-            .split('/')
-            .filter(|v| !v.is_empty())
-            .collect::<Vec<&str>>()
-            .join("/");
-
-        if has_trailing && !p.eq("/") {
-            p.push('/')
-        }
+    /// Read the manifest of an S3 Inventory report at `path`, like
+    /// `inventory/source-bucket/config/2022-01-01T01-00Z/manifest.json`.
+    pub async fn read_inventory_manifest(&self, path: &str) -> Result<InventoryManifest> {
+        let op = OpRead {
+            path: path.to_string(),
+            ..Default::default()
+        };
+        let bs: Vec<Bytes> = self.read(&op).await?.try_collect().await?;
 
-        p
+        InventoryManifest::from_slice(&self.get_abs_path("read", path)?, &bs.concat())
     }
 
-    /// get_abs_path will return the absolute path of the given path in the s3 format.
+    /// Read the S3 Inventory report of the manifest at `path`, which yields
+    /// objects just like a recursive listing, but is far cheaper for buckets
+    /// with lots of keys.
     ///
-    /// Read [RFC-112](https://github.com/datafuselabs/opendal/pull/112) for more details.
-    pub(crate) fn get_abs_path(&self, path: &str) -> String {
-        let path = Backend::normalize_path(path);
+    /// - The report must be delivered to the bucket of this backend.
+    /// - Only `CSV` reports are supported, `Kind::Unsupported` will be returned
+    ///   for `ORC` and `Parquet`, whose data files can be read directly by the
+    ///   keys in [`InventoryManifest`].
+    /// - Delete markers and non-current versions are skipped.
+    /// - If the inventory is taken for this bucket, paths are relative to the
+    ///   root and keys outside of it are skipped. Otherwise, paths are the keys
+    ///   of the source bucket, while objects are still bound to this backend.
+    ///
+    /// Data files are verified against the size and MD5 in the manifest while
+    /// streaming, `Kind::ObjectChecksumMismatch` will be returned at the end
+    /// of mismatched files, whose entries may have been partly yielded.
+    pub async fn read_inventory(&self, path: &str) -> Result<BoxedObjectStream> {
+        let manifest = self.read_inventory_manifest(path).await?;
+        debug!(
+            "inventory {} has {} data files of {}",
+            path,
+            manifest.files.len(),
+            manifest.file_format
+        );
+
+        if manifest.destination_bucket_name() != self.bucket {
+            return Err(Error::Object {
+                kind: Kind::Unsupported,
+                op: "read",
+                path: self.get_abs_path("read", path)?,
+                context: HashMap::from([(
+                    "destination_bucket".to_string(),
+                    manifest.destination_bucket.clone(),
+                )]),
+                source: anyhow!(
+                    "inventory is delivered to another bucket than {}",
+                    self.bucket
+                ),
+            });
+        }
+        let prefix = if manifest.source_bucket == self.bucket {
+            Some(self.root.trim_start_matches('/').to_string())
+        } else {
+            None
+        };
+
+        inventory_stream(
+            self.clone(),
+            &self.get_abs_path("read", path)?,
+            manifest,
+            prefix,
+        )
+    }
+
+    /// List in-progress multipart uploads of objects under `path`, which are
+    /// neither completed nor aborted.
+    ///
+    /// Uploads left by crashed writers are billed until they are aborted,
+    /// see [`Backend::abort_stale_multipart_uploads`].
+    pub async fn list_multipart_uploads(&self, path: &str) -> Result<Vec<MultipartUpload>> {
+        let p = self.get_abs_path("list_multipart_uploads", path)?;
+
+        let mut uploads = Vec::new();
+        let (mut key_marker, mut upload_id_marker) = (String::new(), String::new());
+        loop {
+            let resp = self
+                .list_multipart_uploads_page(&p, &key_marker, &upload_id_marker)
+                .await?;
+            if resp.status() != StatusCode::OK {
+                return Err(parse_error_response(resp, "list_multipart_uploads", &p).await);
+            }
+            let bs = hyper::body::to_bytes(resp.into_body())
+                .await
+                .map_err(|e| Error::Object {
+                    kind: Kind::Unexpected,
+                    op: "list_multipart_uploads",
+                    path: p.clone(),
+                    context: HashMap::new(),
+                    source: anyhow!("read body: {:?}", e),
+                })?;
+            let output = ListMultipartUploadsOutput::from_slice(&p, &bs)?;
+
+            for upload in &output.upload {
+                uploads.push(MultipartUpload {
+                    path: self.get_rel_path("list_multipart_uploads", &upload.key)?,
+                    upload_id: upload.upload_id.clone(),
+                    initiated: upload.initiated(),
+                });
+            }
+            if !output.has_more() {
+                break;
+            }
+            key_marker = output.next_key_marker;
+            upload_id_marker = output.next_upload_id_marker;
+        }
+
+        debug!("object {} has {} multipart uploads", &p, uploads.len());
+        Ok(uploads)
+    }
+
+    /// Abort the multipart upload `upload_id` of the object at `path`, and
+    /// free the parts that have been uploaded.
+    pub async fn abort_multipart(&self, path: &str, upload_id: &str) -> Result<()> {
+        let p = self.get_abs_path("abort_multipart", path)?;
+
+        let resp = self.abort_multipart_upload(&p, upload_id).await?;
+        match resp.status() {
+            StatusCode::NO_CONTENT => {
+                debug!("object {} multipart upload {} aborted", &p, upload_id);
+                Ok(())
+            }
+            _ => Err(parse_error_response(resp, "abort_multipart", &p).await),
+        }
+    }
+
+    /// Abort multipart uploads under `path` initiated more than `age` ago,
+    /// returns the aborted uploads.
+    ///
+    /// Uploads without initiated time are kept. Aborting stops at the first
+    /// failure, uploads aborted before it stay aborted.
+    pub async fn abort_stale_multipart_uploads(
+        &self,
+        path: &str,
+        age: Duration,
+    ) -> Result<Vec<MultipartUpload>> {
+        let now = SystemTime::now();
+        let stale = self
+            .list_multipart_uploads(path)
+            .await?
+            .into_iter()
+            .filter(|upload| match upload.initiated {
+                Some(t) => now.duration_since(t).unwrap_or_default() > age,
+                None => false,
+            })
+            .collect::<Vec<_>>();
+
+        for upload in &stale {
+            self.abort_multipart(&upload.path, &upload.upload_id)
+                .await?;
+        }
+        Ok(stale)
+    }
+
+    // normalize_path removes all internal `//` inside path.
+    pub(crate) fn normalize_path(path: &str) -> String {
+        let has_trailing = path.ends_with('/');
+
+        let mut p = path
+            .split('/')
+            .filter(|v| !v.is_empty())
+            .collect::<Vec<&str>>()
+            .join("/");
+
+        if has_trailing && !p.eq("/") {
+            p.push('/')
+        }
+
+        p
+    }
+
+    /// get_abs_path will return the absolute path of the given path in the s3 format.
+    ///
+    /// Read [RFC-112](https://github.com/datafuselabs/opendal/pull/112) for more details.
+    ///
+    /// Paths with `..` segments or NUL bytes are rejected with
+    /// `Kind::ObjectPathInvalid`, so that they can't escape the root.
+    pub(crate) fn get_abs_path(&self, op: &'static str, path: &str) -> Result<String> {
+        validate_path(op, path)?;
+
+        // Same as `normalize_path` with root prepended, but built in one allocation
+        // since it's called by every request.
+        //
         // root must be normalized like `/abc/`
-        format!("{}{}", self.root, path)
-            .trim_start_matches('/')
-            .to_string()
+        let mut p = String::with_capacity(self.root.len() + path.len());
+        p.push_str(&self.root);
+        for (idx, seg) in path.split('/').filter(|v| !v.is_empty()).enumerate() {
+            if idx > 0 {
+                p.push('/');
+            }
+            p.push_str(seg);
+        }
+        if path.ends_with('/') {
+            p.push('/');
+        }
+
+        let leading = p.len() - p.trim_start_matches('/').len();
+        p.drain(..leading);
+        Ok(p)
     }
 
     /// get_rel_path will return the relative path of the given path in the s3 format.
-    pub(crate) fn get_rel_path(&self, path: &str) -> String {
+    ///
+    /// Returns `Kind::ObjectOutOfScope` if the path is not under the root.
+    pub(crate) fn get_rel_path(&self, op: &'static str, path: &str) -> Result<String> {
         let path = Backend::normalize_path(path);
         let path = format!("/{}", path);
 
         match path.strip_prefix(&self.root) {
-            Some(v) => v.to_string(),
-            None => unreachable!(
-                "invalid path {} that not start with backend root {}",
-                &path, &self.root
+            Some(v) => Ok(v.to_string()),
+            None => Err(out_of_root(op, &path, &self.root)),
+        }
+    }
+
+    async fn build_signer(region: &str, credential: Option<&Credential>) -> Result<Signer> {
+        let mut signer_builder = reqsign::services::aws::v4::Signer::builder();
+        signer_builder.service("s3");
+        signer_builder.region(region);
+        signer_builder.allow_anonymous();
+
+        if let Some(Credential::HMAC {
+            access_key_id,
+            secret_access_key,
+        }) = credential
+        {
+            signer_builder.access_key(access_key_id);
+            signer_builder.secret_key(secret_access_key);
+        }
+
+        Ok(signer_builder.build().await?)
+    }
+
+    /// refresh_credential will rebuild signers so that credentials will be
+    /// loaded again, for example, rotated STS credentials from env or profile.
+    async fn refresh_credential(&self) -> Result<()> {
+        increment_counter!("opendal_s3_credential_refreshes");
+
+        let credential = self.credential.clone();
+        #[cfg(test)]
+        let credential = self
+            .credential_loader
+            .as_ref()
+            .map(|l| (l.0)())
+            .or(credential);
+        let signer = Backend::build_signer(&self.region, credential.as_ref()).await?;
+        let read_signer = Backend::build_signer(&self.read_region, credential.as_ref()).await?;
+        self.signer.store(Arc::new(signer));
+        self.read_signer.store(Arc::new(read_signer));
+        Ok(())
+    }
+
+    /// send_read will sign and send the read request built by `build`.
+    ///
+    /// If s3 reports that the credential has expired, credential will be
+    /// refreshed and the request will be retried, at most
+    /// `CREDENTIAL_REFRESH_RETRIES` times. `build` will be called for every
+    /// attempt.
+    async fn send_read(
+        &self,
+        op: &'static str,
+        path: &str,
+        build: impl Fn() -> hyper::Request<hyper::Body>,
+    ) -> Result<hyper::Response<hyper::Body>> {
+        let mut retries = 0;
+        loop {
+            let mut req = build();
+            self.sign(&self.read_signer, &mut req).await;
+
+            let resp = self.client.request(req).await.map_err(|e| {
+                error!("object {} {}: {:?}", path, op, e);
+                Error::Object {
+                    kind: Kind::Unexpected,
+                    op,
+                    path: path.to_string(),
+                    context: HashMap::new(),
+                    source: e,
+                }
+            })?;
+
+            if retries >= CREDENTIAL_REFRESH_RETRIES
+                || !matches!(
+                    resp.status(),
+                    StatusCode::BAD_REQUEST | StatusCode::FORBIDDEN
+                )
+            {
+                return Ok(resp);
+            }
+
+            // Error responses are small, buffer it so that we can check the code.
+            let (parts, body) = resp.into_parts();
+            let bs = hyper::body::to_bytes(body)
+                .await
+                .map_err(|e| Error::Object {
+                    kind: Kind::Unexpected,
+                    op,
+                    path: path.to_string(),
+                    context: HashMap::new(),
+                    source: anyhow!("read error response: {:?}", e),
+                })?;
+            let code = de::from_reader::<_, ErrorResponse>(bs.clone().reader())
+                .map(|v| v.code)
+                .unwrap_or_default();
+            if !CREDENTIAL_EXPIRED_CODES.contains(&code.as_str()) {
+                return Ok(hyper::Response::from_parts(parts, hyper::Body::from(bs)));
+            }
+
+            warn!(
+                "object {} {} got {}, refresh credential and retry",
+                path, op, code
+            );
+            self.refresh_credential().await?;
+            retries += 1;
+        }
+    }
+
+    /// sign will attach the client info and requester pays headers and
+    /// sign the request.
+    ///
+    /// Requests to an anonymous backend will be sent without signing.
+    async fn sign(&self, signer: &ArcSwap<Signer>, req: &mut hyper::Request<hyper::Body>) {
+        insert_client_info(req, &self.user_agent);
+        if self.requester_pays {
+            req.headers_mut().insert(
+                HeaderName::from_static(constants::X_AMZ_REQUEST_PAYER),
+                HeaderValue::from_static("requester"),
+            );
+        }
+
+        if self.anonymous {
+            return;
+        }
+
+        signer.load().sign(req).await.expect("sign must success");
+    }
+
+    /// Insert headers of object options like content type, storage class, acl, cache control,
+    /// tags and user metadata.
+    ///
+    /// Storage class of the write overrides the backend's storage class.
+    ///
+    /// Empty values will be ignored, and invalid values will be rejected
+    /// with `Kind::BackendConfigurationInvalid`.
+    pub(crate) fn insert_write_headers(
+        &self,
+        mut req: http::request::Builder,
+        path: &str,
+        args: &OpWrite,
+    ) -> Result<http::request::Builder> {
+        let storage_class = args.storage_class.as_ref().or(self.storage_class.as_ref());
+        if let Some(v) = storage_class {
+            if !v.is_empty() && !STORAGE_CLASSES.contains(&v.as_str()) {
+                return Err(Error::Object {
+                    kind: Kind::BackendConfigurationInvalid,
+                    op: "write",
+                    path: path.to_string(),
+                    context: HashMap::new(),
+                    source: anyhow!("storage class must be one of {:?}", STORAGE_CLASSES),
+                });
+            }
+        }
+
+        let expires = args
+            .expires
+            .map(format_http_date)
+            .transpose()
+            .map_err(|e| Error::Object {
+                kind: Kind::BackendConfigurationInvalid,
+                op: "write",
+                path: path.to_string(),
+                context: HashMap::new(),
+                source: anyhow!("invalid expires: {:?}", e),
+            })?;
+
+        let headers = [
+            (http::header::CONTENT_TYPE, args.content_type.as_ref()),
+            (
+                HeaderName::from_static(constants::X_AMZ_STORAGE_CLASS),
+                storage_class,
             ),
+            (http::header::CACHE_CONTROL, args.cache_control.as_ref()),
+            (
+                http::header::CONTENT_DISPOSITION,
+                args.content_disposition.as_ref(),
+            ),
+            (
+                http::header::CONTENT_ENCODING,
+                args.content_encoding.as_ref(),
+            ),
+            (http::header::EXPIRES, expires.as_ref()),
+            (
+                HeaderName::from_static(constants::X_AMZ_ACL),
+                self.default_acl.as_ref(),
+            ),
+        ];
+        for (name, value) in headers {
+            let value = match value {
+                Some(v) if !v.is_empty() => v,
+                _ => continue,
+            };
+            let value = HeaderValue::from_str(value).map_err(|e| Error::Object {
+                kind: Kind::BackendConfigurationInvalid,
+                op: "write",
+                path: path.to_string(),
+                context: HashMap::new(),
+                source: anyhow!("invalid value {:?} of header {}: {:?}", value, name, e),
+            })?;
+            req = req.header(name, value);
+        }
+        if args.tags.len() > MAX_TAGS {
+            return Err(Error::Object {
+                kind: Kind::BackendConfigurationInvalid,
+                op: "write",
+                path: path.to_string(),
+                context: HashMap::new(),
+                source: anyhow!(
+                    "s3 allows at most {} tags on one object, but got {}",
+                    MAX_TAGS,
+                    args.tags.len()
+                ),
+            });
+        }
+        if !args.tags.is_empty() {
+            // Sort tags so that the same tags always generate the same header.
+            let mut tags = args.tags.iter().collect::<Vec<_>>();
+            tags.sort();
+            let tagging = tags
+                .into_iter()
+                .map(|(k, v)| {
+                    format!(
+                        "{}={}",
+                        utf8_percent_encode(k, NON_ALPHANUMERIC),
+                        utf8_percent_encode(v, NON_ALPHANUMERIC)
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join("&");
+            req = req.header(HeaderName::from_static(constants::X_AMZ_TAGGING), tagging);
+        }
+        for (k, v) in &args.user_metadata {
+            let invalid = |msg: String| Error::Object {
+                kind: Kind::BackendConfigurationInvalid,
+                op: "write",
+                path: path.to_string(),
+                context: HashMap::new(),
+                source: anyhow!("invalid user metadata {:?}: {}", k, msg),
+            };
+            let name = HeaderName::from_bytes(
+                format!("{}{}", constants::X_AMZ_META_PREFIX, k.to_lowercase()).as_bytes(),
+            )
+            .map_err(|e| invalid(format!("{:?}", e)))?;
+            if !v.is_ascii() {
+                return Err(invalid("value must be ascii".to_string()));
+            }
+            let value = HeaderValue::from_str(v).map_err(|e| invalid(format!("{:?}", e)))?;
+            req = req.header(name, value);
         }
+
+        Ok(req)
     }
 
     /// # Note
@@ -652,50 +1760,35 @@ impl Backend {
     ) -> http::request::Builder {
         if is_write {
             if let Some(v) = &self.server_side_encryption {
-                let mut v: HeaderValue = v.parse().expect("must be valid header value");
-                v.set_sensitive(true);
-
                 req = req.header(
                     HeaderName::from_static(constants::X_AMZ_SERVER_SIDE_ENCRYPTION),
-                    v,
+                    v.clone(),
                 )
             }
             if let Some(v) = &self.server_side_encryption_aws_kms_key_id {
-                let mut v: HeaderValue = v.parse().expect("must be valid header value");
-                v.set_sensitive(true);
-
                 req = req.header(
                     HeaderName::from_static(constants::X_AMZ_SERVER_SIDE_ENCRYPTION_AWS_KMS_KEY_ID),
-                    v,
+                    v.clone(),
                 )
             }
         }
 
         if let Some(v) = &self.server_side_encryption_customer_algorithm {
-            let mut v: HeaderValue = v.parse().expect("must be valid header value");
-            v.set_sensitive(true);
-
             req = req.header(
                 HeaderName::from_static(constants::X_AMZ_SERVER_SIDE_ENCRYPTION_CUSTOMER_ALGORITHM),
-                v,
+                v.clone(),
             )
         }
         if let Some(v) = &self.server_side_encryption_customer_key {
-            let mut v: HeaderValue = v.parse().expect("must be valid header value");
-            v.set_sensitive(true);
-
             req = req.header(
                 HeaderName::from_static(constants::X_AMZ_SERVER_SIDE_ENCRYPTION_CUSTOMER_KEY),
-                v,
+                v.clone(),
             )
         }
         if let Some(v) = &self.server_side_encryption_customer_key_md5 {
-            let mut v: HeaderValue = v.parse().expect("must be valid header value");
-            v.set_sensitive(true);
-
             req = req.header(
                 HeaderName::from_static(constants::X_AMZ_SERVER_SIDE_ENCRYPTION_CUSTOMER_KEY_MD5),
-                v,
+                v.clone(),
             )
         }
 
@@ -705,61 +1798,80 @@ impl Backend {
 
 #[async_trait]
 impl Accessor for Backend {
-    #[trace("read")]
+    #[cfg_attr(feature = "minitrace", trace("read"))]
     async fn read(&self, args: &OpRead) -> Result<BytesStream> {
-        increment_counter!("opendal_s3_read_requests");
-
-        let p = self.get_abs_path(&args.path);
-        debug!(
-            "object {} read start: offset {:?}, size {:?}",
-            &p, args.offset, args.size
-        );
+        let (s, _) = self.read_object(args, false).await?;
+        Ok(s)
+    }
 
-        let resp = self.get_object(&p, args.offset, args.size).await?;
+    #[cfg_attr(feature = "minitrace", trace("read_with_metadata"))]
+    async fn read_with_metadata(&self, args: &OpRead) -> Result<(BytesStream, Metadata)> {
+        match self.read_object(args, true).await? {
+            (s, Some(meta)) => Ok((s, meta)),
+            // No metadata in the response, like reads with zero size or at
+            // the end of object.
+            (s, None) => {
+                let mut op = OpStat::new(&args.path);
+                op.version = args.version.clone();
+                op.raw_headers = args.raw_headers;
+                Ok((s, self.stat(&op).await?))
+            }
+        }
+    }
 
-        match resp.status() {
-            StatusCode::OK | StatusCode::PARTIAL_CONTENT => {
-                debug!(
-                    "object {} reader created: offset {:?}, size {:?}",
-                    &p, args.offset, args.size
-                );
+    #[cfg_attr(feature = "minitrace", trace("write"))]
+    async fn write(&self, mut r: BoxedAsyncReader, args: &OpWrite) -> Result<Metadata> {
+        let p = self.get_abs_path("write", &args.path)?;
+        debug!("object {} write start: size {:?}", &p, args.size);
 
-                Ok(Box::new(resp.into_body().into_stream().map_err(move |e| {
-                    Error::Object {
+        let size = match args.size {
+            Some(size) if size > self.multipart_threshold => {
+                return self.write_by_multipart(&p, r, args).await;
+            }
+            Some(size) => size,
+            // Read ahead to decide whether multipart upload is required
+            // for content of unknown size.
+            None => {
+                let mut bs = Vec::new();
+                (&mut r)
+                    .take(self.multipart_threshold + 1)
+                    .read_to_end(&mut bs)
+                    .await
+                    .map_err(|e| Error::Object {
                         kind: Kind::Unexpected,
-                        op: "read",
+                        op: "write",
                         path: p.to_string(),
+                        context: HashMap::new(),
                         source: anyhow::Error::from(e),
-                    }
-                })))
+                    })?;
+                let size = bs.len() as u64;
+                let head = futures::io::Cursor::new(bs);
+                if size > self.multipart_threshold {
+                    return self
+                        .write_by_multipart(&p, Box::new(head.chain(r)), args)
+                        .await;
+                }
+                r = Box::new(head);
+                size
             }
-            _ => Err(parse_error_response(resp, "read", &p).await),
-        }
-    }
-
-    #[trace("write")]
-    async fn write(&self, r: BoxedAsyncReader, args: &OpWrite) -> Result<usize> {
-        let p = self.get_abs_path(&args.path);
-        debug!("object {} write start: size {}", &p, args.size);
+        };
 
-        let resp = self.put_object(&p, r, args.size).await?;
+        let resp = self.put_object(&p, r, size, args).await?;
         match resp.status() {
             StatusCode::CREATED | StatusCode::OK => {
-                debug!("object {} write finished: size {:?}", &p, args.size);
-                Ok(args.size as usize)
+                debug!("object {} write finished: size {:?}", &p, size);
+                Ok(parse_write_metadata(&args.path, size, resp.headers()))
             }
             _ => Err(parse_error_response(resp, "write", &p).await),
         }
     }
-    #[trace("stat")]
+    #[cfg_attr(feature = "minitrace", trace("stat"))]
     async fn stat(&self, args: &OpStat) -> Result<Metadata> {
-        increment_counter!("opendal_s3_stat_requests");
-
-        let p = self.get_abs_path(&args.path);
+        let p = self.get_abs_path("stat", &args.path)?;
         debug!("object {} stat start", &p);
 
         // Stat root always returns a DIR.
-        if self.get_rel_path(&p).is_empty() {
+        if self.get_rel_path("stat", &p)?.is_empty() {
             let mut m = Metadata::default();
             m.set_path(&args.path);
             m.set_content_length(0);
@@ -770,48 +1882,42 @@ impl Accessor for Backend {
             return Ok(m);
         }
 
-        let resp = self.head_object(&p).await?;
+        let resp = self.head_object(&p, args).await?;
 
         match resp.status() {
             StatusCode::OK => {
-                let mut m = Metadata::default();
-                m.set_path(&args.path);
+                let mut m = parse_object_metadata("stat", &args.path, &p, resp.headers())?;
 
                 // Parse content_length
-                if let Some(v) = resp.headers().get(http::header::CONTENT_LENGTH) {
-                    let v =
-                        u64::from_str(v.to_str().expect("header must not contain non-ascii value"))
-                            .expect("content length header must contain valid length");
-
+                if let Some(v) =
+                    parse_header_u64("stat", &p, resp.headers(), &http::header::CONTENT_LENGTH)?
+                {
                     m.set_content_length(v);
                 }
 
-                // Parse content_md5
-                if let Some(v) = resp.headers().get(HeaderName::from_static("content-md5")) {
-                    let v = v.to_str().expect("header must not contain non-ascii value");
-                    m.set_content_md5(v);
+                if args.raw_headers {
+                    m.set_raw_headers(resp.headers().clone());
                 }
 
-                // Parse last_modified
-                if let Some(v) = resp.headers().get(http::header::LAST_MODIFIED) {
-                    let v = v.to_str().expect("header must not contain non-ascii value");
-                    let t =
-                        OffsetDateTime::parse(v, &Rfc2822).expect("must contain valid time format");
-                    m.set_last_modified(t.into());
-                }
-
-                if p.ends_with('/') {
-                    m.set_mode(ObjectMode::DIR);
-                } else {
-                    m.set_mode(ObjectMode::FILE);
-                };
-
                 m.set_complete();
 
                 debug!("object {} stat finished: {:?}", &p, m);
                 Ok(m)
             }
             StatusCode::NOT_FOUND if p.ends_with('/') => {
+                // A dir on s3 may exist without a marker object, check
+                // whether there is anything under this prefix instead.
+                let mut s = S3ObjectStream::new(self.clone(), p.clone()).max_keys(Some(1));
+                if s.next().await.transpose()?.is_none() {
+                    return Err(Error::Object {
+                        kind: Kind::ObjectNotExist,
+                        op: "stat",
+                        path: p,
+                        context: HashMap::new(),
+                        source: anyhow!("dir not exists: no object under this prefix"),
+                    });
+                }
+
                 let mut m = Metadata::default();
                 m.set_path(&args.path);
                 m.set_content_length(0);
@@ -824,14 +1930,12 @@ impl Accessor for Backend {
             _ => Err(parse_error_response(resp, "stat", &p).await),
         }
     }
-    #[trace("delete")]
+    #[cfg_attr(feature = "minitrace", trace("delete"))]
     async fn delete(&self, args: &OpDelete) -> Result<()> {
-        increment_counter!("opendal_s3_delete_requests");
-
-        let p = self.get_abs_path(&args.path);
+        let p = self.get_abs_path("delete", &args.path)?;
         debug!("object {} delete start", &p);
 
-        let resp = self.delete_object(&p).await?;
+        let resp = self.delete_object(&p, &args.if_match).await?;
 
         match resp.status() {
             StatusCode::NO_CONTENT => {
@@ -841,79 +1945,849 @@ impl Accessor for Backend {
             _ => Err(parse_error_response(resp, "delete", &p).await),
         }
     }
-    #[trace("list")]
-    async fn list(&self, args: &OpList) -> Result<BoxedObjectStream> {
-        increment_counter!("opendal_s3_list_requests");
+    #[cfg_attr(feature = "minitrace", trace("batch"))]
+    async fn batch(&self, args: &OpBatch) -> Result<Vec<(String, BatchResult)>> {
+        // DeleteObjects doesn't support conditions, conditional deletes and
+        // other operations run one by one. Deletes of invalid paths run one
+        // by one too, so that they fail by their own.
+        let (deletes, others): (Vec<_>, Vec<_>) =
+            args.ops.iter().enumerate().partition(|(_, op)| {
+                matches!(op, BatchOperation::Delete(v) if v.if_match.is_none())
+                    && validate_path("delete", op.path()).is_ok()
+            });
+        debug!(
+            "batch start: {} deletes, {} other operations",
+            deletes.len(),
+            others.len()
+        );
 
-        let mut path = self.get_abs_path(&args.path);
-        // Make sure list path is endswith '/'
-        if !path.ends_with('/') && !path.is_empty() {
-            path.push('/')
+        let mut results: Vec<Option<BatchResult>> = args.ops.iter().map(|_| None).collect();
+
+        let others_args = OpBatch {
+            ops: others.iter().map(|(_, op)| (*op).clone()).collect(),
+            concurrency: args.concurrency,
+            priority: args.priority,
+        };
+        for ((idx, _), (_, res)) in others.iter().zip(batch_each(self, &others_args).await) {
+            results[*idx] = Some(res);
+        }
+
+        // Chunks own their indexes and keys, futures of `batch` must not
+        // hold borrows of them.
+        let chunks = deletes
+            .chunks(MAX_DELETE_OBJECTS)
+            .map(|chunk| {
+                chunk
+                    .iter()
+                    .map(|(idx, op)| {
+                        let key = self
+                            .get_abs_path("delete", op.path())
+                            .expect("paths of deletes must have been validated");
+                        (*idx, key)
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        let deleted = futures::stream::iter(chunks)
+            .map(|chunk| async move {
+                let keys = chunk.iter().map(|(_, key)| key.clone()).collect::<Vec<_>>();
+                let errors = self.delete_objects(&keys).await;
+                (chunk, errors)
+            })
+            .buffered(args.concurrency.max(1))
+            .collect::<Vec<_>>()
+            .await;
+        for (chunk, errors) in deleted {
+            for (idx, key) in chunk {
+                // Keys absent from the errors have been deleted.
+                let res = match &errors {
+                    Ok(errors) => errors.get(&key).map(share_error).map_or(Ok(()), Err),
+                    Err(err) => Err(share_error(err)),
+                };
+                results[idx] = Some(BatchResult::Delete(res));
+            }
         }
-        debug!("object {} list start", &path);
 
-        Ok(Box::new(S3ObjectStream::new(self.clone(), path)))
+        debug!("batch finished: {} operations", args.ops.len());
+        Ok(args
+            .ops
+            .iter()
+            .zip(results)
+            .map(|(op, res)| {
+                let res = res.expect("every operation must have a result");
+                (op.path().to_string(), res)
+            })
+            .collect())
     }
-}
+    #[cfg_attr(feature = "minitrace", trace("copy"))]
+    async fn copy(&self, args: &OpCopy) -> Result<Metadata> {
+        let src = self.get_abs_path("copy", &args.src)?;
+        let dst = self.get_abs_path("copy", &args.dst)?;
+        debug!("object {} copy start: to {}", &src, &dst);
 
-impl Backend {
-    #[trace("get_object")]
-    pub(crate) async fn get_object(
-        &self,
-        path: &str,
-        offset: Option<u64>,
-        size: Option<u64>,
-    ) -> Result<hyper::Response<hyper::Body>> {
-        let mut req = hyper::Request::get(&format!("{}/{}/{}", self.endpoint, self.bucket, path));
+        // Stat the source first, its size decides whether multipart copy is
+        // required, and its metadata has to be set again on copies that
+        // don't keep it.
+        let resp = self.head_object(&src, &OpStat::new(&src)).await?;
+        if resp.status() != StatusCode::OK {
+            return Err(parse_error_response(resp, "copy", &src).await);
+        }
+        let size = parse_header_u64("copy", &src, resp.headers(), &http::header::CONTENT_LENGTH)?
+            .unwrap_or_default();
+        let meta = parse_object_metadata("copy", &args.src, &src, resp.headers())?;
+        let write_args = self.copy_write_args(&meta, resp.headers());
 
-        if offset.is_some() || size.is_some() {
-            req = req.header(
-                http::header::RANGE,
-                HeaderRange::new(offset, size).to_string(),
-            );
+        let mut m = if size > MAX_COPY_OBJECT_SIZE {
+            self.copy_multipart(&src, &dst, size, &write_args).await?
+        } else {
+            // S3 rejects copying an object onto itself without changing
+            // anything, replace its metadata with the same values instead.
+            let resp = self
+                .copy_object(&src, &dst, &write_args, src == dst)
+                .await?;
+            let (part, bs) = read_result_response(resp, "copy", &src).await?;
+            let output: CopyObjectResult = de::from_reader(bs.reader()).unwrap_or_default();
+            let mut m = parse_write_metadata(&args.dst, size, &part.headers);
+            if !output.e_tag.is_empty() {
+                m.set_etag(&output.e_tag);
+            }
+            if let Ok(t) = parse_datetime(&output.last_modified) {
+                m.set_last_modified(t.into());
+            }
+            m
+        };
+        m.set_path(&args.dst);
+
+        debug!("object {} copy finished: to {}", &src, &dst);
+        Ok(m)
+    }
+    fn presign(&self, args: &OpPresign) -> Result<PresignedRequest> {
+        let p = self.get_abs_path("presign", &args.path)?;
+        debug!("object {} presign start: {}", &p, &args.method);
+
+        let unsupported = |reason: String| Error::Object {
+            kind: Kind::Unsupported,
+            op: "presign",
+            path: p.clone(),
+            context: HashMap::from([("method".to_string(), args.method.to_string())]),
+            source: anyhow!(reason),
+        };
+        let is_write = match args.method {
+            Method::GET | Method::HEAD => false,
+            Method::PUT => true,
+            _ => {
+                return Err(unsupported(
+                    "only GET, PUT and HEAD can be presigned".to_string(),
+                ))
+            }
+        };
+        if args.method != Method::GET && args.response_overrides != ResponseOverrides::default() {
+            return Err(unsupported(
+                "response overrides are only supported by GET".to_string(),
+            ));
+        }
+        if args.expire.is_zero() || args.expire > MAX_PRESIGN_EXPIRE {
+            return Err(unsupported(format!(
+                "expire {:?} is not in (0, {:?}]",
+                args.expire, MAX_PRESIGN_EXPIRE
+            )));
         }
 
-        // Set SSE headers.
-        req = self.insert_sse_headers(req, false);
+        let mut url = format!(
+            "{}/{}/{}",
+            if is_write {
+                &self.endpoint
+            } else {
+                &self.read_endpoint
+            },
+            self.bucket,
+            utf8_percent_encode(&p, PATH_ENCODE_SET)
+        );
+        push_response_overrides(&mut url, "presign", &p, &args.response_overrides)?;
 
-        let mut req = req
-            .body(hyper::Body::empty())
-            .expect("must be valid request");
+        // Headers like SSE-C are signed, so that clients have to send them too.
+        let mut req = http::Request::builder()
+            .method(args.method.clone())
+            .uri(&url);
+        req = self.insert_sse_headers(req, is_write);
+        if self.requester_pays {
+            req = req.header(
+                HeaderName::from_static(constants::X_AMZ_REQUEST_PAYER),
+                HeaderValue::from_static("requester"),
+            );
+        }
+        let mut req = req.body(()).map_err(|e| Error::Object {
+            kind: Kind::Unexpected,
+            op: "presign",
+            path: p.clone(),
+            context: HashMap::new(),
+            source: anyhow!("build request: {:?}", e),
+        })?;
 
-        self.signer.sign(&mut req).await.expect("sign must success");
+        // Requests to an anonymous backend are not signed either.
+        if !self.anonymous {
+            let (access_key_id, secret_access_key) = match &self.credential {
+                Some(Credential::HMAC {
+                    access_key_id,
+                    secret_access_key,
+                }) => (access_key_id, secret_access_key),
+                _ => {
+                    return Err(unsupported(
+                        "presign requires a static hmac credential".to_string(),
+                    ))
+                }
+            };
+            presign_v4(
+                &mut req,
+                access_key_id,
+                secret_access_key,
+                if is_write {
+                    &self.region
+                } else {
+                    &self.read_region
+                },
+                OffsetDateTime::now_utc(),
+                args.expire,
+            );
+        }
 
-        self.client.request(req).await.map_err(|e| {
-            error!("object {} get_object: {:?}", path, e);
-            Error::Object {
-                kind: Kind::Unexpected,
-                op: "read",
-                path: path.to_string(),
-                source: anyhow::Error::from(e),
-            }
+        let (parts, _) = req.into_parts();
+        debug!("object {} presign finished", &p);
+        Ok(PresignedRequest {
+            method: parts.method,
+            uri: parts.uri,
+            headers: parts.headers,
         })
     }
+    #[cfg_attr(feature = "minitrace", trace("create_multipart"))]
+    async fn create_multipart(&self, args: &OpCreateMultipart) -> Result<String> {
+        let p = self.get_abs_path("create_multipart", &args.path)?;
+        debug!("object {} create_multipart start", &p);
 
-    #[trace("put_object")]
-    pub(crate) async fn put_object(
+        let op = OpWrite {
+            path: args.path.clone(),
+            ..Default::default()
+        };
+        let resp = self.create_multipart_upload(&p, &op).await?;
+        let (_, bs) = read_result_response(resp, "create_multipart", &p).await?;
+        let output: InitiateMultipartUploadResult =
+            de::from_reader(bs.reader()).map_err(|e| Error::Object {
+                kind: Kind::Unexpected,
+                op: "create_multipart",
+                path: p.clone(),
+                context: HashMap::new(),
+                source: anyhow!("deserialize initiate multipart upload output: {:?}", e),
+            })?;
+
+        debug!(
+            "object {} create_multipart finished: {}",
+            &p, &output.upload_id
+        );
+        Ok(output.upload_id)
+    }
+    #[cfg_attr(feature = "minitrace", trace("write_multipart"))]
+    async fn write_multipart(
         &self,
-        path: &str,
         r: BoxedAsyncReader,
-        size: u64,
-    ) -> Result<hyper::Response<hyper::Body>> {
-        let mut req = hyper::Request::put(&format!("{}/{}/{}", self.endpoint, self.bucket, path));
+        args: &OpWriteMultipart,
+    ) -> Result<ObjectPart> {
+        let p = self.get_abs_path("write_multipart", &args.path)?;
+        debug!(
+            "object {} write_multipart start: upload {} part {} size {}",
+            &p, &args.upload_id, args.part_number, args.size
+        );
+        let err = |kind, source| Error::Object {
+            kind,
+            op: "write_multipart",
+            path: p.clone(),
+            context: HashMap::from([
+                ("upload_id".to_string(), args.upload_id.clone()),
+                ("part_number".to_string(), args.part_number.to_string()),
+            ]),
+            source,
+        };
+        if !(1..=MAX_MULTIPART_PARTS).contains(&(args.part_number as u64)) {
+            return Err(err(
+                Kind::Unexpected,
+                anyhow!(
+                    "part number {} is out of range 1..={}",
+                    args.part_number,
+                    MAX_MULTIPART_PARTS
+                ),
+            ));
+        }
+        if args.size > MAX_MULTIPART_PART_SIZE {
+            return Err(err(
+                Kind::Unexpected,
+                anyhow!(
+                    "part size {} exceeds the max part size {}",
+                    args.size,
+                    MAX_MULTIPART_PART_SIZE
+                ),
+            ));
+        }
 
-        // Set content length.
+        let resp = self
+            .upload_part(&p, &args.upload_id, args.part_number, r, args.size)
+            .await?;
+        if resp.status() != StatusCode::OK {
+            return Err(parse_error_response(resp, "write_multipart", &p).await);
+        }
+        let etag = parse_header_str("write_multipart", &p, resp.headers(), &http::header::ETAG)?
+            .ok_or_else(|| {
+                err(
+                    Kind::Unexpected,
+                    anyhow!("upload part {} response has no etag", args.part_number),
+                )
+            })?
+            .to_string();
+
+        debug!(
+            "object {} write_multipart finished: upload {} part {}",
+            &p, &args.upload_id, args.part_number
+        );
+        Ok(ObjectPart {
+            part_number: args.part_number,
+            etag,
+        })
+    }
+    #[cfg_attr(feature = "minitrace", trace("complete_multipart"))]
+    async fn complete_multipart(&self, args: &OpCompleteMultipart) -> Result<Metadata> {
+        let p = self.get_abs_path("complete_multipart", &args.path)?;
+        debug!(
+            "object {} complete_multipart start: upload {} with {} parts",
+            &p,
+            &args.upload_id,
+            args.parts.len()
+        );
+
+        // Parts must be `1..=n`, so that missing parts are not silently
+        // dropped from the object.
+        let mut parts = args.parts.clone();
+        parts.sort_by_key(|v| v.part_number);
+        let missing = (1..=parts.len())
+            .filter(|n| parts.binary_search_by_key(n, |v| v.part_number).is_err())
+            .collect::<Vec<_>>();
+        if parts.is_empty() || !missing.is_empty() {
+            return Err(Error::Object {
+                kind: Kind::Unexpected,
+                op: "complete_multipart",
+                path: p,
+                context: HashMap::from([("upload_id".to_string(), args.upload_id.clone())]),
+                source: anyhow!(
+                    "parts must be numbered 1..={} without missing or duplicated ones, \
+                     missing: {:?}",
+                    parts.len(),
+                    missing
+                ),
+            });
+        }
+
+        let etags = parts.into_iter().map(|v| v.etag).collect::<Vec<_>>();
+        let resp = self
+            .complete_multipart_upload(&p, &args.upload_id, &etags, &OpWrite::default())
+            .await?;
+        let (part, bs) = read_result_response(resp, "complete_multipart", &p).await?;
+
+        let mut m = Metadata::default();
+        m.set_path(&args.path).set_mode(ObjectMode::FILE);
+        if let Some(v) = header_str(&part.headers, constants::X_AMZ_VERSION_ID) {
+            m.set_version_id(v);
+        }
+        // The etag of multipart upload is returned in the body.
+        let output: CompleteMultipartUploadResult =
+            de::from_reader(bs.reader()).unwrap_or_default();
+        if !output.e_tag.is_empty() {
+            m.set_etag(&output.e_tag);
+        }
+
+        debug!(
+            "object {} complete_multipart finished: upload {}",
+            &p, &args.upload_id
+        );
+        Ok(m)
+    }
+    #[cfg_attr(feature = "minitrace", trace("abort_multipart"))]
+    async fn abort_multipart(&self, args: &OpAbortMultipart) -> Result<()> {
+        Backend::abort_multipart(self, &args.path, &args.upload_id).await
+    }
+    #[cfg_attr(feature = "minitrace", trace("list"))]
+    async fn list(&self, args: &OpList) -> Result<BoxedObjectStream> {
+        let mut path = self.get_abs_path("list", &args.path)?;
+        // Make sure list path is endswith '/'
+        if !path.ends_with('/') && !path.is_empty() {
+            path.push('/')
+        }
+        debug!("object {} list start", &path);
+
+        // S3 skips the keys by `start-after` natively.
+        let start_after = args
+            .start_after
+            .as_ref()
+            .map(|v| self.get_abs_path("list", v))
+            .transpose()?;
+
+        let obs: BoxedObjectStream = Box::new(LimitedObjectStream::new(
+            Box::new(
+                S3ObjectStream::new(self.clone(), path.clone())
+                    .start_after(start_after)
+                    .max_keys(args.page_size.map(|v| v.min(self.list_max_page_size)))
+                    .recursive(args.recursive)
+                    .ordered(args.ordered)
+                    .versions(args.versions)
+                    .v1(self.list_objects_v1)
+                    .pattern(args.pattern.as_deref()),
+            ),
+            &path,
+            args.max_results,
+            self.list_scan_limit,
+        ));
+
+        // Fields in the list response come for free, only complete
+        // metadata costs a HEAD per entry.
+        Ok(match args.metadata {
+            ListMetadata::Mode | ListMetadata::Listed => obs,
+            ListMetadata::Complete => {
+                complete_object_stream(obs, LIST_STAT_CONCURRENCY, args.priority)
+            }
+        })
+    }
+
+    #[cfg_attr(feature = "minitrace", trace("scan"))]
+    async fn scan(&self, args: &OpScan) -> Result<BoxedObjectStream> {
+        let mut path = self.get_abs_path("scan", &args.path)?;
+        // Make sure scan path is endswith '/'
+        if !path.ends_with('/') && !path.is_empty() {
+            path.push('/')
+        }
+        debug!("object {} scan start", &path);
+
+        // List without delimiter, and skip dir markers since scan yields
+        // files only.
+        let s = S3ObjectStream::new(self.clone(), path)
+            .recursive(true)
+            .v1(self.list_objects_v1)
+            .try_filter(|o| futures::future::ready(o.metadata_ref().mode() == ObjectMode::FILE));
+
+        Ok(Box::new(s))
+    }
+
+    fn metadata(&self) -> AccessorMetadata {
+        let mut am = AccessorMetadata::default();
+        am.set_scheme(Scheme::S3)
+            .set_root(&self.root)
+            .set_list(true)
+            .set_copy(true)
+            .set_presign(true)
+            .set_multipart(true);
+        // S3 lists keys in lexicographical order, but entries are yielded
+        // while parsing, and common prefixes are returned after contents.
+        // Ordered list has to sort every page.
+        am.set_conditional_write(true);
+        am.set_recursive_list(true);
+        am
+    }
+}
+
+impl Backend {
+    /// Read the object into a bytes stream.
+    ///
+    /// Metadata will be parsed from the response if `with_metadata` is set,
+    /// `None` will be returned if the response doesn't carry it.
+    async fn read_object(
+        &self,
+        args: &OpRead,
+        with_metadata: bool,
+    ) -> Result<(BytesStream, Option<Metadata>)> {
+        let p = self.get_abs_path("read", &args.path)?;
+        debug!(
+            "object {} read start: offset {:?}, size {:?}",
+            &p,
+            args.range.offset(),
+            args.range.size()
+        );
+
+        if args.range.size() == Some(0) {
+            debug!("object {} read with zero size, skip request", &p);
+            return Ok((Box::new(futures::stream::empty()), None));
+        }
+
+        // Read the first chunk alone to learn the length of the object, the
+        // rest of chunks will be read in parallel.
+        //
+        // Suffix reads are never split since where they start is unknown.
+        let parallel = self.read_concurrency > 1
+            && !args.range.is_suffix()
+            && !matches!(args.range.size(), Some(size) if size <= self.read_chunk_size);
+        let first_chunk;
+        let first = if parallel {
+            let offset = args.range.offset().unwrap_or_default();
+            first_chunk = OpRead {
+                range: BytesRange::from(offset..offset + self.read_chunk_size),
+                ..args.clone()
+            };
+            &first_chunk
+        } else {
+            args
+        };
+
+        let resp = self.get_object(&p, first).await?;
+
+        match resp.status() {
+            StatusCode::OK | StatusCode::PARTIAL_CONTENT => {
+                debug!(
+                    "object {} reader created: offset {:?}, size {:?}",
+                    &p,
+                    args.range.offset(),
+                    args.range.size()
+                );
+
+                // Only the whole object could be decoded.
+                let decoded =
+                    self.read_decompress && args.range.is_full() && is_gzip_encoded(resp.headers());
+                let mut meta = if with_metadata {
+                    parse_read_metadata(&args.path, &p, resp.status(), resp.headers(), decoded)?
+                } else {
+                    None
+                };
+                if let Some(m) = meta.as_mut().filter(|_| args.raw_headers) {
+                    m.set_raw_headers(resp.headers().clone());
+                }
+                let rest = if parallel && resp.status() == StatusCode::PARTIAL_CONTENT {
+                    self.read_rest(&p, args, resp.headers())
+                } else {
+                    None
+                };
+
+                // Don't rely on the empty body behavior of hyper, which differs
+                // between HTTP/1.1 and h2.
+                if parse_content_length(resp.headers()) == Some(0) {
+                    return Ok((Box::new(futures::stream::empty()), meta));
+                }
+
+                // Suffix reads are resumed by the range they turn out to be.
+                let resolved;
+                let first = if first.range.is_suffix() {
+                    resolved = OpRead {
+                        range: BytesRange::from(
+                            parse_content_range_start(resp.headers()).unwrap_or_default()..,
+                        ),
+                        ..first.clone()
+                    };
+                    &resolved
+                } else {
+                    first
+                };
+
+                let etag = header_str(resp.headers(), &http::header::ETAG).map(|v| v.to_string());
+                let s = HttpBodyStream::new(
+                    resp.into_body(),
+                    &p,
+                    first.range.offset().unwrap_or_default(),
+                );
+                let s = self.resumable(&p, first, etag, s);
+                let s: BytesStream = match rest {
+                    Some(rest) => Box::new(s.chain(rest)),
+                    None => Box::new(s),
+                };
+                if decoded {
+                    debug!("object {} read with gzip decoded", &p);
+                    Ok((decode_gzip(&p, s), meta))
+                } else {
+                    Ok((s, meta))
+                }
+            }
+            // Reading from the end of object like `bytes=N-` where N equals
+            // to object's length is valid, and should return an empty stream,
+            // so as suffix reads like `bytes=-N` on empty objects.
+            StatusCode::RANGE_NOT_SATISFIABLE
+                if (first.range.offset().is_some() || first.range.is_suffix())
+                    && Some(first.range.offset().unwrap_or_default())
+                        == parse_content_range_total(resp.headers()) =>
+            {
+                debug!("object {} read at the end of object", &p);
+                Ok((Box::new(futures::stream::empty()), None))
+            }
+            _ => Err(parse_error_response(resp, "read", &p).await),
+        }
+    }
+
+    /// Resume the body stream of `args` by ranged requests from where it
+    /// broke, at most `read_resume_retries` times.
+    ///
+    /// `etag` is the ETag of the first response, which resumed responses
+    /// must match.
+    fn resumable(
+        &self,
+        path: &str,
+        args: &OpRead,
+        etag: Option<String>,
+        s: HttpBodyStream,
+    ) -> BytesStream {
+        // There is no way to tell whether the object has been replaced.
+        if self.read_resume_retries == 0 || (etag.is_none() && args.version.is_none()) {
+            return Box::new(s);
+        }
+
+        let mut op = args.clone();
+        if op.if_match.is_none() {
+            op.if_match = etag.clone();
+        }
+        let state = ResumableRead {
+            backend: self.clone(),
+            path: path.to_string(),
+            args: op,
+            etag,
+            read: 0,
+            retries: 0,
+        };
+        let s = futures::stream::try_unfold((state, Some(s)), |(mut state, s)| async move {
+            let mut s = match s {
+                Some(s) => s,
+                None => return Ok(None),
+            };
+            loop {
+                match s.next().await {
+                    Some(Ok(bs)) => {
+                        state.read += bs.len() as u64;
+                        return Ok(Some((bs, (state, Some(s)))));
+                    }
+                    Some(Err(e))
+                        if e.kind() == Kind::ObjectReadInterrupted
+                            && state.retries < state.backend.read_resume_retries =>
+                    {
+                        warn!("object {} read interrupted, resume: {:?}", state.path, e);
+                        state.retries += 1;
+                        match state.resume().await? {
+                            Some(resumed) => s = resumed,
+                            None => return Ok(None),
+                        }
+                    }
+                    Some(Err(e)) => return Err(state.with_retries(e)),
+                    None => return Ok(None),
+                }
+            }
+        });
+        Box::new(Box::pin(s))
+    }
+
+    /// Read the whole object by `key` of the bucket, which is not relative to
+    /// the root, like data files of inventory reports.
+    pub(crate) async fn read_key(&self, key: &str) -> Result<BytesStream> {
+        let args = OpRead {
+            path: key.to_string(),
+            ..Default::default()
+        };
+        let resp = self.get_object(key, &args).await?;
+
+        match resp.status() {
+            StatusCode::OK => {
+                if parse_content_length(resp.headers()) == Some(0) {
+                    return Ok(Box::new(futures::stream::empty()));
+                }
+                let etag = header_str(resp.headers(), &http::header::ETAG).map(|v| v.to_string());
+                let s = HttpBodyStream::new(resp.into_body(), key, 0);
+                Ok(self.resumable(key, &args, etag, s))
+            }
+            _ => Err(parse_error_response(resp, "read", key).await),
+        }
+    }
+
+    /// Read the chunks after the first one by parallel ranged requests,
+    /// returns `None` if the first chunk covers the whole read.
+    ///
+    /// `headers` are the headers of the first chunk's response.
+    fn read_rest(&self, path: &str, args: &OpRead, headers: &HeaderMap) -> Option<BytesStream> {
+        let total = parse_content_range_total(headers)?;
+        let offset = args.range.offset().unwrap_or_default();
+        let end = match args.range.size() {
+            Some(size) => total.min(offset.saturating_add(size)),
+            None => total,
+        };
+        let chunk_size = self.read_chunk_size;
+        let start = offset + chunk_size;
+        if start >= end {
+            return None;
+        }
+
+        // Chunks must come from the same object as the first one, reads
+        // will fail instead of mixing contents if the object is replaced.
+        let mut op = args.clone();
+        if op.if_match.is_none() && op.version.is_none() {
+            op.if_match = header_str(headers, &http::header::ETAG).map(|v| v.to_string());
+        }
+
+        debug!(
+            "object {} read in parallel: range {}-{}, chunk size {}",
+            path, offset, end, chunk_size
+        );
+        let this = self.clone();
+        let path = path.to_string();
+        let count = (end - start).div_ceil(chunk_size);
+        let s = futures::stream::iter(0..count)
+            .map(move |i| {
+                let (this, path) = (this.clone(), path.clone());
+                let offset = start + i * chunk_size;
+                let op = OpRead {
+                    range: BytesRange::from(offset..offset + chunk_size.min(end - offset)),
+                    ..op.clone()
+                };
+                async move { this.read_chunk(&path, &op).await }
+            })
+            .buffered(self.read_concurrency);
+        Some(Box::new(s))
+    }
+
+    /// Read a chunk of the object into memory.
+    async fn read_chunk(&self, path: &str, args: &OpRead) -> Result<Bytes> {
+        let resp = self.get_object(path, args).await?;
+        if resp.status() != StatusCode::PARTIAL_CONTENT {
+            return Err(parse_error_response(resp, "read", path).await);
+        }
+
+        let offset = args.range.offset().unwrap_or_default();
+        let size = args.range.size().unwrap_or_default();
+        let mut s = HttpBodyStream::new(resp.into_body(), path, offset);
+        let mut buf = BytesMut::with_capacity(size as usize);
+        while let Some(bs) = s.try_next().await? {
+            buf.extend_from_slice(&bs);
+        }
+        if buf.len() as u64 != size {
+            return Err(Error::Object {
+                kind: Kind::ObjectReadInterrupted,
+                op: "read",
+                path: path.to_string(),
+                context: HashMap::from([(
+                    "offset".to_string(),
+                    (offset + buf.len() as u64).to_string(),
+                )]),
+                source: anyhow!("chunk ended at {} of {} bytes", buf.len(), size),
+            });
+        }
+        Ok(buf.freeze())
+    }
+
+    #[cfg_attr(feature = "minitrace", trace("get_object"))]
+    pub(crate) async fn get_object(
+        &self,
+        path: &str,
+        args: &OpRead,
+    ) -> Result<hyper::Response<hyper::Body>> {
+        let condition = |v: &Option<String>| {
+            v.as_deref()
+                .map(|v| HeaderValue::from_str(&quote_etag(v)))
+                .transpose()
+                .map_err(|e| Error::Object {
+                    kind: Kind::Unexpected,
+                    op: "read",
+                    path: path.to_string(),
+                    context: HashMap::new(),
+                    source: anyhow!("invalid etag condition: {:?}", e),
+                })
+        };
+        let if_match = condition(&args.if_match)?;
+        let if_none_match = condition(&args.if_none_match)?;
+        let date = |v: Option<OffsetDateTime>| {
+            v.map(format_http_date)
+                .transpose()
+                .map_err(|e| Error::Object {
+                    kind: Kind::Unexpected,
+                    op: "read",
+                    path: path.to_string(),
+                    context: HashMap::new(),
+                    source: anyhow!("invalid time condition: {:?}", e),
+                })
+        };
+        let if_modified_since = date(args.if_modified_since)?;
+        let if_unmodified_since = date(args.if_unmodified_since)?;
+
+        // The version and overrides must be in the url before signing, as
+        // they are part of the canonical query string.
+        let mut url = format!(
+            "{}/{}/{}",
+            self.read_endpoint,
+            self.bucket,
+            utf8_percent_encode(path, PATH_ENCODE_SET)
+        );
+        if let Some(v) = &args.version {
+            push_query(&mut url, "read", path, "versionId", v)?;
+        }
+        push_response_overrides(&mut url, "read", path, &args.response_overrides)?;
+
+        self.send_read("read", path, || {
+            let mut req = hyper::Request::get(&url);
+
+            if args.range.offset().is_some() || args.range.size().is_some() {
+                req = req.header(http::header::RANGE, args.range.to_string());
+            }
+            if let Some(v) = &if_match {
+                req = req.header(http::header::IF_MATCH, v.clone());
+            }
+            if let Some(v) = &if_none_match {
+                req = req.header(http::header::IF_NONE_MATCH, v.clone());
+            }
+            if let Some(v) = &if_modified_since {
+                req = req.header(http::header::IF_MODIFIED_SINCE, v);
+            }
+            if let Some(v) = &if_unmodified_since {
+                req = req.header(http::header::IF_UNMODIFIED_SINCE, v);
+            }
+
+            // Set SSE headers.
+            req = self.insert_sse_headers(req, false);
+
+            req.body(hyper::Body::empty())
+                .expect("must be valid request")
+        })
+        .await
+    }
+
+    #[cfg_attr(feature = "minitrace", trace("put_object"))]
+    pub(crate) async fn put_object(
+        &self,
+        path: &str,
+        r: BoxedAsyncReader,
+        size: u64,
+        args: &OpWrite,
+    ) -> Result<hyper::Response<hyper::Body>> {
+        let mut req = hyper::Request::put(&format!(
+            "{}/{}/{}",
+            self.endpoint,
+            self.bucket,
+            utf8_percent_encode(path, PATH_ENCODE_SET)
+        ));
+
+        // Set content length.
         req = req.header(http::header::CONTENT_LENGTH, size.to_string());
 
+        req = self.insert_write_headers(req, path, args)?;
+        req = insert_condition_headers(req, "write", path, &args.if_match, &args.if_none_match)?;
+
         // Set SSE headers.
         req = self.insert_sse_headers(req, true);
 
         // Set body
-        let mut req = req
-            .body(hyper::body::Body::wrap_stream(ReaderStream::new(r)))
-            .expect("must be valid request");
+        let body = if self.write_checksum {
+            let mut r = r;
+            let mut bs = Vec::with_capacity(size as usize);
+            r.read_to_end(&mut bs).await.map_err(|e| Error::Object {
+                kind: Kind::Unexpected,
+                op: "write",
+                path: path.to_string(),
+                context: HashMap::new(),
+                source: anyhow::Error::from(e),
+            })?;
+            req = req.header(
+                HeaderName::from_static(constants::CONTENT_MD5),
+                base64::encode(md5::compute(&bs).as_slice()),
+            );
+            hyper::Body::from(bs)
+        } else {
+            hyper::body::Body::wrap_stream(ReaderStream::new(r))
+        };
+        let mut req = req.body(body).expect("must be valid request");
 
-        self.signer.sign(&mut req).await.expect("sign must success");
+        self.sign(&self.signer, &mut req).await;
 
         self.client.request(req).await.map_err(|e| {
             error!("object {} put_object: {:?}", path, e);
@@ -921,172 +2795,5567 @@ impl Backend {
                 kind: Kind::Unexpected,
                 op: "write",
                 path: path.to_string(),
-                source: anyhow::Error::from(e),
+                context: HashMap::new(),
+                source: e,
             }
         })
     }
 
-    #[trace("head_object")]
-    pub(crate) async fn head_object(&self, path: &str) -> Result<hyper::Response<hyper::Body>> {
-        let mut req = hyper::Request::head(&format!("{}/{}/{}", self.endpoint, self.bucket, path));
+    /// Upload object by multipart upload, data will be read part by part so
+    /// that we never buffer the whole object.
+    ///
+    /// If the size is unknown, the reader will be read until EOF, and the
+    /// object can't be larger than `MAX_MULTIPART_PARTS` parts.
+    ///
+    /// The multipart upload will be aborted if any part failed, so that
+    /// uploaded parts will not be leaked.
+    async fn write_by_multipart(
+        &self,
+        path: &str,
+        mut r: BoxedAsyncReader,
+        args: &OpWrite,
+    ) -> Result<Metadata> {
+        let resp = self.create_multipart_upload(path, args).await?;
+        if resp.status() != StatusCode::OK {
+            return Err(parse_error_response(resp, "write", path).await);
+        }
+        let bs = hyper::body::to_bytes(resp.into_body())
+            .await
+            .map_err(|e| Error::Object {
+                kind: Kind::Unexpected,
+                op: "write",
+                path: path.to_string(),
+                context: HashMap::new(),
+                source: anyhow::Error::from(e),
+            })?;
+        let output: InitiateMultipartUploadResult =
+            de::from_reader(bs.reader()).map_err(|e| Error::Object {
+                kind: Kind::Unexpected,
+                op: "write",
+                path: path.to_string(),
+                context: HashMap::new(),
+                source: anyhow!("deserialize initiate multipart upload output: {:?}", e),
+            })?;
+        let upload_id = output.upload_id;
+        debug!("object {} multipart upload {} created", path, &upload_id);
+        let guard = MultipartUploadGuard {
+            backend: self.clone(),
+            path: path.to_string(),
+            upload_id: upload_id.clone(),
+            armed: true,
+        };
 
-        // Set SSE headers.
-        req = self.insert_sse_headers(req, false);
+        let mut written = 0;
+        let result = async {
+            // Enlarge the part size if the object can't fit in max parts.
+            let part_size = max(
+                self.multipart_part_size,
+                args.size.unwrap_or_default().div_ceil(MAX_MULTIPART_PARTS),
+            );
 
-        let mut req = req
-            .body(hyper::Body::empty())
-            .expect("must be valid request");
+            let mut etags = Vec::new();
+            loop {
+                let buf = match args.size {
+                    Some(total) => {
+                        let size = min(part_size, total - written);
+                        if size == 0 {
+                            break;
+                        }
+                        let mut buf = vec![0; size as usize];
+                        r.read_exact(&mut buf).await.map(|_| buf)
+                    }
+                    None => {
+                        let mut buf = Vec::with_capacity(part_size as usize);
+                        (&mut r)
+                            .take(part_size)
+                            .read_to_end(&mut buf)
+                            .await
+                            .map(|_| buf)
+                    }
+                }
+                .map_err(|e| Error::Object {
+                    kind: Kind::Unexpected,
+                    op: "write",
+                    path: path.to_string(),
+                    context: HashMap::new(),
+                    source: anyhow::Error::from(e),
+                })?;
+                // The reader of unknown size has reached EOF.
+                if buf.is_empty() {
+                    break;
+                }
+                let size = buf.len() as u64;
 
-        self.signer.sign(&mut req).await.expect("sign must success");
+                let part_number = etags.len() + 1;
+                if part_number as u64 > MAX_MULTIPART_PARTS {
+                    return Err(Error::Object {
+                        kind: Kind::Unsupported,
+                        op: "write",
+                        path: path.to_string(),
+                        context: HashMap::new(),
+                        source: anyhow!(
+                            "object of unknown size exceeds {} parts of {} bytes, \
+                             increase the backend's multipart_part_size",
+                            MAX_MULTIPART_PARTS,
+                            part_size
+                        ),
+                    });
+                }
+                let resp = self
+                    .upload_part(
+                        path,
+                        &upload_id,
+                        part_number,
+                        Box::new(futures::io::Cursor::new(buf)),
+                        size,
+                    )
+                    .await?;
+                if resp.status() != StatusCode::OK {
+                    return Err(parse_error_response(resp, "write", path).await);
+                }
+                let etag = parse_header_str("write", path, resp.headers(), &http::header::ETAG)?
+                    .ok_or_else(|| Error::Object {
+                        kind: Kind::Unexpected,
+                        op: "write",
+                        path: path.to_string(),
+                        context: HashMap::new(),
+                        source: anyhow!("upload part {} response has no etag", part_number),
+                    })?
+                    .to_string();
+                debug!(
+                    "object {} multipart upload {} part {} finished: size {}",
+                    path, &upload_id, part_number, size
+                );
 
-        self.client.request(req).await.map_err(|e| {
-            error!("object {} head_object: {:?}", path, e);
-            Error::Object {
-                kind: Kind::Unexpected,
-                op: "stat",
-                path: path.to_string(),
-                source: anyhow::Error::from(e),
+                etags.push(etag);
+                written += size;
             }
-        })
-    }
 
-    #[trace("delete_object")]
-    pub(crate) async fn delete_object(&self, path: &str) -> Result<hyper::Response<hyper::Body>> {
-        let mut req =
-            hyper::Request::delete(&format!("{}/{}/{}", self.endpoint, self.bucket, path))
-                .body(hyper::Body::empty())
-                .expect("must be valid request");
+            let resp = self
+                .complete_multipart_upload(path, &upload_id, &etags, args)
+                .await?;
+            let (part, body) = resp.into_parts();
+            let bs = hyper::body::to_bytes(body)
+                .await
+                .map_err(|e| Error::Object {
+                    kind: Kind::Unexpected,
+                    op: "write",
+                    path: path.to_string(),
+                    context: HashMap::new(),
+                    source: anyhow::Error::from(e),
+                })?;
+            // CompleteMultipartUpload could return an error with 200 OK.
+            if part.status != StatusCode::OK || bs.windows(7).any(|v| v == b"<Error>") {
+                return Err(Error::Object {
+                    kind: parse_error_kind(part.status, &bs),
+                    op: "write",
+                    path: path.to_string(),
+                    context: HashMap::new(),
+                    source: anyhow!(
+                        "complete multipart upload failed: response part: {:?}, body: {:?}",
+                        part,
+                        String::from_utf8_lossy(&bs)
+                    ),
+                });
+            }
 
-        self.signer.sign(&mut req).await.expect("sign must success");
+            let mut meta = parse_write_metadata(&args.path, written, &part.headers);
+            // The etag of multipart upload is returned in the body.
+            let output: CompleteMultipartUploadResult =
+                de::from_reader(bs.reader()).unwrap_or_default();
+            if !output.e_tag.is_empty() {
+                meta.set_etag(&output.e_tag);
+            }
+            Ok(meta)
+        }
+        .await;
 
-        self.client.request(req).await.map_err(|e| {
-            error!("object {} delete_object: {:?}", path, e);
-            Error::Object {
-                kind: Kind::Unexpected,
-                op: "delete",
-                path: path.to_string(),
-                source: anyhow::Error::from(e),
+        let meta = match result {
+            Ok(meta) => meta,
+            Err(e) => {
+                match self.abort_multipart_upload(path, &upload_id).await {
+                    Ok(resp) if resp.status() == StatusCode::NO_CONTENT => {
+                        debug!("object {} multipart upload {} aborted", path, &upload_id)
+                    }
+                    Ok(resp) => warn!(
+                        "object {} abort multipart upload {} got unexpected response: {:?}",
+                        path, &upload_id, resp
+                    ),
+                    Err(err) => warn!(
+                        "object {} abort multipart upload {}: {:?}",
+                        path, &upload_id, err
+                    ),
+                }
+                guard.disarm();
+                return Err(e);
             }
-        })
+        };
+        guard.disarm();
+
+        debug!(
+            "object {} write finished: size {}",
+            path,
+            meta.content_length()
+        );
+        Ok(meta)
     }
 
-    #[trace("list_objects")]
-    pub(crate) async fn list_objects(
+    #[cfg_attr(feature = "minitrace", trace("create_multipart_upload"))]
+    pub(crate) async fn create_multipart_upload(
         &self,
         path: &str,
-        continuation_token: &str,
+        args: &OpWrite,
     ) -> Result<hyper::Response<hyper::Body>> {
-        let mut uri = format!(
-            "{}/{}?list-type=2&delimiter=/&prefix={}",
-            self.endpoint, self.bucket, path
-        );
-        if !continuation_token.is_empty() {
-            uri.push_str(&format!("&continuation-token={}", continuation_token))
-        }
+        let mut req = hyper::Request::post(&format!(
+            "{}/{}/{}?uploads",
+            self.endpoint,
+            self.bucket,
+            utf8_percent_encode(path, PATH_ENCODE_SET)
+        ));
+
+        req = self.insert_write_headers(req, path, args)?;
 
-        let mut req = hyper::Request::get(uri)
+        // Set SSE headers.
+        req = self.insert_sse_headers(req, true);
+
+        let mut req = req
             .body(hyper::Body::empty())
             .expect("must be valid request");
 
-        self.signer.sign(&mut req).await.expect("sign must success");
+        self.sign(&self.signer, &mut req).await;
 
         self.client.request(req).await.map_err(|e| {
-            error!("object {} list_object: {:?}", path, e);
+            error!("object {} create_multipart_upload: {:?}", path, e);
             Error::Object {
                 kind: Kind::Unexpected,
-                op: "list",
+                op: "write",
                 path: path.to_string(),
-                source: anyhow::Error::from(e),
+                context: HashMap::new(),
+                source: e,
             }
         })
     }
-}
 
-// Read and decode whole error response.
-async fn parse_error_response(resp: Response<Body>, op: &'static str, path: &str) -> Error {
-    let (part, mut body) = resp.into_parts();
-    let kind = match part.status {
-        StatusCode::NOT_FOUND => Kind::ObjectNotExist,
-        StatusCode::FORBIDDEN => Kind::ObjectPermissionDenied,
+    #[cfg_attr(feature = "minitrace", trace("upload_part"))]
+    pub(crate) async fn upload_part(
+        &self,
+        path: &str,
+        upload_id: &str,
+        part_number: usize,
+        r: BoxedAsyncReader,
+        size: u64,
+    ) -> Result<hyper::Response<hyper::Body>> {
+        let mut req = hyper::Request::put(&format!(
+            "{}/{}/{}?partNumber={}&uploadId={}",
+            self.endpoint,
+            self.bucket,
+            utf8_percent_encode(path, PATH_ENCODE_SET),
+            part_number,
+            utf8_percent_encode(upload_id, QUERY_ENCODE_SET)
+        ));
+
+        req = req.header(http::header::CONTENT_LENGTH, size.to_string());
+
+        // Set SSE headers, only SSE-C headers are allowed here.
+        req = self.insert_sse_headers(req, false);
+
+        // Set body, only the part needs to be buffered for the checksum.
+        let r = r.take(size);
+        let body = if self.write_checksum {
+            let mut r = r;
+            let mut bs = Vec::with_capacity(size as usize);
+            r.read_to_end(&mut bs).await.map_err(|e| Error::Object {
+                kind: Kind::Unexpected,
+                op: "write",
+                path: path.to_string(),
+                context: HashMap::new(),
+                source: anyhow::Error::from(e),
+            })?;
+            req = req.header(
+                HeaderName::from_static(constants::CONTENT_MD5),
+                base64::encode(md5::compute(&bs).as_slice()),
+            );
+            hyper::Body::from(bs)
+        } else {
+            hyper::body::Body::wrap_stream(ReaderStream::new(Box::new(r)))
+        };
+        let mut req = req.body(body).expect("must be valid request");
+
+        self.sign(&self.signer, &mut req).await;
+
+        self.client.request(req).await.map_err(|e| {
+            error!("object {} upload_part: {:?}", path, e);
+            Error::Object {
+                kind: Kind::Unexpected,
+                op: "write",
+                path: path.to_string(),
+                context: HashMap::new(),
+                source: e,
+            }
+        })
+    }
+
+    #[cfg_attr(feature = "minitrace", trace("complete_multipart_upload"))]
+    pub(crate) async fn complete_multipart_upload(
+        &self,
+        path: &str,
+        upload_id: &str,
+        etags: &[String],
+        args: &OpWrite,
+    ) -> Result<hyper::Response<hyper::Body>> {
+        let mut body = String::from("<CompleteMultipartUpload>");
+        for (idx, etag) in etags.iter().enumerate() {
+            body.push_str(&format!(
+                "<Part><PartNumber>{}</PartNumber><ETag>{}</ETag></Part>",
+                idx + 1,
+                etag
+            ));
+        }
+        body.push_str("</CompleteMultipartUpload>");
+
+        let req = hyper::Request::post(&format!(
+            "{}/{}/{}?uploadId={}",
+            self.endpoint,
+            self.bucket,
+            utf8_percent_encode(path, PATH_ENCODE_SET),
+            utf8_percent_encode(upload_id, QUERY_ENCODE_SET)
+        ))
+        .header(http::header::CONTENT_LENGTH, body.len().to_string());
+        // Preconditions of multipart upload are checked while completing.
+        let mut req =
+            insert_condition_headers(req, "write", path, &args.if_match, &args.if_none_match)?
+                .body(hyper::Body::from(body))
+                .expect("must be valid request");
+
+        self.sign(&self.signer, &mut req).await;
+
+        self.client.request(req).await.map_err(|e| {
+            error!("object {} complete_multipart_upload: {:?}", path, e);
+            Error::Object {
+                kind: Kind::Unexpected,
+                op: "write",
+                path: path.to_string(),
+                context: HashMap::new(),
+                source: e,
+            }
+        })
+    }
+
+    /// Build the write args which set the metadata of `meta` again, for
+    /// copies that don't keep the metadata of the source.
+    ///
+    /// The storage class is kept unless the backend has its own.
+    fn copy_write_args(&self, meta: &Metadata, headers: &HeaderMap) -> OpWrite {
+        let header = |name: &HeaderName| header_str(headers, name).map(|v| v.to_string());
+
+        OpWrite {
+            path: meta.path().to_string(),
+            content_type: meta.content_type().map(|v| v.to_string()),
+            storage_class: self.storage_class.clone().or_else(|| meta.storage_class()),
+            cache_control: header(&http::header::CACHE_CONTROL),
+            content_disposition: header(&http::header::CONTENT_DISPOSITION),
+            content_encoding: meta.content_encoding(),
+            expires: meta.expires(),
+            user_metadata: meta.user_metadata().clone(),
+            ..Default::default()
+        }
+    }
+
+    /// Copy object by multipart upload whose parts are copied from ranges
+    /// of the source by UploadPartCopy.
+    ///
+    /// Like `write_multipart`, the multipart upload will be aborted if any
+    /// part failed. Tags of the source are not copied.
+    async fn copy_multipart(
+        &self,
+        src: &str,
+        dst: &str,
+        size: u64,
+        args: &OpWrite,
+    ) -> Result<Metadata> {
+        let resp = self.create_multipart_upload(dst, args).await?;
+        if resp.status() != StatusCode::OK {
+            return Err(parse_error_response(resp, "copy", dst).await);
+        }
+        let (_, bs) = read_result_response(resp, "copy", dst).await?;
+        let output: InitiateMultipartUploadResult =
+            de::from_reader(bs.reader()).map_err(|e| Error::Object {
+                kind: Kind::Unexpected,
+                op: "copy",
+                path: dst.to_string(),
+                context: HashMap::new(),
+                source: anyhow!("deserialize initiate multipart upload output: {:?}", e),
+            })?;
+        let upload_id = output.upload_id;
+        debug!("object {} multipart copy {} created", dst, &upload_id);
+        let guard = MultipartUploadGuard {
+            backend: self.clone(),
+            path: dst.to_string(),
+            upload_id: upload_id.clone(),
+            armed: true,
+        };
+
+        let result = async {
+            let part_size = max(self.multipart_part_size, size.div_ceil(MAX_MULTIPART_PARTS));
+
+            let mut etags = Vec::new();
+            let mut offset = 0;
+            while offset < size {
+                let end = min(offset + part_size, size) - 1;
+                let part_number = etags.len() + 1;
+                let resp = self
+                    .upload_part_copy(src, dst, &upload_id, part_number, offset, end)
+                    .await?;
+                let (_, bs) = read_result_response(resp, "copy", src).await?;
+                let output: CopyObjectResult = de::from_reader(bs.reader()).unwrap_or_default();
+                if output.e_tag.is_empty() {
+                    return Err(Error::Object {
+                        kind: Kind::Unexpected,
+                        op: "copy",
+                        path: dst.to_string(),
+                        context: HashMap::new(),
+                        source: anyhow!("upload part copy {} response has no etag", part_number),
+                    });
+                }
+                debug!(
+                    "object {} multipart copy {} part {} finished: range {}-{}",
+                    dst, &upload_id, part_number, offset, end
+                );
+
+                etags.push(output.e_tag);
+                offset = end + 1;
+            }
+
+            let resp = self
+                .complete_multipart_upload(dst, &upload_id, &etags, args)
+                .await?;
+            let (part, bs) = read_result_response(resp, "copy", dst).await?;
+            let mut meta = parse_write_metadata(dst, size, &part.headers);
+            let output: CompleteMultipartUploadResult =
+                de::from_reader(bs.reader()).unwrap_or_default();
+            if !output.e_tag.is_empty() {
+                meta.set_etag(&output.e_tag);
+            }
+            Ok(meta)
+        }
+        .await;
+
+        if result.is_err() {
+            match self.abort_multipart_upload(dst, &upload_id).await {
+                Ok(resp) if resp.status() == StatusCode::NO_CONTENT => {
+                    debug!("object {} multipart copy {} aborted", dst, &upload_id)
+                }
+                Ok(resp) => warn!(
+                    "object {} abort multipart copy {} got unexpected response: {:?}",
+                    dst, &upload_id, resp
+                ),
+                Err(err) => warn!(
+                    "object {} abort multipart copy {}: {:?}",
+                    dst, &upload_id, err
+                ),
+            }
+        }
+        guard.disarm();
+        result
+    }
+
+    /// Value of `x-amz-copy-source`, the bucket and key must be encoded.
+    fn copy_source(&self, src: &str) -> String {
+        format!(
+            "/{}/{}",
+            utf8_percent_encode(&self.bucket, QUERY_ENCODE_SET),
+            utf8_percent_encode(src, PATH_ENCODE_SET)
+        )
+    }
+
+    /// Insert SSE-C headers to decrypt the source of copies, which is
+    /// encrypted by the same customer key.
+    fn insert_copy_source_sse_headers(
+        &self,
+        mut req: http::request::Builder,
+    ) -> http::request::Builder {
+        let headers = [
+            (
+                constants::X_AMZ_COPY_SOURCE_SERVER_SIDE_ENCRYPTION_CUSTOMER_ALGORITHM,
+                &self.server_side_encryption_customer_algorithm,
+            ),
+            (
+                constants::X_AMZ_COPY_SOURCE_SERVER_SIDE_ENCRYPTION_CUSTOMER_KEY,
+                &self.server_side_encryption_customer_key,
+            ),
+            (
+                constants::X_AMZ_COPY_SOURCE_SERVER_SIDE_ENCRYPTION_CUSTOMER_KEY_MD5,
+                &self.server_side_encryption_customer_key_md5,
+            ),
+        ];
+        for (name, value) in headers {
+            if let Some(v) = value {
+                req = req.header(HeaderName::from_static(name), v.clone());
+            }
+        }
+
+        req
+    }
+
+    /// Copy object by CopyObject.
+    ///
+    /// The metadata of the source is kept, or replaced by `args` if
+    /// `replace` is set. The storage class and ACL are always set like
+    /// writes.
+    #[cfg_attr(feature = "minitrace", trace("copy_object"))]
+    pub(crate) async fn copy_object(
+        &self,
+        src: &str,
+        dst: &str,
+        args: &OpWrite,
+        replace: bool,
+    ) -> Result<hyper::Response<hyper::Body>> {
+        let mut req = hyper::Request::put(&format!(
+            "{}/{}/{}",
+            self.endpoint,
+            self.bucket,
+            utf8_percent_encode(dst, PATH_ENCODE_SET)
+        ))
+        .header(constants::X_AMZ_COPY_SOURCE, self.copy_source(src))
+        .header(http::header::CONTENT_LENGTH, "0");
+
+        if replace {
+            req = req.header(constants::X_AMZ_METADATA_DIRECTIVE, "REPLACE");
+            req = self.insert_write_headers(req, dst, args)?;
+        } else {
+            let args = OpWrite {
+                storage_class: args.storage_class.clone(),
+                ..Default::default()
+            };
+            req = self.insert_write_headers(req, dst, &args)?;
+        }
+
+        // Set SSE headers of both the destination and the source.
+        req = self.insert_sse_headers(req, true);
+        req = self.insert_copy_source_sse_headers(req);
+
+        let mut req = req
+            .body(hyper::Body::empty())
+            .expect("must be valid request");
+
+        self.sign(&self.signer, &mut req).await;
+
+        self.client.request(req).await.map_err(|e| {
+            error!("object {} copy_object: {:?}", src, e);
+            Error::Object {
+                kind: Kind::Unexpected,
+                op: "copy",
+                path: src.to_string(),
+                context: HashMap::new(),
+                source: e,
+            }
+        })
+    }
+
+    /// Copy the range `[start, end]` of `src` as a part of the multipart
+    /// upload by UploadPartCopy.
+    #[cfg_attr(feature = "minitrace", trace("upload_part_copy"))]
+    pub(crate) async fn upload_part_copy(
+        &self,
+        src: &str,
+        dst: &str,
+        upload_id: &str,
+        part_number: usize,
+        start: u64,
+        end: u64,
+    ) -> Result<hyper::Response<hyper::Body>> {
+        let mut req = hyper::Request::put(&format!(
+            "{}/{}/{}?partNumber={}&uploadId={}",
+            self.endpoint,
+            self.bucket,
+            utf8_percent_encode(dst, PATH_ENCODE_SET),
+            part_number,
+            utf8_percent_encode(upload_id, QUERY_ENCODE_SET)
+        ))
+        .header(constants::X_AMZ_COPY_SOURCE, self.copy_source(src))
+        .header(
+            constants::X_AMZ_COPY_SOURCE_RANGE,
+            format!("bytes={}-{}", start, end),
+        )
+        .header(http::header::CONTENT_LENGTH, "0");
+
+        // Set SSE headers, only SSE-C headers are allowed here.
+        req = self.insert_sse_headers(req, false);
+        req = self.insert_copy_source_sse_headers(req);
+
+        let mut req = req
+            .body(hyper::Body::empty())
+            .expect("must be valid request");
+
+        self.sign(&self.signer, &mut req).await;
+
+        self.client.request(req).await.map_err(|e| {
+            error!("object {} upload_part_copy: {:?}", src, e);
+            Error::Object {
+                kind: Kind::Unexpected,
+                op: "copy",
+                path: src.to_string(),
+                context: HashMap::new(),
+                source: e,
+            }
+        })
+    }
+
+    #[cfg_attr(feature = "minitrace", trace("list_multipart_uploads"))]
+    pub(crate) async fn list_multipart_uploads_page(
+        &self,
+        path: &str,
+        key_marker: &str,
+        upload_id_marker: &str,
+    ) -> Result<hyper::Response<hyper::Body>> {
+        let mut uri = format!("{}/{}?uploads", self.endpoint, self.bucket);
+        push_query(&mut uri, "list_multipart_uploads", path, "prefix", path)?;
+        if !key_marker.is_empty() {
+            push_query(
+                &mut uri,
+                "list_multipart_uploads",
+                path,
+                "key-marker",
+                key_marker,
+            )?;
+        }
+        if !upload_id_marker.is_empty() {
+            push_query(
+                &mut uri,
+                "list_multipart_uploads",
+                path,
+                "upload-id-marker",
+                upload_id_marker,
+            )?;
+        }
+
+        let mut req = hyper::Request::get(&uri)
+            .body(hyper::Body::empty())
+            .expect("must be valid request");
+
+        self.sign(&self.signer, &mut req).await;
+
+        self.client.request(req).await.map_err(|e| {
+            error!("object {} list_multipart_uploads: {:?}", path, e);
+            Error::Object {
+                kind: Kind::Unexpected,
+                op: "list_multipart_uploads",
+                path: path.to_string(),
+                context: HashMap::new(),
+                source: e,
+            }
+        })
+    }
+
+    #[cfg_attr(feature = "minitrace", trace("abort_multipart_upload"))]
+    pub(crate) async fn abort_multipart_upload(
+        &self,
+        path: &str,
+        upload_id: &str,
+    ) -> Result<hyper::Response<hyper::Body>> {
+        let mut req = hyper::Request::delete(&format!(
+            "{}/{}/{}?uploadId={}",
+            self.endpoint,
+            self.bucket,
+            utf8_percent_encode(path, PATH_ENCODE_SET),
+            utf8_percent_encode(upload_id, QUERY_ENCODE_SET)
+        ))
+        .body(hyper::Body::empty())
+        .expect("must be valid request");
+
+        self.sign(&self.signer, &mut req).await;
+
+        self.client.request(req).await.map_err(|e| {
+            error!("object {} abort_multipart_upload: {:?}", path, e);
+            Error::Object {
+                kind: Kind::Unexpected,
+                op: "write",
+                path: path.to_string(),
+                context: HashMap::new(),
+                source: e,
+            }
+        })
+    }
+
+    #[cfg_attr(feature = "minitrace", trace("head_object"))]
+    pub(crate) async fn head_object(
+        &self,
+        path: &str,
+        args: &OpStat,
+    ) -> Result<hyper::Response<hyper::Body>> {
+        let mut url = format!(
+            "{}/{}/{}",
+            self.read_endpoint,
+            self.bucket,
+            utf8_percent_encode(path, PATH_ENCODE_SET)
+        );
+        if let Some(v) = &args.version {
+            push_query(&mut url, "stat", path, "versionId", v)?;
+        }
+
+        let mut req = hyper::Request::head(&url);
+        req = insert_condition_headers(req, "stat", path, &args.if_match, &None)?;
+
+        // Set SSE headers.
+        req = self.insert_sse_headers(req, false);
+
+        let mut req = req
+            .body(hyper::Body::empty())
+            .expect("must be valid request");
+
+        self.sign(&self.read_signer, &mut req).await;
+
+        self.client.request(req).await.map_err(|e| {
+            error!("object {} head_object: {:?}", path, e);
+            Error::Object {
+                kind: Kind::Unexpected,
+                op: "stat",
+                path: path.to_string(),
+                context: HashMap::new(),
+                source: e,
+            }
+        })
+    }
+
+    #[cfg_attr(feature = "minitrace", trace("delete_object"))]
+    pub(crate) async fn delete_object(
+        &self,
+        path: &str,
+        if_match: &Option<String>,
+    ) -> Result<hyper::Response<hyper::Body>> {
+        let req = hyper::Request::delete(&format!(
+            "{}/{}/{}",
+            self.endpoint,
+            self.bucket,
+            utf8_percent_encode(path, PATH_ENCODE_SET)
+        ));
+        let mut req = insert_condition_headers(req, "delete", path, if_match, &None)?
+            .body(hyper::Body::empty())
+            .expect("must be valid request");
+
+        self.sign(&self.signer, &mut req).await;
+
+        self.client.request(req).await.map_err(|e| {
+            error!("object {} delete_object: {:?}", path, e);
+            Error::Object {
+                kind: Kind::Unexpected,
+                op: "delete",
+                path: path.to_string(),
+                context: HashMap::new(),
+                source: e,
+            }
+        })
+    }
+
+    /// Delete at most [`MAX_DELETE_OBJECTS`] keys by DeleteObjects, returns
+    /// errors of the keys failed to be deleted.
+    #[cfg_attr(feature = "minitrace", trace("delete_objects"))]
+    pub(crate) async fn delete_objects(&self, keys: &[String]) -> Result<HashMap<String, Error>> {
+        let path = keys.first().map(|v| v.as_str()).unwrap_or_default();
+
+        // Only errors are returned in quiet mode.
+        let mut body = String::from("<Delete><Quiet>true</Quiet>");
+        for key in keys {
+            body.push_str(&format!("<Object><Key>{}</Key></Object>", escape_xml(key)));
+        }
+        body.push_str("</Delete>");
+
+        let mut req = hyper::Request::post(&format!("{}/{}?delete", self.endpoint, self.bucket))
+            .header(http::header::CONTENT_LENGTH, body.len().to_string())
+            // DeleteObjects requires `Content-MD5`.
+            .header(
+                HeaderName::from_static(constants::CONTENT_MD5),
+                base64::encode(md5::compute(body.as_bytes()).as_slice()),
+            )
+            .body(hyper::Body::from(body))
+            .expect("must be valid request");
+
+        self.sign(&self.signer, &mut req).await;
+
+        let resp = self.client.request(req).await.map_err(|e| {
+            error!("object {} delete_objects: {:?}", path, e);
+            Error::Object {
+                kind: Kind::Unexpected,
+                op: "batch",
+                path: path.to_string(),
+                context: HashMap::new(),
+                source: e,
+            }
+        })?;
+        if resp.status() != StatusCode::OK {
+            return Err(parse_error_response(resp, "batch", path).await);
+        }
+
+        let bs = hyper::body::to_bytes(resp.into_body())
+            .await
+            .map_err(|e| Error::Object {
+                kind: Kind::Unexpected,
+                op: "batch",
+                path: path.to_string(),
+                context: HashMap::new(),
+                source: anyhow::Error::from(e),
+            })?;
+        let output: DeleteObjectsResult =
+            de::from_reader(bs.reader()).map_err(|e| Error::Object {
+                kind: Kind::Unexpected,
+                op: "batch",
+                path: path.to_string(),
+                context: HashMap::new(),
+                source: anyhow!("parse DeleteObjects result: {:?}", e),
+            })?;
+
+        Ok(output
+            .error
+            .into_iter()
+            .map(|v| {
+                let kind = match v.code.as_str() {
+                    "AccessDenied" => Kind::ObjectPermissionDenied,
+                    _ => Kind::Unexpected,
+                };
+                let err = Error::Object {
+                    kind,
+                    op: "delete",
+                    path: v.key.clone(),
+                    context: HashMap::from([("code".to_string(), v.code)]),
+                    source: anyhow!("{}", v.message),
+                };
+                (v.key, err)
+            })
+            .collect())
+    }
+
+    #[cfg_attr(feature = "minitrace", trace("list_objects"))]
+    pub(crate) async fn list_objects(
+        &self,
+        path: &str,
+        continuation_token: &str,
+        start_after: Option<&str>,
+        max_keys: Option<usize>,
+        recursive: bool,
+    ) -> Result<hyper::Response<hyper::Body>> {
+        let mut uri = format!("{}/{}?list-type=2", self.read_endpoint, self.bucket);
+        push_query(&mut uri, "list", path, "prefix", path)?;
+        // Keys in the response are percent-encoded, so that keys with
+        // control chars won't break the xml.
+        uri.push_str("&encoding-type=url");
+        // Without delimiter, s3 will return all keys under the prefix.
+        if !recursive {
+            uri.push_str("&delimiter=%2F")
+        }
+        // Tokens are opaque and could contain chars like `+` and `=`.
+        if !continuation_token.is_empty() {
+            push_query(
+                &mut uri,
+                "list",
+                path,
+                "continuation-token",
+                continuation_token,
+            )?;
+        }
+        if let Some(start_after) = start_after {
+            push_query(&mut uri, "list", path, "start-after", start_after)?;
+        }
+        if let Some(max_keys) = max_keys {
+            uri.push_str(&format!("&max-keys={}", max_keys))
+        }
+
+        self.send_read("list", path, || {
+            hyper::Request::get(&uri)
+                .body(hyper::Body::empty())
+                .expect("must be valid request")
+        })
+        .await
+    }
+
+    /// List objects by ListObjects (v1), see `Builder::enable_list_objects_v1`.
+    ///
+    /// Pages are continued by the `marker` from the previous page.
+    pub(crate) async fn list_objects_v1(
+        &self,
+        path: &str,
+        marker: &str,
+        max_keys: Option<usize>,
+        recursive: bool,
+    ) -> Result<hyper::Response<hyper::Body>> {
+        let mut uri = format!("{}/{}", self.read_endpoint, self.bucket);
+        push_query(&mut uri, "list", path, "prefix", path)?;
+        uri.push_str("&encoding-type=url");
+        if !recursive {
+            uri.push_str("&delimiter=%2F")
+        }
+        if !marker.is_empty() {
+            push_query(&mut uri, "list", path, "marker", marker)?;
+        }
+        if let Some(max_keys) = max_keys {
+            uri.push_str(&format!("&max-keys={}", max_keys))
+        }
+
+        self.send_read("list", path, || {
+            hyper::Request::get(&uri)
+                .body(hyper::Body::empty())
+                .expect("must be valid request")
+        })
+        .await
+    }
+
+    /// List versions and delete markers of objects by ListObjectVersions.
+    ///
+    /// Pages are continued by the `NextKeyMarker` and `NextVersionIdMarker`
+    /// of the previous page.
+    pub(crate) async fn list_object_versions(
+        &self,
+        path: &str,
+        key_marker: &str,
+        version_id_marker: &str,
+        max_keys: Option<usize>,
+        recursive: bool,
+    ) -> Result<hyper::Response<hyper::Body>> {
+        let mut uri = format!("{}/{}?versions", self.read_endpoint, self.bucket);
+        push_query(&mut uri, "list", path, "prefix", path)?;
+        uri.push_str("&encoding-type=url");
+        if !recursive {
+            uri.push_str("&delimiter=%2F")
+        }
+        if !key_marker.is_empty() {
+            push_query(&mut uri, "list", path, "key-marker", key_marker)?;
+        }
+        if !version_id_marker.is_empty() {
+            push_query(
+                &mut uri,
+                "list",
+                path,
+                "version-id-marker",
+                version_id_marker,
+            )?;
+        }
+        if let Some(max_keys) = max_keys {
+            uri.push_str(&format!("&max-keys={}", max_keys))
+        }
+
+        self.send_read("list", path, || {
+            hyper::Request::get(&uri)
+                .body(hyper::Body::empty())
+                .expect("must be valid request")
+        })
+        .await
+    }
+}
+
+/// Error response returned by s3.
+#[derive(Default, Debug, Deserialize)]
+#[serde(default, rename_all = "PascalCase")]
+struct ErrorResponse {
+    code: String,
+    message: String,
+    argument_name: String,
+    /// Storage class of the object, returned by `InvalidObjectState` while
+    /// reading archived objects like `GLACIER`.
+    storage_class: String,
+}
+
+/// Decode the gzip encoded body stream, which could be multiple members
+/// concatenated.
+///
+/// Corrupted bodies fail with `Kind::ObjectCorrupted`.
+fn decode_gzip(path: &str, s: BytesStream) -> BytesStream {
+    let path = path.to_string();
+    let corrupted = move |e: std::io::Error| Error::Object {
+        kind: Kind::ObjectCorrupted,
+        op: "read",
+        path: path.clone(),
+        context: HashMap::new(),
+        source: anyhow!("decode gzip: {:?}", e),
+    };
+
+    let decoder = Some(MultiGzDecoder::new(Vec::new()));
+    let s = futures::stream::try_unfold((s, decoder), move |(mut s, decoder)| {
+        let corrupted = corrupted.clone();
+        async move {
+            let mut decoder = match decoder {
+                Some(decoder) => decoder,
+                None => return Ok(None),
+            };
+            loop {
+                match s.try_next().await? {
+                    Some(bs) => {
+                        decoder.write_all(&bs).map_err(&corrupted)?;
+                        let bs = mem::take(decoder.get_mut());
+                        // Wait for more input if nothing decoded yet.
+                        if !bs.is_empty() {
+                            return Ok(Some((Bytes::from(bs), (s, Some(decoder)))));
+                        }
+                    }
+                    None => {
+                        decoder.try_finish().map_err(&corrupted)?;
+                        let bs = mem::take(decoder.get_mut());
+                        if bs.is_empty() {
+                            return Ok(None);
+                        }
+                        return Ok(Some((Bytes::from(bs), (s, None))));
+                    }
+                }
+            }
+        }
+    });
+    Box::new(Box::pin(s))
+}
+
+/// Output of DeleteObjects in quiet mode, which only contains errors.
+#[derive(Default, Debug, Deserialize)]
+#[serde(default, rename_all = "PascalCase")]
+struct DeleteObjectsResult {
+    error: Vec<DeleteObjectsError>,
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(default, rename_all = "PascalCase")]
+struct DeleteObjectsError {
+    key: String,
+    code: String,
+    message: String,
+}
+
+/// Escape the text of xml elements, like keys in DeleteObjects.
+fn escape_xml(v: &str) -> String {
+    let mut s = String::with_capacity(v.len());
+    for c in v.chars() {
+        match c {
+            '&' => s.push_str("&amp;"),
+            '<' => s.push_str("&lt;"),
+            '>' => s.push_str("&gt;"),
+            '"' => s.push_str("&quot;"),
+            '\'' => s.push_str("&apos;"),
+            c => s.push(c),
+        }
+    }
+    s
+}
+
+/// Output of CompleteMultipartUpload.
+#[derive(Default, Debug, Deserialize)]
+#[serde(default, rename_all = "PascalCase")]
+struct CompleteMultipartUploadResult {
+    e_tag: String,
+}
+
+/// Output of CopyObject and UploadPartCopy.
+#[derive(Default, Debug, Deserialize)]
+#[serde(default, rename_all = "PascalCase")]
+struct CopyObjectResult {
+    e_tag: String,
+    last_modified: String,
+}
+
+/// Read the whole body of responses like copies and completes of
+/// multipart uploads.
+///
+/// They could fail after `200 OK` has been sent, the error is returned
+/// in the body instead.
+async fn read_result_response(
+    resp: Response<Body>,
+    op: &'static str,
+    path: &str,
+) -> Result<(http::response::Parts, Bytes)> {
+    if resp.status() != StatusCode::OK {
+        return Err(parse_error_response(resp, op, path).await);
+    }
+
+    let (part, body) = resp.into_parts();
+    let bs = hyper::body::to_bytes(body)
+        .await
+        .map_err(|e| Error::Object {
+            kind: Kind::Unexpected,
+            op,
+            path: path.to_string(),
+            context: HashMap::new(),
+            source: anyhow::Error::from(e),
+        })?;
+    if bs.windows(7).any(|v| v == b"<Error>") {
+        return Err(Error::Object {
+            kind: parse_error_kind(part.status, &bs),
+            op,
+            path: path.to_string(),
+            context: HashMap::new(),
+            source: anyhow!(
+                "{} failed: response part: {:?}, body: {:?}",
+                op,
+                part,
+                String::from_utf8_lossy(&bs)
+            ),
+        });
+    }
+
+    Ok((part, bs))
+}
+
+/// Append a query parameter to the url.
+///
+/// The signer of reqsign serializes query values as form urlencoded,
+/// which differs from SigV4 for ` `, `*` and `~`. Values containing them
+/// will be rejected instead of failing with `403 SignatureDoesNotMatch`.
+fn push_query(
+    url: &mut String,
+    op: &'static str,
+    path: &str,
+    key: &str,
+    value: &str,
+) -> Result<()> {
+    if value.contains([' ', '*', '~']) {
+        return Err(Error::Object {
+            kind: Kind::Unsupported,
+            op,
+            path: path.to_string(),
+            context: HashMap::new(),
+            source: anyhow!(
+                "value {:?} of {} contains chars that can't be signed: ' ', '*' or '~'",
+                value,
+                key
+            ),
+        });
+    }
+
+    url.push(if url.contains('?') { '&' } else { '?' });
+    url.push_str(key);
+    url.push('=');
+    url.extend(utf8_percent_encode(value, QUERY_ENCODE_SET));
+    Ok(())
+}
+
+/// Append the response overrides to the url as query parameters like
+/// `response-content-type`.
+fn push_response_overrides(
+    url: &mut String,
+    op: &'static str,
+    path: &str,
+    overrides: &ResponseOverrides,
+) -> Result<()> {
+    let params = [
+        ("response-content-type", &overrides.content_type),
+        ("response-content-language", &overrides.content_language),
+        ("response-expires", &overrides.expires),
+        ("response-cache-control", &overrides.cache_control),
+        (
+            "response-content-disposition",
+            &overrides.content_disposition,
+        ),
+        ("response-content-encoding", &overrides.content_encoding),
+    ];
+
+    for (key, value) in params {
+        if let Some(v) = value {
+            push_query(url, op, path, key, v)?;
+        }
+    }
+    Ok(())
+}
+
+/// Quote the ETag for conditional headers, as ETags in [`Metadata`] have
+/// no quotes. `*`, weak and already quoted ETags are kept as is.
+fn quote_etag(etag: &str) -> String {
+    if etag == "*" || etag.starts_with('"') || etag.starts_with("W/") {
+        etag.to_string()
+    } else {
+        format!("\"{}\"", etag)
+    }
+}
+
+/// Insert `If-Match` and `If-None-Match` headers of the preconditions.
+fn insert_condition_headers(
+    mut req: http::request::Builder,
+    op: &'static str,
+    path: &str,
+    if_match: &Option<String>,
+    if_none_match: &Option<String>,
+) -> Result<http::request::Builder> {
+    let headers = [
+        (http::header::IF_MATCH, if_match),
+        (http::header::IF_NONE_MATCH, if_none_match),
+    ];
+    for (name, value) in headers {
+        if let Some(v) = value {
+            let v = HeaderValue::from_str(&quote_etag(v)).map_err(|e| Error::Object {
+                kind: Kind::Unexpected,
+                op,
+                path: path.to_string(),
+                context: HashMap::new(),
+                source: anyhow!("invalid value {:?} of header {}: {:?}", v, name, e),
+            })?;
+            req = req.header(name, v);
+        }
+    }
+    Ok(req)
+}
+
+/// State of a read resumed by [`Backend::resumable`].
+struct ResumableRead {
+    backend: Backend,
+    path: String,
+    /// The original read, with `if_match` pinned to the object.
+    args: OpRead,
+    etag: Option<String>,
+    /// Bytes yielded so far.
+    read: u64,
+    retries: usize,
+}
+
+impl ResumableRead {
+    /// Send a ranged request from where the read broke, returns `None` if
+    /// there is nothing left to read.
+    async fn resume(&self) -> Result<Option<HttpBodyStream>> {
+        increment_counter!("opendal_s3_read_resumes");
+
+        let offset = self.args.range.offset().unwrap_or_default() + self.read;
+        let size = match self.args.range.size() {
+            Some(size) if size <= self.read => return Ok(None),
+            Some(size) => Some(size - self.read),
+            None => None,
+        };
+        let op = OpRead {
+            range: BytesRange::new(Some(offset), size),
+            ..self.args.clone()
+        };
+        debug!(
+            "object {} read resume at {}, attempt {}",
+            &self.path, offset, self.retries
+        );
+
+        let changed = |source: anyhow::Error| Error::Object {
+            kind: Kind::ObjectChanged,
+            op: "read",
+            path: self.path.clone(),
+            context: HashMap::from([("offset".to_string(), offset.to_string())]),
+            source,
+        };
+        let resp = self.backend.get_object(&self.path, &op).await?;
+        match resp.status() {
+            StatusCode::PARTIAL_CONTENT => {
+                let etag =
+                    parse_header_str("read", &self.path, resp.headers(), &http::header::ETAG)?;
+                if let (Some(expected), Some(actual)) = (&self.etag, etag) {
+                    if expected != actual {
+                        return Err(changed(anyhow!(
+                            "etag changed from {} to {} while resuming the read",
+                            expected,
+                            actual
+                        )));
+                    }
+                }
+                Ok(Some(HttpBodyStream::new(
+                    resp.into_body(),
+                    &self.path,
+                    offset,
+                )))
+            }
+            StatusCode::RANGE_NOT_SATISFIABLE
+                if parse_content_range_total(resp.headers()) == Some(offset) =>
+            {
+                Ok(None)
+            }
+            StatusCode::PRECONDITION_FAILED => Err(changed(anyhow!(
+                "object doesn't match {:?} while resuming the read",
+                self.args.if_match
+            ))),
+            _ => Err(parse_error_response(resp, "read", &self.path).await),
+        }
+    }
+
+    /// Attach the count of retries to the error that the read gives up on.
+    fn with_retries(&self, mut err: Error) -> Error {
+        if let Error::Object { context, .. } = &mut err {
+            if self.retries > 0 {
+                context.insert("retries".to_string(), self.retries.to_string());
+            }
+        }
+        err
+    }
+}
+
+/// MultipartUploadGuard aborts the multipart upload in background if the
+/// write is dropped in the middle, like cancelled by a timeout, so that
+/// uploaded parts will not be leaked.
+struct MultipartUploadGuard {
+    backend: Backend,
+    path: String,
+    upload_id: String,
+    armed: bool,
+}
+
+impl MultipartUploadGuard {
+    /// The upload has been completed or aborted.
+    fn disarm(mut self) {
+        self.armed = false;
+    }
+}
+
+impl Drop for MultipartUploadGuard {
+    fn drop(&mut self) {
+        if !self.armed {
+            return;
+        }
+
+        let (backend, path, upload_id) = (
+            self.backend.clone(),
+            self.path.clone(),
+            self.upload_id.clone(),
+        );
+        spawn_cleanup("s3_multipart_upload", &self.path, async move {
+            match backend.abort_multipart_upload(&path, &upload_id).await {
+                Ok(resp) if resp.status() == StatusCode::NO_CONTENT => debug!(
+                    "object {} multipart upload {} aborted in background",
+                    path, &upload_id
+                ),
+                Ok(resp) => warn!(
+                    "object {} abort multipart upload {} got unexpected response: {:?}",
+                    path, &upload_id, resp
+                ),
+                Err(err) => warn!(
+                    "object {} abort multipart upload {}: {:?}",
+                    path, &upload_id, err
+                ),
+            }
+        });
+    }
+}
+
+/// Parse the metadata of the object from the headers of HeadObject or
+/// GetObject responses, except `content_length` which differs between them.
+///
+/// `path` is the relative path to set into the metadata, while `abs_path`
+/// is used for errors.
+fn parse_object_metadata(
+    op: &'static str,
+    path: &str,
+    abs_path: &str,
+    headers: &HeaderMap,
+) -> Result<Metadata> {
+    let mut m = Metadata::default();
+    m.set_path(path);
+
+    // Invalid optional headers are skipped with warnings.
+    let optional = |name| lenient(parse_header_str(op, abs_path, headers, name));
+
+    // Parse content_md5
+    if let Some(v) = optional("content-md5") {
+        m.set_content_md5(v);
+    }
+
+    // Parse content_type
+    //
+    // Dir markers carry the content type like `application/x-directory`
+    // of their empty content, which is not the dir's.
+    if !abs_path.ends_with('/') {
+        if let Some(v) = optional(http::header::CONTENT_TYPE.as_str()) {
+            m.set_content_type(v);
+        }
+    }
+
+    // Parse content_encoding
+    if let Some(v) = optional(http::header::CONTENT_ENCODING.as_str()) {
+        m.set_content_encoding(v);
+    }
+
+    // Parse storage_class, which is absent for `STANDARD` objects.
+    //
+    // Like content type, the storage class of dir markers is not the dir's.
+    if !abs_path.ends_with('/') {
+        m.set_storage_class(optional(constants::X_AMZ_STORAGE_CLASS).unwrap_or("STANDARD"));
+    }
+
+    // Parse server side encryption
+    if let Some(v) = optional(constants::X_AMZ_SERVER_SIDE_ENCRYPTION) {
+        m.set_server_side_encryption(v);
+    }
+    if let Some(v) = optional(constants::X_AMZ_SERVER_SIDE_ENCRYPTION_AWS_KMS_KEY_ID) {
+        m.set_server_side_encryption_aws_kms_key_id(v);
+    }
+
+    // Parse etag
+    if let Some(v) = optional(http::header::ETAG.as_str()) {
+        m.set_etag(v);
+    }
+
+    // Parse version_id
+    if let Some(v) = optional(constants::X_AMZ_VERSION_ID) {
+        m.set_version_id(v);
+    }
+
+    // Parse last_modified, invalid values like non http-date are errors,
+    // since callers compare it to decide whether objects are changed.
+    if let Some(t) = parse_header_datetime(op, abs_path, headers, &http::header::LAST_MODIFIED)? {
+        m.set_last_modified(t.into());
+    }
+
+    // Parse expires, services emit values like `0` which are not valid
+    // http-date, skip them with warnings.
+    if let Some(t) = lenient(parse_header_datetime(
+        op,
+        abs_path,
+        headers,
+        &http::header::EXPIRES,
+    )) {
+        m.set_expires(t);
+    }
+
+    // Parse user metadata, which is returned to users as is, so invalid
+    // values are errors instead.
+    let mut user_metadata = HashMap::new();
+    for (k, v) in headers {
+        let key = match k.as_str().strip_prefix(constants::X_AMZ_META_PREFIX) {
+            Some(key) => key,
+            None => continue,
+        };
+        let v = parse_value_str(op, abs_path, k, v)?;
+        user_metadata.insert(key.to_string(), v.to_string());
+    }
+    m.set_user_metadata(user_metadata);
+
+    if abs_path.ends_with('/') {
+        m.set_mode(ObjectMode::DIR);
+    } else {
+        m.set_mode(ObjectMode::FILE);
+    };
+
+    Ok(m)
+}
+
+/// Parse the metadata of the whole object from the GetObject response.
+///
+/// The length of the object comes from the total of `Content-Range` for
+/// ranged reads, returns `None` if it's unknown.
+///
+/// The length of `decoded` reads is unknown, so their metadata is never
+/// complete.
+fn parse_read_metadata(
+    path: &str,
+    abs_path: &str,
+    status: StatusCode,
+    headers: &HeaderMap,
+    decoded: bool,
+) -> Result<Option<Metadata>> {
+    if decoded {
+        return parse_object_metadata("read", path, abs_path, headers).map(Some);
+    }
+
+    let total = match status {
+        StatusCode::PARTIAL_CONTENT => parse_content_range_total(headers),
+        _ => parse_content_length(headers),
+    };
+    let total = match total {
+        Some(v) => v,
+        None => return Ok(None),
+    };
+
+    let mut m = parse_object_metadata("read", path, abs_path, headers)?;
+    m.set_content_length(total);
+    m.set_complete();
+    Ok(Some(m))
+}
+
+/// Build the metadata of a finished write from the response headers.
+fn parse_write_metadata(path: &str, size: u64, headers: &HeaderMap) -> Metadata {
+    let mut m = Metadata::default();
+    m.set_path(path)
+        .set_mode(ObjectMode::FILE)
+        .set_content_length(size);
+    if let Some(v) = header_str(headers, &http::header::ETAG) {
+        m.set_etag(v);
+    }
+    if let Some(v) = header_str(headers, constants::X_AMZ_VERSION_ID) {
+        m.set_version_id(v);
+    }
+    m
+}
+
+/// Output of CreateMultipartUpload.
+#[derive(Default, Debug, Deserialize)]
+#[serde(default, rename_all = "PascalCase")]
+struct InitiateMultipartUploadResult {
+    upload_id: String,
+}
+
+// Read and decode whole error response.
+async fn parse_error_response(resp: Response<Body>, op: &'static str, path: &str) -> Error {
+    let (part, mut body) = resp.into_parts();
+
+    // Only read 4KiB from the response to avoid broken services.
+    let mut bs = Vec::new();
+    let mut limit = 4 * 1024;
+
+    while let Some(b) = body.data().await {
+        match b {
+            Ok(b) => {
+                bs.put_slice(&b[..min(b.len(), limit)]);
+                limit = limit.saturating_sub(b.len());
+                if limit == 0 {
+                    break;
+                }
+            }
+            Err(e) => return Error::from(anyhow!("parse error response parse: {:?}", e)),
+        }
+    }
+
+    // Reading a version which is a delete marker returns `405 Method Not Allowed`.
+    let kind = match part.headers.get(constants::X_AMZ_DELETE_MARKER) {
+        Some(v) if v == "true" => Kind::ObjectNotExist,
+        _ => parse_error_kind(part.status, &bs),
+    };
+
+    let mut context = HashMap::new();
+    if let Ok(resp) = de::from_reader::<_, ErrorResponse>(bs.as_slice()) {
+        if !resp.code.is_empty() {
+            context.insert("code".to_string(), resp.code);
+        }
+        if !resp.storage_class.is_empty() {
+            context.insert("storage_class".to_string(), resp.storage_class);
+        }
+    }
+
+    Error::Object {
+        kind,
+        op,
+        path: path.to_string(),
+        context,
+        source: anyhow!(
+            "response part: {:?}, body: {:?}",
+            part,
+            String::from_utf8_lossy(&bs)
+        ),
+    }
+}
+
+/// Decide the error kind by the status code and the error code in body.
+fn parse_error_kind(status: StatusCode, bs: &[u8]) -> Kind {
+    let kind = match status {
+        StatusCode::NOT_FOUND => Kind::ObjectNotExist,
+        StatusCode::FORBIDDEN => Kind::ObjectPermissionDenied,
+        StatusCode::NOT_MODIFIED => Kind::ObjectNotModified,
+        StatusCode::PRECONDITION_FAILED => Kind::ObjectPreconditionFailed,
+        StatusCode::INTERNAL_SERVER_ERROR
+        | StatusCode::BAD_GATEWAY
+        | StatusCode::SERVICE_UNAVAILABLE
+        | StatusCode::GATEWAY_TIMEOUT => Kind::ServiceUnavailable,
         _ => Kind::Unexpected,
     };
 
-    // Only read 4KiB from the response to avoid broken services.
-    let mut bs = Vec::new();
-    let mut limit = 4 * 1024;
+    match de::from_reader::<_, ErrorResponse>(bs) {
+        Ok(resp) if resp.code == "BadDigest" => Kind::ObjectChecksumMismatch,
+        // Some s3 compatible services throttle with other status codes.
+        Ok(resp) if resp.code == "SlowDown" => Kind::ServiceUnavailable,
+        // Reading archived objects that have not been restored.
+        Ok(resp) if resp.code == "InvalidObjectState" => Kind::ObjectNotAvailable,
+        // Returned while a concurrent conditional write is in progress.
+        Ok(resp) if resp.code == "ConditionalRequestConflict" => Kind::ObjectPreconditionFailed,
+        Ok(resp) if resp.code == "PreconditionFailed" => Kind::ObjectPreconditionFailed,
+        // Malformed version ids are rejected with `400 Bad Request`.
+        Ok(resp) if resp.code == "InvalidArgument" && resp.argument_name == "versionId" => {
+            Kind::ObjectNotExist
+        }
+        _ => kind,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::atomic::Ordering;
+    use std::sync::Mutex;
+    use std::time::Duration;
+
+    use futures::StreamExt;
+
+    use super::*;
+    use crate::http_util::X_OPENDAL_REQUEST_ID;
+    use crate::layers::RequestIdLayer;
+    use crate::ops::OpCreate;
+    use crate::tests::mock::mock_flaky_server;
+    use crate::tests::mock::mock_s3_operator;
+    use crate::tests::mock::mock_server;
+    use crate::Object;
+    use crate::ObjectStream;
+    use crate::Operator;
+    use crate::ShutdownGuard;
+
+    #[tokio::test]
+    async fn test_detect_region() {
+        let client = HttpClient::new();
+        let ua = user_agent(None).unwrap();
+
+        // endpoint = `https://s3.amazonaws.com`, region = None
+        let b = Builder::default();
+        let (endpoint, region) = b
+            .detect_region(&client, &ua, "test", &HashMap::new())
+            .await
+            .expect("detect region must success");
+        assert_eq!(endpoint, "https://s3.us-east-2.amazonaws.com");
+        assert_eq!(region, "us-east-2");
+
+        // endpoint = `https://s3.amazonaws.com`, region = `us-east-2`
+        let mut b = Builder::default();
+        b.region("us-east-2");
+        let (endpoint, region) = b
+            .detect_region(&client, &ua, "test", &HashMap::new())
+            .await
+            .expect("detect region must success");
+        assert_eq!(endpoint, "https://s3.us-east-2.amazonaws.com");
+        assert_eq!(region, "us-east-2");
+
+        // endpoint = `https://s3.amazonaws.com`, region = `us-east-2`
+        let mut b = Builder::default();
+        b.endpoint("https://s3.amazonaws.com");
+        b.region("us-east-2");
+        let (endpoint, region) = b
+            .detect_region(&client, &ua, "test", &HashMap::new())
+            .await
+            .expect("detect region must success");
+        assert_eq!(endpoint, "https://s3.us-east-2.amazonaws.com");
+        assert_eq!(region, "us-east-2");
+
+        // endpoint = `https://s3.us-east-2.amazonaws.com`, region = `us-east-2`
+        let mut b = Builder::default();
+        b.endpoint("https://s3.us-east-2.amazonaws.com");
+        b.region("us-east-2");
+        let (endpoint, region) = b
+            .detect_region(&client, &ua, "test", &HashMap::new())
+            .await
+            .expect("detect region must success");
+        assert_eq!(endpoint, "https://s3.us-east-2.amazonaws.com");
+        assert_eq!(region, "us-east-2");
+    }
+
+    #[tokio::test]
+    async fn test_client_info() -> Result<()> {
+        let (endpoint, requests) = mock_server(|req| {
+            let status = match (req.method(), req.uri().path()) {
+                (_, "/test/missing") => StatusCode::NOT_FOUND,
+                (&http::Method::DELETE, _) => StatusCode::NO_CONTENT,
+                _ => StatusCode::OK,
+            };
+            let body = if req
+                .uri()
+                .query()
+                .unwrap_or_default()
+                .contains("list-type=2")
+            {
+                r#"<ListBucketResult><IsTruncated>false</IsTruncated></ListBucketResult>"#
+            } else {
+                ""
+            };
+            hyper::Response::builder()
+                .status(status)
+                .header(http::header::CONTENT_LENGTH, body.len())
+                .body(hyper::Body::from(body))
+                .unwrap()
+        });
+        let mut builder = Backend::build();
+        builder
+            .bucket("test")
+            .endpoint(&endpoint)
+            .region("us-east-1")
+            .user_agent("my-app/1.0")
+            .credential(Credential::hmac("access_key_id", "secret_access_key"));
+        let op = Operator::new(builder.finish().await?).layer(RequestIdLayer);
+
+        op.object("file").writer().write_bytes(vec![0; 4]).await?;
+        op.object("file")
+            .stream(None, None)
+            .await?
+            .try_collect::<Vec<_>>()
+            .await?;
+        op.object("file").metadata().await?;
+        op.object("file").delete().await?;
+        op.objects("dir/").try_collect::<Vec<_>>().await?;
+        let err = op.object("missing").metadata().await.unwrap_err();
+        assert_eq!(err.kind(), Kind::ObjectNotExist);
+
+        let ua = format!("opendal/{} my-app/1.0", env!("CARGO_PKG_VERSION"));
+        let mut ids = Vec::new();
+        for req in requests.lock().unwrap().iter() {
+            assert_eq!(req.headers()[http::header::USER_AGENT], ua.as_str());
+            let id = req.headers()[X_OPENDAL_REQUEST_ID]
+                .to_str()
+                .unwrap()
+                .to_string();
+            assert!(uuid::Uuid::parse_str(&id).is_ok(), "invalid id {}", id);
+            // Headers are inserted before signing.
+            let auth = req.headers()[http::header::AUTHORIZATION].to_str().unwrap();
+            assert!(auth.contains(X_OPENDAL_REQUEST_ID), "{}", auth);
+            ids.push(id);
+        }
+        assert_eq!(ids.len(), 6);
+        // Every operation has its own id.
+        ids.dedup();
+        assert_eq!(ids.len(), 6);
+        // The id of the failed operation is attached to the error.
+        assert_eq!(&err.context()["request_id"], ids.last().unwrap());
+
+        // Request hooks observe the id.
+        let observed = Arc::new(Mutex::new(Vec::new()));
+        let hook_observed = observed.clone();
+        let hook: crate::http_util::RequestHook = Arc::new(move |req| {
+            let id = req.headers().get(X_OPENDAL_REQUEST_ID).cloned();
+            hook_observed.lock().unwrap().push(id);
+        });
+        let err = crate::http_util::with_request_hook(hook, op.object("missing").metadata())
+            .await
+            .unwrap_err();
+        let observed = observed.lock().unwrap();
+        assert_eq!(observed.len(), 1);
+        assert_eq!(
+            observed[0].as_ref().unwrap(),
+            err.context()["request_id"].as_str()
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_client_info_default() -> Result<()> {
+        let (endpoint, requests) = mock_server(|_| hyper::Response::new(hyper::Body::empty()));
+        let op = mock_s3_operator(&endpoint).await;
+        op.object("file").metadata().await?;
+
+        let mut builder = Backend::build();
+        builder.bucket("test").user_agent("bad\n");
+        let err = builder.finish().await.unwrap_err();
+        assert_eq!(err.kind(), Kind::BackendConfigurationInvalid);
+
+        let requests = requests.lock().unwrap();
+        let ua = format!("opendal/{}", env!("CARGO_PKG_VERSION"));
+        assert_eq!(requests[0].headers()[http::header::USER_AGENT], ua.as_str());
+        assert!(requests[0].headers().get(X_OPENDAL_REQUEST_ID).is_none());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_read_endpoint() -> Result<()> {
+        let (endpoint, write_requests) = mock_server(|req| {
+            let status = match *req.method() {
+                http::Method::DELETE => StatusCode::NO_CONTENT,
+                _ => StatusCode::OK,
+            };
+            hyper::Response::builder()
+                .status(status)
+                .body(hyper::Body::empty())
+                .unwrap()
+        });
+        let (read_endpoint, read_requests) = mock_server(|req| {
+            if req
+                .uri()
+                .query()
+                .unwrap_or_default()
+                .contains("list-type=2")
+            {
+                hyper::Response::new(hyper::Body::from(
+                    r#"<ListBucketResult><IsTruncated>false</IsTruncated></ListBucketResult>"#,
+                ))
+            } else {
+                hyper::Response::new(hyper::Body::empty())
+            }
+        });
+
+        let mut builder = Backend::build();
+        builder
+            .bucket("test")
+            .endpoint(&endpoint)
+            .read_endpoint(&read_endpoint)
+            .region("us-east-1")
+            .read_region("eu-west-1")
+            .credential(Credential::hmac("access_key_id", "secret_access_key"));
+        assert!(format!("{:?}", builder).contains(&read_endpoint));
+        let op = Operator::new(builder.finish().await?);
+
+        op.object("file").writer().write_bytes(vec![0; 4]).await?;
+        op.object("file").delete().await?;
+        let _ = op.object("file").stream(None, None).await?;
+        op.object("file").metadata().await?;
+        let mut obs = op.objects("dir/");
+        while let Some(o) = obs.next().await {
+            o?;
+        }
+
+        // Read requests are signed with the credential scope of read_region.
+        for (endpoint, requests, methods, region) in [
+            (
+                &endpoint,
+                write_requests,
+                vec!["PUT", "DELETE"],
+                "us-east-1",
+            ),
+            (
+                &read_endpoint,
+                read_requests,
+                vec!["GET", "HEAD", "GET"],
+                "eu-west-1",
+            ),
+        ] {
+            let requests = requests.lock().unwrap();
+            assert_eq!(
+                requests
+                    .iter()
+                    .map(|v| v.method().as_str())
+                    .collect::<Vec<_>>(),
+                methods
+            );
+
+            let host = endpoint.trim_start_matches("http://");
+            for req in requests.iter() {
+                assert_eq!(req.headers().get(http::header::HOST).unwrap(), host);
+
+                let auth = req
+                    .headers()
+                    .get(http::header::AUTHORIZATION)
+                    .unwrap()
+                    .to_str()
+                    .unwrap();
+                let scope = format!("/{}/s3/aws4_request", region);
+                assert!(auth.contains(&scope), "{}", auth);
+                assert!(auth.contains("host"), "{}", auth);
+            }
+        }
+
+        // So are presigned reads.
+        for (method, region) in [
+            (http::Method::GET, "eu-west-1"),
+            (http::Method::PUT, "us-east-1"),
+        ] {
+            let req = op.object("file").presign(method, Duration::from_secs(60))?;
+            let scope = format!("%2F{}%2Fs3%2Faws4_request", region);
+            assert!(req.uri.query().unwrap().contains(&scope), "{}", req.uri);
+        }
+
+        Ok(())
+    }
+
+    fn multipart_mock(fail_part: Option<&'static str>) -> (String, crate::tests::mock::Recorded) {
+        mock_server(move |req| {
+            let query = req.uri().query().unwrap_or_default();
+            let resp = hyper::Response::builder();
+            match (req.method().as_str(), query) {
+                ("POST", "uploads") => resp.body(hyper::Body::from(
+                    "<InitiateMultipartUploadResult><Bucket>test</Bucket><Key>file</Key>\
+                     <UploadId>upload-id</UploadId></InitiateMultipartUploadResult>",
+                )),
+                ("PUT", q) if fail_part.map(|v| q.contains(v)).unwrap_or_default() => resp
+                    .status(StatusCode::INTERNAL_SERVER_ERROR)
+                    .body(hyper::Body::empty()),
+                ("PUT", q) if q.contains("partNumber") => {
+                    let part = q
+                        .split('&')
+                        .next()
+                        .unwrap()
+                        .trim_start_matches("partNumber=");
+                    resp.header(http::header::ETAG, format!("\"etag-{}\"", part))
+                        .body(hyper::Body::empty())
+                }
+                ("DELETE", _) => resp
+                    .status(StatusCode::NO_CONTENT)
+                    .body(hyper::Body::empty()),
+                _ => resp.body(hyper::Body::empty()),
+            }
+            .unwrap()
+        })
+    }
+
+    #[tokio::test]
+    async fn test_multipart_api() -> Result<()> {
+        let (endpoint, requests) = multipart_mock(None);
+        let op = mock_s3_operator(&endpoint).await;
+        let o = op.object("dir/file");
+        let reader =
+            |n: usize| -> BoxedAsyncReader { Box::new(futures::io::Cursor::new(vec![0; n])) };
+
+        let upload_id = o.create_multipart().await?;
+        assert_eq!(upload_id, "upload-id");
+
+        // Parts could be written in any order.
+        let (p2, p1) = futures::try_join!(
+            o.write_multipart(&upload_id, 2, reader(3), 3),
+            o.write_multipart(&upload_id, 1, reader(5), 5),
+        )?;
+        assert_eq!(p1.etag, "\"etag-1\"");
+        assert_eq!(p2.etag, "\"etag-2\"");
+        let mut sizes = requests
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|req| req.method() == http::Method::PUT)
+            .map(|req| req.body().len())
+            .collect::<Vec<_>>();
+        sizes.sort();
+        assert_eq!(sizes, vec![3, 5]);
+        let sent = requests.lock().unwrap().len();
+
+        for part_number in [0, 10001] {
+            let err = o
+                .write_multipart(&upload_id, part_number, reader(1), 1)
+                .await
+                .unwrap_err();
+            assert_eq!(err.kind(), Kind::Unexpected);
+            assert!(err.to_string().contains("out of range"), "{}", err);
+        }
+        // Oversized parts are rejected before reading anything.
+        let err = o
+            .write_multipart(&upload_id, 3, reader(1), MAX_MULTIPART_PART_SIZE + 1)
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), Kind::Unexpected);
+        assert!(err.to_string().contains("max part size"), "{}", err);
+        for parts in [vec![], vec![p2.clone()], vec![p1.clone(), p1.clone()]] {
+            let err = o.complete_multipart(&upload_id, parts).await.unwrap_err();
+            assert_eq!(err.kind(), Kind::Unexpected);
+            assert!(err.to_string().contains("missing"), "{}", err);
+        }
+        assert_eq!(requests.lock().unwrap().len(), sent);
+
+        let meta = o.complete_multipart(&upload_id, vec![p2, p1]).await?;
+        assert_eq!(meta.path(), "dir/file");
+        {
+            let requests = requests.lock().unwrap();
+            let req = requests.last().unwrap();
+            assert_eq!(req.method(), http::Method::POST);
+            assert_eq!(req.uri().query(), Some("uploadId=upload-id"));
+            assert_eq!(
+                std::str::from_utf8(req.body()).unwrap(),
+                "<CompleteMultipartUpload>\
+                 <Part><PartNumber>1</PartNumber><ETag>\"etag-1\"</ETag></Part>\
+                 <Part><PartNumber>2</PartNumber><ETag>\"etag-2\"</ETag></Part>\
+                 </CompleteMultipartUpload>"
+            );
+        }
+
+        o.abort_multipart(&upload_id).await?;
+        let requests = requests.lock().unwrap();
+        let req = requests.last().unwrap();
+        assert_eq!(req.method(), http::Method::DELETE);
+        assert_eq!(req.uri().query(), Some("uploadId=upload-id"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_stat_invalid_last_modified() -> Result<()> {
+        let (endpoint, _) = mock_server(|_| {
+            hyper::Response::builder()
+                .header(http::header::CONTENT_LENGTH, "5")
+                .header(http::header::LAST_MODIFIED, "2016-04-30 23:51:29")
+                .body(hyper::Body::empty())
+                .unwrap()
+        });
+        let op = mock_s3_operator(&endpoint).await;
+
+        let err = op.object("file").metadata().await.unwrap_err();
+        assert_eq!(err.kind(), Kind::Unexpected);
+        assert_eq!(err.context()["header"], "last-modified");
+        assert!(err.to_string().contains("2016-04-30 23:51:29"), "{}", err);
+
+        Ok(())
+    }
+
+    async fn multipart_operator(endpoint: &str) -> Result<Operator> {
+        let mut builder = Backend::build();
+        builder
+            .bucket("test")
+            .endpoint(endpoint)
+            .region("us-east-1")
+            .multipart_threshold(MIN_MULTIPART_PART_SIZE)
+            .multipart_part_size(MIN_MULTIPART_PART_SIZE)
+            .credential(Credential::hmac("access_key_id", "secret_access_key"));
+
+        Ok(Operator::new(builder.finish().await?))
+    }
+
+    #[tokio::test]
+    async fn test_invalid_headers() -> Result<()> {
+        // Every response carries headers which are not valid ASCII.
+        let (endpoint, _) = mock_server(|req| {
+            let invalid = HeaderValue::from_bytes(b"\xff\xfe").unwrap();
+            let mut resp = hyper::Response::builder();
+            for name in [
+                "etag",
+                "content-type",
+                "content-md5",
+                "content-encoding",
+                "content-range",
+                "x-amz-version-id",
+                "x-amz-bucket-region",
+            ] {
+                resp = resp.header(name, invalid.clone());
+            }
+            let query = req.uri().query().unwrap_or_default();
+            match (req.method().as_str(), query) {
+                ("HEAD", _) => resp.header(http::header::CONTENT_LENGTH, "5"),
+                ("GET", q) if q.contains("list-type=2") => {
+                    return resp
+                        .body(hyper::Body::from(
+                            "<ListBucketResult><IsTruncated>false</IsTruncated>\
+                             <Contents><Key>file</Key>\
+                             <Size>5</Size></Contents></ListBucketResult>",
+                        ))
+                        .unwrap()
+                }
+                ("GET", _) => return resp.body(hyper::Body::from("Hello")).unwrap(),
+                ("POST", "uploads") => {
+                    return resp
+                        .body(hyper::Body::from(
+                            "<InitiateMultipartUploadResult>\
+                             <UploadId>upload-id</UploadId></InitiateMultipartUploadResult>",
+                        ))
+                        .unwrap()
+                }
+                ("DELETE", _) => resp.status(StatusCode::NO_CONTENT),
+                _ => resp,
+            }
+            .body(hyper::Body::empty())
+            .unwrap()
+        });
+
+        // Region detection.
+        let mut builder = Backend::build();
+        builder
+            .bucket("test")
+            .endpoint(&endpoint)
+            .credential(Credential::hmac("access_key_id", "secret_access_key"));
+        let err = builder.finish().await.unwrap_err();
+        assert_eq!(err.kind(), Kind::BackendConfigurationInvalid);
+
+        // Invalid optional headers are skipped.
+        let op = mock_s3_operator(&endpoint).await;
+        let meta = op.object("file").metadata().await?;
+        assert_eq!(meta.content_length(), 5);
+        assert_eq!(meta.etag(), None);
+        assert_eq!(meta.last_modified(), None);
+        assert_eq!(meta.content_type(), None);
+        assert_eq!(meta.version_id(), None);
+
+        let (s, meta) = op.object("file").read_with_metadata(None, None).await?;
+        let (bs, err) = collect(s).await;
+        assert!(err.is_none(), "{:?}", err);
+        assert_eq!(bs, b"Hello");
+        assert_eq!(meta.etag(), None);
+
+        let meta = op.object("file").writer().write_bytes(vec![0; 4]).await?;
+        assert_eq!(meta.etag(), None);
+
+        op.object("file").delete().await?;
+        let listed: Vec<Object> = op.objects("").try_collect().await?;
+        assert_eq!(listed.len(), 1);
+
+        // ETag of parts is required to complete the upload.
+        let err = multipart_operator(&endpoint)
+            .await?
+            .object("file")
+            .writer()
+            .write_bytes(vec![0; 2 * MIN_MULTIPART_PART_SIZE as usize])
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), Kind::Unexpected);
+        assert_eq!(err.context()["header"], "etag");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_request_path_and_sse() -> Result<()> {
+        let (endpoint, requests) = mock_server(|_| {
+            hyper::Response::builder()
+                .header(http::header::CONTENT_LENGTH, "4")
+                .body(hyper::Body::empty())
+                .unwrap()
+        });
+
+        // Stat on dirs will not send requests.
+        let paths = ["file", "/dir//file", "//a/b/c"];
+        for root in ["/", "/abc/", "abc//def"] {
+            let mut builder = Backend::build();
+            builder
+                .root(root)
+                .bucket("test")
+                .endpoint(&endpoint)
+                .region("us-east-1")
+                .server_side_encryption_with_customer_key("AES256", &[0; 32])
+                .credential(Credential::hmac("access_key_id", "secret_access_key"));
+            let op = Operator::new(builder.finish().await?);
+
+            for path in paths {
+                op.object(path).metadata().await?;
+
+                let req = requests.lock().unwrap().pop().unwrap();
+                // Keep consistent with `normalize_path` with root prepended.
+                let root = format!("/{}/", Backend::normalize_path(root).trim_matches('/'));
+                let root = root.replace("//", "/");
+                let expected = format!("{}{}", root, Backend::normalize_path(path));
+                let expected = format!("/test/{}", expected.trim_start_matches('/'));
+                assert_eq!(req.uri().path(), expected, "root {} path {}", root, path);
+
+                let key = req
+                    .headers()
+                    .get(constants::X_AMZ_SERVER_SIDE_ENCRYPTION_CUSTOMER_KEY)
+                    .unwrap();
+                assert_eq!(key, base64::encode([0; 32]).as_str());
+            }
+
+            // Paths escaping the root are rejected before sending requests.
+            for path in ["a/../../b", "../abc/file", "/../file", "./a", "a\0b"] {
+                let err = op.object(path).metadata().await.unwrap_err();
+                assert_eq!(err.kind(), Kind::ObjectPathInvalid, "path {}", path);
+            }
+            assert!(requests.lock().unwrap().is_empty());
+        }
+
+        let mut builder = Backend::build();
+        builder
+            .bucket("test")
+            .endpoint(&endpoint)
+            .region("us-east-1")
+            .server_side_encryption("AES256\n");
+        let err = builder.finish().await.unwrap_err();
+        assert_eq!(err.kind(), Kind::BackendConfigurationInvalid);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_read_conditional() -> Result<()> {
+        let (endpoint, requests) = mock_server(|req| {
+            let header = |name| req.headers().get(name).map(|v| v.to_str().unwrap());
+            let status = match (
+                header(http::header::IF_MATCH),
+                header(http::header::IF_NONE_MATCH),
+            ) {
+                (Some(v), _) if v != "\"v1\"" => StatusCode::PRECONDITION_FAILED,
+                (_, Some("\"v1\"")) => StatusCode::NOT_MODIFIED,
+                _ => StatusCode::OK,
+            };
+            hyper::Response::builder()
+                .status(status)
+                .body(hyper::Body::from(match status {
+                    StatusCode::OK => "data",
+                    _ => "",
+                }))
+                .unwrap()
+        });
+        let op = mock_s3_operator(&endpoint).await;
+        let read = |r: crate::Reader| async move {
+            let mut bs = Vec::new();
+            let mut r = r;
+            r.read_to_end(&mut bs)
+                .await
+                .map(|_| bs)
+                .map_err(|e| e.into_inner().unwrap().downcast::<Error>().unwrap().kind())
+        };
+
+        let o = op.object("file");
+        assert_eq!(read(o.reader()).await, Ok(b"data".to_vec()));
+        assert_eq!(
+            read(o.reader().if_match("\"v1\"")).await,
+            Ok(b"data".to_vec())
+        );
+        assert_eq!(
+            read(o.reader().if_match("\"v2\"")).await,
+            Err(Kind::ObjectPreconditionFailed)
+        );
+        assert_eq!(
+            read(o.reader().if_none_match("\"v2\"")).await,
+            Ok(b"data".to_vec())
+        );
+        assert_eq!(
+            read(o.reader().if_none_match("\"v1\"")).await,
+            Err(Kind::ObjectNotModified)
+        );
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 5);
+        assert!(requests[0].headers().get(http::header::IF_MATCH).is_none());
+        assert!(requests[0]
+            .headers()
+            .get(http::header::IF_NONE_MATCH)
+            .is_none());
+        assert_eq!(requests[2].headers()[http::header::IF_MATCH], "\"v2\"");
+        assert_eq!(requests[4].headers()[http::header::IF_NONE_MATCH], "\"v1\"");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_stat_conditional() -> Result<()> {
+        let (endpoint, requests) = mock_server(|req| {
+            let status = match req.headers().get(http::header::IF_MATCH) {
+                Some(v) if v != "\"v1\"" => StatusCode::PRECONDITION_FAILED,
+                _ => StatusCode::OK,
+            };
+            hyper::Response::builder()
+                .status(status)
+                .header(http::header::CONTENT_LENGTH, "4")
+                .header(http::header::ETAG, "\"v1\"")
+                .body(hyper::Body::empty())
+                .unwrap()
+        });
+        let op = mock_s3_operator(&endpoint).await;
+
+        let stat = |etag: &str| OpStat {
+            if_match: Some(etag.to_string()),
+            ..OpStat::new("file")
+        };
+        let meta = op.inner().stat(&stat("\"v1\"")).await?;
+        assert_eq!(meta.etag(), Some("v1".to_string()));
+        let err = op.inner().stat(&stat("\"v2\"")).await.unwrap_err();
+        assert_eq!(err.kind(), Kind::ObjectPreconditionFailed);
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[1].method(), http::Method::HEAD);
+        assert_eq!(requests[1].headers()[http::header::IF_MATCH], "\"v2\"");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_read_response_overrides() -> Result<()> {
+        let (endpoint, requests) = mock_server(|_| {
+            hyper::Response::builder()
+                .header(http::header::CONTENT_DISPOSITION, "attachment")
+                .body(hyper::Body::from("data"))
+                .unwrap()
+        });
+        let op = mock_s3_operator(&endpoint).await;
+
+        let overrides = ResponseOverrides {
+            content_type: Some("text/plain;charset=utf-8".to_string()),
+            content_disposition: Some("attachment;filename=\"%E6%96%87.txt\"".to_string()),
+            ..Default::default()
+        };
+        let mut bs = Vec::new();
+        op.object("dir/file")
+            .reader()
+            .response_overrides(overrides)
+            .read_to_end(&mut bs)
+            .await
+            .unwrap();
+        assert_eq!(bs, b"data");
+
+        // Values that can't be signed correctly are rejected before sending.
+        for v in [
+            "text/plain; charset=utf-8",
+            "attachment;filename*=UTF-8''%E6%96%87.txt",
+            "~",
+        ] {
+            let err = op
+                .object("dir/file")
+                .reader()
+                .response_overrides(ResponseOverrides {
+                    content_type: Some(v.to_string()),
+                    ..Default::default()
+                })
+                .read_to_end(&mut bs)
+                .await
+                .unwrap_err();
+            let err = err.into_inner().unwrap().downcast::<Error>().unwrap();
+            assert_eq!(err.kind(), Kind::Unsupported, "{}", v);
+        }
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].uri().path(), "/test/dir/file");
+        assert_eq!(
+            requests[0].uri().query(),
+            Some(
+                "response-content-type=text%2Fplain%3Bcharset%3Dutf-8\
+                 &response-content-disposition=attachment%3Bfilename%3D%22%25E6%2596%2587.txt%22"
+            )
+        );
+        assert!(requests[0]
+            .headers()
+            .contains_key(http::header::AUTHORIZATION));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_presign() -> Result<()> {
+        let (endpoint, requests) = mock_server(|_| {
+            hyper::Response::builder()
+                .status(StatusCode::INTERNAL_SERVER_ERROR)
+                .body(hyper::Body::empty())
+                .unwrap()
+        });
+        let mut builder = Backend::build();
+        builder
+            .bucket("test")
+            .endpoint(&endpoint)
+            .region("us-east-1")
+            .server_side_encryption_with_customer_key("AES256", &[0; 32])
+            .credential(Credential::hmac("access_key_id", "secret_access_key"));
+        let acc = builder.finish().await?;
+        let query = |req: &PresignedRequest| -> HashMap<String, String> {
+            req.uri
+                .query()
+                .unwrap_or_default()
+                .split('&')
+                .map(|kv| {
+                    let (k, v) = kv.split_once('=').unwrap();
+                    (k.to_string(), v.to_string())
+                })
+                .collect()
+        };
+
+        let mut op = OpPresign::new("dir/a b", Method::GET, Duration::from_secs(3600));
+        op.response_overrides.content_disposition = Some("attachment".to_string());
+        let req = acc.presign(&op)?;
+        assert_eq!(req.method, Method::GET);
+        assert_eq!(req.uri.path(), "/test/dir/a%20b");
+        let params = query(&req);
+        assert_eq!(params["X-Amz-Algorithm"], "AWS4-HMAC-SHA256");
+        assert_eq!(params["X-Amz-Expires"], "3600");
+        assert_eq!(params["X-Amz-Signature"].len(), 64);
+        assert_eq!(params["response-content-disposition"], "attachment");
+        assert!(params["X-Amz-Credential"].starts_with("access_key_id%2F"));
+
+        // SSE-C headers are signed and must be sent by clients.
+        let req = acc.presign(&OpPresign::new(
+            "dir/a b",
+            Method::PUT,
+            Duration::from_secs(60),
+        ))?;
+        assert_eq!(
+            query(&req)["X-Amz-SignedHeaders"],
+            "host%3Bx-amz-server-side-encryption-customer-algorithm\
+             %3Bx-amz-server-side-encryption-customer-key\
+             %3Bx-amz-server-side-encryption-customer-key-md5"
+        );
+        assert_eq!(
+            req.headers[constants::X_AMZ_SERVER_SIDE_ENCRYPTION_CUSTOMER_KEY],
+            base64::encode([0; 32]).as_str()
+        );
+
+        let req = acc.presign(&OpPresign::new(
+            "dir/a b",
+            Method::HEAD,
+            Duration::from_secs(60),
+        ))?;
+        assert_eq!(req.method, Method::HEAD);
+
+        for op in [
+            OpPresign::new("dir/a b", Method::DELETE, Duration::from_secs(60)),
+            OpPresign::new("dir/a b", Method::GET, Duration::from_secs(8 * 24 * 3600)),
+            OpPresign::new("dir/a b", Method::GET, Duration::ZERO),
+        ] {
+            let err = acc.presign(&op).unwrap_err();
+            assert_eq!(err.kind(), Kind::Unsupported, "{:?}", op);
+        }
+
+        // Presign never sends requests.
+        assert!(requests.lock().unwrap().is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_stat_version_id() -> Result<()> {
+        let (endpoint, _) = mock_server(|req| {
+            let resp = hyper::Response::builder();
+            match req.uri().path() {
+                // Objects of versioning enabled buckets.
+                "/test/dir/versioned" => resp
+                    .header(constants::X_AMZ_VERSION_ID, "v1")
+                    .header(http::header::CONTENT_LENGTH, 3)
+                    .body(hyper::Body::empty()),
+                // Objects of buckets whose versioning is off.
+                "/test/dir/plain" => resp
+                    .header(http::header::CONTENT_LENGTH, 3)
+                    .body(hyper::Body::empty()),
+                _ => resp
+                    .status(StatusCode::METHOD_NOT_ALLOWED)
+                    .header(constants::X_AMZ_DELETE_MARKER, "true")
+                    .body(hyper::Body::empty()),
+            }
+            .unwrap()
+        });
+        let op = mock_s3_operator(&endpoint).await;
+
+        let meta = op.object("dir/versioned").metadata().await?;
+        assert_eq!(meta.version_id().as_deref(), Some("v1"));
+        let meta = op.object("dir/plain").metadata().await?;
+        assert_eq!(meta.version_id(), None);
+
+        // Delete markers are not objects.
+        let err = op.object("dir/deleted").metadata().await.unwrap_err();
+        assert_eq!(err.kind(), Kind::ObjectNotExist);
+        assert!(!op.object("dir/deleted").is_exist().await?);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_read_version() -> Result<()> {
+        let (endpoint, requests) = mock_server(|req| {
+            let version = req
+                .uri()
+                .query()
+                .unwrap_or_default()
+                .split('&')
+                .find_map(|v| v.strip_prefix("versionId="))
+                .unwrap_or_default();
+            let resp = hyper::Response::builder();
+            match version {
+                "" | "v1" => resp
+                    .header(constants::X_AMZ_VERSION_ID, "v1")
+                    .header(http::header::CONTENT_LENGTH, 3)
+                    .body(hyper::Body::from(if req.method() == http::Method::HEAD {
+                        ""
+                    } else {
+                        "old"
+                    })),
+                "dm" => resp
+                    .status(StatusCode::METHOD_NOT_ALLOWED)
+                    .header(constants::X_AMZ_DELETE_MARKER, "true")
+                    .body(hyper::Body::empty()),
+                "bad" => resp.status(StatusCode::BAD_REQUEST).body(hyper::Body::from(
+                    "<Error><Code>InvalidArgument</Code><Message>Invalid version id specified</Message>\
+                     <ArgumentName>versionId</ArgumentName></Error>",
+                )),
+                _ => resp.status(StatusCode::NOT_FOUND).body(hyper::Body::from(
+                    "<Error><Code>NoSuchVersion</Code></Error>",
+                )),
+            }
+            .unwrap()
+        });
+        let op = mock_s3_operator(&endpoint).await;
+
+        let mut bs = vec![];
+        op.object("dir/file")
+            .reader()
+            .version("v1")
+            .read_to_end(&mut bs)
+            .await
+            .unwrap();
+        assert_eq!(bs, b"old");
+        let meta = op.object("dir/file").version_metadata("v1").await?;
+        assert_eq!(meta.version_id().as_deref(), Some("v1"));
+        assert_eq!(meta.content_length(), 3);
+        // Latest version has its version id too.
+        let meta = op.object("dir/file").metadata().await?;
+        assert_eq!(meta.version_id().as_deref(), Some("v1"));
+
+        {
+            let requests = requests.lock().unwrap();
+            let summary = requests
+                .iter()
+                .map(|v| (v.method().to_string(), v.uri().to_string()))
+                .collect::<Vec<_>>();
+            assert_eq!(
+                summary,
+                vec![
+                    ("GET".to_string(), "/test/dir/file?versionId=v1".to_string()),
+                    (
+                        "HEAD".to_string(),
+                        "/test/dir/file?versionId=v1".to_string()
+                    ),
+                    ("HEAD".to_string(), "/test/dir/file".to_string()),
+                ]
+            );
+            assert!(requests
+                .iter()
+                .all(|v| v.headers().contains_key(http::header::AUTHORIZATION)));
+        }
+
+        for version in ["missing", "dm", "bad"] {
+            // HEAD responses have no body to tell malformed version ids
+            // from other bad requests.
+            if version != "bad" {
+                let err = op
+                    .object("dir/file")
+                    .version_metadata(version)
+                    .await
+                    .unwrap_err();
+                assert_eq!(err.kind(), Kind::ObjectNotExist, "{}", version);
+            }
+
+            let err = op
+                .object("dir/file")
+                .reader()
+                .version(version)
+                .read_to_end(&mut bs)
+                .await
+                .unwrap_err();
+            let err = err.into_inner().unwrap().downcast::<Error>().unwrap();
+            assert_eq!(err.kind(), Kind::ObjectNotExist, "{}", version);
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_stat_content_type() -> Result<()> {
+        let (endpoint, _) = mock_server(|req| {
+            let content_type = if req.uri().path().ends_with('/') {
+                "application/x-directory"
+            } else {
+                "text/plain"
+            };
+            hyper::Response::builder()
+                .header(http::header::CONTENT_TYPE, content_type)
+                .header(http::header::CONTENT_LENGTH, "0")
+                .body(hyper::Body::empty())
+                .unwrap()
+        });
+        let op = mock_s3_operator(&endpoint).await;
+
+        let meta = op.object("dir/file").metadata().await?;
+        assert_eq!(meta.content_type(), Some("text/plain"));
+
+        let meta = op.object("dir/").metadata().await?;
+        assert_eq!(meta.mode(), ObjectMode::DIR);
+        assert_eq!(meta.content_type(), None);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_read_with_metadata() -> Result<()> {
+        let content = "Hello, World!";
+        let (endpoint, requests) = mock_server(move |req| {
+            let resp = hyper::Response::builder()
+                .header(http::header::ETAG, "\"etag\"")
+                .header(http::header::CONTENT_TYPE, "text/plain")
+                .header(http::header::LAST_MODIFIED, "Sun, 01 May 2016 00:51:29 GMT")
+                .header("x-amz-meta-foo", "bar");
+            let range = req
+                .headers()
+                .get(http::header::RANGE)
+                .map(|v| v.to_str().unwrap().to_string());
+            match (req.method(), range.as_deref()) {
+                (&http::Method::HEAD, _) => resp
+                    .header(http::header::CONTENT_LENGTH, content.len())
+                    .body(hyper::Body::empty()),
+                (_, None) => resp.body(hyper::Body::from(content)),
+                (_, Some("bytes=13-")) => resp
+                    .status(StatusCode::RANGE_NOT_SATISFIABLE)
+                    .header(http::header::CONTENT_RANGE, "bytes */13")
+                    .body(hyper::Body::empty()),
+                (_, Some(range)) => {
+                    assert_eq!(range, "bytes=7-10");
+                    resp.status(StatusCode::PARTIAL_CONTENT)
+                        .header(http::header::CONTENT_RANGE, "bytes 7-10/13")
+                        .body(hyper::Body::from(&content[7..11]))
+                }
+            }
+            .unwrap()
+        });
+        let op = mock_s3_operator(&endpoint).await;
+        let o = op.object("dir/file");
+
+        let cases = vec![
+            ("full", None, None, "Hello, World!"),
+            ("range", Some(7), Some(4), "Worl"),
+            ("end of object", Some(13), None, ""),
+            ("zero size", None, Some(0), ""),
+        ];
+        for (name, offset, size, expected) in cases {
+            let (mut s, meta) = o.read_with_metadata(offset, size).await?;
+            let mut bs = Vec::new();
+            while let Some(v) = s.next().await {
+                bs.extend_from_slice(&v?);
+            }
+            assert_eq!(bs, expected.as_bytes(), "{}", name);
+            assert_eq!(meta.path(), "dir/file", "{}", name);
+            assert_eq!(meta.content_length(), 13, "{}", name);
+            assert_eq!(meta.etag().as_deref(), Some("etag"), "{}", name);
+            assert_eq!(meta.content_type(), Some("text/plain"));
+            assert_eq!(meta.last_modified_ms(), Some(1462063889000), "{}", name);
+            assert_eq!(meta.user_metadata().get("foo").unwrap(), "bar");
+            assert_eq!(meta.mode(), ObjectMode::FILE, "{}", name);
+        }
+
+        // Metadata comes from the read response, except when it carries
+        // none: reads at the end of object and reads without request.
+        let requests = requests.lock().unwrap();
+        let methods = requests
+            .iter()
+            .map(|v| v.method().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(methods, vec!["GET", "GET", "GET", "HEAD", "HEAD"]);
+
+        Ok(())
+    }
+
+    /// Serve `content` with range support, the etag of the object is decided
+    /// by `etag` with the count of requests served before.
+    fn range_mock(
+        content: Bytes,
+        etag: fn(usize) -> &'static str,
+    ) -> (String, crate::tests::mock::Recorded) {
+        let served = AtomicUsize::new(0);
+        mock_server(move |req| {
+            let etag = etag(served.fetch_add(1, Ordering::SeqCst));
+            let resp = hyper::Response::builder().header(http::header::ETAG, etag);
+            if let Some(v) = req.headers().get(http::header::IF_MATCH) {
+                if v != etag {
+                    return resp
+                        .status(StatusCode::PRECONDITION_FAILED)
+                        .body(hyper::Body::from(
+                            "<Error><Code>PreconditionFailed</Code></Error>",
+                        ))
+                        .unwrap();
+                }
+            }
+
+            let range = match req.headers().get(http::header::RANGE) {
+                Some(v) => v.to_str().unwrap().strip_prefix("bytes=").unwrap(),
+                None => return resp.body(hyper::Body::from(content.clone())).unwrap(),
+            };
+            let (start, end) = range.split_once('-').unwrap();
+            let start = start.parse::<usize>().unwrap();
+            let end = match end {
+                "" => content.len(),
+                v => (v.parse::<usize>().unwrap() + 1).min(content.len()),
+            };
+            resp.status(StatusCode::PARTIAL_CONTENT)
+                .header(
+                    http::header::CONTENT_RANGE,
+                    format!("bytes {}-{}/{}", start, end - 1, content.len()),
+                )
+                .body(hyper::Body::from(content.slice(start..end)))
+                .unwrap()
+        })
+    }
+
+    async fn parallel_read_operator(endpoint: &str) -> Result<Operator> {
+        let mut builder = Backend::build();
+        builder
+            .bucket("test")
+            .endpoint(endpoint)
+            .region("us-east-1")
+            .read_concurrency(3)
+            .read_chunk_size(16)
+            .credential(Credential::hmac("access_key_id", "secret_access_key"));
+
+        Ok(Operator::new(builder.finish().await?))
+    }
+
+    #[tokio::test]
+    async fn test_parallel_read() -> Result<()> {
+        let content = Bytes::from((0..100).collect::<Vec<u8>>());
+        let (endpoint, requests) = range_mock(content.clone(), |_| "\"v1\"");
+        let op = parallel_read_operator(&endpoint).await?;
+
+        let cases = vec![
+            (
+                "whole object",
+                None,
+                None,
+                vec!["0-15", "16-31", "32-47", "48-63", "64-79", "80-95", "96-99"],
+            ),
+            ("range", Some(10), Some(40), vec!["10-25", "26-41", "42-49"]),
+            (
+                "first chunk covers the rest",
+                Some(90),
+                None,
+                vec!["90-105"],
+            ),
+            ("fit in a single chunk", Some(3), Some(16), vec!["3-18"]),
+        ];
+        for (name, offset, size, ranges) in cases {
+            requests.lock().unwrap().clear();
+
+            let mut s = op.object("dir/file").stream(offset, size).await?;
+            let mut bs = Vec::new();
+            while let Some(v) = s.next().await {
+                bs.extend_from_slice(&v?);
+            }
+            let start = offset.unwrap_or_default() as usize;
+            let end = size.map_or(content.len(), |v| start + v as usize);
+            assert_eq!(bs, &content[start..end], "{}", name);
+
+            // The first chunk is sent alone, others are sent concurrently
+            // in any order.
+            let requests = requests.lock().unwrap();
+            let mut actual = requests
+                .iter()
+                .map(|v| v.headers()[http::header::RANGE].to_str().unwrap())
+                .collect::<Vec<_>>();
+            let mut expected = ranges
+                .iter()
+                .map(|v| format!("bytes={}", v))
+                .collect::<Vec<_>>();
+            assert_eq!(actual[0], expected[0], "{}", name);
+            actual.sort_unstable();
+            expected.sort_unstable();
+            assert_eq!(actual, expected, "{}", name);
+            // Chunks are pinned to the etag of the first chunk.
+            assert!(!requests[0].headers().contains_key(http::header::IF_MATCH));
+            assert!(requests[1..]
+                .iter()
+                .all(|v| v.headers()[http::header::IF_MATCH] == "\"v1\""));
+        }
+
+        // Metadata comes from the first chunk.
+        let (_, meta) = op.object("dir/file").read_with_metadata(None, None).await?;
+        assert_eq!(meta.content_length(), 100);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_parallel_read_replaced() -> Result<()> {
+        // The object is replaced after the first chunk has been served.
+        let content = Bytes::from((0..100).collect::<Vec<u8>>());
+        let (endpoint, _) = range_mock(content, |n| if n == 0 { "\"v1\"" } else { "\"v2\"" });
+        let op = parallel_read_operator(&endpoint).await?;
+
+        let mut s = op.object("dir/file").stream(None, None).await?;
+        let mut err = None;
+        while let Some(v) = s.next().await {
+            if let Err(e) = v {
+                err = Some(e);
+                break;
+            }
+        }
+        assert_eq!(err.unwrap().kind(), Kind::ObjectPreconditionFailed);
+
+        let mut builder = Backend::build();
+        builder.bucket("test").read_concurrency(0);
+        let err = builder.finish().await.unwrap_err();
+        assert_eq!(err.kind(), Kind::BackendConfigurationInvalid);
+
+        Ok(())
+    }
+
+    async fn resume_operator(endpoint: &str, retries: usize) -> Result<Operator> {
+        let mut builder = Backend::build();
+        builder
+            .bucket("test")
+            .endpoint(endpoint)
+            .region("us-east-1")
+            .read_resume_retries(retries)
+            .credential(Credential::hmac("access_key_id", "secret_access_key"));
+
+        Ok(Operator::new(builder.finish().await?))
+    }
+
+    /// Collect the stream until it ends or fails.
+    async fn collect(mut s: BytesStream) -> (Vec<u8>, Option<Error>) {
+        let mut bs = Vec::new();
+        while let Some(v) = s.next().await {
+            match v {
+                Ok(v) => bs.extend_from_slice(&v),
+                Err(e) => return (bs, Some(e)),
+            }
+        }
+        (bs, None)
+    }
+
+    #[tokio::test]
+    async fn test_read_resume() -> Result<()> {
+        let content: Vec<u8> = (0..100).collect();
+        let object = Arc::new(Mutex::new((content.clone(), "\"v1\"".to_string())));
+        // The first 2 responses break after 10 bytes.
+        let (endpoint, heads) = mock_flaky_server(object, 10, 2);
+        let op = resume_operator(&endpoint, 3).await?;
+
+        let s = op.object("file").stream(Some(5), Some(50)).await?;
+        let (bs, err) = collect(s).await;
+        assert!(err.is_none(), "{:?}", err);
+        assert_eq!(bs, content[5..55]);
+
+        // Resumed reads keep the original end, and are pinned to the etag.
+        let heads = heads.lock().unwrap();
+        let header = |head: &str, name: &str| {
+            head.lines()
+                .find_map(|l| l.strip_prefix(&format!("{}: ", name)))
+                .map(|v| v.to_string())
+        };
+        let ranges: Vec<_> = heads.iter().map(|h| header(h, "range")).collect();
+        assert_eq!(
+            ranges,
+            vec![
+                Some("bytes=5-54".to_string()),
+                Some("bytes=15-54".to_string()),
+                Some("bytes=25-54".to_string()),
+            ]
+        );
+        assert_eq!(header(&heads[0], "if-match"), None);
+        assert_eq!(header(&heads[1], "if-match"), Some("\"v1\"".to_string()));
+        assert_eq!(header(&heads[2], "if-match"), Some("\"v1\"".to_string()));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_read_suffix() -> Result<()> {
+        let content: Vec<u8> = (0..100).collect();
+        let object = Arc::new(Mutex::new((content.clone(), "\"v1\"".to_string())));
+        // The first response breaks after 10 bytes.
+        let (endpoint, heads) = mock_flaky_server(object.clone(), 10, 1);
+        let op = resume_operator(&endpoint, 3).await?;
+
+        let s = op.object("file").stream(None, Some(30)).await?;
+        let (bs, err) = collect(s).await;
+        assert!(err.is_none(), "{:?}", err);
+        assert_eq!(bs, content[70..]);
+
+        // Suffixes longer than the object return the whole object.
+        let (s, meta) = op
+            .object("file")
+            .read_with_metadata(None, Some(1000))
+            .await?;
+        let (bs, err) = collect(s).await;
+        assert!(err.is_none(), "{:?}", err);
+        assert_eq!(bs, content);
+        assert_eq!(meta.content_length(), 100);
+
+        // Suffix reads are resumed from where they broke by the range they
+        // turned out to be.
+        let ranges: Vec<_> = heads
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|head| {
+                head.lines()
+                    .find_map(|l| l.strip_prefix("range: "))
+                    .map(|v| v.to_string())
+            })
+            .collect();
+        assert_eq!(
+            ranges,
+            vec![
+                Some("bytes=-30".to_string()),
+                Some("bytes=80-".to_string()),
+                Some("bytes=-1000".to_string()),
+            ]
+        );
+
+        // Suffix reads on empty objects are empty.
+        object.lock().unwrap().0.clear();
+        let s = op.object("file").stream(None, Some(30)).await?;
+        let (bs, err) = collect(s).await;
+        assert!(err.is_none(), "{:?}", err);
+        assert!(bs.is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_read_decompress() -> Result<()> {
+        let content = "Hello, World!".repeat(100);
+        // Multiple gzip members concatenated.
+        let mut encoded = gzip(&content[..600]);
+        encoded.extend(gzip(&content[600..]));
+        let (served, plain) = (encoded.clone(), content.clone());
+        let (endpoint, _) = mock_server(move |req| {
+            let (encoding, body) = match req.uri().path() {
+                "/test/gzip" => ("gzip", served.clone()),
+                "/test/corrupted" => ("gzip", served[..served.len() - 4].to_vec()),
+                _ => ("identity", plain.clone().into_bytes()),
+            };
+            hyper::Response::builder()
+                .header(http::header::CONTENT_ENCODING, encoding)
+                .header(http::header::CONTENT_LENGTH, body.len())
+                .body(hyper::Body::from(body))
+                .unwrap()
+        });
+
+        let mut builder = Backend::build();
+        builder
+            .bucket("test")
+            .endpoint(&endpoint)
+            .region("us-east-1")
+            .credential(Credential::hmac("access_key_id", "secret_access_key"));
+        let raw = Operator::new(builder.finish().await?);
+        builder.enable_read_decompress(true);
+        let op = Operator::new(builder.finish().await?);
+
+        let (s, meta) = op.object("gzip").read_with_metadata(None, None).await?;
+        let (bs, err) = collect(s).await;
+        assert!(err.is_none(), "{:?}", err);
+        assert_eq!(bs, content.as_bytes());
+        assert_eq!(meta.content_encoding(), Some("gzip".to_string()));
+        assert_eq!(meta.try_content_length(), None);
+        assert!(!meta.complete());
+
+        // Objects without encoding are not touched.
+        let (s, meta) = op.object("plain").read_with_metadata(None, None).await?;
+        let (bs, err) = collect(s).await;
+        assert!(err.is_none(), "{:?}", err);
+        assert_eq!(bs, content.as_bytes());
+        assert_eq!(meta.try_content_length(), Some(content.len() as u64));
+
+        // Encoded bytes are returned as they are if not enabled.
+        let s = raw.object("gzip").stream(None, None).await?;
+        let (bs, err) = collect(s).await;
+        assert!(err.is_none(), "{:?}", err);
+        assert_eq!(bs, encoded);
+
+        let s = op.object("corrupted").stream(None, None).await?;
+        let (_, err) = collect(s).await;
+        assert_eq!(err.unwrap().kind(), Kind::ObjectCorrupted);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_read_resume_exhausted() -> Result<()> {
+        let content: Vec<u8> = (0..100).collect();
+        for (retries, read) in [(0, 10), (2, 30)] {
+            let object = Arc::new(Mutex::new((content.clone(), "\"v1\"".to_string())));
+            let (endpoint, heads) = mock_flaky_server(object, 10, usize::MAX);
+            let op = resume_operator(&endpoint, retries).await?;
+
+            let s = op.object("file").stream(None, None).await?;
+            let (bs, err) = collect(s).await;
+            assert_eq!(bs, content[..read], "retries: {}", retries);
+            let err = err.unwrap();
+            assert_eq!(err.kind(), Kind::ObjectReadInterrupted);
+            assert_eq!(err.context()["offset"], read.to_string());
+            assert_eq!(
+                err.context().get("retries"),
+                (retries > 0).then(|| retries.to_string()).as_ref()
+            );
+            assert_eq!(heads.lock().unwrap().len(), retries + 1);
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_read_resume_changed() -> Result<()> {
+        let content: Vec<u8> = (0..100).collect();
+        let object = Arc::new(Mutex::new((content.clone(), "\"v1\"".to_string())));
+        let (endpoint, _) = mock_flaky_server(object.clone(), 10, 1);
+        let op = resume_operator(&endpoint, 3).await?;
+
+        let mut s = op.object("file").stream(None, None).await?;
+        let bs = s.next().await.unwrap()?;
+        assert_eq!(bs, content[..10]);
+
+        // The object is replaced before the read is resumed.
+        *object.lock().unwrap() = (vec![1; 100], "\"v2\"".to_string());
+        let (bs, err) = collect(s).await;
+        assert!(bs.is_empty());
+        let err = err.unwrap();
+        assert_eq!(err.kind(), Kind::ObjectChanged);
+        assert_eq!(err.context()["offset"], "10");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_read_time_conditional() -> Result<()> {
+        // Last modified of the object, s3 compares in seconds.
+        let modified = OffsetDateTime::from_unix_timestamp(1462060289).unwrap();
+        let (endpoint, requests) = mock_server(move |req| {
+            // Invalid dates are ignored like s3 does.
+            let header = |name| {
+                req.headers()
+                    .get(name)
+                    .and_then(|v| parse_datetime(v.to_str().unwrap()).ok())
+            };
+            let status = match (
+                header(http::header::IF_MODIFIED_SINCE),
+                header(http::header::IF_UNMODIFIED_SINCE),
+            ) {
+                (Some(t), _) if modified <= t => StatusCode::NOT_MODIFIED,
+                (_, Some(t)) if modified > t => StatusCode::PRECONDITION_FAILED,
+                _ => StatusCode::OK,
+            };
+            hyper::Response::builder()
+                .status(status)
+                .body(hyper::Body::empty())
+                .unwrap()
+        });
+        let op = mock_s3_operator(&endpoint).await;
+        let read = |r: crate::Reader| async move {
+            let mut r = r;
+            r.read_to_end(&mut Vec::new())
+                .await
+                .map(|_| ())
+                .map_err(|e| e.into_inner().unwrap().downcast::<Error>().unwrap().kind())
+        };
+        let o = op.object("file");
+        let ms = time::Duration::milliseconds;
+
+        // Checkpoints after the modification within the same second.
+        for t in [modified, modified + ms(1), modified + ms(999)] {
+            assert_eq!(
+                read(o.reader().if_modified_since(t)).await,
+                Err(Kind::ObjectNotModified),
+                "{}",
+                t
+            );
+            assert_eq!(
+                read(o.reader().if_unmodified_since(t)).await,
+                Ok(()),
+                "{}",
+                t
+            );
+        }
+        // Checkpoints before the modification.
+        for t in [modified - ms(1), modified - ms(1000)] {
+            assert_eq!(read(o.reader().if_modified_since(t)).await, Ok(()), "{}", t);
+            assert_eq!(
+                read(o.reader().if_unmodified_since(t)).await,
+                Err(Kind::ObjectPreconditionFailed),
+                "{}",
+                t
+            );
+        }
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(
+            requests[2].headers()[http::header::IF_MODIFIED_SINCE],
+            "Sat, 30 Apr 2016 23:51:29 GMT"
+        );
+        assert_eq!(
+            requests[6].headers()[http::header::IF_MODIFIED_SINCE],
+            "Sat, 30 Apr 2016 23:51:28 GMT"
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_conditional_write() -> Result<()> {
+        let (endpoint, requests) = mock_server(|req| {
+            let query = req.uri().query().unwrap_or_default();
+            let resp = hyper::Response::builder();
+            match (req.method().as_str(), query) {
+                ("POST", "uploads") => resp.body(hyper::Body::from(
+                    "<InitiateMultipartUploadResult><UploadId>upload-id</UploadId></InitiateMultipartUploadResult>",
+                )),
+                // Concurrent conditional writes conflict while completing.
+                ("POST", _) => resp.body(hyper::Body::from(
+                    "<Error><Code>ConditionalRequestConflict</Code><Message></Message></Error>",
+                )),
+                ("PUT", "") => resp
+                    .status(StatusCode::PRECONDITION_FAILED)
+                    .body(hyper::Body::empty()),
+                ("DELETE", _) => resp
+                    .status(StatusCode::NO_CONTENT)
+                    .body(hyper::Body::empty()),
+                _ => resp
+                    .header(http::header::ETAG, "\"part\"")
+                    .body(hyper::Body::empty()),
+            }
+            .unwrap()
+        });
+        let op = multipart_operator(&endpoint).await?;
+        assert!(op.metadata().conditional_write());
+
+        let err = op
+            .object("small")
+            .writer()
+            .if_none_match("*")
+            .write_bytes(vec![0; 4])
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), Kind::ObjectPreconditionFailed);
+
+        let err = op
+            .object("large")
+            .writer()
+            .if_match("\"etag\"")
+            .write_bytes(vec![0; MIN_MULTIPART_PART_SIZE as usize + 1])
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), Kind::ObjectPreconditionFailed);
+
+        op.inner()
+            .delete(&OpDelete {
+                path: "small".to_string(),
+                if_match: Some("\"etag\"".to_string()),
+                ..Default::default()
+            })
+            .await?;
+
+        let requests = requests.lock().unwrap();
+        let conditions = requests
+            .iter()
+            .map(|v| {
+                let header = |name| v.headers().get(name).map(|v| v.to_str().unwrap());
+                (
+                    v.method().as_str(),
+                    header(http::header::IF_MATCH),
+                    header(http::header::IF_NONE_MATCH),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            conditions,
+            vec![
+                ("PUT", None, Some("*")),
+                // Only the completion of multipart upload is conditional.
+                ("POST", None, None),
+                ("PUT", None, None),
+                ("PUT", None, None),
+                ("POST", Some("\"etag\""), None),
+                // Abort the failed upload.
+                ("DELETE", None, None),
+                ("DELETE", Some("\"etag\""), None),
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_multipart_upload() -> Result<()> {
+        let (endpoint, requests) = multipart_mock(None);
+        let op = multipart_operator(&endpoint).await?;
+
+        // Small writes still use a single PUT.
+        op.object("small").writer().write_bytes(vec![0; 4]).await?;
+
+        let size = 2 * MIN_MULTIPART_PART_SIZE as usize + 1024;
+        let meta = op
+            .object("file")
+            .writer()
+            .storage_class("STANDARD_IA")
+            .write_bytes(vec![1; size])
+            .await?;
+        assert_eq!(meta.content_length(), size as u64);
+
+        let requests = requests.lock().unwrap();
+        let summary = requests
+            .iter()
+            .map(|v| {
+                (
+                    v.method().to_string(),
+                    v.uri().to_string(),
+                    v.body().len() as u64,
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            vec![
+                ("PUT".to_string(), "/test/small".to_string(), 4),
+                ("POST".to_string(), "/test/file?uploads".to_string(), 0),
+                (
+                    "PUT".to_string(),
+                    "/test/file?partNumber=1&uploadId=upload-id".to_string(),
+                    MIN_MULTIPART_PART_SIZE
+                ),
+                (
+                    "PUT".to_string(),
+                    "/test/file?partNumber=2&uploadId=upload-id".to_string(),
+                    MIN_MULTIPART_PART_SIZE
+                ),
+                (
+                    "PUT".to_string(),
+                    "/test/file?partNumber=3&uploadId=upload-id".to_string(),
+                    1024
+                ),
+                (
+                    "POST".to_string(),
+                    "/test/file?uploadId=upload-id".to_string(),
+                    requests[5].body().len() as u64
+                ),
+            ]
+        );
+        assert_eq!(
+            requests[1].headers().get("x-amz-storage-class").unwrap(),
+            "STANDARD_IA"
+        );
+        assert_eq!(
+            String::from_utf8_lossy(requests[5].body()),
+            "<CompleteMultipartUpload>\
+             <Part><PartNumber>1</PartNumber><ETag>\"etag-1\"</ETag></Part>\
+             <Part><PartNumber>2</PartNumber><ETag>\"etag-2\"</ETag></Part>\
+             <Part><PartNumber>3</PartNumber><ETag>\"etag-3\"</ETag></Part>\
+             </CompleteMultipartUpload>"
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_write_unknown_size() -> Result<()> {
+        let (endpoint, requests) = multipart_mock(None);
+        let op = multipart_operator(&endpoint).await?;
+
+        let part = MIN_MULTIPART_PART_SIZE as usize;
+        for (path, size) in [
+            ("small", 4),
+            ("large", 2 * part + 1024),
+            ("exact", 2 * part),
+        ] {
+            let r = Box::new(futures::io::Cursor::new(vec![1; size]));
+            let n = op.object(path).writer().write_stream(r).await?;
+            assert_eq!(n.content_length(), size as u64, "{}", path);
+        }
+
+        let requests = requests.lock().unwrap();
+        let puts = requests
+            .iter()
+            .filter(|v| v.method() == http::Method::PUT)
+            .map(|v| {
+                let length = v
+                    .headers()
+                    .get(http::header::CONTENT_LENGTH)
+                    .map(|v| v.to_str().unwrap().to_string());
+                (v.uri().to_string(), v.body().len(), length)
+            })
+            .collect::<Vec<_>>();
+        let part_put = |path: &str, number: usize, size: usize| {
+            (
+                format!("/test/{}?partNumber={}&uploadId=upload-id", path, number),
+                size,
+                Some(size.to_string()),
+            )
+        };
+        assert_eq!(
+            puts,
+            vec![
+                // Content smaller than the threshold still uses a single PUT.
+                ("/test/small".to_string(), 4, Some("4".to_string())),
+                part_put("large", 1, part),
+                part_put("large", 2, part),
+                part_put("large", 3, 1024),
+                part_put("exact", 1, part),
+                part_put("exact", 2, part),
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_write_metadata() -> Result<()> {
+        let (endpoint, _) = mock_server(|req| {
+            let query = req.uri().query().unwrap_or_default();
+            let resp = hyper::Response::builder().header(constants::X_AMZ_VERSION_ID, "v1");
+            match (req.method().as_str(), query) {
+                ("POST", "uploads") => resp.body(hyper::Body::from(
+                    "<InitiateMultipartUploadResult><UploadId>upload-id</UploadId></InitiateMultipartUploadResult>",
+                )),
+                ("POST", _) => resp.body(hyper::Body::from(
+                    "<CompleteMultipartUploadResult><ETag>\"multipart-2\"</ETag></CompleteMultipartUploadResult>",
+                )),
+                _ => resp
+                    .header(http::header::ETAG, "\"etag\"")
+                    .body(hyper::Body::empty()),
+            }
+            .unwrap()
+        });
+        let op = multipart_operator(&endpoint).await?;
+
+        let meta = op.object("small").writer().write_bytes(vec![0; 4]).await?;
+        assert_eq!(meta.path(), "small");
+        assert_eq!(meta.mode(), ObjectMode::FILE);
+        assert_eq!(meta.content_length(), 4);
+        assert_eq!(meta.etag().as_deref(), Some("etag"));
+        assert_eq!(meta.version_id().as_deref(), Some("v1"));
+
+        let size = MIN_MULTIPART_PART_SIZE + 1;
+        let meta = op
+            .object("large")
+            .writer()
+            .write_bytes(vec![0; size as usize])
+            .await?;
+        assert_eq!(meta.content_length(), size);
+        // The etag of the whole object instead of the last part.
+        assert_eq!(meta.etag().as_deref(), Some("multipart-2"));
+        assert_eq!(meta.version_id().as_deref(), Some("v1"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_multipart_upload_abort() -> Result<()> {
+        let (endpoint, requests) = multipart_mock(Some("partNumber=2"));
+        let op = multipart_operator(&endpoint).await?;
+
+        let size = 2 * MIN_MULTIPART_PART_SIZE as usize + 1024;
+        let result = op.object("file").writer().write_bytes(vec![1; size]).await;
+        assert!(result.is_err());
+
+        let requests = requests.lock().unwrap();
+        let last = requests.last().unwrap();
+        assert_eq!(last.method(), http::Method::DELETE);
+        assert_eq!(last.uri().to_string(), "/test/file?uploadId=upload-id");
+        // No more parts will be uploaded after failure.
+        assert_eq!(requests.len(), 4);
+
+        Ok(())
+    }
+
+    /// Write by a reader which never yields, so that the write is stuck
+    /// after the multipart upload has been created.
+    async fn stuck_write(op: &Operator) -> Result<Metadata> {
+        let r = futures::stream::pending::<std::io::Result<Vec<u8>>>().into_async_read();
+        op.object("file")
+            .writer()
+            .write_reader(Box::new(r), 2 * MIN_MULTIPART_PART_SIZE)
+            .await
+    }
+
+    fn has_abort(requests: &crate::tests::mock::Recorded) -> bool {
+        requests
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|req| req.method() == http::Method::DELETE)
+    }
+
+    #[tokio::test]
+    async fn test_multipart_upload_cancelled() -> Result<()> {
+        let (endpoint, requests) = multipart_mock(None);
+        let op = multipart_operator(&endpoint).await?;
+
+        let result = tokio::time::timeout(Duration::from_millis(200), stuck_write(&op)).await;
+        assert!(result.is_err());
+
+        // The dropped upload is aborted in background.
+        for _ in 0..100 {
+            if has_abort(&requests) {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        let requests = requests.lock().unwrap();
+        let methods: Vec<_> = requests.iter().map(|req| req.method().as_str()).collect();
+        assert_eq!(methods, vec!["POST", "DELETE"]);
+        assert_eq!(
+            requests[1].uri().to_string(),
+            "/test/file?uploadId=upload-id"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_multipart_upload_dropped_outside_runtime() -> anyhow::Result<()> {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+        let (w, requests) = rt.block_on(async {
+            let (endpoint, requests) = multipart_mock(None);
+            let op = multipart_operator(&endpoint).await?;
+            let mut w = Box::pin(async move { stuck_write(&op).await });
+            let result = tokio::time::timeout(Duration::from_millis(200), &mut w).await;
+            assert!(result.is_err());
+            Ok::<_, Error>((w, requests))
+        })?;
+
+        // The upload can't be aborted without a runtime, and is counted.
+        let abandoned = ShutdownGuard::abandoned();
+        drop(w);
+        assert!(ShutdownGuard::abandoned() > abandoned);
+        rt.block_on(async { tokio::time::sleep(Duration::from_millis(50)).await });
+        assert!(!has_abort(&requests));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_multipart_part_size_too_small() {
+        let mut builder = Backend::build();
+        builder
+            .bucket("test")
+            .endpoint("http://127.0.0.1:9000")
+            .region("us-east-1")
+            .multipart_part_size(1024);
+
+        let err = builder.finish().await.unwrap_err();
+        assert_eq!(err.kind(), Kind::BackendConfigurationInvalid);
+    }
+
+    #[tokio::test]
+    async fn test_write_content_type() -> Result<()> {
+        let content_type = Arc::new(std::sync::Mutex::new(None));
+        let stored = content_type.clone();
+        let (endpoint, _) = mock_server(move |req| {
+            let mut stored = stored.lock().unwrap();
+            match *req.method() {
+                http::Method::PUT => {
+                    *stored = req.headers().get(http::header::CONTENT_TYPE).cloned();
+                    hyper::Response::new(hyper::Body::empty())
+                }
+                _ => hyper::Response::builder()
+                    .header(http::header::CONTENT_LENGTH, "2")
+                    .header(
+                        http::header::CONTENT_TYPE,
+                        stored
+                            .clone()
+                            .unwrap_or_else(|| HeaderValue::from_static("binary/octet-stream")),
+                    )
+                    .body(hyper::Body::empty())
+                    .unwrap(),
+            }
+        });
+        let op = crate::tests::mock::mock_s3_operator(&endpoint).await;
+
+        op.object("file")
+            .writer()
+            .write_bytes(b"{}".to_vec())
+            .await?;
+        let meta = op.object("file").metadata().await?;
+        assert_eq!(meta.content_type(), Some("binary/octet-stream"));
+
+        op.object("file.json")
+            .writer()
+            .content_type("application/json")
+            .write_bytes(b"{}".to_vec())
+            .await?;
+        let meta = op.object("file.json").metadata().await?;
+        assert_eq!(meta.content_type(), Some("application/json"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_write_headers() -> Result<()> {
+        let (endpoint, requests) = mock_server(|_| hyper::Response::new(hyper::Body::empty()));
+        let op = crate::tests::mock::mock_s3_operator(&endpoint).await;
+
+        op.object("index.js.gz")
+            .writer()
+            .cache_control("public, max-age=31536000")
+            .content_disposition("attachment; filename=\"index.js\"")
+            .content_encoding("gzip")
+            .content_type("")
+            .write_bytes(b"{}".to_vec())
+            .await?;
+        {
+            let requests = requests.lock().unwrap();
+            let headers = requests[0].headers();
+            assert_eq!(headers["cache-control"], "public, max-age=31536000");
+            assert_eq!(
+                headers["content-disposition"],
+                "attachment; filename=\"index.js\""
+            );
+            assert_eq!(headers["content-encoding"], "gzip");
+            assert!(headers.get("content-type").is_none());
+        }
+
+        let err = op
+            .object("file")
+            .writer()
+            .content_disposition("attachment\nx-amz-acl: public-read")
+            .write_bytes(b"{}".to_vec())
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), Kind::BackendConfigurationInvalid);
+        assert_eq!(requests.lock().unwrap().len(), 1);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_expires() -> Result<()> {
+        let (endpoint, requests) = mock_server(|req| {
+            let expires = if req.uri().path().ends_with("/invalid") {
+                "0"
+            } else {
+                "Sun, 01 May 2016 00:51:29 GMT"
+            };
+            hyper::Response::builder()
+                .header(http::header::EXPIRES, expires)
+                .body(hyper::Body::empty())
+                .unwrap()
+        });
+        let op = mock_s3_operator(&endpoint).await;
+
+        let expires = OffsetDateTime::from_unix_timestamp(1462063889).unwrap();
+        op.object("file")
+            .writer()
+            .expires(expires)
+            .write_bytes(b"{}".to_vec())
+            .await?;
+        assert_eq!(
+            requests.lock().unwrap()[0].headers()["expires"],
+            "Sun, 01 May 2016 00:51:29 GMT"
+        );
+
+        let meta = op.object("file").metadata().await?;
+        assert_eq!(meta.expires(), Some(expires));
+        // Invalid expires are skipped.
+        let meta = op.object("invalid").metadata().await?;
+        assert_eq!(meta.expires(), None);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_write_storage_class() -> Result<()> {
+        let (endpoint, requests) = mock_server(|_| hyper::Response::new(hyper::Body::empty()));
+
+        let mut builder = Backend::build();
+        builder
+            .bucket("test")
+            .endpoint(&endpoint)
+            .region("us-east-1")
+            .storage_class("GLACIER")
+            .credential(Credential::hmac("access_key_id", "secret_access_key"));
+        let op = Operator::new(builder.finish().await?);
+
+        op.object("cold").writer().write_bytes(vec![0; 4]).await?;
+        op.object("warm")
+            .writer()
+            .storage_class("STANDARD_IA")
+            .write_bytes(vec![0; 4])
+            .await?;
+        let err = op
+            .object("invalid")
+            .writer()
+            .storage_class("COLD")
+            .write_bytes(vec![0; 4])
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), Kind::BackendConfigurationInvalid);
+
+        let classes = requests
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|v| {
+                (
+                    v.uri().path().to_string(),
+                    v.headers()["x-amz-storage-class"].clone(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            classes,
+            vec![
+                (
+                    "/test/cold".to_string(),
+                    HeaderValue::from_static("GLACIER")
+                ),
+                (
+                    "/test/warm".to_string(),
+                    HeaderValue::from_static("STANDARD_IA")
+                ),
+            ]
+        );
+
+        let mut builder = Backend::build();
+        builder
+            .bucket("test")
+            .endpoint(&endpoint)
+            .region("us-east-1")
+            .storage_class("standard");
+        let err = builder.finish().await.unwrap_err();
+        assert_eq!(err.kind(), Kind::BackendConfigurationInvalid);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_write_tags() -> Result<()> {
+        let (endpoint, requests) = multipart_mock(None);
+        let op = crate::tests::mock::mock_s3_operator(&endpoint).await;
+
+        op.object("file")
+            .writer()
+            .tag("team", "ingest")
+            .tag("note", "a=b&c d")
+            .write_bytes(vec![0; 4])
+            .await?;
+        assert_eq!(
+            requests.lock().unwrap()[0].headers().get("x-amz-tagging"),
+            Some(&HeaderValue::from_static("note=a%3Db%26c%20d&team=ingest"))
+        );
+
+        let mut w = op.object("too_many").writer();
+        for i in 0..=MAX_TAGS {
+            w = w.tag(&format!("k{}", i), "v");
+        }
+        let err = w.write_bytes(vec![0; 4]).await.unwrap_err();
+        assert_eq!(err.kind(), Kind::BackendConfigurationInvalid);
+        assert_eq!(requests.lock().unwrap().len(), 1);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_write_default_acl() -> Result<()> {
+        let (endpoint, requests) = multipart_mock(None);
+
+        let mut builder = Backend::build();
+        builder
+            .bucket("test")
+            .endpoint(&endpoint)
+            .region("us-east-1")
+            .default_acl("bucket-owner-full-control")
+            .multipart_threshold(MIN_MULTIPART_PART_SIZE)
+            .credential(Credential::hmac("access_key_id", "secret_access_key"));
+        let op = Operator::new(builder.finish().await?);
+
+        op.object("small").writer().write_bytes(vec![0; 4]).await?;
+        op.object("large")
+            .writer()
+            .write_bytes(vec![0; MIN_MULTIPART_PART_SIZE as usize + 1])
+            .await?;
+
+        // PUT of the small object and the initiation of multipart upload.
+        let acls = requests
+            .lock()
+            .unwrap()
+            .iter()
+            .filter_map(|v| {
+                v.headers()
+                    .get("x-amz-acl")
+                    .map(|acl| (v.method().clone(), acl.clone()))
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            acls,
+            vec![
+                (
+                    http::Method::PUT,
+                    HeaderValue::from_static("bucket-owner-full-control")
+                ),
+                (
+                    http::Method::POST,
+                    HeaderValue::from_static("bucket-owner-full-control")
+                ),
+            ]
+        );
+
+        let mut builder = Backend::build();
+        builder
+            .bucket("test")
+            .endpoint(&endpoint)
+            .region("us-east-1")
+            .default_acl("owner-full-control");
+        let err = builder.finish().await.unwrap_err();
+        assert_eq!(err.kind(), Kind::BackendConfigurationInvalid);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_user_metadata() -> Result<()> {
+        let stored = Arc::new(std::sync::Mutex::new(http::HeaderMap::new()));
+        let headers = stored.clone();
+        let (endpoint, requests) = mock_server(move |req| {
+            let mut headers = headers.lock().unwrap();
+            match *req.method() {
+                http::Method::PUT => {
+                    *headers = req.headers().clone();
+                    hyper::Response::new(hyper::Body::empty())
+                }
+                _ => {
+                    let mut resp =
+                        hyper::Response::builder().header(http::header::CONTENT_LENGTH, "2");
+                    for (k, v) in headers.iter() {
+                        if k.as_str().starts_with("x-amz-meta-") {
+                            resp = resp.header(k, v);
+                        }
+                    }
+                    if req.uri().path().ends_with("non-ascii") {
+                        resp = resp.header(
+                            "x-amz-meta-owner",
+                            HeaderValue::from_bytes("李".as_bytes()).unwrap(),
+                        );
+                    }
+                    resp.body(hyper::Body::empty()).unwrap()
+                }
+            }
+        });
+        let op = crate::tests::mock::mock_s3_operator(&endpoint).await;
+
+        op.object("file")
+            .writer()
+            .user_metadata("Owner", "alice")
+            .user_metadata("source-pipeline", "ingest/v2")
+            .write_bytes(b"{}".to_vec())
+            .await?;
+        assert_eq!(stored.lock().unwrap()["x-amz-meta-owner"], "alice");
+
+        let meta = op.object("file").metadata().await?;
+        assert_eq!(
+            meta.user_metadata(),
+            &HashMap::from([
+                ("owner".to_string(), "alice".to_string()),
+                ("source-pipeline".to_string(), "ingest/v2".to_string()),
+            ])
+        );
+
+        let err = op.object("non-ascii").metadata().await.unwrap_err();
+        assert_eq!(err.kind(), Kind::Unexpected);
+
+        for (k, v) in [("bad key", "v"), ("owner", "李"), ("owner", "a\nb")] {
+            let err = op
+                .object("invalid")
+                .writer()
+                .user_metadata(k, v)
+                .write_bytes(b"{}".to_vec())
+                .await
+                .unwrap_err();
+            assert_eq!(
+                err.kind(),
+                Kind::BackendConfigurationInvalid,
+                "{:?}",
+                (k, v)
+            );
+        }
+        let requests = requests.lock().unwrap();
+        assert!(requests.iter().all(|v| v.uri().path() != "/test/invalid"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_copy() -> Result<()> {
+        let (endpoint, requests) = mock_server(|req| {
+            let path = req.uri().path();
+            let resp = hyper::Response::builder();
+            match *req.method() {
+                http::Method::HEAD if path == "/test/src%20a" => resp
+                    .header(http::header::CONTENT_LENGTH, "4")
+                    .header(http::header::CONTENT_TYPE, "text/csv")
+                    .header(http::header::CACHE_CONTROL, "no-cache")
+                    .header("x-amz-meta-owner", "alice")
+                    .body(hyper::Body::empty()),
+                http::Method::HEAD => resp
+                    .status(StatusCode::NOT_FOUND)
+                    .body(hyper::Body::empty()),
+                // Copies could fail with `200 OK`.
+                http::Method::PUT if path == "/test/broken" => resp.body(hyper::Body::from(
+                    "<Error><Code>InternalError</Code></Error>",
+                )),
+                http::Method::PUT => resp.body(hyper::Body::from(
+                    "<CopyObjectResult>\
+                     <LastModified>2022-07-01T00:00:00.000Z</LastModified>\
+                     <ETag>\"copied\"</ETag>\
+                     </CopyObjectResult>",
+                )),
+                _ => resp
+                    .status(StatusCode::METHOD_NOT_ALLOWED)
+                    .body(hyper::Body::empty()),
+            }
+            .unwrap()
+        });
+        let op = mock_s3_operator(&endpoint).await;
+        let header = |req: &http::Request<Bytes>, name: &str| {
+            req.headers()
+                .get(name)
+                .map(|v| v.to_str().unwrap().to_string())
+        };
+
+        let meta = op.object("src a").copy_to("dst/b").await?;
+        assert_eq!(meta.path(), "dst/b");
+        assert_eq!(meta.content_length(), 4);
+        assert_eq!(meta.etag().as_deref(), Some("copied"));
+        assert!(meta.last_modified().is_some());
+        {
+            let requests = requests.lock().unwrap();
+            let req = requests.last().unwrap();
+            assert_eq!(req.method(), http::Method::PUT);
+            assert_eq!(req.uri().path(), "/test/dst/b");
+            assert_eq!(
+                header(req, "x-amz-copy-source").as_deref(),
+                Some("/test/src%20a")
+            );
+            // Metadata is copied by s3 itself.
+            assert_eq!(header(req, "x-amz-metadata-directive"), None);
+            assert_eq!(header(req, "content-type"), None);
+        }
+
+        // Copy onto itself replaces the metadata with the same values.
+        op.object("src a").copy_to("src a").await?;
+        {
+            let requests = requests.lock().unwrap();
+            let req = requests.last().unwrap();
+            assert_eq!(req.uri().path(), "/test/src%20a");
+            assert_eq!(
+                header(req, "x-amz-metadata-directive").as_deref(),
+                Some("REPLACE")
+            );
+            assert_eq!(header(req, "content-type").as_deref(), Some("text/csv"));
+            assert_eq!(header(req, "cache-control").as_deref(), Some("no-cache"));
+            assert_eq!(header(req, "x-amz-meta-owner").as_deref(), Some("alice"));
+        }
+
+        let err = op.object("not-exist").copy_to("dst/c").await.unwrap_err();
+        assert_eq!(err.kind(), Kind::ObjectNotExist);
+        assert_eq!(
+            requests.lock().unwrap().last().unwrap().method(),
+            http::Method::HEAD
+        );
+
+        let err = op.object("src a").copy_to("broken").await.unwrap_err();
+        assert_eq!(err.kind(), Kind::Unexpected);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_copy_multipart() -> Result<()> {
+        let size: u64 = 6 * 1024 * 1024 * 1024;
+        let (endpoint, requests) = mock_server(move |req| {
+            let query = req.uri().query().unwrap_or_default();
+            let resp = hyper::Response::builder();
+            let body = match *req.method() {
+                http::Method::HEAD => {
+                    return resp
+                        .header(http::header::CONTENT_LENGTH, size.to_string())
+                        .body(hyper::Body::empty())
+                        .unwrap()
+                }
+                http::Method::POST if query == "uploads" => {
+                    "<InitiateMultipartUploadResult><UploadId>u1</UploadId>\
+                     </InitiateMultipartUploadResult>"
+                        .to_string()
+                }
+                http::Method::PUT => format!(
+                    "<CopyPartResult><ETag>\"{}\"</ETag></CopyPartResult>",
+                    query.replace('&', "-")
+                ),
+                _ => "<CompleteMultipartUploadResult><ETag>\"done-3\"</ETag>\
+                      </CompleteMultipartUploadResult>"
+                    .to_string(),
+            };
+            resp.body(hyper::Body::from(body)).unwrap()
+        });
+        let mut builder = Backend::build();
+        builder
+            .bucket("test")
+            .endpoint(&endpoint)
+            .region("us-east-1")
+            .multipart_part_size(2 * 1024 * 1024 * 1024)
+            .server_side_encryption_with_customer_key("AES256", &[0; 32])
+            .credential(Credential::hmac("access_key_id", "secret_access_key"));
+        let op = Operator::new(builder.finish().await?);
+
+        let meta = op.object("src").copy_to("dst").await?;
+        assert_eq!(meta.content_length(), size);
+        assert_eq!(meta.etag().as_deref(), Some("done-3"));
+
+        let requests = requests.lock().unwrap();
+        let parts: Vec<_> = requests
+            .iter()
+            .filter(|req| req.method() == http::Method::PUT)
+            .collect();
+        let ranges: Vec<_> = parts
+            .iter()
+            .map(|req| req.headers()["x-amz-copy-source-range"].to_str().unwrap())
+            .collect();
+        assert_eq!(
+            ranges,
+            vec![
+                "bytes=0-2147483647",
+                "bytes=2147483648-4294967295",
+                "bytes=4294967296-6442450943"
+            ]
+        );
+        for req in parts {
+            assert_eq!(req.headers()["x-amz-copy-source"], "/test/src");
+            // SSE-C headers of both the destination and the source.
+            for name in [
+                constants::X_AMZ_SERVER_SIDE_ENCRYPTION_CUSTOMER_KEY,
+                constants::X_AMZ_COPY_SOURCE_SERVER_SIDE_ENCRYPTION_CUSTOMER_KEY,
+            ] {
+                assert_eq!(req.headers()[name], base64::encode([0; 32]).as_str());
+            }
+        }
+        let complete = std::str::from_utf8(requests.last().unwrap().body()).unwrap();
+        assert!(
+            complete
+                .contains("<PartNumber>3</PartNumber><ETag>\"partNumber=3-uploadId=u1\"</ETag>"),
+            "{}",
+            complete
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_create() -> Result<()> {
+        let (endpoint, requests) = mock_server(|req| {
+            let resp = hyper::Response::builder();
+            match *req.method() {
+                http::Method::PUT => resp.header(http::header::ETAG, "\"empty\""),
+                // Dir markers exist even without children.
+                _ => resp.header(http::header::CONTENT_LENGTH, "0"),
+            }
+            .body(hyper::Body::empty())
+            .unwrap()
+        });
+        let op = mock_s3_operator(&endpoint).await;
+
+        for (path, key, mode) in [
+            ("dir/", "/test/dir/", ObjectMode::DIR),
+            ("dir/file", "/test/dir/file", ObjectMode::FILE),
+        ] {
+            let meta = op.object(path).create().await?;
+            assert_eq!(meta.mode(), mode, "create {}", path);
+            {
+                let requests = requests.lock().unwrap();
+                let req = requests.last().unwrap();
+                assert_eq!(req.method(), http::Method::PUT);
+                assert_eq!(req.uri().path(), key);
+                assert_eq!(req.headers()[http::header::CONTENT_LENGTH], "0");
+                assert!(req.body().is_empty());
+            }
+
+            let meta = op.object(path).metadata().await?;
+            assert_eq!(meta.mode(), mode, "stat {}", path);
+        }
+
+        // Paths of dirs get `/` appended.
+        let meta = op
+            .inner()
+            .create(&OpCreate::new("another", ObjectMode::DIR))
+            .await?;
+        assert_eq!(meta.path(), "another/");
+        assert_eq!(
+            requests.lock().unwrap().last().unwrap().uri().path(),
+            "/test/another/"
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_rename() -> Result<()> {
+        let (endpoint, requests) = mock_server(|req| {
+            let path = req.uri().path();
+            let resp = hyper::Response::builder();
+            match *req.method() {
+                http::Method::HEAD => resp
+                    .header(http::header::CONTENT_LENGTH, "4")
+                    .body(hyper::Body::empty()),
+                http::Method::PUT => resp.body(hyper::Body::from(
+                    "<CopyObjectResult><ETag>\"copied\"</ETag></CopyObjectResult>",
+                )),
+                http::Method::DELETE if path == "/test/locked" => {
+                    resp.status(StatusCode::FORBIDDEN).body(hyper::Body::from(
+                        "<Error><Code>AccessDenied</Code></Error>",
+                    ))
+                }
+                http::Method::DELETE => resp
+                    .status(StatusCode::NO_CONTENT)
+                    .body(hyper::Body::empty()),
+                _ => resp
+                    .status(StatusCode::METHOD_NOT_ALLOWED)
+                    .body(hyper::Body::empty()),
+            }
+            .unwrap()
+        });
+        let op = mock_s3_operator(&endpoint).await;
+        let methods = |requests: &Arc<Mutex<Vec<http::Request<Bytes>>>>| {
+            requests
+                .lock()
+                .unwrap()
+                .drain(..)
+                .map(|req| format!("{} {}", req.method(), req.uri().path()))
+                .collect::<Vec<_>>()
+        };
+
+        let meta = op.object("src").rename_to("dst").await?;
+        assert_eq!(meta.path(), "dst");
+        assert_eq!(meta.etag().as_deref(), Some("copied"));
+        assert_eq!(
+            methods(&requests),
+            vec!["HEAD /test/src", "PUT /test/dst", "DELETE /test/src"]
+        );
+
+        // Rename onto itself must not delete the object.
+        op.object("src").rename_to("/src").await?;
+        assert_eq!(methods(&requests), vec!["HEAD /test/src", "PUT /test/src"]);
+
+        let err = op.object("locked").rename_to("dst").await.unwrap_err();
+        assert_eq!(err.kind(), Kind::ObjectPermissionDenied);
+        assert!(
+            err.to_string()
+                .contains("object has been copied to dst, but failed to delete the source"),
+            "{}",
+            err
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_write_checksum() -> Result<()> {
+        let (endpoint, requests) = mock_server(|req| {
+            let query = req.uri().query().unwrap_or_default();
+            let resp = hyper::Response::builder();
+            let expected = base64::encode(md5::compute(req.body()).as_slice());
+            let digest_ok = req
+                .headers()
+                .get("content-md5")
+                .map(|v| v == expected.as_str())
+                .unwrap_or(true);
+            // Emulate a proxy that corrupts the content.
+            if !digest_ok || req.uri().path().ends_with("corrupted") {
+                return resp
+                    .status(http::StatusCode::BAD_REQUEST)
+                    .body(hyper::Body::from(
+                        "<Error><Code>BadDigest</Code><Message>The Content-MD5 you specified did not match what we received.</Message></Error>",
+                    ))
+                    .unwrap();
+            }
+            match (req.method().as_str(), query) {
+                ("POST", "uploads") => resp.body(hyper::Body::from(
+                    "<InitiateMultipartUploadResult><UploadId>upload-id</UploadId></InitiateMultipartUploadResult>",
+                )),
+                ("PUT", q) if q.starts_with("partNumber") => {
+                    resp.header(http::header::ETAG, "\"etag\"").body(hyper::Body::empty())
+                }
+                _ => resp.body(hyper::Body::empty()),
+            }
+            .unwrap()
+        });
+
+        let mut builder = Backend::build();
+        builder
+            .bucket("test")
+            .endpoint(&endpoint)
+            .region("us-east-1")
+            .enable_write_checksum(true)
+            .multipart_threshold(MIN_MULTIPART_PART_SIZE)
+            .multipart_part_size(MIN_MULTIPART_PART_SIZE)
+            .credential(Credential::hmac("access_key_id", "secret_access_key"));
+        let op = Operator::new(builder.finish().await?);
+
+        op.object("small").writer().write_bytes(vec![1; 4]).await?;
+        op.object("large")
+            .writer()
+            .write_bytes(vec![2; MIN_MULTIPART_PART_SIZE as usize + 1])
+            .await?;
+        {
+            let requests = requests.lock().unwrap();
+            let digests = requests
+                .iter()
+                .filter(|v| v.method() == http::Method::PUT)
+                .map(|v| v.headers()["content-md5"].to_str().unwrap().to_string())
+                .collect::<Vec<_>>();
+            assert_eq!(
+                digests,
+                vec![
+                    base64::encode(md5::compute(vec![1; 4]).as_slice()),
+                    base64::encode(
+                        md5::compute(vec![2; MIN_MULTIPART_PART_SIZE as usize]).as_slice()
+                    ),
+                    base64::encode(md5::compute(vec![2; 1]).as_slice()),
+                ]
+            );
+        }
+
+        let err = op
+            .object("corrupted")
+            .writer()
+            .write_bytes(vec![1; 4])
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), Kind::ObjectChecksumMismatch);
+
+        // Checksum is disabled by default.
+        let op = crate::tests::mock::mock_s3_operator(&endpoint).await;
+        op.object("small").writer().write_bytes(vec![1; 4]).await?;
+        let requests = requests.lock().unwrap();
+        assert!(requests
+            .last()
+            .unwrap()
+            .headers()
+            .get("content-md5")
+            .is_none());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_last_modified_consistent() -> Result<()> {
+        let (endpoint, _) = mock_server(|req| match *req.method() {
+            http::Method::HEAD => hyper::Response::builder()
+                .header(http::header::CONTENT_LENGTH, "56")
+                .header(http::header::LAST_MODIFIED, "Sat, 30 Apr 2016 23:51:29 GMT")
+                .body(hyper::Body::empty())
+                .unwrap(),
+            _ => hyper::Response::new(hyper::Body::from(
+                r#"<ListBucketResult>
+  <IsTruncated>false</IsTruncated>
+  <Contents>
+    <Key>dir/file</Key>
+    <LastModified>2016-04-30T23:51:29.000Z</LastModified>
+    <Size>56</Size>
+  </Contents>
+</ListBucketResult>"#,
+            )),
+        });
+        let op = crate::tests::mock::mock_s3_operator(&endpoint).await;
+
+        let mut obs = op.objects("dir/");
+        let mut o = obs.next().await.expect("must have entry")?;
+        let listed = o.metadata_mut().clone();
+        let stated = o.metadata().await?;
+
+        assert_eq!(listed.last_modified_ms(), Some(1462060289000));
+        assert_eq!(listed.last_modified(), stated.last_modified());
+        assert_eq!(listed.last_modified_ms(), stated.last_modified_ms());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_etag_consistent() -> Result<()> {
+        // ETag of multipart uploaded objects is not the MD5 of the content.
+        let etag = "\"d41d8cd98f00b204e9800998ecf8427e-2\"";
+        let (endpoint, _) = mock_server(move |req| match *req.method() {
+            http::Method::HEAD => hyper::Response::builder()
+                .header(http::header::CONTENT_LENGTH, "56")
+                .header(http::header::ETAG, etag)
+                .body(hyper::Body::empty())
+                .unwrap(),
+            // Conditional reads must send the quoted ETag.
+            http::Method::GET if req.uri().path().ends_with("/dir/file") => {
+                let status = match req.headers().get(http::header::IF_MATCH) {
+                    Some(v) if v == etag => http::StatusCode::OK,
+                    _ => http::StatusCode::PRECONDITION_FAILED,
+                };
+                hyper::Response::builder()
+                    .status(status)
+                    .body(hyper::Body::empty())
+                    .unwrap()
+            }
+            _ => hyper::Response::new(hyper::Body::from(format!(
+                r#"<ListBucketResult>
+  <IsTruncated>false</IsTruncated>
+  <Contents>
+    <Key>dir/file</Key>
+    <ETag>{}</ETag>
+    <Size>56</Size>
+  </Contents>
+</ListBucketResult>"#,
+                etag.replace('"', "&quot;")
+            ))),
+        });
+        let op = crate::tests::mock::mock_s3_operator(&endpoint).await;
+
+        let mut obs = op.objects("dir/");
+        let mut o = obs.next().await.expect("must have entry")?;
+        let listed = o.metadata_mut().clone();
+        let stated = o.metadata().await?;
+
+        // ETags are returned without the surrounding quotes.
+        assert_eq!(
+            listed.etag().as_deref(),
+            Some("d41d8cd98f00b204e9800998ecf8427e-2")
+        );
+        assert_eq!(listed.etag(), stated.etag());
+        assert_eq!(listed.content_md5(), None);
+        assert_eq!(stated.content_md5(), None);
+
+        let etag = stated.etag().unwrap();
+        op.object("dir/file")
+            .reader()
+            .if_match(&etag)
+            .read_to_end(&mut vec![])
+            .await
+            .map_err(anyhow::Error::from)?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_storage_class() -> Result<()> {
+        let (endpoint, _) = mock_server(|req| {
+            let cold = req.uri().path().ends_with("/cold");
+            match *req.method() {
+                http::Method::HEAD if cold => hyper::Response::builder()
+                    .header(http::header::CONTENT_LENGTH, "0")
+                    .header(constants::X_AMZ_STORAGE_CLASS, "GLACIER")
+                    .body(hyper::Body::empty()),
+                http::Method::HEAD => hyper::Response::builder()
+                    .header(http::header::CONTENT_LENGTH, "0")
+                    .body(hyper::Body::empty()),
+                _ if cold => hyper::Response::builder()
+                    .status(StatusCode::FORBIDDEN)
+                    .body(hyper::Body::from(
+                        r#"<Error>
+  <Code>InvalidObjectState</Code>
+  <Message>The operation is not valid for the object's storage class</Message>
+  <StorageClass>GLACIER</StorageClass>
+</Error>"#,
+                    )),
+                _ => hyper::Response::builder().body(hyper::Body::from(
+                    r#"<ListBucketResult>
+  <IsTruncated>false</IsTruncated>
+  <Contents>
+    <Key>dir/cold</Key>
+    <Size>0</Size>
+    <StorageClass>GLACIER</StorageClass>
+  </Contents>
+  <Contents>
+    <Key>dir/hot</Key>
+    <Size>0</Size>
+    <StorageClass>STANDARD</StorageClass>
+  </Contents>
+</ListBucketResult>"#,
+                )),
+            }
+            .unwrap()
+        });
+        let op = mock_s3_operator(&endpoint).await;
+
+        let meta = op.object("dir/cold").metadata().await?;
+        assert_eq!(meta.storage_class().as_deref(), Some("GLACIER"));
+        // Absent storage class means `STANDARD`.
+        let meta = op.object("dir/hot").metadata().await?;
+        assert_eq!(meta.storage_class().as_deref(), Some("STANDARD"));
+        let meta = op.object("dir/").metadata().await?;
+        assert_eq!(meta.storage_class(), None);
+
+        let mut listed = vec![];
+        let mut obs = op.objects("dir/");
+        while let Some(mut o) = obs.next().await.transpose()? {
+            let meta = o.metadata_mut();
+            listed.push((meta.path().to_string(), meta.storage_class()));
+        }
+        assert_eq!(
+            listed,
+            vec![
+                ("dir/cold".to_string(), Some("GLACIER".to_string())),
+                ("dir/hot".to_string(), Some("STANDARD".to_string())),
+            ]
+        );
+
+        let err = op
+            .object("dir/cold")
+            .stream(None, None)
+            .await
+            .err()
+            .expect("read of archived objects must fail");
+        assert_eq!(err.kind(), Kind::ObjectNotAvailable);
+        assert_eq!(err.context()["code"], "InvalidObjectState");
+        assert_eq!(err.context()["storage_class"], "GLACIER");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_raw_headers() -> Result<()> {
+        let (endpoint, _) = mock_server(|req| {
+            hyper::Response::builder()
+                .header("x-amz-replication-status", "COMPLETED")
+                .body(hyper::Body::from(if req.method() == http::Method::HEAD {
+                    ""
+                } else {
+                    "Hello"
+                }))
+                .unwrap()
+        });
+        let op = mock_s3_operator(&endpoint).await;
+
+        let meta = op.object("file").metadata().await?;
+        assert!(meta.raw_headers().is_none());
+        let meta = op.object("file").metadata_with_raw_headers().await?;
+        let headers = meta.raw_headers().expect("raw headers must exist");
+        assert_eq!(headers["x-amz-replication-status"], "COMPLETED");
+
+        let (_, meta) = op.object("file").read_with_metadata(None, None).await?;
+        assert!(meta.raw_headers().is_none());
+        let (_, meta) = op.object("file").read_with_raw_headers(None, None).await?;
+        let headers = meta.raw_headers().expect("raw headers must exist");
+        assert_eq!(headers["x-amz-replication-status"], "COMPLETED");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_stat_server_side_encryption() -> Result<()> {
+        let (endpoint, _) = mock_server(|req| {
+            let mut builder = hyper::Response::builder().header(http::header::CONTENT_LENGTH, "0");
+            if req.uri().path().ends_with("/kms") {
+                builder = builder
+                    .header(constants::X_AMZ_SERVER_SIDE_ENCRYPTION, "aws:kms")
+                    .header(
+                        constants::X_AMZ_SERVER_SIDE_ENCRYPTION_AWS_KMS_KEY_ID,
+                        "arn:aws:kms:us-east-2:111122223333:key/test",
+                    );
+            }
+            builder.body(hyper::Body::empty()).unwrap()
+        });
+        let op = mock_s3_operator(&endpoint).await;
+
+        let meta = op.object("kms").metadata().await?;
+        assert_eq!(meta.server_side_encryption().as_deref(), Some("aws:kms"));
+        assert_eq!(
+            meta.server_side_encryption_aws_kms_key_id().as_deref(),
+            Some("arn:aws:kms:us-east-2:111122223333:key/test")
+        );
+
+        let meta = op.object("plain").metadata().await?;
+        assert_eq!(meta.server_side_encryption(), None);
+        assert_eq!(meta.server_side_encryption_aws_kms_key_id(), None);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_list_refresh_credential() -> Result<()> {
+        // Credential will be loaded via the loader, and rotated by the mock server.
+        let accepted = Arc::new(std::sync::Mutex::new("ak1".to_string()));
+        let current = accepted.clone();
+        let (endpoint, requests) = mock_server(move |req| {
+            let auth = req
+                .headers()
+                .get(http::header::AUTHORIZATION)
+                .map(|v| v.to_str().unwrap().to_string())
+                .unwrap_or_default();
+            let ak = auth
+                .split("Credential=")
+                .nth(1)
+                .and_then(|v| v.split('/').next())
+                .unwrap_or_default();
+            let token = req
+                .uri()
+                .query()
+                .unwrap_or_default()
+                .split('&')
+                .find_map(|v| v.strip_prefix("continuation-token="))
+                .unwrap_or_default();
+
+            let mut accepted = accepted.lock().unwrap();
+            // Rotate the credential before the second page.
+            if token == "t1" && accepted.as_str() == "ak1" {
+                *accepted = "ak2".to_string();
+            }
+            if ak != accepted.as_str() {
+                return hyper::Response::builder()
+                    .status(http::StatusCode::BAD_REQUEST)
+                    .body(hyper::Body::from(
+                        "<Error><Code>ExpiredToken</Code><Message>The provided token has expired.</Message></Error>",
+                    ))
+                    .unwrap();
+            }
+
+            let (keys, next) = match token {
+                "" => (vec!["a", "b"], Some("t1")),
+                "t1" => (vec!["c", "d"], Some("t2")),
+                _ => (vec!["e"], None),
+            };
+            let mut body = format!(
+                "<ListBucketResult><IsTruncated>{}</IsTruncated>",
+                next.is_some()
+            );
+            if let Some(next) = next {
+                body.push_str(&format!(
+                    "<NextContinuationToken>{}</NextContinuationToken>",
+                    next
+                ));
+            }
+            for key in keys {
+                body.push_str(&format!(
+                    "<Contents><Key>{}</Key><Size>1</Size></Contents>",
+                    key
+                ));
+            }
+            body.push_str("</ListBucketResult>");
+            hyper::Response::new(hyper::Body::from(body))
+        });
+
+        let mut builder = Backend::build();
+        builder
+            .bucket("test")
+            .endpoint(&endpoint)
+            .region("us-east-1")
+            .credential_loader(move || {
+                let ak = current.lock().unwrap().clone();
+                let sk = ak.replace("ak", "sk");
+                Credential::hmac(&ak, &sk)
+            });
+        let op = Operator::new(builder.finish().await?);
+
+        let mut paths = vec![];
+        let mut obs = op.objects("");
+        while let Some(o) = obs.next().await {
+            paths.push(o?.metadata_mut().path().to_string());
+        }
+        assert_eq!(paths, vec!["a", "b", "c", "d", "e"]);
+
+        // The failed page has been retried only once with the new credential.
+        let requests = requests.lock().unwrap();
+        let signed = requests
+            .iter()
+            .map(|v| {
+                let auth = v.headers()[http::header::AUTHORIZATION].to_str().unwrap();
+                auth.split("Credential=").nth(1).unwrap()[..3].to_string()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(signed, vec!["ak1", "ak1", "ak2", "ak2"]);
+
+        Ok(())
+    }
+
+    fn gzip(csv: &str) -> Vec<u8> {
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        std::io::Write::write_all(&mut encoder, csv.as_bytes()).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    /// Serve an inventory report at `*/inventory/manifest.json` with
+    /// `data/{idx}.csv.gz` data files.
+    fn inventory_mock(format: &str, files: Vec<Vec<u8>>, md5_override: Option<&str>) -> String {
+        let manifest = serde_json::json!({
+            "sourceBucket": "test",
+            "destinationBucket": "arn:aws:s3:::test",
+            "version": "2016-11-30",
+            "fileFormat": format,
+            "fileSchema": "Bucket, Key, VersionId, IsLatest, IsDeleteMarker, Size, LastModifiedDate, ETag, StorageClass",
+            "files": files.iter().enumerate().map(|(idx, bs)| serde_json::json!({
+                "key": format!("data/{}.csv.gz", idx),
+                "size": bs.len(),
+                "MD5checksum": md5_override
+                    .map(|v| v.to_string())
+                    .unwrap_or_else(|| format!("{:x}", md5::compute(bs))),
+            })).collect::<Vec<_>>(),
+        });
+        let manifest = serde_json::to_vec(&manifest).unwrap();
+
+        let (endpoint, _) = mock_server(move |req| {
+            let path = req.uri().path();
+            let body = if path.ends_with("/inventory/manifest.json") {
+                Some(manifest.clone())
+            } else {
+                path.strip_prefix("/test/data/")
+                    .and_then(|v| v.strip_suffix(".csv.gz"))
+                    .and_then(|v| files.get(v.parse::<usize>().ok()?))
+                    .cloned()
+            };
+            match body {
+                Some(bs) => hyper::Response::new(hyper::Body::from(bs)),
+                None => hyper::Response::builder()
+                    .status(StatusCode::NOT_FOUND)
+                    .body(hyper::Body::empty())
+                    .unwrap(),
+            }
+        });
+        endpoint
+    }
+
+    async fn inventory_backend(endpoint: &str, root: &str) -> Result<Backend> {
+        let mut builder = Backend::build();
+        builder
+            .root(root)
+            .bucket("test")
+            .endpoint(endpoint)
+            .region("us-east-1")
+            .credential(Credential::hmac("access_key_id", "secret_access_key"));
+
+        builder.build_backend().await
+    }
+
+    type InventoryEntry = (String, ObjectMode, u64, Option<String>, Option<String>);
 
-    while let Some(b) = body.data().await {
-        match b {
-            Ok(b) => {
-                bs.put_slice(&b[..min(b.len(), limit)]);
-                limit -= b.len();
-                if limit == 0 {
-                    break;
+    async fn inventory_entries(backend: &Backend) -> Result<Vec<InventoryEntry>> {
+        let objects: Vec<Object> = backend
+            .read_inventory("inventory/manifest.json")
+            .await?
+            .try_collect()
+            .await?;
+
+        Ok(objects
+            .into_iter()
+            .map(|mut o| {
+                let meta = o.metadata_mut();
+                (
+                    meta.path().to_string(),
+                    meta.mode(),
+                    meta.content_length(),
+                    meta.etag(),
+                    meta.version_id(),
+                )
+            })
+            .collect())
+    }
+
+    #[tokio::test]
+    async fn test_read_inventory() -> Result<()> {
+        // Data files may consist of multiple gzip members.
+        let first = [
+            gzip(concat!(
+                "\"test\",\"dir/a.txt\",\"v1\",\"true\",\"false\",\"10\",\"2022-01-01T00:00:00.000Z\",\"etag-a\",\"STANDARD\"\n",
+                "\"test\",\"dir/raw, comma\nand \"\"quote\"\"\",\"v2\",\"true\",\"false\",\"20\",\"2022-01-02T00:00:00.000Z\",\"etag-b\",\"STANDARD\"\n",
+            )),
+            gzip(concat!(
+                "\"test\",\"dir/old\",\"v3\",\"false\",\"false\",\"30\",\"2022-01-03T00:00:00.000Z\",\"etag-c\",\"STANDARD\"\n",
+                "\"test\",\"dir/deleted\",\"v4\",\"true\",\"true\",\"\",\"2022-01-04T00:00:00.000Z\",\"\",\"\"\n",
+            )),
+        ]
+        .concat();
+        let second = gzip(concat!(
+            "\"test\",\"dir/sub/space+name%2Bplus%2C\",\"v5\",\"true\",\"false\",\"40\",\"2022-01-05T00:00:00.000Z\",\"etag-d\",\"GLACIER\"\n",
+            "\"test\",\"other/x\",\"v6\",\"true\",\"false\",\"50\",\"2022-01-06T00:00:00.000Z\",\"etag-e\",\"STANDARD\"\n",
+            "\"test\",\"dir/sub/\",\"\",\"true\",\"false\",\"0\",\"2022-01-07T00:00:00.000Z\",\"etag-f\",\"STANDARD\"",
+        ));
+        let endpoint = inventory_mock("CSV", vec![first, second], None);
+        let backend = inventory_backend(&endpoint, "/").await?;
+
+        let manifest = backend
+            .read_inventory_manifest("inventory/manifest.json")
+            .await?;
+        assert_eq!(manifest.destination_bucket_name(), "test");
+        assert_eq!(manifest.files.len(), 2);
+
+        let entries = inventory_entries(&backend).await?;
+        let file = |path: &str, size: u64, etag: &str, version: &str| {
+            (
+                path.to_string(),
+                ObjectMode::FILE,
+                size,
+                Some(etag.to_string()),
+                Some(version.to_string()),
+            )
+        };
+        let dir = |path: &str| {
+            (
+                path.to_string(),
+                ObjectMode::DIR,
+                0,
+                Some("etag-f".to_string()),
+                None,
+            )
+        };
+        // Non-current versions and delete markers are skipped.
+        assert_eq!(
+            entries,
+            vec![
+                file("dir/a.txt", 10, "etag-a", "v1"),
+                file("dir/raw, comma\nand \"quote\"", 20, "etag-b", "v2"),
+                file("dir/sub/space name+plus,", 40, "etag-d", "v5"),
+                file("other/x", 50, "etag-e", "v6"),
+                dir("dir/sub/"),
+            ]
+        );
+
+        // Keys are relative to the root, and keys out of it are skipped,
+        // while data files are still read by their keys.
+        let backend = inventory_backend(&endpoint, "/dir/").await?;
+        let entries = inventory_entries(&backend).await?;
+        assert_eq!(
+            entries,
+            vec![
+                file("a.txt", 10, "etag-a", "v1"),
+                file("raw, comma\nand \"quote\"", 20, "etag-b", "v2"),
+                file("sub/space name+plus,", 40, "etag-d", "v5"),
+                dir("sub/"),
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_read_inventory_checksum_mismatch() -> Result<()> {
+        let data = gzip("\"test\",\"a\",\"v1\",\"true\",\"false\",\"1\",\"\",\"\",\"\"\n");
+        let endpoint = inventory_mock("CSV", vec![data], Some("0123456789abcdef"));
+        let backend = inventory_backend(&endpoint, "/").await?;
+
+        let err = backend
+            .read_inventory("inventory/manifest.json")
+            .await?
+            .try_collect::<Vec<_>>()
+            .await
+            .expect_err("must fail");
+        assert_eq!(err.kind(), Kind::ObjectChecksumMismatch);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_read_inventory_unsupported() -> Result<()> {
+        let endpoint = inventory_mock("Parquet", vec![vec![0; 16]], None);
+        let backend = inventory_backend(&endpoint, "/").await?;
+
+        let err = backend
+            .read_inventory("inventory/manifest.json")
+            .await
+            .err()
+            .expect("must fail");
+        assert_eq!(err.kind(), Kind::Unsupported);
+
+        // Data files can still be read by the keys in the manifest.
+        let manifest = backend
+            .read_inventory_manifest("inventory/manifest.json")
+            .await?;
+        assert_eq!(manifest.file_format, "Parquet");
+        assert_eq!(manifest.files[0].key, "data/0.csv.gz");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_stat_dir() -> Result<()> {
+        let (endpoint, _) = mock_server(|req| {
+            let path = req.uri().path().to_string();
+            let query = req.uri().query().unwrap_or_default().to_string();
+            match *req.method() {
+                // Only `marker/` has an explicit dir marker object.
+                http::Method::HEAD if path.ends_with("/marker/") => hyper::Response::builder()
+                    .header(http::header::CONTENT_LENGTH, "0")
+                    .body(hyper::Body::empty()),
+                http::Method::HEAD => hyper::Response::builder()
+                    .status(StatusCode::NOT_FOUND)
+                    .body(hyper::Body::empty()),
+                _ => {
+                    assert!(query.contains("max-keys=1"), "{}", query);
+                    let contents = if query.contains("prefix=parent%2F") {
+                        "<Contents><Key>parent/child</Key><Size>1</Size></Contents>"
+                    } else {
+                        ""
+                    };
+                    hyper::Response::builder().body(hyper::Body::from(format!(
+                        "<ListBucketResult><IsTruncated>false</IsTruncated>{}</ListBucketResult>",
+                        contents
+                    )))
                 }
             }
-            Err(e) => return Error::Unexpected(anyhow!("parse error response parse: {:?}", e)),
+            .unwrap()
+        });
+        let op = mock_s3_operator(&endpoint).await;
+
+        let meta = op.object("parent/").metadata().await?;
+        assert_eq!(meta.mode(), ObjectMode::DIR);
+        let meta = op.object("marker/").metadata().await?;
+        assert_eq!(meta.mode(), ObjectMode::DIR);
+
+        let err = op.object("absent/").metadata().await.unwrap_err();
+        assert_eq!(err.kind(), Kind::ObjectNotExist);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_list_recursive() -> Result<()> {
+        let (endpoint, requests) = mock_server(|_| {
+            hyper::Response::builder()
+                .body(hyper::Body::from(
+                    r#"<ListBucketResult>
+  <IsTruncated>false</IsTruncated>
+  <Contents><Key>dir/</Key><Size>0</Size></Contents>
+  <Contents><Key>dir/a</Key><Size>1</Size></Contents>
+  <Contents><Key>dir/sub/</Key><Size>0</Size><StorageClass>STANDARD</StorageClass></Contents>
+  <Contents><Key>dir/sub/b</Key><Size>2</Size></Contents>
+</ListBucketResult>"#,
+                ))
+                .unwrap()
+        });
+        let op = mock_s3_operator(&endpoint).await;
+
+        let mut listed = vec![];
+        let mut obs = op.objects("dir/").recursive();
+        while let Some(mut o) = obs.next().await.transpose()? {
+            let meta = o.metadata_mut();
+            listed.push((
+                meta.path().to_string(),
+                meta.mode(),
+                meta.content_length(),
+                meta.storage_class(),
+            ));
         }
+        assert_eq!(
+            listed,
+            vec![
+                ("dir/a".to_string(), ObjectMode::FILE, 1, None),
+                ("dir/sub/".to_string(), ObjectMode::DIR, 0, None),
+                ("dir/sub/b".to_string(), ObjectMode::FILE, 2, None),
+            ]
+        );
+
+        let query = requests.lock().unwrap()[0]
+            .uri()
+            .query()
+            .unwrap()
+            .to_string();
+        assert!(!query.contains("delimiter"), "{}", query);
+
+        Ok(())
     }
 
-    Error::Object {
-        kind,
-        op,
-        path: path.to_string(),
-        source: anyhow!(
-            "response part: {:?}, body: {:?}",
-            part,
-            String::from_utf8_lossy(&bs)
-        ),
+    #[tokio::test]
+    async fn test_list_metadata() -> Result<()> {
+        let (endpoint, requests) = mock_server(|req| {
+            let resp = hyper::Response::builder();
+            if req.method() == http::Method::HEAD {
+                // dir/gone has been deleted after listing.
+                return if req.uri().path().ends_with("gone") {
+                    resp.status(StatusCode::NOT_FOUND)
+                        .body(hyper::Body::empty())
+                } else {
+                    resp.header(http::header::CONTENT_LENGTH, 3)
+                        .header(http::header::CONTENT_TYPE, "text/plain")
+                        .header(http::header::ETAG, "\"e1\"")
+                        .body(hyper::Body::empty())
+                }
+                .unwrap();
+            }
+            resp.body(hyper::Body::from(
+                r#"<ListBucketResult>
+  <IsTruncated>false</IsTruncated>
+  <Contents><Key>dir/a</Key><Size>3</Size><ETag>"e1"</ETag></Contents>
+  <Contents><Key>dir/gone</Key><Size>1</Size></Contents>
+  <CommonPrefixes><Prefix>dir/sub/</Prefix></CommonPrefixes>
+</ListBucketResult>"#,
+            ))
+            .unwrap()
+        });
+        let op = mock_s3_operator(&endpoint).await;
+
+        // Fields in the list response don't need extra requests.
+        let obs: Vec<_> = op.objects("dir/").try_collect().await?;
+        assert_eq!(obs.len(), 3);
+        let meta = obs[0].metadata_ref();
+        assert_eq!(meta.content_length(), 3);
+        assert_eq!(meta.etag(), Some("e1".to_string()));
+        assert_eq!(meta.content_type(), None);
+        assert!(!meta.complete());
+        assert_eq!(requests.lock().unwrap().len(), 1);
+
+        // Complete metadata stats files, but not dirs.
+        requests.lock().unwrap().clear();
+        let obs: Vec<_> = op
+            .objects("dir/")
+            .metadata(ListMetadata::Complete)
+            .try_collect()
+            .await?;
+        let listed: Vec<_> = obs
+            .iter()
+            .map(|o| (o.metadata_ref().path(), o.metadata_ref().mode()))
+            .collect();
+        assert_eq!(
+            listed,
+            vec![("dir/a", ObjectMode::FILE), ("dir/sub/", ObjectMode::DIR)]
+        );
+        let meta = obs[0].metadata_ref();
+        assert!(meta.complete());
+        assert_eq!(meta.content_type(), Some("text/plain"));
+        let methods: Vec<_> = requests
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|req| req.method().clone())
+            .collect();
+        assert_eq!(
+            methods,
+            vec![http::Method::GET, http::Method::HEAD, http::Method::HEAD]
+        );
+
+        Ok(())
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    #[tokio::test]
+    async fn test_scan() -> Result<()> {
+        let (endpoint, requests) = mock_server(|req| {
+            let query = req.uri().query().unwrap_or_default();
+            let body = if query.contains("continuation-token=t1") {
+                r#"<ListBucketResult>
+  <IsTruncated>false</IsTruncated>
+  <Contents><Key>data/dir/sub/</Key><Size>0</Size></Contents>
+  <Contents><Key>data/dir/sub/b</Key><Size>2</Size></Contents>
+</ListBucketResult>"#
+            } else {
+                r#"<ListBucketResult>
+  <IsTruncated>true</IsTruncated>
+  <NextContinuationToken>t1</NextContinuationToken>
+  <Contents><Key>data/dir/</Key><Size>0</Size></Contents>
+  <Contents><Key>data/dir/a</Key><Size>1</Size></Contents>
+</ListBucketResult>"#
+            };
+            hyper::Response::builder()
+                .body(hyper::Body::from(body))
+                .unwrap()
+        });
+        let mut builder = Backend::build();
+        builder
+            .root("/data")
+            .bucket("test")
+            .endpoint(&endpoint)
+            .region("us-east-1")
+            .credential(Credential::hmac("access_key_id", "secret_access_key"));
+        let op = Operator::new(builder.finish().await?);
+
+        let mut scanned = vec![];
+        let mut obs = op.scan("dir").await?;
+        while let Some(mut o) = obs.next().await.transpose()? {
+            let meta = o.metadata_mut();
+            scanned.push((meta.path().to_string(), meta.mode(), meta.content_length()));
+        }
+        // Dir markers are skipped, paths are relative to the root.
+        assert_eq!(
+            scanned,
+            vec![
+                ("dir/a".to_string(), ObjectMode::FILE, 1),
+                ("dir/sub/b".to_string(), ObjectMode::FILE, 2),
+            ]
+        );
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 2);
+        for req in requests.iter() {
+            let query = req.uri().query().unwrap();
+            assert!(!query.contains("delimiter"), "{}", query);
+            assert!(query.contains("prefix=data%2Fdir%2F"), "{}", query);
+        }
+
+        Ok(())
+    }
 
     #[tokio::test]
-    async fn test_detect_region() {
-        let client = hyper::Client::builder().build(hyper_tls::HttpsConnector::new());
+    async fn test_list_page_size() -> Result<()> {
+        let (endpoint, requests) = mock_server(|req| {
+            let query = req.uri().query().unwrap_or_default();
+            let (key, next) = if query.contains("continuation-token=t1") {
+                ("dir/b", None)
+            } else {
+                ("dir/a", Some("t1"))
+            };
+            let mut body = format!(
+                "<ListBucketResult><IsTruncated>{}</IsTruncated>",
+                next.is_some()
+            );
+            if let Some(next) = next {
+                body.push_str(&format!(
+                    "<NextContinuationToken>{}</NextContinuationToken>",
+                    next
+                ));
+            }
+            body.push_str(&format!(
+                "<Contents><Key>{}</Key><Size>1</Size></Contents></ListBucketResult>",
+                key
+            ));
+            hyper::Response::builder()
+                .body(hyper::Body::from(body))
+                .unwrap()
+        });
+        let op = mock_s3_operator(&endpoint).await;
 
-        // endpoint = `https://s3.amazonaws.com`, region = None
-        let b = Builder::default();
-        let (endpoint, region) = b
-            .detect_region(&client, "test", &HashMap::new())
-            .await
-            .expect("detect region must success");
-        assert_eq!(endpoint, "https://s3.us-east-2.amazonaws.com");
-        assert_eq!(region, "us-east-2");
+        let queries = |n: usize| -> Vec<String> {
+            requests.lock().unwrap()[n..]
+                .iter()
+                .map(|req| req.uri().query().unwrap_or_default().to_string())
+                .collect()
+        };
 
-        // endpoint = `https://s3.amazonaws.com`, region = `us-east-2`
-        let mut b = Builder::default();
-        b.region("us-east-2");
-        let (endpoint, region) = b
-            .detect_region(&client, "test", &HashMap::new())
+        let obs = op.objects("dir/").page_size(1);
+        assert_eq!(obs.try_collect::<Vec<_>>().await?.len(), 2);
+        let qs = queries(0);
+        assert_eq!(qs.len(), 2);
+        for q in qs {
+            assert!(q.contains("max-keys=1"), "{}", q);
+        }
+
+        // Page size larger than s3 allows will be clamped.
+        let obs = op.objects("dir/").page_size(5000);
+        assert_eq!(obs.try_collect::<Vec<_>>().await?.len(), 2);
+        for q in queries(2) {
+            assert!(q.contains("max-keys=1000"), "{}", q);
+        }
+
+        // The server's default is used if not set.
+        let obs = op.objects("dir/");
+        assert_eq!(obs.try_collect::<Vec<_>>().await?.len(), 2);
+        for q in queries(4) {
+            assert!(!q.contains("max-keys"), "{}", q);
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_list_pattern() -> Result<()> {
+        let (endpoint, _) = mock_server(|_| {
+            let body = "<ListBucketResult><IsTruncated>false</IsTruncated>\
+                 <Contents><Key>dir/_SUCCESS</Key><Size>0</Size></Contents>\
+                 <Contents><Key>dir/a.parquet</Key><Size>1</Size></Contents>\
+                 <Contents><Key>dir/a.parquet.crc</Key><Size>1</Size></Contents>\
+                 <Contents><Key>dir/b.parquet</Key><Size>1</Size></Contents>\
+                 <CommonPrefixes><Prefix>dir/sub/</Prefix></CommonPrefixes>\
+                 </ListBucketResult>";
+            hyper::Response::builder()
+                .body(hyper::Body::from(body))
+                .unwrap()
+        });
+        let op = mock_s3_operator(&endpoint).await;
+
+        let paths = |obs: Vec<Object>| -> Vec<String> {
+            obs.iter()
+                .map(|o| o.metadata_ref().path().to_string())
+                .collect()
+        };
+
+        // Dirs always pass through.
+        let obs = op.objects("dir/").pattern("dir/*.parquet");
+        assert_eq!(
+            paths(obs.try_collect().await?),
+            vec!["dir/a.parquet", "dir/b.parquet", "dir/sub/"]
+        );
+
+        // Entries are filtered before max_results is applied.
+        let obs = op.objects("dir/").pattern("**.crc").max_results(1);
+        assert_eq!(paths(obs.try_collect().await?), vec!["dir/a.parquet.crc"]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_list_objects_v1() -> Result<()> {
+        let (endpoint, requests) = mock_server(|req| {
+            let query = req.uri().query().unwrap_or_default();
+            // v1 pages without delimiter have no `NextMarker`.
+            let body = if query.contains("marker=dir%2Fb") {
+                "<ListBucketResult><IsTruncated>false</IsTruncated>\
+                 <Contents><Key>dir/c</Key><Size>1</Size></Contents>\
+                 </ListBucketResult>"
+            } else {
+                "<ListBucketResult><IsTruncated>true</IsTruncated>\
+                 <Contents><Key>dir/a</Key><Size>1</Size></Contents>\
+                 <Contents><Key>dir/b</Key><Size>1</Size></Contents>\
+                 </ListBucketResult>"
+            };
+            hyper::Response::builder()
+                .body(hyper::Body::from(body))
+                .unwrap()
+        });
+        let mut builder = Backend::build();
+        builder
+            .bucket("test")
+            .endpoint(&endpoint)
+            .region("us-east-1")
+            .credential(Credential::hmac("access_key_id", "secret_access_key"))
+            .enable_list_objects_v1(true);
+        let op = Operator::new(builder.finish().await?);
+
+        let obs = op.objects("dir/").recursive().start_after("dir/0");
+        let paths: Vec<String> = obs
+            .try_collect::<Vec<_>>()
+            .await?
+            .iter()
+            .map(|o| o.metadata_ref().path().to_string())
+            .collect();
+        assert_eq!(paths, vec!["dir/a", "dir/b", "dir/c"]);
+
+        let queries: Vec<String> = requests
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|req| req.uri().query().unwrap_or_default().to_string())
+            .collect();
+        assert_eq!(queries.len(), 2);
+        for q in &queries {
+            assert!(!q.contains("list-type"), "{}", q);
+            assert!(!q.contains("continuation-token"), "{}", q);
+        }
+        assert!(queries[0].contains("marker=dir%2F0"), "{}", queries[0]);
+        assert!(queries[1].contains("marker=dir%2Fb"), "{}", queries[1]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_list_max_results() -> Result<()> {
+        let (endpoint, requests) = mock_server(|req| {
+            let query = req.uri().query().unwrap_or_default();
+            let body = if query.contains("continuation-token=t1") {
+                "<ListBucketResult><IsTruncated>false</IsTruncated>\
+                 <Contents><Key>dir/c</Key><Size>1</Size></Contents>\
+                 </ListBucketResult>"
+            } else {
+                "<ListBucketResult><IsTruncated>true</IsTruncated>\
+                 <NextContinuationToken>t1</NextContinuationToken>\
+                 <Contents><Key>dir/a</Key><Size>1</Size></Contents>\
+                 <Contents><Key>dir/b</Key><Size>1</Size></Contents>\
+                 </ListBucketResult>"
+            };
+            hyper::Response::builder()
+                .body(hyper::Body::from(body))
+                .unwrap()
+        });
+        let op = mock_s3_operator(&endpoint).await;
+
+        for (max_results, entries, sent) in [(1, 1, 1), (2, 2, 1), (3, 3, 2), (4, 3, 2)] {
+            requests.lock().unwrap().clear();
+            let obs = op.objects("dir/").max_results(max_results);
+            let got = obs.try_collect::<Vec<_>>().await?;
+            assert_eq!(got.len(), entries, "max_results {}", max_results);
+            // The second page must not be fetched if the first one is enough.
+            assert_eq!(
+                requests.lock().unwrap().len(),
+                sent,
+                "max_results {}",
+                max_results
+            );
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_list_start_after() -> Result<()> {
+        let (endpoint, requests) = mock_server(|req| {
+            let query = req.uri().query().unwrap_or_default();
+            // Fixture listing of `dir/a` to `dir/e`, two keys per page.
+            let keys = ["dir/a", "dir/b", "dir/c", "dir/d", "dir/e"];
+            let start = if let Some(token) = query
+                .split('&')
+                .find_map(|v| v.strip_prefix("continuation-token="))
+            {
+                token.parse::<usize>().unwrap()
+            } else if query.contains("start-after=dir%2Fb") {
+                2
+            } else {
+                0
+            };
+            let end = (start + 2).min(keys.len());
+            let mut body = format!(
+                "<ListBucketResult><IsTruncated>{}</IsTruncated>",
+                end < keys.len()
+            );
+            if end < keys.len() {
+                body.push_str(&format!(
+                    "<NextContinuationToken>{}</NextContinuationToken>",
+                    end
+                ));
+            }
+            for key in &keys[start..end] {
+                body.push_str(&format!(
+                    "<Contents><Key>{}</Key><Size>1</Size></Contents>",
+                    key
+                ));
+            }
+            body.push_str("</ListBucketResult>");
+            hyper::Response::builder()
+                .body(hyper::Body::from(body))
+                .unwrap()
+        });
+        let op = mock_s3_operator(&endpoint).await;
+
+        let mut paths = vec![];
+        let mut obs = op.objects("dir/").start_after("dir/b");
+        while let Some(mut o) = obs.next().await.transpose()? {
+            paths.push(o.metadata_mut().path().to_string());
+        }
+        assert_eq!(paths, vec!["dir/c", "dir/d", "dir/e"]);
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 2);
+        let first = requests[0].uri().query().unwrap_or_default();
+        assert!(first.contains("start-after=dir%2Fb"), "{}", first);
+        assert!(!first.contains("continuation-token"), "{}", first);
+        let second = requests[1].uri().query().unwrap_or_default();
+        assert!(second.contains("continuation-token=4"), "{}", second);
+        assert!(!second.contains("start-after"), "{}", second);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_list_encode_query() -> Result<()> {
+        let (endpoint, requests) = mock_server(|req| {
+            let query = req.uri().query().unwrap_or_default();
+            let body = if query.contains("continuation-token") {
+                "<ListBucketResult><IsTruncated>false</IsTruncated></ListBucketResult>"
+            } else {
+                "<ListBucketResult>\
+                 <IsTruncated>true</IsTruncated>\
+                 <NextContinuationToken>a+b/c=</NextContinuationToken>\
+                 </ListBucketResult>"
+            };
+            hyper::Response::builder()
+                .body(hyper::Body::from(body))
+                .unwrap()
+        });
+        let op = mock_s3_operator(&endpoint).await;
+
+        op.objects("a#b/c#d%e+f/")
+            .start_after("a#b/c#d%e+f/g")
+            .try_collect::<Vec<_>>()
+            .await?;
+
+        let queries: Vec<String> = requests
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|req| req.uri().query().unwrap_or_default().to_string())
+            .collect();
+        assert_eq!(queries.len(), 2);
+        assert!(
+            queries[0].contains("prefix=a%23b%2Fc%23d%25e%2Bf%2F"),
+            "{}",
+            queries[0]
+        );
+        assert!(
+            queries[0].contains("start-after=a%23b%2Fc%23d%25e%2Bf%2Fg"),
+            "{}",
+            queries[0]
+        );
+        assert!(
+            queries[1].contains("continuation-token=a%2Bb%2Fc%3D"),
+            "{}",
+            queries[1]
+        );
+
+        // Spaces can't be signed by our signer, reject them instead of
+        // failing with `SignatureDoesNotMatch`.
+        let err = op
+            .objects("a b/")
+            .try_collect::<Vec<_>>()
             .await
-            .expect("detect region must success");
-        assert_eq!(endpoint, "https://s3.us-east-2.amazonaws.com");
-        assert_eq!(region, "us-east-2");
+            .unwrap_err();
+        assert_eq!(err.kind(), Kind::Unsupported);
+        assert_eq!(requests.lock().unwrap().len(), 2);
 
-        // endpoint = `https://s3.amazonaws.com`, region = `us-east-2`
-        let mut b = Builder::default();
-        b.endpoint("https://s3.amazonaws.com");
-        b.region("us-east-2");
-        let (endpoint, region) = b
-            .detect_region(&client, "test", &HashMap::new())
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_list_selected_metadata() -> Result<()> {
+        let (endpoint, requests) = mock_server(|_| {
+            hyper::Response::builder()
+                .body(hyper::Body::from(
+                    r#"<ListBucketResult>
+  <IsTruncated>false</IsTruncated>
+  <Contents>
+    <Key>dir/a</Key>
+    <LastModified>2022-03-01T08:00:00.000Z</LastModified>
+    <ETag>"d41d8cd98f00b204e9800998ecf8427e"</ETag>
+    <Size>56</Size>
+  </Contents>
+  <CommonPrefixes><Prefix>dir/sub/</Prefix></CommonPrefixes>
+</ListBucketResult>"#,
+                ))
+                .unwrap()
+        });
+        let op = mock_s3_operator(&endpoint).await;
+
+        let obs = op.objects("dir/").try_collect::<Vec<_>>().await?;
+        assert_eq!(obs.len(), 2);
+
+        let meta = obs[0].metadata_ref();
+        assert_eq!(meta.path(), "dir/a");
+        assert_eq!(meta.mode(), ObjectMode::FILE);
+        assert_eq!(meta.content_length(), 56);
+        assert_eq!(
+            meta.etag().as_deref(),
+            Some("d41d8cd98f00b204e9800998ecf8427e")
+        );
+        assert_eq!(
+            meta.last_modified(),
+            Some(std::time::UNIX_EPOCH + std::time::Duration::from_secs(1_646_121_600))
+        );
+        // Fields like content type are only returned by stat.
+        assert!(!meta.complete());
+
+        let meta = obs[1].metadata_ref();
+        assert_eq!(meta.path(), "dir/sub/");
+        assert_eq!(meta.mode(), ObjectMode::DIR);
+        assert!(meta.complete());
+
+        // No stat has been sent.
+        assert_eq!(requests.lock().unwrap().len(), 1);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_list_common_prefixes() -> Result<()> {
+        let (endpoint, _) = mock_server(|_| {
+            hyper::Response::builder()
+                .body(hyper::Body::from(
+                    r#"<ListBucketResult>
+  <IsTruncated>false</IsTruncated>
+  <Contents><Key>dir/a/</Key><Size>0</Size></Contents>
+  <Contents><Key>dir/b</Key><Size>1</Size></Contents>
+  <CommonPrefixes><Prefix>dir/a/</Prefix></CommonPrefixes>
+  <CommonPrefixes><Prefix>dir/c/</Prefix></CommonPrefixes>
+</ListBucketResult>"#,
+                ))
+                .unwrap()
+        });
+        let op = mock_s3_operator(&endpoint).await;
+
+        let list = |obs: ObjectStream| async move {
+            obs.map_ok(|o| o.metadata_ref().path().to_string())
+                .try_collect::<Vec<_>>()
+                .await
+        };
+
+        // `dir/a/` is returned both as a marker and a common prefix.
+        let mut paths = list(op.objects("dir/")).await?;
+        assert_eq!(paths.len(), 3, "{:?}", paths);
+        paths.sort();
+        assert_eq!(paths, vec!["dir/a/", "dir/b", "dir/c/"]);
+
+        let paths = list(op.objects("dir/").ordered()).await?;
+        assert_eq!(paths, vec!["dir/a/", "dir/b", "dir/c/"]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_list_versions() -> Result<()> {
+        let (endpoint, requests) = mock_server(|req| {
+            let query = req.uri().query().unwrap_or_default();
+            if !query.starts_with("versions") {
+                // Read of the given version.
+                assert!(query.contains("versionId=v1"), "{}", query);
+                return hyper::Response::builder()
+                    .header(http::header::CONTENT_LENGTH, "2")
+                    .body(hyper::Body::from("v1"))
+                    .unwrap();
+            }
+
+            let body = if query.contains("key-marker=dir%2Fa") {
+                r#"<ListVersionsResult>
+  <IsTruncated>false</IsTruncated>
+  <Version>
+    <Key>dir/b</Key>
+    <VersionId>v3</VersionId>
+    <IsLatest>true</IsLatest>
+    <LastModified>2022-03-01T08:00:00.000Z</LastModified>
+    <ETag>"abc"</ETag>
+    <Size>3</Size>
+  </Version>
+</ListVersionsResult>"#
+            } else {
+                r#"<ListVersionsResult>
+  <IsTruncated>true</IsTruncated>
+  <NextKeyMarker>dir/a</NextKeyMarker>
+  <NextVersionIdMarker>v1</NextVersionIdMarker>
+  <DeleteMarker>
+    <Key>dir/a</Key>
+    <VersionId>v2</VersionId>
+    <IsLatest>true</IsLatest>
+    <LastModified>2022-03-01T08:00:00.000Z</LastModified>
+  </DeleteMarker>
+  <Version>
+    <Key>dir/a</Key>
+    <VersionId>v1</VersionId>
+    <IsLatest>false</IsLatest>
+    <LastModified>2022-02-01T08:00:00.000Z</LastModified>
+    <Size>2</Size>
+  </Version>
+</ListVersionsResult>"#
+            };
+            hyper::Response::builder()
+                .body(hyper::Body::from(body))
+                .unwrap()
+        });
+        let op = mock_s3_operator(&endpoint).await;
+
+        let obs = op
+            .objects("dir/")
+            .versions()
+            .try_collect::<Vec<_>>()
+            .await?;
+        let listed: Vec<_> = obs
+            .iter()
+            .map(|o| {
+                let meta = o.metadata_ref();
+                (
+                    meta.path().to_string(),
+                    meta.version_id(),
+                    meta.is_latest(),
+                    meta.is_delete_marker(),
+                    meta.content_length(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            listed,
+            vec![
+                (
+                    "dir/a".to_string(),
+                    Some("v2".to_string()),
+                    Some(true),
+                    true,
+                    0
+                ),
+                (
+                    "dir/a".to_string(),
+                    Some("v1".to_string()),
+                    Some(false),
+                    false,
+                    2
+                ),
+                (
+                    "dir/b".to_string(),
+                    Some("v3".to_string()),
+                    Some(true),
+                    false,
+                    3
+                ),
+            ]
+        );
+
+        let queries: Vec<String> = requests
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|req| req.uri().query().unwrap_or_default().to_string())
+            .collect();
+        assert!(queries[0].contains("prefix=dir%2F"), "{}", queries[0]);
+        assert!(!queries[0].contains("key-marker"), "{}", queries[0]);
+        assert!(
+            queries[1].contains("key-marker=dir%2Fa&version-id-marker=v1"),
+            "{}",
+            queries[1]
+        );
+
+        // Restore the content of the old version.
+        let old = &obs[1];
+        let mut bs = Vec::new();
+        old.reader()
+            .version(&old.metadata_ref().version_id().unwrap())
+            .read_to_end(&mut bs)
             .await
-            .expect("detect region must success");
-        assert_eq!(endpoint, "https://s3.us-east-2.amazonaws.com");
-        assert_eq!(region, "us-east-2");
+            .unwrap();
+        assert_eq!(bs, b"v1");
 
-        // endpoint = `https://s3.us-east-2.amazonaws.com`, region = `us-east-2`
-        let mut b = Builder::default();
-        b.endpoint("https://s3.us-east-2.amazonaws.com");
-        b.region("us-east-2");
-        let (endpoint, region) = b
-            .detect_region(&client, "test", &HashMap::new())
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_list_encoding_type() -> Result<()> {
+        let (endpoint, requests) = mock_server(|req| {
+            let body = match req.uri().path() {
+                "/test/dir/a%0Ab%25c" => "data",
+                // Keys are percent-encoded by `encoding-type=url`.
+                "/test" => {
+                    "<ListBucketResult><IsTruncated>false</IsTruncated>\
+                     <Contents><Key>dir/a%0Ab%25c</Key><Size>4</Size></Contents>\
+                     <CommonPrefixes><Prefix>dir/d%01/</Prefix></CommonPrefixes>\
+                     </ListBucketResult>"
+                }
+                _ => {
+                    return hyper::Response::builder()
+                        .status(StatusCode::NOT_FOUND)
+                        .body(hyper::Body::empty())
+                        .unwrap()
+                }
+            };
+            hyper::Response::builder()
+                .body(hyper::Body::from(body))
+                .unwrap()
+        });
+        let op = mock_s3_operator(&endpoint).await;
+
+        let obs = op.objects("dir/").try_collect::<Vec<_>>().await?;
+        let paths: Vec<_> = obs.iter().map(|o| o.metadata_ref().path()).collect();
+        assert_eq!(paths, vec!["dir/a\nb%c", "dir/d\u{1}/"]);
+        let query = requests.lock().unwrap()[0]
+            .uri()
+            .query()
+            .unwrap_or_default()
+            .to_string();
+        assert!(query.contains("encoding-type=url"), "{}", query);
+
+        // Decoded keys are encoded again in the path of requests.
+        let mut bs = Vec::new();
+        obs[0].reader().read_to_end(&mut bs).await.unwrap();
+        assert_eq!(bs, b"data");
+        assert_eq!(obs[0].metadata().await?.content_length(), 4);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_abort_stale_multipart_uploads() -> Result<()> {
+        let (endpoint, requests) = mock_server(|req| {
+            if req.method() == http::Method::DELETE {
+                return hyper::Response::builder()
+                    .status(StatusCode::NO_CONTENT)
+                    .body(hyper::Body::empty())
+                    .unwrap();
+            }
+
+            let query = req.uri().query().unwrap_or_default();
+            let body = if query.contains("key-marker=root%2Fdir%2Fb") {
+                r#"<ListMultipartUploadsResult>
+  <IsTruncated>false</IsTruncated>
+  <Upload><Key>root/dir/c</Key><UploadId>u3</UploadId></Upload>
+</ListMultipartUploadsResult>"#
+                    .to_string()
+            } else {
+                let recent = format_http_date(time::OffsetDateTime::now_utc()).unwrap();
+                format!(
+                    r#"<ListMultipartUploadsResult>
+  <IsTruncated>true</IsTruncated>
+  <NextKeyMarker>root/dir/b</NextKeyMarker>
+  <NextUploadIdMarker>u2</NextUploadIdMarker>
+  <Upload><Key>root/dir/a</Key><UploadId>u1</UploadId><Initiated>2010-11-10T20:48:33.000Z</Initiated></Upload>
+  <Upload><Key>root/dir/b</Key><UploadId>u2</UploadId><Initiated>{}</Initiated></Upload>
+</ListMultipartUploadsResult>"#,
+                    recent
+                )
+            };
+            hyper::Response::builder()
+                .body(hyper::Body::from(body))
+                .unwrap()
+        });
+        let backend = inventory_backend(&endpoint, "/root/").await?;
+
+        let uploads = backend.list_multipart_uploads("dir/").await?;
+        assert_eq!(
+            uploads
+                .iter()
+                .map(|v| (v.path.as_str(), v.upload_id.as_str()))
+                .collect::<Vec<_>>(),
+            vec![("dir/a", "u1"), ("dir/b", "u2"), ("dir/c", "u3")]
+        );
+
+        let aborted = backend
+            .abort_stale_multipart_uploads("dir/", Duration::from_secs(24 * 3600))
+            .await?;
+        assert_eq!(aborted.len(), 1);
+        assert_eq!(aborted[0].upload_id, "u1");
+
+        let requests = requests.lock().unwrap();
+        let first = requests[0].uri().query().unwrap_or_default();
+        assert!(
+            first.starts_with("uploads&prefix=root%2Fdir%2F"),
+            "{}",
+            first
+        );
+        let deletes = requests
+            .iter()
+            .filter(|req| req.method() == http::Method::DELETE)
+            .map(|req| req.uri().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(deletes, vec!["/test/root/dir/a?uploadId=u1"]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_get_rel_path() -> Result<()> {
+        let (endpoint, _) = mock_server(|_| hyper::Response::new(hyper::Body::empty()));
+        let mut builder = Backend::build();
+        builder
+            .root("/abc")
+            .bucket("test")
+            .endpoint(&endpoint)
+            .region("us-east-1")
+            .credential(Credential::hmac("access_key_id", "secret_access_key"));
+        let backend = builder.build_backend().await?;
+
+        assert_eq!(backend.get_rel_path("list", "abc/dir/file")?, "dir/file");
+        // Keys outside of the root are errors instead of panics.
+        let err = backend.get_rel_path("list", "other/file").unwrap_err();
+        assert_eq!(err.kind(), Kind::ObjectOutOfScope);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_accessor_metadata() -> Result<()> {
+        let (endpoint, _) = mock_server(|_| hyper::Response::new(hyper::Body::empty()));
+        let mut builder = Backend::build();
+        builder
+            .root("/abc")
+            .bucket("test")
+            .endpoint(&endpoint)
+            .region("us-east-1")
+            .credential(Credential::hmac("access_key_id", "secret_access_key"));
+        let op = Operator::new(builder.finish().await?);
+
+        let am = op.metadata();
+        assert_eq!(am.scheme(), Some(Scheme::S3));
+        assert_eq!(am.root(), "/abc/");
+        assert!(am.list());
+        assert!(am.copy());
+        assert!(am.presign());
+        assert!(am.multipart());
+        // S3 objects can't be appended, appends are rejected cleanly.
+        assert!(!am.append());
+        let err = op
+            .object("log")
+            .append(0, Box::new(futures::io::Cursor::new(b"abc")), 3)
             .await
-            .expect("detect region must success");
-        assert_eq!(endpoint, "https://s3.us-east-2.amazonaws.com");
-        assert_eq!(region, "us-east-2");
+            .unwrap_err();
+        assert_eq!(err.kind(), Kind::Unsupported);
+
+        let am = op.layer(ImmutableLayer).metadata();
+        assert!(am.presign());
+        assert!(!am.copy());
+        assert!(!am.multipart());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_batch() -> Result<()> {
+        let (endpoint, requests) = mock_server(|req| {
+            let resp = hyper::Response::builder();
+            match *req.method() {
+                http::Method::POST => resp.body(hyper::Body::from(
+                    "<DeleteResult><Error><Key>denied</Key><Code>AccessDenied</Code>\
+                     <Message>Access Denied</Message></Error></DeleteResult>",
+                )),
+                http::Method::DELETE => resp
+                    .status(StatusCode::NO_CONTENT)
+                    .body(hyper::Body::empty()),
+                _ => resp
+                    .header(http::header::CONTENT_LENGTH, "4")
+                    .body(hyper::Body::empty()),
+            }
+            .unwrap()
+        });
+        let op = mock_s3_operator(&endpoint).await;
+
+        let mut ops = (0..MAX_DELETE_OBJECTS)
+            .map(|i| BatchOperation::Delete(OpDelete::new(&format!("k{}", i))))
+            .collect::<Vec<_>>();
+        ops.push(BatchOperation::Delete(OpDelete::new("a&b")));
+        ops.push(BatchOperation::Delete(OpDelete::new("denied")));
+        let mut cond = OpDelete::new("cond");
+        cond.if_match = Some("\"etag\"".to_string());
+        ops.push(BatchOperation::Delete(cond));
+        ops.push(BatchOperation::Stat(OpStat::new("file")));
+        let results = op.batch(ops.clone()).await?;
+
+        assert_eq!(results.len(), ops.len());
+        for ((path, res), op) in results.iter().zip(&ops) {
+            assert_eq!(path, op.path());
+            match (path.as_str(), res) {
+                ("denied", BatchResult::Delete(Err(err))) => {
+                    assert_eq!(err.kind(), Kind::ObjectPermissionDenied);
+                    assert_eq!(err.context()["code"], "AccessDenied");
+                }
+                ("file", BatchResult::Stat(Ok(meta))) => {
+                    assert_eq!(meta.path(), "file");
+                    assert_eq!(meta.content_length(), 4);
+                }
+                (_, BatchResult::Delete(Ok(()))) => {}
+                (path, res) => panic!("unexpected result of {}: {:?}", path, res),
+            }
+        }
+
+        let requests = requests.lock().unwrap();
+        let posts = requests
+            .iter()
+            .filter(|req| req.method() == http::Method::POST)
+            .collect::<Vec<_>>();
+        assert_eq!(posts.len(), 2, "deletes must be sent in 2 requests");
+        for req in &posts {
+            assert_eq!(req.uri().path(), "/test");
+            assert_eq!(req.uri().query(), Some("delete"));
+            assert_eq!(
+                req.headers()[constants::CONTENT_MD5],
+                base64::encode(md5::compute(req.body()).as_slice()).as_str()
+            );
+        }
+        let bodies = posts
+            .iter()
+            .map(|req| String::from_utf8_lossy(req.body()).to_string())
+            .collect::<String>();
+        assert!(bodies.contains("<Quiet>true</Quiet>"));
+        assert!(bodies.contains("<Key>k999</Key>"));
+        assert!(bodies.contains("<Key>a&amp;b</Key>"));
+        assert!(!bodies.contains("cond"));
+
+        let deletes = requests
+            .iter()
+            .filter(|req| req.method() == http::Method::DELETE)
+            .collect::<Vec<_>>();
+        assert_eq!(deletes.len(), 1);
+        assert_eq!(deletes[0].uri().path(), "/test/cond");
+        assert_eq!(deletes[0].headers()[http::header::IF_MATCH], "\"etag\"");
+
+        Ok(())
     }
 }
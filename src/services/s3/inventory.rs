@@ -0,0 +1,535 @@
+// Copyright 2022 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+use std::io::Write;
+use std::mem;
+use std::sync::Arc;
+
+use anyhow::anyhow;
+use bytes::Bytes;
+use csv_core::ReadRecordResult;
+use flate2::write::MultiGzDecoder;
+use futures::StreamExt;
+use futures::TryStreamExt;
+use log::debug;
+use log::warn;
+use percent_encoding::percent_decode_str;
+use serde::Deserialize;
+use serde::Serialize;
+
+use super::Backend;
+use crate::error::Error;
+use crate::error::Kind;
+use crate::error::Result;
+use crate::http_util::parse_datetime;
+use crate::io::BytesStream;
+use crate::Accessor;
+use crate::BoxedObjectStream;
+use crate::Object;
+use crate::ObjectMode;
+
+/// InventoryManifest is the `manifest.json` of an [S3 Inventory](https://docs.aws.amazon.com/AmazonS3/latest/userguide/storage-inventory.html)
+/// report, which lists the data files of the report.
+///
+/// Only the fields we rely on are parsed, unknown fields will be ignored.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InventoryManifest {
+    /// The bucket that the inventory is taken for.
+    pub source_bucket: String,
+    /// The bucket that the report is delivered to, like `arn:aws:s3:::bucket`.
+    pub destination_bucket: String,
+    /// Format of data files, one of `CSV`, `ORC` and `Parquet`.
+    pub file_format: String,
+    /// Comma separated column names of data files, like `Bucket, Key, Size`.
+    ///
+    /// Only set for `CSV` reports, the other formats carry the schema in
+    /// data files.
+    #[serde(default)]
+    pub file_schema: String,
+    pub files: Vec<InventoryFile>,
+}
+
+/// InventoryFile is a data file of the inventory report.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InventoryFile {
+    /// The key of the data file in the destination bucket.
+    pub key: String,
+    pub size: u64,
+    /// Hex encoded MD5 of the data file.
+    #[serde(rename = "MD5checksum")]
+    pub md5_checksum: String,
+}
+
+impl InventoryManifest {
+    /// Parse the manifest from bytes, `path` is used for errors.
+    pub(crate) fn from_slice(path: &str, bs: &[u8]) -> Result<Self> {
+        serde_json::from_slice(bs).map_err(|e| Error::Object {
+            kind: Kind::ObjectCorrupted,
+            op: "read",
+            path: path.to_string(),
+            context: HashMap::new(),
+            source: anyhow!("parse inventory manifest: {:?}", e),
+        })
+    }
+
+    /// The name of the destination bucket, without the `arn:aws:s3:::` prefix.
+    pub fn destination_bucket_name(&self) -> &str {
+        self.destination_bucket
+            .strip_prefix("arn:aws:s3:::")
+            .unwrap_or(&self.destination_bucket)
+    }
+}
+
+/// Positions of the columns we care about in data files, which are driven
+/// by the `fileSchema` of the manifest.
+#[derive(Debug, Clone, Default)]
+struct Columns {
+    key: usize,
+    size: Option<usize>,
+    last_modified: Option<usize>,
+    etag: Option<usize>,
+    version_id: Option<usize>,
+    is_latest: Option<usize>,
+    is_delete_marker: Option<usize>,
+}
+
+impl Columns {
+    /// Returns `None` if there is no `Key` column.
+    fn parse(schema: &str) -> Option<Self> {
+        let names: Vec<&str> = schema.split(',').map(|v| v.trim()).collect();
+        let find = |name: &str| names.iter().position(|v| *v == name);
+
+        Some(Columns {
+            key: find("Key")?,
+            size: find("Size"),
+            last_modified: find("LastModifiedDate"),
+            etag: find("ETag"),
+            version_id: find("VersionId"),
+            is_latest: find("IsLatest"),
+            is_delete_marker: find("IsDeleteMarker"),
+        })
+    }
+}
+
+/// Stream the entries of CSV inventory data files as objects, just like a
+/// recursive listing.
+///
+/// Keys are made relative to the backend's root by `prefix`, keys outside
+/// of it will be skipped. Keys are used as is if `prefix` is `None`.
+pub(crate) fn inventory_stream(
+    backend: Backend,
+    manifest_path: &str,
+    manifest: InventoryManifest,
+    prefix: Option<String>,
+) -> Result<BoxedObjectStream> {
+    if !manifest.file_format.eq_ignore_ascii_case("CSV") {
+        return Err(Error::Object {
+            kind: Kind::Unsupported,
+            op: "read",
+            path: manifest_path.to_string(),
+            context: HashMap::from([("format".to_string(), manifest.file_format.clone())]),
+            source: anyhow!(
+                "inventory format {} is not supported, read data files of the manifest directly instead",
+                manifest.file_format
+            ),
+        });
+    }
+    let columns = Columns::parse(&manifest.file_schema).ok_or_else(|| Error::Object {
+        kind: Kind::ObjectCorrupted,
+        op: "read",
+        path: manifest_path.to_string(),
+        context: HashMap::new(),
+        source: anyhow!("inventory schema {} has no Key", manifest.file_schema),
+    })?;
+
+    let acc: Arc<dyn Accessor> = Arc::new(backend.clone());
+    let s = futures::stream::iter(manifest.files)
+        .then(move |file| {
+            let backend = backend.clone();
+            async move {
+                let body = backend.read_key(&file.key).await?;
+                Ok::<_, Error>((file, body))
+            }
+        })
+        .map_ok(move |(file, body)| {
+            let state = DataFile::new(acc.clone(), columns.clone(), prefix.clone(), file, body);
+            futures::stream::try_unfold(state, |mut state| async move {
+                Ok::<_, Error>(state.next_batch().await?.map(|batch| (batch, state)))
+            })
+            .map_ok(|batch| futures::stream::iter(batch.into_iter().map(Ok)))
+            .try_flatten()
+        })
+        .try_flatten();
+
+    Ok(Box::new(Box::pin(s)))
+}
+
+/// DataFile parses a data file while streaming, in batches of objects.
+struct DataFile {
+    acc: Arc<dyn Accessor>,
+    columns: Columns,
+    prefix: Option<String>,
+    file: InventoryFile,
+
+    body: BytesStream,
+    /// `None` if the data file is not compressed.
+    decoder: Option<MultiGzDecoder<Vec<u8>>>,
+    records: CsvRecords,
+    md5: md5::Context,
+    read: u64,
+    done: bool,
+}
+
+impl DataFile {
+    fn new(
+        acc: Arc<dyn Accessor>,
+        columns: Columns,
+        prefix: Option<String>,
+        file: InventoryFile,
+        body: BytesStream,
+    ) -> Self {
+        let decoder = if file.key.ends_with(".gz") {
+            Some(MultiGzDecoder::new(Vec::new()))
+        } else {
+            None
+        };
+
+        Self {
+            acc,
+            columns,
+            prefix,
+            file,
+
+            body,
+            decoder,
+            records: CsvRecords::new(),
+            md5: md5::Context::new(),
+            read: 0,
+            done: false,
+        }
+    }
+
+    /// Returns `None` if the data file has been consumed.
+    async fn next_batch(&mut self) -> Result<Option<Vec<Object>>> {
+        while !self.done {
+            let records = match self.body.try_next().await? {
+                Some(bs) => {
+                    self.md5.consume(&bs);
+                    self.read += bs.len() as u64;
+                    let bs = self.decompress(bs, false)?;
+                    self.records.feed(&bs)
+                }
+                None => {
+                    self.done = true;
+                    self.verify()?;
+                    let bs = self.decompress(Bytes::new(), true)?;
+                    let mut records = self.records.feed(&bs);
+                    records.extend(self.records.finish());
+                    records
+                }
+            };
+
+            let mut batch = Vec::with_capacity(records.len());
+            for record in records {
+                if let Some(o) = self.parse_record(&record)? {
+                    batch.push(o);
+                }
+            }
+            if !batch.is_empty() {
+                return Ok(Some(batch));
+            }
+        }
+
+        debug!("inventory data file {} read done", &self.file.key);
+        Ok(None)
+    }
+
+    fn decompress(&mut self, bs: Bytes, finish: bool) -> Result<Bytes> {
+        let res = match &mut self.decoder {
+            Some(decoder) => if finish {
+                decoder.try_finish()
+            } else {
+                decoder.write_all(&bs)
+            }
+            .map(|_| mem::take(decoder.get_mut())),
+            None => return Ok(bs),
+        };
+
+        let bs =
+            res.map_err(|e| self.error(Kind::ObjectCorrupted, anyhow!("decompress: {:?}", e)))?;
+        Ok(Bytes::from(bs))
+    }
+
+    /// Verify the data file against the manifest.
+    fn verify(&self) -> Result<()> {
+        if self.read != self.file.size {
+            return Err(self.error(
+                Kind::ObjectChecksumMismatch,
+                anyhow!(
+                    "data file size is {}, but {} in manifest",
+                    self.read,
+                    self.file.size
+                ),
+            ));
+        }
+        let md5 = format!("{:x}", self.md5.clone().compute());
+        if !md5.eq_ignore_ascii_case(&self.file.md5_checksum) {
+            return Err(self.error(
+                Kind::ObjectChecksumMismatch,
+                anyhow!(
+                    "data file md5 is {}, but {} in manifest",
+                    md5,
+                    self.file.md5_checksum
+                ),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Parse the record into an object, returns `None` if the record should
+    /// not be listed.
+    fn parse_record(&self, record: &[String]) -> Result<Option<Object>> {
+        let field = |idx: Option<usize>| -> Result<Option<&str>> {
+            match idx {
+                None => Ok(None),
+                Some(idx) => match record.get(idx) {
+                    Some(v) => Ok(Some(v.as_str())),
+                    None => Err(self.error(
+                        Kind::ObjectCorrupted,
+                        anyhow!(
+                            "record has {} fields, but schema requires {}",
+                            record.len(),
+                            idx + 1
+                        ),
+                    )),
+                },
+            }
+        };
+
+        // Only the latest version of objects are listed.
+        if field(self.columns.is_delete_marker)? == Some("true")
+            || field(self.columns.is_latest)? == Some("false")
+        {
+            return Ok(None);
+        }
+
+        let key = field(Some(self.columns.key))?.expect("key column must exist");
+        let key = decode_key(key)
+            .map_err(|e| self.error(Kind::ObjectCorrupted, anyhow!("decode key: {:?}", e)))?;
+        let path = match &self.prefix {
+            Some(prefix) => match key.strip_prefix(prefix.as_str()) {
+                Some(v) if !v.is_empty() => v.to_string(),
+                _ => return Ok(None),
+            },
+            None => key,
+        };
+
+        let mut o = Object::new(self.acc.clone(), &path);
+        let meta = o.metadata_mut();
+        if path.ends_with('/') {
+            meta.set_mode(ObjectMode::DIR);
+        } else {
+            meta.set_mode(ObjectMode::FILE);
+        }
+        if let Some(v) = field(self.columns.size)?.filter(|v| !v.is_empty()) {
+            let size = v.parse::<u64>().map_err(|e| {
+                self.error(
+                    Kind::ObjectCorrupted,
+                    anyhow!("invalid size {}: {:?}", v, e),
+                )
+            })?;
+            meta.set_content_length(size);
+        }
+        if let Some(v) = field(self.columns.etag)?.filter(|v| !v.is_empty()) {
+            meta.set_etag(v);
+        }
+        if let Some(v) = field(self.columns.version_id)?.filter(|v| !v.is_empty()) {
+            meta.set_version_id(v);
+        }
+        if let Some(v) = field(self.columns.last_modified)?.filter(|v| !v.is_empty()) {
+            match parse_datetime(v) {
+                Ok(t) => {
+                    meta.set_last_modified(t.into());
+                }
+                Err(e) => warn!(
+                    "object {} got invalid last modified {}: {:?}",
+                    meta.path(),
+                    v,
+                    e
+                ),
+            }
+        }
+
+        Ok(Some(o))
+    }
+
+    fn error(&self, kind: Kind, source: anyhow::Error) -> Error {
+        Error::Object {
+            kind,
+            op: "read",
+            path: self.file.key.clone(),
+            context: HashMap::from([("record".to_string(), self.records.count.to_string())]),
+            source,
+        }
+    }
+}
+
+/// Keys in inventory are URL encoded like forms, in which `+` is space.
+fn decode_key(key: &str) -> std::result::Result<String, std::str::Utf8Error> {
+    let key = key.replace('+', " ");
+    Ok(percent_decode_str(&key).decode_utf8()?.into_owned())
+}
+
+/// CsvRecords splits CSV into records incrementally, fields in quotes can
+/// contain delimiters, line breaks and escaped quotes like `""`.
+struct CsvRecords {
+    reader: csv_core::Reader,
+    output: Vec<u8>,
+    nout: usize,
+    ends: Vec<usize>,
+    nend: usize,
+    /// Records parsed so far.
+    count: u64,
+}
+
+impl CsvRecords {
+    fn new() -> Self {
+        Self {
+            reader: csv_core::Reader::new(),
+            output: vec![0; 1024],
+            nout: 0,
+            ends: vec![0; 16],
+            nend: 0,
+            count: 0,
+        }
+    }
+
+    /// Feed the input, returns records completed by it.
+    fn feed(&mut self, input: &[u8]) -> Vec<Vec<String>> {
+        let mut records = Vec::new();
+        let mut input = input;
+        // Empty input means the end of CSV for the reader.
+        while !input.is_empty() && self.read(&mut input, &mut records) {}
+        records
+    }
+
+    /// Finish the CSV, returns the last record if it's not terminated.
+    fn finish(&mut self) -> Vec<Vec<String>> {
+        let mut records = Vec::new();
+        while self.read(&mut &[][..], &mut records) {}
+        records
+    }
+
+    /// Read from input once, returns `false` if more input is required.
+    fn read(&mut self, input: &mut &[u8], records: &mut Vec<Vec<String>>) -> bool {
+        let (res, nin, nout, nend) = self.reader.read_record(
+            input,
+            &mut self.output[self.nout..],
+            &mut self.ends[self.nend..],
+        );
+        *input = &input[nin..];
+        self.nout += nout;
+        self.nend += nend;
+
+        match res {
+            ReadRecordResult::InputEmpty | ReadRecordResult::End => false,
+            ReadRecordResult::OutputFull => {
+                self.output.resize(self.output.len() * 2, 0);
+                true
+            }
+            ReadRecordResult::OutputEndsFull => {
+                self.ends.resize(self.ends.len() * 2, 0);
+                true
+            }
+            ReadRecordResult::Record => {
+                let mut start = 0;
+                let record = self.ends[..self.nend]
+                    .iter()
+                    .map(|&end| {
+                        let field = String::from_utf8_lossy(&self.output[start..end]);
+                        start = end;
+                        field.into_owned()
+                    })
+                    .collect();
+                records.push(record);
+                self.count += 1;
+                self.nout = 0;
+                self.nend = 0;
+                true
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_csv_records() {
+        let csv = "\"a\",\"b, c\",\"1\"\n\"d\",\"e\nf \"\"g\"\"\",\"2\"\r\n\"h\",\"\",\"3\"";
+        let expected: Vec<Vec<String>> = vec![
+            vec!["a".into(), "b, c".into(), "1".into()],
+            vec!["d".into(), "e\nf \"g\"".into(), "2".into()],
+            vec!["h".into(), "".into(), "3".into()],
+        ];
+
+        // Records must be the same no matter how the input is split.
+        for size in [1, 2, 7, csv.len()] {
+            let mut records = CsvRecords::new();
+            let mut actual = Vec::new();
+            for chunk in csv.as_bytes().chunks(size) {
+                actual.extend(records.feed(chunk));
+            }
+            actual.extend(records.finish());
+            assert_eq!(actual, expected, "chunk size {}", size);
+            assert_eq!(records.count, 3);
+        }
+    }
+
+    #[test]
+    fn test_csv_records_long_field() {
+        let key = "k".repeat(5000);
+        let csv = format!("\"{}\",\"1\"\n", key);
+
+        let mut records = CsvRecords::new();
+        let mut actual = records.feed(csv.as_bytes());
+        actual.extend(records.finish());
+        assert_eq!(actual, vec![vec![key, "1".to_string()]]);
+    }
+
+    #[test]
+    fn test_parse_columns() {
+        let columns =
+            Columns::parse("Bucket, Key, VersionId, IsLatest, IsDeleteMarker, Size, ETag")
+                .expect("must have key");
+        assert_eq!(columns.key, 1);
+        assert_eq!(columns.version_id, Some(2));
+        assert_eq!(columns.is_latest, Some(3));
+        assert_eq!(columns.is_delete_marker, Some(4));
+        assert_eq!(columns.size, Some(5));
+        assert_eq!(columns.etag, Some(6));
+        assert_eq!(columns.last_modified, None);
+
+        assert!(Columns::parse("Bucket, Size").is_none());
+    }
+
+    #[test]
+    fn test_decode_key() {
+        assert_eq!(decode_key("a+b%2Bc%2C%E4%BD%A0").unwrap(), "a b+c,你");
+        assert!(decode_key("%FF").is_err());
+    }
+}
@@ -70,4 +70,16 @@ mod backend;
 pub use backend::Backend;
 pub use backend::Builder;
 
+mod inventory;
+pub use inventory::InventoryFile;
+pub use inventory::InventoryManifest;
+
+mod multipart;
+pub use multipart::MultipartUpload;
+
 mod object_stream;
+
+mod presign;
+
+mod public_dataset;
+pub use public_dataset::PublicDataset;
@@ -0,0 +1,142 @@
+// Copyright 2022 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+use std::time::SystemTime;
+
+use anyhow::anyhow;
+use log::warn;
+use quick_xml::de;
+use serde::Deserialize;
+
+use crate::error::Error;
+use crate::error::Kind;
+use crate::error::Result;
+use crate::http_util::parse_datetime;
+
+/// MultipartUpload is an in-progress multipart upload, which is neither
+/// completed nor aborted, like the ones left by crashed writers.
+///
+/// Parts of it are billed until the upload is aborted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MultipartUpload {
+    /// Path of the object relative to the backend's root.
+    pub path: String,
+    pub upload_id: String,
+    /// When the upload was initiated, `None` if the service didn't return it.
+    pub initiated: Option<SystemTime>,
+}
+
+/// Output of ListMultipartUploads.
+#[derive(Default, Debug, Deserialize)]
+#[serde(default, rename_all = "PascalCase")]
+pub(crate) struct ListMultipartUploadsOutput {
+    pub is_truncated: Option<bool>,
+    pub next_key_marker: String,
+    pub next_upload_id_marker: String,
+    pub upload: Vec<OutputUpload>,
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(default, rename_all = "PascalCase")]
+pub(crate) struct OutputUpload {
+    pub key: String,
+    pub upload_id: String,
+    pub initiated: String,
+}
+
+impl ListMultipartUploadsOutput {
+    /// Parse the output from bytes, `path` is used for errors.
+    pub(crate) fn from_slice(path: &str, bs: &[u8]) -> Result<Self> {
+        de::from_reader(bs).map_err(|e| Error::Object {
+            kind: Kind::Unexpected,
+            op: "list_multipart_uploads",
+            path: path.to_string(),
+            context: HashMap::new(),
+            source: anyhow!("deserialize list_multipart_uploads output: {:?}", e),
+        })
+    }
+
+    /// Whether there are more uploads to list after this page.
+    pub(crate) fn has_more(&self) -> bool {
+        match self.is_truncated {
+            Some(v) => v,
+            None => !self.next_key_marker.is_empty(),
+        }
+    }
+}
+
+impl OutputUpload {
+    pub(crate) fn initiated(&self) -> Option<SystemTime> {
+        if self.initiated.is_empty() {
+            return None;
+        }
+        match parse_datetime(&self.initiated) {
+            Ok(t) => Some(t.into()),
+            Err(e) => {
+                warn!(
+                    "multipart upload {} of {} got invalid initiated {}: {:?}",
+                    &self.upload_id, &self.key, &self.initiated, e
+                );
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_list_multipart_uploads_output() {
+        let bs = r#"<?xml version="1.0" encoding="UTF-8"?>
+<ListMultipartUploadsResult xmlns="http://s3.amazonaws.com/doc/2006-03-01/">
+  <Bucket>bucket</Bucket>
+  <KeyMarker></KeyMarker>
+  <UploadIdMarker></UploadIdMarker>
+  <NextKeyMarker>my-movie.m2ts</NextKeyMarker>
+  <NextUploadIdMarker>YW55IGlkZWEgd2h5IGVsdmluZydzIHVwbG9hZCBmYWlsZWQ</NextUploadIdMarker>
+  <MaxUploads>3</MaxUploads>
+  <IsTruncated>true</IsTruncated>
+  <Upload>
+    <Key>my-divisor</Key>
+    <UploadId>XMgbGlrZSBlbHZpbmcncyBub3QgaGF2aW5nIG11Y2ggbHVjaw</UploadId>
+    <StorageClass>REDUCED_REDUNDANCY</StorageClass>
+    <Initiated>2010-11-10T20:48:33.000Z</Initiated>
+  </Upload>
+  <Upload>
+    <Key>my-movie.m2ts</Key>
+    <UploadId>VXBsb2FkIElEIGZvciBlbHZpbmcncyBteS1tb3ZpZS5tMnRzIHVwbG9hZA</UploadId>
+    <StorageClass>STANDARD</StorageClass>
+    <Initiated>invalid</Initiated>
+  </Upload>
+</ListMultipartUploadsResult>"#;
+
+        let out = ListMultipartUploadsOutput::from_slice("", bs.as_bytes()).expect("must success");
+        assert!(out.has_more());
+        assert_eq!(out.next_key_marker, "my-movie.m2ts");
+        assert_eq!(
+            out.next_upload_id_marker,
+            "YW55IGlkZWEgd2h5IGVsdmluZydzIHVwbG9hZCBmYWlsZWQ"
+        );
+        assert_eq!(out.upload.len(), 2);
+        assert_eq!(out.upload[0].key, "my-divisor");
+        assert_eq!(
+            out.upload[0].initiated(),
+            Some(std::time::UNIX_EPOCH + std::time::Duration::from_secs(1_289_422_113))
+        );
+        assert_eq!(out.upload[1].initiated(), None);
+    }
+}
@@ -12,6 +12,9 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::collections::VecDeque;
 use std::future::Future;
 use std::pin::Pin;
 use std::sync::Arc;
@@ -20,11 +23,13 @@ use std::task::Poll;
 
 use anyhow::anyhow;
 use bytes::Buf;
-use bytes::BufMut;
+use bytes::BytesMut;
 use futures::future::BoxFuture;
 use futures::ready;
-use futures::StreamExt;
+use futures::Stream;
 use log::debug;
+use log::warn;
+use percent_encoding::percent_decode_str;
 use quick_xml::de;
 use serde::Deserialize;
 
@@ -32,22 +37,48 @@ use super::Backend;
 use crate::error::Error;
 use crate::error::Kind;
 use crate::error::Result;
+use crate::http_util::parse_datetime;
+use crate::layers::glob::Glob;
+use crate::Metadata;
 use crate::Object;
 use crate::ObjectMode;
 
 pub struct S3ObjectStream {
     backend: Backend,
     path: String,
+    start_after: Option<String>,
+    max_keys: Option<usize>,
+    recursive: bool,
+    ordered: bool,
+    versions: bool,
+    v1: bool,
+    pattern: Option<Glob>,
 
+    /// Continuation token of ListObjectsV2.
     token: String,
+    /// Markers of ListObjects (v1) and ListObjectVersions.
+    key_marker: String,
+    version_id_marker: String,
     done: bool,
     state: State,
 }
 
 enum State {
     Idle,
-    Sending(BoxFuture<'static, Result<bytes::Bytes>>),
-    Listing((Output, usize, usize)),
+    Sending(BoxFuture<'static, Result<hyper::Body>>),
+    Listing(Box<Page>),
+}
+
+/// A page of ListObjectsV2, whose body is parsed while it's arriving.
+struct Page {
+    body: hyper::Body,
+    body_done: bool,
+    parser: OutputParser,
+    /// Parsed entries that have not been yielded yet.
+    entries: VecDeque<Entry>,
+    /// Dirs yielded in this page, some services return a dir both as a
+    /// marker object in contents and in common prefixes.
+    dirs: HashSet<String>,
 }
 
 impl S3ObjectStream {
@@ -55,140 +86,517 @@ impl S3ObjectStream {
         Self {
             backend,
             path,
+            start_after: None,
+            max_keys: None,
+            recursive: false,
+            ordered: false,
+            versions: false,
+            v1: false,
+            pattern: None,
 
             token: "".to_string(),
+            key_marker: "".to_string(),
+            version_id_marker: "".to_string(),
             done: false,
             state: State::Idle,
         }
     }
+
+    /// Only list keys after `start_after`, which must be an absolute path.
+    pub fn start_after(mut self, start_after: Option<String>) -> Self {
+        self.start_after = start_after;
+        self
+    }
+
+    /// Set the max keys of every page.
+    pub fn max_keys(mut self, max_keys: Option<usize>) -> Self {
+        self.max_keys = max_keys;
+        self
+    }
+
+    /// List without delimiter to yield all keys under the path.
+    pub fn recursive(mut self, recursive: bool) -> Self {
+        self.recursive = recursive;
+        self
+    }
+
+    /// Sort entries of every page by their keys.
+    pub fn ordered(mut self, ordered: bool) -> Self {
+        self.ordered = ordered;
+        self
+    }
+
+    /// List versions of objects by ListObjectVersions.
+    pub fn versions(mut self, versions: bool) -> Self {
+        self.versions = versions;
+        self
+    }
+
+    /// List by ListObjects (v1) with `marker` instead of ListObjectsV2, for
+    /// s3 compatible services without `list-type=2`.
+    pub fn v1(mut self, v1: bool) -> Self {
+        self.v1 = v1;
+        self
+    }
+
+    /// Only yield files whose relative paths match the glob `pattern`.
+    pub fn pattern(mut self, pattern: Option<&str>) -> Self {
+        self.pattern = pattern.map(Glob::new);
+        self
+    }
 }
 
-impl futures::Stream for S3ObjectStream {
+impl Stream for S3ObjectStream {
     type Item = Result<Object>;
 
     fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        let backend = self.backend.clone();
-
-        match &mut self.state {
-            State::Idle => {
-                let backend = self.backend.clone();
-                let path = self.path.clone();
-                let token = self.token.clone();
-                let fut = async move {
-                    let mut resp = backend.list_objects(&path, &token).await?;
-
-                    if resp.status() != http::StatusCode::OK {
-                        let e = Err(Error::Object {
-                            kind: Kind::Unexpected,
-                            op: "list",
-                            path: path.clone(),
-                            source: anyhow!("{:?}", resp),
-                        });
-                        debug!("error response: {:?}", resp);
-                        return e;
+        let this = &mut *self;
+
+        loop {
+            match &mut this.state {
+                State::Idle => {
+                    let backend = this.backend.clone();
+                    let path = this.path.clone();
+                    let token = this.token.clone();
+                    // `start-after` only applies to the first page, later pages
+                    // are continued by the token.
+                    let start_after = if token.is_empty() {
+                        this.start_after.clone()
+                    } else {
+                        None
+                    };
+                    // Versions and v1 are listed after the key marker, which
+                    // starts from `start_after` too.
+                    let key_marker = if this.key_marker.is_empty() {
+                        this.start_after.clone().unwrap_or_default()
+                    } else {
+                        this.key_marker.clone()
+                    };
+                    let version_id_marker = this.version_id_marker.clone();
+                    let max_keys = this.max_keys;
+                    let recursive = this.recursive;
+                    let versions = this.versions;
+                    let v1 = this.v1;
+                    let fut = async move {
+                        let resp = if versions {
+                            backend
+                                .list_object_versions(
+                                    &path,
+                                    &key_marker,
+                                    &version_id_marker,
+                                    max_keys,
+                                    recursive,
+                                )
+                                .await?
+                        } else if v1 {
+                            backend
+                                .list_objects_v1(&path, &key_marker, max_keys, recursive)
+                                .await?
+                        } else {
+                            backend
+                                .list_objects(
+                                    &path,
+                                    &token,
+                                    start_after.as_deref(),
+                                    max_keys,
+                                    recursive,
+                                )
+                                .await?
+                        };
+
+                        if resp.status() != http::StatusCode::OK {
+                            let e = Err(Error::Object {
+                                kind: Kind::Unexpected,
+                                op: "list",
+                                path: path.clone(),
+                                context: HashMap::new(),
+                                source: anyhow!("{:?}", resp),
+                            });
+                            debug!("error response: {:?}", resp);
+                            return e;
+                        }
+
+                        Ok(resp.into_body())
+                    };
+                    this.state = State::Sending(Box::pin(fut));
+                }
+                State::Sending(fut) => {
+                    let body = ready!(Pin::new(fut).poll(cx))?;
+                    this.state = State::Listing(Box::new(Page {
+                        body,
+                        body_done: false,
+                        parser: OutputParser::default(),
+                        entries: VecDeque::new(),
+                        dirs: HashSet::new(),
+                    }));
+                }
+                State::Listing(page) => {
+                    // Entries are yielded as soon as they are parsed, except
+                    // for ordered list: s3 returns common prefixes after all
+                    // contents, so we have to sort the whole page.
+                    if page.body_done || !this.ordered {
+                        if let Some(entry) = page.entries.pop_front() {
+                            match to_object(
+                                &this.backend,
+                                &this.path,
+                                this.pattern.as_ref(),
+                                &mut page.dirs,
+                                &entry,
+                            ) {
+                                Some(o) => return Poll::Ready(Some(o)),
+                                None => continue,
+                            }
+                        }
+                    }
+
+                    if !page.body_done {
+                        match ready!(Pin::new(&mut page.body).poll_next(cx)) {
+                            Some(Ok(bs)) => {
+                                page.parser.feed(&bs);
+                                page.parser
+                                    .parse(&mut page.entries)
+                                    .map_err(|e| parse_error(&this.path, e))?;
+                            }
+                            Some(Err(e)) => {
+                                return Poll::Ready(Some(Err(Error::Object {
+                                    kind: Kind::Unexpected,
+                                    op: "list",
+                                    path: this.path.clone(),
+                                    context: HashMap::new(),
+                                    source: anyhow!("read body: {:?}", e),
+                                })));
+                            }
+                            None => {
+                                page.parser
+                                    .finish()
+                                    .map_err(|e| parse_error(&this.path, e))?;
+                                page.body_done = true;
+                                if this.ordered {
+                                    page.entries
+                                        .make_contiguous()
+                                        .sort_by(|a, b| a.key().cmp(b.key()));
+                                }
+                            }
+                        }
+                        continue;
                     }
 
-                    let body = resp.body_mut();
-                    let mut bs = bytes::BytesMut::new();
-                    while let Some(b) = body.next().await {
-                        let b = b.map_err(|e| Error::Object {
-                            kind: Kind::Unexpected,
-                            op: "list",
-                            path: path.clone(),
-                            source: anyhow!("read body: {:?}", e),
-                        })?;
-                        bs.put_slice(&b)
+                    // Try our best to check whether this list is done.
+                    //
+                    // - Check `is_truncated`
+                    // - Check `next_continuation_token`
+                    // - Check whether there is any entry in this page (very rarely case)
+                    let parser = &page.parser;
+                    // `NextMarker` of v1 is returned only with delimiter, the
+                    // last key of this page is the marker otherwise.
+                    let next_marker = parser
+                        .next_marker
+                        .clone()
+                        .or_else(|| parser.last_key.clone());
+                    let next = if this.versions {
+                        parser.next_key_marker.as_ref()
+                    } else if this.v1 {
+                        next_marker.as_ref()
+                    } else {
+                        parser.next_continuation_token.as_ref()
+                    };
+                    this.done = if let Some(is_truncated) = parser.is_truncated {
+                        !is_truncated
+                    } else if let Some(next) = next {
+                        next.is_empty()
+                    } else {
+                        !parser.has_entries
+                    };
+                    if this.done {
+                        debug!("object {} list done", &this.path);
+                        return Poll::Ready(None);
                     }
 
-                    Ok(bs.freeze())
-                };
-                self.state = State::Sending(Box::pin(fut));
-                self.poll_next(cx)
+                    this.token = parser.next_continuation_token.clone().unwrap_or_default();
+                    this.key_marker = if this.v1 {
+                        next_marker.unwrap_or_default()
+                    } else {
+                        parser.next_key_marker.clone().unwrap_or_default()
+                    };
+                    this.version_id_marker =
+                        parser.next_version_id_marker.clone().unwrap_or_default();
+                    this.state = State::Idle;
+                }
             }
-            State::Sending(fut) => {
-                let bs = ready!(Pin::new(fut).poll(cx))?;
-                let output: Output = de::from_reader(bs.reader()).map_err(|e| Error::Object {
-                    kind: Kind::Unexpected,
-                    op: "list",
-                    path: self.path.clone(),
-                    source: anyhow!("deserialize list_bucket output: {:?}", e),
-                })?;
-
-                // Try our best to check whether this list is done.
-                //
-                // - Check `is_truncated`
-                // - Check `next_continuation_token`
-                // - Check the length of `common_prefixes` and `contents` (very rarely case)
-                self.done = if let Some(is_truncated) = output.is_truncated {
-                    !is_truncated
-                } else if let Some(next_continuation_token) =
-                    output.next_continuation_token.as_ref()
-                {
-                    next_continuation_token.is_empty()
-                } else {
-                    output.common_prefixes.is_empty() && output.contents.is_empty()
-                };
-                self.token = output.next_continuation_token.clone().unwrap_or_default();
-                self.state = State::Listing((output, 0, 0));
-                self.poll_next(cx)
+        }
+    }
+}
+
+/// Convert the entry into an object, returns `None` if it should be skipped.
+fn to_object(
+    backend: &Backend,
+    path: &str,
+    pattern: Option<&Glob>,
+    dirs: &mut HashSet<String>,
+    entry: &Entry,
+) -> Option<Result<Object>> {
+    let key = entry.key();
+    // The marker of the listing dir itself is not its child.
+    if key == path {
+        return None;
+    }
+    // Versions of the same dir marker are different entries.
+    let is_version = matches!(entry, Entry::Version(_) | Entry::DeleteMarker(_));
+    if key.ends_with('/') && !is_version && !dirs.insert(key.to_string()) {
+        debug!("object {} skip duplicated dir: {}", path, key);
+        return None;
+    }
+
+    // Match against the path that callers will see.
+    let rel_path = match backend.get_rel_path("list", key) {
+        Ok(v) => v,
+        Err(e) => return Some(Err(e)),
+    };
+    let is_dir = matches!(entry, Entry::CommonPrefix(_)) || key.ends_with('/');
+    if let Some(pattern) = pattern {
+        if !is_dir && !pattern.matches(&rel_path) {
+            return None;
+        }
+    }
+
+    let mut o = Object::new(Arc::new(backend.clone()), &rel_path);
+    let meta = o.metadata_mut();
+    match entry {
+        Entry::CommonPrefix(_) => {
+            meta.set_mode(ObjectMode::DIR)
+                .set_content_length(0)
+                .set_complete();
+        }
+        Entry::Content(object) => set_object_metadata(
+            meta,
+            &object.key,
+            object.size,
+            &object.e_tag,
+            &object.storage_class,
+            &object.last_modified,
+        ),
+        Entry::Version(object) | Entry::DeleteMarker(object) => {
+            set_object_metadata(
+                meta,
+                &object.key,
+                object.size,
+                &object.e_tag,
+                &object.storage_class,
+                &object.last_modified,
+            );
+            meta.set_version_id(&object.version_id)
+                .set_is_latest(object.is_latest);
+            if matches!(entry, Entry::DeleteMarker(_)) {
+                meta.set_delete_marker();
             }
-            State::Listing((output, common_prefixes_idx, objects_idx)) => {
-                let prefixes = &output.common_prefixes;
-                if *common_prefixes_idx < prefixes.len() {
-                    *common_prefixes_idx += 1;
-                    let prefix = &prefixes[*common_prefixes_idx - 1].prefix;
-
-                    let mut o =
-                        Object::new(Arc::new(backend.clone()), &backend.get_rel_path(prefix));
-                    let meta = o.metadata_mut();
-                    meta.set_mode(ObjectMode::DIR)
-                        .set_content_length(0)
-                        .set_complete();
-
-                    debug!(
-                        "object {} got entry, path: {}, mode: {}",
-                        &self.path,
-                        meta.path(),
-                        meta.mode()
-                    );
-                    return Poll::Ready(Some(Ok(o)));
-                }
+        }
+    }
 
-                let objects = &output.contents;
-                if *objects_idx < objects.len() {
-                    *objects_idx += 1;
-                    let object = &objects[*objects_idx - 1];
-
-                    let mut o = Object::new(
-                        Arc::new(backend.clone()),
-                        &backend.get_rel_path(&object.key),
-                    );
-                    let meta = o.metadata_mut();
-                    meta.set_mode(ObjectMode::FILE)
-                        .set_content_length(object.size as u64);
-
-                    debug!(
-                        "object {} got entry, path: {}, mode: {}",
-                        &self.path,
-                        meta.path(),
-                        meta.mode()
-                    );
-                    return Poll::Ready(Some(Ok(o)));
-                }
+    debug!(
+        "object {} got entry, path: {}, mode: {}",
+        path,
+        meta.path(),
+        meta.mode()
+    );
+    Some(Ok(o))
+}
+
+/// Set the fields of objects and versions returned by list.
+fn set_object_metadata(
+    meta: &mut Metadata,
+    key: &str,
+    size: u64,
+    e_tag: &str,
+    storage_class: &str,
+    last_modified: &str,
+) {
+    // Dir markers could be returned while listing recursively.
+    if key.ends_with('/') {
+        meta.set_mode(ObjectMode::DIR).set_content_length(0);
+    } else {
+        meta.set_mode(ObjectMode::FILE).set_content_length(size);
+    }
+    // Quotes are stripped by `set_etag` as `stat` does.
+    if !e_tag.is_empty() {
+        meta.set_etag(e_tag);
+    }
+    if !storage_class.is_empty() && meta.mode() == ObjectMode::FILE {
+        meta.set_storage_class(storage_class);
+    }
+    if !last_modified.is_empty() {
+        match parse_datetime(last_modified) {
+            Ok(t) => {
+                meta.set_last_modified(t.into());
+            }
+            Err(e) => warn!(
+                "object {} got invalid last modified {}: {:?}",
+                key, last_modified, e
+            ),
+        }
+    }
+}
+
+fn parse_error(path: &str, e: anyhow::Error) -> Error {
+    Error::Object {
+        kind: Kind::Unexpected,
+        op: "list",
+        path: path.to_string(),
+        context: HashMap::new(),
+        source: anyhow!("deserialize list_bucket output: {:?}", e),
+    }
+}
+
+/// Entry parsed from the children of `ListBucketResult`.
+enum Entry {
+    Content(OutputContent),
+    CommonPrefix(OutputCommonPrefix),
+    Version(OutputVersion),
+    DeleteMarker(OutputVersion),
+}
+
+impl Entry {
+    fn key(&self) -> &str {
+        match self {
+            Entry::Content(v) => &v.key,
+            Entry::CommonPrefix(v) => &v.prefix,
+            Entry::Version(v) | Entry::DeleteMarker(v) => &v.key,
+        }
+    }
+}
 
-                if self.done {
-                    debug!("object {} list done", &self.path);
-                    return Poll::Ready(None);
+/// Children of `ListBucketResult` and `ListVersionsResult` that we care about.
+const OUTPUT_TAGS: [&str; 9] = [
+    "Contents",
+    "CommonPrefixes",
+    "Version",
+    "DeleteMarker",
+    "IsTruncated",
+    "NextContinuationToken",
+    "NextMarker",
+    "NextKeyMarker",
+    "NextVersionIdMarker",
+];
+const MAX_START_TAG_LEN: usize = "<NextContinuationToken>".len();
+
+/// OutputParser parses the body of ListObjectsV2 (and v1) incrementally.
+///
+/// Children of `ListBucketResult` are deserialized one by one as soon as
+/// they have been received completely, so that only the incomplete tail of
+/// the body will be buffered no matter how large the page is.
+#[derive(Default)]
+struct OutputParser {
+    buf: BytesMut,
+
+    is_truncated: Option<bool>,
+    next_continuation_token: Option<String>,
+    next_marker: Option<String>,
+    next_key_marker: Option<String>,
+    next_version_id_marker: Option<String>,
+    has_entries: bool,
+    /// The last key of contents and common prefixes, used as the marker of
+    /// the next v1 page if `NextMarker` is absent.
+    last_key: Option<String>,
+}
+
+impl OutputParser {
+    fn feed(&mut self, bs: &[u8]) {
+        self.buf.extend_from_slice(bs)
+    }
+
+    /// Parse all complete children in the buffer, and push parsed entries
+    /// into `entries`.
+    fn parse(&mut self, entries: &mut VecDeque<Entry>) -> anyhow::Result<()> {
+        while let Some((start, tag)) = self.find_start() {
+            let end_tag = format!("</{}>", tag);
+            let end = match find(&self.buf[start..], end_tag.as_bytes()) {
+                Some(idx) => start + idx + end_tag.len(),
+                None => {
+                    // Wait for the rest of this child.
+                    self.buf.advance(start);
+                    return Ok(());
                 }
+            };
+
+            // Wrap the child so that it could be deserialized as `Output`.
+            let mut xml = b"<ListBucketResult>".to_vec();
+            xml.extend_from_slice(&self.buf[start..end]);
+            xml.extend_from_slice(b"</ListBucketResult>");
+            self.buf.advance(end);
 
-                self.state = State::Idle;
-                self.poll_next(cx)
+            let output = Output::decode(de::from_reader(xml.as_slice())?)?;
+            if output.is_truncated.is_some() {
+                self.is_truncated = output.is_truncated;
+            }
+            if output.next_continuation_token.is_some() {
+                self.next_continuation_token = output.next_continuation_token;
+            }
+            if output.next_marker.is_some() {
+                self.next_marker = output.next_marker;
+            }
+            if output.next_key_marker.is_some() {
+                self.next_key_marker = output.next_key_marker;
+            }
+            if output.next_version_id_marker.is_some() {
+                self.next_version_id_marker = output.next_version_id_marker;
+            }
+            for v in output.contents {
+                self.has_entries = true;
+                self.set_last_key(&v.key);
+                entries.push_back(Entry::Content(v));
+            }
+            for v in output.common_prefixes {
+                self.has_entries = true;
+                self.set_last_key(&v.prefix);
+                entries.push_back(Entry::CommonPrefix(v));
+            }
+            for v in output.version {
+                self.has_entries = true;
+                entries.push_back(Entry::Version(v));
+            }
+            for v in output.delete_marker {
+                self.has_entries = true;
+                entries.push_back(Entry::DeleteMarker(v));
             }
         }
+
+        // Keep the tail which could be the beginning of a start tag.
+        let keep = self.buf.len().min(MAX_START_TAG_LEN - 1);
+        self.buf.advance(self.buf.len() - keep);
+        Ok(())
     }
+
+    /// Contents and common prefixes are returned in separate orders, keep
+    /// the largest one.
+    fn set_last_key(&mut self, key: &str) {
+        if self.last_key.as_deref().is_none_or(|v| v < key) {
+            self.last_key = Some(key.to_string());
+        }
+    }
+
+    /// Check that the body doesn't end in the middle of a child.
+    fn finish(&self) -> anyhow::Result<()> {
+        match self.find_start() {
+            Some((_, tag)) => Err(anyhow!("body ends in the middle of {}", tag)),
+            None => Ok(()),
+        }
+    }
+
+    /// Find the first start tag of the children we care about.
+    fn find_start(&self) -> Option<(usize, &'static str)> {
+        OUTPUT_TAGS
+            .iter()
+            .filter_map(|tag| {
+                find(&self.buf, format!("<{}>", tag).as_bytes()).map(|idx| (idx, *tag))
+            })
+            .min_by_key(|(idx, _)| *idx)
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
 }
 
-/// Output of ListBucket/ListObjects.
+/// Output of ListObjects, ListObjectsV2 and ListObjectVersions.
 ///
 /// ## Note
 ///
@@ -202,8 +610,50 @@ impl futures::Stream for S3ObjectStream {
 struct Output {
     is_truncated: Option<bool>,
     next_continuation_token: Option<String>,
+    next_marker: Option<String>,
+    next_key_marker: Option<String>,
+    next_version_id_marker: Option<String>,
     common_prefixes: Vec<OutputCommonPrefix>,
     contents: Vec<OutputContent>,
+    version: Vec<OutputVersion>,
+    delete_marker: Vec<OutputVersion>,
+}
+
+impl Output {
+    /// Decode keys and markers, which are percent-encoded as we list with
+    /// `encoding-type=url`.
+    ///
+    /// `NextContinuationToken` is decoded too for services that encode it,
+    /// tokens of s3 never contain `%`.
+    fn decode(mut self) -> anyhow::Result<Self> {
+        for v in [
+            &mut self.next_continuation_token,
+            &mut self.next_marker,
+            &mut self.next_key_marker,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            *v = decode_value(v)?;
+        }
+        for v in self.contents.iter_mut() {
+            v.key = decode_value(&v.key)?;
+        }
+        for v in self.common_prefixes.iter_mut() {
+            v.prefix = decode_value(&v.prefix)?;
+        }
+        for v in self.version.iter_mut().chain(self.delete_marker.iter_mut()) {
+            v.key = decode_value(&v.key)?;
+        }
+        Ok(self)
+    }
+}
+
+fn decode_value(v: &str) -> anyhow::Result<String> {
+    Ok(percent_decode_str(v)
+        .decode_utf8()
+        .map_err(|e| anyhow!("decode {:?}: {:?}", v, e))?
+        .to_string())
 }
 
 #[derive(Default, Debug, Eq, PartialEq, Deserialize)]
@@ -211,6 +661,12 @@ struct Output {
 struct OutputContent {
     key: String,
     size: u64,
+    #[serde(default)]
+    last_modified: String,
+    #[serde(default, rename = "ETag")]
+    e_tag: String,
+    #[serde(default)]
+    storage_class: String,
 }
 
 #[derive(Default, Debug, Eq, PartialEq, Deserialize)]
@@ -219,6 +675,21 @@ struct OutputCommonPrefix {
     prefix: String,
 }
 
+/// Version or delete marker of ListObjectVersions, delete markers have
+/// no size, ETag or storage class.
+#[derive(Default, Debug, Eq, PartialEq, Deserialize)]
+#[serde(default, rename_all = "PascalCase")]
+struct OutputVersion {
+    key: String,
+    version_id: String,
+    is_latest: bool,
+    size: u64,
+    last_modified: String,
+    #[serde(rename = "ETag")]
+    e_tag: String,
+    storage_class: String,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -274,13 +745,145 @@ mod tests {
             vec![
                 OutputContent {
                     key: "photos/2006".to_string(),
-                    size: 56
+                    size: 56,
+                    last_modified: "2016-04-30T23:51:29.000Z".to_string(),
+                    e_tag: "\"d41d8cd98f00b204e9800998ecf8427e\"".to_string(),
+                    storage_class: "STANDARD".to_string(),
                 },
                 OutputContent {
                     key: "photos/2007".to_string(),
-                    size: 100
+                    size: 100,
+                    last_modified: "2016-04-30T23:51:29.000Z".to_string(),
+                    e_tag: "\"d41d8cd98f00b204e9800998ecf8427e\"".to_string(),
+                    storage_class: "STANDARD".to_string(),
                 }
             ]
         )
     }
+
+    #[test]
+    fn test_parse_list_output_multipart_etag() {
+        let bs = bytes::Bytes::from(
+            r#"<ListBucketResult xmlns="http://s3.amazonaws.com/doc/2006-03-01/">
+  <Name>example-bucket</Name>
+  <Prefix>photos/</Prefix>
+  <KeyCount>1</KeyCount>
+  <MaxKeys>1000</MaxKeys>
+  <IsTruncated>false</IsTruncated>
+  <Contents>
+    <Key>photos/2008</Key>
+    <LastModified>2016-04-30T23:51:29.000Z</LastModified>
+    <ETag>"3858f62230ac3c915f300c664312c11f-9"</ETag>
+    <Size>47185920</Size>
+    <StorageClass>STANDARD</StorageClass>
+  </Contents>
+</ListBucketResult>"#,
+        );
+
+        let out: Output = de::from_reader(bs.reader()).expect("must success");
+
+        // ETags of multipart uploaded objects are suffixed by the number
+        // of parts.
+        assert_eq!(out.contents.len(), 1);
+        assert_eq!(out.contents[0].key, "photos/2008");
+        assert_eq!(
+            out.contents[0].e_tag,
+            "\"3858f62230ac3c915f300c664312c11f-9\""
+        );
+    }
+
+    #[test]
+    fn test_parse_list_output_incrementally() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<ListBucketResult xmlns="http://s3.amazonaws.com/doc/2006-03-01/">
+  <Name>example-bucket</Name>
+  <Prefix>photos/</Prefix>
+  <IsTruncated>true</IsTruncated>
+  <NextContinuationToken>a+b=</NextContinuationToken>
+  <Contents>
+    <Key>photos/a&amp;b</Key>
+    <ETag>"d41d8cd98f00b204e9800998ecf8427e"</ETag>
+    <Size>56</Size>
+  </Contents>
+  <CommonPrefixes>
+    <Prefix>photos/2006/</Prefix>
+  </CommonPrefixes>
+</ListBucketResult>"#;
+
+        // Feed the body byte by byte to make sure children split across
+        // chunks are handled.
+        let mut parser = OutputParser::default();
+        let mut entries = VecDeque::new();
+        for b in xml.as_bytes() {
+            parser.feed(&[*b]);
+            parser.parse(&mut entries).expect("must success");
+            assert!(parser.buf.len() <= xml.len() / 2, "buffer must be bounded");
+        }
+        parser.finish().expect("must success");
+
+        assert_eq!(parser.is_truncated, Some(true));
+        assert_eq!(parser.next_continuation_token.as_deref(), Some("a+b="));
+        assert!(parser.has_entries);
+        assert_eq!(
+            entries.iter().map(|v| v.key()).collect::<Vec<_>>(),
+            vec!["photos/a&b", "photos/2006/"]
+        );
+        match &entries[0] {
+            Entry::Content(v) => assert_eq!(v.size, 56),
+            _ => panic!("must be content"),
+        }
+
+        // Body ends in the middle of a child.
+        let mut parser = OutputParser::default();
+        parser.feed(&xml.as_bytes()[..xml.find("</Contents>").unwrap()]);
+        parser.parse(&mut VecDeque::new()).expect("must success");
+        assert!(parser.finish().is_err());
+    }
+
+    #[test]
+    fn test_parse_list_output_v1() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<ListBucketResult xmlns="http://s3.amazonaws.com/doc/2006-03-01/">
+  <Name>example-bucket</Name>
+  <Prefix>photos/</Prefix>
+  <Marker>photos/0</Marker>
+  <NextMarker>photos/2006/</NextMarker>
+  <MaxKeys>2</MaxKeys>
+  <Delimiter>/</Delimiter>
+  <IsTruncated>true</IsTruncated>
+  <Contents>
+    <Key>photos/a</Key>
+    <Size>56</Size>
+  </Contents>
+  <CommonPrefixes>
+    <Prefix>photos/2006/</Prefix>
+  </CommonPrefixes>
+</ListBucketResult>"#;
+
+        let mut parser = OutputParser::default();
+        let mut entries = VecDeque::new();
+        parser.feed(xml.as_bytes());
+        parser.parse(&mut entries).expect("must success");
+        parser.finish().expect("must success");
+
+        assert_eq!(parser.is_truncated, Some(true));
+        assert_eq!(parser.next_marker.as_deref(), Some("photos/2006/"));
+        assert_eq!(parser.next_continuation_token, None);
+        assert_eq!(
+            entries.iter().map(|v| v.key()).collect::<Vec<_>>(),
+            vec!["photos/a", "photos/2006/"]
+        );
+
+        // Without delimiter, `NextMarker` is absent and the last key is used.
+        let xml = r#"<ListBucketResult>
+  <IsTruncated>true</IsTruncated>
+  <Contents><Key>photos/b</Key><Size>1</Size></Contents>
+  <Contents><Key>photos/c/d</Key><Size>1</Size></Contents>
+</ListBucketResult>"#;
+        let mut parser = OutputParser::default();
+        parser.feed(xml.as_bytes());
+        parser.parse(&mut VecDeque::new()).expect("must success");
+        assert_eq!(parser.next_marker, None);
+        assert_eq!(parser.last_key.as_deref(), Some("photos/c/d"));
+    }
 }
@@ -0,0 +1,367 @@
+// Copyright 2022 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+
+use anyhow::anyhow;
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::error::Error;
+use crate::error::Kind;
+use crate::error::Result;
+
+/// The only manifest version we understand for now.
+const MANIFEST_VERSION: u32 = 1;
+
+/// Manifests of well-known public datasets that shipped with OpenDAL.
+///
+/// It shares the same format with URL-loaded manifests.
+const BUILTIN_MANIFESTS: &str = r#"[
+  {
+    "version": 1,
+    "name": "sentinel-2",
+    "description": "Sentinel-2 Level-2A Cloud-Optimized GeoTIFFs",
+    "bucket": "sentinel-cogs",
+    "region": "us-west-2",
+    "anonymous": true
+  },
+  {
+    "version": 1,
+    "name": "sentinel-2-l1c",
+    "description": "Sentinel-2 Level-1C tiles, requester pays",
+    "bucket": "sentinel-s2-l1c",
+    "region": "eu-central-1",
+    "requester_pays": true,
+    "anonymous": false
+  },
+  {
+    "version": 1,
+    "name": "common-crawl",
+    "description": "Common Crawl web archives",
+    "bucket": "commoncrawl",
+    "region": "us-east-1",
+    "anonymous": true
+  },
+  {
+    "version": 1,
+    "name": "noaa-goes16",
+    "description": "NOAA GOES-16 satellite imagery",
+    "bucket": "noaa-goes16",
+    "region": "us-east-1",
+    "anonymous": true
+  }
+]"#;
+
+/// PublicDataset is the manifest that describes how to access a public dataset.
+///
+/// # Format
+///
+/// Manifests are JSON documents like:
+///
+/// ```json
+/// {
+///   "version": 1,
+///   "name": "sentinel-2",
+///   "description": "Sentinel-2 Level-2A Cloud-Optimized GeoTIFFs",
+///   "bucket": "sentinel-cogs",
+///   "region": "us-west-2",
+///   "endpoint": "https://s3.us-west-2.amazonaws.com",
+///   "root": "/",
+///   "requester_pays": false,
+///   "anonymous": true
+/// }
+/// ```
+///
+/// - `version`: manifest format version, only `1` is supported.
+/// - `bucket` and `region` are required.
+/// - `endpoint`, `root` and `description` are optional.
+/// - `requester_pays` defaults to `false`, `anonymous` defaults to `true`.
+///
+/// Unknown fields will be ignored, so that manifests can carry extra information.
+///
+/// Manifests loaded from urls whose path ends with `.yaml` or `.yml` are
+/// parsed as YAML documents with the same fields:
+///
+/// ```yaml
+/// version: 1
+/// name: sentinel-2
+/// bucket: sentinel-cogs
+/// region: us-west-2
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PublicDataset {
+    pub version: u32,
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    pub bucket: String,
+    pub region: String,
+    #[serde(default)]
+    pub endpoint: Option<String>,
+    #[serde(default)]
+    pub root: Option<String>,
+    /// Send `x-amz-request-payer: requester` with every request.
+    #[serde(default)]
+    pub requester_pays: bool,
+    /// Send requests without signing.
+    #[serde(default = "default_anonymous")]
+    pub anonymous: bool,
+}
+
+fn default_anonymous() -> bool {
+    true
+}
+
+/// Whether the manifest at `url` is a YAML document, judged by the
+/// extension of its path.
+fn is_yaml(url: &str) -> bool {
+    let path = url.split(['?', '#']).next().unwrap_or_default();
+    path.ends_with(".yaml") || path.ends_with(".yml")
+}
+
+impl PublicDataset {
+    /// Load manifest from a known dataset name or an `http(s)://` url.
+    pub async fn load(dataset: &str) -> Result<Self> {
+        if dataset.starts_with("http://") || dataset.starts_with("https://") {
+            Self::fetch(dataset).await
+        } else {
+            Self::builtin(dataset)
+        }
+    }
+
+    /// Load a built-in manifest by name.
+    pub fn builtin(name: &str) -> Result<Self> {
+        Self::builtins()
+            .into_iter()
+            .find(|v| v.name == name)
+            .ok_or_else(|| Error::Backend {
+                kind: Kind::BackendConfigurationInvalid,
+                context: HashMap::from([("dataset".to_string(), name.to_string())]),
+                source: anyhow!("public dataset {} is not a known dataset", name),
+            })
+    }
+
+    /// List all built-in manifests.
+    pub fn builtins() -> Vec<Self> {
+        serde_json::from_str(BUILTIN_MANIFESTS).expect("built-in manifests must be valid")
+    }
+
+    /// Parse and validate a JSON manifest from bytes.
+    pub fn from_slice(bs: &[u8]) -> Result<Self> {
+        let manifest: PublicDataset = serde_json::from_slice(bs).map_err(|e| Error::Backend {
+            kind: Kind::BackendConfigurationInvalid,
+            context: HashMap::new(),
+            source: anyhow!("parse public dataset manifest: {:?}", e),
+        })?;
+        manifest.validate()?;
+
+        Ok(manifest)
+    }
+
+    /// Parse and validate a YAML manifest from bytes.
+    pub fn from_yaml_slice(bs: &[u8]) -> Result<Self> {
+        let manifest: PublicDataset = serde_yaml::from_slice(bs).map_err(|e| Error::Backend {
+            kind: Kind::BackendConfigurationInvalid,
+            context: HashMap::new(),
+            source: anyhow!("parse public dataset manifest: {:?}", e),
+        })?;
+        manifest.validate()?;
+
+        Ok(manifest)
+    }
+
+    async fn fetch(url: &str) -> Result<Self> {
+        let context = HashMap::from([("manifest".to_string(), url.to_string())]);
+
+        let client = hyper::Client::builder().build(hyper_tls::HttpsConnector::new());
+        let req = hyper::Request::get(url)
+            .body(hyper::Body::empty())
+            .map_err(|e| Error::Backend {
+                kind: Kind::BackendConfigurationInvalid,
+                context: context.clone(),
+                source: anyhow::Error::from(e),
+            })?;
+        let resp = client.request(req).await.map_err(|e| Error::Backend {
+            kind: Kind::BackendConfigurationInvalid,
+            context: context.clone(),
+            source: anyhow::Error::from(e),
+        })?;
+        if resp.status() != http::StatusCode::OK {
+            return Err(Error::Backend {
+                kind: Kind::BackendConfigurationInvalid,
+                context,
+                source: anyhow!("fetch manifest got unexpected response: {:?}", resp),
+            });
+        }
+        let bs = hyper::body::to_bytes(resp.into_body())
+            .await
+            .map_err(|e| Error::Backend {
+                kind: Kind::BackendConfigurationInvalid,
+                context: context.clone(),
+                source: anyhow::Error::from(e),
+            })?;
+
+        let manifest = if is_yaml(url) {
+            Self::from_yaml_slice(&bs)
+        } else {
+            Self::from_slice(&bs)
+        };
+        manifest.map_err(|e| match e {
+            Error::Backend { kind, source, .. } => Error::Backend {
+                kind,
+                context,
+                source,
+            },
+            e => e,
+        })
+    }
+
+    fn validate(&self) -> Result<()> {
+        let invalid = |msg: String| Error::Backend {
+            kind: Kind::BackendConfigurationInvalid,
+            context: HashMap::from([("dataset".to_string(), self.name.clone())]),
+            source: anyhow!(msg),
+        };
+
+        if self.version != MANIFEST_VERSION {
+            return Err(invalid(format!(
+                "manifest version {} is not supported",
+                self.version
+            )));
+        }
+        if self.bucket.is_empty() {
+            return Err(invalid("bucket is empty".to_string()));
+        }
+        if self.region.is_empty() {
+            return Err(invalid("region is empty".to_string()));
+        }
+        // Requester pays requires signed requests to know who the requester is.
+        if self.requester_pays && self.anonymous {
+            return Err(invalid(
+                "requester pays dataset can't be accessed anonymously".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::Infallible;
+    use std::net::TcpListener;
+
+    use hyper::service::make_service_fn;
+    use hyper::service::service_fn;
+
+    use super::*;
+
+    #[test]
+    fn test_builtin_manifests() {
+        let manifests = PublicDataset::builtins();
+        assert!(!manifests.is_empty());
+
+        for m in manifests {
+            m.validate().expect("built-in manifest must be valid");
+            assert_eq!(PublicDataset::builtin(&m.name).unwrap(), m);
+        }
+
+        let err = PublicDataset::builtin("not-exist").unwrap_err();
+        assert_eq!(err.kind(), Kind::BackendConfigurationInvalid);
+    }
+
+    #[test]
+    fn test_invalid_manifest() {
+        for bs in [
+            r#"{"version": 2, "name": "x", "bucket": "b", "region": "r"}"#,
+            r#"{"version": 1, "name": "x", "bucket": "", "region": "r"}"#,
+            r#"{"version": 1, "name": "x", "bucket": "b", "region": "r", "requester_pays": true}"#,
+            r#"{"version": 1, "name": "x"}"#,
+            r#"not json"#,
+        ] {
+            let err = PublicDataset::from_slice(bs.as_bytes()).unwrap_err();
+            assert_eq!(err.kind(), Kind::BackendConfigurationInvalid, "{}", bs);
+        }
+    }
+
+    #[test]
+    fn test_yaml_manifest() {
+        let manifest = PublicDataset::from_yaml_slice(
+            b"version: 1\nname: x\nbucket: b\nregion: r\nrequester_pays: true\nanonymous: false\n",
+        )
+        .unwrap();
+        assert_eq!(
+            manifest,
+            PublicDataset::from_slice(
+                br#"{"version": 1, "name": "x", "bucket": "b", "region": "r", "requester_pays": true, "anonymous": false}"#
+            )
+            .unwrap()
+        );
+
+        for bs in [
+            "version: 2\nname: x\nbucket: b\nregion: r\n",
+            "[not, a, manifest]",
+        ] {
+            let err = PublicDataset::from_yaml_slice(bs.as_bytes()).unwrap_err();
+            assert_eq!(err.kind(), Kind::BackendConfigurationInvalid, "{}", bs);
+        }
+
+        assert!(is_yaml("https://example.com/manifest.yaml"));
+        assert!(is_yaml("https://example.com/manifest.yml?versionId=1"));
+        assert!(!is_yaml("https://example.com/manifest.json"));
+    }
+
+    #[tokio::test]
+    async fn test_fetch_yaml_manifest() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = hyper::Server::from_tcp(listener)
+            .unwrap()
+            .serve(make_service_fn(|_| async {
+                Ok::<_, Infallible>(service_fn(|_| async {
+                    Ok::<_, Infallible>(hyper::Response::new(hyper::Body::from(
+                        "version: 1\nname: yaml\nbucket: b\nregion: r\n",
+                    )))
+                }))
+            }));
+        tokio::spawn(server);
+
+        let url = format!("http://{}/manifest.yaml", addr);
+        let manifest = PublicDataset::load(&url).await.unwrap();
+        assert_eq!(manifest.name, "yaml");
+        assert!(manifest.anonymous);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_malformed_manifest() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = hyper::Server::from_tcp(listener)
+            .unwrap()
+            .serve(make_service_fn(|_| async {
+                Ok::<_, Infallible>(service_fn(|_| async {
+                    Ok::<_, Infallible>(hyper::Response::new(hyper::Body::from(
+                        r#"{"version": 1, "name": "broken""#,
+                    )))
+                }))
+            }));
+        tokio::spawn(server);
+
+        let url = format!("http://{}/manifest.json", addr);
+        let err = PublicDataset::load(&url).await.unwrap_err();
+        assert_eq!(err.kind(), Kind::BackendConfigurationInvalid);
+        assert!(err.to_string().contains(&url), "{}", err);
+    }
+}
@@ -0,0 +1,542 @@
+// Copyright 2022 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+use anyhow::anyhow;
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures::future;
+use futures::stream;
+#[cfg(feature = "minitrace")]
+use minitrace::trace;
+use percent_encoding::percent_decode_str;
+use time::OffsetDateTime;
+
+use crate::error::Error;
+use crate::error::Kind;
+use crate::error::Result;
+use crate::io::BytesStream;
+use crate::object::filter_by_pattern;
+use crate::object::unquote_etag;
+use crate::object::BoxedObjectStream;
+use crate::object::LimitedObjectStream;
+use crate::ops::OpDelete;
+use crate::ops::OpList;
+use crate::ops::OpRead;
+use crate::ops::OpStat;
+use crate::ops::OpWrite;
+use crate::path::validate_path;
+use crate::Accessor;
+use crate::AccessorMetadata;
+use crate::BoxedAsyncReader;
+use crate::Metadata;
+use crate::Object;
+use crate::ObjectMode;
+use crate::Scheme;
+
+#[derive(Default, Debug, Clone)]
+pub struct Builder {
+    entries: Vec<(String, Entry)>,
+    last_modified: Option<SystemTime>,
+    list_scan_limit: Option<u64>,
+}
+
+impl Builder {
+    /// Build from a map of path to value.
+    ///
+    /// Values starting with `data:` are decoded as data URLs, others are
+    /// served as literal text.
+    pub fn from_map(map: HashMap<String, String>) -> Result<Builder> {
+        let mut builder = Builder::default();
+        for (path, value) in map {
+            builder.entry_value(&path, &value)?;
+        }
+
+        Ok(builder)
+    }
+
+    /// Build from a JSON document like `{"a.json": "...", "dir": {"b.txt": "..."}}`.
+    ///
+    /// Nested objects are dirs, and string values are handled like
+    /// [`Builder::from_map`].
+    pub fn from_json(doc: &str) -> Result<Builder> {
+        let value: serde_json::Value = serde_json::from_str(doc).map_err(|e| Error::Backend {
+            kind: Kind::BackendConfigurationInvalid,
+            context: HashMap::new(),
+            source: anyhow!("parse json document: {}", e),
+        })?;
+
+        let mut builder = Builder::default();
+        builder.entry_json("", &value)?;
+
+        Ok(builder)
+    }
+
+    /// Add an object with the content.
+    ///
+    /// Objects added later will replace the earlier ones of the same path.
+    pub fn entry(&mut self, path: &str, content: impl Into<Bytes>) -> &mut Self {
+        self.entries.push((
+            path.to_string(),
+            Entry {
+                content: content.into(),
+                content_type: None,
+            },
+        ));
+
+        self
+    }
+
+    /// Add an object with the content and content type decoded from a data
+    /// URL like `data:application/json;base64,e30=`.
+    pub fn entry_uri(&mut self, path: &str, uri: &str) -> Result<&mut Self> {
+        let entry = parse_data_url(uri).map_err(|e| Error::Backend {
+            kind: Kind::BackendConfigurationInvalid,
+            context: HashMap::from([("path".to_string(), path.to_string())]),
+            source: e,
+        })?;
+        self.entries.push((path.to_string(), entry));
+
+        Ok(self)
+    }
+
+    /// Set the last modified time of all objects.
+    ///
+    /// Default to `UNIX_EPOCH` so that the metadata is deterministic.
+    pub fn last_modified(&mut self, last_modified: SystemTime) -> &mut Self {
+        self.last_modified = Some(last_modified);
+
+        self
+    }
+
+    /// Set the max entries that a `list` without `OpList::max_results` could yield.
+    ///
+    /// Listing will end with a `Kind::ScanLimitExceeded` error once exceeded.
+    pub fn list_scan_limit(&mut self, limit: u64) -> &mut Self {
+        self.list_scan_limit = Some(limit);
+
+        self
+    }
+
+    pub async fn finish(&mut self) -> Result<Arc<dyn Accessor>> {
+        let mut entries = BTreeMap::new();
+        for (path, entry) in &self.entries {
+            let p = normalize_path(path);
+            if p.is_empty() || p.ends_with('/') {
+                return Err(Error::Backend {
+                    kind: Kind::BackendConfigurationInvalid,
+                    context: HashMap::from([("path".to_string(), path.to_string())]),
+                    source: anyhow!("path of object must not be a dir"),
+                });
+            }
+            entries.insert(p, entry.clone());
+        }
+
+        Ok(Arc::new(Backend {
+            entries: Arc::new(entries),
+            last_modified: self.last_modified.unwrap_or(UNIX_EPOCH),
+            list_scan_limit: self.list_scan_limit,
+        }))
+    }
+
+    fn entry_value(&mut self, path: &str, value: &str) -> Result<&mut Self> {
+        if value.starts_with("data:") {
+            self.entry_uri(path, value)
+        } else {
+            Ok(self.entry(path, value.to_string()))
+        }
+    }
+
+    fn entry_json(&mut self, path: &str, value: &serde_json::Value) -> Result<()> {
+        match value {
+            serde_json::Value::String(v) => {
+                self.entry_value(path, v)?;
+            }
+            serde_json::Value::Object(children) => {
+                for (name, child) in children {
+                    self.entry_json(&format!("{}/{}", path, name), child)?;
+                }
+            }
+            v => {
+                return Err(Error::Backend {
+                    kind: Kind::BackendConfigurationInvalid,
+                    context: HashMap::from([("path".to_string(), path.to_string())]),
+                    source: anyhow!("value must be a string or an object, got {}", v),
+                })
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Entry {
+    content: Bytes,
+    content_type: Option<String>,
+}
+
+/// Parse a data URL like `data:[<mediatype>][;base64],<data>`.
+///
+/// Content type is left unset if the media type is omitted.
+fn parse_data_url(uri: &str) -> anyhow::Result<Entry> {
+    let rest = uri
+        .strip_prefix("data:")
+        .ok_or_else(|| anyhow!("data url must start with data:"))?;
+    let (params, data) = rest
+        .split_once(',')
+        .ok_or_else(|| anyhow!("data url must contain ,"))?;
+
+    let (media_type, is_base64) = match params.rsplit_once(';') {
+        Some((media_type, v)) if v.eq_ignore_ascii_case("base64") => (media_type, true),
+        _ if params.eq_ignore_ascii_case("base64") => ("", true),
+        _ => (params, false),
+    };
+
+    let data = percent_decode_str(data).collect::<Vec<u8>>();
+    let content = if is_base64 {
+        base64::decode(&data).map_err(|e| anyhow!("decode base64 data: {}", e))?
+    } else {
+        data
+    };
+
+    Ok(Entry {
+        content: Bytes::from(content),
+        content_type: (!media_type.is_empty()).then(|| media_type.to_string()),
+    })
+}
+
+// normalize_path removes all internal `//` inside path.
+fn normalize_path(path: &str) -> String {
+    let has_trailing = path.ends_with('/');
+
+    let mut p = path
+        .split('/')
+        .filter(|v| !v.is_empty())
+        .collect::<Vec<&str>>()
+        .join("/");
+
+    if has_trailing && !p.is_empty() {
+        p.push('/')
+    }
+
+    p
+}
+
+/// ETag of the content, which is the MD5 of the content like s3.
+fn etag(bs: &[u8]) -> String {
+    format!("{:x}", md5::compute(bs))
+}
+
+#[derive(Debug, Clone)]
+pub struct Backend {
+    entries: Arc<BTreeMap<String, Entry>>,
+    last_modified: SystemTime,
+    list_scan_limit: Option<u64>,
+}
+
+impl Backend {
+    pub fn build() -> Builder {
+        Builder::default()
+    }
+
+    fn get(&self, op: &'static str, path: &str) -> Result<&Entry> {
+        self.entries.get(path).ok_or_else(|| Error::Object {
+            kind: Kind::ObjectNotExist,
+            op,
+            path: path.to_string(),
+            context: HashMap::new(),
+            source: anyhow!("key not exists in static files"),
+        })
+    }
+
+    /// Whether the dir exists, which is the root or the parent of any object.
+    fn is_dir(&self, path: &str) -> bool {
+        path.is_empty()
+            || self
+                .entries
+                .range(path.to_string()..)
+                .next()
+                .map(|(k, _)| k.starts_with(path))
+                .unwrap_or_default()
+    }
+
+    fn file_metadata(&self, path: &str, entry: &Entry) -> Metadata {
+        let mut meta = Metadata::default();
+        meta.set_path(path)
+            .set_mode(ObjectMode::FILE)
+            .set_content_length(entry.content.len() as u64)
+            .set_etag(&etag(&entry.content))
+            .set_last_modified(self.last_modified);
+        if let Some(v) = &entry.content_type {
+            meta.set_content_type(v);
+        }
+        meta.set_complete();
+
+        meta
+    }
+
+    fn dir_metadata(path: &str) -> Metadata {
+        let mut meta = Metadata::default();
+        meta.set_path(path)
+            .set_mode(ObjectMode::DIR)
+            .set_content_length(0)
+            .set_complete();
+
+        meta
+    }
+
+    /// Check the ETag preconditions of a read or stat against the entry.
+    fn check_etag(
+        op: &'static str,
+        path: &str,
+        entry: &Entry,
+        if_match: &Option<String>,
+        if_none_match: &Option<String>,
+    ) -> Result<()> {
+        let current = etag(&entry.content);
+        if let Some(expected) = if_match {
+            if expected != "*" && unquote_etag(expected) != current {
+                return Err(Error::Object {
+                    kind: Kind::ObjectPreconditionFailed,
+                    op,
+                    path: path.to_string(),
+                    context: HashMap::new(),
+                    source: anyhow!("etag {} doesn't match {}", current, expected),
+                });
+            }
+        }
+        if let Some(expected) = if_none_match {
+            if expected == "*" || unquote_etag(expected) == current {
+                return Err(Error::Object {
+                    kind: Kind::ObjectNotModified,
+                    op,
+                    path: path.to_string(),
+                    context: HashMap::new(),
+                    source: anyhow!("etag {} matches {}", current, expected),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Check the time preconditions of a read in seconds.
+    fn check_modified(
+        &self,
+        path: &str,
+        if_modified_since: &Option<OffsetDateTime>,
+        if_unmodified_since: &Option<OffsetDateTime>,
+    ) -> Result<()> {
+        let modified = self
+            .last_modified
+            .duration_since(UNIX_EPOCH)
+            .map(|v| v.as_secs() as i64)
+            .unwrap_or_default();
+
+        if let Some(t) = if_unmodified_since {
+            if modified > t.unix_timestamp() {
+                return Err(Error::Object {
+                    kind: Kind::ObjectPreconditionFailed,
+                    op: "read",
+                    path: path.to_string(),
+                    context: HashMap::new(),
+                    source: anyhow!("object has been modified since {}", t),
+                });
+            }
+        }
+        if let Some(t) = if_modified_since {
+            if modified <= t.unix_timestamp() {
+                return Err(Error::Object {
+                    kind: Kind::ObjectNotModified,
+                    op: "read",
+                    path: path.to_string(),
+                    context: HashMap::new(),
+                    source: anyhow!("object has not been modified since {}", t),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    fn unsupported<T>(op: &'static str, path: &str) -> Result<T> {
+        Err(Error::Object {
+            kind: Kind::Unsupported,
+            op,
+            path: path.to_string(),
+            context: HashMap::new(),
+            source: anyhow!("static files are read only"),
+        })
+    }
+}
+
+#[async_trait]
+impl Accessor for Backend {
+    #[cfg_attr(feature = "minitrace", trace("read"))]
+    async fn read(&self, args: &OpRead) -> Result<BytesStream> {
+        validate_path("read", &args.path)?;
+        let path = normalize_path(&args.path);
+        if args.version.is_some() {
+            return Err(Error::Object {
+                kind: Kind::Unsupported,
+                op: "read",
+                path,
+                context: HashMap::new(),
+                source: anyhow!("static files don't support versioning"),
+            });
+        }
+
+        if args.range.size() == Some(0) {
+            return Ok(Box::new(stream::empty()));
+        }
+
+        let entry = self.get("read", &path)?;
+        Backend::check_etag("read", &path, entry, &args.if_match, &args.if_none_match)?;
+        self.check_modified(&path, &args.if_modified_since, &args.if_unmodified_since)?;
+
+        let len = entry.content.len() as u64;
+        let (offset, size) = match (args.range.offset(), args.range.size()) {
+            // Read the last `size` bytes, or the whole object if it's shorter.
+            (None, Some(size)) => (len - size.min(len), Some(size)),
+            (offset, size) => (offset.unwrap_or_default(), size),
+        };
+        if offset > len {
+            return Err(Error::Object {
+                kind: Kind::Unexpected,
+                op: "read",
+                path,
+                context: HashMap::new(),
+                source: anyhow!("offset out of bound {} > {}", offset, len),
+            });
+        }
+        // Clamp the range to the end of the object like s3.
+        let end = size.map(|v| (offset + v).min(len)).unwrap_or(len);
+        let data = entry.content.slice(offset as usize..end as usize);
+
+        Ok(Box::new(stream::once(future::ready(Ok(data)))))
+    }
+    #[cfg_attr(feature = "minitrace", trace("write"))]
+    async fn write(&self, _: BoxedAsyncReader, args: &OpWrite) -> Result<Metadata> {
+        Backend::unsupported("write", &args.path)
+    }
+    #[cfg_attr(feature = "minitrace", trace("stat"))]
+    async fn stat(&self, args: &OpStat) -> Result<Metadata> {
+        validate_path("stat", &args.path)?;
+        let path = normalize_path(&args.path);
+        if args.version.is_some() {
+            return Err(Error::Object {
+                kind: Kind::Unsupported,
+                op: "stat",
+                path,
+                context: HashMap::new(),
+                source: anyhow!("static files don't support versioning"),
+            });
+        }
+
+        if path.ends_with('/') || path.is_empty() {
+            if !self.is_dir(&path) {
+                return Err(Error::Object {
+                    kind: Kind::ObjectNotExist,
+                    op: "stat",
+                    path,
+                    context: HashMap::new(),
+                    source: anyhow!("no objects under dir"),
+                });
+            }
+            return Ok(Backend::dir_metadata(&path));
+        }
+
+        let entry = self.get("stat", &path)?;
+        Backend::check_etag("stat", &path, entry, &args.if_match, &None)?;
+
+        Ok(self.file_metadata(&path, entry))
+    }
+    #[cfg_attr(feature = "minitrace", trace("delete"))]
+    async fn delete(&self, args: &OpDelete) -> Result<()> {
+        Backend::unsupported("delete", &args.path)
+    }
+    #[cfg_attr(feature = "minitrace", trace("list"))]
+    async fn list(&self, args: &OpList) -> Result<BoxedObjectStream> {
+        validate_path("list", &args.path)?;
+        let mut path = normalize_path(&args.path);
+        if args.versions {
+            return Err(Error::Object {
+                kind: Kind::Unsupported,
+                op: "list",
+                path,
+                context: HashMap::new(),
+                source: anyhow!("static files don't support versioning"),
+            });
+        }
+        if !path.is_empty() && !path.ends_with('/') {
+            path.push('/');
+        }
+
+        // Direct children of the dir, dirs are synthesized from the paths of
+        // objects under them. While listing recursively, all objects under
+        // the dir are yielded instead.
+        let mut children = BTreeSet::new();
+        for (k, _) in self.entries.range(path.clone()..) {
+            let rest = match k.strip_prefix(path.as_str()) {
+                Some(rest) => rest,
+                None => break,
+            };
+            match rest.find('/') {
+                Some(_) if args.recursive => children.insert(k.clone()),
+                Some(idx) => children.insert(k[..path.len() + idx + 1].to_string()),
+                None => children.insert(k.clone()),
+            };
+        }
+
+        let acc: Arc<dyn Accessor> = Arc::new(self.clone());
+        let objects = children
+            .into_iter()
+            .filter(|k| match &args.start_after {
+                Some(start_after) => k.as_str() > start_after.as_str(),
+                None => true,
+            })
+            .map(|k| {
+                let meta = match self.entries.get(&k) {
+                    Some(entry) => self.file_metadata(&k, entry),
+                    None => Backend::dir_metadata(&k),
+                };
+                let mut o = Object::new(acc.clone(), &k);
+                *o.metadata_mut() = meta;
+                Ok(o)
+            })
+            .collect::<Vec<_>>();
+
+        Ok(Box::new(LimitedObjectStream::new(
+            filter_by_pattern(Box::new(stream::iter(objects)), args.pattern.as_deref()),
+            &args.path,
+            args.max_results,
+            self.list_scan_limit,
+        )))
+    }
+
+    fn metadata(&self) -> AccessorMetadata {
+        let mut am = AccessorMetadata::default();
+        am.set_scheme(Scheme::StaticFiles)
+            .set_root("/")
+            .set_list(true)
+            .set_ordered_list(true)
+            .set_recursive_list(true);
+        am
+    }
+}
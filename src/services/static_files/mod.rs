@@ -0,0 +1,54 @@
+// Copyright 2022 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Read-only backend serving objects defined inline, for example, fixtures
+//! embedded in configurations.
+//!
+//! Values starting with `data:` are decoded as [data URLs](https://datatracker.ietf.org/doc/html/rfc2397),
+//! others are served as literal text. Dirs are synthesized from the paths of
+//! objects, and all mutations return `Kind::Unsupported`.
+//!
+//! # Example
+//!
+//! ```
+//! use anyhow::Result;
+//! use futures::AsyncReadExt;
+//! use opendal::services::static_files;
+//! use opendal::Operator;
+//!
+//! #[tokio::main]
+//! async fn main() -> Result<()> {
+//!     let acc = static_files::Builder::from_json(
+//!         r#"{
+//!             "manifest.json": "{\"version\": 1}",
+//!             "data": { "empty.bin": "data:application/octet-stream;base64," }
+//!         }"#,
+//!     )?
+//!     .finish()
+//!     .await?;
+//!     let op = Operator::new(acc);
+//!
+//!     let mut bs = Vec::new();
+//!     op.object("manifest.json")
+//!         .reader()
+//!         .read_to_end(&mut bs)
+//!         .await?;
+//!     assert_eq!(bs, b"{\"version\": 1}");
+//!     Ok(())
+//! }
+//! ```
+
+mod backend;
+pub use backend::Backend;
+pub use backend::Builder;
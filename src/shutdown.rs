@@ -0,0 +1,223 @@
+// Copyright 2022 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Shutdown discipline of components holding state that outlives a call,
+//! like held locks, in-flight multipart uploads, http recordings and
+//! uncommitted writes.
+//!
+//! - Components provide an explicit `async fn close(&mut self)` by [`Close`]
+//!   for graceful cleanup.
+//! - `Drop` never blocks: cleanup is spawned as a detached task if there is
+//!   a tokio runtime, otherwise the state is abandoned with a warning and
+//!   the `opendal_shutdown_abandoned` counter, labeled by `component`.
+//! - [`ShutdownGuard`] closes a group of components in order.
+
+use std::fmt::Debug;
+use std::fmt::Formatter;
+use std::future::Future;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use log::debug;
+use log::warn;
+use metrics::increment_counter;
+use tokio::sync::Notify;
+
+use crate::error::Result;
+
+/// Count of abandoned cleanups in this process.
+static ABANDONED: AtomicU64 = AtomicU64::new(0);
+
+/// Close releases the state held by a component gracefully.
+///
+/// Closing an already closed component is a no-op.
+#[async_trait]
+pub trait Close: Send {
+    async fn close(&mut self) -> Result<()>;
+}
+
+/// Run `cleanup` of `component` in background without blocking, used by
+/// `Drop` implementations.
+///
+/// The cleanup is abandoned if there is no tokio runtime in the current
+/// thread, or the runtime is shutting down.
+pub(crate) fn spawn_cleanup<F>(component: &'static str, target: &str, cleanup: F)
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    match tokio::runtime::Handle::try_current() {
+        Ok(handle) => {
+            debug!("{} {} dropped, cleanup in background", component, target);
+            handle.spawn(cleanup);
+        }
+        Err(_) => abandon(component, target),
+    }
+}
+
+/// Record that the state of `component` has been abandoned without cleanup.
+pub(crate) fn abandon(component: &'static str, target: &str) {
+    warn!(
+        "{} {} dropped without close outside of runtime, its state is abandoned",
+        component, target
+    );
+    ABANDONED.fetch_add(1, Ordering::Relaxed);
+    increment_counter!("opendal_shutdown_abandoned", "component" => component);
+}
+
+/// InFlight counts the in-flight work of a component, so that its `close`
+/// can wait for all of them to finish.
+#[derive(Debug, Default)]
+pub(crate) struct InFlight {
+    count: AtomicUsize,
+    notify: Notify,
+}
+
+impl InFlight {
+    /// Track a work until the returned token is dropped.
+    pub fn enter(self: &Arc<Self>) -> InFlightToken {
+        self.count.fetch_add(1, Ordering::AcqRel);
+        InFlightToken(self.clone())
+    }
+
+    /// Count of works in flight.
+    pub fn count(&self) -> usize {
+        self.count.load(Ordering::Acquire)
+    }
+
+    /// Wait until no work is in flight.
+    pub async fn wait(&self) {
+        loop {
+            // Register before checking the count to not miss the wakeup.
+            let notified = self.notify.notified();
+            if self.count() == 0 {
+                return;
+            }
+            notified.await;
+        }
+    }
+}
+
+/// InFlightToken marks a work in flight until dropped.
+#[derive(Debug)]
+pub(crate) struct InFlightToken(Arc<InFlight>);
+
+impl Drop for InFlightToken {
+    fn drop(&mut self) {
+        if self.0.count.fetch_sub(1, Ordering::AcqRel) == 1 {
+            self.0.notify.notify_waiters();
+        }
+    }
+}
+
+/// ShutdownGuard closes registered components in the reverse order of
+/// registration, so that components should be registered from the inner
+/// most one, like an operator's layers.
+///
+/// Components that have not been closed while dropping the guard are
+/// dropped with their own best-effort cleanup, and counted as abandoned.
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use anyhow::Result;
+/// use opendal::services::memory;
+/// use opendal::Close;
+/// use opendal::Lock;
+/// use opendal::Operator;
+/// use opendal::ShutdownGuard;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<()> {
+///     let op = Operator::new(memory::Backend::build().finish().await?);
+///
+///     let mut guard = ShutdownGuard::new();
+///     let lock = Lock::acquire(&op, "locks/daily", Duration::from_secs(60), "worker-1").await?;
+///     guard.register("daily", lock);
+///
+///     // Release the lock and everything else registered.
+///     guard.close().await?;
+///
+///     Ok(())
+/// }
+/// ```
+#[derive(Default)]
+pub struct ShutdownGuard {
+    components: Vec<(String, Box<dyn Close>)>,
+}
+
+impl Debug for ShutdownGuard {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ShutdownGuard")
+            .field(
+                "components",
+                &self.components.iter().map(|(v, _)| v).collect::<Vec<_>>(),
+            )
+            .finish()
+    }
+}
+
+impl ShutdownGuard {
+    /// Create an empty guard.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register the component with `name`, which will be closed before the
+    /// ones registered earlier.
+    pub fn register(&mut self, name: &str, component: impl Close + 'static) -> &mut Self {
+        self.components
+            .push((name.to_string(), Box::new(component)));
+        self
+    }
+
+    /// Count of cleanups that have been abandoned in this process, because
+    /// components were dropped without close outside of a runtime.
+    pub fn abandoned() -> u64 {
+        ABANDONED.load(Ordering::Relaxed)
+    }
+}
+
+#[async_trait]
+impl Close for ShutdownGuard {
+    /// Close all components even if some of them failed, returns the first
+    /// error with the name of the component in `component` context.
+    async fn close(&mut self) -> Result<()> {
+        let mut first_err = None;
+        while let Some((name, mut component)) = self.components.pop() {
+            debug!("shutdown guard close {}", name);
+            if let Err(err) = component.close().await {
+                warn!("shutdown guard close {}: {:?}", name, err);
+                first_err.get_or_insert_with(|| err.with_context("component", &name));
+            }
+        }
+
+        match first_err {
+            Some(err) => Err(err),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for ShutdownGuard {
+    fn drop(&mut self) {
+        for (name, _) in self.components.iter().rev() {
+            abandon("shutdown_guard", name);
+        }
+    }
+}
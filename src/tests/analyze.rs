@@ -0,0 +1,142 @@
+// Copyright 2022 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+
+use crate::analyze::extension;
+use crate::error::Kind;
+use crate::services::fs;
+use crate::services::memory;
+use crate::AnalyzeOptions;
+use crate::KeySize;
+use crate::KeyStats;
+use crate::Operator;
+
+#[test]
+fn test_extension() {
+    let cases = vec![
+        ("a.parquet", "parquet"),
+        ("dir/a.JSON", "json"),
+        ("dir/a.tar.gz", "gz"),
+        ("dir.d/readme", ""),
+        ("dir/.env", ""),
+        ("dir/a.", ""),
+        ("dir/", ""),
+    ];
+    for (path, expected) in cases {
+        assert_eq!(extension(path), expected, "{}", path);
+    }
+}
+
+async fn analyze_fixture(op: &Operator) -> Result<()> {
+    let files: Vec<(String, usize)> = vec![
+        ("data/a.parquet".to_string(), 100),
+        ("data/b.json".to_string(), 10),
+        ("data/2022/c.parquet".to_string(), 300),
+        ("data/2022/d.csv".to_string(), 30),
+        ("data/2022/06/e.tar.gz".to_string(), 50),
+        ("data/2022/06/README".to_string(), 5),
+        ("other/f.parquet".to_string(), 1000),
+    ]
+    .into_iter()
+    .chain((0..12).map(|i| (format!("data/logs/{:02}.json", i), i)))
+    .collect();
+    for (path, size) in files {
+        op.object(&path).writer().write_bytes(vec![0; size]).await?;
+    }
+    Ok(())
+}
+
+fn stats(count: u64, bytes: u64) -> KeyStats {
+    KeyStats { count, bytes }
+}
+
+fn key(path: &str, size: u64) -> KeySize {
+    KeySize {
+        size,
+        path: path.to_string(),
+    }
+}
+
+async fn check_analyze(op: &Operator) -> Result<()> {
+    analyze_fixture(op).await?;
+
+    let analysis = op
+        .analyze_prefix("data/", AnalyzeOptions::default())
+        .await?;
+    // 0 + 1 + ... + 11 bytes of logs.
+    assert_eq!(analysis.total, stats(18, 495 + 66));
+    assert_eq!(
+        analysis.extensions,
+        BTreeMap::from([
+            ("".to_string(), stats(1, 5)),
+            ("csv".to_string(), stats(1, 30)),
+            ("gz".to_string(), stats(1, 50)),
+            ("json".to_string(), stats(13, 76)),
+            ("parquet".to_string(), stats(2, 400)),
+        ])
+    );
+    assert_eq!(analysis.depths, BTreeMap::from([(1, 2), (2, 14), (3, 2)]));
+    // Ties of the same size are ordered by path.
+    assert_eq!(
+        analysis.largest,
+        vec![
+            key("data/2022/c.parquet", 300),
+            key("data/a.parquet", 100),
+            key("data/2022/06/e.tar.gz", 50),
+            key("data/2022/d.csv", 30),
+            key("data/logs/11.json", 11),
+            key("data/b.json", 10),
+            key("data/logs/10.json", 10),
+            key("data/logs/09.json", 9),
+            key("data/logs/08.json", 8),
+            key("data/logs/07.json", 7),
+        ]
+    );
+    let json = serde_json::to_value(&analysis)?;
+    assert_eq!(json["extensions"]["parquet"]["bytes"], 400);
+    assert_eq!(json["largest"][0]["path"], "data/2022/c.parquet");
+
+    let analysis = op
+        .analyze_prefix("data/", AnalyzeOptions::default().max_depth(1))
+        .await?;
+    assert_eq!(analysis.total, stats(2, 110));
+    assert_eq!(analysis.depths, BTreeMap::from([(1, 2)]));
+
+    let err = op
+        .analyze_prefix("data/", AnalyzeOptions::default().scan_limit(3))
+        .await
+        .unwrap_err();
+    assert_eq!(err.kind(), Kind::ScanLimitExceeded);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_analyze_prefix_memory() -> Result<()> {
+    let op = Operator::new(memory::Backend::build().finish().await?);
+    check_analyze(&op).await
+}
+
+#[tokio::test]
+async fn test_analyze_prefix_fs() -> Result<()> {
+    let root = format!("/tmp/opendal-test-{}", uuid::Uuid::new_v4());
+    let op = Operator::new(fs::Backend::build().root(&root).finish().await?);
+    check_analyze(&op).await?;
+
+    std::fs::remove_dir_all(&root)?;
+    Ok(())
+}
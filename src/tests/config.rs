@@ -0,0 +1,125 @@
+// Copyright 2022 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+
+use anyhow::Result;
+
+use super::mock::mock_server;
+use crate::config::ConfigMap;
+use crate::error::Kind;
+use crate::services::s3;
+use crate::Operator;
+use crate::Scheme;
+
+fn config_map(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+    pairs
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect()
+}
+
+#[test]
+fn test_config_map() -> Result<()> {
+    let mut map = ConfigMap::new(
+        Scheme::S3,
+        config_map(&[("a", "1"), ("empty", ""), ("flag", "true"), ("ak", "x")]),
+    );
+    assert_eq!(map.take("a").as_deref(), Some("1"));
+    assert_eq!(map.take("empty"), None);
+    assert_eq!(map.take_bool("flag")?, Some(true));
+    assert_eq!(map.take_bool("flag")?, None);
+
+    let err = map.take_required("bucket").unwrap_err();
+    assert_eq!(err.kind(), Kind::BackendConfigurationInvalid);
+    assert_eq!(err.context()["key"], "bucket");
+
+    let err = map.take_pair("ak", "sk").unwrap_err();
+    assert_eq!(err.context()["key"], "sk");
+    map.finish()?;
+
+    let mut map = ConfigMap::new(Scheme::S3, config_map(&[("flag", "yes"), ("b", "2")]));
+    let err = map.take_bool("flag").unwrap_err();
+    assert_eq!(err.context()["key"], "flag");
+    // Values could be secrets.
+    assert!(!format!("{:?}", err).contains("yes"));
+    let err = map.finish().unwrap_err();
+    assert_eq!(err.context()["key"], "b");
+
+    Ok(())
+}
+
+#[test]
+fn test_s3_from_map() {
+    assert!(s3::Builder::from_map(config_map(&[
+        ("bucket", "test"),
+        ("root", "/data"),
+        ("region", "us-east-2"),
+        ("access_key_id", "ak"),
+        ("secret_access_key", "sk"),
+        ("anonymous", "false"),
+        ("requester_pays", "true"),
+    ]))
+    .is_ok());
+
+    for (pairs, key) in [
+        (vec![("root", "/data")], "bucket"),
+        (vec![("bucket", "")], "bucket"),
+        (
+            vec![("bucket", "test"), ("secret_access_key", "sk")],
+            "access_key_id",
+        ),
+        (vec![("bucket", "test"), ("regoin", "us-east-2")], "regoin"),
+    ] {
+        let err = s3::Builder::from_map(config_map(&pairs)).unwrap_err();
+        assert_eq!(err.kind(), Kind::BackendConfigurationInvalid);
+        assert_eq!(err.context()["key"], key);
+    }
+}
+
+#[tokio::test]
+async fn test_operator_from_env() -> Result<()> {
+    let (endpoint, _) = mock_server(|_| hyper::Response::new(hyper::Body::empty()));
+
+    // Only this test sets variables of s3.
+    std::env::set_var("OPENDAL_S3_ROOT", "/data");
+    let err = Operator::from_env(Scheme::S3).await.err().unwrap();
+    assert_eq!(err.kind(), Kind::BackendConfigurationInvalid);
+    assert_eq!(err.context()["env"], "OPENDAL_S3_BUCKET");
+
+    std::env::set_var("OPENDAL_S3_BUCKET", "test");
+    std::env::set_var("OPENDAL_S3_ENDPOINT", &endpoint);
+    std::env::set_var("OPENDAL_S3_REGION", "us-east-1");
+    std::env::set_var("OPENDAL_S3_REGOIN", "us-east-1");
+    let err = Operator::from_env(Scheme::S3).await.err().unwrap();
+    assert_eq!(err.context()["env"], "OPENDAL_S3_REGOIN");
+
+    std::env::remove_var("OPENDAL_S3_REGOIN");
+    let op = Operator::from_env(Scheme::S3).await?;
+    assert_eq!(op.metadata().root(), "/data/");
+
+    for key in [
+        "OPENDAL_S3_ROOT",
+        "OPENDAL_S3_BUCKET",
+        "OPENDAL_S3_ENDPOINT",
+        "OPENDAL_S3_REGION",
+    ] {
+        std::env::remove_var(key);
+    }
+
+    let err = Operator::from_env(Scheme::StaticFiles).await.err().unwrap();
+    assert_eq!(err.kind(), Kind::BackendNotSupported);
+
+    Ok(())
+}
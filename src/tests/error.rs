@@ -0,0 +1,148 @@
+// Copyright 2022 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+use anyhow::anyhow;
+
+use crate::error::Error;
+use crate::error::Kind;
+use crate::error::Result;
+use crate::ops::OpStat;
+use crate::Accessor;
+use crate::Layer;
+use crate::Metadata;
+use crate::Operator;
+
+#[test]
+fn test_with_context() {
+    let err = Error::Object {
+        kind: Kind::ObjectNotExist,
+        op: "stat",
+        path: "path/to/file".to_string(),
+        context: Default::default(),
+        source: anyhow!("not found"),
+    };
+    assert_eq!(
+        err.to_string(),
+        "object not exist: (op: stat, path: path/to/file, source: not found)"
+    );
+
+    let err = err
+        .with_context("table", "t1")
+        .with_context("query_id", 42)
+        .with_context("table", "t2");
+    assert_eq!(err.kind(), Kind::ObjectNotExist);
+    assert_eq!(err.context().len(), 2);
+    assert_eq!(err.context()["table"], "t2");
+    assert_eq!(err.context()["query_id"], "42");
+    assert_eq!(
+        err.to_string(),
+        r#"object not exist: (op: stat, path: path/to/file, context: {"query_id": "42", "table": "t2"}, source: not found)"#
+    );
+
+    let err = Error::from(anyhow!("oops"))
+        .with_context("b", "2")
+        .with_context("a", "1");
+    assert_eq!(err.kind(), Kind::Unexpected);
+    assert_eq!(
+        err.to_string(),
+        r#"unexpected: (context: {"a": "1", "b": "2"}, source: oops)"#
+    );
+
+    let err = Error::Backend {
+        kind: Kind::BackendConfigurationInvalid,
+        context: Default::default(),
+        source: anyhow!("bucket is empty"),
+    }
+    .with_context("bucket", "")
+    .with_context("app", "x");
+    assert_eq!(err.kind(), Kind::BackendConfigurationInvalid);
+    assert_eq!(
+        err.to_string(),
+        r#"backend configuration invalid: (context: {"app": "x", "bucket": ""}, source: bucket is empty)"#
+    );
+}
+
+/// Fail always fails stat with the attempt in context.
+#[derive(Debug, Default)]
+struct Fail {
+    attempts: AtomicUsize,
+}
+
+#[async_trait::async_trait]
+impl Accessor for Fail {
+    async fn stat(&self, args: &OpStat) -> Result<Metadata> {
+        let attempt = self.attempts.fetch_add(1, Ordering::SeqCst) + 1;
+        Err(Error::Object {
+            kind: Kind::ObjectPermissionDenied,
+            op: "stat",
+            path: args.path.clone(),
+            context: Default::default(),
+            source: anyhow!("denied"),
+        }
+        .with_context("attempt", attempt))
+    }
+}
+
+/// Retry retries stat and returns the last error as is.
+#[derive(Debug)]
+struct Retry {
+    inner: Arc<dyn Accessor>,
+}
+
+impl Layer for &Retry {
+    fn layer(&self, inner: Arc<dyn Accessor>) -> Arc<dyn Accessor> {
+        Arc::new(Retry { inner })
+    }
+}
+
+#[async_trait::async_trait]
+impl Accessor for Retry {
+    async fn stat(&self, args: &OpStat) -> Result<Metadata> {
+        let mut result = self.inner.stat(args).await;
+        for _ in 1..3 {
+            if result.is_ok() {
+                break;
+            }
+            result = self.inner.stat(args).await;
+        }
+        result
+    }
+}
+
+#[tokio::test]
+async fn test_context_through_layers() {
+    let fail: Arc<dyn Accessor> = Arc::new(Fail::default());
+    let retry = Retry {
+        inner: fail.clone(),
+    };
+    let op = Operator::new(fail).layer(&retry);
+
+    let err = op
+        .object("file")
+        .metadata()
+        .await
+        .unwrap_err()
+        .with_context("table", "t1");
+
+    assert_eq!(err.kind(), Kind::ObjectPermissionDenied);
+    assert_eq!(err.context()["attempt"], "3");
+    assert_eq!(err.context()["table"], "t1");
+    assert!(err
+        .to_string()
+        .contains(r#"context: {"attempt": "3", "table": "t1"}"#));
+}
@@ -0,0 +1,348 @@
+// Copyright 2022 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+
+use bytes::Bytes;
+use bytes::BytesMut;
+use futures::io::Cursor;
+use futures::stream;
+use futures::AsyncReadExt;
+use futures::StreamExt;
+
+use crate::error::Kind;
+use crate::error::Result;
+use crate::format::framed::*;
+
+/// XorCodec stores the chunk index as the nonce and prefixes every payload
+/// with a tag byte, so that the payload differs from the plaintext and has
+/// a fixed overhead.
+#[derive(Debug)]
+struct XorCodec;
+
+impl XorCodec {
+    fn key(index: u64) -> u8 {
+        (index as u8) ^ 0x5a
+    }
+}
+
+impl ChunkCodec for XorCodec {
+    fn nonce_len(&self) -> u8 {
+        2
+    }
+
+    fn encode(&self, index: u64, plain: &[u8], nonce: &mut [u8]) -> Result<Vec<u8>> {
+        nonce.copy_from_slice(&(index as u16).to_be_bytes());
+        let mut payload = vec![0xaa];
+        payload.extend(plain.iter().map(|b| b ^ Self::key(index)));
+        Ok(payload)
+    }
+
+    fn decode(&self, index: u64, payload: &[u8], nonce: &[u8], _: usize) -> Result<Vec<u8>> {
+        assert_eq!(nonce, (index as u16).to_be_bytes());
+        assert_eq!(payload[0], 0xaa);
+        Ok(payload[1..].iter().map(|b| b ^ Self::key(index)).collect())
+    }
+}
+
+fn encode(codec: Arc<dyn ChunkCodec>, header: Header, plain: &[u8]) -> Vec<u8> {
+    let mut encoder = Encoder::new(codec, header);
+    let mut out = BytesMut::new();
+    // Push in uneven pieces to cover chunks spanning several pushes.
+    for piece in plain.chunks(3) {
+        encoder.push(piece, &mut out).unwrap();
+    }
+    encoder.finish(&mut out).unwrap();
+    out.to_vec()
+}
+
+fn decode(codec: Arc<dyn ChunkCodec>, framed: &[u8]) -> Result<Vec<u8>> {
+    let mut decoder = Decoder::new("test", codec);
+    let mut plain = Vec::new();
+    // Feed byte by byte to cover every partial state of the decoder.
+    for b in framed {
+        for bs in decoder.feed(&[*b])? {
+            plain.extend_from_slice(&bs);
+        }
+    }
+    decoder.finish()?;
+    Ok(plain)
+}
+
+#[test]
+fn test_crc32c() {
+    assert_eq!(crc32c(b""), 0);
+    assert_eq!(crc32c(b"123456789"), 0xe306_9283);
+}
+
+/// Name, codec, header, plaintext and the framed object.
+type GoldenCase = (
+    &'static str,
+    Arc<dyn ChunkCodec>,
+    Header,
+    &'static [u8],
+    &'static [u8],
+);
+
+#[test]
+fn test_golden() {
+    let cases: Vec<GoldenCase> = vec![
+        (
+            "empty",
+            Arc::new(Identity),
+            Header::new(4),
+            b"",
+            include_bytes!("testdata/framed/empty.bin"),
+        ),
+        (
+            "checksum_digest",
+            Arc::new(Identity),
+            Header::new(4).checksum(true).digest(true),
+            b"Hello, world!",
+            include_bytes!("testdata/framed/checksum_digest.bin"),
+        ),
+        (
+            "nonce",
+            Arc::new(XorCodec),
+            Header::new(5).nonce_len(2).checksum(true),
+            b"The quick brown fox",
+            include_bytes!("testdata/framed/nonce.bin"),
+        ),
+    ];
+
+    for (name, codec, header, plain, golden) in cases {
+        assert_eq!(
+            encode(codec.clone(), header, plain),
+            golden,
+            "encode {}",
+            name
+        );
+        assert_eq!(decode(codec, golden).unwrap(), plain, "decode {}", name);
+    }
+}
+
+#[test]
+fn test_spec() {
+    let mut expected = Vec::new();
+    expected.extend_from_slice(b"OFRM");
+    expected.extend_from_slice(&[1, 0b11, 0, 0]);
+    expected.extend_from_slice(&4u32.to_be_bytes());
+    for chunk in [&b"Hell"[..], b"o, w", b"orld", b"!"] {
+        expected.extend_from_slice(&(chunk.len() as u32).to_be_bytes());
+        expected.extend_from_slice(&(chunk.len() as u32).to_be_bytes());
+        expected.extend_from_slice(&crc32c(chunk).to_be_bytes());
+        expected.extend_from_slice(chunk);
+    }
+    expected.extend_from_slice(&[0; 8]);
+    expected.extend_from_slice(&13u64.to_be_bytes());
+    expected.extend_from_slice(&4u64.to_be_bytes());
+    expected.extend_from_slice(&md5::compute(b"Hello, world!").0);
+    expected.extend_from_slice(b"OFRE");
+
+    assert_eq!(
+        &expected[..],
+        include_bytes!("testdata/framed/checksum_digest.bin")
+    );
+
+    let header = Header::new(4).checksum(true).digest(true);
+    assert_eq!(header.encoded_len(13, 0), expected.len() as u64);
+    assert_eq!(
+        header.trailer_len(),
+        8 + 16 + 16 + 4,
+        "trailer includes end marker"
+    );
+}
+
+#[test]
+fn test_round_trip() {
+    let plain: Vec<u8> = (0..1000u32).map(|i| (i * 7 % 251) as u8).collect();
+
+    for chunk_size in [1, 3, 64, 1000, 4096] {
+        for size in [0, 1, 2, 63, 64, 65, 999, 1000] {
+            let plain = &plain[..size];
+            let header = Header::new(chunk_size).checksum(true).digest(true);
+            let framed = encode(Arc::new(Identity), header, plain);
+            assert_eq!(framed.len() as u64, header.encoded_len(size as u64, 0));
+            assert_eq!(decode(Arc::new(Identity), &framed).unwrap(), plain);
+
+            let header = Header::new(chunk_size).nonce_len(2);
+            let framed = encode(Arc::new(XorCodec), header, plain);
+            assert_eq!(framed.len() as u64, header.encoded_len(size as u64, 1));
+            assert_eq!(decode(Arc::new(XorCodec), &framed).unwrap(), plain);
+        }
+    }
+}
+
+#[test]
+fn test_chunk_range() {
+    let header = Header::new(4);
+    let cases = vec![
+        ("full", 0, None, (0, 4, 0, 13)),
+        ("middle", 5, Some(4), (1, 2, 1, 4)),
+        ("single chunk", 4, Some(4), (1, 1, 0, 4)),
+        ("last byte", 12, None, (3, 1, 0, 1)),
+        ("size overflow", 10, Some(100), (2, 2, 2, 3)),
+        ("offset at end", 13, None, (3, 0, 0, 0)),
+        ("offset past end", 20, Some(1), (3, 0, 0, 0)),
+        ("zero size", 2, Some(0), (0, 0, 0, 0)),
+    ];
+
+    for (name, offset, size, (first, count, skip, len)) in cases {
+        assert_eq!(
+            header.chunk_range(13, offset, size),
+            ChunkRange {
+                first,
+                count,
+                skip,
+                len
+            },
+            "{}",
+            name
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_decode_stream_range() {
+    let plain = b"The quick brown fox";
+    let framed = Bytes::from_static(include_bytes!("testdata/framed/nonce.bin"));
+    let header = Header::parse("test", &framed).unwrap();
+    let total = plain.len() as u64;
+
+    for offset in 0..=total {
+        for size in 0..=total - offset {
+            let range = header.chunk_range(total, offset, Some(size));
+            let (start, len) = header.stored_range(total, &range, 1);
+            let input = framed.slice(start as usize..(start + len) as usize);
+
+            // Split the input to cover chunks spanning several items.
+            let items: Vec<Result<Bytes>> = input
+                .chunks(4)
+                .map(|v| Ok(Bytes::copy_from_slice(v)))
+                .collect();
+            let decoder = Decoder::resume("test", Arc::new(XorCodec), header, range.first);
+            let mut s = DecodeStream::new(Box::new(stream::iter(items)), decoder)
+                .window(range.skip, range.len);
+
+            let mut actual = Vec::new();
+            while let Some(bs) = s.next().await {
+                actual.extend_from_slice(&bs.unwrap());
+            }
+            assert_eq!(
+                actual,
+                &plain[offset as usize..(offset + size) as usize],
+                "offset {} size {}",
+                offset,
+                size
+            );
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_encode_reader() {
+    let plain = b"The quick brown fox";
+    let header = Header::new(5).nonce_len(2).checksum(true);
+    let mut r = EncodeReader::new(
+        Cursor::new(plain.to_vec()),
+        Encoder::new(Arc::new(XorCodec), header),
+    );
+
+    let mut framed = Vec::new();
+    r.read_to_end(&mut framed).await.unwrap();
+    assert_eq!(&framed[..], include_bytes!("testdata/framed/nonce.bin"));
+}
+
+#[tokio::test]
+async fn test_decode_stream_truncated() {
+    let framed = include_bytes!("testdata/framed/checksum_digest.bin");
+    let items: Vec<Result<Bytes>> = vec![Ok(Bytes::copy_from_slice(&framed[..framed.len() - 1]))];
+    let mut s = DecodeStream::new(
+        Box::new(stream::iter(items)),
+        Decoder::new("test", Arc::new(Identity)),
+    );
+
+    let mut err = None;
+    while let Some(bs) = s.next().await {
+        if let Err(e) = bs {
+            err = Some(e);
+        }
+    }
+    assert_eq!(err.unwrap().kind(), Kind::ObjectCorrupted);
+}
+
+#[test]
+fn test_corrupted() {
+    let golden = include_bytes!("testdata/framed/checksum_digest.bin");
+    let trailer_start = golden.len() - Header::new(4).digest(true).trailer_len();
+
+    let mut cases: Vec<(&str, Vec<u8>, Kind)> = vec![
+        ("empty input", vec![], Kind::ObjectCorrupted),
+        ("header only", golden[..12].to_vec(), Kind::ObjectCorrupted),
+        (
+            "truncated trailer",
+            golden[..golden.len() - 1].to_vec(),
+            Kind::ObjectCorrupted,
+        ),
+        (
+            "missing end of chunks",
+            golden[..trailer_start].to_vec(),
+            Kind::ObjectCorrupted,
+        ),
+        (
+            "trailing data",
+            [&golden[..], b"x"].concat(),
+            Kind::ObjectCorrupted,
+        ),
+    ];
+
+    let mut patch = |name: &'static str, pos: usize, v: u8, kind: Kind| {
+        let mut bs = golden.to_vec();
+        bs[pos] = v;
+        cases.push((name, bs, kind));
+    };
+    patch("bad magic", 0, b'X', Kind::ObjectCorrupted);
+    patch("unknown version", 4, 2, Kind::Unsupported);
+    patch("unknown flags", 5, 0b111, Kind::Unsupported);
+    patch("nonce length mismatch", 6, 1, Kind::ObjectCorrupted);
+    patch("reserved", 7, 1, Kind::ObjectCorrupted);
+    // The first chunk starts at 12: stored_len, plain_len, crc, payload.
+    patch("zero stored length", 15, 0, Kind::ObjectCorrupted);
+    patch("zero plain length", 19, 0, Kind::ObjectCorrupted);
+    patch("plain length over chunk size", 19, 5, Kind::ObjectCorrupted);
+    patch("partial chunk in the middle", 19, 3, Kind::ObjectCorrupted);
+    patch("checksum mismatch", 20, 0, Kind::ObjectCorrupted);
+    patch("flipped payload", 24, b'h', Kind::ObjectCorrupted);
+    patch("end marker", trailer_start + 7, 1, Kind::ObjectCorrupted);
+    patch("plain total", trailer_start + 15, 14, Kind::ObjectCorrupted);
+    patch("chunk count", trailer_start + 23, 3, Kind::ObjectCorrupted);
+    patch("digest", trailer_start + 24, 0, Kind::ObjectCorrupted);
+    patch("end magic", golden.len() - 1, b'X', Kind::ObjectCorrupted);
+
+    for (name, bs, kind) in cases {
+        let err = decode(Arc::new(Identity), &bs).unwrap_err();
+        assert_eq!(err.kind(), kind, "{}: {:?}", name, err);
+    }
+}
+
+#[test]
+fn test_corrupted_without_checksum() {
+    // Without checksum, the digest still catches modified payloads.
+    let plain = b"Hello, world!";
+    let mut framed = encode(Arc::new(Identity), Header::new(4).digest(true), plain);
+    framed[12 + 8] = b'h';
+
+    let err = decode(Arc::new(Identity), &framed).unwrap_err();
+    assert_eq!(err.kind(), Kind::ObjectCorrupted);
+}
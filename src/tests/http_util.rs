@@ -0,0 +1,144 @@
+// Copyright 2022 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use anyhow::Result;
+use futures::AsyncReadExt;
+
+use super::mock::mock_server;
+use crate::credential::Credential;
+use crate::http_util::Recorder;
+use crate::http_util::ReplayClient;
+use crate::services::s3;
+use crate::Close;
+use crate::Operator;
+
+const SSE_KEY: &[u8] = b"0123456789abcdef0123456789abcdef";
+
+async fn s3_operator(
+    endpoint: &str,
+    recorder: Option<Recorder>,
+    replay: Option<ReplayClient>,
+) -> Result<Operator> {
+    let mut builder = s3::Backend::build();
+    builder
+        .bucket("test")
+        .endpoint(endpoint)
+        .region("us-east-1")
+        .server_side_encryption_with_customer_key("AES256", SSE_KEY)
+        .credential(Credential::hmac("access_key_id", "secret_access_key"));
+    if let Some(recorder) = recorder {
+        builder.http_recorder(recorder);
+    }
+    if let Some(replay) = replay {
+        builder.http_replay(replay);
+    }
+
+    Ok(Operator::new(builder.finish().await?))
+}
+
+#[tokio::test]
+async fn test_record_and_replay() -> Result<()> {
+    let stored = Arc::new(Mutex::new(Vec::new()));
+    let content = stored.clone();
+    let (endpoint, _) = mock_server(move |req| {
+        let mut content = content.lock().unwrap();
+        match *req.method() {
+            http::Method::PUT => {
+                *content = req.body().to_vec();
+                hyper::Response::new(hyper::Body::empty())
+            }
+            http::Method::HEAD => hyper::Response::builder()
+                .header(http::header::CONTENT_LENGTH, content.len())
+                .body(hyper::Body::empty())
+                .unwrap(),
+            _ => hyper::Response::new(hyper::Body::from(content.clone())),
+        }
+    });
+
+    let dir = format!("/tmp/opendal-record-{}", uuid::Uuid::new_v4());
+    let op = s3_operator(&endpoint, Some(Recorder::new(&dir)), None).await?;
+    op.object("file")
+        .writer()
+        .write_bytes(b"Hello, world!".to_vec())
+        .await?;
+    let meta = op.object("file").metadata().await?;
+    assert_eq!(meta.content_length(), 13);
+    let mut buf = vec![];
+    op.object("file").reader().read_to_end(&mut buf).await?;
+    assert_eq!(buf, b"Hello, world!");
+
+    // Secrets must not be recorded.
+    let mut rd = tokio::fs::read_dir(&dir).await?;
+    let mut files = 0;
+    while let Some(entry) = rd.next_entry().await? {
+        files += 1;
+        let content = String::from_utf8_lossy(&tokio::fs::read(entry.path()).await?).to_string();
+        for secret in [
+            base64::encode(SSE_KEY).as_str(),
+            "Signature=",
+            "authorization",
+            "secret_access_key",
+        ] {
+            assert!(!content.contains(secret), "{} leaked", secret);
+        }
+    }
+    // 3 exchanges, with a request body for PUT and a response body for GET.
+    assert_eq!(files, 5);
+
+    // Replay without touching the network.
+    let replay = ReplayClient::from_dir(&dir).await?;
+    let op = s3_operator("http://127.0.0.1:1", None, Some(replay)).await?;
+    op.object("file")
+        .writer()
+        .write_bytes(b"Hello, world!".to_vec())
+        .await?;
+    let meta = op.object("file").metadata().await?;
+    assert_eq!(meta.content_length(), 13);
+    let mut buf = vec![];
+    op.object("file").reader().read_to_end(&mut buf).await?;
+    assert_eq!(buf, b"Hello, world!");
+
+    // Every exchange is served only once.
+    assert!(op.object("file").metadata().await.is_err());
+
+    tokio::fs::remove_dir_all(&dir).await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_recorder_close() -> Result<()> {
+    let (endpoint, _) = mock_server(|_| hyper::Response::new(hyper::Body::empty()));
+
+    let dir = format!("/tmp/opendal-record-{}", uuid::Uuid::new_v4());
+    let mut recorder = Recorder::new(&dir);
+    let op = s3_operator(&endpoint, Some(recorder.clone()), None).await?;
+    op.object("file").metadata().await?;
+    recorder.close().await?;
+
+    // Exchanges after close are not recorded, and closing again is a no-op.
+    op.object("file").metadata().await?;
+    recorder.close().await?;
+    let mut rd = tokio::fs::read_dir(&dir).await?;
+    let mut files = 0;
+    while rd.next_entry().await?.is_some() {
+        files += 1;
+    }
+    assert_eq!(files, 1);
+
+    tokio::fs::remove_dir_all(&dir).await?;
+    Ok(())
+}
@@ -13,13 +13,35 @@
 // limitations under the License.
 use std::io::SeekFrom;
 use std::str::from_utf8;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
 
 use anyhow::Result;
+use async_trait::async_trait;
 use futures::AsyncReadExt;
 use futures::AsyncSeekExt;
+use futures::AsyncWriteExt;
+use futures::StreamExt;
 
+use super::mock::mock_broken_server;
+use super::mock::mock_s3_operator;
+use super::mock::mock_server;
+use crate::error::Error;
+use crate::error::Kind;
+use crate::io::BytesStream;
+use crate::ops::OpDelete;
+use crate::ops::OpRead;
+use crate::ops::OpStat;
 use crate::services::fs;
+use crate::services::memory;
+use crate::Accessor;
+use crate::Close;
+use crate::Metadata;
 use crate::Operator;
+use crate::SeekableReader;
+use crate::ShutdownGuard;
 
 #[tokio::test]
 async fn test_reader() -> Result<()> {
@@ -34,7 +56,7 @@ async fn test_reader() -> Result<()> {
         .write_bytes("Hello, world!".to_string().into_bytes())
         .await
         .unwrap();
-    assert_eq!(x, 13);
+    assert_eq!(x.content_length(), 13);
 
     let mut r = f.object(&path).reader();
 
@@ -43,8 +65,7 @@ async fn test_reader() -> Result<()> {
     assert_eq!(n, 3);
 
     // Read only one byte.
-    let mut bs = Vec::new();
-    bs.resize(1, 0);
+    let mut bs = vec![0; 1];
     let n = r.read(&mut bs).await?;
     assert_eq!("l", from_utf8(&bs).unwrap());
     assert_eq!(n, 1);
@@ -56,8 +77,7 @@ async fn test_reader() -> Result<()> {
     assert_eq!(n, 12);
 
     // Read only one byte.
-    let mut bs = Vec::new();
-    bs.resize(1, 0);
+    let mut bs = vec![0; 1];
     let n = r.read(&mut bs).await?;
     assert_eq!("!", from_utf8(&bs)?);
     assert_eq!(n, 1);
@@ -80,7 +100,7 @@ async fn test_range_reader() -> Result<()> {
         .write_bytes("Hello, world!".to_string().into_bytes())
         .await
         .unwrap();
-    assert_eq!(x, 13);
+    assert_eq!(x.content_length(), 13);
 
     let mut r = f.object(&path).range_reader(1, 10);
     let mut buf = vec![];
@@ -109,7 +129,7 @@ async fn test_offset_reader() -> Result<()> {
         .write_bytes("Hello, world!".to_string().into_bytes())
         .await
         .unwrap();
-    assert_eq!(x, 13);
+    assert_eq!(x.content_length(), 13);
 
     let mut r = f.object(&path).offset_reader(1);
     let mut buf = vec![];
@@ -138,7 +158,7 @@ async fn test_limited_reader() -> Result<()> {
         .write_bytes("Hello, world!".to_string().into_bytes())
         .await
         .unwrap();
-    assert_eq!(x, 13);
+    assert_eq!(x.content_length(), 13);
 
     let mut r = f.object(&path).limited_reader(5);
     let mut buf = vec![];
@@ -153,3 +173,487 @@ async fn test_limited_reader() -> Result<()> {
 
     Ok(())
 }
+
+#[tokio::test]
+async fn test_zero_length_reads() -> Result<()> {
+    let op = Operator::new(memory::Backend::build().finish().await?);
+    op.object("empty").writer().write_bytes(vec![]).await?;
+    op.object("file").writer().write_bytes(vec![1; 4]).await?;
+
+    // Read size 0 doesn't require the object to exist.
+    let bs = read_stream(op.object("not_exist").stream(Some(0), Some(0)).await?).await?;
+    assert!(bs.is_empty());
+
+    for (path, offset, size) in [
+        ("empty", None, None),
+        ("empty", Some(0), None),
+        ("file", Some(2), Some(0)),
+        // Read from the end of object.
+        ("file", Some(4), None),
+    ] {
+        let bs = read_stream(op.object(path).stream(offset, size).await?).await?;
+        assert!(bs.is_empty(), "{} {:?} {:?}", path, offset, size);
+    }
+
+    let mut r = op.object("empty").reader();
+    let mut buf = vec![];
+    assert_eq!(r.read_to_end(&mut buf).await?, 0);
+    assert_eq!(r.seek(SeekFrom::End(0)).await?, 0);
+
+    let mut r = op.object("file").range_reader(4, 0);
+    assert_eq!(r.read_to_end(&mut buf).await?, 0);
+
+    let mut r = op.object("file").offset_reader(4);
+    assert_eq!(r.read_to_end(&mut buf).await?, 0);
+    assert_eq!(r.seek(SeekFrom::End(0)).await?, 0);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_s3_zero_length_reads() -> Result<()> {
+    let (endpoint, requests) = mock_server(|req| {
+        match req.headers().get(http::header::RANGE) {
+            // The object is 4 bytes long.
+            Some(v) if v == "bytes=4-" => hyper::Response::builder()
+                .status(http::StatusCode::RANGE_NOT_SATISFIABLE)
+                .header(http::header::CONTENT_RANGE, "bytes */4")
+                .body(hyper::Body::empty())
+                .unwrap(),
+            Some(_) => hyper::Response::builder()
+                .status(http::StatusCode::RANGE_NOT_SATISFIABLE)
+                .header(http::header::CONTENT_RANGE, "bytes */4")
+                .body(hyper::Body::from("InvalidRange"))
+                .unwrap(),
+            // Zero-byte object.
+            None => hyper::Response::builder()
+                .header(http::header::CONTENT_LENGTH, "0")
+                .body(hyper::Body::empty())
+                .unwrap(),
+        }
+    });
+    let op = mock_s3_operator(&endpoint).await;
+
+    // Read size 0 will not send any request.
+    let bs = read_stream(op.object("file").stream(Some(2), Some(0)).await?).await?;
+    assert!(bs.is_empty());
+    let mut r = op.object("file").range_reader(2, 0);
+    let mut buf = vec![];
+    assert_eq!(r.read_to_end(&mut buf).await?, 0);
+    assert_eq!(requests.lock().unwrap().len(), 0);
+
+    // Zero-byte object.
+    let bs = read_stream(op.object("empty").stream(None, None).await?).await?;
+    assert!(bs.is_empty());
+
+    // Read from the end of object.
+    let bs = read_stream(op.object("file").stream(Some(4), None).await?).await?;
+    assert!(bs.is_empty());
+
+    // Read beyond the end of object is still an error.
+    assert!(op.object("file").stream(Some(5), None).await.is_err());
+
+    assert_eq!(requests.lock().unwrap().len(), 3);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_read_interrupted() -> Result<()> {
+    for reset in [false, true] {
+        let endpoint = mock_broken_server(1024, 100, reset);
+        let op = mock_s3_operator(&endpoint).await;
+
+        // Errors from the body carry the offset of the object reached so far.
+        let mut s = op.object("file").stream(Some(10), Some(1024)).await?;
+        let mut read = 0;
+        let err = loop {
+            match s.next().await {
+                Some(Ok(bs)) => read += bs.len(),
+                Some(Err(e)) => break e,
+                None => panic!("stream must fail"),
+            }
+        };
+        assert_eq!(read, 100, "reset: {}", reset);
+        assert_eq!(err.kind(), Kind::ObjectReadInterrupted, "reset: {}", reset);
+        assert_eq!(err.context()["offset"], "110", "reset: {}", reset);
+
+        // Reader keeps the error as the source of io error.
+        let mut bs = Vec::new();
+        let err = op
+            .object("file")
+            .reader()
+            .read_to_end(&mut bs)
+            .await
+            .unwrap_err();
+        let err = err.into_inner().unwrap().downcast::<Error>().unwrap();
+        assert_eq!(err.kind(), Kind::ObjectReadInterrupted, "reset: {}", reset);
+        assert_eq!(err.context()["offset"], "100", "reset: {}", reset);
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_conditional_write() -> Result<()> {
+    let op = Operator::new(memory::Backend::build().finish().await?);
+    assert!(op.metadata().conditional_write());
+    let w = || op.object("file").writer();
+
+    let err = w()
+        .if_match("\"x\"")
+        .write_bytes(vec![0])
+        .await
+        .unwrap_err();
+    assert_eq!(err.kind(), Kind::ObjectNotExist);
+    let etag = w().if_none_match("*").write_bytes(vec![0]).await?.etag();
+    assert_eq!(etag, op.object("file").metadata().await?.etag());
+    let etag = etag.unwrap();
+
+    let err = w()
+        .if_none_match("*")
+        .write_bytes(vec![1])
+        .await
+        .unwrap_err();
+    assert_eq!(err.kind(), Kind::ObjectPreconditionFailed);
+    let err = w()
+        .if_match("\"x\"")
+        .write_bytes(vec![1])
+        .await
+        .unwrap_err();
+    assert_eq!(err.kind(), Kind::ObjectPreconditionFailed);
+    let err = w()
+        .if_none_match(&etag)
+        .write_bytes(vec![1])
+        .await
+        .unwrap_err();
+    assert_eq!(err.kind(), Kind::ObjectPreconditionFailed);
+    let new_etag = w()
+        .if_match(&etag)
+        .write_bytes(vec![1])
+        .await?
+        .etag()
+        .unwrap();
+    assert_ne!(new_etag, etag);
+
+    // Conditional reads are supported as well.
+    let mut bs = Vec::new();
+    let err = op
+        .object("file")
+        .reader()
+        .if_none_match(&new_etag)
+        .read_to_end(&mut bs)
+        .await
+        .unwrap_err();
+    let err = err.into_inner().unwrap().downcast::<Error>().unwrap();
+    assert_eq!(err.kind(), Kind::ObjectNotModified);
+
+    let acc = op.inner();
+    let delete = |etag: &str| OpDelete {
+        path: "file".to_string(),
+        if_match: Some(etag.to_string()),
+        ..Default::default()
+    };
+    let err = acc.delete(&delete(&etag)).await.unwrap_err();
+    assert_eq!(err.kind(), Kind::ObjectPreconditionFailed);
+    acc.delete(&delete(&new_etag)).await?;
+    assert!(!op.object("file").is_exist().await?);
+
+    // Backends without support reject conditional writes.
+    let f = Operator::new(fs::Backend::build().finish().await?);
+    assert!(!f.metadata().conditional_write());
+    let path = format!("/tmp/{}", uuid::Uuid::new_v4());
+    let err = f
+        .object(&path)
+        .writer()
+        .if_none_match("*")
+        .write_bytes(vec![0])
+        .await
+        .unwrap_err();
+    assert_eq!(err.kind(), Kind::Unsupported);
+    assert!(!f.object(&path).is_exist().await?);
+
+    Ok(())
+}
+
+async fn read_stream(mut s: BytesStream) -> Result<Vec<u8>> {
+    let mut bs = vec![];
+    while let Some(b) = s.next().await {
+        bs.extend_from_slice(&b?);
+    }
+    Ok(bs)
+}
+
+#[tokio::test]
+async fn test_write_stream() -> Result<()> {
+    let op = Operator::new(memory::Backend::build().finish().await?);
+
+    let r = Box::new(futures::io::Cursor::new(vec![1; 1024]));
+    let meta = op.object("file").writer().write_stream(r).await?;
+    assert_eq!(meta.content_length(), 1024);
+    assert_eq!(op.object("file").metadata().await?.content_length(), 1024);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_async_writer() -> Result<()> {
+    let op = Operator::new(memory::Backend::build().finish().await?);
+
+    let mut w = op.object("file").writer().buffer_size(4);
+    w.write_all(b"Hello, ").await?;
+    w.flush().await?;
+    w.write_all(b"World!").await?;
+    // Nothing is committed before closing.
+    assert!(!op.object("file").is_exist().await?);
+    let meta = w.close().await?;
+    assert_eq!(meta.content_length(), 13);
+    assert_eq!(w.close().await?.content_length(), 13);
+    assert!(w.write_all(b"more").await.is_err());
+
+    let mut bs = vec![];
+    op.object("file").reader().read_to_end(&mut bs).await?;
+    assert_eq!(bs, b"Hello, World!");
+
+    // Closing without writing creates an empty object.
+    let mut w = op.object("empty").writer();
+    w.close().await?;
+    assert_eq!(op.object("empty").metadata().await?.content_length(), 0);
+
+    // Dropping without closing aborts the write.
+    let mut w = op.object("dropped").writer().buffer_size(4);
+    w.write_all(b"partial data").await?;
+    drop(w);
+    assert!(!op.object("dropped").is_exist().await?);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_async_writer_fs() -> Result<()> {
+    let root = format!("/tmp/opendal-test-{}", uuid::Uuid::new_v4());
+    let op = Operator::new(fs::Backend::build().root(&root).finish().await?);
+
+    op.object("file").writer().write_bytes(vec![1; 64]).await?;
+
+    // Dropping without closing keeps the existing content.
+    let mut w = op.object("file").writer().buffer_size(4);
+    w.write_all(&[2; 16]).await?;
+    w.flush().await?;
+    drop(w);
+    assert_eq!(std::fs::read(format!("{}/file", root))?, vec![1; 64]);
+
+    // Shorter content replaces the existing one as a whole.
+    let mut w = op.object("file").writer().buffer_size(4);
+    w.write_all(&[3; 16]).await?;
+    w.close().await?;
+    assert_eq!(std::fs::read(format!("{}/file", root))?, vec![3; 16]);
+
+    // Temp files of dropped writes are removed in background.
+    for _ in 0..100 {
+        if std::fs::read_dir(&root)?.count() == 1 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    assert_eq!(std::fs::read_dir(&root)?.count(), 1);
+
+    // Temp files of writes in progress are not objects.
+    std::fs::create_dir(format!("{}/dir", root))?;
+    std::fs::write(format!("{}/dir/file.0.opendal-tmp", root), b"partial")?;
+    let mut paths = vec![];
+    let mut obs = op.objects("dir/");
+    while let Some(o) = obs.next().await {
+        paths.push(o?.metadata_mut().path().to_string());
+    }
+    let mut obs = op.scan("").await?;
+    while let Some(o) = obs.next().await {
+        paths.push(o?.metadata_mut().path().to_string());
+    }
+    assert_eq!(paths, vec!["file"]);
+
+    std::fs::remove_dir_all(&root)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_async_writer_shutdown_guard() -> Result<()> {
+    let op = Operator::new(memory::Backend::build().finish().await?);
+
+    let mut w = op.object("file").writer();
+    w.write_all(b"Hello").await?;
+    let mut guard = ShutdownGuard::new();
+    guard.register("writer", w);
+    assert!(!op.object("file").is_exist().await?);
+
+    // Writers are committed while closing the guard.
+    guard.close().await?;
+    assert_eq!(op.object("file").metadata().await?.content_length(), 5);
+
+    Ok(())
+}
+
+/// Accessor counting the reads and stats sent to the inner accessor.
+#[derive(Debug)]
+struct Counting {
+    inner: Arc<dyn Accessor>,
+    reads: AtomicUsize,
+    stats: AtomicUsize,
+}
+
+impl Counting {
+    async fn new() -> Result<Arc<Self>> {
+        Ok(Arc::new(Self {
+            inner: memory::Backend::build().finish().await?,
+            reads: AtomicUsize::new(0),
+            stats: AtomicUsize::new(0),
+        }))
+    }
+
+    fn counts(&self) -> (usize, usize) {
+        (
+            self.reads.load(Ordering::SeqCst),
+            self.stats.load(Ordering::SeqCst),
+        )
+    }
+}
+
+#[async_trait]
+impl Accessor for Counting {
+    async fn read(&self, args: &OpRead) -> crate::error::Result<BytesStream> {
+        self.reads.fetch_add(1, Ordering::SeqCst);
+        self.inner.read(args).await
+    }
+    async fn write(
+        &self,
+        r: crate::BoxedAsyncReader,
+        args: &crate::ops::OpWrite,
+    ) -> crate::error::Result<Metadata> {
+        self.inner.write(r, args).await
+    }
+    async fn stat(&self, args: &OpStat) -> crate::error::Result<Metadata> {
+        self.stats.fetch_add(1, Ordering::SeqCst);
+        self.inner.stat(args).await
+    }
+}
+
+#[tokio::test]
+async fn test_seekable_reader_like_file() -> Result<()> {
+    let content: Vec<u8> = (0..1000).map(|v| (v % 251) as u8).collect();
+    let op = Operator::new(memory::Backend::build().finish().await?);
+    op.object("file")
+        .writer()
+        .write_bytes(content.clone())
+        .await?;
+    let path = format!("/tmp/opendal-test-{}", uuid::Uuid::new_v4());
+    tokio::fs::write(&path, &content).await?;
+
+    let mut r = op.object("file").seekable_reader().buffer_size(64);
+    let mut f = tokio::fs::File::open(&path).await?;
+    let cases = [
+        (SeekFrom::End(-10), 4),
+        (SeekFrom::Current(-100), 200),
+        (SeekFrom::Start(500), 100),
+        (SeekFrom::Current(0), 1),
+        (SeekFrom::Start(990), 100),
+        (SeekFrom::Start(2000), 10),
+        (SeekFrom::End(5), 10),
+        (SeekFrom::End(0), 10),
+        (SeekFrom::Start(0), 1000),
+        (SeekFrom::Current(-2000), 10),
+        (SeekFrom::End(-2000), 10),
+        (SeekFrom::Start(7), 3),
+    ];
+    for (seek, size) in cases {
+        let expected = match tokio::io::AsyncSeekExt::seek(&mut f, seek).await {
+            Ok(pos) => {
+                let mut buf = Vec::new();
+                let mut take = tokio::io::AsyncReadExt::take(&mut f, size);
+                tokio::io::AsyncReadExt::read_to_end(&mut take, &mut buf).await?;
+                Ok((pos, buf))
+            }
+            Err(e) => Err(e.kind()),
+        };
+        let actual = match r.seek(seek).await {
+            Ok(pos) => {
+                let mut buf = Vec::new();
+                (&mut r).take(size).read_to_end(&mut buf).await?;
+                Ok((pos, buf))
+            }
+            Err(e) => Err(e.kind()),
+        };
+        assert_eq!(actual, expected, "{:?}", seek);
+    }
+    tokio::fs::remove_file(&path).await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_seekable_reader_requests() -> Result<()> {
+    let acc = Counting::new().await?;
+    let op = Operator::new(acc.clone());
+    let content: Vec<u8> = (0..256 * 1024).map(|v| (v % 251) as u8).collect();
+    op.object("file")
+        .writer()
+        .write_bytes(content.clone())
+        .await?;
+
+    // Small reads are served by the read-ahead buffer.
+    let mut r = op.object("file").seekable_reader();
+    let mut bs = Vec::new();
+    let mut buf = vec![0; 8 * 1024];
+    loop {
+        let n = r.read(&mut buf).await?;
+        if n == 0 {
+            break;
+        }
+        bs.extend_from_slice(&buf[..n]);
+    }
+    assert_eq!(bs, content);
+    assert_eq!(acc.counts(), (4, 1));
+
+    // Seeking within the buffer sends nothing.
+    r.seek(SeekFrom::End(-100)).await?;
+    r.read_exact(&mut buf[..10]).await?;
+    r.seek(SeekFrom::Current(-50)).await?;
+    r.read_exact(&mut buf[..10]).await?;
+    assert_eq!(acc.counts(), (4, 1));
+
+    // Reads larger than the buffer are sent at once.
+    let mut r = SeekableReader::new(acc.clone(), "file").content_length(content.len() as u64);
+    let mut bs = vec![0; 200 * 1024];
+    r.read_exact(&mut bs).await?;
+    assert_eq!(bs, content[..200 * 1024]);
+    assert_eq!(acc.counts(), (5, 1));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_seekable_reader_replaced() -> Result<()> {
+    let op = Operator::new(memory::Backend::build().finish().await?);
+    op.object("file").writer().write_bytes(vec![0; 100]).await?;
+
+    let mut r = op.object("file").seekable_reader().buffer_size(10);
+    let mut buf = vec![0; 10];
+    r.read_exact(&mut buf).await?;
+
+    // Reads after the object has been replaced fail instead of mixing contents.
+    op.object("file").writer().write_bytes(vec![1; 100]).await?;
+    let err = r.read_exact(&mut buf).await.unwrap_err();
+    let err = err.into_inner().unwrap().downcast::<Error>().unwrap();
+    assert_eq!(err.kind(), Kind::ObjectPreconditionFailed);
+
+    // The etag of listed objects is reused without stat.
+    let mut o = op.objects("").next().await.unwrap()?;
+    assert!(o.metadata_cached().await?.complete());
+    let mut r = o.seekable_reader();
+    op.object("file").writer().write_bytes(vec![2; 100]).await?;
+    r.seek(SeekFrom::End(-10)).await?;
+    let err = r.read_exact(&mut buf).await.unwrap_err();
+    let err = err.into_inner().unwrap().downcast::<Error>().unwrap();
+    assert_eq!(err.kind(), Kind::ObjectPreconditionFailed);
+
+    Ok(())
+}
@@ -12,15 +12,55 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
+use std::time::Duration;
+use std::time::Instant;
 
+use futures::future::join;
+use futures::future::join_all;
 use futures::lock::Mutex;
+use futures::AsyncReadExt;
+use futures::StreamExt;
 
+use crate::credential::Credential;
+use crate::error::Kind;
 use crate::error::Result;
+use crate::io::BytesStream;
+use crate::layers::qos::QosLayer;
+use crate::layers::CacheLayer;
+use crate::layers::CacheRange;
+use crate::layers::ConcurrencyLimitLayer;
+use crate::layers::CostLayer;
+use crate::layers::ExponentialBackoff;
+use crate::layers::ImmutableLayer;
+use crate::layers::MetricsLayer;
+use crate::layers::PriceTable;
+use crate::layers::RequestIdLayer;
+use crate::layers::RetryLayer;
+use crate::layers::ScopeGuardLayer;
+use crate::layers::SubdirLayer;
+use crate::layers::ThrottleLayer;
+use crate::layers::TimeoutLayer;
+use crate::layers::Usage;
 use crate::ops::OpDelete;
+use crate::ops::OpList;
+use crate::ops::OpPriority;
+use crate::ops::OpRead;
+use crate::ops::OpStat;
+use crate::ops::OpWrite;
 use crate::services::fs;
+use crate::services::memory;
+use crate::services::s3;
+use crate::tests::mock::mock_s3_operator;
+use crate::tests::mock::mock_server;
 use crate::Accessor;
+use crate::BoxedAsyncReader;
+use crate::BoxedObjectStream;
+use crate::Close;
 use crate::Layer;
+use crate::Metadata;
 use crate::Operator;
 
 #[derive(Debug)]
@@ -65,3 +105,977 @@ async fn test_layer() {
 
     assert!(*test.deleted.clone().lock().await);
 }
+
+#[tokio::test]
+async fn test_layer_metadata() -> Result<()> {
+    fn assert_send_sync<T: Send + Sync>(_: &T) {}
+
+    let acc = memory::Backend::build().finish().await?;
+    let op = Operator::new(acc.clone())
+        .layer(RequestIdLayer)
+        .layer(TimeoutLayer::new(Duration::from_secs(1)));
+    assert_send_sync(&op.inner());
+
+    // Layers keep the metadata of the inner backend.
+    let (inner, layered) = (acc.metadata(), op.metadata());
+    assert_eq!(layered.scheme(), inner.scheme());
+    assert_eq!(layered.root(), inner.root());
+    assert_eq!(layered.list(), inner.list());
+    assert_eq!(layered.copy(), inner.copy());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_immutable_layer() -> Result<()> {
+    let acc = memory::Backend::build().finish().await?;
+    let op = Operator::new(acc.clone());
+    op.object("file").writer().write_bytes(vec![0; 4]).await?;
+
+    let op = Operator::new(acc).layer(ImmutableLayer);
+    assert_eq!(op.object("file").metadata().await?.content_length(), 4);
+
+    let err = op
+        .object("file")
+        .writer()
+        .write_bytes(vec![1; 4])
+        .await
+        .unwrap_err();
+    assert_eq!(err.kind(), Kind::ObjectPermissionDenied);
+
+    let err = op.object("file").delete().await.unwrap_err();
+    assert_eq!(err.kind(), Kind::ObjectPermissionDenied);
+    assert!(op.object("file").is_exist().await?);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_immutable_layer_list() -> Result<()> {
+    let acc = memory::Backend::build().finish().await?;
+    let op = Operator::new(acc.clone());
+    op.object("dir/file")
+        .writer()
+        .write_bytes(vec![0; 4])
+        .await?;
+
+    let op = Operator::new(acc).layer(ImmutableLayer);
+    let o = op
+        .objects("dir/")
+        .next()
+        .await
+        .expect("listed object must exist")?;
+
+    let err = o.delete().await.unwrap_err();
+    assert_eq!(err.kind(), Kind::ObjectPermissionDenied);
+    assert!(o.is_exist().await?);
+
+    Ok(())
+}
+
+/// Misroute is a misbehaving layer that rewrites every path it sees.
+#[derive(Debug, Clone, Copy)]
+struct Misroute(fn(&str) -> String);
+
+impl Layer for Misroute {
+    fn layer(&self, inner: Arc<dyn Accessor>) -> Arc<dyn Accessor> {
+        Arc::new(MisrouteAccessor { inner, f: self.0 })
+    }
+}
+
+#[derive(Debug)]
+struct MisrouteAccessor {
+    inner: Arc<dyn Accessor>,
+    f: fn(&str) -> String,
+}
+
+#[async_trait::async_trait]
+impl Accessor for MisrouteAccessor {
+    async fn read(&self, args: &OpRead) -> Result<BytesStream> {
+        let mut args = args.clone();
+        args.path = (self.f)(&args.path);
+        self.inner.read(&args).await
+    }
+    async fn write(&self, r: BoxedAsyncReader, args: &OpWrite) -> Result<Metadata> {
+        let mut args = args.clone();
+        args.path = (self.f)(&args.path);
+        self.inner.write(r, &args).await
+    }
+    async fn stat(&self, args: &OpStat) -> Result<Metadata> {
+        self.inner.stat(&OpStat::new(&(self.f)(&args.path))).await
+    }
+    async fn delete(&self, args: &OpDelete) -> Result<()> {
+        self.inner
+            .delete(&OpDelete::new(&(self.f)(&args.path)))
+            .await
+    }
+    async fn list(&self, args: &OpList) -> Result<BoxedObjectStream> {
+        let mut args = args.clone();
+        args.path = (self.f)(&args.path);
+        self.inner.list(&args).await
+    }
+}
+
+#[tokio::test]
+async fn test_scope_guard_layer() -> Result<()> {
+    let acc = memory::Backend::build().finish().await?;
+    let op = Operator::new(acc.clone());
+    op.object("tenant-a/file")
+        .writer()
+        .write_bytes(vec![0; 4])
+        .await?;
+    op.object("tenant-b/file")
+        .writer()
+        .write_bytes(vec![1; 4])
+        .await?;
+
+    // Well-behaved operator works as usual.
+    let op = Operator::new(acc.clone())
+        .layer(ScopeGuardLayer::new("/tenant-a/"))
+        .layer(SubdirLayer::new("tenant-a"));
+    assert_eq!(op.object("file").metadata().await?.content_length(), 4);
+    let (_, meta) = op.object("file").read_with_metadata(Some(1), None).await?;
+    assert_eq!(meta.path(), "file");
+    assert_eq!(meta.content_length(), 4);
+    let mut obs = op.objects("");
+    let mut paths = vec![];
+    while let Some(o) = obs.next().await {
+        paths.push(o?.metadata().await?.path().to_string());
+    }
+    assert_eq!(paths, vec!["file".to_string()]);
+
+    // Relative segments spliced above the subdir.
+    let op = Operator::new(acc.clone())
+        .layer(ScopeGuardLayer::new("tenant-a"))
+        .layer(SubdirLayer::new("tenant-a"))
+        .layer(Misroute(|p| format!("../tenant-b/{}", p)));
+    let err = op.object("file").metadata().await.unwrap_err();
+    assert_eq!(err.kind(), Kind::ObjectOutOfScope);
+    let err = op
+        .object("file")
+        .writer()
+        .write_bytes(vec![2; 4])
+        .await
+        .unwrap_err();
+    assert_eq!(err.kind(), Kind::ObjectOutOfScope);
+
+    // Prefix replaced below the subdir.
+    let op = Operator::new(acc.clone())
+        .layer(ScopeGuardLayer::new("tenant-a"))
+        .layer(Misroute(|p| p.replacen("tenant-a/", "tenant-b/", 1)))
+        .layer(SubdirLayer::new("tenant-a"));
+    let err = op.object("file").delete().await.unwrap_err();
+    assert_eq!(err.kind(), Kind::ObjectOutOfScope);
+    let mut buf = vec![];
+    let err = op
+        .object("file")
+        .reader()
+        .read_to_end(&mut buf)
+        .await
+        .unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::Other);
+
+    // Keys returned by list are checked too.
+    let op = Operator::new(acc.clone())
+        .layer(Misroute(|p| p.replacen("tenant-a/", "tenant-b/", 1)))
+        .layer(ScopeGuardLayer::new("tenant-a"))
+        .layer(SubdirLayer::new("tenant-a"));
+    let mut obs = op.objects("");
+    let err = obs.next().await.expect("must have entry").unwrap_err();
+    assert_eq!(err.kind(), Kind::ObjectOutOfScope);
+
+    // Nothing has been touched.
+    let op = Operator::new(acc);
+    assert!(op.object("tenant-a/file").is_exist().await?);
+    assert!(op.object("tenant-b/file").is_exist().await?);
+    assert!(!op.object("file").is_exist().await?);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cost_layer() -> Result<()> {
+    let (endpoint, requests) = mock_server(|req| {
+        let query = req.uri().query().unwrap_or_default();
+        let resp = hyper::Response::builder();
+        match (req.method().as_str(), req.uri().path(), query) {
+            ("HEAD", "/test", _) => resp
+                .header("x-amz-bucket-region", "us-east-1")
+                .body(hyper::Body::empty()),
+            ("HEAD", _, _) => resp
+                .header(http::header::CONTENT_LENGTH, 5)
+                .body(hyper::Body::empty()),
+            ("GET", _, q) if q.contains("continuation-token=t1") => resp.body(hyper::Body::from(
+                "<ListBucketResult><IsTruncated>false</IsTruncated>\
+                 <Contents><Key>b</Key><Size>1</Size></Contents></ListBucketResult>",
+            )),
+            ("GET", _, q) if q.contains("list-type=2") => resp.body(hyper::Body::from(
+                "<ListBucketResult><IsTruncated>true</IsTruncated>\
+                 <NextContinuationToken>t1</NextContinuationToken>\
+                 <Contents><Key>a</Key><Size>1</Size></Contents></ListBucketResult>",
+            )),
+            ("GET", _, _) => resp.body(hyper::Body::from("hello")),
+            ("POST", _, "uploads") => resp.body(hyper::Body::from(
+                "<InitiateMultipartUploadResult><Bucket>test</Bucket><Key>large</Key>\
+                 <UploadId>upload-id</UploadId></InitiateMultipartUploadResult>",
+            )),
+            ("PUT", _, q) if q.contains("partNumber") => resp
+                .header(http::header::ETAG, "\"etag\"")
+                .body(hyper::Body::empty()),
+            ("DELETE", _, _) => resp
+                .status(http::StatusCode::NO_CONTENT)
+                .body(hyper::Body::empty()),
+            _ => resp.body(hyper::Body::empty()),
+        }
+        .unwrap()
+    });
+
+    let part_size = 5 * 1024 * 1024;
+    let cost = CostLayer::new(PriceTable::default());
+    // Region will be detected while building.
+    let acc = cost
+        .instrument("build", async {
+            let mut builder = s3::Backend::build();
+            builder
+                .bucket("test")
+                .endpoint(&endpoint)
+                .multipart_threshold(part_size)
+                .multipart_part_size(part_size)
+                .credential(Credential::hmac("access_key_id", "secret_access_key"));
+            builder.finish().await
+        })
+        .await?;
+    let op = Operator::new(acc).layer(cost.clone());
+
+    op.object("small")
+        .writer()
+        .write_bytes(b"abc".to_vec())
+        .await?;
+    // Initiation, three parts and completion.
+    let size = 2 * part_size as usize + 1;
+    op.object("large")
+        .writer()
+        .write_bytes(vec![0; size])
+        .await?;
+    let mut buf = vec![];
+    op.object("small")
+        .reader()
+        .read_to_end(&mut buf)
+        .await
+        .unwrap();
+    assert_eq!(buf, b"hello");
+    op.object("small").metadata().await?;
+    let mut obs = op.objects("");
+    let mut paths = vec![];
+    while let Some(o) = obs.next().await {
+        paths.push(o?.metadata_mut().path().to_string());
+    }
+    assert_eq!(paths, vec!["a", "b"]);
+    op.object("small").delete().await?;
+
+    let report = cost.cost_report();
+    let usage = |class_a, class_b, free, bytes_read, bytes_written| Usage {
+        class_a,
+        class_b,
+        free,
+        bytes_read,
+        bytes_written,
+    };
+    assert_eq!(
+        report.operations.into_iter().collect::<Vec<_>>(),
+        vec![
+            ("build".to_string(), usage(0, 1, 0, 0, 0)),
+            ("delete".to_string(), usage(0, 0, 1, 0, 0)),
+            ("list".to_string(), usage(2, 0, 0, 0, 0)),
+            ("read".to_string(), usage(0, 1, 0, 5, 0)),
+            ("stat".to_string(), usage(0, 1, 0, 0, 0)),
+            ("write".to_string(), usage(6, 0, 0, 0, 3 + size as u64)),
+        ]
+    );
+    assert_eq!(report.total, usage(8, 3, 1, 5, 3 + size as u64));
+    assert_eq!(requests.lock().unwrap().len(), 12);
+    // 8 * $0.005 / 1000 + 3 * $0.0004 / 1000, bytes read are rounded down.
+    assert_eq!(report.cost, 41_200);
+
+    let prices = PriceTable {
+        class_a_per_1k: 1000,
+        class_b_per_1k: 2000,
+        egress_per_gib: 1 << 30,
+        ingress_per_gib: 3 << 30,
+    };
+    assert_eq!(
+        prices.cost(&report.total),
+        8 + 3 * 2 + 5 + 3 * (3 + size as u64)
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cost_layer_local() -> Result<()> {
+    let cost = CostLayer::new(PriceTable::default());
+    let op = Operator::new(memory::Backend::build().finish().await?).layer(cost.clone());
+
+    op.object("file")
+        .writer()
+        .write_bytes(vec![0; 1024])
+        .await?;
+    let mut buf = vec![];
+    op.object("file")
+        .reader()
+        .read_to_end(&mut buf)
+        .await
+        .unwrap();
+    op.object("file").delete().await?;
+
+    let report = cost.cost_report();
+    assert_eq!(
+        report.total,
+        Usage {
+            bytes_read: 1024,
+            bytes_written: 1024,
+            ..Default::default()
+        }
+    );
+    // No requests are sent, 1 KiB egress costs $0.09 / 1024 / 1024.
+    assert_eq!(report.cost, 85);
+
+    Ok(())
+}
+
+/// Slow is a backend whose every stat takes `delay`, and records the paths
+/// in the order of finishing.
+#[derive(Debug)]
+struct Slow {
+    delay: Duration,
+    finished: Arc<std::sync::Mutex<Vec<String>>>,
+}
+
+#[async_trait::async_trait]
+impl Accessor for Slow {
+    async fn stat(&self, args: &OpStat) -> Result<Metadata> {
+        tokio::time::sleep(self.delay).await;
+        self.finished.lock().unwrap().push(args.path.clone());
+
+        let mut meta = Metadata::default();
+        meta.set_path(&args.path);
+        Ok(meta)
+    }
+}
+
+async fn stat_with_priority(acc: &Arc<dyn Accessor>, path: &str, priority: OpPriority) {
+    let mut op = OpStat::new(path);
+    op.priority = priority;
+    acc.stat(&op).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_qos_layer_order() {
+    let cases = vec![
+        (
+            "strict",
+            QosLayer::new(1),
+            vec!["n0", "h1", "h2", "h3", "h4", "n1", "l1"],
+        ),
+        (
+            "weighted",
+            QosLayer::new(1).weights(2, 1, 1),
+            vec!["n0", "h1", "n1", "l1", "h2", "h3", "h4"],
+        ),
+    ];
+
+    for (name, layer, expected) in cases {
+        let finished = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let acc = layer.layer(Arc::new(Slow {
+            delay: Duration::from_millis(5),
+            finished: finished.clone(),
+        }));
+
+        // `n0` is admitted at once, others are queued in this order.
+        let ops = [
+            ("n0", OpPriority::Normal),
+            ("l1", OpPriority::Low),
+            ("h1", OpPriority::High),
+            ("n1", OpPriority::Normal),
+            ("h2", OpPriority::High),
+            ("h3", OpPriority::High),
+            ("h4", OpPriority::High),
+        ];
+        join_all(
+            ops.iter()
+                .map(|(path, priority)| stat_with_priority(&acc, path, *priority)),
+        )
+        .await;
+
+        assert_eq!(*finished.lock().unwrap(), expected, "{}", name);
+    }
+}
+
+#[tokio::test]
+async fn test_qos_layer_load() {
+    let delay = Duration::from_millis(20);
+    let acc = QosLayer::new(2).weights(8, 4, 1).layer(Arc::new(Slow {
+        delay,
+        finished: Arc::new(std::sync::Mutex::new(Vec::new())),
+    }));
+
+    // A backlog of low priority operations, which takes 400ms alone.
+    let low = (0..40).map(|i| {
+        let acc = acc.clone();
+        async move {
+            stat_with_priority(&acc, &format!("low-{}", i), OpPriority::Low).await;
+            Instant::now()
+        }
+    });
+    // High priority operations arriving every 20ms.
+    let high = (0..10).map(|i| {
+        let acc = acc.clone();
+        async move {
+            tokio::time::sleep(delay * (i + 1)).await;
+            let start = Instant::now();
+            stat_with_priority(&acc, &format!("high-{}", i), OpPriority::High).await;
+            (start.elapsed(), Instant::now())
+        }
+    });
+    let (low, high) = join(join_all(low), join_all(high)).await;
+
+    // High priority operations only wait for running ones.
+    let mut latencies = high.iter().map(|(v, _)| *v).collect::<Vec<_>>();
+    latencies.sort();
+    let p99 = latencies[(latencies.len() * 99).div_ceil(100) - 1];
+    assert!(p99 < delay * 4, "p99 of high priority: {:?}", p99);
+
+    // Low priority operations still progress meanwhile.
+    let high_end = high.iter().map(|(_, v)| *v).max().unwrap();
+    assert!(low.iter().filter(|v| **v < high_end).count() > 10);
+}
+
+#[tokio::test]
+async fn test_qos_layer_permit() -> Result<()> {
+    let acc = QosLayer::new(1).layer(memory::Backend::build().finish().await?);
+    Operator::new(acc.clone())
+        .object("file")
+        .writer()
+        .write_bytes(vec![0; 4])
+        .await?;
+
+    // The permit is held by the stream.
+    let s = acc
+        .read(&OpRead {
+            path: "file".to_string(),
+            ..Default::default()
+        })
+        .await?;
+    let stat =
+        tokio::time::timeout(Duration::from_millis(50), acc.stat(&OpStat::new("file"))).await;
+    assert!(stat.is_err(), "stat must wait for the stream");
+
+    // The cancelled stat doesn't take the permit away.
+    drop(s);
+    assert_eq!(acc.stat(&OpStat::new("file")).await?.content_length(), 4);
+
+    // Exhausted streams release the permit too.
+    let mut s = acc
+        .read(&OpRead {
+            path: "file".to_string(),
+            ..Default::default()
+        })
+        .await?;
+    while s.next().await.is_some() {}
+    assert_eq!(acc.stat(&OpStat::new("file")).await?.content_length(), 4);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_qos_layer_nested() -> Result<()> {
+    let op = Operator::new(memory::Backend::build().finish().await?).layer(QosLayer::new(1));
+    op.object("a").writer().write_bytes(vec![0; 4]).await?;
+    op.object("b").writer().write_bytes(vec![1; 4]).await?;
+
+    let nested = async {
+        // Listed objects could be stat-ed while listing.
+        let mut sizes = vec![];
+        let mut obs = op.objects("");
+        while let Some(o) = obs.next().await {
+            sizes.push(o?.metadata().await?.content_length());
+        }
+        assert_eq!(sizes, vec![4, 4]);
+
+        // A reader could be piped into a writer.
+        let r = op.object("a").reader();
+        op.object("c").writer().write_reader(Box::new(r), 4).await?;
+        op.object("c").read().await
+    };
+    let bs = tokio::time::timeout(Duration::from_secs(5), nested)
+        .await
+        .expect("nested operations must not deadlock")?;
+    assert_eq!(bs, vec![0; 4]);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_retry_layer() -> Result<()> {
+    let heads = Arc::new(AtomicUsize::new(0));
+    let puts = Arc::new(AtomicUsize::new(0));
+    let (hc, pc) = (heads.clone(), puts.clone());
+    let (endpoint, requests) = mock_server(move |req| {
+        let resp = hyper::Response::builder();
+        match (req.method().as_str(), req.uri().path()) {
+            ("HEAD", "/test/flaky") if hc.fetch_add(1, Ordering::SeqCst) < 2 => resp
+                .status(http::StatusCode::SERVICE_UNAVAILABLE)
+                .body(hyper::Body::empty()),
+            ("HEAD", "/test/down") => resp
+                .status(http::StatusCode::SERVICE_UNAVAILABLE)
+                .body(hyper::Body::empty()),
+            ("HEAD", "/test/forbidden") => resp
+                .status(http::StatusCode::FORBIDDEN)
+                .body(hyper::Body::empty()),
+            ("HEAD", _) => resp
+                .header(http::header::CONTENT_LENGTH, 5)
+                .body(hyper::Body::empty()),
+            ("PUT", _) if pc.fetch_add(1, Ordering::SeqCst) < 1 => resp
+                .status(http::StatusCode::INTERNAL_SERVER_ERROR)
+                .body(hyper::Body::from(
+                    "<Error><Code>InternalError</Code></Error>",
+                )),
+            _ => resp.body(hyper::Body::empty()),
+        }
+        .unwrap()
+    });
+    let backoff = ExponentialBackoff::default()
+        .min_delay(Duration::from_millis(1))
+        .max_times(3)
+        .jitter(false);
+    let op = mock_s3_operator(&endpoint)
+        .await
+        .layer(RetryLayer::with_backoff(backoff));
+    let count = |path: &str| {
+        requests
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|v| v.uri().path() == path)
+            .count()
+    };
+
+    // Transient failures are retried.
+    assert_eq!(op.object("flaky").metadata().await?.content_length(), 5);
+    assert_eq!(count("/test/flaky"), 3);
+
+    // Retrying stops after max times.
+    let err = op.object("down").metadata().await.unwrap_err();
+    assert_eq!(err.kind(), Kind::ServiceUnavailable);
+    assert_eq!(err.context()["retries"], "3");
+    assert_eq!(count("/test/down"), 4);
+
+    // Other errors are returned at once.
+    let err = op.object("forbidden").metadata().await.unwrap_err();
+    assert_eq!(err.kind(), Kind::ObjectPermissionDenied);
+    assert!(!err.context().contains_key("retries"));
+    assert_eq!(count("/test/forbidden"), 1);
+
+    // Writes are buffered and sent again.
+    op.object("put")
+        .writer()
+        .write_bytes(b"hello".to_vec())
+        .await?;
+    let bodies: Vec<_> = requests
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|v| v.uri().path() == "/test/put")
+        .map(|v| v.body().clone())
+        .collect();
+    assert_eq!(bodies, vec!["hello", "hello"]);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_retry_layer_max_elapsed() -> Result<()> {
+    let (endpoint, requests) = mock_server(|_| {
+        hyper::Response::builder()
+            .status(http::StatusCode::SERVICE_UNAVAILABLE)
+            .body(hyper::Body::empty())
+            .unwrap()
+    });
+    let backoff = ExponentialBackoff::default()
+        .min_delay(Duration::from_millis(40))
+        .max_times(10)
+        .jitter(false);
+    let op = mock_s3_operator(&endpoint)
+        .await
+        .layer(RetryLayer::with_backoff(backoff).max_elapsed(Duration::from_millis(100)));
+
+    // Delays are 40ms and 80ms, the second one exceeds the max elapsed.
+    let err = op.object("down").metadata().await.unwrap_err();
+    assert_eq!(err.kind(), Kind::ServiceUnavailable);
+    assert_eq!(err.context()["retries"], "1");
+    assert_eq!(requests.lock().unwrap().len(), 2);
+
+    // Plain iterators are backoff policies too.
+    let op = mock_s3_operator(&endpoint)
+        .await
+        .layer(RetryLayer::with_backoff(std::iter::repeat_n(
+            Duration::from_millis(1),
+            2,
+        )));
+    let err = op.object("down").metadata().await.unwrap_err();
+    assert_eq!(err.context()["retries"], "2");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_metrics_layer() -> Result<()> {
+    let op = Operator::new(memory::Backend::build().finish().await?).layer(MetricsLayer);
+
+    // Counting bytes must not change the content.
+    let content = vec![1; 4096];
+    op.object("file")
+        .writer()
+        .write_bytes(content.clone())
+        .await?;
+    let mut buf = Vec::new();
+    op.object("file")
+        .reader()
+        .read_to_end(&mut buf)
+        .await
+        .map_err(anyhow::Error::from)?;
+    assert_eq!(buf, content);
+
+    // Errors are recorded without being changed.
+    let err = op.object("not_exist").metadata().await.unwrap_err();
+    assert_eq!(err.kind(), Kind::ObjectNotExist);
+
+    Ok(())
+}
+
+/// Stalled yields one chunk and then stalls, or stalls at once if `empty`.
+#[derive(Debug)]
+struct Stalled {
+    empty: bool,
+}
+
+#[async_trait::async_trait]
+impl Accessor for Stalled {
+    async fn read(&self, _: &OpRead) -> Result<BytesStream> {
+        let first = if self.empty {
+            vec![]
+        } else {
+            vec![Ok(bytes::Bytes::from("a"))]
+        };
+        Ok(Box::new(
+            futures::stream::iter(first).chain(futures::stream::pending()),
+        ))
+    }
+}
+
+#[tokio::test]
+async fn test_timeout_layer() -> Result<()> {
+    let read = |acc: Arc<dyn Accessor>| async move {
+        acc.read(&OpRead {
+            path: "file".to_string(),
+            ..Default::default()
+        })
+        .await
+    };
+
+    // Stalled transfers fail with io timeout.
+    let acc = TimeoutLayer::new(Duration::from_secs(10))
+        .io_timeout(Duration::from_millis(50))
+        .layer(Arc::new(Stalled { empty: false }));
+    let mut s = read(acc).await?;
+    assert_eq!(s.next().await.unwrap()?, "a");
+    let err = s.next().await.unwrap().unwrap_err();
+    assert_eq!(err.kind(), Kind::Timeout);
+    assert!(err.is_retryable());
+    assert!(err.to_string().contains("no data received"), "{}", err);
+    assert!(s.next().await.is_none());
+
+    // The first chunk is limited by first byte timeout.
+    let acc = TimeoutLayer::new(Duration::from_secs(10))
+        .first_byte_timeout(Duration::from_millis(50))
+        .layer(Arc::new(Stalled { empty: true }));
+    let err = read(acc).await?.next().await.unwrap().unwrap_err();
+    assert_eq!(err.kind(), Kind::Timeout);
+    assert!(err.to_string().contains("first byte"), "{}", err);
+
+    // Consuming the stream is limited by the operation timeout.
+    let acc =
+        TimeoutLayer::new(Duration::from_millis(50)).layer(Arc::new(Stalled { empty: false }));
+    let start = Instant::now();
+    let mut s = read(acc).await?;
+    assert_eq!(s.next().await.unwrap()?, "a");
+    let err = s.next().await.unwrap().unwrap_err();
+    assert_eq!(err.kind(), Kind::Timeout);
+    assert!(err.to_string().contains("operation timeout"), "{}", err);
+    assert!(start.elapsed() < Duration::from_secs(5));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_concurrency_limit_layer() -> Result<()> {
+    let acc =
+        ConcurrencyLimitLayer::with_limits(1, 1).layer(memory::Backend::build().finish().await?);
+    let op = Operator::new(acc.clone());
+    op.object("file").writer().write_bytes(vec![0; 4]).await?;
+    let args = OpRead {
+        path: "file".to_string(),
+        ..Default::default()
+    };
+    let read = || acc.read(&args);
+
+    // The permit is held by the stream.
+    let s = read().await?;
+    let stat =
+        tokio::time::timeout(Duration::from_millis(50), acc.stat(&OpStat::new("file"))).await;
+    assert!(stat.is_err(), "stat must wait for the stream");
+
+    // Writes are limited separately.
+    op.object("other").writer().write_bytes(vec![0; 4]).await?;
+
+    // Dropped streams release the permit.
+    drop(s);
+    assert_eq!(acc.stat(&OpStat::new("file")).await?.content_length(), 4);
+
+    // Exhausted streams release the permit too.
+    let mut s = read().await?;
+    while s.next().await.is_some() {}
+    assert_eq!(acc.stat(&OpStat::new("file")).await?.content_length(), 4);
+
+    // Listing holds the permit until the first entry is listed.
+    let mut obs = acc.list(&OpList::new("")).await?;
+    let stat =
+        tokio::time::timeout(Duration::from_millis(50), acc.stat(&OpStat::new("file"))).await;
+    assert!(stat.is_err(), "stat must wait for the listing");
+    obs.next().await.unwrap()?;
+    assert_eq!(acc.stat(&OpStat::new("file")).await?.content_length(), 4);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_concurrency_limit_layer_nested() -> Result<()> {
+    let op = Operator::new(memory::Backend::build().finish().await?)
+        .layer(ConcurrencyLimitLayer::new(1));
+    op.object("a").writer().write_bytes(vec![0; 4]).await?;
+    op.object("b").writer().write_bytes(vec![1; 4]).await?;
+
+    let nested = async {
+        // Listed objects could be stat-ed while listing.
+        let mut sizes = vec![];
+        let mut obs = op.objects("");
+        while let Some(o) = obs.next().await {
+            sizes.push(o?.metadata().await?.content_length());
+        }
+        assert_eq!(sizes, vec![4, 4]);
+
+        // A reader could be piped into a writer.
+        let r = op.object("a").reader();
+        op.object("c").writer().write_reader(Box::new(r), 4).await?;
+        op.object("c").read().await
+    };
+    let bs = tokio::time::timeout(Duration::from_secs(5), nested)
+        .await
+        .expect("nested operations must not deadlock")?;
+    assert_eq!(bs, vec![0; 4]);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_throttle_layer() -> Result<()> {
+    let (bandwidth, burst, size) = (4 * 1024 * 1024, 64 * 1024, 1024 * 1024);
+    let op = Operator::new(memory::Backend::build().finish().await?)
+        .layer(ThrottleLayer::new(bandwidth, burst));
+    // Bytes beyond the burst are transferred at the bandwidth.
+    let expected = Duration::from_secs_f64((size - burst) as f64 / bandwidth as f64);
+    let check = |elapsed: Duration| {
+        assert!(
+            elapsed >= expected.mul_f64(0.95) && elapsed <= expected.mul_f64(1.5),
+            "expected about {:?}, got {:?}",
+            expected,
+            elapsed
+        );
+    };
+
+    let start = Instant::now();
+    op.object("file")
+        .writer()
+        .write_bytes(vec![1; size as usize])
+        .await?;
+    check(start.elapsed());
+
+    // Wait for the bucket to be refilled.
+    tokio::time::sleep(Duration::from_secs_f64(burst as f64 / bandwidth as f64)).await;
+
+    let start = Instant::now();
+    let mut buf = Vec::new();
+    op.object("file")
+        .reader()
+        .read_to_end(&mut buf)
+        .await
+        .map_err(anyhow::Error::from)?;
+    check(start.elapsed());
+    assert_eq!(buf.len(), size as usize);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cache_layer() -> Result<()> {
+    let backend = Operator::new(memory::Backend::build().finish().await?);
+    let cache = Operator::new(memory::Backend::build().finish().await?);
+    let op = backend.clone().layer(CacheLayer::new(cache.clone()));
+    let read = |op: Operator, path: &'static str| async move {
+        let mut buf = Vec::new();
+        op.object(path)
+            .reader()
+            .read_to_end(&mut buf)
+            .await
+            .unwrap();
+        Ok::<_, crate::error::Error>(buf)
+    };
+    // Caches are filled in background.
+    let cached = |path: &'static str| {
+        let cache = cache.clone();
+        async move {
+            for _ in 0..100 {
+                if cache.object(path).is_exist().await? {
+                    return read(cache, path).await;
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+            panic!("{} is not cached", path)
+        }
+    };
+
+    op.object("file")
+        .writer()
+        .write_bytes(b"v1".to_vec())
+        .await?;
+    assert_eq!(read(op.clone(), "file").await?, b"v1");
+    assert_eq!(cached("file").await?, b"v1");
+
+    // Changes bypassing the layer are not seen.
+    backend
+        .object("file")
+        .writer()
+        .write_bytes(b"v2".to_vec())
+        .await?;
+    assert_eq!(read(op.clone(), "file").await?, b"v1");
+
+    // Writes through the layer invalidate the cache.
+    op.object("file")
+        .writer()
+        .write_bytes(b"v3".to_vec())
+        .await?;
+    assert!(!cache.object("file").is_exist().await?);
+    assert_eq!(read(op.clone(), "file").await?, b"v3");
+    assert_eq!(cached("file").await?, b"v3");
+
+    // Deletes invalidate the cache too.
+    op.object("file").delete().await?;
+    assert!(!cache.object("file").is_exist().await?);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cache_layer_validate_and_range() -> Result<()> {
+    let backend = Operator::new(memory::Backend::build().finish().await?);
+    let cache = Operator::new(memory::Backend::build().finish().await?);
+    let op = backend.clone().layer(
+        CacheLayer::new(cache.clone())
+            .validate(true)
+            .range(CacheRange::WholeObject),
+    );
+    let range_read = |op: Operator, offset: u64, size: u64| async move {
+        let mut buf = Vec::new();
+        op.object("file")
+            .range_reader(offset, size)
+            .read_to_end(&mut buf)
+            .await
+            .unwrap();
+        Ok::<_, crate::error::Error>(buf)
+    };
+
+    backend
+        .object("file")
+        .writer()
+        .write_bytes(b"0123456789".to_vec())
+        .await?;
+
+    // The whole object is cached by a ranged read.
+    assert_eq!(range_read(op.clone(), 2, 3).await?, b"234");
+    let mut buf = Vec::new();
+    cache
+        .object("file")
+        .reader()
+        .read_to_end(&mut buf)
+        .await
+        .unwrap();
+    assert_eq!(buf, b"0123456789");
+
+    // Changes bypassing the layer are detected.
+    backend
+        .object("file")
+        .writer()
+        .write_bytes(b"abcdefghij".to_vec())
+        .await?;
+    assert_eq!(range_read(op.clone(), 2, 3).await?, b"cde");
+    let mut buf = Vec::new();
+    cache
+        .object("file")
+        .reader()
+        .read_to_end(&mut buf)
+        .await
+        .unwrap();
+    assert_eq!(buf, b"abcdefghij");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cache_layer_close() -> Result<()> {
+    let backend = Operator::new(memory::Backend::build().finish().await?);
+    let cache = Operator::new(memory::Backend::build().finish().await?);
+    let mut layer = CacheLayer::new(cache.clone());
+    let op = backend.clone().layer(layer.clone());
+    let read = |path: &'static str| {
+        let op = op.clone();
+        async move {
+            let mut buf = Vec::new();
+            op.object(path)
+                .reader()
+                .read_to_end(&mut buf)
+                .await
+                .map_err(anyhow::Error::from)?;
+            Ok::<_, crate::error::Error>(buf)
+        }
+    };
+
+    for path in ["file", "other"] {
+        backend
+            .object(path)
+            .writer()
+            .write_bytes(b"v1".to_vec())
+            .await?;
+    }
+
+    // Close waits for fills in background.
+    assert_eq!(read("file").await?, b"v1");
+    layer.close().await?;
+    assert!(cache.object("file").is_exist().await?);
+
+    // Nothing is filled once closed.
+    assert_eq!(read("other").await?, b"v1");
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert!(!cache.object("other").is_exist().await?);
+
+    Ok(())
+}
@@ -0,0 +1,132 @@
+// Copyright 2022 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::time::Duration;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+use anyhow::Result;
+use futures::future::join_all;
+
+use crate::error::Kind;
+use crate::lock::LockContent;
+use crate::lock::LOCK_CLOCK_SKEW_MARGIN;
+use crate::services::fs;
+use crate::services::memory;
+use crate::Lock;
+use crate::Operator;
+
+const TTL: Duration = Duration::from_secs(60);
+
+/// Overwrite the lock object as if it has expired.
+async fn expire(op: &Operator, path: &str, owner: &str) -> Result<()> {
+    let expired_at = SystemTime::now() - LOCK_CLOCK_SKEW_MARGIN - Duration::from_secs(1);
+    let content = LockContent {
+        owner: owner.to_string(),
+        expires_at: expired_at.duration_since(UNIX_EPOCH)?.as_millis() as u64,
+    };
+    op.object(path)
+        .writer()
+        .write_bytes(serde_json::to_vec(&content)?)
+        .await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_lock_unsupported() -> Result<()> {
+    let op = Operator::new(fs::Backend::build().finish().await?);
+    let err = Lock::acquire(&op, "/tmp/lock", TTL, "a")
+        .await
+        .err()
+        .unwrap();
+    assert_eq!(err.kind(), Kind::Unsupported);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_lock() -> Result<()> {
+    let op = Operator::new(memory::Backend::build().finish().await?);
+
+    let mut lock = Lock::acquire(&op, "lock", TTL, "a").await?;
+    assert_eq!(lock.owner(), "a");
+    let err = Lock::acquire(&op, "lock", TTL, "b").await.err().unwrap();
+    assert_eq!(err.kind(), Kind::ObjectLocked);
+    assert_eq!(err.context()["owner"], "a");
+
+    // Renew extends the lease.
+    let expires_at = lock.expires_at();
+    tokio::time::sleep(Duration::from_millis(2)).await;
+    lock.renew().await?;
+    assert!(lock.expires_at() > expires_at);
+
+    lock.release().await?;
+    assert!(!op.object("lock").is_exist().await?);
+    let lock = Lock::acquire(&op, "lock", TTL, "b").await?;
+    assert_eq!(lock.owner(), "b");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_lock_steal() -> Result<()> {
+    let op = Operator::new(memory::Backend::build().finish().await?);
+
+    let mut lock = Lock::acquire(&op, "lock", TTL, "a").await?;
+    expire(&op, "lock", "a").await?;
+    let stolen = Lock::acquire(&op, "lock", TTL, "b").await?;
+
+    // The lost lock can't be renewed or released.
+    let err = lock.renew().await.unwrap_err();
+    assert_eq!(err.kind(), Kind::ObjectPreconditionFailed);
+    let err = lock.release().await.unwrap_err();
+    assert_eq!(err.kind(), Kind::ObjectPreconditionFailed);
+
+    let err = Lock::acquire(&op, "lock", TTL, "c").await.err().unwrap();
+    assert_eq!(err.context()["owner"], "b");
+    stolen.release().await?;
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 8)]
+async fn test_lock_contention() -> Result<()> {
+    let op = Operator::new(memory::Backend::build().finish().await?);
+
+    for epoch in 0..8 {
+        let tasks = (0..32).map(|i| {
+            let op = op.clone();
+            tokio::spawn(async move { Lock::acquire(&op, "lock", TTL, &i.to_string()).await })
+        });
+
+        let mut holders = vec![];
+        for res in join_all(tasks).await {
+            match res? {
+                Ok(lock) => holders.push(lock),
+                Err(e) => assert_eq!(e.kind(), Kind::ObjectLocked, "epoch {}", epoch),
+            }
+        }
+        assert_eq!(holders.len(), 1, "epoch {}", epoch);
+
+        // Start the next epoch by expiring or releasing the lock.
+        let holder = holders.pop().unwrap();
+        if epoch % 2 == 0 {
+            expire(&op, "lock", holder.owner()).await?;
+        } else {
+            holder.release().await?;
+        }
+    }
+
+    Ok(())
+}
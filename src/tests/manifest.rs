@@ -0,0 +1,223 @@
+// Copyright 2022 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+
+use anyhow::Result;
+use futures::TryStreamExt;
+
+use crate::error::Kind;
+use crate::layers::manifest::ManifestLayer;
+use crate::services::memory;
+use crate::Object;
+use crate::Operator;
+
+async fn read(o: &Object) -> crate::error::Result<Vec<u8>> {
+    let s = o.stream(None, None).await?;
+    s.map_ok(|v| v.to_vec()).try_concat().await
+}
+
+async fn list(op: &Operator, path: &str) -> Result<Vec<(String, u64)>> {
+    let mut obs = op.objects(path);
+    let mut entries = vec![];
+    while let Some(mut o) = obs.try_next().await? {
+        let meta = o.metadata_cached().await?;
+        entries.push((meta.path().to_string(), meta.content_length()));
+    }
+    Ok(entries)
+}
+
+#[tokio::test]
+async fn test_manifest_round_trip() -> Result<()> {
+    let op = Operator::new(memory::Backend::build().finish().await?);
+    let manifests = Operator::new(memory::Backend::build().finish().await?);
+    for (path, size) in [
+        ("data/a", 1),
+        ("data/b", 2),
+        ("data/sub/c", 3),
+        ("other", 4),
+    ] {
+        op.object(path).writer().write_bytes(vec![0; size]).await?;
+    }
+
+    let count = op.snapshot_prefix("data/", &manifests.object("v1")).await?;
+    assert_eq!(count, 3);
+
+    let layer = ManifestLayer::load(&manifests, "v1").await?;
+    assert_eq!(layer.len(), 3);
+    let view = op.clone().layer(layer);
+
+    // Objects written after the snapshot are not visible.
+    op.object("data/d").writer().write_bytes(vec![0; 5]).await?;
+    let expected = vec![
+        ("data/a".to_string(), 1),
+        ("data/b".to_string(), 2),
+        ("data/sub/c".to_string(), 3),
+    ];
+    assert_eq!(list(&view, "data/").await?, expected);
+    assert_eq!(list(&view, "data/sub/").await?, expected[2..].to_vec());
+    let err = read(&view.object("data/d")).await.unwrap_err();
+    assert_eq!(err.kind(), Kind::ObjectNotExist);
+
+    assert_eq!(read(&view.object("data/a")).await?, vec![0; 1]);
+    assert_eq!(view.object("data/b").metadata().await?.content_length(), 2);
+    let (s, meta) = view
+        .object("data/sub/c")
+        .read_with_metadata(None, None)
+        .await?;
+    assert_eq!(meta.content_length(), 3);
+    assert_eq!(s.map_ok(|v| v.to_vec()).try_concat().await?, vec![0; 3]);
+
+    // Overwritten and deleted objects fail instead of serving new bytes.
+    op.object("data/a").writer().write_bytes(vec![1; 1]).await?;
+    op.object("data/b").delete().await?;
+    for path in ["data/a", "data/b"] {
+        let err = read(&view.object(path)).await.unwrap_err();
+        assert_eq!(err.kind(), Kind::ObjectChanged, "read {}", path);
+        let err = view.object(path).metadata().await.unwrap_err();
+        assert_eq!(err.kind(), Kind::ObjectChanged, "stat {}", path);
+        let err = view
+            .object(path)
+            .read_with_metadata(None, None)
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(
+            err.kind(),
+            Kind::ObjectChanged,
+            "read_with_metadata {}",
+            path
+        );
+    }
+    // The listing is still served from the manifest.
+    assert_eq!(list(&view, "data/").await?, expected);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_manifest_read_only() -> Result<()> {
+    let op = Operator::new(memory::Backend::build().finish().await?);
+    op.object("data/a").writer().write_bytes(vec![0; 1]).await?;
+    op.snapshot_prefix("data/", &op.object("manifest")).await?;
+    let view = op
+        .clone()
+        .layer(ManifestLayer::load(&op, "manifest").await?);
+
+    let err = view
+        .object("data/a")
+        .writer()
+        .write_bytes(vec![1; 1])
+        .await
+        .unwrap_err();
+    assert_eq!(err.kind(), Kind::ObjectPermissionDenied);
+    let err = view.object("data/a").delete().await.unwrap_err();
+    assert_eq!(err.kind(), Kind::ObjectPermissionDenied);
+    assert_eq!(read(&op.object("data/a")).await?, vec![0; 1]);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_manifest_list_options() -> Result<()> {
+    let op = Operator::new(memory::Backend::build().finish().await?);
+    for i in 0..10 {
+        op.object(&format!("data/{:02}", i))
+            .writer()
+            .write_bytes(vec![0; i])
+            .await?;
+    }
+    op.snapshot_prefix("data/", &op.object("manifest")).await?;
+    let view = op
+        .clone()
+        .layer(ManifestLayer::load(&op, "manifest").await?);
+    assert!(view.metadata().ordered_list());
+
+    let mut obs = view.objects("data/").start_after("data/03").max_results(3);
+    let mut paths = vec![];
+    while let Some(mut o) = obs.try_next().await? {
+        paths.push(o.metadata_cached().await?.path().to_string());
+    }
+    assert_eq!(paths, vec!["data/04", "data/05", "data/06"]);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_manifest_load() -> Result<()> {
+    let op = Operator::new(memory::Backend::build().finish().await?);
+    let header = r#"{"format":"opendal-manifest","version":1}"#;
+
+    // Large manifests without the trailing newline.
+    let mut content = header.to_string();
+    for i in 0..10000 {
+        content.push_str(&format!(
+            "\n{{\"path\":\"data/{:05}\",\"etag\":null,\"size\":{}}}",
+            i, i
+        ));
+    }
+    op.object("large")
+        .writer()
+        .write_bytes(content.into())
+        .await?;
+    let layer = ManifestLayer::load(&op, "large").await?;
+    assert_eq!(layer.len(), 10000);
+
+    let cases = [
+        ("empty", "".to_string(), Kind::ObjectCorrupted, 0),
+        (
+            "version",
+            r#"{"format":"opendal-manifest","version":2}"#.to_string(),
+            Kind::Unsupported,
+            1,
+        ),
+        (
+            "format",
+            r#"{"format":"other","version":1}"#.to_string(),
+            Kind::ObjectCorrupted,
+            1,
+        ),
+        (
+            "entry",
+            format!("{}\n{{\"path\":\"a\"}}\n", header),
+            Kind::ObjectCorrupted,
+            2,
+        ),
+        (
+            "duplicate",
+            format!(
+                "{}\n{}\n{}\n",
+                header,
+                r#"{"path":"a","etag":null,"size":1}"#,
+                r#"{"path":"/a","etag":null,"size":1}"#
+            ),
+            Kind::ObjectCorrupted,
+            3,
+        ),
+    ];
+    for (name, content, kind, line) in cases {
+        op.object(name).writer().write_bytes(content.into()).await?;
+        let err = ManifestLayer::load(&op, name).await.unwrap_err();
+        assert_eq!(err.kind(), kind, "{}", name);
+        let expected = HashMap::from([("line".to_string(), line.to_string())]);
+        match err {
+            crate::error::Error::Object { context, .. } => {
+                assert_eq!(context, expected, "{}", name)
+            }
+            _ => panic!("unexpected error {:?}", err),
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,239 @@
+// Copyright 2022 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use anyhow::anyhow;
+use anyhow::Result;
+use async_trait::async_trait;
+use futures::AsyncReadExt;
+
+use crate::error::Error;
+use crate::error::Kind;
+use crate::io::BytesStream;
+use crate::layers::mirror::MirrorComparison;
+use crate::layers::mirror::MirrorLayer;
+use crate::layers::mirror::MirrorPolicy;
+use crate::ops::OpRead;
+use crate::ops::OpStat;
+use crate::services::memory;
+use crate::Accessor;
+use crate::Close;
+use crate::Metadata;
+use crate::Operator;
+
+/// Broken fails every operation.
+#[derive(Debug)]
+struct Broken;
+
+impl Broken {
+    fn error(op: &'static str, path: &str) -> Error {
+        Error::Object {
+            kind: Kind::Unexpected,
+            op,
+            path: path.to_string(),
+            context: HashMap::new(),
+            source: anyhow!("broken"),
+        }
+    }
+}
+
+#[async_trait]
+impl Accessor for Broken {
+    async fn read(&self, args: &OpRead) -> crate::error::Result<BytesStream> {
+        Err(Broken::error("read", &args.path))
+    }
+    async fn stat(&self, args: &OpStat) -> crate::error::Result<Metadata> {
+        Err(Broken::error("stat", &args.path))
+    }
+}
+
+/// Slow fails every stat after a while.
+#[derive(Debug)]
+struct Slow;
+
+#[async_trait]
+impl Accessor for Slow {
+    async fn stat(&self, args: &OpStat) -> crate::error::Result<Metadata> {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        Err(Broken::error("stat", &args.path))
+    }
+}
+
+/// Collect comparisons of the layer.
+fn collector(
+    shadow: Operator,
+    policy: MirrorPolicy,
+) -> (MirrorLayer, Arc<Mutex<Vec<MirrorComparison>>>) {
+    let compared = Arc::new(Mutex::new(vec![]));
+    let c = compared.clone();
+    let layer =
+        MirrorLayer::new(shadow, policy).comparator(move |v| c.lock().unwrap().push(v.clone()));
+    (layer, compared)
+}
+
+/// Wait for `n` comparisons, sorted by op and path.
+async fn wait_compared(
+    compared: &Arc<Mutex<Vec<MirrorComparison>>>,
+    n: usize,
+) -> Vec<MirrorComparison> {
+    for _ in 0..100 {
+        if compared.lock().unwrap().len() >= n {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+
+    let mut v = compared.lock().unwrap().clone();
+    v.sort_by(|a, b| (a.op, &a.path).cmp(&(b.op, &b.path)));
+    v
+}
+
+#[tokio::test]
+async fn test_mirror_divergence() -> Result<()> {
+    let primary = Operator::new(memory::Backend::build().finish().await?);
+    let shadow = Operator::new(memory::Backend::build().finish().await?);
+    for (op, content) in [(&primary, "hello"), (&shadow, "hello")] {
+        op.object("same")
+            .writer()
+            .write_bytes(content.into())
+            .await?;
+    }
+    for (op, content) in [(&primary, "hello"), (&shadow, "world")] {
+        op.object("changed")
+            .writer()
+            .write_bytes(content.into())
+            .await?;
+    }
+    primary
+        .object("missing")
+        .writer()
+        .write_bytes("hello".into())
+        .await?;
+
+    let (layer, compared) = collector(shadow, MirrorPolicy::new(1.0).content_hash(true));
+    let op = primary.layer(layer);
+
+    for path in ["same", "changed", "missing"] {
+        let mut buf = vec![];
+        op.object(path).reader().read_to_end(&mut buf).await?;
+        assert_eq!(buf, b"hello");
+        assert_eq!(op.object(path).metadata().await?.content_length(), 5);
+    }
+
+    let compared = wait_compared(&compared, 6).await;
+    let diverged: Vec<_> = compared
+        .iter()
+        .map(|c| (c.op, c.path.as_str(), c.diverged()))
+        .collect();
+    assert_eq!(
+        diverged,
+        vec![
+            ("read", "changed", true),
+            ("read", "missing", true),
+            ("read", "same", false),
+            ("stat", "changed", false),
+            ("stat", "missing", true),
+            ("stat", "same", false),
+        ]
+    );
+
+    // Same size but different content is only detected by content hash.
+    assert_eq!(compared[0].primary.size, compared[0].shadow.size);
+    assert_ne!(
+        compared[0].primary.content_md5,
+        compared[0].shadow.content_md5
+    );
+    assert_eq!(compared[1].shadow.error, Some(Kind::ObjectNotExist));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_mirror_shadow_errors_invisible() -> Result<()> {
+    let primary = Operator::new(memory::Backend::build().finish().await?);
+    primary
+        .object("file")
+        .writer()
+        .write_bytes("hello".into())
+        .await?;
+
+    let (layer, compared) = collector(Operator::new(Arc::new(Broken)), MirrorPolicy::new(1.0));
+    let op = primary.layer(layer);
+
+    let mut buf = vec![];
+    op.object("file").reader().read_to_end(&mut buf).await?;
+    assert_eq!(buf, b"hello");
+    assert_eq!(op.object("file").metadata().await?.content_length(), 5);
+
+    let compared = wait_compared(&compared, 2).await;
+    assert_eq!(compared.len(), 2);
+    assert!(compared.iter().all(|c| c.diverged()));
+    assert!(compared
+        .iter()
+        .all(|c| c.primary.error.is_none() && c.shadow.error == Some(Kind::Unexpected)));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_mirror_sample_rate() -> Result<()> {
+    let primary = Operator::new(memory::Backend::build().finish().await?);
+    let shadow = Operator::new(memory::Backend::build().finish().await?);
+    primary
+        .object("file")
+        .writer()
+        .write_bytes(vec![0; 4])
+        .await?;
+
+    let (layer, compared) = collector(shadow, MirrorPolicy::new(0.25).read(false));
+    let op = primary.layer(layer);
+
+    for _ in 0..8 {
+        op.object("file").metadata().await?;
+    }
+
+    assert_eq!(wait_compared(&compared, 2).await.len(), 2);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_mirror_close() -> Result<()> {
+    let primary = Operator::new(memory::Backend::build().finish().await?);
+    primary
+        .object("file")
+        .writer()
+        .write_bytes(vec![0; 4])
+        .await?;
+
+    let (mut layer, compared) = collector(Operator::new(Arc::new(Slow)), MirrorPolicy::new(1.0));
+    let op = primary.layer(layer.clone());
+
+    for _ in 0..2 {
+        op.object("file").metadata().await?;
+    }
+    assert!(compared.lock().unwrap().is_empty());
+
+    // Close waits for pending replays, and stops mirroring.
+    layer.close().await?;
+    assert_eq!(compared.lock().unwrap().len(), 2);
+    op.object("file").metadata().await?;
+    assert_eq!(wait_compared(&compared, 3).await.len(), 2);
+
+    Ok(())
+}
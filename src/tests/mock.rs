@@ -0,0 +1,235 @@
+// Copyright 2022 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::convert::Infallible;
+use std::net::TcpListener;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use bytes::Bytes;
+use hyper::service::make_service_fn;
+use hyper::service::service_fn;
+use tokio::io::AsyncReadExt;
+use tokio::io::AsyncWriteExt;
+
+use crate::credential::Credential;
+use crate::services::s3;
+use crate::Operator;
+
+/// Requests recorded by the mock server.
+pub type Recorded = Arc<Mutex<Vec<http::Request<Bytes>>>>;
+
+/// Start a mock http server which records all requests and responds by `handler`.
+///
+/// Returns the endpoint like `http://127.0.0.1:1234`.
+pub fn mock_server<F>(handler: F) -> (String, Recorded)
+where
+    F: Fn(&http::Request<Bytes>) -> hyper::Response<hyper::Body> + Send + Sync + 'static,
+{
+    let recorded: Recorded = Arc::new(Mutex::new(Vec::new()));
+    let handler = Arc::new(handler);
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let requests = recorded.clone();
+    let server = hyper::Server::from_tcp(listener)
+        .unwrap()
+        .serve(make_service_fn(move |_| {
+            let requests = requests.clone();
+            let handler = handler.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |req: hyper::Request<hyper::Body>| {
+                    let requests = requests.clone();
+                    let handler = handler.clone();
+                    async move {
+                        let (parts, body) = req.into_parts();
+                        let body = hyper::body::to_bytes(body).await.unwrap();
+                        let req = http::Request::from_parts(parts, body);
+
+                        let resp = handler(&req);
+                        requests.lock().unwrap().push(req);
+                        Ok::<_, Infallible>(resp)
+                    }
+                }))
+            }
+        }));
+    tokio::spawn(server);
+
+    (format!("http://{}", addr), recorded)
+}
+
+/// Build a s3 operator on the mock server.
+pub async fn mock_s3_operator(endpoint: &str) -> Operator {
+    let mut builder = s3::Backend::build();
+    builder
+        .bucket("test")
+        .endpoint(endpoint)
+        .region("us-east-1")
+        .credential(Credential::hmac("access_key_id", "secret_access_key"));
+
+    Operator::new(builder.finish().await.expect("build s3 backend"))
+}
+
+/// Start a mock http server which responds every request with a body of
+/// `total` bytes, but kills the connection after `sent` bytes.
+///
+/// The connection is reset if `reset` is true, otherwise closed gracefully.
+pub fn mock_broken_server(total: usize, sent: usize, reset: bool) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    listener.set_nonblocking(true).unwrap();
+    let addr = listener.local_addr().unwrap();
+    let listener = tokio::net::TcpListener::from_std(listener).unwrap();
+
+    tokio::spawn(async move {
+        loop {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            tokio::spawn(async move {
+                // Read until the end of request head, requests are all bodiless.
+                let mut head = Vec::new();
+                while !head.ends_with(b"\r\n\r\n") {
+                    let mut buf = [0; 1024];
+                    let n = stream.read(&mut buf).await.unwrap();
+                    if n == 0 {
+                        return;
+                    }
+                    head.extend_from_slice(&buf[..n]);
+                }
+
+                let resp = format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n", total);
+                stream.write_all(resp.as_bytes()).await.unwrap();
+                stream.write_all(&vec![0; sent]).await.unwrap();
+                stream.flush().await.unwrap();
+                if reset {
+                    // Zero linger resets the connection on drop without blocking.
+                    #[allow(deprecated)]
+                    stream.set_linger(Some(Duration::ZERO)).unwrap();
+                } else {
+                    stream.shutdown().await.unwrap();
+                }
+            });
+        }
+    });
+
+    format!("http://{}", addr)
+}
+
+/// Content and ETag of the object served by [`mock_flaky_server`].
+pub type FlakyObject = Arc<Mutex<(Vec<u8>, String)>>;
+
+/// Start a mock http server which serves ranged reads of `object`, including
+/// suffix ranges, but closes the connection after `sent` bytes of the body
+/// for the first `breaks` responses.
+///
+/// `If-Match` is checked against the current ETag of `object`. Returns the
+/// endpoint and the heads of all requests.
+pub fn mock_flaky_server(
+    object: FlakyObject,
+    sent: usize,
+    breaks: usize,
+) -> (String, Arc<Mutex<Vec<String>>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    listener.set_nonblocking(true).unwrap();
+    let addr = listener.local_addr().unwrap();
+    let listener = tokio::net::TcpListener::from_std(listener).unwrap();
+    let heads = Arc::new(Mutex::new(Vec::new()));
+    let responses = Arc::new(AtomicUsize::new(0));
+
+    let recorded = heads.clone();
+    tokio::spawn(async move {
+        loop {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let (object, heads, responses) = (object.clone(), heads.clone(), responses.clone());
+            tokio::spawn(async move {
+                loop {
+                    // Read until the end of request head, requests are all bodiless.
+                    let mut head = Vec::new();
+                    while !head.ends_with(b"\r\n\r\n") {
+                        let mut buf = [0; 1];
+                        match stream.read(&mut buf).await {
+                            Ok(1) => head.push(buf[0]),
+                            _ => return,
+                        }
+                    }
+                    let head = String::from_utf8(head).unwrap().to_lowercase();
+                    heads.lock().unwrap().push(head.clone());
+                    let header = |name: &str| {
+                        head.lines()
+                            .find_map(|l| l.strip_prefix(&format!("{}: ", name)))
+                            .map(|v| v.to_string())
+                    };
+
+                    let (content, etag) = object.lock().unwrap().clone();
+                    let total = content.len();
+                    if matches!(header("if-match"), Some(v) if v != etag.to_lowercase()) {
+                        let resp = "HTTP/1.1 412 Precondition Failed\r\nContent-Length: 0\r\n\r\n";
+                        stream.write_all(resp.as_bytes()).await.unwrap();
+                        continue;
+                    }
+                    let (start, end) = match header("range") {
+                        Some(range) => {
+                            let (start, end) = range
+                                .strip_prefix("bytes=")
+                                .unwrap()
+                                .split_once('-')
+                                .unwrap();
+                            match start {
+                                // Suffix ranges like `bytes=-N`.
+                                "" => (total.saturating_sub(end.parse().unwrap()), total),
+                                start => (
+                                    start.parse().unwrap(),
+                                    end.parse::<usize>().map_or(total, |v| (v + 1).min(total)),
+                                ),
+                            }
+                        }
+                        None => (0, total),
+                    };
+                    if start >= total {
+                        let resp = format!(
+                            "HTTP/1.1 416 Range Not Satisfiable\r\nContent-Range: bytes */{}\r\nContent-Length: 0\r\n\r\n",
+                            total
+                        );
+                        stream.write_all(resp.as_bytes()).await.unwrap();
+                        continue;
+                    }
+
+                    let resp = format!(
+                        "HTTP/1.1 206 Partial Content\r\nContent-Range: bytes {}-{}/{}\r\nContent-Length: {}\r\nETag: {}\r\n\r\n",
+                        start,
+                        end - 1,
+                        total,
+                        end - start,
+                        etag
+                    );
+                    stream.write_all(resp.as_bytes()).await.unwrap();
+                    if responses.fetch_add(1, Ordering::SeqCst) < breaks {
+                        let sent = sent.min(end - start);
+                        stream
+                            .write_all(&content[start..start + sent])
+                            .await
+                            .unwrap();
+                        stream.flush().await.unwrap();
+                        stream.shutdown().await.unwrap();
+                        return;
+                    }
+                    stream.write_all(&content[start..end]).await.unwrap();
+                }
+            });
+        }
+    });
+
+    (format!("http://{}", addr), recorded)
+}
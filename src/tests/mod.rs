@@ -12,7 +12,24 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+mod analyze;
+mod config;
+mod error;
+mod framed;
+mod http_util;
 mod io;
 mod layer;
+mod lock;
+mod manifest;
+mod mirror;
+pub(crate) mod mock;
+mod object;
+mod operator;
 mod ops;
+mod path;
+mod policy;
+mod read_many;
 mod readers;
+mod shutdown;
+mod static_files;
+mod uri;
@@ -0,0 +1,265 @@
+// Copyright 2022 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use anyhow::Result;
+use futures::io::Cursor;
+use futures::AsyncReadExt;
+use futures::StreamExt;
+
+use super::mock::mock_s3_operator;
+use super::mock::mock_server;
+use crate::error::Kind;
+use crate::services::fs;
+use crate::services::memory;
+use crate::ObjectStream;
+use crate::Operator;
+
+async fn new_memory_operator(scan_limit: Option<u64>) -> Result<Operator> {
+    let mut builder = memory::Backend::build();
+    if let Some(limit) = scan_limit {
+        builder.list_scan_limit(limit);
+    }
+    let op = Operator::new(builder.finish().await?);
+
+    for i in 0..3 {
+        op.object(&format!("dir/file-{}", i))
+            .writer()
+            .write_bytes("Hello, World!".as_bytes().to_vec())
+            .await?;
+    }
+
+    Ok(op)
+}
+
+/// Collect the listing into (entries, errors).
+async fn collect(mut obs: ObjectStream) -> (usize, Vec<Kind>) {
+    let mut entries = 0;
+    let mut errors = vec![];
+    while let Some(o) = obs.next().await {
+        match o {
+            Ok(_) => entries += 1,
+            Err(e) => errors.push(e.kind()),
+        }
+    }
+
+    (entries, errors)
+}
+
+#[tokio::test]
+async fn test_list_max_results() -> Result<()> {
+    let op = new_memory_operator(None).await?;
+
+    for (max_results, expected) in [(0, 0), (2, 2), (3, 3), (4, 3)] {
+        let (entries, errors) = collect(op.objects("dir/").max_results(max_results)).await;
+        assert_eq!(entries, expected, "max_results {}", max_results);
+        assert!(errors.is_empty(), "max_results {}", max_results);
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_list_pattern() -> Result<()> {
+    // Filtered entries are not counted by the scan limit.
+    let op = new_memory_operator(Some(2)).await?;
+
+    for (pattern, expected) in [("dir/file-?", 2), ("dir/*-1", 1), ("**.csv", 0)] {
+        let obs = op.objects("dir/").pattern(pattern).max_results(2);
+        let (entries, errors) = collect(obs).await;
+        assert_eq!(entries, expected, "pattern {}", pattern);
+        assert!(errors.is_empty(), "pattern {}", pattern);
+    }
+
+    let (entries, errors) = collect(op.objects("dir/").pattern("dir/*-1")).await;
+    assert_eq!(entries, 1);
+    assert!(errors.is_empty());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_list_scan_limit() -> Result<()> {
+    // Listing exactly the scan limit should not be reported as truncated.
+    let op = new_memory_operator(Some(3)).await?;
+    let (entries, errors) = collect(op.objects("dir/")).await;
+    assert_eq!(entries, 3);
+    assert!(errors.is_empty());
+
+    // Listing more than the scan limit should end with an error.
+    let op = new_memory_operator(Some(2)).await?;
+    let (entries, errors) = collect(op.objects("dir/")).await;
+    assert_eq!(entries, 2);
+    assert_eq!(errors, vec![Kind::ScanLimitExceeded]);
+
+    // max_results takes precedence over the scan limit.
+    let (entries, errors) = collect(op.objects("dir/").max_results(3)).await;
+    assert_eq!(entries, 3);
+    assert!(errors.is_empty());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_list_ordered() -> Result<()> {
+    let root = format!("/tmp/opendal-test-{}", uuid::Uuid::new_v4());
+    let op = Operator::new(
+        fs::Backend::build()
+            .root(&root)
+            .list_sort_limit(8)
+            .finish()
+            .await?,
+    );
+
+    let mut expected = vec![];
+    for i in (0..8).rev() {
+        let path = format!("dir/file-{}", i);
+        op.object(&path).writer().write_bytes(vec![0; 1]).await?;
+        expected.push(path);
+    }
+    expected.sort();
+
+    let mut obs = op.objects("dir/").ordered();
+    let mut paths = vec![];
+    while let Some(o) = obs.next().await {
+        paths.push(o?.metadata().await?.path().to_string());
+    }
+    assert_eq!(paths, expected);
+
+    // Dirs larger than the sort limit can't be listed in order.
+    op.object("dir/file-8")
+        .writer()
+        .write_bytes(vec![0; 1])
+        .await?;
+    let (entries, errors) = collect(op.objects("dir/").ordered()).await;
+    assert_eq!(entries, 0);
+    assert_eq!(errors, vec![Kind::Unsupported]);
+
+    std::fs::remove_dir_all(&root)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_append_fs() -> Result<()> {
+    let root = format!("/tmp/opendal-test-{}", uuid::Uuid::new_v4());
+    let op = Operator::new(fs::Backend::build().root(&root).finish().await?);
+    assert!(op.metadata().append());
+
+    let o = op.object("dir/log");
+    let append = |position: u64, bs: &'static [u8], size: u64| {
+        o.append(position, Box::new(Cursor::new(bs)), size)
+    };
+
+    // Appending a nonexistent object at other than 0 doesn't create it.
+    let err = append(3, b"abc", 3).await.unwrap_err();
+    assert_eq!(err.kind(), Kind::ObjectNotExist);
+
+    assert_eq!(append(0, b"abc", 3).await?.content_length(), 3);
+    assert_eq!(append(3, b"defg", 4).await?.content_length(), 7);
+
+    // Stale positions are rejected without appending anything.
+    let err = append(3, b"xyz", 3).await.unwrap_err();
+    assert_eq!(err.kind(), Kind::ObjectPreconditionFailed);
+
+    // Partial content of a failed append is truncated.
+    let err = append(7, b"hi", 4).await.unwrap_err();
+    assert_eq!(err.kind(), Kind::Unexpected);
+
+    let mut content = vec![];
+    o.reader().read_to_end(&mut content).await?;
+    assert_eq!(content, b"abcdefg");
+
+    std::fs::remove_dir_all(&root)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_append_unsupported() -> Result<()> {
+    let op = Operator::new(memory::Backend::build().finish().await?);
+    assert!(!op.metadata().append());
+
+    let err = op
+        .object("log")
+        .append(0, Box::new(Cursor::new(b"abc")), 3)
+        .await
+        .unwrap_err();
+    assert_eq!(err.kind(), Kind::Unsupported);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_is_exist() -> Result<()> {
+    let (endpoint, _) = mock_server(|req| {
+        let path = req.uri().path();
+        let resp = hyper::Response::builder();
+        if req.method() == http::Method::HEAD {
+            let status = match path {
+                "/test/file" => 200,
+                "/test/denied" => 403,
+                _ => 404,
+            };
+            return resp.status(status).body(hyper::Body::empty()).unwrap();
+        }
+        // Listing to check dirs without marker.
+        let query = req.uri().query().unwrap_or_default();
+        let contents = if query.contains("prefix=dir%2F") {
+            "<Contents><Key>dir/file</Key><Size>1</Size></Contents>"
+        } else {
+            ""
+        };
+        resp.body(hyper::Body::from(format!(
+            "<ListBucketResult><IsTruncated>false</IsTruncated>{}</ListBucketResult>",
+            contents
+        )))
+        .unwrap()
+    });
+    let op = mock_s3_operator(&endpoint).await;
+
+    assert!(op.exists("file").await?);
+    assert!(!op.exists("missing").await?);
+    assert!(op.exists("dir/").await?);
+    assert!(!op.exists("empty/").await?);
+    // Errors other than not exist are not treated as not exist.
+    let err = op.object("denied").is_exist().await.unwrap_err();
+    assert_eq!(err.kind(), Kind::ObjectPermissionDenied);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_range_read() -> Result<()> {
+    let op = Operator::new(memory::Backend::build().finish().await?);
+    let o = op.object("file");
+    o.writer()
+        .write_bytes("Hello, World!".as_bytes().to_vec())
+        .await?;
+
+    assert_eq!(o.read().await?, b"Hello, World!");
+    assert_eq!(o.range_read(7..).await?, b"World!");
+    assert_eq!(o.range_read(..6).await?, b"World!");
+    assert_eq!(o.range_read(0..5).await?, b"Hello");
+
+    // Only the size of the range is limited.
+    assert_eq!(o.range_read_with_max_size(0..5, 5).await?, b"Hello");
+    assert_eq!(o.range_read_with_max_size(..100, 13).await?.len(), 13);
+    let err = o.range_read_with_max_size(.., 5).await.unwrap_err();
+    assert_eq!(err.kind(), Kind::ObjectTooLarge);
+    assert_eq!(err.context()["size"], "13");
+    assert!(err.to_string().contains("sized 13"), "{}", err);
+
+    let err = op.object("missing").read().await.unwrap_err();
+    assert_eq!(err.kind(), Kind::ObjectNotExist);
+
+    Ok(())
+}
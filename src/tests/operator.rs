@@ -0,0 +1,696 @@
+// Copyright 2022 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::io::SeekFrom;
+use std::pin::Pin;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::task::Context;
+use std::task::Poll;
+use std::time::Duration;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use futures::io::Cursor;
+use futures::AsyncRead;
+use futures::AsyncReadExt;
+use futures::AsyncSeek;
+use futures::StreamExt;
+use futures::TryStreamExt;
+
+use super::mock::mock_s3_operator;
+use super::mock::mock_server;
+use crate::error::Kind;
+use crate::layers::ImmutableLayer;
+use crate::layers::SubdirLayer;
+use crate::operator::key_midpoint;
+use crate::operator::LIST_TAIL_PROBE_BUDGET;
+use crate::ops::BatchOperation;
+use crate::ops::BatchResult;
+use crate::ops::BytesRange;
+use crate::ops::OpDelete;
+use crate::ops::OpList;
+use crate::ops::OpRead;
+use crate::ops::OpStat;
+use crate::ops::OpWrite;
+use crate::services::fs;
+use crate::services::memory;
+use crate::Accessor;
+use crate::BoxedAsyncReader;
+use crate::BoxedObjectStream;
+use crate::Digest;
+use crate::Metadata;
+use crate::Object;
+use crate::Operator;
+use crate::WriteOutcome;
+
+#[tokio::test]
+async fn test_clone_with_root() -> Result<()> {
+    let acc = memory::Backend::build().finish().await?;
+    let op = Operator::new(acc.clone());
+    assert_eq!(Arc::strong_count(&acc), 2);
+
+    let derived = op.clone_with(|opts| opts.root("/other/"))?;
+    // The derived operator shares the same backend.
+    assert_eq!(Arc::strong_count(&acc), 3);
+
+    derived
+        .object("dir/file")
+        .writer()
+        .write_bytes(vec![0; 4])
+        .await?;
+    assert_eq!(
+        op.object("other/dir/file")
+            .metadata()
+            .await?
+            .content_length(),
+        4
+    );
+    assert_eq!(
+        derived.object("dir/file").metadata().await?.path(),
+        "dir/file"
+    );
+
+    let mut obs = derived.objects("dir/");
+    let mut paths = vec![];
+    while let Some(o) = obs.next().await {
+        let mut o = o?;
+        paths.push(o.metadata_cached().await?.path().to_string());
+    }
+    assert_eq!(paths, vec!["dir/file"]);
+
+    derived.object("dir/file").delete().await?;
+    assert!(!op.object("other/dir/file").is_exist().await?);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_clone_with_read_only() -> Result<()> {
+    let op = Operator::new(memory::Backend::build().finish().await?);
+    op.object("file").writer().write_bytes(vec![0; 4]).await?;
+
+    let derived = op.clone_with(|opts| opts.read_only())?;
+    assert_eq!(derived.object("file").metadata().await?.content_length(), 4);
+    let err = derived.object("file").delete().await.unwrap_err();
+    assert_eq!(err.kind(), Kind::ObjectPermissionDenied);
+
+    // The original operator is not affected.
+    op.object("file").delete().await?;
+
+    Ok(())
+}
+
+#[derive(Debug)]
+struct Slow;
+
+#[async_trait]
+impl Accessor for Slow {
+    async fn stat(&self, _: &OpStat) -> crate::error::Result<Metadata> {
+        tokio::time::sleep(Duration::from_secs(10)).await;
+        Ok(Metadata::default())
+    }
+}
+
+#[tokio::test]
+async fn test_clone_with_timeout() -> Result<()> {
+    let op = Operator::new(Arc::new(Slow));
+
+    let derived = op.clone_with(|opts| opts.timeout(Duration::from_millis(10)))?;
+    let err = derived.object("file").metadata().await.unwrap_err();
+    assert_eq!(err.kind(), Kind::Timeout);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_clone_with_rebuild_required() -> Result<()> {
+    let op = Operator::new(memory::Backend::build().finish().await?);
+
+    let err = op
+        .clone_with(|opts| opts.root("/other/").bucket("another"))
+        .err()
+        .unwrap();
+    assert_eq!(err.kind(), Kind::Unsupported);
+    assert!(err.to_string().contains("bucket"), "{}", err);
+
+    Ok(())
+}
+
+/// A reader which fails on read, to make sure the content is never consumed.
+struct Unreadable;
+
+impl AsyncRead for Unreadable {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        _: &mut Context<'_>,
+        _: &mut [u8],
+    ) -> Poll<std::io::Result<usize>> {
+        Poll::Ready(Err(std::io::Error::other("content should not be read")))
+    }
+}
+
+impl AsyncSeek for Unreadable {
+    fn poll_seek(
+        self: Pin<&mut Self>,
+        _: &mut Context<'_>,
+        _: SeekFrom,
+    ) -> Poll<std::io::Result<u64>> {
+        Poll::Ready(Ok(0))
+    }
+}
+
+#[tokio::test]
+async fn test_write_if_changed() -> Result<()> {
+    let content = vec![1; 4];
+    let etag = format!("\"{:x}\"", md5::compute(&content));
+    let (endpoint, requests) = mock_server(move |req| {
+        let resp = hyper::Response::builder();
+        if req.method() != http::Method::HEAD {
+            return resp.body(hyper::Body::empty()).unwrap();
+        }
+        let etag = match req.uri().path() {
+            "/test/same" => etag.clone(),
+            "/test/changed" => format!("\"{:x}\"", md5::compute([2; 4])),
+            "/test/multipart" => format!("{}-2\"", etag.trim_end_matches('"')),
+            _ => {
+                return resp
+                    .status(http::StatusCode::NOT_FOUND)
+                    .body(hyper::Body::empty())
+                    .unwrap()
+            }
+        };
+        resp.header(http::header::CONTENT_LENGTH, "4")
+            .header(http::header::ETAG, etag)
+            .body(hyper::Body::empty())
+            .unwrap()
+    });
+    let op = mock_s3_operator(&endpoint).await;
+
+    let puts = || {
+        requests
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|req| req.method() == http::Method::PUT)
+            .map(|req| req.uri().path().to_string())
+            .collect::<Vec<_>>()
+    };
+
+    for (path, expected) in [
+        ("same", WriteOutcome::Skipped { bytes_saved: 4 }),
+        ("changed", WriteOutcome::Written { size: 4 }),
+        ("multipart", WriteOutcome::Written { size: 4 }),
+        ("not_exist", WriteOutcome::Written { size: 4 }),
+    ] {
+        let outcome = op
+            .write_if_changed(path, Cursor::new(content.clone()), 4, None)
+            .await?;
+        assert_eq!(outcome, expected, "{}", path);
+    }
+    assert_eq!(
+        puts(),
+        vec!["/test/changed", "/test/multipart", "/test/not_exist"]
+    );
+
+    // With a precomputed digest, the content is not read at all.
+    let digest = Digest::Md5(md5::compute(&content).0);
+    let outcome = op
+        .write_if_changed("same", Unreadable, 4, Some(digest))
+        .await?;
+    assert_eq!(outcome, WriteOutcome::Skipped { bytes_saved: 4 });
+    assert_eq!(puts().len(), 3);
+
+    // Only the next `size` bytes from the current position are hashed and
+    // written, and the reader is rewound to that position.
+    let padded = [vec![9; 2], content.clone(), vec![9; 2]].concat();
+    for (path, expected) in [
+        ("same", WriteOutcome::Skipped { bytes_saved: 4 }),
+        ("changed", WriteOutcome::Written { size: 4 }),
+    ] {
+        let mut r = Cursor::new(padded.clone());
+        r.set_position(2);
+        let outcome = op.write_if_changed(path, r, 4, None).await?;
+        assert_eq!(outcome, expected, "{}", path);
+    }
+    assert_eq!(puts().len(), 4);
+    let requests = requests.lock().unwrap();
+    let put = requests
+        .iter()
+        .filter(|req| req.method() == http::Method::PUT)
+        .last()
+        .unwrap();
+    assert_eq!(put.body().as_ref(), content.as_slice());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_list_start_after() -> Result<()> {
+    let op = Operator::new(memory::Backend::build().finish().await?);
+    for path in ["dir/a", "dir/b", "dir/c", "dir/d"] {
+        op.object(path).writer().write_bytes(vec![0]).await?;
+    }
+
+    let mut paths = vec![];
+    let mut obs = op
+        .objects("dir/")
+        .ordered()
+        .start_after("dir/b")
+        .max_results(1);
+    while let Some(o) = obs.next().await {
+        paths.push(o?.metadata_cached().await?.path().to_string());
+    }
+    // Skipped entries are not counted by max_results.
+    assert_eq!(paths, vec!["dir/c"]);
+
+    let (endpoint, requests) = mock_server(|_| {
+        hyper::Response::new(hyper::Body::from(
+            "<ListBucketResult><IsTruncated>false</IsTruncated></ListBucketResult>",
+        ))
+    });
+    let op = mock_s3_operator(&endpoint).await;
+    let mut obs = op.objects("dir/").start_after("dir/b#c");
+    while let Some(o) = obs.next().await {
+        o?;
+    }
+    let query = requests.lock().unwrap()[0]
+        .uri()
+        .query()
+        .unwrap_or_default()
+        .to_string();
+    assert!(query.contains("start-after=dir%2Fb%23c"), "{}", query);
+
+    Ok(())
+}
+
+/// Accessor which counts list requests.
+#[derive(Debug)]
+struct CountList {
+    inner: Arc<dyn Accessor>,
+    lists: AtomicUsize,
+}
+
+#[async_trait]
+impl Accessor for CountList {
+    async fn write(&self, r: BoxedAsyncReader, args: &OpWrite) -> crate::error::Result<Metadata> {
+        self.inner.write(r, args).await
+    }
+
+    async fn stat(&self, args: &OpStat) -> crate::error::Result<Metadata> {
+        self.inner.stat(args).await
+    }
+
+    async fn list(&self, args: &OpList) -> crate::error::Result<BoxedObjectStream> {
+        self.lists.fetch_add(1, Ordering::SeqCst);
+        self.inner.list(args).await
+    }
+}
+
+async fn list_tail_fixture(keys: &[String]) -> Result<(Operator, Arc<CountList>)> {
+    let acc = Arc::new(CountList {
+        inner: memory::Backend::build().finish().await?,
+        lists: AtomicUsize::new(0),
+    });
+    let op = Operator::new(acc.clone());
+    for key in keys {
+        op.object(key).writer().write_bytes(vec![0]).await?;
+    }
+    Ok((op, acc))
+}
+
+fn minute_key(minute: u32) -> String {
+    format!("logs/2024-06-01T{:02}:{:02}:00Z", minute / 60, minute % 60)
+}
+
+fn expected_tail(keys: &[String], n: usize) -> Vec<String> {
+    let mut keys = keys.to_vec();
+    keys.sort();
+    keys.split_off(keys.len().saturating_sub(n))
+}
+
+async fn paths(obs: Vec<Object>) -> Result<Vec<String>> {
+    let mut paths = vec![];
+    for mut o in obs {
+        paths.push(o.metadata_cached().await?.path().to_string());
+    }
+    Ok(paths)
+}
+
+#[tokio::test]
+async fn test_list_tail() -> Result<()> {
+    let _ = env_logger::try_init();
+
+    // One key per minute of a day.
+    let keys: Vec<String> = (0..1440).map(minute_key).collect();
+    let (op, acc) = list_tail_fixture(&keys).await?;
+    let expected = expected_tail(&keys, 100);
+
+    // A good hint doubles the window until it contains enough keys.
+    let obs = op
+        .list_tail("logs/", 100, |k| {
+            1440u32.checked_sub(10 << k).map(minute_key)
+        })
+        .await?;
+    assert_eq!(paths(obs).await?, expected);
+    assert_eq!(acc.lists.swap(0, Ordering::SeqCst), 5);
+
+    // A hint that jumps too far is narrowed down by bisection.
+    let obs = op
+        .list_tail("logs/", 100, |k| match k {
+            0 => Some(minute_key(1430)),
+            1 => Some(minute_key(0)),
+            _ => None,
+        })
+        .await?;
+    assert_eq!(paths(obs).await?, expected);
+    assert!(acc.lists.swap(0, Ordering::SeqCst) <= 6);
+
+    // A hint that is too early still returns the tail within the budget.
+    let obs = op
+        .list_tail("logs/", 100, |k| (k == 0).then(String::new))
+        .await?;
+    assert_eq!(paths(obs).await?, expected);
+    assert!(acc.lists.swap(0, Ordering::SeqCst) as u32 <= LIST_TAIL_PROBE_BUDGET + 1);
+
+    // A hint that doesn't move backwards is ignored after the first probe.
+    let obs = op
+        .list_tail("logs/", 100, |_| Some(minute_key(1430)))
+        .await?;
+    assert_eq!(paths(obs).await?, expected);
+    assert_eq!(acc.lists.swap(0, Ordering::SeqCst), 2);
+
+    // Without a hint, the whole prefix is scanned once.
+    let obs = op.list_tail("logs/", 100, |_| None).await?;
+    assert_eq!(paths(obs).await?, expected);
+    assert_eq!(acc.lists.swap(0, Ordering::SeqCst), 1);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_list_tail_skewed() -> Result<()> {
+    // Most keys are written in the last minute.
+    let mut keys: Vec<String> = (0..100).map(|m| minute_key(m * 14)).collect();
+    keys.extend((0..1000).map(|i| format!("{}-{:04}", minute_key(1439), i)));
+    let (op, acc) = list_tail_fixture(&keys).await?;
+
+    let obs = op
+        .list_tail("logs/", 10, |k| {
+            1440u32.checked_sub(10 << k).map(minute_key)
+        })
+        .await?;
+    assert_eq!(paths(obs).await?, expected_tail(&keys, 10));
+    assert!(acc.lists.swap(0, Ordering::SeqCst) as u32 <= LIST_TAIL_PROBE_BUDGET + 1);
+
+    // Less keys than required.
+    let obs = op
+        .list_tail("logs/2024-06-01T00", 10, |k| {
+            (k < 3).then(|| minute_key(30 - k * 10))
+        })
+        .await?;
+    assert_eq!(
+        paths(obs).await?,
+        (0..5).map(|m| minute_key(m * 14)).collect::<Vec<_>>()
+    );
+
+    assert!(op.list_tail("logs/", 0, |_| None).await?.is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn test_key_midpoint() {
+    let cases = [
+        ("a", "c", Some("b")),
+        ("a", "b", Some("a@")),
+        ("logs/", "logs/\u{7f}", Some("logs/?@")),
+        ("a", "a\u{0}", None),
+        ("b", "a", None),
+    ];
+    for (lo, hi, expected) in cases {
+        assert_eq!(key_midpoint(lo, hi).as_deref(), expected, "{} {}", lo, hi);
+    }
+
+    let mid = key_midpoint("日", "本").unwrap();
+    assert!("日" < mid.as_str() && mid.as_str() < "本");
+}
+
+async fn check_read_mmap(op: &Operator) -> Result<()> {
+    let content: Vec<u8> = (0..4096u32).map(|v| (v % 251) as u8).collect();
+    op.object("dir/file")
+        .writer()
+        .write_bytes(content.clone())
+        .await?;
+
+    let bs = op.read_mmap("dir/file", ..).await?;
+    assert_eq!(bs.as_ref(), content.as_slice());
+
+    for (offset, size) in [(0, 1), (100, 1000), (4000, 96)] {
+        let mut expected = vec![];
+        op.object("dir/file")
+            .range_reader(offset, size)
+            .read_to_end(&mut expected)
+            .await?;
+        let bs = op.read_mmap("dir/file", offset..offset + size).await?;
+        assert_eq!(bs.as_ref(), expected.as_slice(), "{}..+{}", offset, size);
+    }
+    assert_eq!(
+        op.read_mmap("dir/file", 4000..).await?.as_ref(),
+        &content[4000..]
+    );
+    assert_eq!(
+        op.read_mmap("dir/file", ..=9).await?.as_ref(),
+        &content[..10]
+    );
+    assert!(op.read_mmap("dir/file", 10..10).await?.is_empty());
+
+    // Layers keep the fast path.
+    let sub = op.clone().layer(SubdirLayer::new("dir"));
+    assert_eq!(sub.read_mmap("file", 1..3).await?.as_ref(), &content[1..3]);
+
+    let err = op.read_mmap("not_exist", ..).await.unwrap_err();
+    assert_eq!(err.kind(), Kind::ObjectNotExist);
+
+    Ok(())
+}
+
+async fn check_read_suffix(op: &Operator) -> Result<()> {
+    let content: Vec<u8> = (0..4096u32).map(|v| (v % 251) as u8).collect();
+    op.object("dir/file")
+        .writer()
+        .write_bytes(content.clone())
+        .await?;
+    op.object("dir/empty").writer().write_bytes(vec![]).await?;
+
+    for (path, size, expected) in [
+        ("dir/file", 16, &content[4080..]),
+        ("dir/file", 4096, &content[..]),
+        ("dir/file", 10000, &content[..]),
+        ("dir/empty", 16, &[][..]),
+    ] {
+        let s = op.object(path).stream(None, Some(size)).await?;
+        let bs = s.map_ok(|v| v.to_vec()).try_concat().await?;
+        assert_eq!(bs, expected, "{} -{}", path, size);
+
+        let bs = op
+            .inner()
+            .read_mmap(&OpRead {
+                path: path.to_string(),
+                range: BytesRange::from(..size),
+                ..Default::default()
+            })
+            .await?;
+        assert_eq!(bs.as_ref(), expected, "{} -{}", path, size);
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_read_suffix_memory() -> Result<()> {
+    let op = Operator::new(memory::Backend::build().finish().await?);
+    check_read_suffix(&op).await
+}
+
+#[tokio::test]
+async fn test_read_suffix_fs() -> Result<()> {
+    let root = format!("/tmp/opendal-test-{}", uuid::Uuid::new_v4());
+    let op = Operator::new(fs::Backend::build().root(&root).finish().await?);
+    check_read_suffix(&op).await?;
+
+    std::fs::remove_dir_all(&root)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_read_mmap_memory() -> Result<()> {
+    let op = Operator::new(memory::Backend::build().finish().await?);
+    assert!(!op.metadata().mmap_read());
+    check_read_mmap(&op).await
+}
+
+#[tokio::test]
+async fn test_read_mmap_fs() -> Result<()> {
+    let root = format!("/tmp/opendal-test-{}", uuid::Uuid::new_v4());
+    let op = Operator::new(fs::Backend::build().root(&root).finish().await?);
+    assert_eq!(op.metadata().mmap_read(), cfg!(feature = "mmap"));
+    check_read_mmap(&op).await?;
+
+    // Ranges past the end are truncated like the buffered read.
+    for (offset, size) in [(4000, 200), (4096, 10), (5000, 10)] {
+        let mut expected = vec![];
+        op.object("dir/file")
+            .range_reader(offset, size)
+            .read_to_end(&mut expected)
+            .await?;
+        let bs = op.read_mmap("dir/file", offset..offset + size).await?;
+        assert_eq!(bs.as_ref(), expected.as_slice(), "{}..+{}", offset, size);
+    }
+
+    std::fs::remove_dir_all(&root)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_version_unsupported() -> Result<()> {
+    let op = Operator::new(memory::Backend::build().finish().await?);
+    op.object("file").writer().write_bytes(vec![0; 4]).await?;
+
+    let err = op.object("file").version_metadata("v1").await.unwrap_err();
+    assert_eq!(err.kind(), Kind::Unsupported);
+
+    let mut buf = vec![];
+    let err = op
+        .object("file")
+        .reader()
+        .version("v1")
+        .read_to_end(&mut buf)
+        .await
+        .unwrap_err();
+    let err = err
+        .into_inner()
+        .unwrap()
+        .downcast::<crate::error::Error>()
+        .unwrap();
+    assert_eq!(err.kind(), Kind::Unsupported);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_batch() -> Result<()> {
+    let op = Operator::new(memory::Backend::build().finish().await?);
+    op.object("dir/a").writer().write_bytes(vec![0; 4]).await?;
+    op.object("dir/b").writer().write_bytes(vec![0; 8]).await?;
+
+    let sub = op.clone().layer(SubdirLayer::new("dir"));
+    let results = sub
+        .batch(vec![
+            BatchOperation::Stat(OpStat::new("a")),
+            BatchOperation::Delete(OpDelete::new("b")),
+            BatchOperation::Stat(OpStat::new("c")),
+        ])
+        .await?;
+    let paths: Vec<_> = results.iter().map(|(path, _)| path.as_str()).collect();
+    assert_eq!(paths, vec!["a", "b", "c"]);
+    match &results[0].1 {
+        BatchResult::Stat(Ok(meta)) => {
+            assert_eq!(meta.path(), "a");
+            assert_eq!(meta.content_length(), 4);
+        }
+        res => panic!("unexpected result: {:?}", res),
+    }
+    assert!(matches!(results[1].1, BatchResult::Delete(Ok(()))));
+    match &results[2].1 {
+        BatchResult::Stat(Err(err)) => assert_eq!(err.kind(), Kind::ObjectNotExist),
+        res => panic!("unexpected result: {:?}", res),
+    }
+    assert!(!op.object("dir/b").is_exist().await?);
+
+    // Sub-operations are checked one by one.
+    let read_only = op.clone().layer(ImmutableLayer);
+    let results = read_only
+        .batch(vec![
+            BatchOperation::Delete(OpDelete::new("dir/a")),
+            BatchOperation::Stat(OpStat::new("dir/a")),
+        ])
+        .await?;
+    match &results[0].1 {
+        BatchResult::Delete(Err(err)) => assert_eq!(err.kind(), Kind::ObjectPermissionDenied),
+        res => panic!("unexpected result: {:?}", res),
+    }
+    assert!(results[1].1.is_ok());
+    assert!(op.object("dir/a").is_exist().await?);
+
+    Ok(())
+}
+
+async fn check_scan(op: &Operator) -> Result<()> {
+    op.object("dir/a").writer().write_bytes(vec![0; 4]).await?;
+    op.object("dir/sub/b")
+        .writer()
+        .write_bytes(vec![0; 8])
+        .await?;
+    op.object("dir/sub/deep/c")
+        .writer()
+        .write_bytes(vec![])
+        .await?;
+    op.object("dir/empty/").create().await?;
+    op.object("other").writer().write_bytes(vec![0; 1]).await?;
+
+    let scan = |op: Operator, path: &'static str| async move {
+        let mut paths: Vec<String> = op
+            .scan(path)
+            .await?
+            .map_ok(|o| o.metadata_ref().path().to_string())
+            .try_collect()
+            .await?;
+        paths.sort();
+        Ok::<_, crate::error::Error>(paths)
+    };
+
+    // Dirs and dir markers are skipped.
+    assert_eq!(
+        scan(op.clone(), "dir/").await?,
+        vec!["dir/a", "dir/sub/b", "dir/sub/deep/c"]
+    );
+    assert_eq!(
+        scan(op.clone(), "").await?,
+        vec!["dir/a", "dir/sub/b", "dir/sub/deep/c", "other"]
+    );
+
+    // Paths are relative to the root of the layered operator.
+    let sub = op.clone().layer(SubdirLayer::new("dir/sub"));
+    assert_eq!(scan(sub, "").await?, vec!["b", "deep/c"]);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_scan_memory() -> Result<()> {
+    let op = Operator::new(memory::Backend::build().finish().await?);
+    check_scan(&op).await
+}
+
+#[tokio::test]
+async fn test_scan_fs() -> Result<()> {
+    let root = format!("/tmp/opendal-test-{}", uuid::Uuid::new_v4());
+    let op = Operator::new(fs::Backend::build().root(&root).finish().await?);
+    check_scan(&op).await?;
+
+    std::fs::remove_dir_all(&root)?;
+    Ok(())
+}
@@ -12,16 +12,51 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use crate::ops::HeaderRange;
+use crate::ops::BytesRange;
 
 #[test]
-fn test_header_range() {
-    let h = HeaderRange::new(None, Some(1024));
+fn test_bytes_range_to_string() {
+    let h = BytesRange::from(..1024);
+    assert_eq!(h.to_string(), "bytes=-1024");
+
+    let h = BytesRange::from(0..1024);
     assert_eq!(h.to_string(), "bytes=0-1023");
 
-    let h = HeaderRange::new(Some(1024), None);
+    let h = BytesRange::from(1024..);
     assert_eq!(h.to_string(), "bytes=1024-");
 
-    let h = HeaderRange::new(Some(1024), Some(1024));
+    let h = BytesRange::from(5..);
+    assert_eq!(h.to_string(), "bytes=5-");
+
+    let h = BytesRange::from(1024..2048);
+    assert_eq!(h.to_string(), "bytes=1024-2047");
+
+    let h = BytesRange::from(5..6);
+    assert_eq!(h.to_string(), "bytes=5-5");
+
+    let h = BytesRange::from(..);
+    assert_eq!(h.to_string(), "bytes=0-");
+
+    let h = BytesRange::new(Some(1024), Some(1024));
     assert_eq!(h.to_string(), "bytes=1024-2047");
 }
+
+#[test]
+fn test_bytes_range_from() {
+    let r = BytesRange::from(5..10);
+    assert_eq!((r.offset(), r.size()), (Some(5), Some(5)));
+    assert!(!r.is_full());
+    assert!(!r.is_suffix());
+
+    let r = BytesRange::from(5..);
+    assert_eq!((r.offset(), r.size()), (Some(5), None));
+    assert!(!r.is_full());
+
+    let r = BytesRange::from(..10);
+    assert_eq!((r.offset(), r.size()), (None, Some(10)));
+    assert!(r.is_suffix());
+
+    assert!(BytesRange::from(..).is_full());
+    assert!(BytesRange::from(0..).is_full());
+    assert_eq!(BytesRange::default(), BytesRange::from(..));
+}
@@ -0,0 +1,102 @@
+// Copyright 2022 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use anyhow::Result;
+
+use crate::error::Kind;
+use crate::path::validate_path;
+use crate::services::fs;
+use crate::services::memory;
+use crate::Operator;
+
+#[test]
+fn test_validate_path() {
+    let cases = vec![
+        ("", true),
+        ("a", true),
+        ("a/b/", true),
+        ("/a//b", true),
+        ("a..b/.c/d.", true),
+        ("a/../../b", false),
+        ("a/..", false),
+        ("..", false),
+        ("/../a", false),
+        ("./a", false),
+        ("a/./b", false),
+        ("a\0b", false),
+    ];
+
+    for (path, valid) in cases {
+        let res = validate_path("read", path);
+        assert_eq!(res.is_ok(), valid, "path {:?}", path);
+        if let Err(err) = res {
+            assert_eq!(err.kind(), Kind::ObjectPathInvalid);
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_path_traversal_fs() -> Result<()> {
+    let dir = format!("/tmp/opendal-test-{}", uuid::Uuid::new_v4());
+    let root = format!("{}/root", dir);
+    std::fs::create_dir_all(&root)?;
+    std::fs::write(format!("{}/secret", dir), "secret")?;
+    let op = Operator::new(fs::Backend::build().root(&root).finish().await?);
+
+    for path in ["../secret", "a/../../secret", "/../secret", "./secret"] {
+        let err = op.object(path).metadata().await.unwrap_err();
+        assert_eq!(err.kind(), Kind::ObjectPathInvalid, "path {}", path);
+
+        let err = op
+            .object(path)
+            .writer()
+            .write_bytes(vec![0; 4])
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), Kind::ObjectPathInvalid, "path {}", path);
+    }
+    assert_eq!(std::fs::read(format!("{}/secret", dir))?, b"secret");
+
+    // Absolute-looking paths are relative to the root.
+    let err = op.object("/secret").metadata().await.unwrap_err();
+    assert_eq!(err.kind(), Kind::ObjectNotExist);
+    op.object("/abs/file")
+        .writer()
+        .write_bytes(vec![0; 4])
+        .await?;
+    assert!(std::path::Path::new(&format!("{}/abs/file", root)).exists());
+
+    std::fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_path_traversal_memory() -> Result<()> {
+    let op = Operator::new(memory::Backend::build().finish().await?);
+
+    for path in ["a/../../b", "./a", "a\0b"] {
+        let err = op
+            .object(path)
+            .writer()
+            .write_bytes(vec![0; 4])
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), Kind::ObjectPathInvalid, "path {:?}", path);
+    }
+
+    op.object("/a").writer().write_bytes(vec![0; 4]).await?;
+    assert!(op.object("a").is_exist().await?);
+
+    Ok(())
+}
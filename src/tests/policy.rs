@@ -0,0 +1,74 @@
+// Copyright 2022 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use anyhow::Result;
+
+use super::mock::mock_s3_operator;
+use super::mock::mock_server;
+use crate::layers::policy::StorageClassPolicyLayer;
+use crate::layers::policy::StorageClassRule;
+
+#[tokio::test]
+async fn test_storage_class_policy_headers() -> Result<()> {
+    let (endpoint, requests) = mock_server(|_| hyper::Response::new(hyper::Body::empty()));
+
+    let policy = StorageClassPolicyLayer::new(vec![
+        StorageClassRule::new("tmp/**")
+            .storage_class("STANDARD")
+            .tag("expiry", "7d"),
+        StorageClassRule::new("**")
+            .storage_class("STANDARD_IA")
+            .cache_control("max-age=3600"),
+    ]);
+    let op = mock_s3_operator(&endpoint).await.layer(policy.clone());
+
+    op.object("tmp/a").writer().write_bytes(vec![0; 4]).await?;
+    op.object("data/b").writer().write_bytes(vec![0; 4]).await?;
+    op.object("data/c")
+        .writer()
+        .storage_class("ONEZONE_IA")
+        .write_bytes(vec![0; 4])
+        .await?;
+
+    // Reloaded rules take effect on existing operators.
+    policy.reload(vec![StorageClassRule::new("**").storage_class("GLACIER_IR")]);
+    op.object("data/d").writer().write_bytes(vec![0; 4]).await?;
+
+    let requests = requests.lock().unwrap();
+    let get = |idx: usize, name: &str| {
+        requests[idx]
+            .headers()
+            .get(name)
+            .map(|v| v.to_str().unwrap().to_string())
+    };
+    assert_eq!(requests.len(), 4);
+
+    assert_eq!(get(0, "x-amz-storage-class").as_deref(), Some("STANDARD"));
+    assert_eq!(get(0, "x-amz-tagging").as_deref(), Some("expiry=7d"));
+    assert_eq!(get(0, "cache-control"), None);
+
+    assert_eq!(
+        get(1, "x-amz-storage-class").as_deref(),
+        Some("STANDARD_IA")
+    );
+    assert_eq!(get(1, "cache-control").as_deref(), Some("max-age=3600"));
+    assert_eq!(get(1, "x-amz-tagging"), None);
+
+    assert_eq!(get(2, "x-amz-storage-class").as_deref(), Some("ONEZONE_IA"));
+    assert_eq!(get(2, "cache-control").as_deref(), Some("max-age=3600"));
+
+    assert_eq!(get(3, "x-amz-storage-class").as_deref(), Some("GLACIER_IR"));
+
+    Ok(())
+}
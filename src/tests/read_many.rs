@@ -0,0 +1,208 @@
+// Copyright 2022 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use anyhow::anyhow;
+use anyhow::Result;
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures::StreamExt;
+
+use crate::error::Error;
+use crate::error::Kind;
+use crate::io::BytesStream;
+use crate::ops::OpRead;
+use crate::services::memory;
+use crate::Accessor;
+use crate::Operator;
+use crate::ReadManyOptions;
+
+/// Probe records reads of the inner accessor, reads of `fail/*` fail and
+/// reads of `slow/*` take longer.
+#[derive(Debug)]
+struct Probe {
+    inner: Arc<dyn Accessor>,
+    reads: Mutex<HashMap<String, usize>>,
+    in_flight: AtomicUsize,
+    max_in_flight: AtomicUsize,
+}
+
+#[async_trait]
+impl Accessor for Probe {
+    async fn read(&self, args: &OpRead) -> crate::error::Result<BytesStream> {
+        *self
+            .reads
+            .lock()
+            .unwrap()
+            .entry(args.path.clone())
+            .or_default() += 1;
+        let n = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+        self.max_in_flight.fetch_max(n, Ordering::SeqCst);
+
+        let delay = if args.path.starts_with("slow/") {
+            200
+        } else {
+            10
+        };
+        tokio::time::sleep(Duration::from_millis(delay)).await;
+        self.in_flight.fetch_sub(1, Ordering::SeqCst);
+
+        if args.path.starts_with("fail/") {
+            return Err(Error::Object {
+                kind: Kind::ObjectPermissionDenied,
+                op: "read",
+                path: args.path.clone(),
+                context: HashMap::new(),
+                source: anyhow!("injected"),
+            });
+        }
+        self.inner.read(args).await
+    }
+}
+
+/// Every object contains its own path.
+async fn probe_fixture(paths: &[&str]) -> Result<(Operator, Arc<Probe>)> {
+    let inner = memory::Backend::build().finish().await?;
+    let op = Operator::new(inner.clone());
+    for path in paths {
+        op.object(path)
+            .writer()
+            .write_bytes(path.as_bytes().to_vec())
+            .await?;
+    }
+
+    let probe = Arc::new(Probe {
+        inner,
+        reads: Mutex::new(HashMap::new()),
+        in_flight: AtomicUsize::new(0),
+        max_in_flight: AtomicUsize::new(0),
+    });
+    Ok((Operator::new(probe.clone()), probe))
+}
+
+fn owned(paths: &[&str]) -> Vec<String> {
+    paths.iter().map(|v| v.to_string()).collect()
+}
+
+#[tokio::test]
+async fn test_read_many_concurrency() -> Result<()> {
+    let paths: Vec<String> = (0..20).map(|i| format!("data/{:02}", i)).collect();
+    let (op, probe) = probe_fixture(&paths.iter().map(|v| v.as_str()).collect::<Vec<_>>()).await?;
+
+    let results: Vec<_> = op
+        .read_many(paths.clone(), ReadManyOptions::default().concurrency(4))
+        .collect()
+        .await;
+    assert_eq!(results.len(), 20);
+    assert!(results.iter().all(|(_, res)| res.is_ok()));
+    assert_eq!(probe.max_in_flight.load(Ordering::SeqCst), 4);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_read_many_coalesce() -> Result<()> {
+    let (op, probe) = probe_fixture(&["a", "b", "c"]).await?;
+
+    let results: Vec<_> = op
+        .read_many(
+            owned(&["a", "b", "a", "c", "a", "fail/x", "fail/x"]),
+            ReadManyOptions::default(),
+        )
+        .collect()
+        .await;
+    assert_eq!(results.len(), 7);
+
+    let mut outputs: HashMap<String, Vec<Result<Bytes, Kind>>> = HashMap::new();
+    for (path, res) in results {
+        outputs
+            .entry(path)
+            .or_default()
+            .push(res.map_err(|e| e.kind()));
+    }
+    assert_eq!(outputs["a"], vec![Ok(Bytes::from("a")); 3]);
+    assert_eq!(
+        outputs["fail/x"],
+        vec![Err(Kind::ObjectPermissionDenied); 2]
+    );
+
+    // Duplicated paths are read only once.
+    let reads = probe.reads.lock().unwrap();
+    assert_eq!(reads.len(), 4);
+    assert!(reads.values().all(|v| *v == 1), "{:?}", reads);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_read_many_pairing() -> Result<()> {
+    let files: Vec<String> = (0..10).map(|i| format!("data/{}", i)).collect();
+    let (op, _) = probe_fixture(&files.iter().map(|v| v.as_str()).collect::<Vec<_>>()).await?;
+
+    let mut paths = vec!["slow/a".to_string(), "data/missing".to_string()];
+    for (i, file) in files.iter().enumerate() {
+        paths.push(file.clone());
+        paths.push(format!("fail/{}", i));
+    }
+    let results: Vec<_> = op
+        .read_many(paths, ReadManyOptions::default().concurrency(8))
+        .collect()
+        .await;
+    assert_eq!(results.len(), 22);
+
+    // Slow reads don't block the others.
+    assert_ne!(results[0].0, "slow/a");
+    for (path, res) in results {
+        match res {
+            Ok(bs) => assert_eq!(bs, path.as_bytes(), "{}", path),
+            Err(err) => {
+                let kind = if path.starts_with("fail/") {
+                    Kind::ObjectPermissionDenied
+                } else {
+                    Kind::ObjectNotExist
+                };
+                assert_eq!(err.kind(), kind, "{}", path);
+                assert!(err.to_string().contains(&path), "{}", err);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_read_many_max_size() -> Result<()> {
+    let (op, _) = probe_fixture(&["data/abcd", "data/abc"]).await?;
+
+    let results: HashMap<_, _> = op
+        .read_many(
+            owned(&["data/abcd", "data/abc"]),
+            ReadManyOptions::default().max_size(8),
+        )
+        .collect()
+        .await;
+    assert_eq!(results["data/abc"].as_ref().unwrap(), "data/abc");
+    assert_eq!(
+        results["data/abcd"].as_ref().unwrap_err().kind(),
+        Kind::ObjectTooLarge
+    );
+
+    Ok(())
+}
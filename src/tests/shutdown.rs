@@ -0,0 +1,209 @@
+// Copyright 2022 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use anyhow::anyhow;
+use anyhow::Result;
+use async_trait::async_trait;
+
+use crate::error::Error;
+use crate::error::Kind;
+use crate::services::memory;
+use crate::Close;
+use crate::Lock;
+use crate::Operator;
+use crate::ShutdownGuard;
+
+const TTL: Duration = Duration::from_secs(60);
+
+/// Records the order of close, and fails if `fail` is set.
+struct Recording {
+    name: &'static str,
+    closed: Arc<Mutex<Vec<&'static str>>>,
+    fail: bool,
+}
+
+#[async_trait]
+impl Close for Recording {
+    async fn close(&mut self) -> crate::error::Result<()> {
+        self.closed.lock().unwrap().push(self.name);
+        if self.fail {
+            return Err(Error::Unexpected {
+                context: HashMap::new(),
+                source: anyhow!("close failed"),
+            });
+        }
+        Ok(())
+    }
+}
+
+async fn memory_operator() -> Result<Operator> {
+    Ok(Operator::new(memory::Backend::build().finish().await?))
+}
+
+/// Wait until the object is deleted by background cleanup.
+async fn wait_deleted(op: &Operator, path: &str) -> Result<()> {
+    for _ in 0..100 {
+        if !op.object(path).is_exist().await? {
+            return Ok(());
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    Err(anyhow!("{} is not deleted", path))
+}
+
+#[tokio::test]
+async fn test_shutdown_guard_order() -> Result<()> {
+    let closed = Arc::new(Mutex::new(Vec::new()));
+    let component = |name, fail| Recording {
+        name,
+        closed: closed.clone(),
+        fail,
+    };
+
+    let mut guard = ShutdownGuard::new();
+    guard
+        .register("backend", component("backend", false))
+        .register("cache", component("cache", true))
+        .register("audit", component("audit", false));
+
+    // Components are closed from the last registered one, even if some of
+    // them failed.
+    let err = guard.close().await.unwrap_err();
+    assert_eq!(err.context()["component"], "cache");
+    assert_eq!(*closed.lock().unwrap(), vec!["audit", "cache", "backend"]);
+
+    // Nothing is left to close or abandon.
+    let abandoned = ShutdownGuard::abandoned();
+    guard.close().await?;
+    drop(guard);
+    assert_eq!(closed.lock().unwrap().len(), 3);
+    assert_eq!(ShutdownGuard::abandoned(), abandoned);
+
+    Ok(())
+}
+
+#[test]
+fn test_shutdown_guard_drop_unclosed() {
+    let closed = Arc::new(Mutex::new(Vec::new()));
+    let mut guard = ShutdownGuard::new();
+    guard.register(
+        "audit",
+        Recording {
+            name: "audit",
+            closed: closed.clone(),
+            fail: false,
+        },
+    );
+
+    let abandoned = ShutdownGuard::abandoned();
+    drop(guard);
+    assert!(ShutdownGuard::abandoned() > abandoned);
+    assert!(closed.lock().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_lock_close() -> Result<()> {
+    let op = memory_operator().await?;
+
+    let mut guard = ShutdownGuard::new();
+    guard.register("lock", Lock::acquire(&op, "lock", TTL, "a").await?);
+    guard.close().await?;
+    assert!(!op.object("lock").is_exist().await?);
+
+    // Closing a released lock is a no-op.
+    let mut lock = Lock::acquire(&op, "lock", TTL, "a").await?;
+    lock.close().await?;
+    lock.close().await?;
+    let other = Lock::acquire(&op, "lock", TTL, "b").await?;
+    drop(lock);
+    assert!(op.object("lock").is_exist().await?);
+    other.release().await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_lock_drop_in_runtime() -> Result<()> {
+    let op = memory_operator().await?;
+
+    // Drop releases the lock in background without blocking.
+    let lock = Lock::acquire(&op, "lock", TTL, "a").await?;
+    drop(lock);
+    wait_deleted(&op, "lock").await?;
+    let lock = Lock::acquire(&op, "lock", TTL, "b").await?;
+    assert_eq!(lock.owner(), "b");
+
+    // Locks failed to acquire hold nothing to release.
+    let err = Lock::acquire(&op, "lock", TTL, "c").await.unwrap_err();
+    assert_eq!(err.kind(), Kind::ObjectLocked);
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    assert!(op.object("lock").is_exist().await?);
+
+    Ok(())
+}
+
+#[test]
+fn test_lock_drop_outside_runtime() -> Result<()> {
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?;
+    let (op, lock) = rt.block_on(async {
+        let op = memory_operator().await?;
+        let lock = Lock::acquire(&op, "lock", TTL, "a").await?;
+        Ok::<_, anyhow::Error>((op, lock))
+    })?;
+
+    // The lock is abandoned and kept until it expires.
+    let abandoned = ShutdownGuard::abandoned();
+    drop(lock);
+    assert!(ShutdownGuard::abandoned() > abandoned);
+    assert!(rt.block_on(op.object("lock").is_exist())?);
+
+    Ok(())
+}
+
+#[test]
+fn test_lock_drop_while_runtime_shutdown() -> Result<()> {
+    let rt = tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(2)
+        .enable_all()
+        .build()?;
+    let op = rt.block_on(memory_operator())?;
+
+    // Tasks holding locks are dropped while the runtime shuts down.
+    let (tx, rx) = std::sync::mpsc::channel();
+    for i in 0..4 {
+        let (op, tx) = (op.clone(), tx.clone());
+        rt.spawn(async move {
+            let lock = Lock::acquire(&op, &format!("lock-{}", i), TTL, "a")
+                .await
+                .unwrap();
+            tx.send(()).unwrap();
+            // Hold the lock until the task is dropped.
+            std::future::pending::<()>().await;
+            drop(lock);
+        });
+    }
+    for _ in 0..4 {
+        rx.recv_timeout(Duration::from_secs(5))?;
+    }
+    rt.shutdown_timeout(Duration::from_secs(5));
+
+    Ok(())
+}
@@ -0,0 +1,262 @@
+// Copyright 2022 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+use std::time::Duration;
+use std::time::UNIX_EPOCH;
+
+use anyhow::Result;
+use futures::TryStreamExt;
+
+use crate::error::Kind;
+use crate::services::static_files;
+use crate::Object;
+use crate::ObjectMode;
+use crate::ObjectStream;
+use crate::Operator;
+
+async fn read(o: &Object, offset: Option<u64>, size: Option<u64>) -> crate::error::Result<Vec<u8>> {
+    let s = o.stream(offset, size).await?;
+    s.map_ok(|v| v.to_vec()).try_concat().await
+}
+
+async fn list(obs: ObjectStream) -> Result<Vec<(String, ObjectMode)>> {
+    let mut obs = obs;
+    let mut entries = vec![];
+    while let Some(mut o) = obs.try_next().await? {
+        let meta = o.metadata_cached().await?;
+        entries.push((meta.path().to_string(), meta.mode()));
+    }
+    Ok(entries)
+}
+
+async fn fixture() -> Result<Operator> {
+    let acc = static_files::Builder::from_json(
+        r#"{
+            "manifest.json": "{\"version\": 1}",
+            "data": {
+                "a.bin": "data:application/octet-stream;base64,AAECAwQFBgcICQ==",
+                "b.txt": "data:,hello%20world",
+                "nested": { "c.txt": "c" }
+            },
+            "data//d.txt": "d",
+            "z.txt": "z"
+        }"#,
+    )?
+    .finish()
+    .await?;
+    Ok(Operator::new(acc))
+}
+
+#[tokio::test]
+async fn test_ingest() -> Result<()> {
+    let op = fixture().await?;
+
+    assert_eq!(
+        read(&op.object("manifest.json"), None, None).await?,
+        b"{\"version\": 1}"
+    );
+    assert_eq!(
+        read(&op.object("data/a.bin"), None, None).await?,
+        (0..10).collect::<Vec<u8>>()
+    );
+    assert_eq!(
+        read(&op.object("data/b.txt"), None, None).await?,
+        b"hello world"
+    );
+    assert_eq!(read(&op.object("data/d.txt"), None, None).await?, b"d");
+
+    let meta = op.object("data/a.bin").metadata().await?;
+    assert_eq!(meta.content_type(), Some("application/octet-stream"));
+    assert_eq!(meta.content_length(), 10);
+    let meta = op.object("data/b.txt").metadata().await?;
+    assert_eq!(meta.content_type(), None);
+
+    // Maps share the decoding of values with JSON documents.
+    let acc = static_files::Builder::from_map(HashMap::from([
+        (
+            "a".to_string(),
+            "data:text/plain;charset=utf-8;base64,YQ==".to_string(),
+        ),
+        ("b".to_string(), "literal".to_string()),
+    ]))?
+    .entry("c", "from code")
+    .finish()
+    .await?;
+    let op = Operator::new(acc);
+    assert_eq!(read(&op.object("a"), None, None).await?, b"a");
+    assert_eq!(
+        op.object("a").metadata().await?.content_type(),
+        Some("text/plain;charset=utf-8")
+    );
+    assert_eq!(read(&op.object("b"), None, None).await?, b"literal");
+    assert_eq!(read(&op.object("c"), None, None).await?, b"from code");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_invalid_config() -> Result<()> {
+    let cases = [
+        ("not json", "{"),
+        ("number value", r#"{"a": 1}"#),
+        ("top level array", r#"["a"]"#),
+        ("data url without comma", r#"{"a": "data:text/plain"}"#),
+        ("invalid base64", r#"{"a": "data:;base64,!!!"}"#),
+    ];
+    for (name, doc) in cases {
+        let err = static_files::Builder::from_json(doc).unwrap_err();
+        assert_eq!(err.kind(), Kind::BackendConfigurationInvalid, "{}", name);
+    }
+
+    let err = static_files::Builder::default()
+        .entry_uri("a", "http://example.com")
+        .unwrap_err();
+    assert_eq!(err.kind(), Kind::BackendConfigurationInvalid);
+    assert_eq!(err.context()["path"], "a");
+
+    for path in ["", "/", "dir/"] {
+        let err = static_files::Builder::default()
+            .entry(path, "x")
+            .finish()
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), Kind::BackendConfigurationInvalid, "{:?}", path);
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_range_read() -> Result<()> {
+    let op = fixture().await?;
+    let o = op.object("data/a.bin");
+
+    let cases = [
+        ("offset", Some(4), None, 4..10),
+        ("offset and size", Some(2), Some(3), 2..5),
+        ("size beyond end", Some(8), Some(10), 8..10),
+        ("offset at end", Some(10), None, 10..10),
+        ("suffix", None, Some(3), 7..10),
+        ("suffix longer than object", None, Some(20), 0..10),
+        ("empty", None, Some(0), 0..0),
+    ];
+    for (name, offset, size, expected) in cases {
+        assert_eq!(
+            read(&o, offset, size).await?,
+            expected.map(|v| v as u8).collect::<Vec<_>>(),
+            "{}",
+            name
+        );
+    }
+
+    let err = read(&o, Some(11), None).await.unwrap_err();
+    assert_eq!(err.kind(), Kind::Unexpected);
+    let err = read(&op.object("data/"), None, None).await.unwrap_err();
+    assert_eq!(err.kind(), Kind::ObjectNotExist);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_stat() -> Result<()> {
+    let op = fixture().await?;
+
+    let meta = op.object("z.txt").metadata().await?;
+    assert_eq!(meta.mode(), ObjectMode::FILE);
+    assert_eq!(meta.last_modified(), Some(UNIX_EPOCH));
+    assert_eq!(meta.etag(), Some(format!("{:x}", md5::compute("z"))));
+
+    for path in ["", "/", "data/", "data/nested/"] {
+        let meta = op.object(path).metadata().await?;
+        assert_eq!(meta.mode(), ObjectMode::DIR, "{:?}", path);
+    }
+    for path in ["missing", "missing/", "data", "data/nes/"] {
+        let err = op.object(path).metadata().await.unwrap_err();
+        assert_eq!(err.kind(), Kind::ObjectNotExist, "{:?}", path);
+    }
+
+    // Metadata is deterministic with the configured time.
+    let t = UNIX_EPOCH + Duration::from_secs(1_600_000_000);
+    let acc = static_files::Backend::build()
+        .entry("a", "a")
+        .last_modified(t)
+        .finish()
+        .await?;
+    let op = Operator::new(acc);
+    assert_eq!(op.object("a").metadata().await?.last_modified(), Some(t));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_list() -> Result<()> {
+    let op = fixture().await?;
+
+    assert_eq!(
+        list(op.objects("")).await?,
+        vec![
+            ("data/".to_string(), ObjectMode::DIR),
+            ("manifest.json".to_string(), ObjectMode::FILE),
+            ("z.txt".to_string(), ObjectMode::FILE),
+        ]
+    );
+    let expected = vec![
+        ("data/a.bin".to_string(), ObjectMode::FILE),
+        ("data/b.txt".to_string(), ObjectMode::FILE),
+        ("data/d.txt".to_string(), ObjectMode::FILE),
+        ("data/nested/".to_string(), ObjectMode::DIR),
+    ];
+    assert_eq!(list(op.objects("data/")).await?, expected);
+    assert_eq!(list(op.objects("data")).await?, expected);
+    assert_eq!(
+        list(op.objects("data/nested/")).await?,
+        vec![("data/nested/c.txt".to_string(), ObjectMode::FILE)]
+    );
+    assert_eq!(list(op.objects("missing/")).await?, vec![]);
+    assert_eq!(
+        list(op.objects("data/").recursive()).await?,
+        vec![
+            ("data/a.bin".to_string(), ObjectMode::FILE),
+            ("data/b.txt".to_string(), ObjectMode::FILE),
+            ("data/d.txt".to_string(), ObjectMode::FILE),
+            ("data/nested/c.txt".to_string(), ObjectMode::FILE),
+        ]
+    );
+
+    assert_eq!(
+        list(op.objects("data/").start_after("data/b.txt").max_results(1)).await?,
+        vec![("data/d.txt".to_string(), ObjectMode::FILE)]
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_read_only() -> Result<()> {
+    let op = fixture().await?;
+
+    let err = op
+        .object("new")
+        .writer()
+        .write_bytes(b"x".to_vec())
+        .await
+        .unwrap_err();
+    assert_eq!(err.kind(), Kind::Unsupported);
+    let err = op.object("z.txt").delete().await.unwrap_err();
+    assert_eq!(err.kind(), Kind::Unsupported);
+    assert_eq!(read(&op.object("z.txt"), None, None).await?, b"z");
+
+    Ok(())
+}
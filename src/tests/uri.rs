@@ -0,0 +1,141 @@
+// Copyright 2022 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+
+use anyhow::Result;
+use futures::AsyncReadExt;
+
+use super::mock::mock_server;
+use crate::error::Kind;
+use crate::services::s3;
+use crate::uri::BackendUri;
+use crate::Operator;
+use crate::Scheme;
+
+#[test]
+fn test_parse_backend_uri() -> Result<()> {
+    let map = BackendUri::parse(
+        "S3://bucket/path%20to/root/?region=us-east-2&endpoint=http%3A%2F%2F127.0.0.1%3A9000",
+    )?
+    .into_map(Scheme::S3, Some("bucket"), Some("root"))?;
+    assert_eq!(
+        map,
+        HashMap::from([
+            ("bucket".to_string(), "bucket".to_string()),
+            ("root".to_string(), "/path to/root/".to_string()),
+            ("region".to_string(), "us-east-2".to_string()),
+            ("endpoint".to_string(), "http://127.0.0.1:9000".to_string()),
+        ])
+    );
+
+    let uri = BackendUri::parse("s3://bucket")?;
+    assert!(uri.check_scheme(Scheme::Fs).is_err());
+
+    // Aliases of the scheme are allowed.
+    let map = BackendUri::parse("local:///tmp")?.into_map(Scheme::Fs, None, Some("root"))?;
+    assert_eq!(
+        map,
+        HashMap::from([("root".to_string(), "/tmp".to_string())])
+    );
+
+    // Parts without options, and query parameters overwriting them.
+    for (uri, scheme, host_key, path_key) in [
+        ("fs://host/tmp", Scheme::Fs, None, Some("root")),
+        ("memory:///path", Scheme::Memory, None, None),
+        (
+            "s3://bucket?bucket=other",
+            Scheme::S3,
+            Some("bucket"),
+            Some("root"),
+        ),
+    ] {
+        let err = BackendUri::parse(uri)?
+            .into_map(scheme, host_key, path_key)
+            .unwrap_err();
+        assert_eq!(err.kind(), Kind::BackendConfigurationInvalid, "uri {}", uri);
+    }
+
+    for uri in [
+        "bucket/path",
+        "://bucket",
+        "s3://bucket#fragment",
+        "s3://bucket?region=a&region=b",
+        "s3://bucket/%FF",
+    ] {
+        let err = BackendUri::parse(uri).unwrap_err();
+        assert_eq!(err.kind(), Kind::BackendConfigurationInvalid, "uri {}", uri);
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_operator_from_uri_s3() -> Result<()> {
+    let (endpoint, requests) = mock_server(|_| {
+        hyper::Response::builder()
+            .body(hyper::Body::from("Hello"))
+            .unwrap()
+    });
+    let op = Operator::from_uri(&format!(
+        "s3://test/data%20dir?region=us-east-1&endpoint={}&access_key_id=ak&secret_access_key=sk",
+        endpoint
+    ))
+    .await?;
+    assert_eq!(op.metadata().scheme(), Some(Scheme::S3));
+    assert_eq!(op.metadata().root(), "/data dir/");
+
+    let mut bs = vec![];
+    op.object("a").reader().read_to_end(&mut bs).await?;
+    assert_eq!(bs, b"Hello");
+    {
+        let requests = requests.lock().unwrap();
+        let req = &requests[0];
+        assert_eq!(req.uri().path(), "/test/data%20dir/a");
+        assert!(req.headers().contains_key(http::header::AUTHORIZATION));
+    }
+
+    // Unknown parameters and partial credentials are rejected.
+    for uri in [
+        "s3://test?regoin=us-east-1",
+        "s3://test?access_key_id=ak",
+        "s3://?region=us-east-1",
+        "fs://test",
+    ] {
+        let err = Operator::from_uri(uri).await.err().unwrap();
+        assert_eq!(err.kind(), Kind::BackendConfigurationInvalid, "uri {}", uri);
+    }
+    let err = s3::Builder::from_uri("fs:///tmp").unwrap_err();
+    assert_eq!(err.kind(), Kind::BackendConfigurationInvalid);
+    let err = Operator::from_uri("ftp://test").await.err().unwrap();
+    assert_eq!(err.kind(), Kind::BackendNotSupported);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_operator_from_uri_fs_and_memory() -> Result<()> {
+    let dir = format!("/tmp/opendal-test-{}", uuid::Uuid::new_v4());
+    let op = Operator::from_uri(&format!("fs://{}/with%20space", dir)).await?;
+    op.object("file").writer().write_bytes(vec![0; 4]).await?;
+    assert!(std::path::Path::new(&format!("{}/with space/file", dir)).exists());
+    std::fs::remove_dir_all(&dir)?;
+
+    let op = Operator::from_uri("memory://").await?;
+    assert_eq!(op.metadata().scheme(), Some(Scheme::Memory));
+    let err = Operator::from_uri("memory://host").await.err().unwrap();
+    assert_eq!(err.kind(), Kind::BackendConfigurationInvalid);
+
+    Ok(())
+}
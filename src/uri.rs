@@ -0,0 +1,155 @@
+// Copyright 2022 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+
+use anyhow::anyhow;
+use percent_encoding::percent_decode_str;
+
+use crate::error::Error;
+use crate::error::Kind;
+use crate::error::Result;
+use crate::Scheme;
+
+/// Connection uri of a backend, like `s3://bucket/path/to/root?region=us-east-2`.
+///
+/// The path and query parameters are percent decoded.
+#[derive(Debug)]
+pub(crate) struct BackendUri {
+    scheme: String,
+    host: String,
+    path: String,
+    query: Vec<(String, String)>,
+}
+
+impl BackendUri {
+    pub fn parse(uri: &str) -> Result<Self> {
+        let (scheme, rest) = uri
+            .split_once("://")
+            .ok_or_else(|| invalid_uri(anyhow!("uri must start with a scheme like `s3://`")))?;
+        if scheme.is_empty() {
+            return Err(invalid_uri(anyhow!("scheme of uri is empty")));
+        }
+        // Fragments make no sense for backends.
+        if rest.contains('#') {
+            return Err(invalid_uri(anyhow!("uri must not contain a fragment")));
+        }
+
+        let (rest, query) = rest.split_once('?').unwrap_or((rest, ""));
+        let (host, path) = match rest.find('/') {
+            Some(idx) => rest.split_at(idx),
+            None => (rest, ""),
+        };
+
+        let mut parsed = BackendUri {
+            scheme: scheme.to_lowercase(),
+            host: decode("host", host)?,
+            path: decode("path", path)?,
+            query: vec![],
+        };
+        for pair in query.split('&').filter(|v| !v.is_empty()) {
+            let (k, v) = pair.split_once('=').unwrap_or((pair, ""));
+            let k = decode("query", k)?;
+            if parsed.query.iter().any(|(key, _)| key == &k) {
+                return Err(Error::Backend {
+                    kind: Kind::BackendConfigurationInvalid,
+                    context: HashMap::from([("key".to_string(), k)]),
+                    source: anyhow!("query parameter is duplicated"),
+                });
+            }
+            // Values could be secrets, don't carry them in errors.
+            let v = decode("query", v)?;
+            parsed.query.push((k, v));
+        }
+
+        Ok(parsed)
+    }
+
+    /// Check that the uri is of the backend, aliases like `local` of `fs`
+    /// are allowed.
+    pub fn check_scheme(&self, scheme: Scheme) -> Result<()> {
+        if self.scheme.parse::<Scheme>().ok() != Some(scheme.clone()) {
+            return Err(Error::Backend {
+                kind: Kind::BackendConfigurationInvalid,
+                context: HashMap::from([("scheme".to_string(), self.scheme.clone())]),
+                source: anyhow!("uri is not of {:?} backend", scheme),
+            });
+        }
+        Ok(())
+    }
+
+    /// Convert the uri into options for `from_map` of the backend's builder.
+    ///
+    /// The host and path are stored as `host_key` and `path_key`, they must
+    /// be empty if the backend has no such option.
+    pub fn into_map(
+        self,
+        scheme: Scheme,
+        host_key: Option<&str>,
+        path_key: Option<&str>,
+    ) -> Result<HashMap<String, String>> {
+        self.check_scheme(scheme)?;
+
+        let mut map = HashMap::new();
+        for (part, key, value) in [("host", host_key, self.host), ("path", path_key, self.path)] {
+            match key {
+                Some(key) => {
+                    map.insert(key.to_string(), value);
+                }
+                None if value.is_empty() || value == "/" => {}
+                None => {
+                    return Err(Error::Backend {
+                        kind: Kind::BackendConfigurationInvalid,
+                        context: HashMap::from([
+                            ("scheme".to_string(), self.scheme.clone()),
+                            (part.to_string(), value),
+                        ]),
+                        source: anyhow!("{} of uri is not supported", part),
+                    });
+                }
+            }
+        }
+        for (k, v) in self.query {
+            // Options from the host and path can't be overwritten.
+            if Some(k.as_str()) == host_key || Some(k.as_str()) == path_key {
+                return Err(Error::Backend {
+                    kind: Kind::BackendConfigurationInvalid,
+                    context: HashMap::from([
+                        ("scheme".to_string(), self.scheme.clone()),
+                        ("key".to_string(), k),
+                    ]),
+                    source: anyhow!("query parameter is not supported"),
+                });
+            }
+            map.insert(k, v);
+        }
+
+        Ok(map)
+    }
+}
+
+fn decode(part: &str, v: &str) -> Result<String> {
+    percent_decode_str(v)
+        .decode_utf8()
+        .map(|v| v.to_string())
+        .map_err(|e| invalid_uri(anyhow!("decode {} of uri: {}", part, e)))
+}
+
+fn invalid_uri(source: anyhow::Error) -> Error {
+    Error::Backend {
+        kind: Kind::BackendConfigurationInvalid,
+        context: HashMap::new(),
+        source,
+    }
+}
@@ -28,6 +28,7 @@ use futures::AsyncSeekExt;
 use futures::StreamExt;
 use opendal::error::Kind;
 use opendal::ObjectMode;
+use opendal::ObjectStream;
 use opendal::Operator;
 use rand::prelude::*;
 use sha2::Digest;
@@ -52,6 +53,11 @@ impl BehaviorTest {
         self.test_normal().await?;
         self.test_stat_root().await?;
         self.test_stat_non_exist().await?;
+        self.test_stat_dir().await?;
+        self.test_create().await?;
+        self.test_list_ordered().await?;
+        self.test_copy().await?;
+        self.test_rename().await?;
 
         Ok(())
     }
@@ -65,8 +71,8 @@ impl BehaviorTest {
 
         // Step 2: Write this file
         let w = self.op.object(&path).writer();
-        let n = w.write_bytes(content.clone()).await?;
-        assert_eq!(n, size, "write file");
+        let meta = w.write_bytes(content.clone()).await?;
+        assert_eq!(meta.content_length(), size as u64, "write file");
 
         // Step 3: Stat this file
         let meta = self.op.object(&path).metadata().await?;
@@ -81,7 +87,7 @@ impl BehaviorTest {
         let mut buf = Vec::new();
         let mut r = self.op.object(&path).reader();
         let n = r.read_to_end(&mut buf).await.expect("read to end");
-        assert_eq!(n, size as usize, "check size in read whole file");
+        assert_eq!(n, size, "check size in read whole file");
         assert_eq!(
             format!("{:x}", Sha256::digest(&buf)),
             format!("{:x}", Sha256::digest(&content)),
@@ -89,7 +95,7 @@ impl BehaviorTest {
         );
 
         // Step 4.2: Read the file with random offset and length.
-        let (offset, length) = self.gen_offset_length(size as usize);
+        let (offset, length) = self.gen_offset_length(size);
         let mut buf: Vec<u8> = vec![0; length as usize];
         let mut r = self.op.object(&path).reader();
         let off = r.seek(SeekFrom::Current(offset as i64)).await?;
@@ -153,9 +159,178 @@ impl BehaviorTest {
         Ok(())
     }
 
+    /// Stat a dir should return `DIR` only if there are objects under it.
+    async fn test_stat_dir(&mut self) -> Result<()> {
+        let dir = format!("{}/", uuid::Uuid::new_v4());
+        self.op
+            .object(&format!("{}{}", dir, uuid::Uuid::new_v4()))
+            .writer()
+            .write_bytes(vec![0; 1])
+            .await?;
+
+        let meta = self.op.object(&dir).metadata().await?;
+        assert_eq!(meta.mode(), ObjectMode::DIR);
+
+        let meta = self
+            .op
+            .object(&format!("{}/", uuid::Uuid::new_v4()))
+            .metadata()
+            .await;
+        assert!(meta.is_err());
+        assert_eq!(meta.unwrap_err().kind(), Kind::ObjectNotExist);
+        Ok(())
+    }
+
+    /// Created dirs exist even without any children.
+    async fn test_create(&mut self) -> Result<()> {
+        let parent = format!("{}/", uuid::Uuid::new_v4());
+        let dir = format!("{}{}/", parent, uuid::Uuid::new_v4());
+        let file = format!("{}{}", parent, uuid::Uuid::new_v4());
+
+        self.op.object(&dir).create().await?;
+        let meta = self.op.object(&dir).metadata().await?;
+        assert_eq!(meta.mode(), ObjectMode::DIR, "stat created dir");
+
+        self.op.object(&file).create().await?;
+        let meta = self.op.object(&file).metadata().await?;
+        assert_eq!(meta.mode(), ObjectMode::FILE, "stat created file");
+        assert_eq!(meta.content_length(), 0, "stat created file");
+
+        // Services like fs list dirs without the trailing `/`.
+        let paths = self.list_paths(self.op.objects(&parent)).await?;
+        let found = paths
+            .iter()
+            .filter(|p| p.trim_end_matches('/') == dir.trim_end_matches('/'))
+            .count();
+        assert_eq!(found, 1, "list parent of created dir: {:?}", paths);
+
+        self.op.object(&file).delete().await?;
+        self.op.object(&dir).delete().await?;
+        Ok(())
+    }
+
+    /// Ordered list should yield entries in lexicographical order on all
+    /// services, and so does plain list on services with natural ordering.
+    async fn test_list_ordered(&mut self) -> Result<()> {
+        let dir = format!("{}/", uuid::Uuid::new_v4());
+        let mut expected = Vec::new();
+        for _ in 0..8 {
+            let path = format!("{}{}", dir, uuid::Uuid::new_v4());
+            self.op
+                .object(&path)
+                .writer()
+                .write_bytes(vec![0; 1])
+                .await?;
+            expected.push(path);
+        }
+        expected.sort();
+
+        let paths = self.list_paths(self.op.objects(&dir).ordered()).await?;
+        assert_eq!(paths, expected, "ordered list");
+
+        if self.op.metadata().ordered_list() {
+            let paths = self.list_paths(self.op.objects(&dir)).await?;
+            assert_eq!(paths, expected, "list on service with natural ordering");
+        }
+
+        for path in expected {
+            self.op.object(&path).delete().await?;
+        }
+        Ok(())
+    }
+
+    /// Copy should work inside the storage, including onto itself.
+    async fn test_copy(&mut self) -> Result<()> {
+        let src = uuid::Uuid::new_v4().to_string();
+        let dst = format!("{}/{}", uuid::Uuid::new_v4(), uuid::Uuid::new_v4());
+        let (content, size) = self.gen_bytes();
+        self.op
+            .object(&src)
+            .writer()
+            .write_bytes(content.clone())
+            .await?;
+
+        let meta = self.op.object(&src).copy_to(&dst).await?;
+        assert_eq!(meta.content_length(), size as u64, "copy file");
+        for path in [&src, &dst] {
+            let mut buf = Vec::new();
+            let mut r = self.op.object(path).reader();
+            r.read_to_end(&mut buf).await.expect("read to end");
+            assert_eq!(buf, content, "read {}", path);
+        }
+
+        // Copy onto itself keeps the content.
+        self.op.object(&dst).copy_to(&dst).await?;
+        let meta = self.op.object(&dst).metadata().await?;
+        assert_eq!(meta.content_length(), size as u64, "copy onto itself");
+
+        let err = self
+            .op
+            .object(&uuid::Uuid::new_v4().to_string())
+            .copy_to(&dst)
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), Kind::ObjectNotExist, "copy non exist");
+
+        self.op.object(&src).delete().await?;
+        self.op.object(&dst).delete().await?;
+        Ok(())
+    }
+
+    async fn test_rename(&mut self) -> Result<()> {
+        let src = uuid::Uuid::new_v4().to_string();
+        let dst = format!("{}/{}", uuid::Uuid::new_v4(), uuid::Uuid::new_v4());
+        let (content, size) = self.gen_bytes();
+        self.op
+            .object(&src)
+            .writer()
+            .write_bytes(content.clone())
+            .await?;
+        // Rename overwrites the existing object.
+        self.op
+            .object(&dst)
+            .writer()
+            .write_bytes(b"stale".to_vec())
+            .await?;
+
+        let meta = self.op.object(&src).rename_to(&dst).await?;
+        assert_eq!(meta.content_length(), size as u64, "rename file");
+        let mut buf = Vec::new();
+        let mut r = self.op.object(&dst).reader();
+        r.read_to_end(&mut buf).await.expect("read to end");
+        assert_eq!(buf, content, "read {}", &dst);
+        let err = self.op.object(&src).metadata().await.unwrap_err();
+        assert_eq!(err.kind(), Kind::ObjectNotExist, "source removed");
+
+        // Rename onto itself keeps the object.
+        self.op.object(&dst).rename_to(&dst).await?;
+        let meta = self.op.object(&dst).metadata().await?;
+        assert_eq!(meta.content_length(), size as u64, "rename onto itself");
+
+        let err = self
+            .op
+            .object(&uuid::Uuid::new_v4().to_string())
+            .rename_to(&dst)
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), Kind::ObjectNotExist, "rename non exist");
+
+        self.op.object(&dst).delete().await?;
+        Ok(())
+    }
+
+    async fn list_paths(&self, mut obs: ObjectStream) -> Result<Vec<String>> {
+        let mut paths = Vec::new();
+        while let Some(o) = obs.next().await {
+            let mut o = o?;
+            paths.push(o.metadata_cached().await?.path().to_string());
+        }
+        Ok(paths)
+    }
+
     fn gen_bytes(&mut self) -> (Vec<u8>, usize) {
         let size = self.rng.gen_range(1..4 * 1024 * 1024);
-        let mut content = vec![0; size as usize];
+        let mut content = vec![0; size];
         self.rng.fill_bytes(&mut content);
 
         (content, size)